@@ -0,0 +1,75 @@
+//! Compares hybrid search ranking strategies on a labelled query set.
+//!
+//! Run with `cargo bench --bench search_ranking`. The query set defaults to
+//! `benches/data/search_queries.json`; point `HEAP_EVAL_QUERIES` at another
+//! file recorded from a real mailbox to evaluate on it instead.
+
+use heap::services::{evaluate_ranking, load_labelled_queries, HybridStrategy, SearchSettings};
+
+const TOP_K: usize = 10;
+
+fn main() {
+    let path = std::env::var("HEAP_EVAL_QUERIES").unwrap_or_else(|_| {
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/benches/data/search_queries.json"
+        )
+        .to_string()
+    });
+    let queries = load_labelled_queries(&path).expect("load query set");
+
+    let weighted = SearchSettings {
+        hybrid_strategy: HybridStrategy::Weighted,
+        ..SearchSettings::default()
+    };
+    let strategies = [
+        (
+            "fts only",
+            SearchSettings {
+                semantic_weight: 0.0,
+                min_score: 0.0,
+                ..weighted.clone()
+            },
+        ),
+        (
+            "semantic only",
+            SearchSettings {
+                fts_weight: 0.0,
+                min_score: 0.0,
+                ..weighted.clone()
+            },
+        ),
+        ("weighted", weighted.clone()),
+        (
+            "weighted, no threshold",
+            SearchSettings {
+                min_score: 0.0,
+                ..weighted
+            },
+        ),
+        (
+            "rrf k=10",
+            SearchSettings {
+                rrf_k: 10.0,
+                ..SearchSettings::default()
+            },
+        ),
+        ("rrf k=60", SearchSettings::default()),
+    ];
+
+    println!("queries: {} ({})", queries.len(), path);
+    println!(
+        "{:<24} {:>6} {:>8} {:>10}",
+        "strategy",
+        "mrr",
+        format!("ndcg@{}", TOP_K),
+        format!("recall@{}", TOP_K)
+    );
+    for (name, settings) in &strategies {
+        let metrics = evaluate_ranking(&queries, settings, TOP_K);
+        println!(
+            "{:<24} {:>6.3} {:>8.3} {:>10.3}",
+            name, metrics.mrr, metrics.ndcg, metrics.recall
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Recall and latency of HNSW search against the brute-force baseline.
+//!
+//! Run with `cargo bench --bench vector_search`. The store size and
+//! dimension can be overridden with `HEAP_BENCH_VECTORS` and `HEAP_BENCH_DIM`.
+
+use std::time::{Duration, Instant};
+
+use heap::domain::EmailId;
+use heap::embedding::{Embedding, VectorStore};
+
+const QUERIES: usize = 200;
+const TOP_K: usize = 10;
+
+/// Xorshift generator so runs are reproducible without extra dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+    }
+}
+
+/// Dimensionality of the latent space the synthetic vectors are drawn from.
+const LATENT_DIM: usize = 24;
+
+/// Generates vectors that lie near a low-dimensional subspace.
+///
+/// Real sentence embeddings have a much lower intrinsic dimension than their
+/// vector length; uniformly random vectors would make every method look bad.
+fn synthetic_vectors(rng: &mut Rng, count: usize, dim: usize) -> Vec<Embedding> {
+    let mut projection_rng = Rng(0xBA5E);
+    let projection: Vec<Vec<f32>> = (0..LATENT_DIM)
+        .map(|_| (0..dim).map(|_| projection_rng.next_f32()).collect())
+        .collect();
+
+    (0..count)
+        .map(|_| {
+            let latent: Vec<f32> = (0..LATENT_DIM).map(|_| rng.next_f32()).collect();
+            let values = (0..dim)
+                .map(|d| {
+                    let signal: f32 = latent
+                        .iter()
+                        .zip(&projection)
+                        .map(|(z, row)| z * row[d])
+                        .sum();
+                    signal + rng.next_f32() * 0.05
+                })
+                .collect();
+            let mut embedding = Embedding::new(values);
+            embedding.normalize();
+            embedding
+        })
+        .collect()
+}
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+fn main() {
+    let count = env_usize("HEAP_BENCH_VECTORS", 20_000);
+    let dim = env_usize("HEAP_BENCH_DIM", 384);
+    let mut rng = Rng(0x5EED);
+
+    let vectors = synthetic_vectors(&mut rng, count, dim);
+    let queries = synthetic_vectors(&mut rng, QUERIES, dim);
+
+    let mut store = VectorStore::new();
+    store.set_exact_search_threshold(0);
+
+    let build_start = Instant::now();
+    for (i, embedding) in vectors.into_iter().enumerate() {
+        store
+            .insert(&EmailId::from(format!("email-{}", i)), embedding)
+            .expect("insert");
+    }
+    let build_time = build_start.elapsed();
+
+    let mut exact_time = Duration::ZERO;
+    let mut indexed_time = Duration::ZERO;
+    let mut found = 0;
+
+    for query in &queries {
+        let start = Instant::now();
+        let exact = store.search_exact(query, TOP_K).expect("exact search");
+        exact_time += start.elapsed();
+
+        let start = Instant::now();
+        let indexed = store.search(query, TOP_K).expect("indexed search");
+        indexed_time += start.elapsed();
+
+        found += indexed
+            .iter()
+            .filter(|(id, _)| exact.iter().any(|(expected, _)| expected == id))
+            .count();
+    }
+
+    let recall = found as f64 / (QUERIES * TOP_K) as f64;
+    println!("vectors: {} x {} dims", count, dim);
+    println!("index build: {:.2?}", build_time);
+    println!("brute force: {:.2?} / query", exact_time / QUERIES as u32);
+    println!("hnsw:        {:.2?} / query", indexed_time / QUERIES as u32);
+    println!("recall@{}:   {:.3}", TOP_K, recall);
+}
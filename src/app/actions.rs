@@ -64,6 +64,7 @@ actions!(
         SummarizeThread,
         GenerateReply,
         CategorizeEmail,
+        ClearAiCache,
         // Account management
         SwitchAccount,
         AddAccount,
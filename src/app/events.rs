@@ -92,6 +92,11 @@ pub enum AppEvent {
     NavigateTo(ViewNavigation),
     /// Select a thread.
     SelectThread(ThreadId),
+    /// Open a thread at one of its messages.
+    OpenEmail {
+        thread_id: ThreadId,
+        email_id: EmailId,
+    },
     /// Open the composer.
     OpenComposer(ComposerTrigger),
     /// Close the composer.
@@ -11,7 +11,7 @@ pub mod state;
 
 pub use events::{AppEvent, EventBus};
 pub use state::{
-    AiStatus, AppState, ComposerMode, ComposerState, MessageListState, ReadingPaneState,
+    AiStatus, AppState, AskState, ComposerMode, ComposerState, MessageListState, ReadingPaneState,
     SyncStatus, ViewType,
 };
 
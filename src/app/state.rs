@@ -7,6 +7,8 @@ use std::collections::HashSet;
 
 use crate::config::Settings;
 use crate::domain::{AccountId, LabelId, ThreadId};
+use crate::providers::ai::StreamChunk;
+use crate::services::{parse_citations, AskSource, Citation};
 
 /// The currently active view in the application.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -26,10 +28,14 @@ pub enum ViewType {
     Trash,
     /// Snoozed messages.
     Snoozed,
+    /// Messages the spam filter routed out of the inbox.
+    Junk,
     /// Messages with a specific label.
     Label(LabelId),
     /// Screener queue for new senders.
     Screener,
+    /// Messages from senders rejected in the screener.
+    ScreenedOut,
     /// Search results.
     Search(String),
     /// Settings panel.
@@ -317,6 +323,10 @@ pub struct ReadingPaneState {
     pub composer_visible: bool,
     /// Current scroll position.
     pub scroll_offset: f32,
+    /// AI summary of the thread, filled in as it streams.
+    pub ai_summary: Option<String>,
+    /// Whether the AI summary is still being generated.
+    pub ai_summary_streaming: bool,
 }
 
 impl ReadingPaneState {
@@ -326,6 +336,29 @@ impl ReadingPaneState {
         self.expanded_messages.clear();
         self.composer_visible = false;
         self.scroll_offset = 0.0;
+        self.ai_summary = None;
+        self.ai_summary_streaming = false;
+    }
+
+    /// Start streaming a new AI summary, replacing any previous one.
+    pub fn begin_summary(&mut self) {
+        self.ai_summary = Some(String::new());
+        self.ai_summary_streaming = true;
+    }
+
+    /// Append a streamed chunk to the AI summary.
+    pub fn push_summary_chunk(&mut self, chunk: &StreamChunk) {
+        if let Some(summary) = &mut self.ai_summary {
+            summary.push_str(&chunk.text);
+        }
+        if chunk.finish_reason.is_some() {
+            self.ai_summary_streaming = false;
+        }
+    }
+
+    /// Mark the AI summary as complete.
+    pub fn finish_summary(&mut self) {
+        self.ai_summary_streaming = false;
     }
 
     /// Toggle message expansion.
@@ -373,6 +406,70 @@ impl ReadingPaneState {
     }
 }
 
+/// State for a question asked of the inbox.
+#[derive(Debug, Clone, Default)]
+pub struct AskState {
+    /// The question being answered.
+    pub question: String,
+    /// Answer text, filled in as it streams.
+    pub answer: String,
+    /// Emails the answer may cite.
+    pub sources: Vec<AskSource>,
+    /// Whether the answer is still being generated.
+    pub streaming: bool,
+    /// Error that ended the answer early, if any.
+    pub error: Option<String>,
+}
+
+impl AskState {
+    /// Start answering a question, replacing any previous answer.
+    pub fn begin(&mut self, question: String, sources: Vec<AskSource>) {
+        self.question = question;
+        self.answer.clear();
+        self.sources = sources;
+        self.streaming = true;
+        self.error = None;
+    }
+
+    /// Append a streamed chunk to the answer.
+    pub fn push_chunk(&mut self, chunk: &StreamChunk) {
+        self.answer.push_str(&chunk.text);
+        if chunk.finish_reason.is_some() {
+            self.streaming = false;
+        }
+    }
+
+    /// Mark the answer as complete.
+    pub fn finish(&mut self) {
+        self.streaming = false;
+    }
+
+    /// Stop answering because of an error.
+    pub fn fail(&mut self, error: String) {
+        self.streaming = false;
+        self.error = Some(error);
+    }
+
+    /// Clear the question and answer.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Citations in the answer so far.
+    pub fn citations(&self) -> Vec<Citation> {
+        parse_citations(&self.answer, &self.sources)
+    }
+
+    /// Sources the answer has cited so far, in source order.
+    pub fn cited_sources(&self) -> Vec<&AskSource> {
+        let cited: HashSet<usize> = self.citations().iter().map(|c| c.number).collect();
+        self.sources
+            .iter()
+            .filter(|s| cited.contains(&s.number))
+            .collect()
+    }
+}
+
 /// State for the composer.
 #[derive(Debug, Clone, Default)]
 pub struct ComposerState {
@@ -400,6 +497,8 @@ pub struct ComposerState {
     pub is_sending: bool,
     /// AI draft suggestion, if any.
     pub ai_suggestion: Option<String>,
+    /// Whether the AI suggestion is still being generated.
+    pub ai_suggestion_streaming: bool,
 }
 
 /// Composer mode.
@@ -479,8 +578,34 @@ impl ComposerState {
         self.ai_suggestion = Some(suggestion);
     }
 
+    /// Start streaming a new AI suggestion, replacing any previous one.
+    pub fn begin_ai_suggestion(&mut self) {
+        self.ai_suggestion = Some(String::new());
+        self.ai_suggestion_streaming = true;
+    }
+
+    /// Append a streamed chunk to the AI suggestion.
+    pub fn push_ai_suggestion_chunk(&mut self, chunk: &StreamChunk) {
+        if let Some(suggestion) = &mut self.ai_suggestion {
+            suggestion.push_str(&chunk.text);
+        }
+        if chunk.finish_reason.is_some() {
+            self.ai_suggestion_streaming = false;
+        }
+    }
+
+    /// Mark the AI suggestion as complete.
+    pub fn finish_ai_suggestion(&mut self) {
+        self.ai_suggestion_streaming = false;
+    }
+
     /// Accept AI suggestion into body.
+    ///
+    /// Does nothing while the suggestion is still streaming.
     pub fn accept_ai_suggestion(&mut self) {
+        if self.ai_suggestion_streaming {
+            return;
+        }
         if let Some(suggestion) = self.ai_suggestion.take() {
             self.body = suggestion;
             self.mark_dirty();
@@ -490,6 +615,7 @@ impl ComposerState {
     /// Reject AI suggestion.
     pub fn reject_ai_suggestion(&mut self) {
         self.ai_suggestion = None;
+        self.ai_suggestion_streaming = false;
     }
 
     /// Add an attachment.
@@ -613,4 +739,86 @@ mod tests {
         assert!(state.ai_suggestion.is_none());
         assert!(state.is_dirty);
     }
+
+    fn chunk(text: &str, done: bool) -> StreamChunk {
+        StreamChunk {
+            text: text.to_string(),
+            finish_reason: done.then_some(crate::providers::ai::FinishReason::Stop),
+        }
+    }
+
+    #[test]
+    fn test_reading_pane_streams_summary() {
+        let mut state = ReadingPaneState::default();
+        state.set_thread(Some(ThreadId::from("thread-1")));
+        state.begin_summary();
+        assert_eq!(state.ai_summary.as_deref(), Some(""));
+        assert!(state.ai_summary_streaming);
+
+        state.push_summary_chunk(&chunk("Alice wants ", false));
+        assert_eq!(state.ai_summary.as_deref(), Some("Alice wants "));
+        assert!(state.ai_summary_streaming);
+
+        state.push_summary_chunk(&chunk("to ship Friday.", true));
+        assert_eq!(
+            state.ai_summary.as_deref(),
+            Some("Alice wants to ship Friday.")
+        );
+        assert!(!state.ai_summary_streaming);
+
+        state.set_thread(Some(ThreadId::from("thread-2")));
+        assert!(state.ai_summary.is_none());
+    }
+
+    #[test]
+    fn test_composer_streams_ai_suggestion() {
+        let mut state = ComposerState::new_email();
+        state.begin_ai_suggestion();
+        state.push_ai_suggestion_chunk(&chunk("Sounds ", false));
+
+        // A partial suggestion cannot be accepted.
+        state.accept_ai_suggestion();
+        assert!(state.body.is_empty());
+
+        state.push_ai_suggestion_chunk(&chunk("good.", false));
+        state.finish_ai_suggestion();
+        state.accept_ai_suggestion();
+        assert_eq!(state.body, "Sounds good.");
+        assert!(state.ai_suggestion.is_none());
+    }
+
+    #[test]
+    fn test_ask_state_streams_cited_answer() {
+        let source = |number: usize, id: &str| AskSource {
+            number,
+            email_id: crate::domain::EmailId::from(id),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            subject: None,
+            from: "legal@example.com".to_string(),
+            date: chrono::Utc::now(),
+            text: String::new(),
+        };
+
+        let mut state = AskState::default();
+        state.begin(
+            "When does the contract renew?".to_string(),
+            vec![source(1, "a"), source(2, "b")],
+        );
+        assert!(state.streaming);
+
+        state.push_chunk(&chunk("On March 1 [2", false));
+        assert!(state.citations().is_empty());
+
+        state.push_chunk(&chunk("].", true));
+        assert!(!state.streaming);
+        assert_eq!(state.answer, "On March 1 [2].");
+        let cited = state.cited_sources();
+        assert_eq!(cited.len(), 1);
+        assert_eq!(cited[0].thread_id, ThreadId::from("thread-b"));
+
+        state.fail("connection reset".to_string());
+        state.begin("Another question".to_string(), Vec::new());
+        assert!(state.answer.is_empty());
+        assert!(state.error.is_none());
+    }
 }
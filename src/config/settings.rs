@@ -5,6 +5,7 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 /// Top-level application settings.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -180,6 +181,7 @@ pub enum Tone {
 
 /// Settings for semantic search.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SearchSettings {
     /// Whether semantic search is enabled.
     pub enabled: bool,
@@ -187,6 +189,10 @@ pub struct SearchSettings {
     pub max_results: usize,
     /// Minimum similarity score (0.0 to 1.0).
     pub min_similarity: f32,
+    /// Local directory holding the embedding model, used instead of a download.
+    pub model_dir: Option<PathBuf>,
+    /// Whether the embedding model may be downloaded from Hugging Face.
+    pub allow_model_download: bool,
 }
 
 impl Default for SearchSettings {
@@ -195,6 +201,8 @@ impl Default for SearchSettings {
             enabled: true,
             max_results: 50,
             min_similarity: 0.5,
+            model_dir: None,
+            allow_model_download: true,
         }
     }
 }
@@ -364,6 +372,15 @@ mod tests {
         assert!(deserialized.ai.providers.contains_key("anthropic"));
     }
 
+    #[test]
+    fn search_settings_default_missing_model_fields() {
+        let json = r#"{"enabled": true, "max_results": 20, "min_similarity": 0.4}"#;
+        let search: SearchSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(search.max_results, 20);
+        assert!(search.model_dir.is_none());
+        assert!(search.allow_model_download);
+    }
+
     #[test]
     fn new_email_notification_variants() {
         let all: NewEmailNotification = serde_json::from_str("\"all\"").unwrap();
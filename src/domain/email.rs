@@ -50,6 +50,40 @@ pub struct Email {
     pub labels: Vec<LabelId>,
     /// File attachments.
     pub attachments: Vec<Attachment>,
+    /// Raw message headers, in the order they appear in the message.
+    #[serde(default)]
+    pub headers: Vec<Header>,
+}
+
+impl Email {
+    /// Returns the value of the first header with the given name.
+    ///
+    /// Header names are compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.as_str())
+    }
+}
+
+/// A raw message header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    /// Header field name as it appeared in the message.
+    pub name: String,
+    /// Unfolded header value.
+    pub value: String,
+}
+
+impl Header {
+    /// Creates a new header.
+    pub fn new(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
 }
 
 /// An email address with optional display name.
@@ -170,9 +204,15 @@ mod tests {
             is_draft: false,
             labels: vec![LabelId::from("INBOX")],
             attachments: vec![],
+            headers: vec![
+                Header::new("List-Unsubscribe", "<mailto:unsub@example.com>"),
+                Header::new("Precedence", "bulk"),
+            ],
         };
 
         assert_eq!(email.references.len(), 2);
         assert!(email.in_reply_to.is_some());
+        assert_eq!(email.header("precedence"), Some("bulk"));
+        assert_eq!(email.header("Reply-To"), None);
     }
 }
@@ -13,11 +13,11 @@ mod types;
 
 pub use account::{Account, ProviderConfig, ProviderType};
 pub use contact::Contact;
-pub use email::{Address, Attachment, Email};
+pub use email::{Address, Attachment, Email, Header};
 pub use label::{system_labels, Label};
 pub use screener::{
-    RuleType, ScreenerAction, ScreenerEntry, ScreenerRule, ScreenerStatus, SenderAnalysis,
-    SenderType,
+    first_matching_rule, RuleField, RuleType, ScreenedMessage, ScreenerAction, ScreenerEntry,
+    ScreenerRule, ScreenerStatus, SenderAnalysis, SenderType,
 };
 pub use thread::{Thread, ThreadSummary};
 pub use types::{AccountId, EmailId, LabelId, MessageId, ThreadId};
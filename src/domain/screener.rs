@@ -2,17 +2,22 @@
 //!
 //! Represents the email screener system for filtering unknown senders.
 
+use std::cmp::Ordering;
+
 use chrono::{DateTime, Utc};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 
-use super::EmailId;
+use super::{AccountId, Email, EmailId};
 
 /// An entry in the screener queue for an unknown sender.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScreenerEntry {
     /// Unique identifier for this entry.
     pub id: String,
-    /// Email address of the sender.
+    /// Account the sender wrote to; decisions apply to this account only.
+    pub account_id: AccountId,
+    /// Email address of the sender, lowercased.
     pub sender_email: String,
     /// Display name of the sender.
     pub sender_name: Option<String>,
@@ -45,26 +50,235 @@ pub enum ScreenerStatus {
 pub struct ScreenerRule {
     /// Unique identifier for this rule.
     pub id: String,
+    /// Account the rule applies to, or `None` for a global rule that
+    /// applies to every account.
+    #[serde(default)]
+    pub account_id: Option<AccountId>,
     /// Type of rule.
     pub rule_type: RuleType,
+    /// Field that pattern and regex rules are matched against.
+    ///
+    /// Domain rules always match the sender's domain.
+    #[serde(default)]
+    pub field: RuleField,
     /// Pattern to match (domain, email pattern, etc.).
     pub pattern: String,
     /// Action to take when matched.
     pub action: ScreenerAction,
+    /// Rules with a higher priority are checked first.
+    #[serde(default)]
+    pub priority: i32,
     /// When this rule was created.
     pub created_at: DateTime<Utc>,
 }
 
+impl ScreenerRule {
+    /// Creates a rule for every account with the default priority.
+    pub fn new(rule_type: RuleType, pattern: impl Into<String>, action: ScreenerAction) -> Self {
+        Self {
+            id: format!("rule-{}", uuid::Uuid::new_v4()),
+            account_id: None,
+            rule_type,
+            field: RuleField::Sender,
+            pattern: pattern.into(),
+            action,
+            priority: 0,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Returns true if the rule applies to every account.
+    pub fn is_global(&self) -> bool {
+        self.account_id.is_none()
+    }
+
+    /// Limits the rule to one account.
+    pub fn for_account(mut self, account_id: AccountId) -> Self {
+        self.account_id = Some(account_id);
+        self
+    }
+
+    /// Sets the field that the pattern is matched against.
+    pub fn on_field(mut self, field: RuleField) -> Self {
+        self.field = field;
+        self
+    }
+
+    /// Sets the rule's priority.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Checks that the rule's pattern can be used, returning the reason if not.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pattern.trim().is_empty() {
+            return Err("pattern is empty".to_string());
+        }
+        match self.rule_type {
+            RuleType::DomainAllow | RuleType::DomainBlock => Ok(()),
+            RuleType::Pattern | RuleType::Regex => {
+                self.regex().map(|_| ()).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /// Returns true if the rule applies to the account.
+    pub fn applies_to(&self, account_id: &AccountId) -> bool {
+        self.account_id.as_ref().map_or(true, |id| id == account_id)
+    }
+
+    /// Returns true if the message satisfies the rule's condition.
+    ///
+    /// Domain rules also match subdomains, so `example.com` matches mail
+    /// from `news.example.com`. Rules with an invalid pattern match nothing.
+    pub fn matches(&self, message: &ScreenedMessage) -> bool {
+        match self.rule_type {
+            RuleType::DomainAllow | RuleType::DomainBlock => {
+                let pattern = self.pattern.trim().trim_start_matches('@').to_lowercase();
+                let domain = message.domain();
+                domain == pattern || domain.ends_with(&format!(".{}", pattern))
+            }
+            RuleType::Pattern | RuleType::Regex => {
+                let Some(value) = message.field(self.field) else {
+                    return false;
+                };
+                if self.rule_type == RuleType::Pattern && !is_glob(&self.pattern) {
+                    return value.to_lowercase().contains(&self.pattern.to_lowercase());
+                }
+                self.regex().is_ok_and(|re| re.is_match(value))
+            }
+        }
+    }
+
+    /// Orders rules so that the one to apply first comes first.
+    ///
+    /// Higher priorities come first, then rules for a specific account
+    /// before rules for every account, then newer rules before older ones.
+    pub fn cmp_precedence(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| other.account_id.is_some().cmp(&self.account_id.is_some()))
+            .then_with(|| other.created_at.cmp(&self.created_at))
+            .then_with(|| self.id.cmp(&other.id))
+    }
+
+    fn regex(&self) -> Result<Regex, regex::Error> {
+        let source = match self.rule_type {
+            RuleType::Pattern => glob_to_regex(&self.pattern),
+            _ => self.pattern.clone(),
+        };
+        RegexBuilder::new(&source).case_insensitive(true).build()
+    }
+}
+
+/// Returns the rule that decides a message, if any.
+///
+/// Only rules that apply to the account are considered, in
+/// [precedence](ScreenerRule::cmp_precedence) order.
+pub fn first_matching_rule<'a>(
+    rules: &'a [ScreenerRule],
+    account_id: &AccountId,
+    message: &ScreenedMessage,
+) -> Option<&'a ScreenerRule> {
+    rules
+        .iter()
+        .filter(|rule| rule.applies_to(account_id) && rule.matches(message))
+        .min_by(|a, b| a.cmp_precedence(b))
+}
+
 /// Type of screener rule.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum RuleType {
-    /// Allow all emails from a domain.
+    /// Allow all emails from a domain and its subdomains.
     DomainAllow,
-    /// Block all emails from a domain.
+    /// Block all emails from a domain and its subdomains.
     DomainBlock,
-    /// Pattern-based matching (regex or glob).
+    /// Glob pattern using `*` and `?`; without wildcards it matches any
+    /// value containing the pattern.
     Pattern,
+    /// Regular expression, matched case-insensitively.
+    Regex,
+}
+
+/// Message field a screener rule is matched against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    /// The sender's email address.
+    #[default]
+    Sender,
+    /// The `List-Id` header.
+    ListId,
+    /// The subject line.
+    Subject,
+}
+
+/// The parts of a message that screener rules look at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenedMessage {
+    /// Sender's email address, lowercased.
+    pub sender: String,
+    /// Value of the `List-Id` header.
+    pub list_id: Option<String>,
+    /// Subject line.
+    pub subject: Option<String>,
+}
+
+impl ScreenedMessage {
+    /// Creates a message known only by its sender.
+    pub fn from_sender(sender: &str) -> Self {
+        Self {
+            sender: sender.trim().to_lowercase(),
+            list_id: None,
+            subject: None,
+        }
+    }
+
+    /// Takes the sender, `List-Id` and subject of an email.
+    pub fn from_email(email: &Email) -> Self {
+        Self {
+            sender: email.from.email.trim().to_lowercase(),
+            list_id: email.header("List-Id").map(str::to_string),
+            subject: email.subject.clone(),
+        }
+    }
+
+    /// Returns the sender's domain.
+    pub fn domain(&self) -> &str {
+        self.sender
+            .rsplit_once('@')
+            .map_or("", |(_, domain)| domain)
+    }
+
+    /// Returns the value of a field, if the message has it.
+    pub fn field(&self, field: RuleField) -> Option<&str> {
+        match field {
+            RuleField::Sender => Some(&self.sender),
+            RuleField::ListId => self.list_id.as_deref(),
+            RuleField::Subject => self.subject.as_deref(),
+        }
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?'])
+}
+
+/// Translates a glob into an anchored regular expression.
+fn glob_to_regex(glob: &str) -> String {
+    let mut source = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => source.push_str(".*"),
+            '?' => source.push('.'),
+            c => source.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    source.push('$');
+    source
 }
 
 /// Categorization of a sender type by AI analysis.
@@ -106,6 +320,9 @@ pub struct SenderAnalysis {
     pub reasoning: String,
     /// Suggested action based on analysis.
     pub suggested_action: ScreenerAction,
+    /// Calibrated confidence in the suggested action, from 0.0 to 1.0.
+    #[serde(default)]
+    pub confidence: f32,
 }
 
 #[cfg(test)]
@@ -116,6 +333,7 @@ mod tests {
     fn screener_entry_serialization() {
         let entry = ScreenerEntry {
             id: "entry-1".to_string(),
+            account_id: AccountId::from("account-1"),
             sender_email: "unknown@example.com".to_string(),
             sender_name: Some("Unknown Sender".to_string()),
             first_email_id: Some(EmailId::from("email-1")),
@@ -136,9 +354,12 @@ mod tests {
     fn screener_rule_serialization() {
         let rule = ScreenerRule {
             id: "rule-1".to_string(),
+            account_id: None,
             rule_type: RuleType::DomainAllow,
+            field: RuleField::Sender,
             pattern: "trusted.com".to_string(),
             action: ScreenerAction::Approve,
+            priority: 0,
             created_at: Utc::now(),
         };
 
@@ -155,6 +376,7 @@ mod tests {
             likely_type: SenderType::Newsletter,
             reasoning: "Contains unsubscribe link and weekly format".to_string(),
             suggested_action: ScreenerAction::Review,
+            confidence: 0.8,
         };
 
         let json = serde_json::to_string(&analysis).unwrap();
@@ -162,6 +384,14 @@ mod tests {
 
         assert_eq!(deserialized.likely_type, SenderType::Newsletter);
         assert_eq!(deserialized.suggested_action, ScreenerAction::Review);
+        assert_eq!(deserialized.confidence, 0.8);
+    }
+
+    #[test]
+    fn sender_analysis_without_confidence() {
+        let json = r#"{"likely_type":"newsletter","reasoning":"","suggested_action":"review"}"#;
+        let analysis: SenderAnalysis = serde_json::from_str(json).unwrap();
+        assert_eq!(analysis.confidence, 0.0);
     }
 
     #[test]
@@ -203,4 +433,83 @@ mod tests {
             "\"pattern\""
         );
     }
+
+    #[test]
+    fn domain_rules_match_subdomains() {
+        let rule = ScreenerRule::new(RuleType::DomainBlock, "example.com", ScreenerAction::Reject);
+
+        assert!(rule.matches(&ScreenedMessage::from_sender("a@example.com")));
+        assert!(rule.matches(&ScreenedMessage::from_sender("a@news.Example.com")));
+        assert!(!rule.matches(&ScreenedMessage::from_sender("a@notexample.com")));
+    }
+
+    #[test]
+    fn glob_and_regex_rules() {
+        let glob = ScreenerRule::new(
+            RuleType::Pattern,
+            "noreply@*.shop.*",
+            ScreenerAction::Reject,
+        );
+        assert!(glob.matches(&ScreenedMessage::from_sender("noreply@mail.shop.com")));
+        assert!(!glob.matches(&ScreenedMessage::from_sender("support@mail.shop.com")));
+
+        let substring = ScreenerRule::new(RuleType::Pattern, "billing", ScreenerAction::Approve);
+        assert!(substring.matches(&ScreenedMessage::from_sender("Billing@acme.com")));
+
+        let regex = ScreenerRule::new(
+            RuleType::Regex,
+            r"^(invoice|receipt)s?@",
+            ScreenerAction::Approve,
+        );
+        assert!(regex.matches(&ScreenedMessage::from_sender("receipts@acme.com")));
+        assert!(!regex.matches(&ScreenedMessage::from_sender("news@acme.com")));
+
+        let invalid = ScreenerRule::new(RuleType::Regex, "(", ScreenerAction::Approve);
+        assert!(invalid.validate().is_err());
+        assert!(!invalid.matches(&ScreenedMessage::from_sender("(@acme.com")));
+    }
+
+    #[test]
+    fn header_rules() {
+        let message = ScreenedMessage {
+            sender: "digest@lists.example.org".to_string(),
+            list_id: Some("Rust Digest <rust-digest.lists.example.org>".to_string()),
+            subject: Some("Weekly digest #42".to_string()),
+        };
+
+        let list = ScreenerRule::new(
+            RuleType::Pattern,
+            "*<rust-digest.*",
+            ScreenerAction::Approve,
+        )
+        .on_field(RuleField::ListId);
+        assert!(list.matches(&message));
+
+        let subject = ScreenerRule::new(RuleType::Regex, r"digest #\d+", ScreenerAction::Approve)
+            .on_field(RuleField::Subject);
+        assert!(subject.matches(&message));
+        assert!(!subject.matches(&ScreenedMessage::from_sender("digest@lists.example.org")));
+    }
+
+    #[test]
+    fn first_matching_rule_respects_priority_and_account() {
+        let account = AccountId::from("work");
+        let message = ScreenedMessage::from_sender("news@example.com");
+
+        let block = ScreenerRule::new(RuleType::DomainBlock, "example.com", ScreenerAction::Reject);
+        let allow = ScreenerRule::new(RuleType::Pattern, "news@*", ScreenerAction::Approve)
+            .with_priority(10);
+        let other_account = ScreenerRule::new(RuleType::Pattern, "news@*", ScreenerAction::Review)
+            .with_priority(20)
+            .for_account(AccountId::from("personal"));
+
+        let rules = vec![block.clone(), allow.clone(), other_account];
+        let matched = first_matching_rule(&rules, &account, &message).unwrap();
+        assert_eq!(matched.id, allow.id);
+
+        let scoped = block.for_account(account.clone()).with_priority(10);
+        let rules = vec![allow, scoped.clone()];
+        let matched = first_matching_rule(&rules, &account, &message).unwrap();
+        assert_eq!(matched.id, scoped.id);
+    }
 }
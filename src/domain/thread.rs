@@ -143,6 +143,7 @@ mod tests {
                 is_draft: false,
                 labels: vec![],
                 attachments: vec![],
+                headers: vec![],
             }],
             last_message_date: Utc::now(),
             unread_count: 0,
@@ -0,0 +1,227 @@
+//! Splitting email text into passages for embedding.
+//!
+//! Embedding models only see a fixed number of tokens, so long emails are
+//! cut into overlapping windows that are embedded separately. Quoted reply
+//! history and signatures are removed first: they repeat content from other
+//! emails and would otherwise dominate the passages of long threads.
+
+/// Markers that start a forwarded or quoted message in reply bodies.
+const QUOTE_HEADERS: &[&str] = &[
+    "-----Original Message-----",
+    "________________________________",
+    "---------- Forwarded message ---------",
+    "Begin forwarded message:",
+];
+
+/// Prefixes of automatic mobile client signatures.
+const MOBILE_SIGNATURES: &[&str] = &["Sent from my ", "Get Outlook for "];
+
+/// Removes quoted replies and the signature from a plain text body.
+///
+/// Everything after a signature delimiter (`-- `), a reply attribution
+/// ("On ... wrote:") or a forwarded-message header is dropped, as are lines
+/// starting with `>`. If nothing is left, the original body is returned so
+/// bodies that only forward another message are still searchable.
+pub fn strip_quotes_and_signature(body: &str) -> String {
+    let lines: Vec<&str> = body.lines().collect();
+    let mut kept = Vec::with_capacity(lines.len());
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        if trimmed == "--"
+            || QUOTE_HEADERS.iter().any(|h| trimmed.starts_with(h))
+            || MOBILE_SIGNATURES.iter().any(|s| trimmed.starts_with(s))
+            || is_attribution(trimmed, lines.get(i + 1).map(|l| l.trim()))
+        {
+            break;
+        }
+
+        if trimmed.starts_with('>') {
+            continue;
+        }
+
+        kept.push(*line);
+    }
+
+    let stripped = kept.join("\n").trim().to_string();
+    if stripped.is_empty() {
+        body.trim().to_string()
+    } else {
+        stripped
+    }
+}
+
+/// Returns whether a line starts a reply attribution such as
+/// "On Mon, Jan 1, 2024 at 9:00 AM Alice <alice@example.com> wrote:".
+///
+/// Clients often wrap long attributions, so the "wrote:" may be on the
+/// following line.
+fn is_attribution(line: &str, next: Option<&str>) -> bool {
+    if !line.starts_with("On ") {
+        return false;
+    }
+    line.ends_with("wrote:") || next.is_some_and(|n| !n.starts_with("On ") && n.ends_with("wrote:"))
+}
+
+/// Returns the byte spans of whitespace-separated words in `text`.
+///
+/// Used as an approximation of token spans when no tokenizer is loaded.
+pub fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+
+    spans
+}
+
+/// Splits text into overlapping passages of at most `window` tokens.
+///
+/// `token_spans` are the byte ranges of each token in `text`, in order.
+/// Consecutive passages share `overlap` tokens so sentences cut by a window
+/// boundary appear whole in at least one passage. At most `max_passages`
+/// passages are returned; text beyond them is not embedded.
+pub fn split_passages(
+    text: &str,
+    token_spans: &[(usize, usize)],
+    window: usize,
+    overlap: usize,
+    max_passages: usize,
+) -> Vec<String> {
+    let window = window.max(1);
+    if token_spans.len() <= window {
+        let text = text.trim();
+        return if text.is_empty() {
+            Vec::new()
+        } else {
+            vec![text.to_string()]
+        };
+    }
+
+    let step = window.saturating_sub(overlap).max(1);
+    let mut passages = Vec::new();
+    let mut start = 0;
+
+    while passages.len() < max_passages.max(1) {
+        let end = (start + window).min(token_spans.len());
+        if let Some(passage) = text.get(token_spans[start].0..token_spans[end - 1].1) {
+            passages.push(passage.trim().to_string());
+        }
+
+        if end == token_spans.len() {
+            break;
+        }
+        start += step;
+    }
+
+    passages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_quoted_reply() {
+        let body = "Sounds good, see you then.\n\nOn Mon, Jan 1, 2024 at 9:00 AM Alice <alice@example.com> wrote:\n> Can we meet at 3?\n> Thanks";
+        assert_eq!(
+            strip_quotes_and_signature(body),
+            "Sounds good, see you then."
+        );
+    }
+
+    #[test]
+    fn strips_wrapped_attribution() {
+        let body = "Yes.\n\nOn Mon, Jan 1, 2024 at 9:00 AM Alice Example\n<alice@example.com> wrote:\n> Question?";
+        assert_eq!(strip_quotes_and_signature(body), "Yes.");
+    }
+
+    #[test]
+    fn strips_signature() {
+        let body = "Report attached.\n\n-- \nBob\nACME Corp";
+        assert_eq!(strip_quotes_and_signature(body), "Report attached.");
+
+        let body = "On my way.\n\nSent from my iPhone";
+        assert_eq!(strip_quotes_and_signature(body), "On my way.");
+    }
+
+    #[test]
+    fn strips_outlook_history() {
+        let body = "Approved.\n\n-----Original Message-----\nFrom: Carol\nSubject: Budget";
+        assert_eq!(strip_quotes_and_signature(body), "Approved.");
+    }
+
+    #[test]
+    fn drops_inline_quotes() {
+        let body = "> first question\nanswer one\n> second question\nanswer two";
+        assert_eq!(strip_quotes_and_signature(body), "answer one\nanswer two");
+    }
+
+    #[test]
+    fn keeps_forward_only_bodies() {
+        let body = "---------- Forwarded message ---------\nFrom: Dave\nSee below";
+        assert_eq!(strip_quotes_and_signature(body), body);
+    }
+
+    #[test]
+    fn lines_starting_with_on_are_kept() {
+        let body = "On Tuesday we ship.\nOn Wednesday we rest.";
+        assert_eq!(strip_quotes_and_signature(body), body);
+    }
+
+    #[test]
+    fn word_spans_cover_words() {
+        let text = "  hello  wide\tworld ";
+        let words: Vec<&str> = word_spans(text)
+            .into_iter()
+            .map(|(s, e)| &text[s..e])
+            .collect();
+        assert_eq!(words, vec!["hello", "wide", "world"]);
+    }
+
+    #[test]
+    fn short_text_is_one_passage() {
+        let text = "a short email";
+        let passages = split_passages(text, &word_spans(text), 10, 2, 8);
+        assert_eq!(passages, vec!["a short email"]);
+    }
+
+    #[test]
+    fn empty_text_has_no_passages() {
+        assert!(split_passages("  ", &[], 10, 2, 8).is_empty());
+    }
+
+    #[test]
+    fn long_text_is_split_with_overlap() {
+        let text = "w0 w1 w2 w3 w4 w5 w6 w7 w8 w9";
+        let passages = split_passages(text, &word_spans(text), 4, 1, 8);
+        assert_eq!(passages, vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7 w8 w9"]);
+    }
+
+    #[test]
+    fn passages_are_capped() {
+        let text = "w0 w1 w2 w3 w4 w5 w6 w7 w8 w9";
+        let passages = split_passages(text, &word_spans(text), 2, 0, 3);
+        assert_eq!(passages, vec!["w0 w1", "w2 w3", "w4 w5"]);
+    }
+
+    #[test]
+    fn overlap_larger_than_window_still_advances() {
+        let text = "w0 w1 w2 w3";
+        let passages = split_passages(text, &word_spans(text), 2, 5, 8);
+        assert_eq!(passages, vec!["w0 w1", "w1 w2", "w2 w3"]);
+    }
+}
@@ -8,11 +8,17 @@ use candle_core::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::bert::{BertModel, Config as BertConfig};
 use hf_hub::{api::sync::Api, Repo, RepoType};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokenizers::Tokenizer;
 
+use crate::config::SearchSettings;
 use crate::domain::{Email, EmailId};
-use crate::embedding::VectorStore;
+use crate::embedding::chunking::{split_passages, strip_quotes_and_signature, word_spans};
+use crate::embedding::models::{CONFIG_FILE, TOKENIZER_FILE, WEIGHTS_FILE};
+use crate::embedding::{
+    DownloadStatus, ModelFiles, ModelInfo, ModelType, PassageEmbedding, PassageMatch,
+    QuantizationConfig, VectorStore,
+};
 
 /// A vector embedding representing text semantics.
 ///
@@ -74,26 +80,60 @@ impl Embedding {
 /// Configuration for the embedding engine.
 #[derive(Debug, Clone)]
 pub struct EmbeddingConfig {
-    /// Path to the model weights.
+    /// Local directory holding `config.json`, `tokenizer.json` and
+    /// `model.safetensors`.
+    ///
+    /// When set, the model is only loaded from here and the hash-based
+    /// fallback is never used.
     pub model_path: Option<PathBuf>,
+    /// Directory of bundled model packs, one subdirectory per model.
+    pub bundled_models_path: Option<PathBuf>,
+    /// Whether the model may be downloaded from Hugging Face.
+    pub allow_download: bool,
     /// Model identifier for downloading from Hugging Face.
     pub model_id: String,
+    /// Expected model files and checksums.
+    ///
+    /// Defaults to the registry entry for `model_id`.
+    pub model_info: Option<ModelInfo>,
     /// Maximum sequence length for tokenization.
     pub max_seq_length: usize,
     /// Whether to use GPU acceleration if available.
     pub use_gpu: bool,
     /// Whether to use fallback (hash-based) embeddings when model unavailable.
     pub use_fallback: bool,
+    /// In-memory quantization of stored embeddings.
+    pub quantization: QuantizationConfig,
+    /// Tokens shared by consecutive passages of a long email.
+    pub passage_overlap: usize,
+    /// Maximum passages embedded per email.
+    pub max_passages: usize,
 }
 
 impl Default for EmbeddingConfig {
     fn default() -> Self {
         Self {
             model_path: None,
+            bundled_models_path: None,
+            allow_download: true,
             model_id: "sentence-transformers/all-MiniLM-L6-v2".to_string(),
+            model_info: None,
             max_seq_length: 256,
             use_gpu: false,
             use_fallback: true,
+            quantization: QuantizationConfig::default(),
+            passage_overlap: 32,
+            max_passages: 16,
+        }
+    }
+}
+
+impl From<&SearchSettings> for EmbeddingConfig {
+    fn from(settings: &SearchSettings) -> Self {
+        Self {
+            model_path: settings.model_dir.clone(),
+            allow_download: settings.allow_model_download,
+            ..Default::default()
         }
     }
 }
@@ -109,6 +149,7 @@ pub struct EmbeddingEngine {
     tokenizer: Option<Tokenizer>,
     device: Device,
     initialized: bool,
+    status: DownloadStatus,
 }
 
 impl EmbeddingEngine {
@@ -127,9 +168,19 @@ impl EmbeddingEngine {
             tokenizer: None,
             device,
             initialized: false,
+            status: DownloadStatus::NotDownloaded,
         }
     }
 
+    /// Creates an engine with a vector store built from the configuration.
+    ///
+    /// The store uses the configured quantization, loading any vectors
+    /// already saved in its full-precision file.
+    pub fn from_config(config: EmbeddingConfig) -> Result<Self> {
+        let vector_store = VectorStore::with_quantization(config.quantization.clone())?;
+        Ok(Self::new(config, vector_store))
+    }
+
     /// Creates an embedding engine with default configuration.
     pub fn with_defaults(vector_store: VectorStore) -> Self {
         Self::new(EmbeddingConfig::default(), vector_store)
@@ -138,6 +189,10 @@ impl EmbeddingEngine {
     /// Initializes the model, loading weights from disk or downloading if needed.
     ///
     /// This should be called before using `embed()` or `index_email()`.
+    /// The outcome is reported by [`status`](Self::status). Hash-based
+    /// fallback embeddings are only used when the model was to be
+    /// downloaded and `use_fallback` is set; a configured local model that
+    /// fails to load is always an error.
     pub async fn initialize(&mut self) -> Result<()> {
         if self.initialized {
             return Ok(());
@@ -152,10 +207,12 @@ impl EmbeddingEngine {
         match self.load_model() {
             Ok(()) => {
                 self.initialized = true;
+                self.status = DownloadStatus::Ready;
                 tracing::info!("Embedding model loaded successfully");
             }
             Err(e) => {
-                if self.config.use_fallback {
+                self.status = DownloadStatus::Failed(format!("{:#}", e));
+                if self.config.use_fallback && self.config.model_path.is_none() {
                     tracing::warn!(
                         error = %e,
                         "Failed to load embedding model, using fallback hash-based embeddings"
@@ -170,22 +227,64 @@ impl EmbeddingEngine {
         Ok(())
     }
 
-    /// Loads the model and tokenizer from HuggingFace Hub.
+    /// Returns the state of the embedding model for display in settings.
+    pub fn status(&self) -> &DownloadStatus {
+        &self.status
+    }
+
+    /// Finds the model files, preferring local copies over downloads.
+    ///
+    /// Checks the configured model directory, then the bundled model pack,
+    /// then downloads from HuggingFace Hub if allowed. Every file is verified
+    /// against the checksums pinned in the model's [`ModelInfo`].
+    fn resolve_model_files(&mut self) -> Result<ModelFiles> {
+        let model_type = ModelType::from_hf_model_id(&self.config.model_id);
+        // Models outside the registry have no pinned checksums.
+        let info = self
+            .config
+            .model_info
+            .clone()
+            .or_else(|| model_type.map(ModelInfo::for_model))
+            .unwrap_or_else(|| ModelInfo {
+                files: Vec::new(),
+                ..ModelInfo::for_model(ModelType::default())
+            });
+
+        if let Some(dir) = &self.config.model_path {
+            tracing::info!(path = %dir.display(), "Loading embedding model from local directory");
+            return info
+                .load_from_dir(dir)
+                .context("Invalid local embedding model directory");
+        }
+
+        if let (Some(packs), Some(model_type)) = (&self.config.bundled_models_path, model_type) {
+            let dir = packs.join(model_type.pack_dir_name());
+            if dir.is_dir() {
+                tracing::info!(path = %dir.display(), "Loading bundled embedding model");
+                return info
+                    .load_from_dir(&dir)
+                    .context("Invalid bundled embedding model");
+            }
+        }
+
+        if !self.config.allow_download {
+            anyhow::bail!(
+                "Embedding model {} is not available offline and downloads are disabled",
+                self.config.model_id
+            );
+        }
+
+        self.status = DownloadStatus::Downloading;
+        download_model_files(&self.config.model_id, &info)
+    }
+
+    /// Loads the model and tokenizer from the resolved model files.
     fn load_model(&mut self) -> Result<()> {
-        let api = Api::new().context("Failed to create HuggingFace API client")?;
-        let repo = api.repo(Repo::new(self.config.model_id.clone(), RepoType::Model));
-
-        // Download model files
-        let config_path = repo
-            .get("config.json")
-            .context("Failed to get config.json")?;
-        let tokenizer_path = repo
-            .get("tokenizer.json")
-            .context("Failed to get tokenizer.json")?;
-        let weights_path = repo
-            .get("model.safetensors")
-            .or_else(|_| repo.get("pytorch_model.bin"))
-            .context("Failed to get model weights")?;
+        let ModelFiles {
+            config: config_path,
+            tokenizer: tokenizer_path,
+            weights: weights_path,
+        } = self.resolve_model_files()?;
 
         // Load configuration
         let config_str =
@@ -193,23 +292,16 @@ impl EmbeddingEngine {
         let bert_config: BertConfig =
             serde_json::from_str(&config_str).context("Failed to parse config.json")?;
 
-        // Load tokenizer
-        let tokenizer = Tokenizer::from_file(&tokenizer_path)
+        // Load tokenizer. Long texts are split into passages rather than
+        // truncated, so the tokenizer must see the whole text.
+        let mut tokenizer = Tokenizer::from_file(&tokenizer_path)
             .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+        tokenizer
+            .with_truncation(None)
+            .map_err(|e| anyhow::anyhow!("Failed to configure tokenizer: {}", e))?;
 
         // Load model weights
-        let vb = if weights_path
-            .extension()
-            .is_some_and(|ext| ext == "safetensors")
-        {
-            unsafe {
-                VarBuilder::from_mmaped_safetensors(&[weights_path], DType::F32, &self.device)
-                    .context("Failed to load safetensors weights")?
-            }
-        } else {
-            VarBuilder::from_pth(weights_path, DType::F32, &self.device)
-                .context("Failed to load PyTorch weights")?
-        };
+        let vb = load_weights(&weights_path, &self.device)?;
 
         // Build model
         let model = BertModel::load(vb, &bert_config).context("Failed to build BERT model")?;
@@ -307,16 +399,66 @@ impl EmbeddingEngine {
         self.vector_store.search(query_embedding, limit)
     }
 
-    /// Indexes an email by generating and storing its embedding.
+    /// Searches for emails similar to the query embedding.
     ///
-    /// Combines subject and body text for a comprehensive representation.
+    /// Returns the best-matching passage of each email for highlighting.
+    pub fn search_passages(
+        &self,
+        query_embedding: &Embedding,
+        limit: usize,
+    ) -> Result<Vec<PassageMatch>> {
+        self.vector_store.search_passages(query_embedding, limit)
+    }
+
+    /// Indexes an email by generating and storing its passage embeddings.
     pub fn index_email(&mut self, email: &Email) -> Result<()> {
-        let text = Self::email_to_text(email);
-        let embedding = self.embed(&text)?;
-        self.vector_store.insert(&email.id, embedding)?;
+        let passages = self.embed_passages(email)?;
+        self.vector_store.insert_passages(&email.id, passages)?;
         Ok(())
     }
 
+    /// Generates passage embeddings for an email without storing them.
+    ///
+    /// Emails longer than the model's sequence length are split into
+    /// overlapping token windows, each embedded separately.
+    pub fn embed_passages(&self, email: &Email) -> Result<Vec<PassageEmbedding>> {
+        let text = Self::email_to_text(email);
+        let spans = self.token_spans(&text);
+        // Leave room for the [CLS] and [SEP] tokens added when embedding.
+        let window = self.config.max_seq_length.saturating_sub(2);
+
+        split_passages(
+            &text,
+            &spans,
+            window,
+            self.config.passage_overlap,
+            self.config.max_passages,
+        )
+        .into_iter()
+        .map(|text| {
+            let embedding = self.embed(&text)?;
+            Ok(PassageEmbedding { text, embedding })
+        })
+        .collect()
+    }
+
+    /// Returns the byte spans of the tokens in `text`.
+    ///
+    /// Falls back to whitespace-separated words without a tokenizer.
+    fn token_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        let Some(tokenizer) = &self.tokenizer else {
+            return word_spans(text);
+        };
+
+        match tokenizer.encode(text, false) {
+            Ok(encoding) => encoding.get_offsets().to_vec(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Tokenization failed, splitting on words");
+                word_spans(text)
+            }
+        }
+    }
+
     /// Returns whether the model is loaded (vs using fallback).
     pub fn is_model_loaded(&self) -> bool {
         self.model.is_some() && self.tokenizer.is_some()
@@ -333,6 +475,8 @@ impl EmbeddingEngine {
     }
 
     /// Converts an email to indexable text.
+    ///
+    /// Quoted replies and signatures are stripped from the body.
     fn email_to_text(email: &Email) -> String {
         let mut parts = Vec::new();
 
@@ -341,7 +485,7 @@ impl EmbeddingEngine {
         }
 
         if let Some(body) = &email.body_text {
-            parts.push(body.clone());
+            parts.push(strip_quotes_and_signature(body));
         }
 
         parts.join(" ")
@@ -357,6 +501,47 @@ impl EmbeddingEngine {
     }
 }
 
+/// Loads model weights from a safetensors or PyTorch file.
+pub(crate) fn load_weights(path: &Path, device: &Device) -> Result<VarBuilder<'static>> {
+    if path.extension().is_some_and(|ext| ext == "safetensors") {
+        unsafe {
+            VarBuilder::from_mmaped_safetensors(&[path], DType::F32, device)
+                .context("Failed to load safetensors weights")
+        }
+    } else {
+        VarBuilder::from_pth(path, DType::F32, device).context("Failed to load PyTorch weights")
+    }
+}
+
+/// Downloads a model's files from HuggingFace Hub and verifies them.
+///
+/// Files already in the local Hub cache are not downloaded again.
+pub(crate) fn download_model_files(model_id: &str, info: &ModelInfo) -> Result<ModelFiles> {
+    let api = Api::new().context("Failed to create HuggingFace API client")?;
+    let repo = api.repo(Repo::new(model_id.to_string(), RepoType::Model));
+
+    let config = repo.get(CONFIG_FILE).context("Failed to get config.json")?;
+    let tokenizer = repo
+        .get(TOKENIZER_FILE)
+        .context("Failed to get tokenizer.json")?;
+    let weights = repo
+        .get(WEIGHTS_FILE)
+        .or_else(|_| repo.get("pytorch_model.bin"))
+        .context("Failed to get model weights")?;
+
+    info.verify_file(CONFIG_FILE, &config)?;
+    info.verify_file(TOKENIZER_FILE, &tokenizer)?;
+    if let Some(name) = weights.file_name().and_then(|n| n.to_str()) {
+        info.verify_file(name, &weights)?;
+    }
+
+    Ok(ModelFiles {
+        config,
+        tokenizer,
+        weights,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -385,6 +570,7 @@ mod tests {
             is_draft: false,
             labels: vec![],
             attachments: vec![],
+            headers: vec![],
         }
     }
 
@@ -494,6 +680,58 @@ mod tests {
         assert!(text.contains("Body content here"));
     }
 
+    #[test]
+    fn email_to_text_strips_quoted_reply() {
+        let email = make_test_email(
+            "email-1",
+            "Re: Lunch",
+            "Works for me.\n\nOn Mon, Jan 1, 2024 at 9:00 AM Alice <a@example.com> wrote:\n> Noon?",
+        );
+        let text = EmbeddingEngine::email_to_text(&email);
+        assert_eq!(text, "Re: Lunch Works for me.");
+    }
+
+    #[test]
+    fn long_email_is_indexed_as_passages() {
+        let config = EmbeddingConfig {
+            max_seq_length: 12,
+            passage_overlap: 2,
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::new(config, VectorStore::new());
+
+        let body: Vec<String> = (0..40).map(|i| format!("word{}", i)).collect();
+        let email = make_test_email("email-1", "Newsletter", &body.join(" "));
+        let passages = engine.embed_passages(&email).unwrap();
+        assert_eq!(passages.len(), 5);
+        assert!(passages[0].text.starts_with("Newsletter word0"));
+        assert!(passages[1].text.starts_with("word7 word8"));
+
+        engine.index_email(&email).unwrap();
+        assert_eq!(engine.vector_store().len(), 1);
+        assert_eq!(engine.vector_store().vector_count(), 5);
+
+        let query = engine.embed(&passages[3].text).unwrap();
+        let results = engine.search_passages(&query, 1).unwrap();
+        assert_eq!(results[0].passage_index, 3);
+        assert_eq!(results[0].text.as_deref(), Some(passages[3].text.as_str()));
+    }
+
+    #[test]
+    fn passages_are_capped() {
+        let config = EmbeddingConfig {
+            max_seq_length: 12,
+            passage_overlap: 0,
+            max_passages: 2,
+            ..Default::default()
+        };
+        let engine = EmbeddingEngine::new(config, VectorStore::new());
+
+        let body: Vec<String> = (0..100).map(|i| format!("word{}", i)).collect();
+        let email = make_test_email("email-1", "Digest", &body.join(" "));
+        assert_eq!(engine.embed_passages(&email).unwrap().len(), 2);
+    }
+
     #[test]
     fn default_config() {
         let config = EmbeddingConfig::default();
@@ -501,6 +739,26 @@ mod tests {
         assert_eq!(config.max_seq_length, 256);
         assert!(!config.use_gpu);
         assert!(config.use_fallback);
+        assert!(config.allow_download);
+        assert!(config.model_path.is_none());
+        assert!(!config.quantization.is_enabled());
+        assert_eq!(config.passage_overlap, 32);
+        assert_eq!(config.max_passages, 16);
+    }
+
+    #[test]
+    fn from_config_applies_quantization() {
+        let config = EmbeddingConfig {
+            quantization: QuantizationConfig::new(crate::embedding::Quantization::Int8),
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::from_config(config).unwrap();
+
+        let email = make_test_email("email-1", "Quarterly report", "Numbers attached.");
+        engine.index_email(&email).unwrap();
+
+        assert!(engine.vector_store().quantization().is_enabled());
+        assert!(engine.vector_store().vector_memory_bytes() < 384 * 4);
     }
 
     #[test]
@@ -513,4 +771,76 @@ mod tests {
         let embedding = engine.embed("test").unwrap();
         assert_eq!(embedding.dimension(), 384);
     }
+
+    #[test]
+    fn config_from_search_settings() {
+        let settings = SearchSettings {
+            model_dir: Some(PathBuf::from("/opt/models/minilm")),
+            allow_model_download: false,
+            ..Default::default()
+        };
+        let config = EmbeddingConfig::from(&settings);
+        assert_eq!(config.model_path, settings.model_dir);
+        assert!(!config.allow_download);
+    }
+
+    #[tokio::test]
+    async fn missing_local_model_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EmbeddingConfig {
+            model_path: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::new(config, VectorStore::new());
+
+        // A configured local model never degrades to fallback embeddings.
+        assert!(engine.initialize().await.is_err());
+        assert!(engine.status().is_failed());
+        assert!(engine.status().label().contains("config.json"));
+    }
+
+    #[tokio::test]
+    async fn corrupted_local_model_fails_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in [CONFIG_FILE, TOKENIZER_FILE, WEIGHTS_FILE] {
+            std::fs::write(dir.path().join(name), "{}").unwrap();
+        }
+        let info = ModelInfo::for_model(ModelType::AllMiniLmL6V2).with_checksum(
+            WEIGHTS_FILE,
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        );
+        let config = EmbeddingConfig {
+            model_path: Some(dir.path().to_path_buf()),
+            model_info: Some(info),
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::new(config, VectorStore::new());
+
+        assert!(engine.initialize().await.is_err());
+        assert!(engine.status().label().contains("checksum mismatch"));
+    }
+
+    #[tokio::test]
+    async fn offline_without_model_reports_failure() {
+        let packs = tempfile::tempdir().unwrap();
+        let config = EmbeddingConfig {
+            bundled_models_path: Some(packs.path().to_path_buf()),
+            allow_download: false,
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::new(config, VectorStore::new());
+
+        // Falls back, but the failure stays visible.
+        engine.initialize().await.unwrap();
+        assert!(!engine.is_model_loaded());
+        assert!(engine.status().label().contains("downloads are disabled"));
+
+        let config = EmbeddingConfig {
+            allow_download: false,
+            use_fallback: false,
+            ..Default::default()
+        };
+        let mut engine = EmbeddingEngine::new(config, VectorStore::new());
+        assert!(engine.initialize().await.is_err());
+    }
 }
@@ -0,0 +1,921 @@
+//! Hierarchical Navigable Small World (HNSW) index.
+//!
+//! Provides approximate nearest-neighbor search over embeddings in roughly
+//! logarithmic time. The index only stores the graph; vectors are read through
+//! a [`VectorSource`] so the store does not keep a second copy of every
+//! embedding in memory.
+//!
+//! Removal is incremental: removed nodes become tombstones. Beam searches
+//! still pass through them to reach their neighbors, but never return them.
+//! Once tombstones make up half of the graph,
+//! [`HnswIndex::needs_compaction`] reports that a rebuild is worthwhile.
+//! Until then the greedy descent through the upper layers cannot step onto
+//! tombstones, so routing degrades as they accumulate.
+
+use anyhow::{bail, Context, Result};
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::domain::EmailId;
+use crate::embedding::Embedding;
+
+/// Magic bytes identifying a persisted index file.
+const FILE_MAGIC: &[u8; 4] = b"HNSW";
+
+/// Version of the on-disk format.
+const FILE_VERSION: u32 = 1;
+
+/// Upper bound on node levels, far above what realistic mailboxes reach.
+const MAX_LEVEL: usize = 16;
+
+/// Minimum graph size before tombstones trigger compaction.
+const MIN_COMPACTION_SIZE: usize = 64;
+
+/// Source of vectors referenced by the index.
+///
+/// Sources may store vectors in a compressed form, in which case `vector`
+/// reconstructs an approximation and `dot` may be approximate too.
+pub trait VectorSource {
+    /// Returns the vector stored for an email, if any.
+    fn vector(&self, email_id: &EmailId) -> Option<Cow<'_, [f32]>>;
+
+    /// Returns the dot product of the query with the stored vector.
+    fn dot(&self, query: &[f32], email_id: &EmailId) -> Option<f32>;
+}
+
+impl VectorSource for HashMap<EmailId, Embedding> {
+    fn vector(&self, email_id: &EmailId) -> Option<Cow<'_, [f32]>> {
+        self.get(email_id)
+            .map(|embedding| Cow::Borrowed(embedding.values.as_slice()))
+    }
+
+    fn dot(&self, query: &[f32], email_id: &EmailId) -> Option<f32> {
+        let embedding = self.get(email_id)?;
+        if embedding.values.len() != query.len() {
+            return Some(0.0);
+        }
+        Some(dot(query, &embedding.values))
+    }
+}
+
+/// Tuning parameters for the HNSW graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HnswConfig {
+    /// Maximum links per node on upper layers (layer 0 allows twice as many).
+    pub m: usize,
+    /// Candidate list size used while inserting.
+    pub ef_construction: usize,
+    /// Candidate list size used while searching.
+    pub ef_search: usize,
+}
+
+impl Default for HnswConfig {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// A graph node for a single email.
+#[derive(Debug, Clone)]
+struct Node {
+    email_id: EmailId,
+    /// Neighbor lists, one per layer from 0 up to the node's level.
+    neighbors: Vec<Vec<u32>>,
+    /// L2 norm of the node's vector, cached so comparisons need one pass.
+    norm: f32,
+    deleted: bool,
+}
+
+impl Node {
+    fn level(&self) -> usize {
+        self.neighbors.len().saturating_sub(1)
+    }
+}
+
+/// A query vector with its norm precomputed.
+#[derive(Debug, Clone, Copy)]
+struct Query<'a> {
+    values: &'a [f32],
+    norm: f32,
+}
+
+impl<'a> Query<'a> {
+    fn new(values: &'a [f32]) -> Self {
+        Self {
+            values,
+            norm: l2_norm(values),
+        }
+    }
+}
+
+/// A node paired with its similarity to the current query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored {
+    similarity: f32,
+    node: usize,
+}
+
+impl Eq for Scored {}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .partial_cmp(&other.similarity)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Approximate nearest-neighbor index using cosine similarity.
+#[derive(Debug, Clone)]
+pub struct HnswIndex {
+    config: HnswConfig,
+    nodes: Vec<Node>,
+    lookup: HashMap<EmailId, usize>,
+    entry_point: Option<usize>,
+    deleted: usize,
+    rng_state: u64,
+}
+
+impl HnswIndex {
+    /// Creates an empty index with the given configuration.
+    pub fn new(config: HnswConfig) -> Self {
+        Self {
+            config,
+            nodes: Vec::new(),
+            lookup: HashMap::new(),
+            entry_point: None,
+            deleted: 0,
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Returns the index configuration.
+    pub fn config(&self) -> HnswConfig {
+        self.config
+    }
+
+    /// Returns the number of live (non-removed) entries.
+    pub fn len(&self) -> usize {
+        self.lookup.len()
+    }
+
+    /// Returns whether the index has no live entries.
+    pub fn is_empty(&self) -> bool {
+        self.lookup.is_empty()
+    }
+
+    /// Returns whether the index contains a live entry for the email.
+    pub fn contains(&self, email_id: &EmailId) -> bool {
+        self.lookup.contains_key(email_id)
+    }
+
+    /// Returns an iterator over the live email IDs in the index.
+    pub fn email_ids(&self) -> impl Iterator<Item = &EmailId> {
+        self.lookup.keys()
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.lookup.clear();
+        self.entry_point = None;
+        self.deleted = 0;
+    }
+
+    /// Inserts an email into the graph.
+    ///
+    /// The email's vector must already be available from `source`. Inserting
+    /// an email that is already indexed replaces its previous node.
+    pub fn insert<V: VectorSource>(&mut self, email_id: &EmailId, source: &V) {
+        if self.lookup.contains_key(email_id) {
+            self.remove(email_id);
+        }
+
+        let Some(vector) = source.vector(email_id) else {
+            return;
+        };
+        let query = Query::new(&vector);
+
+        let level = self.random_level();
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            email_id: email_id.clone(),
+            neighbors: vec![Vec::new(); level + 1],
+            norm: query.norm,
+            deleted: false,
+        });
+        self.lookup.insert(email_id.clone(), node);
+
+        let Some(entry) = self.entry_point else {
+            self.entry_point = Some(node);
+            return;
+        };
+
+        let top = self.nodes[entry].level();
+        let mut current = Scored {
+            similarity: self.similarity_to(source, query, entry),
+            node: entry,
+        };
+        for layer in (level + 1..=top).rev() {
+            current = self.greedy_closest(source, query, current, layer);
+        }
+
+        let mut entry_points = vec![current];
+        for layer in (0..=level.min(top)).rev() {
+            let candidates = self.search_layer(
+                source,
+                query,
+                &entry_points,
+                self.config.ef_construction,
+                layer,
+            );
+            let selected = self.select_neighbors(source, &candidates, self.config.m);
+            self.nodes[node].neighbors[layer] = selected.iter().map(|s| s.node as u32).collect();
+
+            let max_connections = self.max_connections(layer);
+            for neighbor in &selected {
+                self.connect(source, neighbor.node, node, layer, max_connections);
+            }
+
+            entry_points = candidates;
+        }
+
+        if level > top {
+            self.entry_point = Some(node);
+        }
+    }
+
+    /// Removes an email from the index.
+    ///
+    /// The node stays in the graph as a tombstone so existing paths through
+    /// it keep working. Returns whether the email was indexed.
+    pub fn remove(&mut self, email_id: &EmailId) -> bool {
+        let Some(node) = self.lookup.remove(email_id) else {
+            return false;
+        };
+
+        self.nodes[node].deleted = true;
+        self.deleted += 1;
+        true
+    }
+
+    /// Returns whether tombstones make up enough of the graph to rebuild it.
+    pub fn needs_compaction(&self) -> bool {
+        self.nodes.len() >= MIN_COMPACTION_SIZE && self.deleted * 2 > self.nodes.len()
+    }
+
+    /// Rebuilds the graph from its live entries, dropping all tombstones.
+    pub fn rebuild<V: VectorSource>(&mut self, source: &V) {
+        let live: Vec<EmailId> = self
+            .nodes
+            .iter()
+            .filter(|node| !node.deleted)
+            .map(|node| node.email_id.clone())
+            .collect();
+
+        self.clear();
+        for email_id in &live {
+            self.insert(email_id, source);
+        }
+    }
+
+    /// Searches for the emails most similar to the query vector.
+    ///
+    /// Returns up to `limit` (EmailId, similarity) pairs sorted by
+    /// similarity in descending order.
+    pub fn search<V: VectorSource>(
+        &self,
+        query: &[f32],
+        limit: usize,
+        source: &V,
+    ) -> Vec<(EmailId, f32)> {
+        let Some(entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if limit == 0 {
+            return Vec::new();
+        }
+
+        let query = Query::new(query);
+        let mut current = Scored {
+            similarity: self.similarity_to(source, query, entry),
+            node: entry,
+        };
+        for layer in (1..=self.nodes[entry].level()).rev() {
+            current = self.greedy_closest(source, query, current, layer);
+        }
+
+        let ef = self.config.ef_search.max(limit);
+        self.search_layer(source, query, &[current], ef, 0)
+            .into_iter()
+            .filter(|scored| !self.nodes[scored.node].deleted)
+            .take(limit)
+            .map(|scored| (self.nodes[scored.node].email_id.clone(), scored.similarity))
+            .collect()
+    }
+
+    /// Writes the graph to disk.
+    ///
+    /// Vectors are not included; they are expected to be persisted with the
+    /// embeddings themselves. The file is written atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+
+        {
+            let file = File::create(&tmp_path)
+                .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+            let mut writer = BufWriter::new(file);
+
+            writer.write_all(FILE_MAGIC)?;
+            write_u32(&mut writer, FILE_VERSION)?;
+            write_u32(&mut writer, self.config.m as u32)?;
+            write_u32(&mut writer, self.config.ef_construction as u32)?;
+            write_u32(&mut writer, self.config.ef_search as u32)?;
+            writer.write_all(&self.rng_state.to_le_bytes())?;
+            let entry = self.entry_point.map(|e| e as i64).unwrap_or(-1);
+            writer.write_all(&entry.to_le_bytes())?;
+            write_u32(&mut writer, self.nodes.len() as u32)?;
+
+            for node in &self.nodes {
+                let id = node.email_id.0.as_bytes();
+                write_u32(&mut writer, id.len() as u32)?;
+                writer.write_all(id)?;
+                writer.write_all(&[node.deleted as u8])?;
+                writer.write_all(&node.norm.to_le_bytes())?;
+                write_u32(&mut writer, node.neighbors.len() as u32)?;
+                for links in &node.neighbors {
+                    write_u32(&mut writer, links.len() as u32)?;
+                    for link in links {
+                        write_u32(&mut writer, *link)?;
+                    }
+                }
+            }
+
+            writer.flush()?;
+        }
+
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Reads a graph previously written by [`HnswIndex::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FILE_MAGIC {
+            bail!("Not an HNSW index file: {}", path.display());
+        }
+        let version = read_u32(&mut reader)?;
+        if version != FILE_VERSION {
+            bail!("Unsupported HNSW index version {}", version);
+        }
+
+        let config = HnswConfig {
+            m: read_u32(&mut reader)? as usize,
+            ef_construction: read_u32(&mut reader)? as usize,
+            ef_search: read_u32(&mut reader)? as usize,
+        };
+        let rng_state = read_u64(&mut reader)?;
+        let entry = read_u64(&mut reader)? as i64;
+        let node_count = read_u32(&mut reader)? as usize;
+
+        let mut nodes = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let id_len = read_u32(&mut reader)? as usize;
+            let mut id = vec![0u8; id_len];
+            reader.read_exact(&mut id)?;
+            let email_id = EmailId(String::from_utf8(id).context("Invalid email ID")?);
+
+            let mut deleted = [0u8; 1];
+            reader.read_exact(&mut deleted)?;
+            let norm = f32::from_bits(read_u32(&mut reader)?);
+
+            let level_count = read_u32(&mut reader)? as usize;
+            let mut neighbors = Vec::with_capacity(level_count);
+            for _ in 0..level_count {
+                let link_count = read_u32(&mut reader)? as usize;
+                let mut links = Vec::with_capacity(link_count);
+                for _ in 0..link_count {
+                    let link = read_u32(&mut reader)?;
+                    if link as usize >= node_count {
+                        bail!("Corrupt HNSW index: link {} out of range", link);
+                    }
+                    links.push(link);
+                }
+                neighbors.push(links);
+            }
+
+            nodes.push(Node {
+                email_id,
+                neighbors,
+                norm,
+                deleted: deleted[0] != 0,
+            });
+        }
+
+        let entry_point = if entry < 0 {
+            None
+        } else if (entry as usize) < node_count {
+            Some(entry as usize)
+        } else {
+            bail!("Corrupt HNSW index: entry point {} out of range", entry);
+        };
+
+        let lookup = nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.deleted)
+            .map(|(i, node)| (node.email_id.clone(), i))
+            .collect();
+        let deleted = nodes.iter().filter(|node| node.deleted).count();
+
+        Ok(Self {
+            config,
+            nodes,
+            lookup,
+            entry_point,
+            deleted,
+            rng_state,
+        })
+    }
+
+    /// Greedily walks a layer towards the query, returning the closest node.
+    fn greedy_closest<V: VectorSource>(
+        &self,
+        source: &V,
+        query: Query<'_>,
+        mut current: Scored,
+        layer: usize,
+    ) -> Scored {
+        loop {
+            let mut improved = false;
+            for &neighbor in self.neighbors(current.node, layer) {
+                let similarity = self.similarity_to(source, query, neighbor as usize);
+                if similarity > current.similarity {
+                    current = Scored {
+                        similarity,
+                        node: neighbor as usize,
+                    };
+                    improved = true;
+                }
+            }
+            if !improved {
+                return current;
+            }
+        }
+    }
+
+    /// Beam search within a single layer.
+    ///
+    /// Returns up to `ef` nodes sorted by similarity in descending order.
+    fn search_layer<V: VectorSource>(
+        &self,
+        source: &V,
+        query: Query<'_>,
+        entry_points: &[Scored],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited = vec![false; self.nodes.len()];
+        for entry in entry_points {
+            visited[entry.node] = true;
+        }
+        let mut candidates: BinaryHeap<Scored> = entry_points.iter().copied().collect();
+        let mut results: BinaryHeap<Reverse<Scored>> =
+            entry_points.iter().copied().map(Reverse).collect();
+        while results.len() > ef {
+            results.pop();
+        }
+
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map(|r| r.0.similarity);
+            if results.len() >= ef && worst.is_some_and(|w| candidate.similarity < w) {
+                break;
+            }
+
+            for &neighbor in self.neighbors(candidate.node, layer) {
+                let neighbor = neighbor as usize;
+                if std::mem::replace(&mut visited[neighbor], true) {
+                    continue;
+                }
+
+                // Tombstones have no vector to score; expand through them at
+                // the score of the node that led here, but never keep them.
+                if self.nodes[neighbor].deleted {
+                    candidates.push(Scored {
+                        similarity: candidate.similarity,
+                        node: neighbor,
+                    });
+                    continue;
+                }
+
+                let similarity = self.similarity_to(source, query, neighbor);
+                let worst = results.peek().map(|r| r.0.similarity);
+                if results.len() < ef || worst.is_some_and(|w| similarity > w) {
+                    let scored = Scored {
+                        similarity,
+                        node: neighbor,
+                    };
+                    candidates.push(scored);
+                    results.push(Reverse(scored));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+
+        let mut found: Vec<Scored> = results.into_iter().map(|r| r.0).collect();
+        found.sort_by(|a, b| b.cmp(a));
+        found
+    }
+
+    /// Picks up to `m` diverse neighbors from candidates sorted best-first.
+    ///
+    /// A candidate is skipped when it is closer to an already selected
+    /// neighbor than to the base node; skipped candidates backfill any
+    /// remaining slots.
+    fn select_neighbors<V: VectorSource>(
+        &self,
+        source: &V,
+        candidates: &[Scored],
+        m: usize,
+    ) -> Vec<Scored> {
+        let mut selected: Vec<Scored> = Vec::with_capacity(m);
+        let mut skipped = Vec::new();
+
+        for &candidate in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let Some(vector) = self.vector(source, candidate.node) else {
+                skipped.push(candidate);
+                continue;
+            };
+            let query = Query {
+                values: &vector,
+                norm: self.nodes[candidate.node].norm,
+            };
+            let diverse = selected.iter().all(|chosen| {
+                self.similarity_to(source, query, chosen.node) < candidate.similarity
+            });
+            if diverse {
+                selected.push(candidate);
+            } else {
+                skipped.push(candidate);
+            }
+        }
+
+        for candidate in skipped {
+            if selected.len() >= m {
+                break;
+            }
+            selected.push(candidate);
+        }
+
+        selected
+    }
+
+    /// Adds a link from `from` to `to`, pruning `from`'s links if needed.
+    fn connect<V: VectorSource>(
+        &mut self,
+        source: &V,
+        from: usize,
+        to: usize,
+        layer: usize,
+        max_connections: usize,
+    ) {
+        {
+            let links = &mut self.nodes[from].neighbors[layer];
+            if links.contains(&(to as u32)) {
+                return;
+            }
+            links.push(to as u32);
+            if links.len() <= max_connections {
+                return;
+            }
+        }
+
+        let Some(vector) = self.vector(source, from) else {
+            return;
+        };
+        let base = Query {
+            values: &vector,
+            norm: self.nodes[from].norm,
+        };
+        let mut scored: Vec<Scored> = self.nodes[from].neighbors[layer]
+            .iter()
+            .map(|&link| Scored {
+                similarity: self.similarity_to(source, base, link as usize),
+                node: link as usize,
+            })
+            .collect();
+        scored.sort_by(|a, b| b.cmp(a));
+
+        let kept = self.select_neighbors(source, &scored, max_connections);
+        self.nodes[from].neighbors[layer] = kept.iter().map(|s| s.node as u32).collect();
+    }
+
+    fn neighbors(&self, node: usize, layer: usize) -> &[u32] {
+        self.nodes[node]
+            .neighbors
+            .get(layer)
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    fn vector<'a, V: VectorSource>(&self, source: &'a V, node: usize) -> Option<Cow<'a, [f32]>> {
+        source.vector(&self.nodes[node].email_id)
+    }
+
+    /// Similarity between the query and a node; nodes without a vector
+    /// (tombstones) sort below every real match.
+    fn similarity_to<V: VectorSource>(&self, source: &V, query: Query<'_>, node: usize) -> f32 {
+        let Some(dot) = source.dot(query.values, &self.nodes[node].email_id) else {
+            return -2.0;
+        };
+        let norm = self.nodes[node].norm;
+        if query.norm == 0.0 || norm == 0.0 {
+            return 0.0;
+        }
+
+        dot / (query.norm * norm)
+    }
+
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.config.m * 2
+        } else {
+            self.config.m
+        }
+    }
+
+    /// Draws a node level from the usual exponentially decaying distribution.
+    fn random_level(&mut self) -> usize {
+        let ml = 1.0 / (self.config.m.max(2) as f64).ln();
+        let level = (-self.next_random().ln() * ml).floor() as usize;
+        level.min(MAX_LEVEL)
+    }
+
+    /// Xorshift64* generator, returning a value in (0, 1).
+    ///
+    /// Deterministic so that rebuilding the same data yields the same graph.
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng_state = x;
+        let value = x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        (value as f64 + 0.5) / (1u64 << 53) as f64
+    }
+}
+
+fn l2_norm(values: &[f32]) -> f32 {
+    dot(values, values).sqrt()
+}
+
+/// Dot product using independent lanes so the compiler can vectorize it.
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    const LANES: usize = 8;
+    let mut lanes = [0.0f32; LANES];
+
+    let chunks_a = a.chunks_exact(LANES);
+    let chunks_b = b.chunks_exact(LANES);
+    let tail: f32 = chunks_a
+        .remainder()
+        .iter()
+        .zip(chunks_b.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+
+    for (x, y) in chunks_a.zip(chunks_b) {
+        for i in 0..LANES {
+            lanes[i] += x[i] * y[i];
+        }
+    }
+
+    lanes.iter().sum::<f32>() + tail
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> Result<()> {
+    writer.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random vectors for tests.
+    fn random_vectors(count: usize, dim: usize, seed: u64) -> HashMap<EmailId, Embedding> {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f32 / (1u64 << 53) as f32 * 2.0 - 1.0
+        };
+
+        (0..count)
+            .map(|i| {
+                let values = (0..dim).map(|_| next()).collect();
+                (
+                    EmailId::from(format!("email-{}", i)),
+                    Embedding::new(values),
+                )
+            })
+            .collect()
+    }
+
+    fn exact_top_k(vectors: &HashMap<EmailId, Embedding>, query: &[f32], k: usize) -> Vec<EmailId> {
+        let mut scored: Vec<(EmailId, f32)> = vectors
+            .iter()
+            .map(|(id, e)| {
+                let similarity = Embedding::new(query.to_vec()).cosine_similarity(e);
+                (id.clone(), similarity)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        scored.into_iter().take(k).map(|(id, _)| id).collect()
+    }
+
+    fn build(vectors: &HashMap<EmailId, Embedding>) -> HnswIndex {
+        let mut index = HnswIndex::new(HnswConfig::default());
+        let mut ids: Vec<&EmailId> = vectors.keys().collect();
+        ids.sort_by(|a, b| a.0.cmp(&b.0));
+        for id in ids {
+            index.insert(id, vectors);
+        }
+        index
+    }
+
+    #[test]
+    fn empty_index_returns_nothing() {
+        let index = HnswIndex::new(HnswConfig::default());
+        let vectors: HashMap<EmailId, Embedding> = HashMap::new();
+        assert!(index.search(&[1.0, 0.0], 10, &vectors).is_empty());
+    }
+
+    #[test]
+    fn finds_exact_match() {
+        let vectors = random_vectors(500, 16, 7);
+        let index = build(&vectors);
+
+        let target = EmailId::from("email-42");
+        let query = vectors[&target].values.clone();
+        let results = index.search(&query, 1, &vectors);
+
+        assert_eq!(results[0].0, target);
+        assert!((results[0].1 - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn recall_against_brute_force() {
+        let vectors = random_vectors(2000, 32, 11);
+        let index = build(&vectors);
+        let queries = random_vectors(50, 32, 99);
+
+        let mut found = 0;
+        for query in queries.values() {
+            let expected = exact_top_k(&vectors, &query.values, 10);
+            let results = index.search(&query.values, 10, &vectors);
+            found += results
+                .iter()
+                .filter(|(id, _)| expected.contains(id))
+                .count();
+        }
+
+        let recall = found as f32 / (50 * 10) as f32;
+        assert!(recall >= 0.9, "recall was {}", recall);
+    }
+
+    #[test]
+    fn removed_entries_are_not_returned() {
+        let mut vectors = random_vectors(300, 8, 3);
+        let mut index = build(&vectors);
+
+        let target = EmailId::from("email-10");
+        let query = vectors[&target].values.clone();
+        index.remove(&target);
+        vectors.remove(&target);
+
+        let results = index.search(&query, 10, &vectors);
+        assert!(results.iter().all(|(id, _)| *id != target));
+        assert_eq!(results.len(), 10);
+        assert_eq!(index.len(), 299);
+    }
+
+    #[test]
+    fn tombstones_keep_the_graph_navigable() {
+        let mut vectors = random_vectors(2000, 32, 17);
+        let mut index = build(&vectors);
+        for i in (0..2000).step_by(5).chain((1..2000).step_by(5)) {
+            let id = EmailId::from(format!("email-{}", i));
+            index.remove(&id);
+            vectors.remove(&id);
+        }
+        assert!(!index.needs_compaction());
+
+        let queries = random_vectors(50, 32, 23);
+        let mut found = 0;
+        for query in queries.values() {
+            let expected = exact_top_k(&vectors, &query.values, 10);
+            let results = index.search(&query.values, 10, &vectors);
+            assert_eq!(results.len(), 10);
+            found += results
+                .iter()
+                .filter(|(id, _)| expected.contains(id))
+                .count();
+        }
+
+        let recall = found as f32 / (50 * 10) as f32;
+        assert!(recall >= 0.9, "recall was {}", recall);
+    }
+
+    #[test]
+    fn compaction_drops_tombstones() {
+        let mut vectors = random_vectors(200, 8, 5);
+        let mut index = build(&vectors);
+
+        for i in 0..150 {
+            let id = EmailId::from(format!("email-{}", i));
+            index.remove(&id);
+            vectors.remove(&id);
+        }
+        assert!(index.needs_compaction());
+
+        index.rebuild(&vectors);
+        assert!(!index.needs_compaction());
+        assert_eq!(index.len(), 50);
+        assert_eq!(index.nodes.len(), 50);
+
+        let query = vectors[&EmailId::from("email-160")].values.clone();
+        let results = index.search(&query, 1, &vectors);
+        assert_eq!(results[0].0, EmailId::from("email-160"));
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let vectors = random_vectors(300, 8, 13);
+        let mut index = build(&vectors);
+        index.remove(&EmailId::from("email-0"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.hnsw");
+        index.save(&path).unwrap();
+
+        let loaded = HnswIndex::load(&path).unwrap();
+        assert_eq!(loaded.len(), index.len());
+        assert_eq!(loaded.config(), index.config());
+        assert!(!loaded.contains(&EmailId::from("email-0")));
+
+        let query = vectors[&EmailId::from("email-5")].values.clone();
+        assert_eq!(
+            loaded.search(&query, 5, &vectors),
+            index.search(&query, 5, &vectors)
+        );
+    }
+
+    #[test]
+    fn load_rejects_foreign_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("garbage.hnsw");
+        std::fs::write(&path, b"not an index").unwrap();
+        assert!(HnswIndex::load(&path).is_err());
+    }
+}
@@ -7,6 +7,10 @@
 //!
 //! - [`EmbeddingEngine`] - Generates embeddings using local transformer models
 //! - [`VectorStore`] - Stores and searches embeddings by similarity
+//! - [`split_passages`] - Splits long emails into overlapping passages
+//! - [`HnswIndex`] - Approximate nearest-neighbor index used by large stores
+//! - [`QuantizedVector`] - Compact int8/binary embeddings for large mailboxes
+//! - [`CrossEncoder`] - Re-scores the top search hits against the query
 //! - [`Embedding`] - A vector representation of text semantics
 //!
 //! # Example
@@ -25,10 +29,24 @@
 //! let results = engine.search(&query, 10)?;
 //! ```
 
+mod chunking;
 mod engine;
+mod hnsw;
 mod models;
+mod quantization;
+mod reranker;
+mod vector_file;
 mod vector_store;
 
+pub use chunking::{split_passages, strip_quotes_and_signature, word_spans};
 pub use engine::{Embedding, EmbeddingConfig, EmbeddingEngine};
-pub use models::{DownloadStatus, ModelInfo, ModelRegistry, ModelType};
-pub use vector_store::VectorStore;
+pub use hnsw::{HnswConfig, HnswIndex, VectorSource};
+pub use models::{
+    DownloadStatus, ModelError, ModelFile, ModelFiles, ModelInfo, ModelRegistry, ModelType,
+};
+pub use quantization::{Quantization, QuantizationConfig, QuantizedVector};
+pub use reranker::{CrossEncoder, RerankerConfig};
+pub use vector_file::VectorFile;
+pub use vector_store::{
+    PassageEmbedding, PassageMatch, VectorStore, DEFAULT_EXACT_SEARCH_THRESHOLD,
+};
@@ -2,8 +2,25 @@
 //!
 //! This module defines the available embedding models and their configurations
 //! for generating text embeddings used in semantic search.
+//!
+//! Models are loaded from a directory holding `config.json`,
+//! `tokenizer.json` and `model.safetensors`, either downloaded from Hugging
+//! Face, shipped as a bundled model pack, or provided by the user. Files are
+//! checked against the checksums pinned in [`ModelInfo`] before loading.
 
+use ring::digest::{Context, SHA256};
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Model configuration file name.
+pub const CONFIG_FILE: &str = "config.json";
+/// Tokenizer file name.
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+/// Model weights file name.
+pub const WEIGHTS_FILE: &str = "model.safetensors";
 
 /// Available embedding model types.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
@@ -71,10 +88,32 @@ impl ModelType {
             _ => None,
         }
     }
+
+    /// Returns the model type for a Hugging Face model ID.
+    pub fn from_hf_model_id(model_id: &str) -> Option<Self> {
+        [
+            Self::MiniLm,
+            Self::AllMiniLmL6V2,
+            Self::BgeSmall,
+            Self::E5Small,
+        ]
+        .into_iter()
+        .find(|m| m.hf_model_id() == model_id)
+    }
+
+    /// Returns the directory name of this model inside a model pack.
+    pub fn pack_dir_name(&self) -> &'static str {
+        match self {
+            Self::MiniLm => "paraphrase-minilm-l6-v2",
+            Self::AllMiniLmL6V2 => "all-minilm-l6-v2",
+            Self::BgeSmall => "bge-small-en-v1.5",
+            Self::E5Small => "e5-small-v2",
+        }
+    }
 }
 
 /// Model download status.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DownloadStatus {
     /// Model is not downloaded.
     NotDownloaded,
@@ -82,8 +121,84 @@ pub enum DownloadStatus {
     Downloading,
     /// Model is downloaded and ready.
     Ready,
-    /// Download failed.
-    Failed,
+    /// Download or loading failed, with the reason shown to the user.
+    Failed(String),
+}
+
+impl DownloadStatus {
+    /// Returns a short description for the settings screen.
+    pub fn label(&self) -> String {
+        match self {
+            Self::NotDownloaded => "Not downloaded".to_string(),
+            Self::Downloading => "Downloading...".to_string(),
+            Self::Ready => "Ready".to_string(),
+            Self::Failed(reason) => format!("Failed: {}", reason),
+        }
+    }
+
+    /// Returns whether loading the model failed.
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(_))
+    }
+}
+
+/// Errors from locating and verifying model files.
+#[derive(Debug, Error)]
+pub enum ModelError {
+    /// A required file is missing from the model directory.
+    #[error("model file not found: {}", .0.display())]
+    MissingFile(PathBuf),
+
+    /// A file does not match its pinned checksum.
+    #[error("checksum mismatch for {file}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        /// File name within the model directory.
+        file: String,
+        /// Pinned SHA-256 digest.
+        expected: String,
+        /// Digest of the file on disk.
+        actual: String,
+    },
+
+    /// A model file could not be read.
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        /// Path of the unreadable file.
+        path: PathBuf,
+        /// Underlying I/O error.
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// A file that is part of a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelFile {
+    /// File name within the model directory.
+    pub name: String,
+    /// Expected lowercase hex SHA-256 digest, if pinned.
+    pub sha256: Option<String>,
+}
+
+impl ModelFile {
+    /// Creates an entry for a file without a pinned checksum.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            sha256: None,
+        }
+    }
+}
+
+/// Paths to the files needed to load a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelFiles {
+    /// Model configuration.
+    pub config: PathBuf,
+    /// Tokenizer definition.
+    pub tokenizer: PathBuf,
+    /// Model weights.
+    pub weights: PathBuf,
 }
 
 /// Information about a model.
@@ -99,6 +214,8 @@ pub struct ModelInfo {
     pub size_bytes: u64,
     /// Download status.
     pub status: DownloadStatus,
+    /// Files that make up the model.
+    pub files: Vec<ModelFile>,
 }
 
 impl ModelInfo {
@@ -133,7 +250,25 @@ impl ModelInfo {
             description: description.to_string(),
             size_bytes,
             status: DownloadStatus::NotDownloaded,
+            files: vec![
+                ModelFile::new(CONFIG_FILE),
+                ModelFile::new(TOKENIZER_FILE),
+                ModelFile::new(WEIGHTS_FILE),
+            ],
+        }
+    }
+
+    /// Pins the SHA-256 digest of a model file.
+    pub fn with_checksum(mut self, name: &str, sha256: impl Into<String>) -> Self {
+        let sha256 = Some(sha256.into().to_lowercase());
+        match self.files.iter_mut().find(|f| f.name == name) {
+            Some(file) => file.sha256 = sha256,
+            None => self.files.push(ModelFile {
+                name: name.to_string(),
+                sha256,
+            }),
         }
+        self
     }
 
     /// Returns the size as a human-readable string.
@@ -141,6 +276,74 @@ impl ModelInfo {
         let mb = self.size_bytes as f64 / 1_000_000.0;
         format!("{:.1} MB", mb)
     }
+
+    /// Checks a file against its pinned checksum.
+    ///
+    /// Files without a pinned checksum are accepted as-is.
+    pub fn verify_file(&self, name: &str, path: &Path) -> Result<(), ModelError> {
+        let Some(expected) = self
+            .files
+            .iter()
+            .find(|f| f.name == name)
+            .and_then(|f| f.sha256.as_ref())
+        else {
+            return Ok(());
+        };
+
+        let actual = sha256_file(path)?;
+        if actual != *expected {
+            return Err(ModelError::ChecksumMismatch {
+                file: name.to_string(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    /// Locates and verifies the model files in a local directory.
+    pub fn load_from_dir(&self, dir: &Path) -> Result<ModelFiles, ModelError> {
+        let locate = |name: &str| -> Result<PathBuf, ModelError> {
+            let path = dir.join(name);
+            if !path.is_file() {
+                return Err(ModelError::MissingFile(path));
+            }
+            self.verify_file(name, &path)?;
+            Ok(path)
+        };
+
+        Ok(ModelFiles {
+            config: locate(CONFIG_FILE)?,
+            tokenizer: locate(TOKENIZER_FILE)?,
+            weights: locate(WEIGHTS_FILE)?,
+        })
+    }
+}
+
+/// Computes the lowercase hex SHA-256 digest of a file.
+fn sha256_file(path: &Path) -> Result<String, ModelError> {
+    let io_error = |source| ModelError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let mut file = File::open(path).map_err(io_error)?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(io_error)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
 }
 
 /// Model registry for managing available models.
@@ -247,6 +450,69 @@ mod tests {
         assert_eq!(ready.len(), 1);
     }
 
+    #[test]
+    fn failed_status_carries_reason() {
+        let status = DownloadStatus::Failed("checksum mismatch".to_string());
+        assert!(status.is_failed());
+        assert_eq!(status.label(), "Failed: checksum mismatch");
+        assert!(!DownloadStatus::Ready.is_failed());
+    }
+
+    #[test]
+    fn model_type_from_hf_id() {
+        assert_eq!(
+            ModelType::from_hf_model_id("BAAI/bge-small-en-v1.5"),
+            Some(ModelType::BgeSmall)
+        );
+        assert_eq!(ModelType::from_hf_model_id("unknown/model"), None);
+    }
+
+    fn write_model_dir(dir: &Path) {
+        std::fs::write(dir.join(CONFIG_FILE), "{}").unwrap();
+        std::fs::write(dir.join(TOKENIZER_FILE), "{}").unwrap();
+        std::fs::write(dir.join(WEIGHTS_FILE), "weights").unwrap();
+    }
+
+    #[test]
+    fn load_from_dir_finds_files() {
+        let dir = tempfile::tempdir().unwrap();
+        write_model_dir(dir.path());
+
+        let info = ModelInfo::for_model(ModelType::AllMiniLmL6V2);
+        let files = info.load_from_dir(dir.path()).unwrap();
+        assert_eq!(files.weights, dir.path().join(WEIGHTS_FILE));
+    }
+
+    #[test]
+    fn load_from_dir_reports_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(CONFIG_FILE), "{}").unwrap();
+
+        let info = ModelInfo::for_model(ModelType::AllMiniLmL6V2);
+        let err = info.load_from_dir(dir.path()).unwrap_err();
+        assert!(matches!(err, ModelError::MissingFile(path) if path.ends_with(TOKENIZER_FILE)));
+    }
+
+    #[test]
+    fn checksums_are_verified() {
+        let dir = tempfile::tempdir().unwrap();
+        write_model_dir(dir.path());
+
+        // SHA-256 of "weights".
+        let digest = "9a129038d9a00aed0cf6a7ea059ca50a813449061ab87848cf1a13eafdf33b2c";
+        let info = ModelInfo::for_model(ModelType::AllMiniLmL6V2)
+            .with_checksum(WEIGHTS_FILE, digest.to_uppercase());
+        assert!(info.load_from_dir(dir.path()).is_ok());
+
+        std::fs::write(dir.path().join(WEIGHTS_FILE), "tampered").unwrap();
+        let err = info.load_from_dir(dir.path()).unwrap_err();
+        assert!(matches!(
+            err,
+            ModelError::ChecksumMismatch { ref file, ref expected, .. }
+                if file == WEIGHTS_FILE && expected == digest
+        ));
+    }
+
     #[test]
     fn model_serialization() {
         let model = ModelType::BgeSmall;
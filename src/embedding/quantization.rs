@@ -0,0 +1,313 @@
+//! Compact embedding representations.
+//!
+//! Full-precision 384-dimension embeddings cost 1.5 KB each. Scalar int8
+//! quantization cuts that by 4x and binary quantization by 32x, at the cost
+//! of approximate similarity scores. Quantized vectors keep a per-vector
+//! scale factor so dot products stay on the original scale.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::domain::EmailId;
+use crate::embedding::hnsw::VectorSource;
+
+/// How embeddings are stored in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// Full-precision f32 values.
+    #[default]
+    None,
+    /// One signed byte per dimension with a per-vector scale.
+    Int8,
+    /// One bit per dimension (the sign) with a per-vector scale.
+    Binary,
+}
+
+/// Configuration for quantized embedding storage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuantizationConfig {
+    /// In-memory representation of embeddings.
+    pub mode: Quantization,
+    /// How many candidates per requested result are re-scored at full precision.
+    pub rescore_multiplier: usize,
+    /// File holding full-precision vectors for re-scoring.
+    ///
+    /// Without it, results are ranked by their quantized scores only.
+    pub full_precision_path: Option<PathBuf>,
+}
+
+impl Default for QuantizationConfig {
+    fn default() -> Self {
+        Self::new(Quantization::None)
+    }
+}
+
+impl QuantizationConfig {
+    /// Creates a configuration for the given mode with default re-scoring.
+    pub fn new(mode: Quantization) -> Self {
+        Self {
+            mode,
+            rescore_multiplier: 4,
+            full_precision_path: None,
+        }
+    }
+
+    /// Sets the file used for full-precision re-scoring.
+    pub fn with_full_precision_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.full_precision_path = Some(path.into());
+        self
+    }
+
+    /// Returns whether vectors are quantized in memory.
+    pub fn is_enabled(&self) -> bool {
+        self.mode != Quantization::None
+    }
+}
+
+/// A quantized embedding vector.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantizedVector {
+    /// Scalar int8 quantization: `value ~= values[i] * scale`.
+    Int8 {
+        /// Quantized components.
+        values: Vec<i8>,
+        /// Scale mapping the int8 range back to the original values.
+        scale: f32,
+    },
+    /// Binary quantization: `value ~= +/-scale` depending on the bit.
+    Binary {
+        /// Sign bits, least significant bit first.
+        bits: Vec<u64>,
+        /// Number of dimensions encoded in `bits`.
+        dimension: usize,
+        /// Mean absolute value of the original components.
+        scale: f32,
+    },
+}
+
+impl QuantizedVector {
+    /// Quantizes a vector, returning `None` for [`Quantization::None`].
+    pub fn quantize(values: &[f32], mode: Quantization) -> Option<Self> {
+        match mode {
+            Quantization::None => None,
+            Quantization::Int8 => {
+                let max_abs = values.iter().fold(0.0f32, |max, v| max.max(v.abs()));
+                let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 0.0 };
+                let values = values
+                    .iter()
+                    .map(|v| {
+                        if scale > 0.0 {
+                            (v / scale).round().clamp(-127.0, 127.0) as i8
+                        } else {
+                            0
+                        }
+                    })
+                    .collect();
+                Some(Self::Int8 { values, scale })
+            }
+            Quantization::Binary => {
+                let mut bits = vec![0u64; values.len().div_ceil(64)];
+                for (i, v) in values.iter().enumerate() {
+                    if *v >= 0.0 {
+                        bits[i / 64] |= 1 << (i % 64);
+                    }
+                }
+                let scale = if values.is_empty() {
+                    0.0
+                } else {
+                    values.iter().map(|v| v.abs()).sum::<f32>() / values.len() as f32
+                };
+                Some(Self::Binary {
+                    bits,
+                    dimension: values.len(),
+                    scale,
+                })
+            }
+        }
+    }
+
+    /// Returns the number of dimensions.
+    pub fn dimension(&self) -> usize {
+        match self {
+            Self::Int8 { values, .. } => values.len(),
+            Self::Binary { dimension, .. } => *dimension,
+        }
+    }
+
+    /// Reconstructs an approximation of the original vector.
+    pub fn dequantize(&self) -> Vec<f32> {
+        match self {
+            Self::Int8 { values, scale } => values.iter().map(|v| *v as f32 * scale).collect(),
+            Self::Binary {
+                bits,
+                dimension,
+                scale,
+            } => (0..*dimension)
+                .map(|i| if Self::bit(bits, i) { *scale } else { -scale })
+                .collect(),
+        }
+    }
+
+    /// Returns the L2 norm of the dequantized vector.
+    pub fn norm(&self) -> f32 {
+        match self {
+            Self::Int8 { values, scale } => {
+                let sum: f32 = values.iter().map(|v| (*v as f32) * (*v as f32)).sum();
+                sum.sqrt() * scale
+            }
+            Self::Binary {
+                dimension, scale, ..
+            } => (*dimension as f32).sqrt() * scale,
+        }
+    }
+
+    /// Approximate dot product with a full-precision query.
+    ///
+    /// Returns 0.0 if the dimensions differ.
+    pub fn dot(&self, query: &[f32]) -> f32 {
+        if query.len() != self.dimension() {
+            return 0.0;
+        }
+
+        match self {
+            Self::Int8 { values, scale } => {
+                let sum: f32 = query.iter().zip(values).map(|(q, v)| q * *v as f32).sum();
+                sum * scale
+            }
+            Self::Binary { bits, scale, .. } => {
+                let mut positive = 0.0f32;
+                let mut total = 0.0f32;
+                for (i, q) in query.iter().enumerate() {
+                    total += q;
+                    if Self::bit(bits, i) {
+                        positive += q;
+                    }
+                }
+                (2.0 * positive - total) * scale
+            }
+        }
+    }
+
+    /// Returns the approximate heap memory used by this vector in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        match self {
+            Self::Int8 { values, .. } => values.len(),
+            Self::Binary { bits, .. } => bits.len() * std::mem::size_of::<u64>(),
+        }
+    }
+
+    fn bit(bits: &[u64], i: usize) -> bool {
+        bits[i / 64] & (1 << (i % 64)) != 0
+    }
+}
+
+impl VectorSource for HashMap<EmailId, QuantizedVector> {
+    fn vector(&self, email_id: &EmailId) -> Option<Cow<'_, [f32]>> {
+        self.get(email_id)
+            .map(|vector| Cow::Owned(vector.dequantize()))
+    }
+
+    fn dot(&self, query: &[f32], email_id: &EmailId) -> Option<f32> {
+        self.get(email_id).map(|vector| vector.dot(query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::hnsw::dot;
+
+    fn sample() -> Vec<f32> {
+        vec![0.5, -0.25, 0.1, -0.9, 0.0, 0.33, -0.02, 0.7]
+    }
+
+    #[test]
+    fn none_mode_does_not_quantize() {
+        assert!(QuantizedVector::quantize(&sample(), Quantization::None).is_none());
+    }
+
+    #[test]
+    fn int8_round_trip_is_close() {
+        let original = sample();
+        let quantized = QuantizedVector::quantize(&original, Quantization::Int8).unwrap();
+        let restored = quantized.dequantize();
+
+        assert_eq!(quantized.dimension(), original.len());
+        for (a, b) in original.iter().zip(&restored) {
+            assert!((a - b).abs() < 0.01, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn int8_dot_approximates_full_precision() {
+        let original = sample();
+        let query: Vec<f32> = original.iter().rev().copied().collect();
+        let quantized = QuantizedVector::quantize(&original, Quantization::Int8).unwrap();
+
+        let exact = dot(&query, &original);
+        assert!((quantized.dot(&query) - exact).abs() < 0.01);
+    }
+
+    #[test]
+    fn binary_keeps_signs() {
+        let original = sample();
+        let quantized = QuantizedVector::quantize(&original, Quantization::Binary).unwrap();
+        let restored = quantized.dequantize();
+
+        for (a, b) in original.iter().zip(&restored) {
+            assert_eq!(*a >= 0.0, *b > 0.0);
+        }
+    }
+
+    #[test]
+    fn binary_dot_matches_dequantized_dot() {
+        let original: Vec<f32> = (0..100).map(|i| ((i * 7) % 13) as f32 - 6.0).collect();
+        let query: Vec<f32> = (0..100).map(|i| ((i * 3) % 11) as f32 - 5.0).collect();
+        let quantized = QuantizedVector::quantize(&original, Quantization::Binary).unwrap();
+
+        let expected = dot(&query, &quantized.dequantize());
+        assert!((quantized.dot(&query) - expected).abs() < 0.001);
+    }
+
+    #[test]
+    fn norm_matches_dequantized_norm() {
+        for mode in [Quantization::Int8, Quantization::Binary] {
+            let quantized = QuantizedVector::quantize(&sample(), mode).unwrap();
+            let restored = quantized.dequantize();
+            let expected = dot(&restored, &restored).sqrt();
+            assert!((quantized.norm() - expected).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn memory_footprint() {
+        let original = vec![0.1f32; 384];
+        let int8 = QuantizedVector::quantize(&original, Quantization::Int8).unwrap();
+        let binary = QuantizedVector::quantize(&original, Quantization::Binary).unwrap();
+
+        assert_eq!(int8.memory_bytes(), 384);
+        assert_eq!(binary.memory_bytes(), 48);
+    }
+
+    #[test]
+    fn mismatched_dimensions_score_zero() {
+        let quantized = QuantizedVector::quantize(&sample(), Quantization::Int8).unwrap();
+        assert_eq!(quantized.dot(&[1.0, 2.0]), 0.0);
+    }
+
+    #[test]
+    fn zero_vector_quantizes_safely() {
+        let zeros = vec![0.0f32; 16];
+        let quantized = QuantizedVector::quantize(&zeros, Quantization::Int8).unwrap();
+        assert!(quantized.dequantize().iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn quantization_serialization() {
+        let json = serde_json::to_string(&Quantization::Int8).unwrap();
+        assert_eq!(json, "\"int8\"");
+    }
+}
@@ -0,0 +1,188 @@
+//! Cross-encoder re-ranking of search results.
+//!
+//! Bi-encoder embeddings compare a query and an email that were embedded
+//! separately, which misses how the words of one relate to the other. A
+//! cross-encoder reads the query and a candidate together and predicts their
+//! relevance directly. It is too slow to run over a whole mailbox, so it only
+//! rescores the top hits of a search.
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use candle_core::{Device, Tensor};
+use candle_nn::{linear, Linear, Module};
+use candle_transformers::models::bert::{BertModel, Config as BertConfig};
+use tokenizers::{Tokenizer, TruncationParams};
+
+use crate::embedding::engine::{download_model_files, load_weights};
+use crate::embedding::{ModelInfo, ModelType};
+
+/// Configuration for the cross-encoder.
+#[derive(Debug, Clone)]
+pub struct RerankerConfig {
+    /// HuggingFace model ID of a BERT sequence classification model with a
+    /// single relevance output, as trained on MS MARCO.
+    pub model_id: String,
+    /// Local directory containing the model files. Takes precedence over
+    /// downloading.
+    pub model_path: Option<PathBuf>,
+    /// Whether the model may be downloaded when no local copy exists.
+    pub allow_download: bool,
+    /// Maximum tokens for a query and candidate together.
+    pub max_seq_length: usize,
+}
+
+impl Default for RerankerConfig {
+    fn default() -> Self {
+        Self {
+            model_id: "cross-encoder/ms-marco-MiniLM-L-6-v2".to_string(),
+            model_path: None,
+            allow_download: true,
+            max_seq_length: 512,
+        }
+    }
+}
+
+/// A BERT cross-encoder that scores query/passage pairs.
+pub struct CrossEncoder {
+    model: BertModel,
+    pooler: Linear,
+    classifier: Linear,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl CrossEncoder {
+    /// Loads the model from the configured directory or HuggingFace Hub.
+    pub fn load(config: &RerankerConfig) -> Result<Self> {
+        // Cross-encoders are not in the embedding model registry, so there
+        // are no pinned checksums.
+        let info = ModelInfo {
+            files: Vec::new(),
+            ..ModelInfo::for_model(ModelType::default())
+        };
+        let files = match &config.model_path {
+            Some(dir) => info
+                .load_from_dir(dir)
+                .context("Invalid local re-ranker model directory")?,
+            None if config.allow_download => download_model_files(&config.model_id, &info)?,
+            None => anyhow::bail!(
+                "Re-ranker model {} is not available offline and downloads are disabled",
+                config.model_id
+            ),
+        };
+
+        let config_str =
+            std::fs::read_to_string(&files.config).context("Failed to read config.json")?;
+        let bert_config: BertConfig =
+            serde_json::from_str(&config_str).context("Failed to parse config.json")?;
+
+        let mut tokenizer = Tokenizer::from_file(&files.tokenizer)
+            .map_err(|e| anyhow::anyhow!("Failed to load tokenizer: {}", e))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_seq_length,
+                ..Default::default()
+            }))
+            .map_err(|e| anyhow::anyhow!("Failed to configure tokenizer: {}", e))?;
+
+        let device = Device::Cpu;
+        let vb = load_weights(&files.weights, &device)?;
+        let hidden = bert_config.hidden_size;
+        let model =
+            BertModel::load(vb.pp("bert"), &bert_config).context("Failed to build BERT model")?;
+        let pooler =
+            linear(hidden, hidden, vb.pp("bert.pooler.dense")).context("Model has no pooler")?;
+        let classifier =
+            linear(hidden, 1, vb.pp("classifier")).context("Model has no relevance head")?;
+
+        Ok(Self {
+            model,
+            pooler,
+            classifier,
+            tokenizer,
+            device,
+        })
+    }
+
+    /// Scores how relevant each passage is to the query, from 0.0 to 1.0.
+    ///
+    /// Passages are scored in order until `deadline`; the returned scores
+    /// cover only the passages scored in time.
+    pub fn score(&self, query: &str, passages: &[&str], deadline: Instant) -> Result<Vec<f32>> {
+        let mut scores = Vec::with_capacity(passages.len());
+        for passage in passages {
+            if Instant::now() >= deadline {
+                break;
+            }
+            scores.push(sigmoid(self.logit(query, passage)?));
+        }
+        Ok(scores)
+    }
+
+    /// Runs the model on one query/passage pair.
+    fn logit(&self, query: &str, passage: &str) -> Result<f32> {
+        let encoding = self
+            .tokenizer
+            .encode((query, passage), true)
+            .map_err(|e| anyhow::anyhow!("Tokenization failed: {}", e))?;
+
+        let token_ids = Tensor::new(encoding.get_ids(), &self.device)?.unsqueeze(0)?;
+        let type_ids = Tensor::new(encoding.get_type_ids(), &self.device)?.unsqueeze(0)?;
+        let attention_mask =
+            Tensor::new(encoding.get_attention_mask(), &self.device)?.unsqueeze(0)?;
+
+        let output = self
+            .model
+            .forward(&token_ids, &type_ids, Some(&attention_mask))?;
+
+        // Classification head: pooled [CLS] state, then a single logit
+        let cls = output.narrow(1, 0, 1)?.squeeze(1)?;
+        let pooled = self.pooler.forward(&cls)?.tanh()?;
+        let logit = self.classifier.forward(&pooled)?.flatten_all()?;
+
+        Ok(logit.to_vec1::<f32>()?[0])
+    }
+}
+
+fn sigmoid(x: f32) -> f32 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_uses_ms_marco_model() {
+        let config = RerankerConfig::default();
+        assert_eq!(config.model_id, "cross-encoder/ms-marco-MiniLM-L-6-v2");
+        assert!(config.model_path.is_none());
+        assert_eq!(config.max_seq_length, 512);
+    }
+
+    #[test]
+    fn sigmoid_maps_logits_to_probabilities() {
+        assert_eq!(sigmoid(0.0), 0.5);
+        assert!(sigmoid(8.0) > 0.99);
+        assert!(sigmoid(-8.0) < 0.01);
+    }
+
+    #[test]
+    fn load_fails_without_model_offline() {
+        let config = RerankerConfig {
+            allow_download: false,
+            ..RerankerConfig::default()
+        };
+        assert!(CrossEncoder::load(&config).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = RerankerConfig {
+            model_path: Some(dir.path().to_path_buf()),
+            ..RerankerConfig::default()
+        };
+        let error = CrossEncoder::load(&config).err().unwrap();
+        assert!(format!("{:#}", error).contains("config.json"));
+    }
+}
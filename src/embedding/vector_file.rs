@@ -0,0 +1,387 @@
+//! Append-only file of full-precision embeddings.
+//!
+//! When embeddings are quantized in memory, the original f32 vectors live in
+//! this file so the top search candidates can be re-scored exactly. Only the
+//! byte offset of each vector is kept in memory.
+//!
+//! Each record is `[id_len: u32][id bytes][dimension: u32][values: f32 * dimension]`,
+//! little-endian. A record with dimension `u32::MAX` and no values marks a
+//! removal. Replaced and removed records are reclaimed by compaction.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::domain::EmailId;
+
+/// Dimension value marking a removal record.
+const TOMBSTONE: u32 = u32::MAX;
+
+/// Location of a vector within the file.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    /// Offset of the first value.
+    offset: u64,
+    /// Number of f32 values.
+    dimension: u32,
+    /// Size of the whole record in bytes.
+    record_len: u64,
+}
+
+/// Full-precision vectors stored on disk.
+#[derive(Debug)]
+pub struct VectorFile {
+    path: PathBuf,
+    file: Mutex<File>,
+    slots: HashMap<EmailId, Slot>,
+    end: u64,
+    dead_bytes: u64,
+}
+
+impl VectorFile {
+    /// Opens the file at `path`, creating it if needed.
+    ///
+    /// A partially written trailing record (from a crash mid-write) is
+    /// discarded.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let (slots, end, dead_bytes) = Self::scan(&mut file)?;
+        if end < file.metadata()?.len() {
+            tracing::warn!(path = %path.display(), "Discarding truncated vector record");
+            file.set_len(end)?;
+        }
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            slots,
+            end,
+            dead_bytes,
+        })
+    }
+
+    /// Returns the path of the backing file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the number of live vectors.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns whether the file holds no live vectors.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Returns whether a vector is stored for the email.
+    pub fn contains(&self, email_id: &EmailId) -> bool {
+        self.slots.contains_key(email_id)
+    }
+
+    /// Returns an iterator over the email IDs with stored vectors.
+    pub fn email_ids(&self) -> impl Iterator<Item = &EmailId> {
+        self.slots.keys()
+    }
+
+    /// Appends a vector, replacing any previous vector for the email.
+    pub fn insert(&mut self, email_id: &EmailId, values: &[f32]) -> Result<()> {
+        let record = Self::encode(email_id, values.len() as u32, values);
+        let values_offset = self.end + 4 + email_id.0.len() as u64 + 4;
+        self.append(&record)?;
+
+        let slot = Slot {
+            offset: values_offset,
+            dimension: values.len() as u32,
+            record_len: record.len() as u64,
+        };
+        if let Some(previous) = self.slots.insert(email_id.clone(), slot) {
+            self.dead_bytes += previous.record_len;
+        }
+        Ok(())
+    }
+
+    /// Reads the vector stored for an email.
+    pub fn get(&self, email_id: &EmailId) -> Result<Option<Vec<f32>>> {
+        let Some(slot) = self.slots.get(email_id) else {
+            return Ok(None);
+        };
+
+        let mut buf = vec![0u8; slot.dimension as usize * 4];
+        {
+            let mut file = self
+                .file
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Vector file lock poisoned"))?;
+            file.seek(SeekFrom::Start(slot.offset))?;
+            file.read_exact(&mut buf)?;
+        }
+
+        Ok(Some(
+            buf.chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        ))
+    }
+
+    /// Removes the vector for an email. Returns whether one was stored.
+    pub fn remove(&mut self, email_id: &EmailId) -> Result<bool> {
+        let Some(previous) = self.slots.remove(email_id) else {
+            return Ok(false);
+        };
+
+        let record = Self::encode(email_id, TOMBSTONE, &[]);
+        self.append(&record)?;
+        self.dead_bytes += previous.record_len + record.len() as u64;
+        Ok(true)
+    }
+
+    /// Removes every vector.
+    pub fn clear(&mut self) -> Result<()> {
+        let file = self
+            .file
+            .get_mut()
+            .map_err(|_| anyhow::anyhow!("Vector file lock poisoned"))?;
+        file.set_len(0)?;
+        self.slots.clear();
+        self.end = 0;
+        self.dead_bytes = 0;
+        Ok(())
+    }
+
+    /// Returns whether replaced and removed records dominate the file.
+    pub fn needs_compaction(&self) -> bool {
+        self.dead_bytes > 1024 * 1024 && self.dead_bytes * 2 > self.end
+    }
+
+    /// Rewrites the file with only the live vectors.
+    pub fn compact(&mut self) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp_path)?);
+            for email_id in self.slots.keys() {
+                if let Some(values) = self.get(email_id)? {
+                    writer.write_all(&Self::encode(email_id, values.len() as u32, &values))?;
+                }
+            }
+            writer.flush()?;
+        }
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        let path = self.path.clone();
+        *self = Self::open(path)?;
+        Ok(())
+    }
+
+    fn append(&mut self, record: &[u8]) -> Result<()> {
+        let file = self
+            .file
+            .get_mut()
+            .map_err(|_| anyhow::anyhow!("Vector file lock poisoned"))?;
+        file.seek(SeekFrom::Start(self.end))?;
+        file.write_all(record)?;
+        self.end += record.len() as u64;
+        Ok(())
+    }
+
+    fn encode(email_id: &EmailId, dimension: u32, values: &[f32]) -> Vec<u8> {
+        let id = email_id.0.as_bytes();
+        let mut record = Vec::with_capacity(8 + id.len() + values.len() * 4);
+        record.extend_from_slice(&(id.len() as u32).to_le_bytes());
+        record.extend_from_slice(id);
+        record.extend_from_slice(&dimension.to_le_bytes());
+        for value in values {
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        record
+    }
+
+    /// Reads every record, returning live slots, the end of the last complete
+    /// record, and the number of dead bytes.
+    fn scan(file: &mut File) -> Result<(HashMap<EmailId, Slot>, u64, u64)> {
+        file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(&mut *file);
+        let mut slots: HashMap<EmailId, Slot> = HashMap::new();
+        let mut end = 0u64;
+        let mut dead_bytes = 0u64;
+
+        loop {
+            let Some(id_len) = read_u32(&mut reader)? else {
+                break;
+            };
+            let mut id = vec![0u8; id_len as usize];
+            if !read_exact_or_eof(&mut reader, &mut id)? {
+                break;
+            }
+            let Some(dimension) = read_u32(&mut reader)? else {
+                break;
+            };
+
+            let value_bytes = if dimension == TOMBSTONE {
+                0
+            } else {
+                dimension as u64 * 4
+            };
+            let mut values = vec![0u8; value_bytes as usize];
+            if !read_exact_or_eof(&mut reader, &mut values)? {
+                break;
+            }
+
+            let email_id = match String::from_utf8(id) {
+                Ok(id) => EmailId(id),
+                Err(_) => bail!("Corrupt vector file: invalid email ID at offset {}", end),
+            };
+            let record_len = 8 + id_len as u64 + value_bytes;
+
+            if dimension == TOMBSTONE {
+                if let Some(previous) = slots.remove(&email_id) {
+                    dead_bytes += previous.record_len;
+                }
+                dead_bytes += record_len;
+            } else {
+                let slot = Slot {
+                    offset: end + 8 + id_len as u64,
+                    dimension,
+                    record_len,
+                };
+                if let Some(previous) = slots.insert(email_id, slot) {
+                    dead_bytes += previous.record_len;
+                }
+            }
+
+            end += record_len;
+        }
+
+        Ok((slots, end, dead_bytes))
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<Option<u32>> {
+    let mut buf = [0u8; 4];
+    if read_exact_or_eof(reader, &mut buf)? {
+        Ok(Some(u32::from_le_bytes(buf)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Fills `buf`, returning false if the input ends first.
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_get() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = VectorFile::open(dir.path().join("vectors.bin")).unwrap();
+
+        file.insert(&EmailId::from("a"), &[1.0, 2.0, 3.0]).unwrap();
+        file.insert(&EmailId::from("b"), &[4.0]).unwrap();
+
+        assert_eq!(file.len(), 2);
+        assert_eq!(
+            file.get(&EmailId::from("a")).unwrap(),
+            Some(vec![1.0, 2.0, 3.0])
+        );
+        assert_eq!(file.get(&EmailId::from("b")).unwrap(), Some(vec![4.0]));
+        assert_eq!(file.get(&EmailId::from("missing")).unwrap(), None);
+    }
+
+    #[test]
+    fn reopen_restores_latest_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        {
+            let mut file = VectorFile::open(&path).unwrap();
+            file.insert(&EmailId::from("a"), &[1.0, 2.0]).unwrap();
+            file.insert(&EmailId::from("a"), &[3.0, 4.0]).unwrap();
+            file.insert(&EmailId::from("b"), &[5.0, 6.0]).unwrap();
+            file.remove(&EmailId::from("b")).unwrap();
+        }
+
+        let file = VectorFile::open(&path).unwrap();
+        assert_eq!(file.len(), 1);
+        assert_eq!(file.get(&EmailId::from("a")).unwrap(), Some(vec![3.0, 4.0]));
+        assert!(!file.contains(&EmailId::from("b")));
+    }
+
+    #[test]
+    fn truncated_record_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        {
+            let mut file = VectorFile::open(&path).unwrap();
+            file.insert(&EmailId::from("a"), &[1.0, 2.0]).unwrap();
+            file.insert(&EmailId::from("b"), &[3.0, 4.0]).unwrap();
+        }
+        let len = std::fs::metadata(&path).unwrap().len();
+        let handle = OpenOptions::new().write(true).open(&path).unwrap();
+        handle.set_len(len - 3).unwrap();
+
+        let mut file = VectorFile::open(&path).unwrap();
+        assert_eq!(file.len(), 1);
+        file.insert(&EmailId::from("c"), &[7.0]).unwrap();
+        assert_eq!(file.get(&EmailId::from("c")).unwrap(), Some(vec![7.0]));
+    }
+
+    #[test]
+    fn compact_keeps_live_vectors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        let mut file = VectorFile::open(&path).unwrap();
+
+        for i in 0..10 {
+            file.insert(&EmailId::from(format!("e{}", i)), &[i as f32; 4])
+                .unwrap();
+        }
+        for i in 0..8 {
+            file.remove(&EmailId::from(format!("e{}", i))).unwrap();
+        }
+        let before = std::fs::metadata(&path).unwrap().len();
+
+        file.compact().unwrap();
+
+        assert!(std::fs::metadata(&path).unwrap().len() < before);
+        assert_eq!(file.len(), 2);
+        assert_eq!(file.get(&EmailId::from("e9")).unwrap(), Some(vec![9.0; 4]));
+    }
+
+    #[test]
+    fn clear_empties_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        let mut file = VectorFile::open(&path).unwrap();
+        file.insert(&EmailId::from("a"), &[1.0]).unwrap();
+
+        file.clear().unwrap();
+
+        assert!(file.is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+    }
+}
@@ -2,60 +2,335 @@
 //!
 //! Stores email embeddings and provides similarity search functionality.
 //! Uses in-memory storage with optional SQLite persistence.
+//!
+//! Small stores are searched exactly with a brute-force scan. Once a store
+//! grows past [`DEFAULT_EXACT_SEARCH_THRESHOLD`] entries, searches go through
+//! an [`HnswIndex`] that is maintained incrementally on every insert and
+//! removal.
+//!
+//! With quantization enabled, only compact [`QuantizedVector`]s are kept in
+//! memory. Candidates are found with quantized dot products and then
+//! re-scored against full-precision vectors read from a [`VectorFile`].
+//! Without a full-precision file, re-scoring is skipped and results keep
+//! their quantized scores.
+//!
+//! Long emails are stored as several passage vectors. Searches rank each
+//! email by its best-matching passage.
 
 use anyhow::Result;
 use std::collections::HashMap;
+use std::path::Path;
 
 use crate::domain::EmailId;
+use crate::embedding::hnsw::{HnswConfig, HnswIndex};
+use crate::embedding::quantization::{QuantizationConfig, QuantizedVector};
+use crate::embedding::vector_file::VectorFile;
 use crate::embedding::Embedding;
 
+/// Store size below which exact search is used even when an index exists.
+pub const DEFAULT_EXACT_SEARCH_THRESHOLD: usize = 5_000;
+
+/// Separates the email ID from the passage number in passage vector keys.
+const PASSAGE_SEPARATOR: char = '\u{1f}';
+
+/// Passage hits fetched per requested email when emails have several passages.
+const PASSAGE_OVERSAMPLE: usize = 4;
+
+/// An embedded passage of an email.
+#[derive(Debug, Clone)]
+pub struct PassageEmbedding {
+    /// Text of the passage.
+    pub text: String,
+    /// Embedding of the passage text.
+    pub embedding: Embedding,
+}
+
+/// An email matched by search, with its best-matching passage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassageMatch {
+    /// The matching email.
+    pub email_id: EmailId,
+    /// Similarity of the best-matching passage.
+    pub score: f32,
+    /// Position of the best-matching passage within the email.
+    pub passage_index: usize,
+    /// Text of the best-matching passage, if known.
+    pub text: Option<String>,
+}
+
 /// In-memory vector store with similarity search.
 ///
 /// Stores embedding vectors indexed by email ID and supports
 /// nearest-neighbor search using cosine similarity.
-#[derive(Debug, Default)]
+///
+/// Vectors are keyed by passage: the first passage of an email uses the
+/// email ID itself, later passages a derived key.
+#[derive(Debug)]
 pub struct VectorStore {
-    /// Map of email IDs to their embeddings.
+    /// Map of passage keys to their embeddings (unquantized mode).
     embeddings: HashMap<EmailId, Embedding>,
+    /// Map of passage keys to compact vectors (quantized mode).
+    quantized: HashMap<EmailId, QuantizedVector>,
+    /// Passage texts by email, in passage order.
+    passages: HashMap<EmailId, Vec<String>>,
+    /// Full-precision vectors on disk for re-scoring quantized candidates.
+    full_precision: Option<VectorFile>,
+    /// Quantization settings.
+    quantization: QuantizationConfig,
+    /// Approximate nearest-neighbor index, if enabled.
+    index: Option<HnswIndex>,
+    /// Store size below which searches bypass the index.
+    exact_search_threshold: usize,
+}
+
+impl Default for VectorStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VectorStore {
-    /// Creates a new empty vector store.
+    /// Creates a new empty vector store with the default HNSW index.
     pub fn new() -> Self {
+        Self::with_index_config(HnswConfig::default())
+    }
+
+    /// Creates a vector store that always uses exact brute-force search.
+    pub fn exact() -> Self {
         Self {
             embeddings: HashMap::new(),
+            quantized: HashMap::new(),
+            passages: HashMap::new(),
+            full_precision: None,
+            quantization: QuantizationConfig::default(),
+            index: None,
+            exact_search_threshold: DEFAULT_EXACT_SEARCH_THRESHOLD,
+        }
+    }
+
+    /// Creates a vector store with a custom HNSW index configuration.
+    pub fn with_index_config(config: HnswConfig) -> Self {
+        Self {
+            index: Some(HnswIndex::new(config)),
+            ..Self::exact()
         }
     }
 
-    /// Inserts or updates an embedding for the given email ID.
+    /// Creates an indexed vector store using the given quantization.
+    ///
+    /// If a full-precision file is configured and already holds vectors,
+    /// they are quantized and loaded into the store.
+    pub fn with_quantization(config: QuantizationConfig) -> Result<Self> {
+        let mut store = Self::new();
+
+        if config.is_enabled() {
+            if let Some(path) = &config.full_precision_path {
+                let file = VectorFile::open(path)?;
+                for email_id in file.email_ids() {
+                    if let Some(values) = file.get(email_id)? {
+                        if let Some(vector) = QuantizedVector::quantize(&values, config.mode) {
+                            store.quantized.insert(email_id.clone(), vector);
+                        }
+                    }
+                }
+                for key in store.quantized.keys() {
+                    let (email_id, passage) = split_passage_key(key);
+                    let texts = store.passages.entry(email_id).or_default();
+                    if texts.len() <= passage {
+                        texts.resize(passage + 1, String::new());
+                    }
+                }
+                if let Some(index) = &mut store.index {
+                    for key in store.quantized.keys() {
+                        index.insert(key, &store.quantized);
+                    }
+                }
+                store.full_precision = Some(file);
+            }
+        }
+
+        store.quantization = config;
+        Ok(store)
+    }
+
+    /// Returns the quantization settings.
+    pub fn quantization(&self) -> &QuantizationConfig {
+        &self.quantization
+    }
+
+    /// Sets the store size below which searches use exact scanning.
+    pub fn set_exact_search_threshold(&mut self, threshold: usize) {
+        self.exact_search_threshold = threshold;
+    }
+
+    /// Returns whether searches currently go through the HNSW index.
+    pub fn uses_index(&self) -> bool {
+        self.index.is_some() && self.vector_count() >= self.exact_search_threshold
+    }
+
+    /// Inserts or updates a single embedding for the given email ID.
+    ///
+    /// Replaces any passages previously stored for the email.
     pub fn insert(&mut self, email_id: &EmailId, embedding: Embedding) -> Result<()> {
-        self.embeddings.insert(email_id.clone(), embedding);
+        self.insert_passages(
+            email_id,
+            vec![PassageEmbedding {
+                text: String::new(),
+                embedding,
+            }],
+        )
+    }
+
+    /// Inserts or replaces the passage embeddings of an email.
+    ///
+    /// An empty list removes the email.
+    pub fn insert_passages(
+        &mut self,
+        email_id: &EmailId,
+        passages: Vec<PassageEmbedding>,
+    ) -> Result<()> {
+        if passages.is_empty() {
+            self.remove(email_id);
+            return Ok(());
+        }
+
+        let previous = self.passages.get(email_id).map_or(0, Vec::len);
+        for passage in passages.len()..previous {
+            self.remove_vector(&passage_key(email_id, passage));
+        }
+
+        let mut texts = Vec::with_capacity(passages.len());
+        for (i, passage) in passages.into_iter().enumerate() {
+            self.insert_vector(&passage_key(email_id, i), passage.embedding)?;
+            texts.push(passage.text);
+        }
+        self.passages.insert(email_id.clone(), texts);
         Ok(())
     }
 
+    /// Returns the stored passage texts of an email.
+    ///
+    /// Texts are empty for embeddings inserted without passage text.
+    pub fn passages(&self, email_id: &EmailId) -> Option<&[String]> {
+        self.passages.get(email_id).map(Vec::as_slice)
+    }
+
     /// Retrieves the embedding for an email, if it exists.
+    ///
+    /// For emails with several passages this is the first passage.
+    ///
+    /// Quantized stores keep no full-precision vectors in memory and always
+    /// return `None`; use [`get_full_precision`](Self::get_full_precision).
     pub fn get(&self, email_id: &EmailId) -> Option<&Embedding> {
         self.embeddings.get(email_id)
     }
 
-    /// Removes the embedding for an email.
-    pub fn remove(&mut self, email_id: &EmailId) -> Option<Embedding> {
-        self.embeddings.remove(email_id)
+    /// Retrieves the full-precision embedding for an email, if it exists.
+    ///
+    /// In quantized mode the vector is read from the full-precision file;
+    /// without one configured, `None` is returned rather than an
+    /// approximation.
+    pub fn get_full_precision(&self, email_id: &EmailId) -> Result<Option<Embedding>> {
+        if let Some(embedding) = self.embeddings.get(email_id) {
+            return Ok(Some(embedding.clone()));
+        }
+
+        match &self.full_precision {
+            Some(file) if self.quantized.contains_key(email_id) => {
+                Ok(file.get(email_id)?.map(Embedding::new))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Removes all embeddings for an email. Returns whether it was stored.
+    pub fn remove(&mut self, email_id: &EmailId) -> bool {
+        let Some(texts) = self.passages.remove(email_id) else {
+            return false;
+        };
+        for passage in 0..texts.len() {
+            self.remove_vector(&passage_key(email_id, passage));
+        }
+        true
+    }
+
+    /// Inserts or updates a single vector.
+    fn insert_vector(&mut self, key: &EmailId, embedding: Embedding) -> Result<()> {
+        if let Some(vector) = QuantizedVector::quantize(&embedding.values, self.quantization.mode) {
+            if let Some(file) = &mut self.full_precision {
+                file.insert(key, &embedding.values)?;
+            }
+            self.quantized.insert(key.clone(), vector);
+            if let Some(index) = &mut self.index {
+                index.insert(key, &self.quantized);
+            }
+        } else {
+            self.embeddings.insert(key.clone(), embedding);
+            if let Some(index) = &mut self.index {
+                index.insert(key, &self.embeddings);
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes a single vector.
+    fn remove_vector(&mut self, key: &EmailId) {
+        self.embeddings.remove(key);
+        self.quantized.remove(key);
+
+        if let Some(file) = &mut self.full_precision {
+            let result = file.remove(key).and_then(|_| {
+                if file.needs_compaction() {
+                    file.compact()?;
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "Failed to remove full-precision vector");
+            }
+        }
+
+        if let Some(index) = &mut self.index {
+            index.remove(key);
+            if index.needs_compaction() {
+                if self.quantization.is_enabled() {
+                    index.rebuild(&self.quantized);
+                } else {
+                    index.rebuild(&self.embeddings);
+                }
+            }
+        }
     }
 
     /// Returns whether an embedding exists for the given email.
     pub fn contains(&self, email_id: &EmailId) -> bool {
-        self.embeddings.contains_key(email_id)
+        self.passages.contains_key(email_id)
     }
 
-    /// Returns the number of stored embeddings.
+    /// Returns the number of emails with stored embeddings.
     pub fn len(&self) -> usize {
-        self.embeddings.len()
+        self.passages.len()
+    }
+
+    /// Returns the number of stored vectors across all passages.
+    pub fn vector_count(&self) -> usize {
+        self.embeddings.len() + self.quantized.len()
     }
 
     /// Returns whether the store is empty.
     pub fn is_empty(&self) -> bool {
-        self.embeddings.is_empty()
+        self.len() == 0
+    }
+
+    /// Returns the approximate memory held by in-memory vectors in bytes.
+    pub fn vector_memory_bytes(&self) -> usize {
+        let full: usize = self
+            .embeddings
+            .values()
+            .map(|e| e.values.len() * std::mem::size_of::<f32>())
+            .sum();
+        let quantized: usize = self.quantized.values().map(|v| v.memory_bytes()).sum();
+        full + quantized
     }
 
     /// Searches for the most similar embeddings to the query.
@@ -63,17 +338,35 @@ impl VectorStore {
     /// Returns up to `limit` results as (EmailId, similarity_score) pairs,
     /// sorted by similarity in descending order.
     pub fn search(&self, query: &Embedding, limit: usize) -> Result<Vec<(EmailId, f32)>> {
-        let mut scores: Vec<(EmailId, f32)> = self
-            .embeddings
-            .iter()
-            .map(|(id, emb)| (id.clone(), query.cosine_similarity(emb)))
-            .collect();
+        Ok(self
+            .search_passages(query, limit)?
+            .into_iter()
+            .map(|m| (m.email_id, m.score))
+            .collect())
+    }
 
-        // Sort by similarity descending
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    /// Searches for the emails whose passages best match the query.
+    ///
+    /// Each email is scored by its best-matching passage, which is returned
+    /// alongside for highlighting.
+    pub fn search_passages(&self, query: &Embedding, limit: usize) -> Result<Vec<PassageMatch>> {
+        let vector_limit = self.vector_limit(limit);
 
-        scores.truncate(limit);
-        Ok(scores)
+        let Some(index) = self.index.as_ref().filter(|_| self.uses_index()) else {
+            return Ok(self.best_passages(self.scan(query, vector_limit), limit));
+        };
+
+        let hits = if self.quantization.is_enabled() {
+            let candidates = index.search(
+                &query.values,
+                self.candidate_count(vector_limit),
+                &self.quantized,
+            );
+            self.rescore(query, candidates, vector_limit)
+        } else {
+            index.search(&query.values, vector_limit, &self.embeddings)
+        };
+        Ok(self.best_passages(hits, limit))
     }
 
     /// Searches with a minimum similarity threshold.
@@ -85,33 +378,236 @@ impl VectorStore {
         limit: usize,
         min_similarity: f32,
     ) -> Result<Vec<(EmailId, f32)>> {
+        let mut scores = self.search(query, limit)?;
+        scores.retain(|(_, score)| *score >= min_similarity);
+        Ok(scores)
+    }
+
+    /// Searches by scanning every stored embedding.
+    ///
+    /// Used directly for small stores and as a baseline for measuring index
+    /// recall. In quantized mode the scan uses quantized scores and the best
+    /// candidates are re-scored at full precision.
+    pub fn search_exact(&self, query: &Embedding, limit: usize) -> Result<Vec<(EmailId, f32)>> {
+        let hits = self.scan(query, self.vector_limit(limit));
+        Ok(self
+            .best_passages(hits, limit)
+            .into_iter()
+            .map(|m| (m.email_id, m.score))
+            .collect())
+    }
+
+    /// Scores every stored vector, returning the best `limit` passage keys.
+    fn scan(&self, query: &Embedding, limit: usize) -> Vec<(EmailId, f32)> {
+        if self.quantization.is_enabled() {
+            let query_norm = query.values.iter().map(|v| v * v).sum::<f32>().sqrt();
+            let mut candidates: Vec<(EmailId, f32)> = self
+                .quantized
+                .iter()
+                .map(|(id, vector)| {
+                    let norm = vector.norm() * query_norm;
+                    let score = if norm > 0.0 {
+                        vector.dot(&query.values) / norm
+                    } else {
+                        0.0
+                    };
+                    (id.clone(), score)
+                })
+                .collect();
+            sort_by_score(&mut candidates);
+            candidates.truncate(self.candidate_count(limit));
+            return self.rescore(query, candidates, limit);
+        }
+
         let mut scores: Vec<(EmailId, f32)> = self
             .embeddings
             .iter()
             .map(|(id, emb)| (id.clone(), query.cosine_similarity(emb)))
-            .filter(|(_, score)| *score >= min_similarity)
             .collect();
 
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        sort_by_score(&mut scores);
 
         scores.truncate(limit);
-        Ok(scores)
+        scores
     }
 
     /// Clears all stored embeddings.
     pub fn clear(&mut self) {
         self.embeddings.clear();
+        self.quantized.clear();
+        self.passages.clear();
+        if let Some(file) = &mut self.full_precision {
+            if let Err(e) = file.clear() {
+                tracing::warn!(error = %e, "Failed to clear full-precision vectors");
+            }
+        }
+        if let Some(index) = &mut self.index {
+            index.clear();
+        }
     }
 
     /// Returns an iterator over all email IDs in the store.
     pub fn email_ids(&self) -> impl Iterator<Item = &EmailId> {
-        self.embeddings.keys()
+        self.passages.keys()
+    }
+
+    /// Writes the HNSW graph to disk.
+    ///
+    /// Does nothing if the store has no index.
+    pub fn save_index(&self, path: impl AsRef<Path>) -> Result<()> {
+        match &self.index {
+            Some(index) => index.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads a previously saved HNSW graph.
+    ///
+    /// Embeddings must be inserted before loading. If the saved graph does
+    /// not cover exactly the stored embeddings, the index is rebuilt from the
+    /// embeddings instead.
+    pub fn load_index(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let loaded = HnswIndex::load(path)?;
+
+        let matches = loaded.len() == self.vector_count()
+            && loaded
+                .email_ids()
+                .all(|key| self.embeddings.contains_key(key) || self.quantized.contains_key(key));
+
+        if matches {
+            self.index = Some(loaded);
+        } else {
+            tracing::warn!(
+                indexed = loaded.len(),
+                stored = self.vector_count(),
+                "Saved vector index is stale, rebuilding"
+            );
+            let mut index = HnswIndex::new(loaded.config());
+            for email_id in self.embeddings.keys() {
+                index.insert(email_id, &self.embeddings);
+            }
+            for email_id in self.quantized.keys() {
+                index.insert(email_id, &self.quantized);
+            }
+            self.index = Some(index);
+        }
+
+        Ok(())
+    }
+
+    /// Number of passage hits to fetch for `limit` distinct emails.
+    fn vector_limit(&self, limit: usize) -> usize {
+        if self.vector_count() > self.len() {
+            limit.saturating_mul(PASSAGE_OVERSAMPLE)
+        } else {
+            limit
+        }
+    }
+
+    /// Keeps the best passage of each email from hits sorted by score.
+    fn best_passages(&self, hits: Vec<(EmailId, f32)>, limit: usize) -> Vec<PassageMatch> {
+        let mut matches: Vec<PassageMatch> = Vec::with_capacity(limit.min(hits.len()));
+
+        for (key, score) in hits {
+            if matches.len() >= limit {
+                break;
+            }
+            let (email_id, passage_index) = split_passage_key(&key);
+            if matches.iter().any(|m| m.email_id == email_id) {
+                continue;
+            }
+            let text = self
+                .passages
+                .get(&email_id)
+                .and_then(|texts| texts.get(passage_index))
+                .filter(|text| !text.is_empty())
+                .cloned();
+            matches.push(PassageMatch {
+                email_id,
+                score,
+                passage_index,
+                text,
+            });
+        }
+
+        matches
+    }
+
+    /// Number of quantized candidates to fetch for `limit` final results.
+    fn candidate_count(&self, limit: usize) -> usize {
+        limit.saturating_mul(self.quantization.rescore_multiplier.max(1))
+    }
+
+    /// Re-ranks quantized candidates by exact cosine similarity.
+    ///
+    /// Without a full-precision file the candidates keep their quantized
+    /// scores. Candidates missing from the file are scored from their
+    /// dequantized vector.
+    fn rescore(
+        &self,
+        query: &Embedding,
+        mut candidates: Vec<(EmailId, f32)>,
+        limit: usize,
+    ) -> Vec<(EmailId, f32)> {
+        if self.full_precision.is_none() {
+            candidates.truncate(limit);
+            return candidates;
+        }
+
+        let mut scores: Vec<(EmailId, f32)> = candidates
+            .into_iter()
+            .filter_map(|(id, _)| {
+                let values = self
+                    .read_full_precision(&id)
+                    .or_else(|| self.quantized.get(&id).map(|v| v.dequantize()))?;
+                let similarity = query.cosine_similarity(&Embedding::new(values));
+                Some((id, similarity))
+            })
+            .collect();
+
+        sort_by_score(&mut scores);
+        scores.truncate(limit);
+        scores
+    }
+
+    fn read_full_precision(&self, email_id: &EmailId) -> Option<Vec<f32>> {
+        let file = self.full_precision.as_ref()?;
+        match file.get(email_id) {
+            Ok(values) => values,
+            Err(e) => {
+                tracing::warn!(error = %e, email_id = %email_id, "Failed to read full-precision vector");
+                None
+            }
+        }
     }
 }
 
+/// Returns the vector key for a passage of an email.
+fn passage_key(email_id: &EmailId, passage: usize) -> EmailId {
+    if passage == 0 {
+        email_id.clone()
+    } else {
+        EmailId(format!("{}{}{}", email_id.0, PASSAGE_SEPARATOR, passage))
+    }
+}
+
+/// Splits a vector key into its email ID and passage number.
+fn split_passage_key(key: &EmailId) -> (EmailId, usize) {
+    key.0
+        .rsplit_once(PASSAGE_SEPARATOR)
+        .and_then(|(email_id, passage)| Some((EmailId::from(email_id), passage.parse().ok()?)))
+        .unwrap_or_else(|| (key.clone(), 0))
+}
+
+/// Sorts (id, score) pairs by score in descending order.
+fn sort_by_score(scores: &mut [(EmailId, f32)]) {
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::embedding::quantization::Quantization;
 
     fn make_embedding(values: &[f32]) -> Embedding {
         Embedding::new(values.to_vec())
@@ -150,9 +646,9 @@ mod tests {
         store.insert(&id, make_embedding(&[1.0])).unwrap();
         assert!(store.contains(&id));
 
-        let removed = store.remove(&id);
-        assert!(removed.is_some());
+        assert!(store.remove(&id));
         assert!(!store.contains(&id));
+        assert!(!store.remove(&id));
     }
 
     #[test]
@@ -264,6 +760,114 @@ mod tests {
         assert_eq!(ids.len(), 2);
     }
 
+    fn passage(text: &str, values: &[f32]) -> PassageEmbedding {
+        PassageEmbedding {
+            text: text.to_string(),
+            embedding: make_embedding(values),
+        }
+    }
+
+    #[test]
+    fn passages_rank_email_by_best_passage() {
+        let mut store = VectorStore::new();
+        store
+            .insert_passages(
+                &EmailId::from("newsletter"),
+                vec![
+                    passage("intro", &[0.0, 1.0]),
+                    passage("the part about budgets", &[1.0, 0.0]),
+                    passage("outro", &[0.0, 1.0]),
+                ],
+            )
+            .unwrap();
+        store
+            .insert(&EmailId::from("short"), make_embedding(&[0.7, 0.7]))
+            .unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.vector_count(), 4);
+
+        let results = store
+            .search_passages(&make_embedding(&[1.0, 0.0]), 10)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].email_id, EmailId::from("newsletter"));
+        assert_eq!(results[0].passage_index, 1);
+        assert_eq!(results[0].text.as_deref(), Some("the part about budgets"));
+        assert!((results[0].score - 1.0).abs() < 0.0001);
+        assert_eq!(results[1].email_id, EmailId::from("short"));
+        assert_eq!(results[1].text, None);
+    }
+
+    #[test]
+    fn reinserting_passages_drops_old_ones() {
+        let mut store = VectorStore::new();
+        let id = EmailId::from("email-1");
+        store
+            .insert_passages(
+                &id,
+                vec![passage("a", &[1.0, 0.0]), passage("b", &[0.0, 1.0])],
+            )
+            .unwrap();
+        store.insert(&id, make_embedding(&[1.0, 0.0])).unwrap();
+
+        assert_eq!(store.vector_count(), 1);
+        let results = store.search(&make_embedding(&[0.0, 1.0]), 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].1 < 0.5);
+    }
+
+    #[test]
+    fn remove_drops_all_passages() {
+        let mut store = VectorStore::new();
+        let id = EmailId::from("email-1");
+        store
+            .insert_passages(
+                &id,
+                vec![passage("a", &[1.0, 0.0]), passage("b", &[0.0, 1.0])],
+            )
+            .unwrap();
+
+        assert!(store.remove(&id));
+        assert!(store.is_empty());
+        assert_eq!(store.vector_count(), 0);
+    }
+
+    #[test]
+    fn passage_keys_round_trip() {
+        let id = EmailId::from("email-1");
+        assert_eq!(passage_key(&id, 0), id);
+        assert_eq!(split_passage_key(&passage_key(&id, 0)), (id.clone(), 0));
+        assert_eq!(split_passage_key(&passage_key(&id, 7)), (id, 7));
+    }
+
+    #[test]
+    fn indexed_passage_search_returns_distinct_emails() {
+        let mut store = VectorStore::new();
+        store.set_exact_search_threshold(0);
+        for i in 0..100 {
+            let angle = i as f32 * 0.01;
+            store
+                .insert_passages(
+                    &EmailId::from(format!("email-{}", i)),
+                    vec![
+                        passage("first", &[angle.cos(), angle.sin(), 0.0]),
+                        passage("second", &[angle.cos(), 0.0, angle.sin()]),
+                    ],
+                )
+                .unwrap();
+        }
+        assert!(store.uses_index());
+
+        let results = store
+            .search_passages(&make_embedding(&[1.0, 0.0, 0.0]), 5)
+            .unwrap();
+        assert_eq!(results.len(), 5);
+        let mut ids: Vec<_> = results.iter().map(|m| m.email_id.clone()).collect();
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+    }
+
     #[test]
     fn search_empty_store() {
         let store = VectorStore::new();
@@ -271,4 +875,230 @@ mod tests {
         let results = store.search(&query, 10).unwrap();
         assert!(results.is_empty());
     }
+
+    fn indexed_store(count: usize) -> VectorStore {
+        let mut store = VectorStore::new();
+        store.set_exact_search_threshold(0);
+        for i in 0..count {
+            let angle = i as f32 * 0.01;
+            store
+                .insert(
+                    &EmailId::from(format!("email-{}", i)),
+                    make_embedding(&[angle.cos(), angle.sin(), (angle * 3.0).sin()]),
+                )
+                .unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn small_store_uses_exact_search() {
+        let mut store = VectorStore::new();
+        store
+            .insert(&EmailId::from("email-1"), make_embedding(&[1.0]))
+            .unwrap();
+        assert!(!store.uses_index());
+
+        store.set_exact_search_threshold(1);
+        assert!(store.uses_index());
+    }
+
+    #[test]
+    fn exact_store_never_uses_index() {
+        let mut store = VectorStore::exact();
+        store.set_exact_search_threshold(0);
+        assert!(!store.uses_index());
+    }
+
+    #[test]
+    fn indexed_search_matches_exact_top_hit() {
+        let store = indexed_store(500);
+        assert!(store.uses_index());
+
+        let query = make_embedding(&[1.0, 0.0, 0.0]);
+        let indexed = store.search(&query, 5).unwrap();
+        let exact = store.search_exact(&query, 5).unwrap();
+
+        assert_eq!(indexed.len(), 5);
+        assert_eq!(indexed[0].0, exact[0].0);
+    }
+
+    #[test]
+    fn indexed_search_skips_removed() {
+        let mut store = indexed_store(200);
+        let query = store.get(&EmailId::from("email-50")).unwrap().clone();
+
+        store.remove(&EmailId::from("email-50"));
+
+        let results = store.search(&query, 3).unwrap();
+        assert!(results
+            .iter()
+            .all(|(id, _)| *id != EmailId::from("email-50")));
+    }
+
+    #[test]
+    fn load_index_round_trip() {
+        let store = indexed_store(200);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.hnsw");
+        store.save_index(&path).unwrap();
+
+        let mut restored = VectorStore::new();
+        restored.set_exact_search_threshold(0);
+        for id in store.email_ids() {
+            restored.insert(id, store.get(id).unwrap().clone()).unwrap();
+        }
+        restored.load_index(&path).unwrap();
+
+        let query = make_embedding(&[0.5, 0.5, 0.0]);
+        assert_eq!(
+            restored.search(&query, 5).unwrap(),
+            store.search(&query, 5).unwrap()
+        );
+    }
+
+    #[test]
+    fn load_index_rebuilds_when_stale() {
+        let store = indexed_store(100);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.hnsw");
+        store.save_index(&path).unwrap();
+
+        let mut restored = VectorStore::new();
+        restored.set_exact_search_threshold(0);
+        restored
+            .insert(&EmailId::from("only"), make_embedding(&[1.0, 0.0, 0.0]))
+            .unwrap();
+        restored.load_index(&path).unwrap();
+
+        let results = restored
+            .search(&make_embedding(&[1.0, 0.0, 0.0]), 5)
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, EmailId::from("only"));
+    }
+
+    fn quantized_store(mode: Quantization, path: Option<&Path>) -> VectorStore {
+        let mut config = QuantizationConfig::new(mode);
+        if let Some(path) = path {
+            config = config.with_full_precision_path(path);
+        }
+        VectorStore::with_quantization(config).unwrap()
+    }
+
+    #[test]
+    fn int8_store_finds_nearest() {
+        let mut store = quantized_store(Quantization::Int8, None);
+        store
+            .insert(&EmailId::from("exact"), make_embedding(&[1.0, 0.0]))
+            .unwrap();
+        store
+            .insert(&EmailId::from("different"), make_embedding(&[0.0, 1.0]))
+            .unwrap();
+
+        let results = store.search(&make_embedding(&[1.0, 0.0]), 1).unwrap();
+        assert_eq!(results[0].0, EmailId::from("exact"));
+        assert!((results[0].1 - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn quantized_store_without_file_keeps_quantized_scores() {
+        let mut store = quantized_store(Quantization::Int8, None);
+        let id = EmailId::from("a");
+        store.insert(&id, make_embedding(&[2.0, 0.0])).unwrap();
+
+        assert!(store.get(&id).is_none());
+        assert!(store.get_full_precision(&id).unwrap().is_none());
+        let results = store.search(&make_embedding(&[3.0, 0.0]), 1).unwrap();
+        assert_eq!(results[0].0, id);
+        assert!((results[0].1 - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn quantized_store_uses_less_memory() {
+        let mut full = VectorStore::new();
+        let mut int8 = quantized_store(Quantization::Int8, None);
+        let mut binary = quantized_store(Quantization::Binary, None);
+
+        for i in 0..10 {
+            let id = EmailId::from(format!("email-{}", i));
+            let embedding = make_embedding(&vec![0.1; 384]);
+            full.insert(&id, embedding.clone()).unwrap();
+            int8.insert(&id, embedding.clone()).unwrap();
+            binary.insert(&id, embedding).unwrap();
+        }
+
+        assert_eq!(int8.vector_memory_bytes() * 4, full.vector_memory_bytes());
+        assert_eq!(
+            binary.vector_memory_bytes() * 32,
+            full.vector_memory_bytes()
+        );
+    }
+
+    #[test]
+    fn binary_store_rescores_with_full_precision() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        let mut store = quantized_store(Quantization::Binary, Some(&path));
+
+        // Identical signs, so binary scores cannot tell these apart.
+        store
+            .insert(&EmailId::from("close"), make_embedding(&[0.9, 0.1, 0.1]))
+            .unwrap();
+        store
+            .insert(&EmailId::from("far"), make_embedding(&[0.1, 0.9, 0.9]))
+            .unwrap();
+
+        let results = store.search(&make_embedding(&[1.0, 0.0, 0.0]), 2).unwrap();
+        assert_eq!(results[0].0, EmailId::from("close"));
+        assert!(results[0].1 > results[1].1);
+
+        assert!(store.get(&EmailId::from("close")).is_none());
+        let stored = store
+            .get_full_precision(&EmailId::from("close"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.values, vec![0.9, 0.1, 0.1]);
+    }
+
+    #[test]
+    fn quantized_store_reloads_from_full_precision_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vectors.bin");
+        {
+            let mut store = quantized_store(Quantization::Int8, Some(&path));
+            store
+                .insert(&EmailId::from("a"), make_embedding(&[1.0, 0.0]))
+                .unwrap();
+            store
+                .insert(&EmailId::from("b"), make_embedding(&[0.0, 1.0]))
+                .unwrap();
+            store.remove(&EmailId::from("b"));
+        }
+
+        let store = quantized_store(Quantization::Int8, Some(&path));
+        assert_eq!(store.len(), 1);
+        assert!(store.contains(&EmailId::from("a")));
+        assert!(!store.contains(&EmailId::from("b")));
+    }
+
+    #[test]
+    fn indexed_quantized_search() {
+        let mut store = quantized_store(Quantization::Int8, None);
+        store.set_exact_search_threshold(0);
+        for i in 0..300 {
+            let angle = i as f32 * 0.01;
+            store
+                .insert(
+                    &EmailId::from(format!("email-{}", i)),
+                    make_embedding(&[angle.cos(), angle.sin(), 0.5]),
+                )
+                .unwrap();
+        }
+
+        let query = make_embedding(&[1.0, 0.0, 0.5]);
+        let results = store.search(&query, 3).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0, EmailId::from("email-0"));
+    }
 }
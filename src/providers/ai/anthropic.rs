@@ -9,7 +9,8 @@ use std::task::{Context, Poll};
 
 use super::traits::{
     CompletionRequest, CompletionResponse, CompletionStream, FinishReason, LlmError, LlmProvider,
-    LlmResult, Message, Role, StreamChunk, TokenUsage,
+    LlmResult, Message, Role, StreamChunk, TokenUsage, ToolCall, ToolRequest, ToolResponse,
+    ToolTurn,
 };
 
 const ANTHROPIC_API_URL: &str = "https://api.anthropic.com/v1/messages";
@@ -38,10 +39,23 @@ struct AnthropicRequest {
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stop_sequences: Option<Vec<String>>,
+    /// Holds the output tool when the request has an output schema.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<AnthropicTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<AnthropicToolChoice>,
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     stream: bool,
 }
 
+/// Forces the model to call the named tool.
+#[derive(Debug, Serialize)]
+struct AnthropicToolChoice {
+    #[serde(rename = "type")]
+    choice_type: &'static str,
+    name: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct AnthropicMessage {
     role: String,
@@ -75,6 +89,8 @@ struct AnthropicContent {
     #[serde(rename = "type")]
     content_type: String,
     text: Option<String>,
+    /// Arguments of a `tool_use` block.
+    input: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -83,6 +99,63 @@ struct AnthropicUsage {
     output_tokens: usize,
 }
 
+/// Anthropic tool-use request format.
+#[derive(Debug, Serialize)]
+struct AnthropicToolRequest {
+    model: String,
+    messages: Vec<AnthropicBlockMessage>,
+    max_tokens: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    tools: Vec<AnthropicTool>,
+}
+
+/// A message made of content blocks.
+#[derive(Debug, Serialize)]
+struct AnthropicBlockMessage {
+    role: &'static str,
+    content: Vec<AnthropicBlock>,
+}
+
+/// Content block in tool-use requests and responses.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicBlock {
+    Text {
+        text: String,
+    },
+    ToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Serialize)]
+struct AnthropicTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
+}
+
+/// Anthropic tool-use response format.
+#[derive(Debug, Deserialize)]
+struct AnthropicToolResponse {
+    content: Vec<AnthropicBlock>,
+    stop_reason: Option<String>,
+    usage: AnthropicUsage,
+}
+
 /// Anthropic streaming event types.
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
@@ -128,6 +201,8 @@ struct AnthropicDelta {
     #[serde(rename = "type")]
     delta_type: String,
     text: Option<String>,
+    /// Fragment of tool arguments in an `input_json_delta`.
+    partial_json: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -240,6 +315,23 @@ impl AnthropicProvider {
             }
         };
 
+        // Structured output is a forced call to a tool whose input schema is
+        // the output schema; the tool's arguments are the reply.
+        let (tools, tool_choice) = match &request.output_schema {
+            Some(schema) => (
+                vec![AnthropicTool {
+                    name: schema.name.clone(),
+                    description: schema.description.clone(),
+                    input_schema: schema.schema.clone(),
+                }],
+                Some(AnthropicToolChoice {
+                    choice_type: "tool",
+                    name: schema.name.clone(),
+                }),
+            ),
+            None => (Vec::new(), None),
+        };
+
         AnthropicRequest {
             model: self.model.clone(),
             messages,
@@ -247,10 +339,108 @@ impl AnthropicProvider {
             system: system_prompt,
             temperature: Some(request.temperature),
             stop_sequences: request.stop.clone(),
+            tools,
+            tool_choice,
             stream,
         }
     }
 
+    fn build_tool_request(&self, request: &ToolRequest) -> AnthropicToolRequest {
+        let mut messages: Vec<AnthropicBlockMessage> = Vec::new();
+
+        for turn in &request.turns {
+            let (role, blocks) = match turn {
+                ToolTurn::User(text) => ("user", vec![AnthropicBlock::Text { text: text.clone() }]),
+                ToolTurn::Assistant { text, tool_calls } => {
+                    let text =
+                        (!text.is_empty()).then(|| AnthropicBlock::Text { text: text.clone() });
+                    let calls = tool_calls.iter().map(|call| AnthropicBlock::ToolUse {
+                        id: call.id.clone(),
+                        name: call.name.clone(),
+                        input: call.arguments.clone(),
+                    });
+                    ("assistant", text.into_iter().chain(calls).collect())
+                }
+                // Tool results are sent back as a user message
+                ToolTurn::ToolResults(results) => (
+                    "user",
+                    results
+                        .iter()
+                        .map(|result| AnthropicBlock::ToolResult {
+                            tool_use_id: result.call_id.clone(),
+                            content: result.content.clone(),
+                            is_error: result.is_error,
+                        })
+                        .collect(),
+                ),
+            };
+
+            // Roles must alternate, so consecutive turns from one role are merged
+            match messages.last_mut() {
+                Some(last) if last.role == role => last.content.extend(blocks),
+                _ => messages.push(AnthropicBlockMessage {
+                    role,
+                    content: blocks,
+                }),
+            }
+        }
+
+        AnthropicToolRequest {
+            model: self.model.clone(),
+            messages,
+            max_tokens: request.max_tokens.unwrap_or(4096),
+            system: request.system_prompt.clone(),
+            temperature: Some(request.temperature),
+            tools: request
+                .tools
+                .iter()
+                .map(|tool| AnthropicTool {
+                    name: tool.name.clone(),
+                    description: tool.description.clone(),
+                    input_schema: tool.parameters.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the reply text, or the arguments of the forced output tool.
+    fn response_text(content: Vec<AnthropicContent>) -> String {
+        if let Some(input) = content
+            .iter()
+            .find(|c| c.content_type == "tool_use")
+            .and_then(|c| c.input.as_ref())
+        {
+            return input.to_string();
+        }
+
+        content
+            .into_iter()
+            .filter(|c| c.content_type == "text")
+            .filter_map(|c| c.text)
+            .collect::<Vec<_>>()
+            .join("")
+    }
+
+    /// Splits response blocks into text and tool calls.
+    fn parse_tool_blocks(blocks: Vec<AnthropicBlock>) -> (String, Vec<ToolCall>) {
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+
+        for block in blocks {
+            match block {
+                AnthropicBlock::Text { text: t } => text.push_str(&t),
+                AnthropicBlock::ToolUse { id, name, input } => tool_calls.push(ToolCall {
+                    id,
+                    name,
+                    arguments: input,
+                }),
+                AnthropicBlock::ToolResult { .. } | AnthropicBlock::Other => {}
+            }
+        }
+
+        (text, tool_calls)
+    }
+
     fn parse_finish_reason(reason: Option<&str>) -> FinishReason {
         match reason {
             Some("end_turn") => FinishReason::Stop,
@@ -323,13 +513,7 @@ impl LlmProvider for AnthropicProvider {
             .await
             .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse response: {}", e)))?;
 
-        let text = api_response
-            .content
-            .into_iter()
-            .filter(|c| c.content_type == "text")
-            .filter_map(|c| c.text)
-            .collect::<Vec<_>>()
-            .join("");
+        let text = Self::response_text(api_response.content);
 
         let tokens_used = TokenUsage {
             prompt_tokens: api_response.usage.input_tokens,
@@ -368,6 +552,47 @@ impl LlmProvider for AnthropicProvider {
         self.model.contains("claude-3")
     }
 
+    fn supports_structured_output(&self) -> bool {
+        // Output schemas are enforced through forced tool use
+        self.supports_function_calling()
+    }
+
+    async fn complete_with_tools(&self, request: &ToolRequest) -> LlmResult<ToolResponse> {
+        let body = self.build_tool_request(request);
+
+        let response = self
+            .client
+            .post(ANTHROPIC_API_URL)
+            .headers(self.build_headers())
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.handle_error_response(response).await);
+        }
+
+        let api_response: AnthropicToolResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse response: {}", e)))?;
+
+        let (text, tool_calls) = Self::parse_tool_blocks(api_response.content);
+
+        let tokens_used = TokenUsage {
+            prompt_tokens: api_response.usage.input_tokens,
+            completion_tokens: api_response.usage.output_tokens,
+            total_tokens: api_response.usage.input_tokens + api_response.usage.output_tokens,
+        };
+
+        Ok(ToolResponse {
+            text,
+            tool_calls,
+            tokens_used,
+            finish_reason: Self::parse_finish_reason(api_response.stop_reason.as_deref()),
+        })
+    }
+
     fn max_context_length(&self) -> usize {
         self.context_length
     }
@@ -393,14 +618,16 @@ impl<S> AnthropicStream<S> {
         match serde_json::from_str::<AnthropicStreamEvent>(data) {
             Ok(event) => match event {
                 AnthropicStreamEvent::ContentBlockDelta { delta } => {
-                    if delta.delta_type == "text_delta" {
-                        Some(Ok(StreamChunk {
-                            text: delta.text.unwrap_or_default(),
-                            finish_reason: None,
-                        }))
-                    } else {
-                        None
-                    }
+                    let text = match delta.delta_type.as_str() {
+                        "text_delta" => delta.text,
+                        // Arguments of the forced output tool
+                        "input_json_delta" => delta.partial_json,
+                        _ => return None,
+                    };
+                    Some(Ok(StreamChunk {
+                        text: text.unwrap_or_default(),
+                        finish_reason: None,
+                    }))
                 }
                 AnthropicStreamEvent::MessageDelta { delta, .. } => {
                     let finish_reason = delta
@@ -603,6 +830,58 @@ mod tests {
         assert_eq!(anthropic_request.messages[0].role, "user");
     }
 
+    #[test]
+    fn test_output_schema_forces_tool() {
+        use super::super::traits::OutputSchema;
+
+        let request = CompletionRequest::new(vec![Message::user("Summarize")]).with_output_schema(
+            OutputSchema {
+                name: "thread_summary".to_string(),
+                description: "A summary".to_string(),
+                schema: serde_json::json!({"type": "object"}),
+            },
+        );
+        let provider = AnthropicProvider::claude_sonnet("key");
+        let json = serde_json::to_value(provider.build_request(&request, false)).unwrap();
+
+        assert_eq!(json["tools"][0]["name"], "thread_summary");
+        assert_eq!(json["tools"][0]["input_schema"]["type"], "object");
+        assert_eq!(json["tool_choice"]["type"], "tool");
+        assert_eq!(json["tool_choice"]["name"], "thread_summary");
+
+        let plain = CompletionRequest::new(vec![Message::user("Hi")]);
+        let json = serde_json::to_value(provider.build_request(&plain, false)).unwrap();
+        assert!(json.get("tools").is_none());
+        assert!(json.get("tool_choice").is_none());
+        assert!(provider.supports_structured_output());
+    }
+
+    #[test]
+    fn test_forced_tool_response_text() {
+        let json = r#"{
+            "content": [{"type": "tool_use", "id": "toolu_1", "name": "thread_summary",
+                         "input": {"text": "Ship Friday"}}],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 10, "output_tokens": 5}
+        }"#;
+
+        let response: AnthropicResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            AnthropicProvider::response_text(response.content),
+            r#"{"text":"Ship Friday"}"#
+        );
+
+        let stream =
+            AnthropicStream::new(futures::stream::empty::<Result<bytes::Bytes, std::io::Error>>());
+        let chunk = stream
+            .parse_event(
+                r#"{"type":"content_block_delta","delta":{"type":"input_json_delta","partial_json":"{\"te"}}"#,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(chunk.text, r#"{"te"#);
+    }
+
     #[test]
     fn test_stream_event_parsing() {
         let delta_json =
@@ -617,4 +896,73 @@ mod tests {
             _ => panic!("Expected ContentBlockDelta"),
         }
     }
+
+    #[test]
+    fn test_tool_request_serialization() {
+        use super::super::traits::{ToolDefinition, ToolResult};
+
+        let request = ToolRequest::new(
+            vec![
+                ToolTurn::User("Snooze the lease thread".to_string()),
+                ToolTurn::Assistant {
+                    text: "Snoozing it.".to_string(),
+                    tool_calls: vec![ToolCall {
+                        id: "toolu_1".to_string(),
+                        name: "snooze".to_string(),
+                        arguments: serde_json::json!({"thread_id": "t1"}),
+                    }],
+                },
+                ToolTurn::ToolResults(vec![ToolResult::success("toolu_1", "done")]),
+                ToolTurn::User("Thanks".to_string()),
+            ],
+            vec![ToolDefinition {
+                name: "snooze".to_string(),
+                description: "Snooze a thread".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }],
+        )
+        .with_system_prompt("Be careful");
+
+        let provider = AnthropicProvider::claude_sonnet("key");
+        let json = serde_json::to_value(provider.build_tool_request(&request)).unwrap();
+
+        assert_eq!(json["system"], "Be careful");
+        assert_eq!(json["tools"][0]["input_schema"]["type"], "object");
+        let messages = json["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[1]["content"][1]["input"]["thread_id"], "t1");
+
+        // The tool result and the next user message share one user turn.
+        let results = messages[2]["content"].as_array().unwrap();
+        assert_eq!(results[0]["type"], "tool_result");
+        assert_eq!(results[0]["tool_use_id"], "toolu_1");
+        assert!(results[0].get("is_error").is_none());
+        assert_eq!(results[1]["text"], "Thanks");
+    }
+
+    #[test]
+    fn test_tool_use_response_parsing() {
+        let json = r#"{
+            "content": [
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "id": "toolu_9", "name": "search", "input": {"query": "lease"}},
+                {"type": "thinking", "thinking": "..."}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 30, "output_tokens": 12}
+        }"#;
+
+        let response: AnthropicToolResponse = serde_json::from_str(json).unwrap();
+        let (text, calls) = AnthropicProvider::parse_tool_blocks(response.content);
+
+        assert_eq!(text, "Let me look.");
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, "toolu_9");
+        assert_eq!(calls[0].arguments["query"], "lease");
+        assert_eq!(
+            AnthropicProvider::parse_finish_reason(response.stop_reason.as_deref()),
+            FinishReason::ToolCalls
+        );
+    }
 }
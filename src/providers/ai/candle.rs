@@ -0,0 +1,788 @@
+//! Local LLM provider using Candle.
+//!
+//! Runs quantized GGUF models (Llama, Phi-3 and Qwen2 families) on the CPU,
+//! so summaries, drafts and categorization work without any network access.
+//! Inference runs on Tokio's blocking pool and streams tokens as they are
+//! sampled.
+//!
+//! Requests with an output schema are decoded under a JSON grammar: tokens
+//! that cannot continue a valid JSON object are masked out before sampling,
+//! and generation ends as soon as the object is closed.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use async_trait::async_trait;
+use candle_core::quantized::gguf_file;
+use candle_core::{Device, Tensor};
+use candle_transformers::generation::LogitsProcessor;
+use candle_transformers::models::{quantized_llama, quantized_phi3, quantized_qwen2};
+use candle_transformers::utils::apply_repeat_penalty;
+use futures::channel::mpsc;
+use tokenizers::Tokenizer;
+
+use super::structured::JsonPrefix;
+use super::traits::{
+    estimate_tokens, CompletionRequest, CompletionResponse, CompletionStream, FinishReason,
+    LlmError, LlmProvider, LlmResult, Role, StreamChunk, TokenUsage,
+};
+
+/// Tokens generated when the request does not set `max_tokens`.
+const DEFAULT_MAX_TOKENS: usize = 512;
+
+/// Model architectures supported by the local provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFamily {
+    /// Llama 3 and compatible models.
+    Llama,
+    /// Microsoft Phi-3.
+    Phi3,
+    /// Alibaba Qwen2 and Qwen2.5.
+    Qwen2,
+}
+
+impl ModelFamily {
+    /// Returns the family for a GGUF `general.architecture` value.
+    pub fn from_architecture(architecture: &str) -> Option<Self> {
+        match architecture {
+            "llama" => Some(Self::Llama),
+            "phi3" => Some(Self::Phi3),
+            "qwen2" => Some(Self::Qwen2),
+            _ => None,
+        }
+    }
+
+    /// Returns the GGUF architecture name used as the metadata key prefix.
+    fn architecture(&self) -> &'static str {
+        match self {
+            Self::Llama => "llama",
+            Self::Phi3 => "phi3",
+            Self::Qwen2 => "qwen2",
+        }
+    }
+
+    /// Returns the token that ends an assistant turn in the chat template.
+    fn end_of_turn(&self) -> &'static str {
+        match self {
+            Self::Llama => "<|eot_id|>",
+            Self::Phi3 => "<|end|>",
+            Self::Qwen2 => "<|im_end|>",
+        }
+    }
+
+    /// Formats a request with the family's chat template.
+    ///
+    /// The prompt ends with the header of the assistant turn, so generation
+    /// continues as the assistant.
+    fn format_prompt(&self, request: &CompletionRequest) -> String {
+        let mut turns: Vec<(Role, &str)> = Vec::new();
+        if let Some(system) = &request.system_prompt {
+            turns.push((Role::System, system));
+        }
+        turns.extend(
+            request
+                .messages
+                .iter()
+                .map(|m| (m.role, m.content.as_str())),
+        );
+
+        let role_name = |role: Role| match role {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        };
+
+        let mut prompt = String::new();
+        match self {
+            Self::Llama => {
+                prompt.push_str("<|begin_of_text|>");
+                for (role, content) in turns {
+                    prompt.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role_name(role),
+                        content
+                    ));
+                }
+                prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+            }
+            Self::Phi3 => {
+                for (role, content) in turns {
+                    prompt.push_str(&format!("<|{}|>\n{}<|end|>\n", role_name(role), content));
+                }
+                prompt.push_str("<|assistant|>\n");
+            }
+            Self::Qwen2 => {
+                for (role, content) in turns {
+                    prompt.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role_name(role),
+                        content
+                    ));
+                }
+                prompt.push_str("<|im_start|>assistant\n");
+            }
+        }
+        prompt
+    }
+}
+
+/// Configuration for the local LLM provider.
+#[derive(Debug, Clone)]
+pub struct CandleLlmConfig {
+    /// Path to the quantized GGUF model file.
+    pub model_path: PathBuf,
+    /// Path to `tokenizer.json`. Defaults to the file next to the model.
+    pub tokenizer_path: Option<PathBuf>,
+    /// Seed for sampling, so identical requests give identical output.
+    pub seed: u64,
+    /// Penalty applied to recently generated tokens (1.0 disables it).
+    pub repeat_penalty: f32,
+    /// Number of recent tokens the repeat penalty looks at.
+    pub repeat_last_n: usize,
+}
+
+impl CandleLlmConfig {
+    /// Creates a configuration for the given model file.
+    pub fn new(model_path: impl Into<PathBuf>) -> Self {
+        Self {
+            model_path: model_path.into(),
+            tokenizer_path: None,
+            seed: 299_792_458,
+            repeat_penalty: 1.1,
+            repeat_last_n: 64,
+        }
+    }
+
+    /// Sets the tokenizer file.
+    pub fn with_tokenizer(mut self, path: impl Into<PathBuf>) -> Self {
+        self.tokenizer_path = Some(path.into());
+        self
+    }
+
+    /// Returns the tokenizer file to load.
+    fn tokenizer_path(&self) -> PathBuf {
+        self.tokenizer_path.clone().unwrap_or_else(|| {
+            self.model_path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join("tokenizer.json")
+        })
+    }
+}
+
+/// Loaded model weights for one of the supported families.
+///
+/// Weights are cloned for each request: the tensors are shared, and the
+/// clone starts with an empty key-value cache.
+#[derive(Clone)]
+enum Weights {
+    Llama(quantized_llama::ModelWeights),
+    Phi3(quantized_phi3::ModelWeights),
+    Qwen2(quantized_qwen2::ModelWeights),
+}
+
+impl Weights {
+    fn forward(&mut self, input: &Tensor, index_pos: usize) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Llama(model) => model.forward(input, index_pos),
+            Self::Phi3(model) => model.forward(input, index_pos),
+            Self::Qwen2(model) => model.forward(input, index_pos),
+        }
+    }
+}
+
+/// Provider that runs a quantized GGUF model locally with Candle.
+pub struct CandleLlmProvider {
+    config: CandleLlmConfig,
+    model_name: String,
+    family: ModelFamily,
+    context_length: usize,
+    eos_token_ids: Vec<u32>,
+    weights: Weights,
+    tokenizer: Arc<Tokenizer>,
+    /// Decoded text of every token, built on the first structured request.
+    vocab: OnceLock<Arc<Vec<String>>>,
+    device: Device,
+}
+
+impl CandleLlmProvider {
+    /// Loads a GGUF model and its tokenizer.
+    ///
+    /// Loading reads the whole model into memory and can take several
+    /// seconds; call it from a blocking context or use [`load_async`].
+    ///
+    /// [`load_async`]: Self::load_async
+    pub fn load(config: CandleLlmConfig) -> LlmResult<Self> {
+        let unavailable = |what: &str, e: &dyn std::fmt::Display| {
+            LlmError::Unavailable(format!("{}: {}", what, e))
+        };
+
+        let mut file = File::open(&config.model_path).map_err(|e| {
+            unavailable(&format!("cannot open {}", config.model_path.display()), &e)
+        })?;
+        let content = gguf_file::Content::read(&mut file)
+            .map_err(|e| unavailable("invalid GGUF file", &e))?;
+
+        let architecture = content
+            .metadata
+            .get("general.architecture")
+            .and_then(|v| v.to_string().ok())
+            .cloned()
+            .unwrap_or_default();
+        let family = ModelFamily::from_architecture(&architecture).ok_or_else(|| {
+            LlmError::Unavailable(format!("unsupported model architecture: {}", architecture))
+        })?;
+
+        let context_length = content
+            .metadata
+            .get(&format!("{}.context_length", family.architecture()))
+            .and_then(|v| v.to_u32().ok())
+            .map(|n| n as usize)
+            .ok_or_else(|| LlmError::Unavailable("model has no context length".to_string()))?;
+        let model_name = content
+            .metadata
+            .get("general.name")
+            .and_then(|v| v.to_string().ok())
+            .cloned()
+            .unwrap_or_else(|| {
+                config
+                    .model_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| architecture.clone())
+            });
+        let gguf_eos = content
+            .metadata
+            .get("tokenizer.ggml.eos_token_id")
+            .and_then(|v| v.to_u32().ok());
+
+        let device = Device::Cpu;
+        let weights = match family {
+            ModelFamily::Llama => {
+                quantized_llama::ModelWeights::from_gguf(content, &mut file, &device)
+                    .map(Weights::Llama)
+            }
+            ModelFamily::Phi3 => {
+                quantized_phi3::ModelWeights::from_gguf(false, content, &mut file, &device)
+                    .map(Weights::Phi3)
+            }
+            ModelFamily::Qwen2 => {
+                quantized_qwen2::ModelWeights::from_gguf(content, &mut file, &device)
+                    .map(Weights::Qwen2)
+            }
+        }
+        .map_err(|e| unavailable("failed to load model weights", &e))?;
+
+        let tokenizer_path = config.tokenizer_path();
+        let tokenizer = Tokenizer::from_file(&tokenizer_path).map_err(|e| {
+            unavailable(
+                &format!("cannot load tokenizer {}", tokenizer_path.display()),
+                &e,
+            )
+        })?;
+
+        let mut eos_token_ids: Vec<u32> = gguf_eos.into_iter().collect();
+        if let Some(id) = tokenizer.token_to_id(family.end_of_turn()) {
+            if !eos_token_ids.contains(&id) {
+                eos_token_ids.push(id);
+            }
+        }
+
+        tracing::info!(
+            model = %model_name,
+            family = ?family,
+            context_length,
+            "Loaded local LLM"
+        );
+
+        Ok(Self {
+            config,
+            model_name,
+            family,
+            context_length,
+            eos_token_ids,
+            weights,
+            tokenizer: Arc::new(tokenizer),
+            vocab: OnceLock::new(),
+            device,
+        })
+    }
+
+    /// Loads a model on the blocking thread pool.
+    pub async fn load_async(config: CandleLlmConfig) -> LlmResult<Self> {
+        tokio::task::spawn_blocking(move || Self::load(config))
+            .await
+            .map_err(|e| LlmError::Unavailable(format!("model loading panicked: {}", e)))?
+    }
+
+    /// Returns the architecture family of the loaded model.
+    pub fn family(&self) -> ModelFamily {
+        self.family
+    }
+
+    /// Returns the decoded text of every token in the vocabulary.
+    fn vocab(&self) -> Arc<Vec<String>> {
+        self.vocab
+            .get_or_init(|| {
+                let size = self.tokenizer.get_vocab_size(true) as u32;
+                let pieces = (0..size)
+                    .map(|id| self.tokenizer.decode(&[id], false).unwrap_or_default())
+                    .collect();
+                Arc::new(pieces)
+            })
+            .clone()
+    }
+
+    /// Prepares a generation run for a request.
+    fn generation(&self, request: &CompletionRequest) -> LlmResult<Generation> {
+        let prompt = self.family.format_prompt(request);
+        let encoding = self
+            .tokenizer
+            .encode(prompt, false)
+            .map_err(|e| LlmError::InferenceError(format!("tokenization failed: {}", e)))?;
+        let prompt_tokens = encoding.get_ids().to_vec();
+
+        if prompt_tokens.len() >= self.context_length {
+            return Err(LlmError::ContextLengthExceeded {
+                used: prompt_tokens.len(),
+                max: self.context_length,
+            });
+        }
+        let max_tokens = request
+            .max_tokens
+            .unwrap_or(DEFAULT_MAX_TOKENS)
+            .min(self.context_length - prompt_tokens.len());
+
+        let mut stops = request.stop.clone().unwrap_or_default();
+        stops.push(self.family.end_of_turn().to_string());
+
+        Ok(Generation {
+            weights: self.weights.clone(),
+            tokenizer: Arc::clone(&self.tokenizer),
+            device: self.device.clone(),
+            eos_token_ids: self.eos_token_ids.clone(),
+            logits: LogitsProcessor::new(
+                self.config.seed,
+                Some(f64::from(request.temperature)),
+                None,
+            ),
+            repeat_penalty: self.config.repeat_penalty,
+            repeat_last_n: self.config.repeat_last_n,
+            prompt_tokens,
+            max_tokens,
+            stops: StopSequences::new(stops),
+            grammar: request.output_schema.as_ref().map(|_| JsonGrammar {
+                vocab: self.vocab(),
+                prefix: JsonPrefix::object(),
+            }),
+        })
+    }
+}
+
+/// Restricts sampling to tokens that keep the output a valid JSON object.
+struct JsonGrammar {
+    vocab: Arc<Vec<String>>,
+    prefix: JsonPrefix,
+}
+
+impl JsonGrammar {
+    /// Sets the logits of tokens that would break the JSON to negative
+    /// infinity.
+    ///
+    /// End-of-sequence tokens are always masked: generation stops once the
+    /// object is closed instead.
+    fn mask(&self, logits: &Tensor) -> candle_core::Result<Tensor> {
+        let mut values = logits.to_vec1::<f32>()?;
+        let mut allowed = 0;
+        for (id, value) in values.iter_mut().enumerate() {
+            let accepts = self
+                .vocab
+                .get(id)
+                .is_some_and(|piece| !piece.is_empty() && self.prefix.clone().push_str(piece));
+            if accepts {
+                allowed += 1;
+            } else {
+                *value = f32::NEG_INFINITY;
+            }
+        }
+        if allowed == 0 {
+            candle_core::bail!("no token can continue the JSON output");
+        }
+        Tensor::new(values, logits.device())
+    }
+
+    /// Advances past a sampled token.
+    fn accept(&mut self, token: u32) {
+        if let Some(piece) = self.vocab.get(token as usize) {
+            self.prefix.push_str(piece);
+        }
+    }
+}
+
+/// State of a single generation run, executed on a blocking thread.
+struct Generation {
+    weights: Weights,
+    tokenizer: Arc<Tokenizer>,
+    device: Device,
+    eos_token_ids: Vec<u32>,
+    logits: LogitsProcessor,
+    repeat_penalty: f32,
+    repeat_last_n: usize,
+    prompt_tokens: Vec<u32>,
+    max_tokens: usize,
+    stops: StopSequences,
+    grammar: Option<JsonGrammar>,
+}
+
+impl Generation {
+    /// Generates tokens, passing text to `emit` as it becomes final.
+    ///
+    /// Stops early if `emit` returns false, e.g. because the stream was
+    /// dropped.
+    fn run(mut self, mut emit: impl FnMut(StreamChunk) -> bool) -> LlmResult<CompletionResponse> {
+        let failed = |e: candle_core::Error| LlmError::InferenceError(e.to_string());
+
+        let mut generated: Vec<u32> = Vec::new();
+        let mut decoded_len = 0;
+        let mut finish_reason = FinishReason::Length;
+        let mut input = self.prompt_tokens.clone();
+        let mut index_pos = 0;
+
+        while generated.len() < self.max_tokens {
+            let tensor = Tensor::new(input.as_slice(), &self.device)
+                .and_then(|t| t.unsqueeze(0))
+                .map_err(failed)?;
+            let logits = self
+                .weights
+                .forward(&tensor, index_pos)
+                .and_then(|l| l.squeeze(0))
+                .map_err(failed)?;
+            index_pos += input.len();
+
+            let logits = if self.repeat_penalty == 1.0 || generated.is_empty() {
+                logits
+            } else {
+                let start = generated.len().saturating_sub(self.repeat_last_n);
+                apply_repeat_penalty(&logits, self.repeat_penalty, &generated[start..])
+                    .map_err(failed)?
+            };
+            let logits = match &self.grammar {
+                Some(grammar) => grammar.mask(&logits).map_err(failed)?,
+                None => logits,
+            };
+            let token = self.logits.sample(&logits).map_err(failed)?;
+
+            if self.eos_token_ids.contains(&token) {
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+            generated.push(token);
+            input = vec![token];
+            let closed = self.grammar.as_mut().is_some_and(|grammar| {
+                grammar.accept(token);
+                grammar.prefix.is_complete()
+            });
+
+            // Decode everything generated so far: tokens do not always map to
+            // whole characters, so decoding them one by one can split UTF-8. Text
+            // ending in a replacement character waits for the next token.
+            let text = self
+                .tokenizer
+                .decode(&generated, true)
+                .map_err(|e| LlmError::InferenceError(format!("decoding failed: {}", e)))?;
+            if text.ends_with('\u{FFFD}') {
+                continue;
+            }
+            let Some(piece) = text.get(decoded_len..) else {
+                continue;
+            };
+            decoded_len = text.len();
+
+            let (ready, stopped) = self.stops.push(piece);
+            if !ready.is_empty()
+                && !emit(StreamChunk {
+                    text: ready,
+                    finish_reason: None,
+                })
+            {
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+            if stopped || closed {
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+        }
+
+        let rest = self.stops.finish();
+        emit(StreamChunk {
+            text: rest,
+            finish_reason: Some(finish_reason),
+        });
+
+        Ok(CompletionResponse {
+            text: self.stops.into_text(),
+            tokens_used: TokenUsage {
+                prompt_tokens: self.prompt_tokens.len(),
+                completion_tokens: generated.len(),
+                total_tokens: self.prompt_tokens.len() + generated.len(),
+            },
+            finish_reason,
+        })
+    }
+}
+
+/// Tracks generated text and cuts it at the first stop sequence.
+///
+/// Text that could be the start of a stop sequence is held back until it
+/// is clear whether the sequence completes, so stop sequences never leak
+/// into streamed output.
+#[derive(Debug)]
+struct StopSequences {
+    stops: Vec<String>,
+    text: String,
+    emitted: usize,
+    stopped: bool,
+}
+
+impl StopSequences {
+    fn new(stops: Vec<String>) -> Self {
+        Self {
+            stops: stops.into_iter().filter(|s| !s.is_empty()).collect(),
+            text: String::new(),
+            emitted: 0,
+            stopped: false,
+        }
+    }
+
+    /// Appends generated text, returning what can be emitted and whether a
+    /// stop sequence was reached.
+    fn push(&mut self, piece: &str) -> (String, bool) {
+        if self.stopped {
+            return (String::new(), true);
+        }
+        self.text.push_str(piece);
+
+        if let Some(pos) = self
+            .stops
+            .iter()
+            .filter_map(|stop| self.text.find(stop.as_str()))
+            .min()
+        {
+            self.text.truncate(pos);
+            self.stopped = true;
+            return (self.take_until(pos), true);
+        }
+
+        let held = self.held_back();
+        (self.take_until(self.text.len() - held), false)
+    }
+
+    /// Returns text held back when generation ends without a stop sequence.
+    fn finish(&mut self) -> String {
+        self.take_until(self.text.len())
+    }
+
+    /// Returns the full generated text, without any stop sequence.
+    fn into_text(self) -> String {
+        self.text
+    }
+
+    /// Length of the longest suffix that is a prefix of a stop sequence.
+    fn held_back(&self) -> usize {
+        self.stops
+            .iter()
+            .flat_map(|stop| {
+                (1..stop.len())
+                    .rev()
+                    .filter(|&n| stop.is_char_boundary(n))
+                    .find(|&n| self.text.ends_with(&stop[..n]))
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn take_until(&mut self, end: usize) -> String {
+        let end = end.max(self.emitted);
+        let ready = self.text[self.emitted..end].to_string();
+        self.emitted = end;
+        ready
+    }
+}
+
+#[async_trait]
+impl LlmProvider for CandleLlmProvider {
+    fn name(&self) -> &str {
+        "candle"
+    }
+
+    fn model(&self) -> &str {
+        &self.model_name
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+        let generation = self.generation(request)?;
+        tokio::task::spawn_blocking(move || generation.run(|_| true))
+            .await
+            .map_err(|e| LlmError::InferenceError(format!("inference task failed: {}", e)))?
+    }
+
+    async fn stream_complete(&self, request: &CompletionRequest) -> LlmResult<CompletionStream> {
+        let generation = self.generation(request)?;
+        let (tx, rx) = mpsc::unbounded();
+
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = generation.run(|chunk| tx.unbounded_send(Ok(chunk)).is_ok()) {
+                let _ = tx.unbounded_send(Err(e));
+            }
+        });
+
+        Ok(Box::pin(rx))
+    }
+
+    fn supports_function_calling(&self) -> bool {
+        false
+    }
+
+    fn supports_structured_output(&self) -> bool {
+        true
+    }
+
+    fn max_context_length(&self) -> usize {
+        self.context_length
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        match self.tokenizer.encode(text, false) {
+            Ok(encoding) => encoding.len(),
+            Err(_) => estimate_tokens(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ai::Message;
+
+    #[test]
+    fn test_family_from_architecture() {
+        assert_eq!(
+            ModelFamily::from_architecture("llama"),
+            Some(ModelFamily::Llama)
+        );
+        assert_eq!(
+            ModelFamily::from_architecture("phi3"),
+            Some(ModelFamily::Phi3)
+        );
+        assert_eq!(
+            ModelFamily::from_architecture("qwen2"),
+            Some(ModelFamily::Qwen2)
+        );
+        assert_eq!(ModelFamily::from_architecture("gpt2"), None);
+    }
+
+    #[test]
+    fn test_chat_templates() {
+        let request =
+            CompletionRequest::new(vec![Message::user("Hi")]).with_system_prompt("Be brief.");
+
+        assert_eq!(
+            ModelFamily::Llama.format_prompt(&request),
+            "<|begin_of_text|>\
+             <|start_header_id|>system<|end_header_id|>\n\nBe brief.<|eot_id|>\
+             <|start_header_id|>user<|end_header_id|>\n\nHi<|eot_id|>\
+             <|start_header_id|>assistant<|end_header_id|>\n\n"
+        );
+        assert_eq!(
+            ModelFamily::Phi3.format_prompt(&request),
+            "<|system|>\nBe brief.<|end|>\n<|user|>\nHi<|end|>\n<|assistant|>\n"
+        );
+        assert_eq!(
+            ModelFamily::Qwen2.format_prompt(&request),
+            "<|im_start|>system\nBe brief.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn test_stop_sequence_cuts_output() {
+        let mut stops = StopSequences::new(vec!["\n\nUser:".to_string()]);
+
+        assert_eq!(stops.push("Sure, "), ("Sure, ".to_string(), false));
+        assert_eq!(stops.push("done."), ("done.".to_string(), false));
+        assert_eq!(stops.push("\n\nUs"), (String::new(), false));
+        assert_eq!(stops.push("er: next"), (String::new(), true));
+        assert_eq!(stops.finish(), "");
+        assert_eq!(stops.into_text(), "Sure, done.");
+    }
+
+    #[test]
+    fn test_partial_stop_is_released() {
+        let mut stops = StopSequences::new(vec!["<|end|>".to_string()]);
+
+        assert_eq!(stops.push("a <|"), ("a ".to_string(), false));
+        assert_eq!(stops.push("b"), ("<|b".to_string(), false));
+        assert_eq!(stops.push(" <"), (" ".to_string(), false));
+        assert_eq!(stops.finish(), "<");
+        assert_eq!(stops.into_text(), "a <|b <");
+    }
+
+    #[test]
+    fn test_stop_within_single_piece() {
+        let mut stops = StopSequences::new(vec!["STOP".to_string(), "END".to_string()]);
+
+        assert_eq!(stops.push("xENDySTOP"), ("x".to_string(), true));
+        assert_eq!(stops.push("more"), (String::new(), true));
+        assert_eq!(stops.into_text(), "x");
+    }
+
+    #[test]
+    fn test_json_grammar_masks_tokens() {
+        let vocab = ["{", "\"", "a", "\":", " 1", "}", "Sure", ""]
+            .iter()
+            .map(|piece| piece.to_string())
+            .collect();
+        let mut grammar = JsonGrammar {
+            vocab: Arc::new(vocab),
+            prefix: JsonPrefix::object(),
+        };
+        let logits = Tensor::new(&[1f32; 8], &Device::Cpu).unwrap();
+
+        let allowed = |grammar: &JsonGrammar| -> Vec<usize> {
+            let masked = grammar.mask(&logits).unwrap().to_vec1::<f32>().unwrap();
+            let allowed = masked.iter().enumerate().filter(|(_, v)| v.is_finite());
+            allowed.map(|(id, _)| id).collect()
+        };
+        assert_eq!(allowed(&grammar), vec![0]);
+
+        for token in [0, 1, 2, 3, 4] {
+            grammar.accept(token);
+        }
+        assert_eq!(allowed(&grammar), vec![5]);
+        grammar.accept(5);
+        assert!(grammar.prefix.is_complete());
+    }
+
+    #[test]
+    fn test_default_tokenizer_path() {
+        let config = CandleLlmConfig::new("/models/qwen2.5-1.5b-instruct-q4_k_m.gguf");
+        assert_eq!(
+            config.tokenizer_path(),
+            PathBuf::from("/models/tokenizer.json")
+        );
+
+        let config = config.with_tokenizer("/tokenizers/qwen.json");
+        assert_eq!(
+            config.tokenizer_path(),
+            PathBuf::from("/tokenizers/qwen.json")
+        );
+    }
+
+    #[test]
+    fn test_missing_model_is_unavailable() {
+        let result = CandleLlmProvider::load(CandleLlmConfig::new("/nonexistent/model.gguf"));
+        assert!(matches!(result, Err(LlmError::Unavailable(_))));
+    }
+}
@@ -7,6 +7,14 @@
 //! - **OpenAI-compatible**: Works with OpenAI, vLLM, LM Studio, and other compatible endpoints
 //! - **Anthropic**: Claude models via Anthropic's API
 //! - **Ollama**: Local LLM inference via Ollama
+//! - **Candle**: Fully offline inference of quantized GGUF models (Llama, Phi-3, Qwen2)
+//!
+//! Requests with an [`OutputSchema`] ask for JSON matching the schema;
+//! [`complete_structured`] validates the reply and asks the model to repair
+//! invalid output.
+//!
+//! [`FallbackProvider`] wraps an ordered chain of providers with per-request
+//! timeouts and a circuit breaker.
 //!
 //! # Example
 //!
@@ -37,14 +45,23 @@
 //! ```
 
 mod anthropic;
+mod candle;
 mod ollama;
 mod openai;
+mod routing;
+mod structured;
 mod traits;
 
 pub use anthropic::AnthropicProvider;
+pub use candle::{CandleLlmConfig, CandleLlmProvider, ModelFamily};
 pub use ollama::OllamaProvider;
 pub use openai::OpenAiCompatibleProvider;
+pub use routing::{BreakerPolicy, CircuitBreaker, FallbackProvider, ServedBy, ServedCallback};
+pub use structured::{
+    complete_structured, extract_json, parse_structured, validate, StructuredOutput,
+};
 pub use traits::{
-    CompletionRequest, CompletionResponse, CompletionStream, FinishReason, LlmError, LlmProvider,
-    LlmResult, Message, Role, StreamChunk, TokenUsage,
+    estimate_tokens, CompletionRequest, CompletionResponse, CompletionStream, FinishReason,
+    LlmError, LlmProvider, LlmResult, Message, OutputSchema, Role, StreamChunk, TokenUsage,
+    ToolCall, ToolDefinition, ToolRequest, ToolResponse, ToolResult, ToolTurn,
 };
@@ -110,6 +110,11 @@ impl LlmProvider for OllamaProvider {
         false
     }
 
+    fn supports_structured_output(&self) -> bool {
+        // Ollama compiles `response_format` schemas into a sampling grammar
+        true
+    }
+
     fn max_context_length(&self) -> usize {
         self.inner.max_context_length()
     }
@@ -141,6 +146,7 @@ mod tests {
         assert_eq!(provider.model(), "llama3.2");
         assert_eq!(provider.max_context_length(), 128_000);
         assert!(!provider.supports_function_calling());
+        assert!(provider.supports_structured_output());
     }
 
     #[test]
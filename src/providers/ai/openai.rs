@@ -11,7 +11,8 @@ use std::task::{Context, Poll};
 
 use super::traits::{
     CompletionRequest, CompletionResponse, CompletionStream, FinishReason, LlmError, LlmProvider,
-    LlmResult, Message, Role, StreamChunk, TokenUsage,
+    LlmResult, Message, Role, StreamChunk, TokenUsage, ToolCall, ToolRequest, ToolResponse,
+    ToolTurn,
 };
 
 /// Default base URL for OpenAI API.
@@ -37,6 +38,11 @@ fn supports_functions(model: &str) -> bool {
     model.starts_with("gpt-4") || model.starts_with("gpt-3.5-turbo") || model.starts_with("gpt-4o")
 }
 
+/// Models that support `json_schema` response formats.
+fn supports_json_schema(model: &str) -> bool {
+    model.starts_with("gpt-4o") || model.starts_with("gpt-4.1") || model.starts_with("o1")
+}
+
 /// OpenAI API request format.
 #[derive(Debug, Serialize)]
 struct OpenAiRequest {
@@ -48,10 +54,28 @@ struct OpenAiRequest {
     max_tokens: Option<usize>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<OpenAiResponseFormat>,
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     stream: bool,
 }
 
+/// Structured output format.
+#[derive(Debug, Serialize)]
+struct OpenAiResponseFormat {
+    #[serde(rename = "type")]
+    format_type: &'static str,
+    json_schema: OpenAiJsonSchema,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAiJsonSchema {
+    name: String,
+    description: String,
+    schema: serde_json::Value,
+    strict: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenAiMessage {
     role: String,
@@ -87,6 +111,8 @@ struct OpenAiChoice {
 #[derive(Debug, Deserialize)]
 struct OpenAiResponseMessage {
     content: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<OpenAiToolCall>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -128,6 +154,105 @@ struct OpenAiErrorDetail {
     code: Option<String>,
 }
 
+/// OpenAI tool-calling request format.
+#[derive(Debug, Serialize)]
+struct OpenAiToolRequest {
+    model: String,
+    messages: Vec<OpenAiToolMessage>,
+    tools: Vec<OpenAiTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<usize>,
+}
+
+/// A chat message that may carry tool calls or a tool result.
+#[derive(Debug, Serialize)]
+struct OpenAiToolMessage {
+    role: &'static str,
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OpenAiToolCall>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+impl OpenAiToolMessage {
+    fn text(role: &'static str, content: String) -> Self {
+        Self {
+            role,
+            content: Some(content),
+            tool_calls: Vec::new(),
+            tool_call_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAiTool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    function: OpenAiFunction,
+}
+
+#[derive(Debug, Serialize)]
+struct OpenAiFunction {
+    name: String,
+    description: String,
+    parameters: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiToolCall {
+    id: String,
+    #[serde(rename = "type")]
+    call_type: String,
+    function: OpenAiFunctionCall,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OpenAiFunctionCall {
+    name: String,
+    /// Arguments encoded as a JSON string.
+    arguments: String,
+}
+
+impl From<&ToolCall> for OpenAiToolCall {
+    fn from(call: &ToolCall) -> Self {
+        Self {
+            id: call.id.clone(),
+            call_type: "function".to_string(),
+            function: OpenAiFunctionCall {
+                name: call.name.clone(),
+                arguments: call.arguments.to_string(),
+            },
+        }
+    }
+}
+
+impl TryFrom<OpenAiToolCall> for ToolCall {
+    type Error = LlmError;
+
+    fn try_from(call: OpenAiToolCall) -> LlmResult<Self> {
+        let arguments = if call.function.arguments.trim().is_empty() {
+            serde_json::Value::Object(Default::default())
+        } else {
+            serde_json::from_str(&call.function.arguments).map_err(|e| {
+                LlmError::InvalidResponse(format!(
+                    "Invalid arguments for tool {}: {}",
+                    call.function.name, e
+                ))
+            })?
+        };
+
+        Ok(Self {
+            id: call.id,
+            name: call.function.name,
+            arguments,
+        })
+    }
+}
+
 /// Provider for OpenAI-compatible APIs.
 ///
 /// Works with:
@@ -222,10 +347,79 @@ impl OpenAiCompatibleProvider {
             temperature: Some(request.temperature),
             max_tokens: request.max_tokens,
             stop: request.stop.clone(),
+            // Endpoints without json_schema support ignore the field or reject
+            // it, and structured callers fall back to prompting either way.
+            response_format: request
+                .output_schema
+                .as_ref()
+                .map(|schema| OpenAiResponseFormat {
+                    format_type: "json_schema",
+                    json_schema: OpenAiJsonSchema {
+                        name: schema.name.clone(),
+                        description: schema.description.clone(),
+                        schema: schema.schema.clone(),
+                        strict: true,
+                    },
+                }),
             stream,
         }
     }
 
+    fn build_tool_request(&self, request: &ToolRequest) -> OpenAiToolRequest {
+        let mut messages = Vec::new();
+
+        if let Some(ref system) = request.system_prompt {
+            messages.push(OpenAiToolMessage::text("system", system.clone()));
+        }
+
+        for turn in &request.turns {
+            match turn {
+                ToolTurn::User(text) => {
+                    messages.push(OpenAiToolMessage::text("user", text.clone()))
+                }
+                ToolTurn::Assistant { text, tool_calls } => messages.push(OpenAiToolMessage {
+                    role: "assistant",
+                    content: (!text.is_empty()).then(|| text.clone()),
+                    tool_calls: tool_calls.iter().map(OpenAiToolCall::from).collect(),
+                    tool_call_id: None,
+                }),
+                // Each result is its own message; failures are marked in the text
+                // since the format has no error flag.
+                ToolTurn::ToolResults(results) => {
+                    messages.extend(results.iter().map(|result| OpenAiToolMessage {
+                        role: "tool",
+                        content: Some(if result.is_error {
+                            format!("Error: {}", result.content)
+                        } else {
+                            result.content.clone()
+                        }),
+                        tool_calls: Vec::new(),
+                        tool_call_id: Some(result.call_id.clone()),
+                    }))
+                }
+            }
+        }
+
+        OpenAiToolRequest {
+            model: self.model.clone(),
+            messages,
+            tools: request
+                .tools
+                .iter()
+                .map(|tool| OpenAiTool {
+                    tool_type: "function",
+                    function: OpenAiFunction {
+                        name: tool.name.clone(),
+                        description: tool.description.clone(),
+                        parameters: tool.parameters.clone(),
+                    },
+                })
+                .collect(),
+            temperature: Some(request.temperature),
+            max_tokens: request.max_tokens,
+        }
+    }
+
     fn parse_finish_reason(reason: Option<&str>) -> FinishReason {
         match reason {
             Some("stop") => FinishReason::Stop,
@@ -349,6 +543,61 @@ impl LlmProvider for OpenAiCompatibleProvider {
         supports_functions(&self.model)
     }
 
+    fn supports_structured_output(&self) -> bool {
+        supports_json_schema(&self.model)
+    }
+
+    async fn complete_with_tools(&self, request: &ToolRequest) -> LlmResult<ToolResponse> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let body = self.build_tool_request(request);
+
+        let response = self
+            .client
+            .post(&url)
+            .headers(self.build_headers())
+            .json(&body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(self.handle_error_response(response).await);
+        }
+
+        let api_response: OpenAiResponse = response
+            .json()
+            .await
+            .map_err(|e| LlmError::InvalidResponse(format!("Failed to parse response: {}", e)))?;
+
+        let choice = api_response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| LlmError::InvalidResponse("No choices in response".to_string()))?;
+
+        let tool_calls = choice
+            .message
+            .tool_calls
+            .into_iter()
+            .map(ToolCall::try_from)
+            .collect::<LlmResult<Vec<_>>>()?;
+
+        let tokens_used = api_response
+            .usage
+            .map(|u| TokenUsage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+            })
+            .unwrap_or_default();
+
+        Ok(ToolResponse {
+            text: choice.message.content.unwrap_or_default(),
+            tool_calls,
+            tokens_used,
+            finish_reason: Self::parse_finish_reason(choice.finish_reason.as_deref()),
+        })
+    }
+
     fn max_context_length(&self) -> usize {
         self.context_length
     }
@@ -490,6 +739,37 @@ mod tests {
         assert!(!json.contains("stream"));
     }
 
+    #[test]
+    fn test_supports_json_schema() {
+        assert!(supports_json_schema("gpt-4o-mini"));
+        assert!(!supports_json_schema("gpt-4"));
+        assert!(!supports_json_schema("llama3"));
+    }
+
+    #[test]
+    fn test_openai_request_with_output_schema() {
+        use super::super::traits::OutputSchema;
+
+        let request = CompletionRequest::new(vec![Message::user("Hi")]);
+        let provider = OpenAiCompatibleProvider::openai("key", "gpt-4o");
+        let json = serde_json::to_value(provider.build_request(&request, false)).unwrap();
+        assert!(json.get("response_format").is_none());
+
+        let request = request.with_output_schema(OutputSchema {
+            name: "greeting".to_string(),
+            description: "A greeting".to_string(),
+            schema: serde_json::json!({"type": "object"}),
+        });
+        let json = serde_json::to_value(provider.build_request(&request, false)).unwrap();
+        assert_eq!(json["response_format"]["type"], "json_schema");
+        assert_eq!(json["response_format"]["json_schema"]["name"], "greeting");
+        assert_eq!(json["response_format"]["json_schema"]["strict"], true);
+        assert_eq!(
+            json["response_format"]["json_schema"]["schema"]["type"],
+            "object"
+        );
+    }
+
     #[test]
     fn test_openai_request_with_stream() {
         let request = CompletionRequest::new(vec![Message::user("Hi")]);
@@ -586,4 +866,78 @@ mod tests {
             OpenAiCompatibleProvider::custom("http://localhost:11434/v1/", None, "llama3");
         assert_eq!(provider.base_url, "http://localhost:11434/v1");
     }
+
+    #[test]
+    fn test_tool_request_serialization() {
+        use super::super::traits::{ToolDefinition, ToolResult};
+
+        let request = ToolRequest::new(
+            vec![
+                ToolTurn::User("Archive the newsletter".to_string()),
+                ToolTurn::Assistant {
+                    text: String::new(),
+                    tool_calls: vec![ToolCall {
+                        id: "call_1".to_string(),
+                        name: "archive".to_string(),
+                        arguments: serde_json::json!({"thread_ids": ["t1"]}),
+                    }],
+                },
+                ToolTurn::ToolResults(vec![ToolResult::error("call_1", "Thread not found")]),
+            ],
+            vec![ToolDefinition {
+                name: "archive".to_string(),
+                description: "Archive threads".to_string(),
+                parameters: serde_json::json!({"type": "object"}),
+            }],
+        )
+        .with_system_prompt("Be careful");
+
+        let provider = OpenAiCompatibleProvider::openai("key", "gpt-4o");
+        let json = serde_json::to_value(provider.build_tool_request(&request)).unwrap();
+
+        assert_eq!(json["tools"][0]["type"], "function");
+        assert_eq!(json["tools"][0]["function"]["name"], "archive");
+        assert_eq!(json["messages"][0]["role"], "system");
+        let call = &json["messages"][2]["tool_calls"][0];
+        assert_eq!(call["id"], "call_1");
+        assert_eq!(call["function"]["arguments"], r#"{"thread_ids":["t1"]}"#);
+        assert!(json["messages"][2]["content"].is_null());
+        assert_eq!(json["messages"][3]["role"], "tool");
+        assert_eq!(json["messages"][3]["tool_call_id"], "call_1");
+        assert_eq!(json["messages"][3]["content"], "Error: Thread not found");
+    }
+
+    #[test]
+    fn test_tool_call_response_parsing() {
+        let json = r#"{
+            "choices": [{
+                "message": {
+                    "content": null,
+                    "tool_calls": [{
+                        "id": "call_abc",
+                        "type": "function",
+                        "function": {"name": "search", "arguments": "{\"query\": \"invoice\"}"}
+                    }]
+                },
+                "finish_reason": "tool_calls"
+            }]
+        }"#;
+
+        let response: OpenAiResponse = serde_json::from_str(json).unwrap();
+        let message = response.choices.into_iter().next().unwrap().message;
+        let call = ToolCall::try_from(message.tool_calls.into_iter().next().unwrap()).unwrap();
+        assert_eq!(call.id, "call_abc");
+        assert_eq!(call.name, "search");
+        assert_eq!(call.arguments["query"], "invoice");
+
+        let bad = OpenAiToolCall {
+            id: "call_bad".to_string(),
+            call_type: "function".to_string(),
+            function: OpenAiFunctionCall {
+                name: "search".to_string(),
+                arguments: "{not json".to_string(),
+            },
+        };
+        assert!(ToolCall::try_from(bad).is_err());
+    }
 }
@@ -0,0 +1,487 @@
+//! Fallback chains and circuit breaking across providers.
+//!
+//! A [`FallbackProvider`] tries an ordered chain of providers, giving each
+//! attempt a timeout. Providers that keep failing are skipped for a cooldown
+//! by a shared [`CircuitBreaker`], so an outage costs one timeout per
+//! cooldown instead of one per request.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+
+use super::traits::{
+    CompletionRequest, CompletionResponse, CompletionStream, LlmError, LlmProvider, LlmResult,
+    ToolRequest, ToolResponse,
+};
+
+/// When the circuit breaker stops sending requests to a provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerPolicy {
+    /// Consecutive failures that open the circuit.
+    pub failure_threshold: u32,
+    /// How long an open circuit skips the provider.
+    pub cooldown: Duration,
+}
+
+impl Default for BreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Tracks failures per provider and skips providers that keep failing.
+///
+/// After the cooldown one request is let through; a success closes the
+/// circuit and a failure opens it again.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    states: Mutex<HashMap<String, CircuitState>>,
+}
+
+impl CircuitBreaker {
+    /// Creates a breaker with every circuit closed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether requests may be sent to a provider.
+    pub fn allows(&self, provider: &str) -> bool {
+        let states = self.states.lock().unwrap();
+        match states.get(provider).and_then(|state| state.open_until) {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+
+    /// Records a successful request, closing the provider's circuit.
+    pub fn record_success(&self, provider: &str) {
+        self.states.lock().unwrap().remove(provider);
+    }
+
+    /// Records a failed request.
+    ///
+    /// Opens the circuit once `policy.failure_threshold` requests in a row
+    /// have failed, or right away for the time a rate limit asks to wait.
+    pub fn record_failure(&self, provider: &str, error: &LlmError, policy: &BreakerPolicy) {
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(provider.to_string()).or_default();
+        state.consecutive_failures += 1;
+
+        let now = Instant::now();
+        if let LlmError::RateLimited {
+            retry_after_secs: Some(secs),
+        } = error
+        {
+            state.open_until = Some(now + Duration::from_secs(*secs));
+        } else if state.consecutive_failures >= policy.failure_threshold {
+            state.open_until = Some(now + policy.cooldown);
+        }
+    }
+}
+
+/// The provider that served a request through a [`FallbackProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServedBy {
+    /// Name the provider is registered under.
+    pub provider: String,
+    /// Model of the provider.
+    pub model: String,
+    /// Providers tried, including the one that served the request.
+    pub attempts: usize,
+    /// Time from the first attempt to the response.
+    pub latency: Duration,
+}
+
+/// Callback told which provider served each request.
+pub type ServedCallback = Arc<dyn Fn(ServedBy) + Send + Sync>;
+
+/// A provider that tries an ordered chain of providers.
+///
+/// Each attempt is limited to the timeout. Streams fall back only while
+/// the stream is being opened; an error in the middle of a stream is
+/// passed on.
+///
+/// [`name`](LlmProvider::name) and [`model`](LlmProvider::model) describe
+/// the first provider in the chain; [`last_served`](Self::last_served)
+/// tells which provider actually answered.
+pub struct FallbackProvider {
+    chain: Vec<(String, Arc<dyn LlmProvider>)>,
+    timeout: Duration,
+    breaker: Arc<CircuitBreaker>,
+    policy: BreakerPolicy,
+    on_served: Option<ServedCallback>,
+    last_served: Mutex<Option<ServedBy>>,
+}
+
+impl FallbackProvider {
+    /// Creates a provider over `chain`, given as `(name, provider)` pairs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chain` is empty.
+    pub fn new(
+        chain: Vec<(String, Arc<dyn LlmProvider>)>,
+        timeout: Duration,
+        breaker: Arc<CircuitBreaker>,
+    ) -> Self {
+        assert!(!chain.is_empty(), "a fallback chain needs a provider");
+        Self {
+            chain,
+            timeout,
+            breaker,
+            policy: BreakerPolicy::default(),
+            on_served: None,
+            last_served: Mutex::new(None),
+        }
+    }
+
+    /// Sets when the circuit breaker skips a provider.
+    pub fn with_policy(mut self, policy: BreakerPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets a callback told which provider served each request.
+    pub fn on_served(mut self, callback: ServedCallback) -> Self {
+        self.on_served = Some(callback);
+        self
+    }
+
+    /// Returns the provider names in the order they are tried.
+    pub fn chain(&self) -> Vec<&str> {
+        self.chain.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Returns the provider that served the most recent request, or `None`
+    /// if no request has succeeded yet.
+    pub fn last_served(&self) -> Option<ServedBy> {
+        self.last_served.lock().unwrap().clone()
+    }
+
+    fn primary(&self) -> &Arc<dyn LlmProvider> {
+        &self.chain[0].1
+    }
+
+    /// Runs `call` against each provider in turn until one succeeds.
+    async fn attempt<'a, T, F, Fut>(&'a self, call: F) -> LlmResult<T>
+    where
+        F: Fn(&'a Arc<dyn LlmProvider>) -> Fut,
+        Fut: Future<Output = LlmResult<T>>,
+    {
+        let started = Instant::now();
+        let mut attempts = 0;
+        let mut last_error = None;
+
+        for (name, provider) in &self.chain {
+            if !self.breaker.allows(name) {
+                tracing::debug!(provider = %name, "Skipping provider with open circuit");
+                continue;
+            }
+            attempts += 1;
+
+            let result = match tokio::time::timeout(self.timeout, call(provider)).await {
+                Ok(result) => result,
+                Err(_) => Err(LlmError::Unavailable(format!(
+                    "{} timed out after {:?}",
+                    name, self.timeout
+                ))),
+            };
+
+            match result {
+                Ok(value) => {
+                    self.breaker.record_success(name);
+                    let served = ServedBy {
+                        provider: name.clone(),
+                        model: provider.model().to_string(),
+                        attempts,
+                        latency: started.elapsed(),
+                    };
+                    *self.last_served.lock().unwrap() = Some(served.clone());
+                    if let Some(on_served) = &self.on_served {
+                        on_served(served);
+                    }
+                    return Ok(value);
+                }
+                Err(e) => {
+                    tracing::warn!(provider = %name, error = %e, "Provider failed, trying next");
+                    self.breaker.record_failure(name, &e, &self.policy);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            LlmError::Unavailable(format!(
+                "No provider available, circuits open for {}",
+                self.chain().join(", ")
+            ))
+        }))
+    }
+}
+
+#[async_trait]
+impl LlmProvider for FallbackProvider {
+    /// Name of the first provider in the chain.
+    fn name(&self) -> &str {
+        self.primary().name()
+    }
+
+    async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+        self.attempt(|provider| provider.complete(request)).await
+    }
+
+    async fn stream_complete(&self, request: &CompletionRequest) -> LlmResult<CompletionStream> {
+        self.attempt(|provider| provider.stream_complete(request))
+            .await
+    }
+
+    fn supports_function_calling(&self) -> bool {
+        self.primary().supports_function_calling()
+    }
+
+    async fn complete_with_tools(&self, request: &ToolRequest) -> LlmResult<ToolResponse> {
+        self.attempt(|provider| provider.complete_with_tools(request))
+            .await
+    }
+
+    /// Whether every provider in the chain enforces output schemas, since
+    /// any of them may serve the request.
+    fn supports_structured_output(&self) -> bool {
+        self.chain
+            .iter()
+            .all(|(_, provider)| provider.supports_structured_output())
+    }
+
+    /// The smallest context window in the chain, so prompts fit any
+    /// provider that may serve them.
+    fn max_context_length(&self) -> usize {
+        self.chain
+            .iter()
+            .map(|(_, provider)| provider.max_context_length())
+            .min()
+            .unwrap_or_default()
+    }
+
+    fn count_tokens(&self, text: &str) -> usize {
+        self.primary().count_tokens(text)
+    }
+
+    /// Model of the first provider in the chain.
+    fn model(&self) -> &str {
+        self.primary().model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ai::{FinishReason, Message, TokenUsage};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provider that fails, hangs or answers with its name.
+    struct TestProvider {
+        name: &'static str,
+        behavior: Behavior,
+        calls: AtomicUsize,
+    }
+
+    enum Behavior {
+        Answer,
+        Fail,
+        Hang,
+    }
+
+    impl TestProvider {
+        fn new(name: &'static str, behavior: Behavior) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                behavior,
+                calls: AtomicUsize::new(0),
+            })
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for TestProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn complete(&self, _request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.behavior {
+                Behavior::Answer => Ok(CompletionResponse {
+                    text: self.name.to_string(),
+                    tokens_used: TokenUsage::default(),
+                    finish_reason: FinishReason::Stop,
+                }),
+                Behavior::Fail => Err(LlmError::ApiError {
+                    status: 503,
+                    message: "overloaded".to_string(),
+                }),
+                Behavior::Hang => std::future::pending().await,
+            }
+        }
+
+        async fn stream_complete(
+            &self,
+            _request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            Err(LlmError::Unavailable("no streaming".to_string()))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn max_context_length(&self) -> usize {
+            match self.behavior {
+                Behavior::Answer => 4096,
+                _ => 200_000,
+            }
+        }
+
+        fn model(&self) -> &str {
+            "test-model"
+        }
+    }
+
+    fn chain(providers: &[&Arc<TestProvider>]) -> Vec<(String, Arc<dyn LlmProvider>)> {
+        providers
+            .iter()
+            .map(|p| (p.name.to_string(), Arc::clone(*p) as Arc<dyn LlmProvider>))
+            .collect()
+    }
+
+    fn request() -> CompletionRequest {
+        CompletionRequest::new(vec![Message::user("Hi")])
+    }
+
+    #[tokio::test]
+    async fn falls_back_and_reports_serving_provider() {
+        let cloud = TestProvider::new("cloud", Behavior::Fail);
+        let local = TestProvider::new("local", Behavior::Answer);
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let log = served.clone();
+
+        let provider = FallbackProvider::new(
+            chain(&[&cloud, &local]),
+            Duration::from_secs(5),
+            Arc::new(CircuitBreaker::new()),
+        )
+        .on_served(Arc::new(move |by| log.lock().unwrap().push(by)));
+
+        assert_eq!(provider.last_served(), None);
+        let response = provider.complete(&request()).await.unwrap();
+        assert_eq!(response.text, "local");
+        assert_eq!(provider.name(), "cloud");
+        assert_eq!(provider.max_context_length(), 4096);
+        assert_eq!(provider.last_served().unwrap().provider, "local");
+
+        let served = served.lock().unwrap();
+        assert_eq!(served[0].provider, "local");
+        assert_eq!(served[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn hung_provider_times_out() {
+        let slow = TestProvider::new("slow", Behavior::Hang);
+        let local = TestProvider::new("local", Behavior::Answer);
+        let provider = FallbackProvider::new(
+            chain(&[&slow, &local]),
+            Duration::from_millis(20),
+            Arc::new(CircuitBreaker::new()),
+        );
+
+        let response = provider.complete(&request()).await.unwrap();
+        assert_eq!(response.text, "local");
+
+        let only_slow = FallbackProvider::new(
+            chain(&[&slow]),
+            Duration::from_millis(20),
+            Arc::new(CircuitBreaker::new()),
+        );
+        let error = only_slow.complete(&request()).await.unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_repeated_failures() {
+        let cloud = TestProvider::new("cloud", Behavior::Fail);
+        let local = TestProvider::new("local", Behavior::Answer);
+        let breaker = Arc::new(CircuitBreaker::new());
+        let provider = FallbackProvider::new(
+            chain(&[&cloud, &local]),
+            Duration::from_secs(5),
+            breaker.clone(),
+        )
+        .with_policy(BreakerPolicy {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(60),
+        });
+
+        for _ in 0..4 {
+            provider.complete(&request()).await.unwrap();
+        }
+        assert_eq!(cloud.calls(), 2);
+        assert_eq!(local.calls(), 4);
+        assert!(!breaker.allows("cloud"));
+
+        breaker.record_success("cloud");
+        assert!(breaker.allows("cloud"));
+    }
+
+    #[tokio::test]
+    async fn rate_limit_opens_circuit_immediately() {
+        let breaker = CircuitBreaker::new();
+        breaker.record_failure(
+            "cloud",
+            &LlmError::RateLimited {
+                retry_after_secs: Some(30),
+            },
+            &BreakerPolicy::default(),
+        );
+        assert!(!breaker.allows("cloud"));
+
+        breaker.record_failure(
+            "other",
+            &LlmError::InvalidResponse("bad".to_string()),
+            &BreakerPolicy::default(),
+        );
+        assert!(breaker.allows("other"));
+    }
+
+    #[tokio::test]
+    async fn all_circuits_open_is_an_error() {
+        let cloud = TestProvider::new("cloud", Behavior::Answer);
+        let breaker = Arc::new(CircuitBreaker::new());
+        breaker.record_failure(
+            "cloud",
+            &LlmError::RateLimited {
+                retry_after_secs: Some(30),
+            },
+            &BreakerPolicy::default(),
+        );
+
+        let provider = FallbackProvider::new(chain(&[&cloud]), Duration::from_secs(5), breaker);
+        let error = provider.complete(&request()).await.unwrap_err();
+        assert!(matches!(error, LlmError::Unavailable(_)));
+        assert_eq!(cloud.calls(), 0);
+    }
+}
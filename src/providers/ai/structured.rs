@@ -0,0 +1,695 @@
+//! Structured JSON output.
+//!
+//! Tasks that read fields out of a reply describe them with an
+//! [`OutputSchema`] instead of asking for an ad-hoc text format. Replies are
+//! extracted, validated against the schema and deserialized; invalid replies
+//! are sent back to the model with the validation error for a bounded number
+//! of repair attempts.
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::traits::{CompletionRequest, LlmError, LlmProvider, LlmResult, Message, OutputSchema};
+
+/// Times an invalid reply is sent back to the model for repair.
+const MAX_REPAIR_ATTEMPTS: usize = 2;
+
+/// Consecutive whitespace characters allowed outside strings while decoding
+/// under [`JsonPrefix`], so a local model cannot pad forever.
+const MAX_WHITESPACE_RUN: usize = 24;
+
+/// A type that can be requested as structured output.
+pub trait StructuredOutput: DeserializeOwned {
+    /// Returns the schema replies are validated against.
+    fn output_schema() -> OutputSchema;
+}
+
+/// Requests a reply matching `T`'s schema and deserializes it.
+///
+/// Providers that cannot enforce the schema themselves get it in the system
+/// prompt. A reply that is not valid JSON or does not match the schema is
+/// returned to the model with the error, up to [`MAX_REPAIR_ATTEMPTS`]
+/// times, before failing with [`LlmError::InvalidResponse`].
+pub async fn complete_structured<T: StructuredOutput>(
+    provider: &dyn LlmProvider,
+    request: &CompletionRequest,
+) -> LlmResult<T> {
+    let schema = T::output_schema();
+    let mut request = request.clone().with_output_schema(schema.clone());
+    if !provider.supports_structured_output() {
+        let instructions = schema_instructions(&schema);
+        request.system_prompt = Some(match request.system_prompt.take() {
+            Some(prompt) => format!("{}\n\n{}", prompt, instructions),
+            None => instructions,
+        });
+    }
+
+    let mut attempt = 0;
+    loop {
+        let response = provider.complete(&request).await?;
+        let error = match parse_structured::<T>(&response.text, &schema.schema) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+
+        if attempt == MAX_REPAIR_ATTEMPTS {
+            return Err(LlmError::InvalidResponse(format!(
+                "{} did not match its schema: {}",
+                schema.name, error
+            )));
+        }
+        attempt += 1;
+        tracing::debug!(schema = %schema.name, attempt, %error, "Repairing structured output");
+
+        request.messages.push(Message::assistant(response.text));
+        request.messages.push(Message::user(format!(
+            "That reply is invalid: {}. Reply again with only the corrected JSON object.",
+            error
+        )));
+    }
+}
+
+/// Parses and validates a reply, returning a description of what is wrong.
+pub fn parse_structured<T: DeserializeOwned>(text: &str, schema: &Value) -> Result<T, String> {
+    let json = extract_json(text).ok_or_else(|| "no JSON object found".to_string())?;
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("invalid JSON: {}", e))?;
+    validate(schema, &value)?;
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Returns the JSON object in a reply, ignoring code fences and any prose
+/// around it.
+pub fn extract_json(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    (start < end).then(|| &text[start..=end])
+}
+
+/// Validates a value against a JSON Schema.
+///
+/// Supports the subset task schemas use: `type`, `enum`, `properties`,
+/// `required`, `additionalProperties: false`, `items`, `minItems`,
+/// `maxItems`, `minimum` and `maximum`. Errors name the offending path.
+pub fn validate(schema: &Value, value: &Value) -> Result<(), String> {
+    validate_at(schema, value, "$")
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Result<(), String> {
+    if let Some(types) = schema.get("type") {
+        let allowed: Vec<&str> = match types {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|name| has_type(value, name)) {
+            return Err(format!("{} should be {}", path, allowed.join(" or ")));
+        }
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum") {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            return Err(format!("{} should be one of {}", path, options.join(", ")));
+        }
+    }
+
+    if let (Some(number), Some(minimum)) = (
+        value.as_f64(),
+        schema.get("minimum").and_then(Value::as_f64),
+    ) {
+        if number < minimum {
+            return Err(format!("{} should be at least {}", path, minimum));
+        }
+    }
+    if let (Some(number), Some(maximum)) = (
+        value.as_f64(),
+        schema.get("maximum").and_then(Value::as_f64),
+    ) {
+        if number > maximum {
+            return Err(format!("{} should be at most {}", path, maximum));
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    return Err(format!("{} is missing \"{}\"", path, name));
+                }
+            }
+        }
+        for (name, field) in fields {
+            match properties.and_then(|p| p.get(name)) {
+                Some(field_schema) => {
+                    validate_at(field_schema, field, &format!("{}.{}", path, name))?
+                }
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{} has unexpected field \"{}\"", path, name));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let Value::Array(items) = value {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                return Err(format!("{} should have at least {} items", path, min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if items.len() as u64 > max {
+                return Err(format!("{} should have at most {} items", path, max));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                validate_at(item_schema, item, &format!("{}[{}]", path, i))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+/// Prompt text asking for a reply that matches `schema`.
+fn schema_instructions(schema: &OutputSchema) -> String {
+    format!(
+        "Reply with only a JSON object ({}) matching this JSON Schema, with no other text:\n{}",
+        schema.description, schema.schema
+    )
+}
+
+/// Incremental recognizer for JSON objects, used to constrain decoding.
+///
+/// Accepts text one character at a time and rejects any character that
+/// cannot continue a valid JSON object, so a sampler can mask out tokens
+/// that would break the syntax. Schema conformance is checked afterwards
+/// by [`validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct JsonPrefix {
+    stack: Vec<Container>,
+    state: State,
+    whitespace_run: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Before the opening brace.
+    Start,
+    /// Expecting a value.
+    Value,
+    /// After `[`: a value or `]`.
+    FirstItem,
+    /// After `{`: a key or `}`.
+    FirstKey,
+    /// After `,` in an object: a key.
+    Key,
+    /// After a key: `:`.
+    Colon,
+    String {
+        key: bool,
+        escape: Escape,
+    },
+    Number(NumberPhase),
+    Literal(&'static str),
+    /// After a value in a container: `,` or the closing bracket.
+    AfterValue,
+    /// The top-level object is closed.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    Backslash,
+    Unicode(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumberPhase {
+    Minus,
+    Zero,
+    Integer,
+    Dot,
+    Fraction,
+    Exponent,
+    ExponentSign,
+    ExponentDigits,
+}
+
+impl NumberPhase {
+    fn next(self, c: char) -> Option<Self> {
+        use NumberPhase::*;
+        match (self, c) {
+            (Minus, '0') => Some(Zero),
+            (Minus, '1'..='9') => Some(Integer),
+            (Integer, '0'..='9') => Some(Integer),
+            (Zero | Integer, '.') => Some(Dot),
+            (Dot | Fraction, '0'..='9') => Some(Fraction),
+            (Zero | Integer | Fraction, 'e' | 'E') => Some(Exponent),
+            (Exponent, '+' | '-') => Some(ExponentSign),
+            (Exponent | ExponentSign | ExponentDigits, '0'..='9') => Some(ExponentDigits),
+            _ => None,
+        }
+    }
+
+    fn is_complete(self) -> bool {
+        matches!(
+            self,
+            NumberPhase::Zero
+                | NumberPhase::Integer
+                | NumberPhase::Fraction
+                | NumberPhase::ExponentDigits
+        )
+    }
+}
+
+impl JsonPrefix {
+    /// Creates a recognizer expecting a JSON object.
+    pub(super) fn object() -> Self {
+        Self {
+            stack: Vec::new(),
+            state: State::Start,
+            whitespace_run: 0,
+        }
+    }
+
+    /// Whether the top-level object is closed.
+    pub(super) fn is_complete(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Feeds text, returning false if it cannot continue the object.
+    ///
+    /// After a rejection the recognizer is in an unspecified state, so
+    /// callers test candidates on a clone.
+    pub(super) fn push_str(&mut self, text: &str) -> bool {
+        text.chars().all(|c| self.push(c))
+    }
+
+    fn push(&mut self, c: char) -> bool {
+        let outside_string = !matches!(self.state, State::String { .. });
+        if outside_string && matches!(c, ' ' | '\t' | '\n' | '\r') {
+            return self.whitespace(c);
+        }
+        if outside_string {
+            self.whitespace_run = 0;
+        }
+
+        match self.state {
+            State::Start => {
+                if c != '{' {
+                    return false;
+                }
+                self.open(Container::Object);
+                true
+            }
+            State::Value => self.start_value(c),
+            State::FirstItem if c == ']' => self.close(Container::Array),
+            State::FirstItem => self.start_value(c),
+            State::FirstKey if c == '}' => self.close(Container::Object),
+            State::FirstKey | State::Key => {
+                if c != '"' {
+                    return false;
+                }
+                self.state = State::String {
+                    key: true,
+                    escape: Escape::None,
+                };
+                true
+            }
+            State::Colon => {
+                if c != ':' {
+                    return false;
+                }
+                self.state = State::Value;
+                true
+            }
+            State::String { key, escape } => self.string(c, key, escape),
+            State::Number(phase) => match phase.next(c) {
+                Some(next) => {
+                    self.state = State::Number(next);
+                    true
+                }
+                None if phase.is_complete() => {
+                    self.end_value();
+                    self.push(c)
+                }
+                None => false,
+            },
+            State::Literal(rest) => {
+                let mut chars = rest.chars();
+                if chars.next() != Some(c) {
+                    return false;
+                }
+                let rest = chars.as_str();
+                if rest.is_empty() {
+                    self.end_value();
+                } else {
+                    self.state = State::Literal(rest);
+                }
+                true
+            }
+            State::AfterValue => match (c, self.stack.last()) {
+                (',', Some(Container::Object)) => {
+                    self.state = State::Key;
+                    true
+                }
+                (',', Some(Container::Array)) => {
+                    self.state = State::Value;
+                    true
+                }
+                ('}', _) => self.close(Container::Object),
+                (']', _) => self.close(Container::Array),
+                _ => false,
+            },
+            State::Done => false,
+        }
+    }
+
+    fn whitespace(&mut self, c: char) -> bool {
+        if let State::Number(phase) = self.state {
+            if !phase.is_complete() {
+                return false;
+            }
+            self.end_value();
+        }
+        if matches!(self.state, State::Done | State::Literal(_)) {
+            return false;
+        }
+        self.whitespace_run += 1;
+        // Newlines reset the run so indented output stays possible
+        if c == '\n' {
+            self.whitespace_run = 1;
+        }
+        self.whitespace_run <= MAX_WHITESPACE_RUN
+    }
+
+    fn start_value(&mut self, c: char) -> bool {
+        self.state = match c {
+            '{' => {
+                self.open(Container::Object);
+                return true;
+            }
+            '[' => {
+                self.open(Container::Array);
+                return true;
+            }
+            '"' => State::String {
+                key: false,
+                escape: Escape::None,
+            },
+            '-' => State::Number(NumberPhase::Minus),
+            '0' => State::Number(NumberPhase::Zero),
+            '1'..='9' => State::Number(NumberPhase::Integer),
+            't' => State::Literal("rue"),
+            'f' => State::Literal("alse"),
+            'n' => State::Literal("ull"),
+            _ => return false,
+        };
+        true
+    }
+
+    fn string(&mut self, c: char, key: bool, escape: Escape) -> bool {
+        let escape = match escape {
+            Escape::None => match c {
+                '"' => {
+                    if key {
+                        self.state = State::Colon;
+                    } else {
+                        self.end_value();
+                    }
+                    return true;
+                }
+                '\\' => Escape::Backslash,
+                c if (c as u32) < 0x20 => return false,
+                _ => Escape::None,
+            },
+            Escape::Backslash => match c {
+                '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' => Escape::None,
+                'u' => Escape::Unicode(0),
+                _ => return false,
+            },
+            Escape::Unicode(n) => {
+                if !c.is_ascii_hexdigit() {
+                    return false;
+                }
+                if n == 3 {
+                    Escape::None
+                } else {
+                    Escape::Unicode(n + 1)
+                }
+            }
+        };
+        self.state = State::String { key, escape };
+        true
+    }
+
+    fn open(&mut self, container: Container) {
+        self.stack.push(container);
+        self.state = match container {
+            Container::Object => State::FirstKey,
+            Container::Array => State::FirstItem,
+        };
+    }
+
+    fn close(&mut self, container: Container) -> bool {
+        if self.stack.last() != Some(&container) {
+            return false;
+        }
+        self.stack.pop();
+        self.end_value();
+        true
+    }
+
+    fn end_value(&mut self) {
+        self.state = if self.stack.is_empty() {
+            State::Done
+        } else {
+            State::AfterValue
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ai::{
+        CompletionResponse, CompletionStream, FinishReason, LlmProvider, TokenUsage,
+    };
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Verdict {
+        label: String,
+        score: f64,
+    }
+
+    impl StructuredOutput for Verdict {
+        fn output_schema() -> OutputSchema {
+            OutputSchema {
+                name: "verdict".to_string(),
+                description: "A label and a score".to_string(),
+                schema: json!({
+                    "type": "object",
+                    "properties": {
+                        "label": {"type": "string", "enum": ["spam", "ham"]},
+                        "score": {"type": "number", "minimum": 0, "maximum": 1}
+                    },
+                    "required": ["label", "score"],
+                    "additionalProperties": false
+                }),
+            }
+        }
+    }
+
+    /// Provider that replies with each of its scripted texts in turn.
+    struct ScriptedProvider {
+        replies: Mutex<Vec<&'static str>>,
+        requests: Mutex<Vec<CompletionRequest>>,
+        native: bool,
+    }
+
+    impl ScriptedProvider {
+        fn new(replies: Vec<&'static str>, native: bool) -> Self {
+            Self {
+                replies: Mutex::new(replies),
+                requests: Mutex::new(Vec::new()),
+                native,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(CompletionResponse {
+                text: self.replies.lock().unwrap().remove(0).to_string(),
+                tokens_used: TokenUsage::default(),
+                finish_reason: FinishReason::Stop,
+            })
+        }
+
+        async fn stream_complete(
+            &self,
+            _request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            Ok(Box::pin(futures::stream::empty()))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn supports_structured_output(&self) -> bool {
+            self.native
+        }
+
+        fn max_context_length(&self) -> usize {
+            4096
+        }
+
+        fn model(&self) -> &str {
+            "scripted-model"
+        }
+    }
+
+    #[test]
+    fn extracts_json_from_prose_and_fences() {
+        assert_eq!(
+            extract_json("Sure! ```json\n{\"a\": {\"b\": 1}}\n``` Hope that helps."),
+            Some("{\"a\": {\"b\": 1}}")
+        );
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[test]
+    fn validation_reports_path() {
+        let schema = Verdict::output_schema().schema;
+        assert!(validate(&schema, &json!({"label": "spam", "score": 0.9})).is_ok());
+
+        let error = validate(&schema, &json!({"label": "eggs", "score": 0.9})).unwrap_err();
+        assert!(error.starts_with("$.label should be one of"));
+        assert_eq!(
+            validate(&schema, &json!({"label": "ham"})).unwrap_err(),
+            "$ is missing \"score\""
+        );
+        assert_eq!(
+            validate(&schema, &json!({"label": "ham", "score": 2})).unwrap_err(),
+            "$.score should be at most 1"
+        );
+        assert_eq!(
+            validate(&schema, &json!({"label": "ham", "score": 0, "why": ""})).unwrap_err(),
+            "$ has unexpected field \"why\""
+        );
+
+        let list = json!({"type": "array", "items": {"type": "integer"}, "maxItems": 2});
+        assert_eq!(
+            validate(&list, &json!([1, "two"])).unwrap_err(),
+            "$[1] should be integer"
+        );
+        assert!(validate(&list, &json!([1, 2, 3])).is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_reply_is_repaired() {
+        let provider = ScriptedProvider::new(
+            vec![
+                "Label: spam",
+                r#"{"label": "junk", "score": 0.8}"#,
+                r#"{"label": "spam", "score": 0.8}"#,
+            ],
+            false,
+        );
+        let request = CompletionRequest::new(vec![Message::user("Classify")])
+            .with_system_prompt("You classify mail.");
+
+        let verdict: Verdict = complete_structured(&provider, &request).await.unwrap();
+        assert_eq!(verdict.label, "spam");
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0]
+            .system_prompt
+            .as_deref()
+            .unwrap()
+            .contains("\"enum\":[\"spam\",\"ham\"]"));
+        assert_eq!(requests[0].output_schema.as_ref().unwrap().name, "verdict");
+
+        // The repair prompt carries the previous reply and the error
+        let last = &requests[2].messages;
+        assert_eq!(last.len(), 5);
+        assert_eq!(last[3].content, r#"{"label": "junk", "score": 0.8}"#);
+        assert!(last[4].content.contains("$.label should be one of"));
+    }
+
+    #[tokio::test]
+    async fn repair_gives_up() {
+        let provider = ScriptedProvider::new(vec!["no", "still no", "nope"], true);
+        let request = CompletionRequest::new(vec![Message::user("Classify")]);
+
+        let result = complete_structured::<Verdict>(&provider, &request).await;
+        assert!(matches!(result, Err(LlmError::InvalidResponse(_))));
+
+        // Native providers enforce the schema, so it is not added to the prompt
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), MAX_REPAIR_ATTEMPTS + 1);
+        assert!(requests[0].system_prompt.is_none());
+    }
+
+    #[test]
+    fn json_prefix_accepts_valid_objects() {
+        for text in [
+            r#"{}"#,
+            r#"{"a": 1, "b": [true, false, null], "c": {"d": "x\"yé"}}"#,
+            r#"{ "n": -0.5e+10, "m": [] }"#,
+        ] {
+            let mut prefix = JsonPrefix::object();
+            assert!(prefix.push_str(text), "{text}");
+            assert!(prefix.is_complete(), "{text}");
+        }
+    }
+
+    #[test]
+    fn json_prefix_rejects_invalid_continuations() {
+        let mut prefix = JsonPrefix::object();
+        assert!(prefix.push_str(r#"{"label": "sp"#));
+        assert!(!prefix.is_complete());
+
+        assert!(!prefix.clone().push_str("\n"));
+        assert!(!JsonPrefix::object().push_str("Sure, {"));
+        assert!(!JsonPrefix::object().push_str(r#"{"a": 01}"#));
+        assert!(!JsonPrefix::object().push_str(r#"{"a": tru }"#));
+        assert!(!JsonPrefix::object().push_str(r#"{"a": 1]"#));
+        assert!(!JsonPrefix::object().push_str(r#"{"a": 1} more"#));
+        assert!(!JsonPrefix::object().push_str(&format!("{{{}", " ".repeat(30))));
+    }
+}
@@ -32,6 +32,9 @@ pub enum LlmError {
 
     #[error("Provider not available: {0}")]
     Unavailable(String),
+
+    #[error("Local inference failed: {0}")]
+    InferenceError(String),
 }
 
 /// Result type for LLM operations.
@@ -97,6 +100,10 @@ pub struct CompletionRequest {
     /// Stop sequences that will halt generation.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<Vec<String>>,
+
+    /// Schema the reply must match, as a JSON object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<OutputSchema>,
 }
 
 fn default_temperature() -> f32 {
@@ -111,6 +118,7 @@ impl Default for CompletionRequest {
             temperature: default_temperature(),
             max_tokens: None,
             stop: None,
+            output_schema: None,
         }
     }
 }
@@ -137,6 +145,28 @@ impl CompletionRequest {
         self.max_tokens = Some(max_tokens);
         self
     }
+
+    pub fn with_output_schema(mut self, schema: OutputSchema) -> Self {
+        self.output_schema = Some(schema);
+        self
+    }
+}
+
+/// JSON Schema that a structured reply must match.
+///
+/// Providers enforce it natively where they can: `response_format` for
+/// OpenAI-compatible endpoints, a forced tool call for Anthropic and
+/// grammar-constrained decoding for local models.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputSchema {
+    /// Name of the output, e.g. `thread_summary`.
+    pub name: String,
+
+    /// What the output describes.
+    pub description: String,
+
+    /// JSON Schema of the output object.
+    pub schema: serde_json::Value,
 }
 
 /// Token usage statistics from a completion.
@@ -199,6 +229,158 @@ pub struct StreamChunk {
 /// Type alias for the streaming response.
 pub type CompletionStream = Pin<Box<dyn Stream<Item = LlmResult<StreamChunk>> + Send>>;
 
+/// A tool the model may call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    /// Tool name the model calls it by.
+    pub name: String,
+
+    /// What the tool does and when to use it.
+    pub description: String,
+
+    /// JSON Schema of the tool's arguments.
+    pub parameters: serde_json::Value,
+}
+
+/// A tool call requested by the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Provider-assigned ID, echoed back with the result.
+    pub id: String,
+
+    /// Name of the tool to call.
+    pub name: String,
+
+    /// Arguments as a JSON object.
+    pub arguments: serde_json::Value,
+}
+
+/// Output of a tool call, returned to the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+    /// ID of the call this answers.
+    pub call_id: String,
+
+    /// Tool output, usually JSON.
+    pub content: String,
+
+    /// Whether the call failed.
+    pub is_error: bool,
+}
+
+impl ToolResult {
+    pub fn success(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            call_id: call_id.into(),
+            content: content.into(),
+            is_error: false,
+        }
+    }
+
+    pub fn error(call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self {
+            call_id: call_id.into(),
+            content: content.into(),
+            is_error: true,
+        }
+    }
+}
+
+/// A turn in a tool-calling conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ToolTurn {
+    /// A user message.
+    User(String),
+
+    /// A model reply, with any tools it called.
+    Assistant {
+        text: String,
+        tool_calls: Vec<ToolCall>,
+    },
+
+    /// Results of the tools called in the previous assistant turn.
+    ToolResults(Vec<ToolResult>),
+}
+
+/// Request for a completion that may call tools.
+#[derive(Debug, Clone)]
+pub struct ToolRequest {
+    /// Optional system prompt to set context.
+    pub system_prompt: Option<String>,
+
+    /// Conversation so far.
+    pub turns: Vec<ToolTurn>,
+
+    /// Tools the model may call.
+    pub tools: Vec<ToolDefinition>,
+
+    /// Sampling temperature (0.0 to 2.0, lower is more deterministic).
+    pub temperature: f32,
+
+    /// Maximum tokens to generate.
+    pub max_tokens: Option<usize>,
+}
+
+impl ToolRequest {
+    pub fn new(turns: Vec<ToolTurn>, tools: Vec<ToolDefinition>) -> Self {
+        Self {
+            system_prompt: None,
+            turns,
+            tools,
+            temperature: default_temperature(),
+            max_tokens: None,
+        }
+    }
+
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+}
+
+/// Response to a tool request.
+#[derive(Debug, Clone)]
+pub struct ToolResponse {
+    /// Generated text content.
+    pub text: String,
+
+    /// Tools the model wants called, in order.
+    pub tool_calls: Vec<ToolCall>,
+
+    /// Token usage statistics.
+    pub tokens_used: TokenUsage,
+
+    /// Why generation finished.
+    pub finish_reason: FinishReason,
+}
+
+/// Estimates how many tokens `text` uses without a tokenizer.
+///
+/// BPE tokenizers average about four ASCII characters per token, while
+/// other scripts often take a token per character or more. The estimate
+/// errs on the high side so prompts built from it fit the context window.
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+    let words = text.split_whitespace().count();
+    (ascii.div_ceil(4) + other).max(words)
+}
+
 /// Trait for LLM providers (OpenAI, Anthropic, Ollama, etc.).
 #[async_trait]
 pub trait LlmProvider: Send + Sync {
@@ -214,9 +396,35 @@ pub trait LlmProvider: Send + Sync {
     /// Whether this provider supports function/tool calling.
     fn supports_function_calling(&self) -> bool;
 
+    /// Performs a completion in which the model may call the request's tools.
+    ///
+    /// Providers without tool calling return [`LlmError::Unavailable`].
+    async fn complete_with_tools(&self, _request: &ToolRequest) -> LlmResult<ToolResponse> {
+        Err(LlmError::Unavailable(format!(
+            "{} does not support tool calling",
+            self.name()
+        )))
+    }
+
+    /// Whether this provider constrains replies to a request's
+    /// [`OutputSchema`].
+    ///
+    /// Providers without it are asked for JSON in the prompt instead.
+    fn supports_structured_output(&self) -> bool {
+        false
+    }
+
     /// Maximum context length in tokens for the configured model.
     fn max_context_length(&self) -> usize;
 
+    /// Counts the tokens `text` takes up with the configured model.
+    ///
+    /// Defaults to [`estimate_tokens`]; providers with a local tokenizer
+    /// count exactly.
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+
     /// Returns the model identifier being used.
     fn model(&self) -> &str;
 }
@@ -305,4 +513,48 @@ mod tests {
         assert_eq!(usage.completion_tokens, 0);
         assert_eq!(usage.total_tokens, 0);
     }
+
+    #[test]
+    fn test_tool_request_builder() {
+        let tool = ToolDefinition {
+            name: "archive".to_string(),
+            description: "Archive threads".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        };
+        let request = ToolRequest::new(vec![ToolTurn::User("Tidy up".to_string())], vec![tool])
+            .with_system_prompt("Be careful")
+            .with_max_tokens(200);
+
+        assert_eq!(request.system_prompt.as_deref(), Some("Be careful"));
+        assert_eq!(request.temperature, 0.7);
+        assert_eq!(request.max_tokens, Some(200));
+        assert_eq!(request.tools[0].name, "archive");
+
+        assert!(!ToolResult::success("call-1", "ok").is_error);
+        assert!(ToolResult::error("call-1", "failed").is_error);
+    }
+
+    #[test]
+    fn test_output_schema_is_optional() {
+        let request = CompletionRequest::new(vec![Message::user("Test")]);
+        let json = serde_json::to_string(&request).unwrap();
+        assert!(!json.contains("output_schema"));
+
+        let request = request.with_output_schema(OutputSchema {
+            name: "answer".to_string(),
+            description: "An answer".to_string(),
+            schema: serde_json::json!({"type": "object"}),
+        });
+        let json = serde_json::to_string(&request).unwrap();
+        let deserialized: CompletionRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized.output_schema.unwrap().name, "answer");
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello there"), 3);
+        assert_eq!(estimate_tokens("a b c d e f"), 6);
+        assert_eq!(estimate_tokens("会議は金曜日"), 6);
+    }
 }
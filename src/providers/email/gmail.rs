@@ -18,6 +18,7 @@
 //! - `users.history.list` for incremental sync
 //! - `users.messages.send` for sending emails
 //! - `users.labels.list` for fetching labels
+//! - `users.settings.filters` for managing server-side filters
 
 use async_trait::async_trait;
 use base64::prelude::*;
@@ -30,8 +31,8 @@ use super::{
     PendingChangeType, ProviderError, Result,
 };
 use crate::domain::{
-    AccountId, Address, Email, EmailId, Label, LabelId, MessageId, ProviderType, Thread, ThreadId,
-    ThreadSummary,
+    AccountId, Address, Email, EmailId, Header, Label, LabelId, MessageId, ProviderType, Thread,
+    ThreadId, ThreadSummary,
 };
 
 const GMAIL_API_BASE: &str = "https://gmail.googleapis.com/gmail/v1/users/me";
@@ -187,6 +188,21 @@ struct ModifyRequest {
     remove_label_ids: Vec<String>,
 }
 
+/// Request body for creating a label.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateLabelRequest<'a> {
+    name: &'a str,
+    label_list_visibility: &'a str,
+    message_list_visibility: &'a str,
+}
+
+/// Gmail filters list response.
+#[derive(Debug, Deserialize)]
+struct FiltersListResponse {
+    filter: Option<Vec<GmailFilter>>,
+}
+
 /// OAuth token response.
 #[derive(Debug, Deserialize)]
 struct TokenResponse {
@@ -208,6 +224,89 @@ pub struct GmailCredentials {
     pub client_secret: String,
 }
 
+/// A Gmail server-side filter.
+///
+/// Gmail filters can't be edited in place; a changed filter is deleted and
+/// created again, which gives it a new ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GmailFilter {
+    /// Filter ID, assigned by Gmail when the filter is created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Messages the filter applies to.
+    #[serde(default)]
+    pub criteria: GmailFilterCriteria,
+    /// What the filter does to matching messages.
+    #[serde(default)]
+    pub action: GmailFilterAction,
+}
+
+/// Conditions a message must meet for a [`GmailFilter`] to apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailFilterCriteria {
+    /// Sender's display name or address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Recipient's display name or address.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+    /// Phrase in the subject.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// Gmail search query the message must match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// Gmail search query the message must not match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negated_query: Option<String>,
+    /// Whether the message must have an attachment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_attachment: Option<bool>,
+}
+
+impl GmailFilterCriteria {
+    /// Returns the criteria written as a Gmail search query.
+    pub fn to_query(&self) -> String {
+        let mut terms = Vec::new();
+        if let Some(from) = &self.from {
+            terms.push(format!("from:({})", from));
+        }
+        if let Some(to) = &self.to {
+            terms.push(format!("to:({})", to));
+        }
+        if let Some(subject) = &self.subject {
+            terms.push(format!("subject:({})", subject));
+        }
+        if let Some(query) = &self.query {
+            terms.push(query.clone());
+        }
+        if let Some(negated) = &self.negated_query {
+            terms.push(format!("-({})", negated));
+        }
+        if self.has_attachment == Some(true) {
+            terms.push("has:attachment".to_string());
+        }
+        terms.join(" ")
+    }
+}
+
+/// Changes a [`GmailFilter`] makes to matching messages.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailFilterAction {
+    /// Label IDs to add; `TRASH`, `STARRED` and `IMPORTANT` are labels too.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add_label_ids: Vec<String>,
+    /// Label IDs to remove; removing `INBOX` skips the inbox and removing
+    /// `SPAM` never sends the message to spam.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove_label_ids: Vec<String>,
+    /// Address to forward the message to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward: Option<String>,
+}
+
 /// Gmail API provider.
 ///
 /// Implements [`EmailProvider`] using the Gmail REST API with OAuth 2.0 authentication.
@@ -421,6 +520,92 @@ impl GmailProvider {
         Ok(())
     }
 
+    /// Makes an authenticated DELETE request to the Gmail API.
+    async fn delete(&self, endpoint: &str) -> Result<()> {
+        let url = format!("{}{}", GMAIL_API_BASE, endpoint);
+        let headers = self.auth_headers()?;
+
+        let response = self
+            .client
+            .delete(&url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| ProviderError::Connection(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(self.handle_error(response).await);
+        }
+        Ok(())
+    }
+
+    /// Lists the account's server-side filters.
+    pub async fn list_filters(&self) -> Result<Vec<GmailFilter>> {
+        if !self.authenticated {
+            return Err(ProviderError::Authentication(
+                "not authenticated".to_string(),
+            ));
+        }
+
+        let response: FiltersListResponse = self.get("/settings/filters").await?;
+        Ok(response.filter.unwrap_or_default())
+    }
+
+    /// Creates a server-side filter, returning it with its new ID.
+    pub async fn create_filter(&self, filter: &GmailFilter) -> Result<GmailFilter> {
+        if !self.authenticated {
+            return Err(ProviderError::Authentication(
+                "not authenticated".to_string(),
+            ));
+        }
+
+        let body = GmailFilter {
+            id: None,
+            ..filter.clone()
+        };
+        self.post("/settings/filters", &body).await
+    }
+
+    /// Deletes a server-side filter.
+    pub async fn delete_filter(&self, filter_id: &str) -> Result<()> {
+        if !self.authenticated {
+            return Err(ProviderError::Authentication(
+                "not authenticated".to_string(),
+            ));
+        }
+
+        self.delete(&format!("/settings/filters/{}", filter_id))
+            .await
+    }
+
+    /// Returns the ID of the user label with the given name, creating the
+    /// label if it doesn't exist.
+    pub async fn find_or_create_label(&self, name: &str) -> Result<String> {
+        if !self.authenticated {
+            return Err(ProviderError::Authentication(
+                "not authenticated".to_string(),
+            ));
+        }
+
+        let response: LabelsListResponse = self.get("/labels").await?;
+        if let Some(label) = response
+            .labels
+            .unwrap_or_default()
+            .into_iter()
+            .find(|l| l.name.eq_ignore_ascii_case(name))
+        {
+            return Ok(label.id);
+        }
+
+        let body = CreateLabelRequest {
+            name,
+            label_list_visibility: "labelShow",
+            message_list_visibility: "show",
+        };
+        let label: GmailLabel = self.post("/labels", &body).await?;
+        Ok(label.id)
+    }
+
     /// Handles API response, checking for errors.
     async fn handle_response<T: for<'de> Deserialize<'de>>(
         &self,
@@ -610,6 +795,14 @@ impl GmailProvider {
 
         let snippet = msg.snippet.clone().unwrap_or_default();
 
+        let raw_headers = headers
+            .map(|h| {
+                h.iter()
+                    .map(|hdr| Header::new(hdr.name.clone(), hdr.value.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Email {
             id: EmailId::from(msg.id.clone()),
             account_id: self.account_id.clone(),
@@ -631,6 +824,7 @@ impl GmailProvider {
             is_draft,
             labels,
             attachments: vec![], // TODO: parse attachments
+            headers: raw_headers,
         }
     }
 
@@ -1227,6 +1421,49 @@ mod tests {
         assert!(matches!(result, Err(ProviderError::Authentication(_))));
     }
 
+    #[tokio::test]
+    async fn gmail_filters_require_auth() {
+        let provider = GmailProvider::new(AccountId::from("test-account"));
+
+        let result = provider.list_filters().await;
+        assert!(matches!(result, Err(ProviderError::Authentication(_))));
+        let result = provider.create_filter(&GmailFilter::default()).await;
+        assert!(matches!(result, Err(ProviderError::Authentication(_))));
+    }
+
+    #[test]
+    fn gmail_filter_serialization() {
+        let filter = GmailFilter {
+            id: None,
+            criteria: GmailFilterCriteria {
+                from: Some("example.com".to_string()),
+                ..Default::default()
+            },
+            action: GmailFilterAction {
+                add_label_ids: vec!["Label_1".to_string()],
+                remove_label_ids: vec!["INBOX".to_string()],
+                forward: None,
+            },
+        };
+
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "criteria": { "from": "example.com" },
+                "action": { "addLabelIds": ["Label_1"], "removeLabelIds": ["INBOX"] }
+            })
+        );
+
+        let listed: FiltersListResponse = serde_json::from_str(
+            r#"{"filter": [{"id": "f1", "criteria": {"query": "list:builds", "hasAttachment": true}, "action": {"addLabelIds": ["STARRED"]}}]}"#,
+        )
+        .unwrap();
+        let listed = listed.filter.unwrap();
+        assert_eq!(listed[0].id.as_deref(), Some("f1"));
+        assert_eq!(listed[0].criteria.to_query(), "list:builds has:attachment");
+    }
+
     #[tokio::test]
     #[ignore = "requires OAuth credentials in keychain"]
     async fn gmail_provider_fetch_threads_empty() {
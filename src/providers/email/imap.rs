@@ -39,8 +39,8 @@ use super::{
     ProviderError, Result,
 };
 use crate::domain::{
-    AccountId, Address, Email, EmailId, Label, LabelId, MessageId, ProviderType, Thread, ThreadId,
-    ThreadSummary,
+    AccountId, Address, Email, EmailId, Header, Label, LabelId, MessageId, ProviderType, Thread,
+    ThreadId, ThreadSummary,
 };
 
 /// IMAP/SMTP configuration.
@@ -367,6 +367,22 @@ impl ImapProvider {
             .unwrap_or_default()
     }
 
+    /// Collects the raw headers of a parsed message, unfolding continuation lines.
+    fn extract_headers(message: &ParsedMessage, raw: &[u8]) -> Vec<Header> {
+        message
+            .headers()
+            .iter()
+            .filter_map(|header| {
+                let value = raw.get(header.offset_start..header.offset_end)?;
+                let value = String::from_utf8_lossy(value);
+                Some(Header::new(
+                    header.name.as_str(),
+                    value.split_whitespace().collect::<Vec<_>>().join(" "),
+                ))
+            })
+            .collect()
+    }
+
     /// Parses a full message from IMAP fetch body data.
     fn parse_message(&self, fetch: &Fetch, folder: &str) -> Option<Email> {
         let uid = fetch.uid?;
@@ -420,6 +436,8 @@ impl ImapProvider {
             .map(|s| s.chars().take(200).collect())
             .unwrap_or_default();
 
+        let headers = Self::extract_headers(&message, body_data);
+
         Some(Email {
             id: EmailId::from(format!("{}:{}", folder, uid)),
             account_id: self.account_id.clone(),
@@ -441,6 +459,7 @@ impl ImapProvider {
             is_draft: folder.eq_ignore_ascii_case("Drafts"),
             labels: vec![LabelId::from(folder.to_string())],
             attachments: vec![],
+            headers,
         })
     }
 
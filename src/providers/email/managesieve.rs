@@ -0,0 +1,722 @@
+//! ManageSieve client.
+//!
+//! Uploads and activates Sieve scripts on IMAP servers using the ManageSieve
+//! protocol (RFC 5804), so that server-side rules sort mail even while the
+//! app is closed.
+//!
+//! # Protocol Details
+//!
+//! - Connects to port 4190 and upgrades with STARTTLS before authenticating
+//! - Authenticates with SASL PLAIN
+//! - Sends scripts as non-synchronizing literals (`{n+}`), which every
+//!   ManageSieve server accepts
+
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+
+use super::{ImapConfig, ProviderError, Result};
+
+/// Standard ManageSieve port.
+pub const MANAGESIEVE_PORT: u16 = 4190;
+
+/// ManageSieve server configuration.
+#[derive(Debug, Clone)]
+pub struct ManageSieveConfig {
+    /// Server hostname.
+    pub host: String,
+    /// Server port.
+    pub port: u16,
+}
+
+impl ManageSieveConfig {
+    /// Creates a configuration for the standard port.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            port: MANAGESIEVE_PORT,
+        }
+    }
+
+    /// Uses the IMAP server's host on the standard port.
+    pub fn for_imap(config: &ImapConfig) -> Self {
+        Self::new(config.imap_host.clone())
+    }
+}
+
+/// Capabilities advertised by a ManageSieve server.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SieveCapabilities {
+    /// Server implementation name.
+    pub implementation: Option<String>,
+    /// Supported Sieve extensions, e.g. `fileinto` or `vacation`.
+    pub extensions: Vec<String>,
+    /// Supported SASL mechanisms.
+    pub sasl: Vec<String>,
+    /// Whether STARTTLS is offered.
+    pub starttls: bool,
+    /// Protocol version.
+    pub version: Option<String>,
+}
+
+impl SieveCapabilities {
+    /// Returns whether the server supports a Sieve extension.
+    pub fn supports(&self, extension: &str) -> bool {
+        self.extensions
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(extension))
+    }
+}
+
+/// A script stored on the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SieveScriptInfo {
+    /// Script name.
+    pub name: String,
+    /// Whether this is the script the server runs.
+    pub active: bool,
+}
+
+/// A word of a server response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Unquoted word, such as `OK` or `ACTIVE`.
+    Atom(String),
+    /// Quoted string or literal.
+    String(String),
+    /// Parenthesized response code, without the parentheses.
+    Code(String),
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Atom(s) | Token::String(s) | Token::Code(s) => s,
+        }
+    }
+}
+
+/// How the server answered a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Status {
+    /// `OK`, `NO` or `BYE`.
+    kind: String,
+    /// Response code, e.g. `NONEXISTENT`.
+    code: Option<String>,
+    /// Human-readable text.
+    text: Option<String>,
+}
+
+impl Status {
+    fn is_ok(&self) -> bool {
+        self.kind == "OK"
+    }
+
+    /// Describes a failed command for an error message.
+    fn describe(&self) -> String {
+        match (&self.code, &self.text) {
+            (Some(code), Some(text)) => format!("{} ({}): {}", self.kind, code, text),
+            (Some(code), None) => format!("{} ({})", self.kind, code),
+            (None, Some(text)) => format!("{}: {}", self.kind, text),
+            (None, None) => self.kind.clone(),
+        }
+    }
+}
+
+/// ManageSieve client over any byte stream.
+///
+/// # Example
+///
+/// ```ignore
+/// use heap::providers::email::{ManageSieveClient, ManageSieveConfig};
+///
+/// let mut client = ManageSieveClient::connect(&ManageSieveConfig::new("imap.example.com")).await?;
+/// client.authenticate("user@example.com", "password").await?;
+/// client.check_script(&script).await?;
+/// client.put_script("heap", &script).await?;
+/// client.set_active("heap").await?;
+/// ```
+pub struct ManageSieveClient<S> {
+    stream: BufReader<S>,
+    capabilities: SieveCapabilities,
+}
+
+impl ManageSieveClient<TlsStream<TcpStream>> {
+    /// Connects to the server and upgrades the connection with STARTTLS.
+    pub async fn connect(config: &ManageSieveConfig) -> Result<Self> {
+        let tcp_stream = TcpStream::connect(format!("{}:{}", config.host, config.port))
+            .await
+            .map_err(|e| ProviderError::Connection(format!("TCP connect failed: {}", e)))?;
+
+        let mut plain = ManageSieveClient::new(tcp_stream).await?;
+        if !plain.capabilities.starttls {
+            return Err(ProviderError::Connection(
+                "server does not offer STARTTLS".to_string(),
+            ));
+        }
+        plain.command("STARTTLS").await?;
+        let tcp_stream = plain.into_inner();
+
+        let tls_config = ClientConfig::builder()
+            .with_root_certificates(tokio_rustls::rustls::RootCertStore::from_iter(
+                webpki_roots::TLS_SERVER_ROOTS.iter().cloned(),
+            ))
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(tls_config));
+        let server_name = ServerName::try_from(config.host.clone())
+            .map_err(|e| ProviderError::Connection(format!("invalid server name: {}", e)))?;
+        let tls_stream = connector
+            .connect(server_name, tcp_stream)
+            .await
+            .map_err(|e| ProviderError::Connection(format!("TLS handshake failed: {}", e)))?;
+
+        // The server repeats its capabilities once TLS is up
+        ManageSieveClient::new(tls_stream).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ManageSieveClient<S> {
+    /// Starts a session on a connected stream, reading the server greeting.
+    pub async fn new(stream: S) -> Result<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+            capabilities: SieveCapabilities::default(),
+        };
+        let (lines, status) = client.read_response().await?;
+        if !status.is_ok() {
+            return Err(ProviderError::Connection(status.describe()));
+        }
+        client.capabilities = parse_capabilities(&lines);
+        Ok(client)
+    }
+
+    /// Returns the capabilities from the latest greeting or `CAPABILITY`.
+    pub fn capabilities(&self) -> &SieveCapabilities {
+        &self.capabilities
+    }
+
+    /// Refreshes the server's capabilities.
+    pub async fn refresh_capabilities(&mut self) -> Result<&SieveCapabilities> {
+        let lines = self.command("CAPABILITY").await?;
+        self.capabilities = parse_capabilities(&lines);
+        Ok(&self.capabilities)
+    }
+
+    /// Authenticates with SASL PLAIN.
+    pub async fn authenticate(&mut self, username: &str, password: &str) -> Result<()> {
+        let credentials = STANDARD.encode(format!("\0{}\0{}", username, password));
+        let command = format!("AUTHENTICATE \"PLAIN\" {}", quote(&credentials));
+        self.send(&command).await?;
+        let (lines, status) = self.read_response().await?;
+        if !status.is_ok() {
+            return Err(ProviderError::Authentication(status.describe()));
+        }
+        // Some servers send fresh capabilities with the OK
+        if !lines.is_empty() {
+            self.capabilities = parse_capabilities(&lines);
+        }
+        Ok(())
+    }
+
+    /// Lists the scripts stored on the server.
+    pub async fn list_scripts(&mut self) -> Result<Vec<SieveScriptInfo>> {
+        let lines = self.command("LISTSCRIPTS").await?;
+        Ok(lines
+            .iter()
+            .filter_map(|line| {
+                let name = match line.first()? {
+                    Token::String(name) => name.clone(),
+                    _ => return None,
+                };
+                let active = line
+                    .get(1)
+                    .is_some_and(|t| t.text().eq_ignore_ascii_case("ACTIVE"));
+                Some(SieveScriptInfo { name, active })
+            })
+            .collect())
+    }
+
+    /// Downloads a script.
+    pub async fn get_script(&mut self, name: &str) -> Result<String> {
+        let lines = self.command(&format!("GETSCRIPT {}", quote(name))).await?;
+        lines
+            .iter()
+            .flatten()
+            .find_map(|token| match token {
+                Token::String(content) => Some(content.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| ProviderError::Provider("GETSCRIPT returned no script".to_string()))
+    }
+
+    /// Checks a script's syntax without storing it.
+    ///
+    /// A script the server rejects is reported as
+    /// [`ProviderError::InvalidRequest`] with the server's explanation.
+    pub async fn check_script(&mut self, content: &str) -> Result<()> {
+        self.send_with_literal("CHECKSCRIPT", content).await?;
+        self.expect_valid_script().await
+    }
+
+    /// Uploads a script, replacing any script with the same name.
+    ///
+    /// The server checks the script too, and rejects it as
+    /// [`ProviderError::InvalidRequest`] if it is not valid.
+    pub async fn put_script(&mut self, name: &str, content: &str) -> Result<()> {
+        self.send_with_literal(&format!("PUTSCRIPT {}", quote(name)), content)
+            .await?;
+        self.expect_valid_script().await
+    }
+
+    /// Makes a script the one the server runs.
+    pub async fn set_active(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("SETACTIVE {}", quote(name)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Deactivates every script.
+    pub async fn deactivate(&mut self) -> Result<()> {
+        self.set_active("").await
+    }
+
+    /// Deletes a script. The active script cannot be deleted.
+    pub async fn delete_script(&mut self, name: &str) -> Result<()> {
+        self.command(&format!("DELETESCRIPT {}", quote(name)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Ends the session.
+    pub async fn logout(mut self) -> Result<()> {
+        self.send("LOGOUT").await?;
+        self.read_response().await.map(|_| ())
+    }
+
+    /// Returns the underlying stream.
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Sends a command and returns the response lines if it succeeded.
+    async fn command(&mut self, command: &str) -> Result<Vec<Vec<Token>>> {
+        self.send(command).await?;
+        let (lines, status) = self.read_response().await?;
+        if !status.is_ok() {
+            return Err(status_error(&status));
+        }
+        Ok(lines)
+    }
+
+    /// Reads the answer to `CHECKSCRIPT` or `PUTSCRIPT`.
+    async fn expect_valid_script(&mut self) -> Result<()> {
+        let (_, status) = self.read_response().await?;
+        match status.kind.as_str() {
+            "OK" => Ok(()),
+            "NO" if status.code.is_none() => Err(ProviderError::InvalidRequest(
+                status.text.unwrap_or_else(|| "invalid script".to_string()),
+            )),
+            _ => Err(status_error(&status)),
+        }
+    }
+
+    async fn send(&mut self, command: &str) -> Result<()> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .await
+            .map_err(io_error)?;
+        stream.flush().await.map_err(io_error)
+    }
+
+    async fn send_with_literal(&mut self, command: &str, content: &str) -> Result<()> {
+        self.send(&format!(
+            "{} {{{}+}}\r\n{}",
+            command,
+            content.len(),
+            content
+        ))
+        .await
+    }
+
+    /// Reads lines up to and including the `OK`, `NO` or `BYE` line.
+    async fn read_response(&mut self) -> Result<(Vec<Vec<Token>>, Status)> {
+        let mut lines = Vec::new();
+        loop {
+            let tokens = self.read_tokens().await?;
+            let kind = match tokens.first() {
+                Some(Token::Atom(atom)) => atom.to_ascii_uppercase(),
+                _ => {
+                    lines.push(tokens);
+                    continue;
+                }
+            };
+            if !matches!(kind.as_str(), "OK" | "NO" | "BYE") {
+                lines.push(tokens);
+                continue;
+            }
+
+            let mut rest = tokens.into_iter().skip(1).peekable();
+            let code = match rest.peek() {
+                Some(Token::Code(_)) => rest.next().map(|t| t.text().to_string()),
+                _ => None,
+            };
+            let text = rest.next().map(|t| t.text().to_string());
+            return Ok((lines, Status { kind, code, text }));
+        }
+    }
+
+    /// Reads one response line, including any literals it contains.
+    async fn read_tokens(&mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self.stream.read_line(&mut line).await.map_err(io_error)?;
+            if read == 0 {
+                return Err(ProviderError::Connection(
+                    "server closed the connection".to_string(),
+                ));
+            }
+            let literal = tokenize(line.trim_end_matches(['\r', '\n']), &mut tokens)?;
+            let Some(size) = literal else {
+                return Ok(tokens);
+            };
+
+            let mut content = vec![0; size];
+            self.stream
+                .read_exact(&mut content)
+                .await
+                .map_err(io_error)?;
+            let content = String::from_utf8(content)
+                .map_err(|e| ProviderError::Provider(format!("literal is not UTF-8: {}", e)))?;
+            tokens.push(Token::String(content));
+        }
+    }
+}
+
+/// Splits a response line into tokens, returning the size of a literal
+/// that ends the line.
+fn tokenize(line: &str, tokens: &mut Vec<Token>) -> Result<Option<usize>> {
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(malformed(line)),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            '(' => {
+                chars.next();
+                let mut value = String::new();
+                let mut in_quotes = false;
+                loop {
+                    match chars.next() {
+                        Some('"') => {
+                            in_quotes = !in_quotes;
+                            value.push('"');
+                        }
+                        Some(')') if !in_quotes => break,
+                        Some(c) => value.push(c),
+                        None => return Err(malformed(line)),
+                    }
+                }
+                tokens.push(Token::Code(value));
+            }
+            '{' => {
+                let rest: String = chars.collect();
+                let size = rest
+                    .trim_start_matches('{')
+                    .trim_end_matches('}')
+                    .trim_end_matches('+')
+                    .parse()
+                    .map_err(|_| malformed(line))?;
+                return Ok(Some(size));
+            }
+            _ => {
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c == ' ' {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Atom(value));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn parse_capabilities(lines: &[Vec<Token>]) -> SieveCapabilities {
+    let mut capabilities = SieveCapabilities::default();
+    for line in lines {
+        let Some(name) = line.first() else {
+            continue;
+        };
+        let value = line.get(1).map(|t| t.text().to_string());
+        match name.text().to_ascii_uppercase().as_str() {
+            "IMPLEMENTATION" => capabilities.implementation = value,
+            "SIEVE" => {
+                capabilities.extensions = words(value);
+            }
+            "SASL" => capabilities.sasl = words(value),
+            "STARTTLS" => capabilities.starttls = true,
+            "VERSION" => capabilities.version = value,
+            _ => {}
+        }
+    }
+    capabilities
+}
+
+fn words(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// Quotes a string for a command.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn status_error(status: &Status) -> ProviderError {
+    match status.code.as_deref() {
+        Some("NONEXISTENT") => ProviderError::NotFound(status.describe()),
+        _ => ProviderError::Provider(status.describe()),
+    }
+}
+
+fn malformed(line: &str) -> ProviderError {
+    ProviderError::Provider(format!("malformed ManageSieve response: {}", line))
+}
+
+fn io_error(e: std::io::Error) -> ProviderError {
+    ProviderError::Connection(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use tokio::io::{duplex, DuplexStream};
+
+    /// Minimal ManageSieve server holding scripts in memory.
+    ///
+    /// Scripts containing `syntax error` fail checks.
+    async fn serve(stream: DuplexStream) {
+        let mut stream = BufReader::new(stream);
+        let mut scripts: BTreeMap<String, String> = BTreeMap::new();
+        let mut active: Option<String> = None;
+
+        async fn reply(stream: &mut BufReader<DuplexStream>, text: &str) {
+            stream.get_mut().write_all(text.as_bytes()).await.unwrap();
+        }
+
+        reply(
+            &mut stream,
+            "\"IMPLEMENTATION\" \"Stand-in\"\r\n\"SIEVE\" \"fileinto vacation imap4flags\"\r\n\
+             \"SASL\" \"PLAIN\"\r\n\"VERSION\" \"1.0\"\r\nOK \"Ready\"\r\n",
+        )
+        .await;
+
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                return;
+            }
+            let mut args = Vec::new();
+            let literal = tokenize(line.trim_end(), &mut args).unwrap();
+            let content = match literal {
+                Some(size) => {
+                    let mut content = vec![0; size];
+                    stream.read_exact(&mut content).await.unwrap();
+                    let mut crlf = String::new();
+                    stream.read_line(&mut crlf).await.unwrap();
+                    Some(String::from_utf8(content).unwrap())
+                }
+                None => None,
+            };
+            let arg = |i: usize| {
+                args.get(i)
+                    .map(|t| t.text().to_string())
+                    .unwrap_or_default()
+            };
+            let valid = |content: &Option<String>| {
+                !content
+                    .as_deref()
+                    .unwrap_or_default()
+                    .contains("syntax error")
+            };
+
+            match arg(0).as_str() {
+                "AUTHENTICATE" => {
+                    let decoded = STANDARD.decode(arg(2)).unwrap();
+                    if decoded == b"\0me@example.com\0secret" {
+                        reply(&mut stream, "OK\r\n").await;
+                    } else {
+                        reply(&mut stream, "NO \"Authentication failed\"\r\n").await;
+                    }
+                }
+                "LISTSCRIPTS" => {
+                    for name in scripts.keys() {
+                        if active.as_ref() == Some(name) {
+                            reply(&mut stream, &format!("\"{}\" ACTIVE\r\n", name)).await;
+                        } else {
+                            reply(&mut stream, &format!("\"{}\"\r\n", name)).await;
+                        }
+                    }
+                    reply(&mut stream, "OK\r\n").await;
+                }
+                "CHECKSCRIPT" if valid(&content) => reply(&mut stream, "OK\r\n").await,
+                "PUTSCRIPT" if valid(&content) => {
+                    scripts.insert(arg(1), content.unwrap_or_default());
+                    reply(&mut stream, "OK\r\n").await;
+                }
+                "CHECKSCRIPT" | "PUTSCRIPT" => {
+                    reply(&mut stream, "NO \"line 1: syntax error\"\r\n").await
+                }
+                "SETACTIVE" if scripts.contains_key(&arg(1)) => {
+                    active = Some(arg(1));
+                    reply(&mut stream, "OK\r\n").await;
+                }
+                "GETSCRIPT" if scripts.contains_key(&arg(1)) => {
+                    let content = &scripts[&arg(1)];
+                    reply(
+                        &mut stream,
+                        &format!("{{{}}}\r\n{}\r\nOK\r\n", content.len(), content),
+                    )
+                    .await;
+                }
+                "SETACTIVE" | "GETSCRIPT" => {
+                    reply(&mut stream, "NO (NONEXISTENT) \"No such script\"\r\n").await
+                }
+                "LOGOUT" => {
+                    reply(&mut stream, "OK \"Bye\"\r\n").await;
+                    return;
+                }
+                _ => reply(&mut stream, "NO \"Unknown command\"\r\n").await,
+            }
+        }
+    }
+
+    async fn stand_in() -> ManageSieveClient<DuplexStream> {
+        let (client, server) = duplex(64 * 1024);
+        tokio::spawn(serve(server));
+        ManageSieveClient::new(client).await.unwrap()
+    }
+
+    #[test]
+    fn tokenize_response_lines() {
+        let mut tokens = Vec::new();
+        let literal =
+            tokenize("NO (QUOTA/MAXSIZE \"x\") \"Too \\\"big\\\"\"", &mut tokens).unwrap();
+        assert_eq!(literal, None);
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Atom("NO".to_string()),
+                Token::Code("QUOTA/MAXSIZE \"x\"".to_string()),
+                Token::String("Too \"big\"".to_string()),
+            ]
+        );
+
+        let mut tokens = Vec::new();
+        assert_eq!(tokenize("\"name\" {12}", &mut tokens).unwrap(), Some(12));
+    }
+
+    #[tokio::test]
+    async fn greeting_sets_capabilities() {
+        let client = stand_in().await;
+        let capabilities = client.capabilities();
+        assert_eq!(capabilities.implementation.as_deref(), Some("Stand-in"));
+        assert!(capabilities.supports("vacation"));
+        assert!(!capabilities.supports("regex"));
+        assert_eq!(capabilities.sasl, vec!["PLAIN"]);
+        assert!(!capabilities.starttls);
+    }
+
+    #[tokio::test]
+    async fn authenticate_with_plain() {
+        let mut client = stand_in().await;
+        assert!(matches!(
+            client.authenticate("me@example.com", "wrong").await,
+            Err(ProviderError::Authentication(_))
+        ));
+        client
+            .authenticate("me@example.com", "secret")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn upload_activate_and_list_scripts() {
+        let mut client = stand_in().await;
+        client
+            .authenticate("me@example.com", "secret")
+            .await
+            .unwrap();
+        let script = "require \"fileinto\";\r\nfileinto \"Lists\";\r\n";
+
+        client.check_script(script).await.unwrap();
+        client.put_script("heap", script).await.unwrap();
+        client.put_script("old", "keep;").await.unwrap();
+        client.set_active("heap").await.unwrap();
+
+        assert_eq!(
+            client.list_scripts().await.unwrap(),
+            vec![
+                SieveScriptInfo {
+                    name: "heap".to_string(),
+                    active: true
+                },
+                SieveScriptInfo {
+                    name: "old".to_string(),
+                    active: false
+                },
+            ]
+        );
+        assert_eq!(client.get_script("heap").await.unwrap(), script);
+        client.logout().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_scripts_and_missing_names_are_reported() {
+        let mut client = stand_in().await;
+
+        match client.check_script("syntax error").await {
+            Err(ProviderError::InvalidRequest(message)) => {
+                assert_eq!(message, "line 1: syntax error")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(matches!(
+            client.put_script("heap", "syntax error").await,
+            Err(ProviderError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            client.set_active("missing").await,
+            Err(ProviderError::NotFound(_))
+        ));
+        assert!(client.list_scripts().await.unwrap().is_empty());
+    }
+}
@@ -6,6 +6,8 @@
 //! - [`GmailProvider`] - Gmail API with OAuth 2.0
 //! - [`ImapProvider`] - Standard IMAP/SMTP
 //!
+//! [`ManageSieveClient`] manages server-side Sieve scripts for IMAP accounts.
+//!
 //! # Architecture
 //!
 //! The email provider abstraction allows the application to work with different
@@ -36,10 +38,14 @@
 
 mod gmail;
 mod imap;
+mod managesieve;
 mod traits;
 
-pub use gmail::GmailProvider;
+pub use gmail::{GmailFilter, GmailFilterAction, GmailFilterCriteria, GmailProvider};
 pub use imap::{ImapConfig, ImapProvider};
+pub use managesieve::{
+    ManageSieveClient, ManageSieveConfig, SieveCapabilities, SieveScriptInfo, MANAGESIEVE_PORT,
+};
 pub use traits::{
     Change, EmailProvider, EmailUpdate, NewEmailData, OutgoingAttachment, OutgoingEmail,
     Pagination, PendingChange, PendingChangeType, ProviderError, Result,
@@ -0,0 +1,1007 @@
+//! Tool-calling email agent.
+//!
+//! The [`AgentService`] lets a model work on the mailbox through typed
+//! [`AgentTool`]s. Tools that leave the mailbox unchanged (search, fetch
+//! thread, draft reply) run as soon as the model calls them. Tools that change
+//! it (archive, label, snooze) are proposed to the user and run only once
+//! confirmed, and each executed action is recorded with the [`UndoService`].
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::domain::{LabelId, ThreadId};
+use crate::providers::ai::{ToolCall, ToolDefinition, ToolRequest, ToolResult, ToolTurn};
+use crate::services::email_service::EmailStorage;
+use crate::services::{
+    ActionState, ActionType, AiService, EmailService, SearchQuery, SearchService, SearchStorage,
+    UndoService, UndoableAction,
+};
+
+/// Instructions for the agent.
+const SYSTEM_PROMPT: &str = "You are an email assistant working in the user's mailbox \
+through the provided tools. Look things up with search and fetch_thread before acting, \
+and only act on threads you have seen. Archive, label and snooze are shown to the user \
+for confirmation; if the user declines an action, do not retry it. When you are done, \
+reply with a short summary of what you did.";
+
+/// A mailbox operation the model can call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "tool", rename_all = "snake_case")]
+pub enum AgentTool {
+    /// Search emails.
+    Search {
+        query: String,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    /// Read a thread's messages.
+    FetchThread { thread_id: ThreadId },
+    /// Draft a reply for the user to review.
+    DraftReply {
+        thread_id: ThreadId,
+        #[serde(default)]
+        instructions: Option<String>,
+    },
+    /// Archive threads.
+    Archive { thread_ids: Vec<ThreadId> },
+    /// Apply a label to threads.
+    Label {
+        thread_ids: Vec<ThreadId>,
+        label_id: LabelId,
+    },
+    /// Snooze a thread until a time.
+    Snooze {
+        thread_id: ThreadId,
+        until: DateTime<Utc>,
+    },
+}
+
+impl AgentTool {
+    /// Returns the tool definitions given to the model.
+    pub fn definitions() -> Vec<ToolDefinition> {
+        let thread_ids = json!({
+            "type": "array",
+            "items": {"type": "string"},
+            "description": "IDs of the threads, as returned by search"
+        });
+
+        vec![
+            ToolDefinition {
+                name: "search".to_string(),
+                description: "Search the user's emails. Returns matching emails with their \
+                              thread IDs."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "query": {"type": "string", "description": "Search text"},
+                        "limit": {"type": "integer", "description": "Maximum results"}
+                    },
+                    "required": ["query"]
+                }),
+            },
+            ToolDefinition {
+                name: "fetch_thread".to_string(),
+                description: "Read all messages in a thread.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {"thread_id": {"type": "string"}},
+                    "required": ["thread_id"]
+                }),
+            },
+            ToolDefinition {
+                name: "draft_reply".to_string(),
+                description: "Draft a reply to a thread for the user to review. Nothing is \
+                              sent."
+                    .to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "thread_id": {"type": "string"},
+                        "instructions": {
+                            "type": "string",
+                            "description": "What the reply should say"
+                        }
+                    },
+                    "required": ["thread_id"]
+                }),
+            },
+            ToolDefinition {
+                name: "archive".to_string(),
+                description: "Archive threads, removing them from the inbox.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {"thread_ids": thread_ids},
+                    "required": ["thread_ids"]
+                }),
+            },
+            ToolDefinition {
+                name: "label".to_string(),
+                description: "Apply a label to threads.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "thread_ids": thread_ids,
+                        "label_id": {"type": "string", "description": "Label to apply"}
+                    },
+                    "required": ["thread_ids", "label_id"]
+                }),
+            },
+            ToolDefinition {
+                name: "snooze".to_string(),
+                description: "Hide a thread from the inbox until a time.".to_string(),
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "thread_id": {"type": "string"},
+                        "until": {
+                            "type": "string",
+                            "format": "date-time",
+                            "description": "RFC 3339 time to bring the thread back"
+                        }
+                    },
+                    "required": ["thread_id", "until"]
+                }),
+            },
+        ]
+    }
+
+    /// Parses a tool call from the model.
+    pub fn from_call(call: &ToolCall) -> Result<Self> {
+        let mut arguments = match &call.arguments {
+            serde_json::Value::Object(map) => map.clone(),
+            serde_json::Value::Null => Default::default(),
+            other => anyhow::bail!(
+                "Arguments for {} must be an object, got {}",
+                call.name,
+                other
+            ),
+        };
+        arguments.insert("tool".to_string(), json!(call.name));
+
+        serde_json::from_value(serde_json::Value::Object(arguments))
+            .with_context(|| format!("Invalid call to {}", call.name))
+    }
+
+    /// Returns whether the tool changes the mailbox and needs confirmation.
+    pub fn changes_mailbox(&self) -> bool {
+        matches!(
+            self,
+            Self::Archive { .. } | Self::Label { .. } | Self::Snooze { .. }
+        )
+    }
+
+    /// Describes the tool call for the user.
+    pub fn describe(&self) -> String {
+        let conversations = |count: usize| {
+            if count == 1 {
+                "1 conversation".to_string()
+            } else {
+                format!("{} conversations", count)
+            }
+        };
+
+        match self {
+            Self::Search { query, .. } => format!("Search for \"{}\"", query),
+            Self::FetchThread { thread_id } => format!("Read thread {}", thread_id),
+            Self::DraftReply { thread_id, .. } => format!("Draft a reply to {}", thread_id),
+            Self::Archive { thread_ids } => format!("Archive {}", conversations(thread_ids.len())),
+            Self::Label {
+                thread_ids,
+                label_id,
+            } => format!("Label {} as {}", conversations(thread_ids.len()), label_id),
+            Self::Snooze { until, .. } => format!(
+                "Snooze 1 conversation until {}",
+                until.format("%Y-%m-%d %H:%M UTC")
+            ),
+        }
+    }
+}
+
+/// An action the model wants to take, awaiting the user's confirmation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposedAction {
+    /// ID of the tool call that proposed the action.
+    pub call_id: String,
+    /// The action.
+    pub tool: AgentTool,
+    /// Description shown to the user.
+    pub description: String,
+}
+
+/// A reply drafted by the agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentDraft {
+    /// Thread the reply is for.
+    pub thread_id: ThreadId,
+    /// Drafted reply text.
+    pub content: String,
+}
+
+/// Outcome of running the agent until it needs the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AgentStep {
+    /// The model proposed actions that need confirmation.
+    NeedsConfirmation(Vec<ProposedAction>),
+    /// The model finished with a reply.
+    Finished(String),
+}
+
+/// Settings for the agent.
+#[derive(Debug, Clone)]
+pub struct AgentSettings {
+    /// Maximum model calls for one request.
+    pub max_steps: usize,
+    /// Maximum search results returned to the model.
+    pub max_search_results: usize,
+    /// Maximum characters of each message body returned to the model.
+    pub max_body_chars: usize,
+}
+
+impl Default for AgentSettings {
+    fn default() -> Self {
+        Self {
+            max_steps: 8,
+            max_search_results: 10,
+            max_body_chars: 4000,
+        }
+    }
+}
+
+/// Conversation state for one request to the agent.
+#[derive(Debug, Clone, Default)]
+pub struct AgentSession {
+    /// Conversation with the model.
+    turns: Vec<ToolTurn>,
+    /// Actions awaiting confirmation.
+    pending: Vec<ProposedAction>,
+    /// Results of calls made alongside the pending actions.
+    results: Vec<ToolResult>,
+    /// Drafts produced so far.
+    drafts: Vec<AgentDraft>,
+    /// Model calls made so far.
+    steps: usize,
+}
+
+impl AgentSession {
+    /// Returns the actions awaiting confirmation.
+    pub fn pending(&self) -> &[ProposedAction] {
+        &self.pending
+    }
+
+    /// Returns the replies drafted so far.
+    pub fn drafts(&self) -> &[AgentDraft] {
+        &self.drafts
+    }
+}
+
+/// Runs tool-calling requests against the mailbox.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut session = agent.start("Archive last week's newsletters");
+/// loop {
+///     match agent.step(&mut session).await? {
+///         AgentStep::NeedsConfirmation(actions) => {
+///             let approved = ask_user(&actions);
+///             agent.resolve(&mut session, &approved).await?;
+///         }
+///         AgentStep::Finished(reply) => break,
+///     }
+/// }
+/// ```
+pub struct AgentService<E: EmailStorage, S: SearchStorage> {
+    /// Mailbox operations.
+    email: Arc<EmailService<E>>,
+    /// Search for the search tool.
+    search: Arc<SearchService<S>>,
+    /// Provider access and draft generation.
+    ai_service: Arc<AiService>,
+    /// History of executed actions.
+    undo: Arc<Mutex<UndoService>>,
+    /// Agent settings.
+    settings: AgentSettings,
+}
+
+impl<E: EmailStorage, S: SearchStorage> AgentService<E, S> {
+    /// Creates a new agent service.
+    pub fn new(
+        email: Arc<EmailService<E>>,
+        search: Arc<SearchService<S>>,
+        ai_service: Arc<AiService>,
+        undo: Arc<Mutex<UndoService>>,
+    ) -> Self {
+        Self {
+            email,
+            search,
+            ai_service,
+            undo,
+            settings: AgentSettings::default(),
+        }
+    }
+
+    /// Sets the agent settings.
+    pub fn with_settings(mut self, settings: AgentSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Starts a session for a request from the user.
+    pub fn start(&self, request: impl Into<String>) -> AgentSession {
+        AgentSession {
+            turns: vec![ToolTurn::User(request.into())],
+            ..Default::default()
+        }
+    }
+
+    /// Runs the model until it finishes or proposes actions.
+    ///
+    /// Tools that leave the mailbox unchanged run immediately. Proposed
+    /// actions must be passed to [`resolve`](Self::resolve) before stepping
+    /// again.
+    pub async fn step(&self, session: &mut AgentSession) -> Result<AgentStep> {
+        if !session.pending.is_empty() {
+            anyhow::bail!("Proposed actions must be confirmed or declined first");
+        }
+
+        let provider = self.ai_service.default_provider().await?;
+        if !provider.supports_function_calling() {
+            anyhow::bail!("{} does not support tool calling", provider.model());
+        }
+
+        loop {
+            if session.steps >= self.settings.max_steps {
+                anyhow::bail!(
+                    "Agent stopped after {} steps without finishing",
+                    session.steps
+                );
+            }
+            session.steps += 1;
+
+            let request = ToolRequest::new(session.turns.clone(), AgentTool::definitions())
+                .with_system_prompt(SYSTEM_PROMPT)
+                .with_temperature(0.2);
+            let response = provider.complete_with_tools(&request).await?;
+
+            session.turns.push(ToolTurn::Assistant {
+                text: response.text.clone(),
+                tool_calls: response.tool_calls.clone(),
+            });
+            if response.tool_calls.is_empty() {
+                return Ok(AgentStep::Finished(response.text));
+            }
+
+            let mut results = Vec::new();
+            for call in response.tool_calls {
+                match AgentTool::from_call(&call) {
+                    Ok(tool) if tool.changes_mailbox() => session.pending.push(ProposedAction {
+                        call_id: call.id,
+                        description: tool.describe(),
+                        tool,
+                    }),
+                    Ok(tool) => match self.run_tool(tool, session).await {
+                        Ok(output) => results.push(ToolResult::success(call.id, output)),
+                        Err(e) => results.push(ToolResult::error(call.id, format!("{:#}", e))),
+                    },
+                    Err(e) => results.push(ToolResult::error(call.id, format!("{:#}", e))),
+                }
+            }
+
+            if !session.pending.is_empty() {
+                session.results = results;
+                return Ok(AgentStep::NeedsConfirmation(session.pending.clone()));
+            }
+            session.turns.push(ToolTurn::ToolResults(results));
+        }
+    }
+
+    /// Executes the approved pending actions and declines the rest.
+    ///
+    /// `approved` holds the call IDs of the actions the user confirmed.
+    /// Returns the executed actions, which are also recorded for undo.
+    pub async fn resolve(
+        &self,
+        session: &mut AgentSession,
+        approved: &[String],
+    ) -> Result<Vec<UndoableAction>> {
+        let mut results = std::mem::take(&mut session.results);
+        let mut executed = Vec::new();
+
+        for action in std::mem::take(&mut session.pending) {
+            if !approved.contains(&action.call_id) {
+                results.push(ToolResult::error(
+                    action.call_id,
+                    "The user declined this action.",
+                ));
+                continue;
+            }
+
+            match self.execute(&action.tool).await {
+                Ok(undoable) => {
+                    results.push(ToolResult::success(
+                        action.call_id,
+                        format!("Done: {}", undoable.description),
+                    ));
+                    self.undo.lock().await.record(undoable.clone());
+                    executed.push(undoable);
+                }
+                Err(e) => results.push(ToolResult::error(action.call_id, format!("{:#}", e))),
+            }
+        }
+
+        session.turns.push(ToolTurn::ToolResults(results));
+        Ok(executed)
+    }
+
+    /// Runs a tool that leaves the mailbox unchanged, returning its output.
+    async fn run_tool(&self, tool: AgentTool, session: &mut AgentSession) -> Result<String> {
+        match tool {
+            AgentTool::Search { query, limit } => {
+                let limit = limit
+                    .unwrap_or(self.settings.max_search_results)
+                    .clamp(1, self.settings.max_search_results);
+                let query = SearchQuery {
+                    limit,
+                    ..SearchQuery::new(query)
+                };
+                let results = self.search.search(query).await?;
+                let hits: Vec<_> = results
+                    .hits
+                    .iter()
+                    .map(|hit| {
+                        json!({
+                            "email_id": hit.email_id,
+                            "thread_id": hit.thread_id,
+                            "subject": hit.subject,
+                            "from": hit.from,
+                            "date": hit.date,
+                            "snippet": hit.snippet,
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "results": hits }).to_string())
+            }
+            AgentTool::FetchThread { thread_id } => {
+                let thread = self.email.get_thread(&thread_id).await?;
+                let messages: Vec<_> = thread
+                    .messages
+                    .iter()
+                    .map(|email| {
+                        let body = email.body_text.as_deref().unwrap_or(&email.snippet);
+                        json!({
+                            "email_id": email.id,
+                            "from": email.from.display(),
+                            "date": email.date,
+                            "body": truncate_chars(body, self.settings.max_body_chars),
+                        })
+                    })
+                    .collect();
+                Ok(json!({
+                    "thread_id": thread.id,
+                    "subject": thread.subject,
+                    "labels": thread.labels,
+                    "messages": messages,
+                })
+                .to_string())
+            }
+            AgentTool::DraftReply {
+                thread_id,
+                instructions,
+            } => {
+                let thread = self.email.get_thread(&thread_id).await?;
+                let draft = self
+                    .ai_service
+                    .draft_reply(&thread, instructions.as_deref())
+                    .await?;
+                session.drafts.push(AgentDraft {
+                    thread_id,
+                    content: draft.content.clone(),
+                });
+                Ok(format!(
+                    "Draft saved for the user to review:\n{}",
+                    draft.content
+                ))
+            }
+            tool => anyhow::bail!("{} needs confirmation", tool.describe()),
+        }
+    }
+
+    /// Executes a confirmed action and returns its undo record.
+    async fn execute(&self, tool: &AgentTool) -> Result<UndoableAction> {
+        match tool {
+            AgentTool::Archive { thread_ids } => {
+                self.email.archive(thread_ids).await?;
+                Ok(UndoableAction::new(
+                    ActionType::Archive,
+                    ActionState::archive(thread_ids.clone(), LabelId::from("INBOX")),
+                ))
+            }
+            AgentTool::Label {
+                thread_ids,
+                label_id,
+            } => {
+                self.email.apply_label(thread_ids, label_id).await?;
+                Ok(UndoableAction::new(
+                    ActionType::AddLabels,
+                    ActionState::labels(thread_ids.clone(), Vec::new(), vec![label_id.clone()]),
+                ))
+            }
+            AgentTool::Snooze { thread_id, until } => {
+                self.email.snooze(thread_id, *until).await?;
+                Ok(UndoableAction::new(
+                    ActionType::Snooze,
+                    ActionState {
+                        thread_ids: vec![thread_id.clone()],
+                        original_folder: Some(LabelId::from("INBOX")),
+                        snooze_until: Some(*until),
+                        ..Default::default()
+                    },
+                ))
+            }
+            tool => anyhow::bail!("{} does not change the mailbox", tool.describe()),
+        }
+    }
+}
+
+/// Truncates text to at most `max_chars` characters.
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, VecDeque};
+
+    use crate::domain::{AccountId, Address, Email, EmailId, MessageId, Thread, ThreadSummary};
+    use crate::providers::ai::{
+        CompletionRequest, CompletionResponse, CompletionStream, FinishReason, LlmProvider,
+        LlmResult, TokenUsage, ToolResponse,
+    };
+    use crate::services::email_service::ThreadMetadataUpdate;
+    use crate::services::{AiSettings, EmailMetadata, FtsHit, Pagination, SenderScope, ViewType};
+
+    fn make_thread(id: &str) -> Thread {
+        let email = Email {
+            id: EmailId::from(format!("{}-1", id)),
+            account_id: AccountId::from("account-1"),
+            thread_id: ThreadId::from(id),
+            message_id: MessageId::from(format!("<{}@example.com>", id)),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::with_name("news@example.com", "Weekly News"),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("This week in Rust".to_string()),
+            body_text: Some("Top stories of the week.".to_string()),
+            body_html: None,
+            snippet: "Top stories".to_string(),
+            date: Utc::now(),
+            is_read: true,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![LabelId::from("INBOX")],
+            attachments: vec![],
+            headers: vec![],
+        };
+
+        Thread {
+            id: ThreadId::from(id),
+            account_id: AccountId::from("account-1"),
+            subject: email.subject.clone(),
+            snippet: email.snippet.clone(),
+            participants: vec![email.from.clone()],
+            last_message_date: email.date,
+            unread_count: 0,
+            is_starred: false,
+            labels: vec![LabelId::from("INBOX")],
+            messages: vec![email],
+        }
+    }
+
+    #[derive(Default)]
+    struct MockEmailStorage {
+        threads: HashMap<ThreadId, Thread>,
+        updates: std::sync::Mutex<Vec<(ThreadId, ThreadMetadataUpdate)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailStorage for MockEmailStorage {
+        async fn get_threads(
+            &self,
+            _account_id: &AccountId,
+            _view: ViewType,
+            _pagination: Pagination,
+        ) -> Result<Vec<ThreadSummary>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_thread(&self, thread_id: &ThreadId) -> Result<Option<Thread>> {
+            Ok(self.threads.get(thread_id).cloned())
+        }
+
+        async fn store_thread(&self, _thread: &Thread) -> Result<()> {
+            Ok(())
+        }
+
+        async fn update_thread_metadata(
+            &self,
+            thread_id: &ThreadId,
+            updates: ThreadMetadataUpdate,
+        ) -> Result<()> {
+            self.updates
+                .lock()
+                .unwrap()
+                .push((thread_id.clone(), updates));
+            Ok(())
+        }
+
+        async fn find_threads_from(
+            &self,
+            _account_id: &AccountId,
+            _senders: &SenderScope,
+            _view: ViewType,
+        ) -> Result<Vec<ThreadId>> {
+            Ok(Vec::new())
+        }
+    }
+
+    struct MockSearchStorage;
+
+    #[async_trait::async_trait]
+    impl SearchStorage for MockSearchStorage {
+        async fn fts_search(&self, _query: &SearchQuery) -> Result<Vec<FtsHit>> {
+            Ok(vec![FtsHit {
+                email_id: EmailId::from("news-1"),
+                thread_id: ThreadId::from("news"),
+                rank: -4.0,
+                snippet: "Top stories".to_string(),
+            }])
+        }
+
+        async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>> {
+            Ok(ids
+                .iter()
+                .map(|id| EmailMetadata {
+                    email_id: id.clone(),
+                    account_id: AccountId::from("account-1"),
+                    thread_id: ThreadId::from("news"),
+                    subject: Some("This week in Rust".to_string()),
+                    snippet: "Top stories".to_string(),
+                    from: "news@example.com".to_string(),
+                    date: Utc::now(),
+                    to: Vec::new(),
+                    is_read: true,
+                    is_starred: false,
+                    is_draft: false,
+                    has_attachments: false,
+                    labels: Vec::new(),
+                })
+                .collect())
+        }
+
+        async fn rebuild_fts_index(&self, _account_id: &AccountId) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Provider that replays scripted tool responses and records requests.
+    struct ScriptedProvider {
+        responses: std::sync::Mutex<VecDeque<ToolResponse>>,
+        requests: std::sync::Mutex<Vec<ToolRequest>>,
+    }
+
+    impl ScriptedProvider {
+        fn new(responses: Vec<ToolResponse>) -> Self {
+            Self {
+                responses: std::sync::Mutex::new(responses.into()),
+                requests: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        async fn complete(&self, _request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            Ok(CompletionResponse {
+                text: "Thanks, I'll read it this weekend.".to_string(),
+                tokens_used: TokenUsage::default(),
+                finish_reason: FinishReason::Stop,
+            })
+        }
+
+        async fn stream_complete(
+            &self,
+            _request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            unimplemented!("the agent does not stream")
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            true
+        }
+
+        async fn complete_with_tools(&self, request: &ToolRequest) -> LlmResult<ToolResponse> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no scripted response left"))
+        }
+
+        fn max_context_length(&self) -> usize {
+            8192
+        }
+
+        fn model(&self) -> &str {
+            "scripted-model"
+        }
+    }
+
+    fn calls(calls: &[(&str, &str, serde_json::Value)]) -> ToolResponse {
+        ToolResponse {
+            text: String::new(),
+            tool_calls: calls
+                .iter()
+                .map(|(id, name, arguments)| ToolCall {
+                    id: id.to_string(),
+                    name: name.to_string(),
+                    arguments: arguments.clone(),
+                })
+                .collect(),
+            tokens_used: TokenUsage::default(),
+            finish_reason: FinishReason::ToolCalls,
+        }
+    }
+
+    fn reply(text: &str) -> ToolResponse {
+        ToolResponse {
+            text: text.to_string(),
+            tool_calls: Vec::new(),
+            tokens_used: TokenUsage::default(),
+            finish_reason: FinishReason::Stop,
+        }
+    }
+
+    struct Fixture {
+        agent: AgentService<MockEmailStorage, MockSearchStorage>,
+        storage: Arc<MockEmailStorage>,
+        provider: Arc<ScriptedProvider>,
+        undo: Arc<Mutex<UndoService>>,
+    }
+
+    async fn fixture(responses: Vec<ToolResponse>) -> Fixture {
+        let storage = Arc::new(MockEmailStorage {
+            threads: HashMap::from([(ThreadId::from("news"), make_thread("news"))]),
+            ..Default::default()
+        });
+        let provider = Arc::new(ScriptedProvider::new(responses));
+        let ai_service = Arc::new(AiService::new(AiSettings::default()));
+        ai_service
+            .register_provider("anthropic", provider.clone())
+            .await;
+        let undo = Arc::new(Mutex::new(UndoService::new()));
+        let agent = AgentService::new(
+            Arc::new(EmailService::new(storage.clone())),
+            Arc::new(SearchService::new(Arc::new(MockSearchStorage))),
+            ai_service,
+            undo.clone(),
+        );
+        Fixture {
+            agent,
+            storage,
+            provider,
+            undo,
+        }
+    }
+
+    #[test]
+    fn parses_typed_tool_calls() {
+        let call = |name: &str, arguments: serde_json::Value| ToolCall {
+            id: "call".to_string(),
+            name: name.to_string(),
+            arguments,
+        };
+
+        let tool = AgentTool::from_call(&call(
+            "label",
+            json!({"thread_ids": ["a", "b"], "label_id": "Receipts"}),
+        ))
+        .unwrap();
+        assert_eq!(
+            tool,
+            AgentTool::Label {
+                thread_ids: vec![ThreadId::from("a"), ThreadId::from("b")],
+                label_id: LabelId::from("Receipts"),
+            }
+        );
+        assert!(tool.changes_mailbox());
+        assert_eq!(tool.describe(), "Label 2 conversations as Receipts");
+
+        let tool = AgentTool::from_call(&call("search", json!({"query": "invoice"}))).unwrap();
+        assert!(!tool.changes_mailbox());
+
+        let tool = AgentTool::from_call(&call(
+            "snooze",
+            json!({"thread_id": "a", "until": "2026-03-02T09:00:00Z"}),
+        ))
+        .unwrap();
+        assert!(tool.describe().contains("2026-03-02 09:00"));
+
+        assert!(AgentTool::from_call(&call("delete_everything", json!({}))).is_err());
+        assert!(AgentTool::from_call(&call("archive", json!({"thread_ids": "a"}))).is_err());
+        assert!(AgentTool::from_call(&call("archive", json!(["a"]))).is_err());
+    }
+
+    #[test]
+    fn definitions_match_tools() {
+        for definition in AgentTool::definitions() {
+            let example = match definition.name.as_str() {
+                "search" => json!({"query": "q"}),
+                "fetch_thread" | "draft_reply" => json!({"thread_id": "t"}),
+                "archive" => json!({"thread_ids": ["t"]}),
+                "label" => json!({"thread_ids": ["t"], "label_id": "l"}),
+                "snooze" => json!({"thread_id": "t", "until": "2026-01-01T00:00:00Z"}),
+                other => panic!("untested tool {}", other),
+            };
+            let call = ToolCall {
+                id: "call".to_string(),
+                name: definition.name.clone(),
+                arguments: example,
+            };
+            assert!(AgentTool::from_call(&call).is_ok(), "{}", definition.name);
+        }
+    }
+
+    #[tokio::test]
+    async fn confirmed_actions_run_and_are_undoable() {
+        let f = fixture(vec![
+            calls(&[("call-1", "search", json!({"query": "newsletter"}))]),
+            calls(&[
+                ("call-2", "archive", json!({"thread_ids": ["news"]})),
+                ("call-3", "fetch_thread", json!({"thread_id": "news"})),
+            ]),
+            reply("Archived the newsletter."),
+        ])
+        .await;
+
+        let mut session = f.agent.start("Archive the newsletter");
+        let actions = match f.agent.step(&mut session).await.unwrap() {
+            AgentStep::NeedsConfirmation(actions) => actions,
+            other => panic!("expected a proposal, got {:?}", other),
+        };
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].description, "Archive 1 conversation");
+
+        // Nothing changes before confirmation.
+        assert!(f.storage.updates.lock().unwrap().is_empty());
+        assert!(f.agent.step(&mut session).await.is_err());
+
+        let executed = f
+            .agent
+            .resolve(&mut session, &["call-2".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(executed.len(), 1);
+        assert_eq!(executed[0].action_type, ActionType::Archive);
+        assert_eq!(
+            f.storage.updates.lock().unwrap()[0].1.remove_labels,
+            vec![LabelId::from("INBOX")]
+        );
+        assert!(f.undo.lock().await.can_undo());
+
+        let step = f.agent.step(&mut session).await.unwrap();
+        assert_eq!(
+            step,
+            AgentStep::Finished("Archived the newsletter.".to_string())
+        );
+
+        // The search result went back to the model, then the archive and
+        // thread results together.
+        let requests = f.provider.requests.lock().unwrap();
+        let ToolTurn::ToolResults(results) = &requests[1].turns[2] else {
+            panic!("expected tool results");
+        };
+        assert!(results[0].content.contains("\"thread_id\":\"news\""));
+        let ToolTurn::ToolResults(results) = &requests[2].turns[4] else {
+            panic!("expected tool results");
+        };
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .any(|r| r.call_id == "call-3" && r.content.contains("Top stories of the week.")));
+        assert!(results
+            .iter()
+            .any(|r| r.call_id == "call-2" && r.content.starts_with("Done")));
+    }
+
+    #[tokio::test]
+    async fn declined_actions_are_reported_to_the_model() {
+        let f = fixture(vec![
+            calls(&[(
+                "call-1",
+                "snooze",
+                json!({"thread_id": "news", "until": "2026-03-02T09:00:00Z"}),
+            )]),
+            reply("Okay, I left it in the inbox."),
+        ])
+        .await;
+
+        let mut session = f.agent.start("Snooze the newsletter");
+        f.agent.step(&mut session).await.unwrap();
+        assert_eq!(session.pending().len(), 1);
+
+        let executed = f.agent.resolve(&mut session, &[]).await.unwrap();
+        assert!(executed.is_empty());
+        assert!(f.storage.updates.lock().unwrap().is_empty());
+        assert!(!f.undo.lock().await.can_undo());
+
+        f.agent.step(&mut session).await.unwrap();
+        let requests = f.provider.requests.lock().unwrap();
+        let ToolTurn::ToolResults(results) = requests[1].turns.last().unwrap() else {
+            panic!("expected tool results");
+        };
+        assert!(results[0].is_error);
+        assert!(results[0].content.contains("declined"));
+    }
+
+    #[tokio::test]
+    async fn drafts_and_bad_calls_run_without_confirmation() {
+        let f = fixture(vec![
+            calls(&[
+                ("call-1", "draft_reply", json!({"thread_id": "news"})),
+                ("call-2", "fetch_thread", json!({"thread_id": "missing"})),
+                ("call-3", "forward", json!({})),
+            ]),
+            reply("I drafted a reply."),
+        ])
+        .await;
+
+        let mut session = f.agent.start("Reply to the newsletter");
+        let step = f.agent.step(&mut session).await.unwrap();
+        assert_eq!(step, AgentStep::Finished("I drafted a reply.".to_string()));
+        assert_eq!(session.drafts().len(), 1);
+        assert_eq!(
+            session.drafts()[0].content,
+            "Thanks, I'll read it this weekend."
+        );
+
+        let requests = f.provider.requests.lock().unwrap();
+        let ToolTurn::ToolResults(results) = &requests[1].turns[2] else {
+            panic!("expected tool results");
+        };
+        assert!(!results[0].is_error);
+        assert!(results[1].is_error);
+        assert!(results[2].is_error);
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_steps() {
+        let looping = || calls(&[("call", "search", json!({"query": "again"}))]);
+        let f = fixture(vec![looping(), looping(), looping()]).await;
+        let agent = f.agent.with_settings(AgentSettings {
+            max_steps: 2,
+            ..AgentSettings::default()
+        });
+
+        let mut session = agent.start("Keep searching");
+        let error = agent.step(&mut session).await.unwrap_err();
+        assert!(error.to_string().contains("2 steps"));
+    }
+}
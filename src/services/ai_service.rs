@@ -6,30 +6,77 @@
 //! - Semantic search
 //! - Email categorization
 //! - Sender analysis
+//!
+//! Providers implement [`LlmProvider`] from `providers::ai`, so the HTTP
+//! providers (Anthropic, OpenAI-compatible, Ollama) can be registered
+//! directly. Summaries and drafts can also be streamed as they are generated.
+//!
+//! Each task is routed through an ordered fallback chain of providers (see
+//! [`RoutingSettings`]), with a timeout per attempt and a circuit breaker
+//! that skips providers which keep failing.
+//!
+//! Threads too long for the provider's context window are summarized in
+//! stages: the messages are split into chunks that fit, each chunk is
+//! summarized, and the chunk summaries are merged into one.
+//!
+//! Summaries, categories and sender analyses are requested as structured
+//! JSON output (see [`StructuredOutput`]) and validated against their
+//! schemas, with a repair prompt when a model replies with invalid JSON.
+//!
+//! Summaries, categorizations and sender analyses are cached in an
+//! [`AiCacheStorage`] when one is set, keyed by a hash of the prompt input,
+//! the prompt version, the provider and the model.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt};
+use ring::digest;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::domain::{AccountId, Email, EmailId, SenderAnalysis, Thread};
-
-/// LLM provider trait for abstracting over different AI backends.
+use crate::domain::{AccountId, Email, EmailId, SenderAnalysis, Thread, ThreadId};
+use crate::embedding::PassageMatch;
+use crate::providers::ai::{
+    complete_structured, BreakerPolicy, CircuitBreaker, CompletionRequest, CompletionStream,
+    FallbackProvider, FinishReason, LlmProvider, LlmResult, Message, OutputSchema, ServedBy,
+    StreamChunk, StructuredOutput,
+};
+use crate::services::sender_signals::format_email_for_analysis;
+use crate::services::{EmailMetadata, SearchQuery, SenderSignals, StatsEvent, StatsStorage};
+
+/// How many more candidates to fetch from the vector index when a semantic
+/// search is filtered, since filtering happens after the nearest-neighbour
+/// lookup.
+const FILTERED_SEARCH_OVERSAMPLING: usize = 4;
+
+/// Version of the summary, categorization and sender prompts.
 ///
-/// Implementations include OpenAI, Anthropic, Ollama, and Candle (local).
-#[async_trait::async_trait]
-pub trait LlmProvider: Send + Sync {
-    /// Returns the provider name (e.g., "openai", "anthropic").
-    fn name(&self) -> &str;
+/// Part of every cache key; bump it when a prompt changes so results from
+/// the old prompt are not reused.
+const AI_PROMPT_VERSION: u32 = 3;
 
-    /// Performs a completion request.
-    async fn complete(&self, request: &CompletionRequest) -> Result<CompletionResponse>;
+/// Number of served requests kept for [`AiService::recent_requests`].
+const SERVED_LOG_LEN: usize = 100;
 
-    /// Returns the maximum context length in tokens.
-    fn max_context_length(&self) -> usize;
-}
+/// Tokens reserved for message formatting around summary prompts.
+const SUMMARY_PROMPT_OVERHEAD_TOKENS: usize = 128;
+
+/// Smallest prompt budget a long thread is worth splitting into chunks for.
+const MIN_SUMMARY_CHUNK_TOKENS: usize = 256;
+
+/// How many chunk summaries are requested at once.
+const SUMMARY_CHUNK_CONCURRENCY: usize = 4;
+
+/// Output layout asked for when a summary is streamed as text, so it can be
+/// read back with [`Summary::parse`].
+const STAGED_SUMMARY_FORMAT: &str = "Reply with a short summary paragraph, then a \
+\"Key points:\" section and an \"Action items:\" section, each with one \"- \" item per line.";
 
 /// Embedding engine trait for semantic search.
 #[async_trait::async_trait]
@@ -40,74 +87,106 @@ pub trait EmbeddingEngine: Send + Sync {
     /// Searches for similar emails by embedding.
     async fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(EmailId, f32)>>;
 
+    /// Searches for similar emails, returning each email's best-matching passage.
+    ///
+    /// The default implementation wraps [`search`](Self::search) and reports
+    /// no passage text.
+    async fn search_passages(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+    ) -> Result<Vec<PassageMatch>> {
+        Ok(self
+            .search(query_embedding, limit)
+            .await?
+            .into_iter()
+            .map(|(email_id, score)| PassageMatch {
+                email_id,
+                score,
+                passage_index: 0,
+                text: None,
+            })
+            .collect())
+    }
+
     /// Indexes an email for future search.
     async fn index_email(&self, email: &Email) -> Result<()>;
 }
 
-/// A request for LLM completion.
-#[derive(Debug, Clone)]
-pub struct CompletionRequest {
-    /// System prompt to set context.
-    pub system_prompt: Option<String>,
-    /// Conversation messages.
-    pub messages: Vec<ChatMessage>,
-    /// Temperature for response randomness (0.0-1.0).
-    pub temperature: f32,
-    /// Maximum tokens to generate.
-    pub max_tokens: Option<usize>,
-}
-
-/// A message in a chat completion.
-#[derive(Debug, Clone)]
-pub struct ChatMessage {
-    /// Role of the message author.
-    pub role: ChatRole,
-    /// Message content.
-    pub content: String,
+/// Storage lookups used to filter and hydrate semantic search results.
+///
+/// Every [`SearchStorage`](crate::services::SearchStorage) implements this,
+/// so the search service's storage can be shared with the AI service.
+#[async_trait::async_trait]
+pub trait SemanticSearchStorage: Send + Sync {
+    /// Looks up email metadata by IDs. Unknown IDs are skipped.
+    async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>>;
 }
 
-/// Role in a chat conversation.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ChatRole {
-    /// System message for context.
-    System,
-    /// User message.
-    User,
-    /// Assistant (AI) response.
-    Assistant,
+/// Kind of AI result held in the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiCacheKind {
+    /// A thread [`Summary`].
+    Summary,
+    /// The categories of an email.
+    Categories,
+    /// A [`SenderAnalysis`].
+    SenderAnalysis,
 }
 
-/// Response from an LLM completion.
-#[derive(Debug, Clone)]
-pub struct CompletionResponse {
-    /// Generated text.
-    pub text: String,
-    /// Token usage statistics.
-    pub tokens_used: TokenUsage,
-    /// Why generation stopped.
-    pub finish_reason: FinishReason,
-}
-
-/// Token usage statistics for a completion.
-#[derive(Debug, Clone, Default)]
-pub struct TokenUsage {
-    /// Tokens in the prompt.
-    pub prompt_tokens: usize,
-    /// Tokens in the response.
-    pub completion_tokens: usize,
-    /// Total tokens used.
-    pub total_tokens: usize,
-}
-
-/// Reason why generation finished.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum FinishReason {
-    /// Natural completion.
-    Stop,
-    /// Hit max token limit.
-    Length,
-    /// Content filter triggered.
-    ContentFilter,
+impl AiCacheKind {
+    /// Returns the name stored with cache entries.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AiCacheKind::Summary => "summary",
+            AiCacheKind::Categories => "categories",
+            AiCacheKind::SenderAnalysis => "sender_analysis",
+        }
+    }
+}
+
+/// A cached AI result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiCacheEntry {
+    /// Hash of the kind, prompt version, provider, model and prompt input.
+    pub key: String,
+    /// What the value holds.
+    pub kind: AiCacheKind,
+    /// Thread the result was generated from, if any.
+    pub thread_id: Option<ThreadId>,
+    /// Provider that generated the result.
+    pub provider: String,
+    /// Model that generated the result.
+    pub model: String,
+    /// The result as JSON.
+    pub value: String,
+}
+
+/// Persistent storage for AI results.
+#[async_trait::async_trait]
+pub trait AiCacheStorage: Send + Sync {
+    /// Returns the cached value for a key.
+    async fn get_cached(&self, key: &str) -> Result<Option<String>>;
+
+    /// Stores a result, replacing earlier results of the same kind for its
+    /// thread.
+    async fn put_cached(&self, entry: AiCacheEntry) -> Result<()>;
+
+    /// Drops the cached results for a thread. Returns how many were removed.
+    async fn invalidate_thread(&self, thread_id: &ThreadId) -> Result<usize>;
+
+    /// Drops every cached result. Returns how many were removed.
+    async fn clear_cached(&self) -> Result<usize>;
+}
+
+/// Cache lookups since the service started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AiCacheStats {
+    /// Results served from the cache.
+    pub hits: u64,
+    /// Results generated because nothing was cached.
+    pub misses: u64,
 }
 
 /// Summary of an email thread.
@@ -124,11 +203,12 @@ pub struct Summary {
 }
 
 impl Summary {
-    /// Parses a summary from LLM output.
+    /// Parses a summary from streamed text.
     ///
-    /// Expects structured output with sections for summary, key points, and action items.
+    /// Expects sections for summary, key points, and action items, in the
+    /// layout [`Summary::to_text`] writes. Complete replies are requested as
+    /// structured output instead.
     pub fn parse(text: &str) -> Self {
-        // Simple parsing - in production this would use structured output
         let mut summary_text = String::new();
         let mut key_points = Vec::new();
         let mut action_items = Vec::new();
@@ -182,6 +262,153 @@ impl Summary {
             action_items,
         }
     }
+
+    /// Formats the summary in the layout [`Summary::parse`] reads.
+    pub fn to_text(&self) -> String {
+        let mut text = self.text.clone();
+        for (title, items) in [
+            ("Key points:", &self.key_points),
+            ("Action items:", &self.action_items),
+        ] {
+            if !items.is_empty() {
+                text.push_str(&format!("\n\n{}", title));
+                for item in items {
+                    text.push_str(&format!("\n- {}", item));
+                }
+            }
+        }
+        text
+    }
+
+    /// Restores key points or action items that merging dropped entirely.
+    ///
+    /// The merge prompt asks the model to carry every item forward, but when
+    /// a merged section comes back empty the items of `parts` are used.
+    fn restore_items(&mut self, parts: &[Summary]) {
+        let collect = |items: fn(&Summary) -> &Vec<String>| {
+            let mut all: Vec<String> = Vec::new();
+            for item in parts.iter().flat_map(items) {
+                if !all.iter().any(|seen| seen.eq_ignore_ascii_case(item)) {
+                    all.push(item.clone());
+                }
+            }
+            all
+        };
+
+        if self.key_points.is_empty() {
+            self.key_points = collect(|part| &part.key_points);
+        }
+        if self.action_items.is_empty() {
+            self.action_items = collect(|part| &part.action_items);
+        }
+    }
+}
+
+impl StructuredOutput for Summary {
+    fn output_schema() -> OutputSchema {
+        let items = serde_json::json!({"type": "array", "items": {"type": "string"}});
+        OutputSchema {
+            name: "thread_summary".to_string(),
+            description: "Summary of an email thread".to_string(),
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "text": {"type": "string", "description": "Concise summary paragraph"},
+                    "key_points": items,
+                    "action_items": items,
+                },
+                "required": ["text", "key_points", "action_items"],
+                "additionalProperties": false,
+            }),
+        }
+    }
+}
+
+impl StructuredOutput for SenderAnalysis {
+    fn output_schema() -> OutputSchema {
+        use crate::domain::{ScreenerAction, SenderType};
+
+        let sender_types: Vec<serde_json::Value> = [
+            SenderType::KnownContact,
+            SenderType::Newsletter,
+            SenderType::Marketing,
+            SenderType::Recruiter,
+            SenderType::Support,
+            SenderType::Unknown,
+        ]
+        .iter()
+        .filter_map(|t| serde_json::to_value(t).ok())
+        .collect();
+        let actions: Vec<serde_json::Value> = [
+            ScreenerAction::Approve,
+            ScreenerAction::Reject,
+            ScreenerAction::Review,
+        ]
+        .iter()
+        .filter_map(|a| serde_json::to_value(a).ok())
+        .collect();
+
+        OutputSchema {
+            name: "sender_analysis".to_string(),
+            description: "Classification of an email sender for the screener".to_string(),
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "likely_type": {"type": "string", "enum": sender_types},
+                    "reasoning": {"type": "string"},
+                    "suggested_action": {"type": "string", "enum": actions},
+                    "confidence": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "Probability that the suggested action is right",
+                    },
+                },
+                "required": ["likely_type", "reasoning", "suggested_action", "confidence"],
+                "additionalProperties": false,
+            }),
+        }
+    }
+}
+
+/// Categories of an email, as returned by the model.
+#[derive(Debug, Deserialize)]
+struct CategoriesOutput {
+    categories: Vec<Category>,
+}
+
+impl StructuredOutput for CategoriesOutput {
+    fn output_schema() -> OutputSchema {
+        let names: Vec<serde_json::Value> = Category::all()
+            .iter()
+            .filter_map(|c| serde_json::to_value(c).ok())
+            .collect();
+        OutputSchema {
+            name: "email_categories".to_string(),
+            description: "Categories of an email, most relevant first".to_string(),
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "categories": {
+                        "type": "array",
+                        "items": {"type": "string", "enum": names},
+                    },
+                },
+                "required": ["categories"],
+                "additionalProperties": false,
+            }),
+        }
+    }
+}
+
+/// The final summary request for a thread.
+struct SummaryPlan {
+    /// Route that serves every stage.
+    provider: Arc<FallbackProvider>,
+    /// Request for the thread, or for merging its chunk summaries.
+    request: CompletionRequest,
+    /// Chunk summaries being merged; empty when the thread fit in one prompt.
+    parts: Vec<Summary>,
 }
 
 /// A suggested draft reply generated by AI.
@@ -204,6 +431,8 @@ pub struct SearchResult {
     pub subject: Option<String>,
     /// Preview snippet.
     pub snippet: String,
+    /// Sender display.
+    pub from: String,
     /// Relevance score (0.0-1.0).
     pub relevance: f32,
     /// Highlighted matching text.
@@ -274,6 +503,70 @@ impl Category {
     }
 }
 
+/// A task that is routed to its own chain of providers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AiTask {
+    /// Thread summaries.
+    Summary,
+    /// Draft replies.
+    Compose,
+    /// Email categorization.
+    Categorize,
+    /// Sender analysis for the screener.
+    SenderAnalysis,
+    /// Ask-my-inbox and the mailbox agent.
+    Assistant,
+}
+
+/// Providers that serve a task.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutePolicy {
+    /// Registered provider names, tried in order.
+    pub providers: Vec<String>,
+    /// Seconds allowed per attempt; the routing default when unset.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Provider routing for AI tasks.
+///
+/// Tasks without a route use their feature's provider override or the
+/// default provider, with no fallback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingSettings {
+    /// Fallback chains by task.
+    pub routes: HashMap<AiTask, RoutePolicy>,
+    /// Seconds allowed per attempt.
+    pub request_timeout_secs: u64,
+    /// Consecutive failures after which a provider is skipped.
+    pub failure_threshold: u32,
+    /// Seconds a failing provider is skipped for.
+    pub cooldown_secs: u64,
+}
+
+impl Default for RoutingSettings {
+    fn default() -> Self {
+        Self {
+            routes: HashMap::new(),
+            request_timeout_secs: 60,
+            failure_threshold: 3,
+            cooldown_secs: 60,
+        }
+    }
+}
+
+/// A request served by a provider.
+#[derive(Debug, Clone)]
+pub struct ServedRequest {
+    /// Task the request was made for.
+    pub task: AiTask,
+    /// Provider that served it.
+    pub served_by: ServedBy,
+    /// When the response arrived.
+    pub at: DateTime<Utc>,
+}
+
 /// Settings for AI functionality.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiSettings {
@@ -287,6 +580,9 @@ pub struct AiSettings {
     pub compose_settings: ComposeSettings,
     /// Settings for semantic search.
     pub search_settings: SearchSettings,
+    /// Provider routing per task.
+    #[serde(default)]
+    pub routing: RoutingSettings,
 }
 
 impl Default for AiSettings {
@@ -297,6 +593,7 @@ impl Default for AiSettings {
             summary_settings: SummarySettings::default(),
             compose_settings: ComposeSettings::default(),
             search_settings: SearchSettings::default(),
+            routing: RoutingSettings::default(),
         }
     }
 }
@@ -418,6 +715,20 @@ pub struct AiService {
     providers: RwLock<HashMap<String, Arc<dyn LlmProvider>>>,
     /// Embedding engine for semantic search.
     embedding_engine: RwLock<Option<Arc<dyn EmbeddingEngine>>>,
+    /// Metadata lookups for semantic search results.
+    search_storage: RwLock<Option<Arc<dyn SemanticSearchStorage>>>,
+    /// Persistent cache of generated results.
+    cache: RwLock<Option<Arc<dyn AiCacheStorage>>>,
+    /// Results served from the cache.
+    cache_hits: AtomicU64,
+    /// Cache lookups that found nothing.
+    cache_misses: AtomicU64,
+    /// Where cache hits are recorded as stats events.
+    stats: RwLock<Option<Arc<dyn StatsStorage>>>,
+    /// Failure tracking shared by every route.
+    breaker: Arc<CircuitBreaker>,
+    /// Recently served requests, oldest first.
+    served: Arc<std::sync::Mutex<VecDeque<ServedRequest>>>,
     /// AI settings.
     settings: RwLock<AiSettings>,
 }
@@ -428,6 +739,13 @@ impl AiService {
         Self {
             providers: RwLock::new(HashMap::new()),
             embedding_engine: RwLock::new(None),
+            search_storage: RwLock::new(None),
+            cache: RwLock::new(None),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            stats: RwLock::new(None),
+            breaker: Arc::new(CircuitBreaker::new()),
+            served: Arc::new(std::sync::Mutex::new(VecDeque::new())),
             settings: RwLock::new(settings),
         }
     }
@@ -444,6 +762,49 @@ impl AiService {
         *embedding = Some(engine);
     }
 
+    /// Sets the storage used to filter and hydrate semantic search results.
+    pub async fn set_search_storage(&self, storage: Arc<dyn SemanticSearchStorage>) {
+        let mut search_storage = self.search_storage.write().await;
+        *search_storage = Some(storage);
+    }
+
+    /// Sets the persistent cache for generated results.
+    pub async fn set_cache(&self, cache: Arc<dyn AiCacheStorage>) {
+        let mut current = self.cache.write().await;
+        *current = Some(cache);
+    }
+
+    /// Sets the storage that cache hits are recorded in, as
+    /// [`StatsEvent::AiCacheHit`] events for the account the result is for.
+    pub async fn set_stats_storage(&self, storage: Arc<dyn StatsStorage>) {
+        let mut stats = self.stats.write().await;
+        *stats = Some(storage);
+    }
+
+    /// Returns the cache hits and misses since the service started.
+    pub fn cache_stats(&self) -> AiCacheStats {
+        AiCacheStats {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Drops every cached result. Returns how many were removed.
+    pub async fn clear_cache(&self) -> Result<usize> {
+        match self.cache.read().await.clone() {
+            Some(cache) => cache.clear_cached().await,
+            None => Ok(0),
+        }
+    }
+
+    /// Drops the cached results for a thread, e.g. after it gained messages.
+    pub async fn invalidate_thread(&self, thread_id: &ThreadId) -> Result<usize> {
+        match self.cache.read().await.clone() {
+            Some(cache) => cache.invalidate_thread(thread_id).await,
+            None => Ok(0),
+        }
+    }
+
     /// Updates the AI settings.
     pub async fn update_settings(&self, settings: AiSettings) {
         let mut current = self.settings.write().await;
@@ -455,16 +816,67 @@ impl AiService {
         self.settings.read().await.clone()
     }
 
-    /// Gets a provider by name, falling back to the default.
-    async fn get_provider(&self, name: Option<&str>) -> Result<Arc<dyn LlmProvider>> {
+    /// Returns the provider route for features without a provider setting
+    /// of their own.
+    pub async fn default_provider(&self) -> Result<Arc<dyn LlmProvider>> {
+        if !self.settings.read().await.enabled {
+            anyhow::bail!("AI features are disabled");
+        }
+        Ok(self.route(AiTask::Assistant, None).await?)
+    }
+
+    /// Returns the most recently served requests, oldest first.
+    pub fn recent_requests(&self) -> Vec<ServedRequest> {
+        self.served.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Builds the fallback chain for a task.
+    ///
+    /// Without a route for the task, the chain is `name` or the default
+    /// provider alone. Providers that are not registered are left out.
+    async fn route(&self, task: AiTask, name: Option<&str>) -> Result<Arc<FallbackProvider>> {
         let settings = self.settings.read().await;
-        let provider_name = name.unwrap_or(&settings.default_provider);
+        let routing = &settings.routing;
+        let policy = routing
+            .routes
+            .get(&task)
+            .filter(|policy| !policy.providers.is_empty());
+        let names = match policy {
+            Some(policy) => policy.providers.clone(),
+            None => vec![name.unwrap_or(&settings.default_provider).to_string()],
+        };
+        let timeout = policy
+            .and_then(|policy| policy.timeout_secs)
+            .unwrap_or(routing.request_timeout_secs);
 
         let providers = self.providers.read().await;
-        providers
-            .get(provider_name)
-            .cloned()
-            .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))
+        let chain: Vec<(String, Arc<dyn LlmProvider>)> = names
+            .iter()
+            .filter_map(|name| Some((name.clone(), providers.get(name)?.clone())))
+            .collect();
+        if chain.is_empty() {
+            anyhow::bail!("Provider not found: {}", names.join(", "));
+        }
+
+        let served = self.served.clone();
+        let provider = FallbackProvider::new(chain, Duration::from_secs(timeout), self.breaker.clone())
+            .with_policy(BreakerPolicy {
+                failure_threshold: routing.failure_threshold,
+                cooldown: Duration::from_secs(routing.cooldown_secs),
+            })
+            .on_served(Arc::new(move |served_by| {
+                tracing::debug!(?task, provider = %served_by.provider, attempts = served_by.attempts, "AI request served");
+                let mut served = served.lock().unwrap();
+                if served.len() == SERVED_LOG_LEN {
+                    served.pop_front();
+                }
+                served.push_back(ServedRequest {
+                    task,
+                    served_by,
+                    at: Utc::now(),
+                });
+            }));
+        Ok(Arc::new(provider))
     }
 
     /// Summarizes an email thread.
@@ -479,30 +891,91 @@ impl AiService {
     ///
     /// A summary containing text, key points, and action items.
     pub async fn summarize_thread(&self, thread: &Thread) -> Result<Summary> {
-        let settings = self.settings.read().await;
-        if !settings.enabled || !settings.summary_settings.enabled {
-            anyhow::bail!("AI summarization is disabled");
+        let (key, input) = self.summary_cache_key(thread).await?;
+        if let Some(summary) = self.cached(&key, &thread.account_id).await {
+            return Ok(summary);
         }
 
-        let provider = self
-            .get_provider(settings.summary_settings.provider.as_deref())
-            .await?;
+        let plan = self.summary_plan(thread).await?;
+        let mut summary: Summary =
+            complete_structured(plan.provider.as_ref(), &plan.request).await?;
+        summary.restore_items(&plan.parts);
+
+        self.store(
+            AiCacheKind::Summary,
+            &input,
+            Some(&thread.id),
+            &plan.provider,
+            &summary,
+        )
+        .await;
+        Ok(summary)
+    }
 
-        // Build the thread content for summarization
-        let thread_content = self.format_thread_for_summary(thread);
+    /// Streams a summary of an email thread as it is generated.
+    ///
+    /// The summary is streamed as readable text rather than JSON; the
+    /// concatenated chunk text can be parsed with [`Summary::parse`] once the
+    /// stream ends. For threads summarized in stages, only the final merge
+    /// is streamed.
+    pub async fn summarize_thread_stream(&self, thread: &Thread) -> Result<CompletionStream> {
+        let (key, input) = self.summary_cache_key(thread).await?;
+        if let Some(summary) = self.cached::<Summary>(&key, &thread.account_id).await {
+            let chunk = StreamChunk {
+                text: summary.to_text(),
+                finish_reason: Some(FinishReason::Stop),
+            };
+            return Ok(Box::pin(futures::stream::iter([Ok(chunk)])));
+        }
 
-        let request = CompletionRequest {
-            system_prompt: Some(settings.summary_settings.system_prompt.clone()),
-            messages: vec![ChatMessage {
-                role: ChatRole::User,
-                content: thread_content,
-            }],
-            temperature: 0.3,
-            max_tokens: Some(settings.summary_settings.max_length),
+        let plan = self.summary_plan(thread).await?;
+        let mut request = plan.request.clone();
+        request.system_prompt = Some(format!(
+            "{}\n\n{}",
+            request.system_prompt.unwrap_or_default(),
+            STAGED_SUMMARY_FORMAT
+        ));
+        let stream = plan.provider.stream_complete(&request).await?;
+        let Some(cache) = self.cache.read().await.clone() else {
+            return Ok(stream);
         };
 
-        let response = provider.complete(&request).await?;
-        Ok(Summary::parse(&response.text))
+        // Cache the summary once the stream finishes without errors
+        let text = Arc::new(std::sync::Mutex::new(Some(String::new())));
+        let recorder = text.clone();
+        let recorded = stream.inspect(move |chunk| {
+            let mut text = recorder.lock().unwrap();
+            match chunk {
+                Ok(chunk) => {
+                    if let Some(text) = text.as_mut() {
+                        text.push_str(&chunk.text);
+                    }
+                }
+                Err(_) => *text = None,
+            }
+        });
+
+        let thread_id = thread.id.clone();
+        let store = futures::stream::once(async move {
+            let Some(text) = text.lock().unwrap().take() else {
+                return;
+            };
+            let mut summary = Summary::parse(&text);
+            summary.restore_items(&plan.parts);
+            let entry = cache_entry(
+                AiCacheKind::Summary,
+                &input,
+                Some(&thread_id),
+                &plan.provider,
+                &summary,
+            );
+            if let Err(e) = cache.put_cached(entry).await {
+                tracing::warn!(error = %e, "Failed to cache summary");
+            }
+        })
+        .filter_map(|()| futures::future::ready(None::<LlmResult<StreamChunk>>));
+
+        Ok(Box::pin(recorded.chain(store)))
     }
 
     /// Generates a draft reply for a thread.
@@ -523,13 +996,196 @@ impl AiService {
         thread: &Thread,
         instructions: Option<&str>,
     ) -> Result<DraftSuggestion> {
+        let (provider, request) = self.draft_request(thread, instructions).await?;
+        let response = provider.complete(&request).await?;
+
+        Ok(DraftSuggestion {
+            content: response.text,
+            confidence: 0.8, // Could be refined based on model confidence
+        })
+    }
+
+    /// Streams a draft reply for a thread as it is generated.
+    pub async fn draft_reply_stream(
+        &self,
+        thread: &Thread,
+        instructions: Option<&str>,
+    ) -> Result<CompletionStream> {
+        let (provider, request) = self.draft_request(thread, instructions).await?;
+        Ok(provider.stream_complete(&request).await?)
+    }
+
+    /// Returns the summary settings, or an error if summaries are disabled.
+    async fn summary_settings(&self) -> Result<SummarySettings> {
+        let settings = self.settings.read().await;
+        if !settings.enabled || !settings.summary_settings.enabled {
+            anyhow::bail!("AI summarization is disabled");
+        }
+        Ok(settings.summary_settings.clone())
+    }
+
+    /// Computes the key to look up a thread summary under, along with the
+    /// input it hashes so the result can be stored under the provider that
+    /// serves it.
+    async fn summary_cache_key(&self, thread: &Thread) -> Result<(String, String)> {
+        let settings = self.summary_settings().await?;
+        let provider = self
+            .route(AiTask::Summary, settings.provider.as_deref())
+            .await?;
+        let input = format!(
+            "{}\n{}\n{}",
+            settings.system_prompt,
+            settings.max_length,
+            self.format_thread_for_summary(thread)
+        );
+        Ok((cache_key(AiCacheKind::Summary, &provider, &input), input))
+    }
+
+    /// Looks up a cached result, counting the hit or miss.
+    ///
+    /// Hits are also recorded as stats events for `account_id`. Returns
+    /// `None` without counting when no cache is set. Cache errors are logged
+    /// and treated as misses.
+    async fn cached<T: DeserializeOwned>(&self, key: &str, account_id: &AccountId) -> Option<T> {
+        let cache = self.cache.read().await.clone()?;
+        let value = match cache.get_cached(key).await {
+            Ok(value) => value.and_then(|json| serde_json::from_str(&json).ok()),
+            Err(e) => {
+                tracing::warn!(error = %e, "AI cache lookup failed");
+                None
+            }
+        };
+
+        if value.is_none() {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        if let Some(stats) = self.stats.read().await.clone() {
+            if let Err(e) = stats.record_event(account_id, StatsEvent::AiCacheHit).await {
+                tracing::warn!(error = %e, "Failed to record AI cache hit");
+            }
+        }
+        value
+    }
+
+    /// Stores a result in the cache, if one is set, under the provider that
+    /// served it.
+    async fn store<T: Serialize>(
+        &self,
+        kind: AiCacheKind,
+        input: &str,
+        thread_id: Option<&ThreadId>,
+        provider: &FallbackProvider,
+        value: &T,
+    ) {
+        let Some(cache) = self.cache.read().await.clone() else {
+            return;
+        };
+        let entry = cache_entry(kind, input, thread_id, provider, value);
+        if let Err(e) = cache.put_cached(entry).await {
+            tracing::warn!(error = %e, kind = kind.as_str(), "Failed to cache AI result");
+        }
+    }
+
+    /// Builds the summarization request and picks its provider.
+    ///
+    /// Threads that do not fit in the provider's context window are split
+    /// into chunks by message and the chunks summarized; the returned request
+    /// merges the chunk summaries.
+    async fn summary_plan(&self, thread: &Thread) -> Result<SummaryPlan> {
+        let settings = self.summary_settings().await?;
+
+        let provider = self
+            .route(AiTask::Summary, settings.provider.as_deref())
+            .await?;
+        let count_tokens = |text: &str| provider.count_tokens(text);
+        // Leave room for the output format, given as a schema or as text
+        let format_tokens = count_tokens(&Summary::output_schema().schema.to_string())
+            .max(count_tokens(STAGED_SUMMARY_FORMAT));
+        let budget = provider.max_context_length().saturating_sub(
+            count_tokens(&settings.system_prompt)
+                + format_tokens
+                + settings.max_length
+                + SUMMARY_PROMPT_OVERHEAD_TOKENS,
+        );
+
+        // Build the thread content for summarization
+        let thread_content = self.format_thread_for_summary(thread);
+        if count_tokens(&thread_content) <= budget {
+            let request = CompletionRequest::new(vec![Message::user(thread_content)])
+                .with_system_prompt(settings.system_prompt.clone())
+                .with_temperature(0.3)
+                .with_max_tokens(settings.max_length);
+            return Ok(SummaryPlan {
+                provider,
+                request,
+                parts: Vec::new(),
+            });
+        }
+
+        if budget < MIN_SUMMARY_CHUNK_TOKENS {
+            anyhow::bail!(
+                "The context window of {} is too small to summarize this thread",
+                provider.model()
+            );
+        }
+
+        let chunk_prompt = format!(
+            "{}\n\nThis is one part of a long email thread.",
+            settings.system_prompt
+        );
+        let requests = chunk_thread(thread, budget, count_tokens)
+            .into_iter()
+            .map(|chunk| {
+                CompletionRequest::new(vec![Message::user(chunk)])
+                    .with_system_prompt(chunk_prompt.clone())
+                    .with_temperature(0.3)
+                    .with_max_tokens(settings.max_length)
+            })
+            .collect();
+        let mut parts = complete_all(provider.as_ref(), requests).await?;
+
+        // Merge in rounds until the remaining summaries fit in one prompt
+        loop {
+            let groups = group_summaries(&parts, budget, count_tokens);
+            if groups.len() <= 1 {
+                break;
+            }
+            if groups.len() == parts.len() {
+                anyhow::bail!("Chunk summaries are too long to merge");
+            }
+            let requests = groups
+                .iter()
+                .map(|group| merge_summaries_request(group, &settings))
+                .collect();
+            parts = complete_all(provider.as_ref(), requests).await?;
+        }
+
+        Ok(SummaryPlan {
+            request: merge_summaries_request(&parts, &settings),
+            provider,
+            parts,
+        })
+    }
+
+    /// Builds the draft reply request and picks its provider.
+    async fn draft_request(
+        &self,
+        thread: &Thread,
+        instructions: Option<&str>,
+    ) -> Result<(Arc<dyn LlmProvider>, CompletionRequest)> {
         let settings = self.settings.read().await;
         if !settings.enabled || !settings.compose_settings.enabled {
             anyhow::bail!("AI drafting is disabled");
         }
 
-        let provider = self
-            .get_provider(settings.compose_settings.provider.as_deref())
+        let provider: Arc<dyn LlmProvider> = self
+            .route(
+                AiTask::Compose,
+                settings.compose_settings.provider.as_deref(),
+            )
             .await?;
 
         let thread_content = self.format_thread_for_reply(thread);
@@ -547,42 +1203,29 @@ impl AiService {
             user_content.push_str(&format!("\n\nAdditional instructions: {}", inst));
         }
 
-        let request = CompletionRequest {
-            system_prompt: Some(settings.compose_settings.system_prompt.clone()),
-            messages: vec![ChatMessage {
-                role: ChatRole::User,
-                content: user_content,
-            }],
-            temperature: 0.7,
-            max_tokens: Some(1000),
-        };
-
-        let response = provider.complete(&request).await?;
+        let request = CompletionRequest::new(vec![Message::user(user_content)])
+            .with_system_prompt(settings.compose_settings.system_prompt.clone())
+            .with_temperature(0.7)
+            .with_max_tokens(1000);
 
-        Ok(DraftSuggestion {
-            content: response.text,
-            confidence: 0.8, // Could be refined based on model confidence
-        })
+        Ok((provider, request))
     }
 
     /// Performs semantic search across emails.
     ///
     /// Uses embeddings to find semantically similar emails regardless
-    /// of exact keyword matches.
+    /// of exact keyword matches. Results are filtered with the same
+    /// predicates as full-text search and hydrated with thread, subject,
+    /// snippet and sender from the search storage.
     ///
     /// # Arguments
     ///
-    /// * `query` - Natural language search query
-    /// * `account_ids` - Accounts to search within
+    /// * `query` - Natural language search text and filters
     ///
     /// # Returns
     ///
     /// A list of search results sorted by relevance.
-    pub async fn semantic_search(
-        &self,
-        query: &str,
-        _account_ids: &[AccountId],
-    ) -> Result<Vec<SearchResult>> {
+    pub async fn semantic_search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
         let settings = self.settings.read().await;
         if !settings.enabled || !settings.search_settings.enabled {
             anyhow::bail!("Semantic search is disabled");
@@ -592,30 +1235,72 @@ impl AiService {
         let engine = engine_guard
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("No embedding engine configured"))?;
+        let storage = self.search_storage.read().await.clone();
+        if storage.is_none() && query.has_filters() {
+            anyhow::bail!("No search storage configured for filtered semantic search");
+        }
 
         // Generate query embedding
-        let query_embedding = engine.embed(query).await?;
+        let query_embedding = engine.embed(&query.text).await?;
 
-        // Search for similar emails
-        let results = engine
-            .search(&query_embedding, settings.search_settings.max_results)
-            .await?;
+        // Search for similar emails, fetching extra candidates when some
+        // will be filtered out
+        let max_results = settings.search_settings.max_results;
+        let candidates = if query.has_filters() {
+            max_results * FILTERED_SEARCH_OVERSAMPLING
+        } else {
+            max_results
+        };
+        let matches: Vec<PassageMatch> = engine
+            .search_passages(&query_embedding, candidates)
+            .await?
+            .into_iter()
+            .filter(|m| m.score >= settings.search_settings.min_relevance)
+            .collect();
 
-        // Filter by minimum relevance and convert to SearchResult
-        let search_results: Vec<SearchResult> = results
+        let Some(storage) = storage else {
+            return Ok(matches
+                .into_iter()
+                .take(max_results)
+                .map(|m| SearchResult {
+                    email_id: m.email_id,
+                    thread_id: crate::domain::ThreadId::from(""),
+                    subject: None,
+                    snippet: String::new(),
+                    from: String::new(),
+                    relevance: m.score,
+                    highlights: m.text.into_iter().collect(),
+                })
+                .collect());
+        };
+
+        // Hydrate from storage, dropping emails that were deleted since they
+        // were indexed or that do not pass the filters. The best-matching
+        // passage is used as the highlight.
+        let ids: Vec<EmailId> = matches.iter().map(|m| m.email_id.clone()).collect();
+        let mut metadata: HashMap<EmailId, EmailMetadata> = storage
+            .get_email_metadata(&ids)
+            .await?
             .into_iter()
-            .filter(|(_, score)| *score >= settings.search_settings.min_relevance)
-            .map(|(email_id, relevance)| SearchResult {
-                email_id,
-                thread_id: crate::domain::ThreadId::from(""), // Would be filled from storage
-                subject: None,
-                snippet: String::new(),
-                relevance,
-                highlights: vec![],
-            })
+            .map(|m| (m.email_id.clone(), m))
             .collect();
 
-        Ok(search_results)
+        Ok(matches
+            .into_iter()
+            .filter_map(|m| {
+                let meta = metadata.remove(&m.email_id)?;
+                query.matches(&meta).then(|| SearchResult {
+                    email_id: m.email_id,
+                    thread_id: meta.thread_id,
+                    subject: meta.subject,
+                    snippet: meta.snippet,
+                    from: meta.from,
+                    relevance: m.score,
+                    highlights: m.text.into_iter().collect(),
+                })
+            })
+            .take(max_results)
+            .collect())
     }
 
     /// Categorizes an email into one or more categories.
@@ -636,7 +1321,7 @@ impl AiService {
             anyhow::bail!("AI features are disabled");
         }
 
-        let provider = self.get_provider(None).await?;
+        let provider = self.route(AiTask::Categorize, None).await?;
 
         let email_content = format!(
             "From: {}\nSubject: {}\n\n{}",
@@ -651,146 +1336,107 @@ impl AiService {
             .collect::<Vec<_>>()
             .join(", ");
 
-        let request = CompletionRequest {
-            system_prompt: Some(format!(
-                "Categorize the following email into one or more categories: {}. \
-                 Return only the category names, comma-separated, most relevant first.",
-                categories_list
-            )),
-            messages: vec![ChatMessage {
-                role: ChatRole::User,
-                content: email_content,
-            }],
-            temperature: 0.2,
-            max_tokens: Some(100),
-        };
+        let system_prompt = format!(
+            "Categorize the following email into one or more categories: {}. \
+             List the most relevant category first.",
+            categories_list
+        );
+        let input = format!("{}\n{}", system_prompt, email_content);
+        let key = cache_key(AiCacheKind::Categories, &provider, &input);
+        if let Some(categories) = self.cached(&key, &email.account_id).await {
+            return Ok(categories);
+        }
 
-        let response = provider.complete(&request).await?;
+        let request = CompletionRequest::new(vec![Message::user(email_content)])
+            .with_system_prompt(system_prompt)
+            .with_temperature(0.2)
+            .with_max_tokens(100);
 
-        // Parse categories from response
-        let categories: Vec<Category> = response
-            .text
-            .split(',')
-            .filter_map(|s| {
-                let s = s.trim().to_lowercase();
-                match s.as_str() {
-                    "primary" => Some(Category::Primary),
-                    "social" => Some(Category::Social),
-                    "promotions" => Some(Category::Promotions),
-                    "updates" => Some(Category::Updates),
-                    "forums" => Some(Category::Forums),
-                    "finance" => Some(Category::Finance),
-                    "travel" => Some(Category::Travel),
-                    "shopping" => Some(Category::Shopping),
-                    "newsletters" => Some(Category::Newsletters),
-                    "work" => Some(Category::Work),
-                    "personal" => Some(Category::Personal),
-                    _ => None,
-                }
-            })
-            .collect();
+        let output: CategoriesOutput = complete_structured(provider.as_ref(), &request).await?;
+        let mut categories: Vec<Category> = Vec::new();
+        for category in output.categories {
+            if !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
 
+        self.store(
+            AiCacheKind::Categories,
+            &input,
+            None,
+            &provider,
+            &categories,
+        )
+        .await;
         Ok(categories)
     }
 
     /// Analyzes a sender to determine their likely type.
     ///
-    /// Uses AI and historical data to categorize senders for the screener.
+    /// The model sees the headers and a body excerpt of the sender's first
+    /// email along with the extracted [`SenderSignals`]. The confidence it
+    /// reports is calibrated against those signals, so the screener can act
+    /// on analyses above a threshold.
     ///
     /// # Arguments
     ///
-    /// * `sender` - The sender email address to analyze
+    /// * `email` - The first email received from the sender
+    /// * `signals` - Signals extracted from that email and the contact history
     ///
     /// # Returns
     ///
-    /// A sender analysis with type, reasoning, and suggested action.
-    pub async fn analyze_sender(&self, sender: &str) -> Result<SenderAnalysis> {
+    /// A sender analysis with type, reasoning, suggested action and confidence.
+    pub async fn analyze_sender(
+        &self,
+        email: &Email,
+        signals: &SenderSignals,
+    ) -> Result<SenderAnalysis> {
         let settings = self.settings.read().await;
         if !settings.enabled {
             anyhow::bail!("AI features are disabled");
         }
 
-        let provider = self.get_provider(None).await?;
-
-        let request = CompletionRequest {
-            system_prompt: Some(
-                "Analyze the sender email address and determine their likely type. \
-                 Categories: known_contact, newsletter, marketing, recruiter, support, unknown. \
-                 Suggest whether to approve, reject, or review. \
-                 Respond in format: TYPE|REASONING|ACTION"
-                    .to_string(),
-            ),
-            messages: vec![ChatMessage {
-                role: ChatRole::User,
-                content: format!("Analyze sender: {}", sender),
-            }],
-            temperature: 0.2,
-            max_tokens: Some(200),
-        };
-
-        let response = provider.complete(&request).await?;
+        let provider = self.route(AiTask::SenderAnalysis, None).await?;
 
-        // Parse the response
-        let parts: Vec<&str> = response.text.split('|').collect();
-
-        let likely_type = if !parts.is_empty() {
-            match parts[0].trim().to_lowercase().as_str() {
-                "known_contact" => crate::domain::SenderType::KnownContact,
-                "newsletter" => crate::domain::SenderType::Newsletter,
-                "marketing" => crate::domain::SenderType::Marketing,
-                "recruiter" => crate::domain::SenderType::Recruiter,
-                "support" => crate::domain::SenderType::Support,
-                _ => crate::domain::SenderType::Unknown,
-            }
-        } else {
-            crate::domain::SenderType::Unknown
-        };
-
-        let reasoning = if parts.len() > 1 {
-            parts[1].trim().to_string()
-        } else {
-            "Unable to analyze sender".to_string()
-        };
-
-        let suggested_action = if parts.len() > 2 {
-            match parts[2].trim().to_lowercase().as_str() {
-                "approve" => crate::domain::ScreenerAction::Approve,
-                "reject" => crate::domain::ScreenerAction::Reject,
-                _ => crate::domain::ScreenerAction::Review,
-            }
-        } else {
-            crate::domain::ScreenerAction::Review
-        };
+        let content = format!(
+            "{}\nSignals:\n{}",
+            format_email_for_analysis(email),
+            signals.describe()
+        );
+        let key = cache_key(AiCacheKind::SenderAnalysis, &provider, &content);
+        if let Some(analysis) = self.cached(&key, &email.account_id).await {
+            return Ok(analysis);
+        }
 
-        Ok(SenderAnalysis {
-            likely_type,
-            reasoning,
-            suggested_action,
-        })
+        let request = CompletionRequest::new(vec![Message::user(content.clone())])
+            .with_system_prompt(
+                "Analyze the sender of this email and determine their likely type. \
+                 Weigh the signals listed after the email. Explain your reasoning \
+                 briefly, suggest whether to approve, reject, or review the sender, \
+                 and give your confidence that the suggestion is right.",
+            )
+            .with_temperature(0.2)
+            .with_max_tokens(250);
+
+        let mut analysis: SenderAnalysis = complete_structured(provider.as_ref(), &request).await?;
+        analysis.confidence = signals.calibrate(&analysis);
+        self.store(
+            AiCacheKind::SenderAnalysis,
+            &content,
+            None,
+            &provider,
+            &analysis,
+        )
+        .await;
+        Ok(analysis)
     }
 
     /// Formats a thread for summarization.
     fn format_thread_for_summary(&self, thread: &Thread) -> String {
-        let mut content = format!(
-            "Subject: {}\nParticipants: {}\n\n",
-            thread.subject.as_deref().unwrap_or("(no subject)"),
-            thread
-                .participants
-                .iter()
-                .map(|p| p.display())
-                .collect::<Vec<_>>()
-                .join(", ")
-        );
-
+        let mut content = summary_header(thread);
         for email in &thread.messages {
-            content.push_str(&format!(
-                "---\nFrom: {}\nDate: {}\n\n{}\n",
-                email.from.display(),
-                email.date.format("%Y-%m-%d %H:%M"),
-                email.body_text.as_deref().unwrap_or(&email.snippet)
-            ));
+            content.push_str(&format_message_for_summary(email));
         }
-
         content
     }
 
@@ -815,9 +1461,210 @@ impl AiService {
     }
 }
 
+/// Returns the provider name and model a route's results are cached under.
+///
+/// That is the provider that served the route's last request, or the first
+/// provider in the chain before a request has been served, so results from
+/// a fallback are never cached as the primary's.
+fn cache_identity(provider: &FallbackProvider) -> (String, String) {
+    match provider.last_served() {
+        Some(served) => (served.provider, served.model),
+        None => (
+            provider.chain()[0].to_string(),
+            provider.model().to_string(),
+        ),
+    }
+}
+
+/// Hashes the inputs that determine an AI result into a cache key.
+fn cache_key(kind: AiCacheKind, provider: &FallbackProvider, input: &str) -> String {
+    let version = AI_PROMPT_VERSION.to_string();
+    let (name, model) = cache_identity(provider);
+    let mut context = digest::Context::new(&digest::SHA256);
+    for part in [kind.as_str(), &version, &name, &model, input] {
+        context.update(part.as_bytes());
+        context.update(&[0]);
+    }
+
+    context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Builds a cache entry for a result served by `provider`.
+fn cache_entry<T: Serialize>(
+    kind: AiCacheKind,
+    input: &str,
+    thread_id: Option<&ThreadId>,
+    provider: &FallbackProvider,
+    value: &T,
+) -> AiCacheEntry {
+    let (name, model) = cache_identity(provider);
+    AiCacheEntry {
+        key: cache_key(kind, provider, input),
+        kind,
+        thread_id: thread_id.cloned(),
+        provider: name,
+        model,
+        // Results are plain data, so serializing cannot fail
+        value: serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+/// Formats the subject and participants that open a summary prompt.
+fn summary_header(thread: &Thread) -> String {
+    format!(
+        "Subject: {}\nParticipants: {}\n\n",
+        thread.subject.as_deref().unwrap_or("(no subject)"),
+        thread
+            .participants
+            .iter()
+            .map(|p| p.display())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Formats one message of a thread for summarization.
+fn format_message_for_summary(email: &Email) -> String {
+    format!(
+        "---\nFrom: {}\nDate: {}\n\n{}\n",
+        email.from.display(),
+        email.date.format("%Y-%m-%d %H:%M"),
+        email.body_text.as_deref().unwrap_or(&email.snippet)
+    )
+}
+
+/// Splits a thread into prompts of at most `budget` tokens.
+///
+/// Messages are kept whole where possible; a message too long for one
+/// prompt is split at line breaks. Each chunk repeats the thread header.
+fn chunk_thread(
+    thread: &Thread,
+    budget: usize,
+    count_tokens: impl Fn(&str) -> usize + Copy,
+) -> Vec<String> {
+    let header = summary_header(thread);
+    // Leave room for the header and the part label
+    let available = budget.saturating_sub(count_tokens(&header) + 16).max(1);
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut used = 0;
+    for email in &thread.messages {
+        for piece in split_to_fit(&format_message_for_summary(email), available, count_tokens) {
+            let cost = count_tokens(&piece);
+            if !current.is_empty() && used + cost > available {
+                chunks.push(std::mem::take(&mut current));
+                used = 0;
+            }
+            current.push_str(&piece);
+            used += cost;
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    let total = chunks.len();
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| format!("{}Part {} of {}:\n{}", header, i + 1, total, chunk))
+        .collect()
+}
+
+/// Splits text into pieces of at most `max_tokens`, preferring line breaks.
+fn split_to_fit(
+    text: &str,
+    max_tokens: usize,
+    count_tokens: impl Fn(&str) -> usize + Copy,
+) -> Vec<String> {
+    if count_tokens(text) <= max_tokens || text.chars().nth(1).is_none() {
+        return vec![text.to_string()];
+    }
+
+    let mut middle = text.len() / 2;
+    while !text.is_char_boundary(middle) {
+        middle += 1;
+    }
+    let cut = match text[..middle].rfind('\n') {
+        Some(newline) if newline > 0 => newline + 1,
+        _ => middle,
+    };
+
+    let (head, tail) = text.split_at(cut);
+    let mut pieces = split_to_fit(head, max_tokens, count_tokens);
+    pieces.extend(split_to_fit(tail, max_tokens, count_tokens));
+    pieces
+}
+
+/// Groups consecutive summaries so each group fits in a `budget`-token prompt.
+fn group_summaries(
+    parts: &[Summary],
+    budget: usize,
+    count_tokens: impl Fn(&str) -> usize,
+) -> Vec<Vec<Summary>> {
+    let mut groups: Vec<Vec<Summary>> = Vec::new();
+    let mut used = 0;
+    for part in parts {
+        // Include the part label in the cost
+        let cost = count_tokens(&part.to_text()) + 8;
+        match groups.last_mut() {
+            Some(group) if used + cost <= budget => group.push(part.clone()),
+            _ => {
+                groups.push(vec![part.clone()]);
+                used = 0;
+            }
+        }
+        used += cost;
+    }
+    groups
+}
+
+/// Builds the request that merges consecutive chunk summaries.
+fn merge_summaries_request(parts: &[Summary], settings: &SummarySettings) -> CompletionRequest {
+    let parts: Vec<String> = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| format!("Part {}:\n{}", i + 1, part.to_text()))
+        .collect();
+    let content = format!(
+        "Summaries of consecutive parts of one email thread, oldest first:\n\n{}\n\n\
+         Combine them into one summary of the whole thread. Keep every action item \
+         and the most important key points.",
+        parts.join("\n\n")
+    );
+
+    CompletionRequest::new(vec![Message::user(content)])
+        .with_system_prompt(settings.system_prompt.clone())
+        .with_temperature(0.3)
+        .with_max_tokens(settings.max_length)
+}
+
+/// Requests summaries a few at a time.
+async fn complete_all(
+    provider: &dyn LlmProvider,
+    requests: Vec<CompletionRequest>,
+) -> Result<Vec<Summary>> {
+    let summaries = futures::stream::iter(&requests)
+        .map(|request| complete_structured::<Summary>(provider, request))
+        .buffered(SUMMARY_CHUNK_CONCURRENCY)
+        .try_collect()
+        .await?;
+    Ok(summaries)
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::providers::ai::{
+        estimate_tokens, CompletionResponse, FinishReason, LlmResult, StreamChunk, TokenUsage,
+    };
+    use futures::StreamExt;
 
     #[test]
     fn summary_parse_basic() {
@@ -891,6 +1738,7 @@ mod tests {
             thread_id: crate::domain::ThreadId::from("thread-1"),
             subject: Some("Test".to_string()),
             snippet: "Preview...".to_string(),
+            from: "alice@example.com".to_string(),
             relevance: 0.95,
             highlights: vec!["matching text".to_string()],
         };
@@ -902,6 +1750,870 @@ mod tests {
         assert_eq!(deserialized.highlights.len(), 1);
     }
 
+    /// Embedding engine whose "long" email matches on its second passage.
+    struct PassageEngine;
+
+    #[async_trait::async_trait]
+    impl EmbeddingEngine for PassageEngine {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(vec![1.0, 0.0])
+        }
+
+        async fn search(
+            &self,
+            _query_embedding: &[f32],
+            _limit: usize,
+        ) -> Result<Vec<(EmailId, f32)>> {
+            Ok(vec![
+                (EmailId::from("long"), 0.9),
+                (EmailId::from("weak"), 0.2),
+            ])
+        }
+
+        async fn search_passages(
+            &self,
+            query_embedding: &[f32],
+            limit: usize,
+        ) -> Result<Vec<PassageMatch>> {
+            let mut matches = Vec::new();
+            for (email_id, score) in self.search(query_embedding, limit).await? {
+                matches.push(PassageMatch {
+                    email_id,
+                    score,
+                    passage_index: 1,
+                    text: Some("the budget is due Friday".to_string()),
+                });
+            }
+            Ok(matches)
+        }
+
+        async fn index_email(&self, _email: &Email) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn semantic_search_highlights_best_passage() {
+        let service = AiService::new(AiSettings::default());
+        service.set_embedding_engine(Arc::new(PassageEngine)).await;
+
+        let results = service
+            .semantic_search(&SearchQuery::new("budget"))
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].email_id, EmailId::from("long"));
+        assert_eq!(results[0].highlights, vec!["the budget is due Friday"]);
+    }
+
+    /// Embedding engine that ranks emails in a fixed order.
+    struct RankedEngine(Vec<&'static str>);
+
+    #[async_trait::async_trait]
+    impl EmbeddingEngine for RankedEngine {
+        async fn embed(&self, _text: &str) -> Result<Vec<f32>> {
+            Ok(vec![1.0, 0.0])
+        }
+
+        async fn search(
+            &self,
+            _query_embedding: &[f32],
+            limit: usize,
+        ) -> Result<Vec<(EmailId, f32)>> {
+            Ok(self
+                .0
+                .iter()
+                .enumerate()
+                .map(|(i, id)| (EmailId::from(*id), 0.9 - i as f32 * 0.01))
+                .take(limit)
+                .collect())
+        }
+
+        async fn index_email(&self, _email: &Email) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct MockSearchStorage(Vec<EmailMetadata>);
+
+    #[async_trait::async_trait]
+    impl SemanticSearchStorage for MockSearchStorage {
+        async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>> {
+            Ok(self
+                .0
+                .iter()
+                .filter(|m| ids.contains(&m.email_id))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn metadata(id: &str, account: &str, labels: &[&str], days_ago: i64) -> EmailMetadata {
+        EmailMetadata {
+            email_id: EmailId::from(id),
+            account_id: AccountId::from(account),
+            thread_id: crate::domain::ThreadId::from(format!("thread-{}", id)),
+            subject: Some(format!("Subject {}", id)),
+            snippet: format!("Snippet {}", id),
+            from: "alice@example.com".to_string(),
+            date: chrono::Utc::now() - chrono::Duration::days(days_ago),
+            to: vec!["me@example.com".to_string()],
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            has_attachments: false,
+            labels: labels
+                .iter()
+                .map(|l| crate::domain::LabelId::from(*l))
+                .collect(),
+        }
+    }
+
+    async fn search_service_with_storage() -> AiService {
+        let service = AiService::new(AiSettings::default());
+        service
+            .set_embedding_engine(Arc::new(RankedEngine(vec!["a", "b", "c", "deleted"])))
+            .await;
+        service
+            .set_search_storage(Arc::new(MockSearchStorage(vec![
+                metadata("a", "work", &["INBOX"], 1),
+                metadata("b", "personal", &["INBOX", "travel"], 2),
+                metadata("c", "work", &["travel"], 30),
+            ])))
+            .await;
+        service
+    }
+
+    #[tokio::test]
+    async fn semantic_search_hydrates_results() {
+        let service = search_service_with_storage().await;
+
+        let results = service
+            .semantic_search(&SearchQuery::new("trip"))
+            .await
+            .unwrap();
+
+        let ids: Vec<&str> = results.iter().map(|r| r.email_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        assert_eq!(
+            results[0].thread_id,
+            crate::domain::ThreadId::from("thread-a")
+        );
+        assert_eq!(results[0].subject.as_deref(), Some("Subject a"));
+        assert_eq!(results[0].snippet, "Snippet a");
+        assert_eq!(results[0].from, "alice@example.com");
+    }
+
+    #[tokio::test]
+    async fn semantic_search_applies_filters() {
+        let service = search_service_with_storage().await;
+
+        let query = SearchQuery::new("trip").with_accounts(vec![AccountId::from("work")]);
+        let results = service.semantic_search(&query).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.email_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+
+        let query = SearchQuery::new("trip")
+            .with_folder(crate::services::SearchFolder::Label("travel".to_string()));
+        let results = service.semantic_search(&query).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.email_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+
+        let now = chrono::Utc::now();
+        let query = SearchQuery::new("trip")
+            .with_folder(crate::services::SearchFolder::Inbox)
+            .with_date_range(now - chrono::Duration::days(7), now);
+        let results = service.semantic_search(&query).await.unwrap();
+        let ids: Vec<&str> = results.iter().map(|r| r.email_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn filtered_semantic_search_requires_storage() {
+        let service = AiService::new(AiSettings::default());
+        service
+            .set_embedding_engine(Arc::new(RankedEngine(vec!["a"])))
+            .await;
+
+        let query = SearchQuery::new("trip").with_accounts(vec![AccountId::from("work")]);
+        assert!(service.semantic_search(&query).await.is_err());
+    }
+
+    /// Provider that replies with a fixed text, streamed word by word.
+    pub(crate) struct MockProvider {
+        reply: String,
+        requests: std::sync::Mutex<Vec<CompletionRequest>>,
+    }
+
+    impl MockProvider {
+        pub(crate) fn new(reply: &str) -> Self {
+            Self {
+                reply: reply.to_string(),
+                requests: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for MockProvider {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            self.requests.lock().unwrap().push(request.clone());
+            Ok(CompletionResponse {
+                text: self.reply.clone(),
+                tokens_used: TokenUsage::default(),
+                finish_reason: FinishReason::Stop,
+            })
+        }
+
+        async fn stream_complete(
+            &self,
+            request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            self.requests.lock().unwrap().push(request.clone());
+            let mut chunks: Vec<LlmResult<StreamChunk>> = self
+                .reply
+                .split_inclusive(' ')
+                .map(|word| {
+                    Ok(StreamChunk {
+                        text: word.to_string(),
+                        finish_reason: None,
+                    })
+                })
+                .collect();
+            chunks.push(Ok(StreamChunk {
+                text: String::new(),
+                finish_reason: Some(FinishReason::Stop),
+            }));
+            Ok(Box::pin(futures::stream::iter(chunks)))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn max_context_length(&self) -> usize {
+            8192
+        }
+
+        fn model(&self) -> &str {
+            "mock-model"
+        }
+    }
+
+    fn make_sender_email(from: &str) -> (Email, SenderSignals) {
+        let mut email = make_thread().messages.remove(0);
+        email.from = crate::domain::Address::new(from);
+        let signals = SenderSignals::from_email(&email, None, 0);
+        (email, signals)
+    }
+
+    pub(crate) fn make_thread() -> Thread {
+        use crate::domain::{Address, MessageId, ThreadId};
+
+        let email = Email {
+            id: EmailId::from("email-1"),
+            account_id: AccountId::from("account-1"),
+            thread_id: ThreadId::from("thread-1"),
+            message_id: MessageId::from("<email-1@example.com>"),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new("alice@example.com"),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("Launch plan".to_string()),
+            body_text: Some("Can we ship on Friday?".to_string()),
+            body_html: None,
+            snippet: "Can we ship on Friday?".to_string(),
+            date: chrono::Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![],
+            attachments: vec![],
+            headers: vec![],
+        };
+
+        Thread {
+            id: ThreadId::from("thread-1"),
+            account_id: AccountId::from("account-1"),
+            subject: Some("Launch plan".to_string()),
+            snippet: email.snippet.clone(),
+            participants: vec![email.from.clone()],
+            last_message_date: email.date,
+            unread_count: 1,
+            is_starred: false,
+            labels: vec![],
+            messages: vec![email],
+        }
+    }
+
+    async fn collect_text(mut stream: CompletionStream) -> (String, Option<FinishReason>) {
+        let mut text = String::new();
+        let mut finish_reason = None;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.unwrap();
+            text.push_str(&chunk.text);
+            finish_reason = chunk.finish_reason.or(finish_reason);
+        }
+        (text, finish_reason)
+    }
+
+    #[tokio::test]
+    async fn summarize_uses_registered_provider() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            r#"{"text": "Shipping Friday.", "key_points": ["Launch plan"], "action_items": []}"#,
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let summary = service.summarize_thread(&make_thread()).await.unwrap();
+        assert_eq!(summary.text, "Shipping Friday.");
+        assert_eq!(summary.key_points, vec!["Launch plan"]);
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests[0].temperature, 0.3);
+        assert!(requests[0].messages[0]
+            .content
+            .contains("Can we ship on Friday?"));
+    }
+
+    #[tokio::test]
+    async fn summary_streams_incremental_chunks() {
+        let service = AiService::new(AiSettings::default());
+        service
+            .register_provider(
+                "anthropic",
+                Arc::new(MockProvider::new("Alice asks about Friday.")),
+            )
+            .await;
+
+        let mut stream = service
+            .summarize_thread_stream(&make_thread())
+            .await
+            .unwrap();
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.text, "Alice ");
+        assert!(first.finish_reason.is_none());
+
+        let (rest, finish_reason) = collect_text(stream).await;
+        assert_eq!(rest, "asks about Friday.");
+        assert_eq!(finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[tokio::test]
+    async fn draft_reply_stream_includes_instructions() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new("Friday works for me."));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let stream = service
+            .draft_reply_stream(&make_thread(), Some("agree"))
+            .await
+            .unwrap();
+        let (text, _) = collect_text(stream).await;
+        assert_eq!(text, "Friday works for me.");
+
+        let requests = provider.requests.lock().unwrap();
+        assert!(requests[0].messages[0]
+            .content
+            .contains("Additional instructions: agree"));
+    }
+
+    #[tokio::test]
+    async fn streaming_respects_disabled_settings() {
+        let mut settings = AiSettings::default();
+        settings.compose_settings.enabled = false;
+        let service = AiService::new(settings);
+        service
+            .register_provider("anthropic", Arc::new(MockProvider::new("unused")))
+            .await;
+
+        assert!(service
+            .draft_reply_stream(&make_thread(), None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn http_providers_can_be_registered() {
+        let service = AiService::new(AiSettings::default());
+        service
+            .register_provider(
+                "ollama",
+                Arc::new(crate::providers::ai::OllamaProvider::llama3()),
+            )
+            .await;
+
+        let provider = service
+            .route(AiTask::Assistant, Some("ollama"))
+            .await
+            .unwrap();
+        assert_eq!(provider.name(), "ollama");
+    }
+
+    /// Provider that is down.
+    #[derive(Default)]
+    struct DownProvider {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for DownProvider {
+        fn name(&self) -> &str {
+            "down"
+        }
+
+        async fn complete(&self, _request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(crate::providers::ai::LlmError::ApiError {
+                status: 529,
+                message: "overloaded".to_string(),
+            })
+        }
+
+        async fn stream_complete(
+            &self,
+            _request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            Err(crate::providers::ai::LlmError::Unavailable(
+                "down".to_string(),
+            ))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn max_context_length(&self) -> usize {
+            200_000
+        }
+
+        fn model(&self) -> &str {
+            "down-model"
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_fall_back_and_report_serving_provider() {
+        let mut settings = AiSettings::default();
+        settings.routing.failure_threshold = 1;
+        settings.routing.routes.insert(
+            AiTask::Summary,
+            RoutePolicy {
+                providers: vec!["anthropic".to_string(), "ollama".to_string()],
+                timeout_secs: None,
+            },
+        );
+        let service = AiService::new(settings);
+        let cloud = Arc::new(DownProvider::default());
+        service.register_provider("anthropic", cloud.clone()).await;
+        service
+            .register_provider(
+                "ollama",
+                Arc::new(MockProvider::new(
+                    r#"{"text": "Local summary.", "key_points": [], "action_items": []}"#,
+                )),
+            )
+            .await;
+
+        let summary = service.summarize_thread(&make_thread()).await.unwrap();
+        assert_eq!(summary.text, "Local summary.");
+
+        let served = service.recent_requests();
+        assert_eq!(served.len(), 1);
+        assert_eq!(served[0].task, AiTask::Summary);
+        assert_eq!(served[0].served_by.provider, "ollama");
+        assert_eq!(served[0].served_by.attempts, 2);
+
+        // The open circuit skips the cloud provider
+        service.summarize_thread(&make_thread()).await.unwrap();
+        assert_eq!(cloud.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(service.recent_requests()[1].served_by.attempts, 1);
+
+        // Tasks without a route use the default provider alone
+        assert!(service
+            .categorize_email(&make_thread().messages[0])
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn routing_settings_default_when_missing() {
+        let json = serde_json::to_value(AiSettings::default()).unwrap();
+        let mut object = json.as_object().unwrap().clone();
+        object.remove("routing");
+
+        let settings: AiSettings = serde_json::from_value(object.into()).unwrap();
+        assert!(settings.routing.routes.is_empty());
+        assert_eq!(settings.routing.request_timeout_secs, 60);
+    }
+
+    /// In-memory [`AiCacheStorage`].
+    #[derive(Default)]
+    pub(crate) struct MemoryCache(std::sync::Mutex<HashMap<String, AiCacheEntry>>);
+
+    #[async_trait::async_trait]
+    impl AiCacheStorage for MemoryCache {
+        async fn get_cached(&self, key: &str) -> Result<Option<String>> {
+            Ok(self.0.lock().unwrap().get(key).map(|e| e.value.clone()))
+        }
+
+        async fn put_cached(&self, entry: AiCacheEntry) -> Result<()> {
+            self.0.lock().unwrap().insert(entry.key.clone(), entry);
+            Ok(())
+        }
+
+        async fn invalidate_thread(&self, thread_id: &ThreadId) -> Result<usize> {
+            let mut entries = self.0.lock().unwrap();
+            let before = entries.len();
+            entries.retain(|_, e| e.thread_id.as_ref() != Some(thread_id));
+            Ok(before - entries.len())
+        }
+
+        async fn clear_cached(&self) -> Result<usize> {
+            let mut entries = self.0.lock().unwrap();
+            let count = entries.len();
+            entries.clear();
+            Ok(count)
+        }
+    }
+
+    #[tokio::test]
+    async fn summaries_are_cached_by_content() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            r#"{"text": "Shipping Friday.", "key_points": [], "action_items": []}"#,
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+        let cache = Arc::new(MemoryCache::default());
+        service.set_cache(cache.clone()).await;
+
+        let mut thread = make_thread();
+        service.summarize_thread(&thread).await.unwrap();
+        let cached = service.summarize_thread(&thread).await.unwrap();
+        assert_eq!(cached.text, "Shipping Friday.");
+        assert_eq!(provider.requests.lock().unwrap().len(), 1);
+        assert_eq!(service.cache_stats(), AiCacheStats { hits: 1, misses: 1 });
+
+        let entry = cache.0.lock().unwrap().values().next().unwrap().clone();
+        assert_eq!(entry.kind, AiCacheKind::Summary);
+        assert_eq!(entry.thread_id, Some(thread.id.clone()));
+        assert_eq!(entry.model, "mock-model");
+
+        // A new message changes the key
+        let mut reply = thread.messages[0].clone();
+        reply.body_text = Some("Friday works.".to_string());
+        thread.messages.push(reply);
+        service.summarize_thread(&thread).await.unwrap();
+        assert_eq!(provider.requests.lock().unwrap().len(), 2);
+
+        assert_eq!(service.invalidate_thread(&thread.id).await.unwrap(), 2);
+        service.summarize_thread(&thread).await.unwrap();
+        assert_eq!(service.clear_cache().await.unwrap(), 1);
+        assert_eq!(provider.requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn fallback_results_are_cached_under_the_serving_provider() {
+        let mut settings = AiSettings::default();
+        settings.routing.routes.insert(
+            AiTask::Summary,
+            RoutePolicy {
+                providers: vec!["anthropic".to_string(), "ollama".to_string()],
+                timeout_secs: None,
+            },
+        );
+        let service = AiService::new(settings);
+        service
+            .register_provider("anthropic", Arc::new(DownProvider::default()))
+            .await;
+        let local = Arc::new(MockProvider::new(
+            r#"{"text": "Local summary.", "key_points": [], "action_items": []}"#,
+        ));
+        service.register_provider("ollama", local.clone()).await;
+        let cache = Arc::new(MemoryCache::default());
+        service.set_cache(cache.clone()).await;
+
+        let thread = make_thread();
+        service.summarize_thread(&thread).await.unwrap();
+        let entry = cache.0.lock().unwrap().values().next().unwrap().clone();
+        assert_eq!(entry.provider, "ollama");
+        assert_eq!(entry.model, "mock-model");
+
+        // Lookups are keyed on the primary, so the fallback's summary is
+        // not returned as the primary's
+        service.summarize_thread(&thread).await.unwrap();
+        assert_eq!(local.requests.lock().unwrap().len(), 2);
+        assert_eq!(service.cache_stats(), AiCacheStats { hits: 0, misses: 2 });
+        assert_eq!(cache.0.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn streamed_summary_fills_cache() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            "Alice asks about Friday.\nAction items:\n- Reply to Alice",
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+        service.set_cache(Arc::new(MemoryCache::default())).await;
+
+        let thread = make_thread();
+        let stream = service.summarize_thread_stream(&thread).await.unwrap();
+        collect_text(stream).await;
+
+        let stream = service.summarize_thread_stream(&thread).await.unwrap();
+        let (text, finish_reason) = collect_text(stream).await;
+        assert_eq!(Summary::parse(&text).action_items, vec!["Reply to Alice"]);
+        assert_eq!(finish_reason, Some(FinishReason::Stop));
+        assert_eq!(provider.requests.lock().unwrap().len(), 1);
+        assert_eq!(service.cache_stats().hits, 1);
+    }
+
+    #[tokio::test]
+    async fn sender_analysis_is_cached() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            r#"{"likely_type": "newsletter", "reasoning": "Bulk sender", "suggested_action": "reject", "confidence": 0.9}"#,
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+        service.set_cache(Arc::new(MemoryCache::default())).await;
+
+        let (email, signals) = make_sender_email("news@example.com");
+        service.analyze_sender(&email, &signals).await.unwrap();
+        let analysis = service.analyze_sender(&email, &signals).await.unwrap();
+        assert_eq!(analysis.reasoning, "Bulk sender");
+        assert_eq!(provider.requests.lock().unwrap().len(), 1);
+
+        let (email, signals) = make_sender_email("other@example.com");
+        service.analyze_sender(&email, &signals).await.unwrap();
+        assert_eq!(provider.requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sender_analysis_sees_email_and_signals() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            r#"{"likely_type": "newsletter", "reasoning": "Bulk sender", "suggested_action": "reject", "confidence": 0.9}"#,
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let (mut email, _) = make_sender_email("news@example.com");
+        email.headers = vec![
+            crate::domain::Header::new("List-Unsubscribe", "<mailto:unsub@example.com>"),
+            crate::domain::Header::new("Precedence", "bulk"),
+        ];
+        let signals = SenderSignals::from_email(&email, None, 0);
+        let analysis = service.analyze_sender(&email, &signals).await.unwrap();
+
+        // Signals agree with the model, so confidence is kept high
+        assert!(analysis.confidence > 0.85);
+        let requests = provider.requests.lock().unwrap();
+        let prompt = &requests[0].messages[0].content;
+        assert!(prompt.contains("Can we ship on Friday?"));
+        assert!(prompt.contains("List-Unsubscribe header: yes"));
+    }
+
+    /// Provider with a small context window that answers chunk prompts
+    /// with numbered items and merge prompts without any.
+    struct ChunkingProvider {
+        calls: std::sync::atomic::AtomicUsize,
+        requests: std::sync::Mutex<Vec<CompletionRequest>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for ChunkingProvider {
+        fn name(&self) -> &str {
+            "chunking"
+        }
+
+        async fn complete(&self, request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            self.requests.lock().unwrap().push(request.clone());
+            let n = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let is_chunk = request
+                .system_prompt
+                .as_deref()
+                .is_some_and(|prompt| prompt.contains("one part of"));
+            let text = if is_chunk {
+                serde_json::json!({
+                    "text": format!("Part {n} discussed."),
+                    "key_points": [format!("point {n}")],
+                    "action_items": [format!("action {n}")],
+                })
+                .to_string()
+            } else {
+                r#"{"text": "The whole thread.", "key_points": [], "action_items": []}"#.to_string()
+            };
+            Ok(CompletionResponse {
+                text,
+                tokens_used: TokenUsage::default(),
+                finish_reason: FinishReason::Stop,
+            })
+        }
+
+        async fn stream_complete(
+            &self,
+            _request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            Ok(Box::pin(futures::stream::empty()))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn max_context_length(&self) -> usize {
+            1500
+        }
+
+        fn model(&self) -> &str {
+            "small-model"
+        }
+    }
+
+    fn make_long_thread(messages: usize) -> Thread {
+        let mut thread = make_thread();
+        let template = thread.messages[0].clone();
+        thread.messages = (0..messages)
+            .map(|i| {
+                let mut email = template.clone();
+                email.id = EmailId::from(format!("email-{i}").as_str());
+                email.body_text =
+                    Some(format!("Message {i}. {}", "Lorem ipsum dolor. ".repeat(20)));
+                email
+            })
+            .collect();
+        thread
+    }
+
+    #[tokio::test]
+    async fn long_thread_is_summarized_in_chunks() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(ChunkingProvider {
+            calls: Default::default(),
+            requests: Default::default(),
+        });
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let summary = service
+            .summarize_thread(&make_long_thread(30))
+            .await
+            .unwrap();
+        assert_eq!(summary.text, "The whole thread.");
+
+        let requests = provider.requests.lock().unwrap();
+        let (merge, chunks) = requests.split_last().unwrap();
+        assert!(chunks.len() > 1);
+        assert!(merge.messages[0].content.contains("Part 1:"));
+
+        // Every message lands in exactly one chunk
+        for i in 0..30 {
+            let marker = format!("Message {i}.");
+            let found = chunks
+                .iter()
+                .filter(|r| r.messages[0].content.contains(&marker))
+                .count();
+            assert_eq!(found, 1, "{marker}");
+        }
+
+        // The merge dropped the action items, so those of the parts are kept
+        assert_eq!(summary.action_items.len(), chunks.len());
+        assert!(summary.action_items.contains(&"action 0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn categories_are_read_from_structured_output() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new(
+            "Here you go:\n```json\n{\"categories\": [\"work\", \"finance\", \"work\"]}\n```",
+        ));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let categories = service
+            .categorize_email(&make_thread().messages[0])
+            .await
+            .unwrap();
+        assert_eq!(categories, vec![Category::Work, Category::Finance]);
+
+        // The mock cannot enforce schemas, so the schema is in the prompt
+        let requests = provider.requests.lock().unwrap();
+        let prompt = requests[0].system_prompt.as_deref().unwrap();
+        assert!(prompt.contains("\"newsletters\""));
+        assert_eq!(
+            requests[0].output_schema.as_ref().unwrap().name,
+            "email_categories"
+        );
+    }
+
+    #[tokio::test]
+    async fn unparseable_sender_analysis_is_an_error() {
+        let service = AiService::new(AiSettings::default());
+        let provider = Arc::new(MockProvider::new("newsletter|Bulk sender|reject"));
+        service
+            .register_provider("anthropic", provider.clone())
+            .await;
+
+        let (email, signals) = make_sender_email("news@example.com");
+        assert!(service.analyze_sender(&email, &signals).await.is_err());
+        // The first reply and two repair attempts
+        assert_eq!(provider.requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn task_schemas_accept_their_types() {
+        let summary = Summary::parse("Done.\nKey points:\n- one");
+        let value = serde_json::to_value(&summary).unwrap();
+        assert!(crate::providers::ai::validate(&Summary::output_schema().schema, &value).is_ok());
+
+        let analysis = SenderAnalysis {
+            likely_type: crate::domain::SenderType::KnownContact,
+            reasoning: "Replied before".to_string(),
+            suggested_action: crate::domain::ScreenerAction::Approve,
+            confidence: 0.7,
+        };
+        let value = serde_json::to_value(&analysis).unwrap();
+        let schema = SenderAnalysis::output_schema().schema;
+        assert!(crate::providers::ai::validate(&schema, &value).is_ok());
+    }
+
+    #[test]
+    fn oversized_text_is_split_at_line_breaks() {
+        let text = "first line\nsecond line\nthird line\nfourth line\n";
+        let pieces = split_to_fit(text, 4, estimate_tokens);
+        assert!(pieces.len() > 1);
+        assert_eq!(pieces.concat(), text);
+        assert!(pieces.iter().all(|p| estimate_tokens(p) <= 4));
+        assert!(pieces[0].ends_with('\n'));
+    }
+
+    #[test]
+    fn summary_text_round_trips() {
+        let summary = Summary::parse("Done.\nKey points:\n- one\nAction items:\n- two");
+        assert_eq!(Summary::parse(&summary.to_text()).action_items, vec!["two"]);
+        assert_eq!(Summary::parse(&summary.to_text()).key_points, vec!["one"]);
+    }
+
     #[test]
     fn draft_suggestion_serialization() {
         let draft = DraftSuggestion {
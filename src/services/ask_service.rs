@@ -0,0 +1,480 @@
+//! Question answering over the mailbox ("Ask my inbox").
+//!
+//! The [`AskService`] retrieves passages with hybrid search, packs as many
+//! as fit in the provider's context window, and asks the model for an
+//! answer that cites its sources as `[1]`, `[2]`, and so on. Citations are
+//! resolved back to emails so the UI can open each cited message.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{EmailId, ThreadId};
+use crate::providers::ai::{CompletionRequest, CompletionStream, Message};
+use crate::services::{
+    AiService, SearchHit, SearchMode, SearchQuery, SearchService, SearchStorage,
+};
+
+/// Instructions for grounded answers with citations.
+const SYSTEM_PROMPT: &str = "You answer questions about the user's email using only the \
+numbered sources provided. Cite the sources that support each statement with their \
+numbers in square brackets, like [1] or [2][3]. If the sources do not answer the \
+question, say so instead of guessing.";
+
+/// Tokens reserved for message formatting around the prompt text.
+const PROMPT_OVERHEAD_TOKENS: usize = 64;
+
+/// Settings for inbox question answering.
+#[derive(Debug, Clone)]
+pub struct AskSettings {
+    /// Maximum number of search hits considered as sources.
+    pub max_sources: usize,
+    /// Tokens reserved for the answer.
+    pub max_answer_tokens: usize,
+    /// Maximum characters of each source's text.
+    pub max_source_chars: usize,
+}
+
+impl Default for AskSettings {
+    fn default() -> Self {
+        Self {
+            max_sources: 12,
+            max_answer_tokens: 800,
+            max_source_chars: 2000,
+        }
+    }
+}
+
+/// An email passage given to the model as a numbered source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AskSource {
+    /// Number the answer cites the source by, starting at 1.
+    pub number: usize,
+    /// ID of the source email.
+    pub email_id: EmailId,
+    /// ID of the thread containing the email.
+    pub thread_id: ThreadId,
+    /// Email subject.
+    pub subject: Option<String>,
+    /// Sender display.
+    pub from: String,
+    /// Email date.
+    pub date: DateTime<Utc>,
+    /// Passage shown to the model.
+    pub text: String,
+}
+
+impl AskSource {
+    /// Formats the source as it appears in the prompt.
+    fn to_prompt(&self) -> String {
+        format!(
+            "[{}] From: {} | Date: {} | Subject: {}\n{}",
+            self.number,
+            self.from,
+            self.date.format("%Y-%m-%d"),
+            self.subject.as_deref().unwrap_or("(no subject)"),
+            self.text
+        )
+    }
+}
+
+/// A streaming answer and the sources it may cite.
+pub struct AskAnswer {
+    /// Sources given to the model, numbered from 1.
+    pub sources: Vec<AskSource>,
+    /// Answer text as it is generated.
+    pub stream: CompletionStream,
+}
+
+/// A citation of a source in an answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    /// Number of the cited source.
+    pub number: usize,
+    /// ID of the cited email.
+    pub email_id: EmailId,
+    /// ID of the thread containing the cited email.
+    pub thread_id: ThreadId,
+    /// Byte range of the `[n]` marker in the answer.
+    pub range: Range<usize>,
+}
+
+/// Finds the `[n]` citation markers in an answer.
+///
+/// Markers that do not refer to a source, such as `[7]` with five sources,
+/// are ignored.
+pub fn parse_citations(answer: &str, sources: &[AskSource]) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    let mut rest = 0;
+
+    while let Some(open) = answer[rest..].find('[').map(|i| rest + i) {
+        let Some(close) = answer[open..].find(']').map(|i| open + i) else {
+            break;
+        };
+        let cited = answer[open + 1..close]
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| sources.iter().find(|s| s.number == n));
+        match cited {
+            Some(source) => {
+                citations.push(Citation {
+                    number: source.number,
+                    email_id: source.email_id.clone(),
+                    thread_id: source.thread_id.clone(),
+                    range: open..close + 1,
+                });
+                rest = close + 1;
+            }
+            None => rest = open + 1,
+        }
+    }
+
+    citations
+}
+
+/// Turns search hits into numbered sources that fit in `budget_tokens`, as
+/// counted by `count_tokens`.
+///
+/// Hits are taken in ranking order. A hit that does not fit is skipped so a
+/// shorter one further down can still be included.
+pub fn select_sources(
+    hits: &[SearchHit],
+    budget_tokens: usize,
+    settings: &AskSettings,
+    count_tokens: impl Fn(&str) -> usize,
+) -> Vec<AskSource> {
+    let mut sources = Vec::new();
+    let mut used = 0;
+
+    for hit in hits.iter().take(settings.max_sources) {
+        let text = hit.highlights.first().unwrap_or(&hit.snippet);
+        let text = truncate_chars(text.trim(), settings.max_source_chars);
+        if text.is_empty() {
+            continue;
+        }
+
+        let source = AskSource {
+            number: sources.len() + 1,
+            email_id: hit.email_id.clone(),
+            thread_id: hit.thread_id.clone(),
+            subject: hit.subject.clone(),
+            from: hit.from.clone(),
+            date: hit.date,
+            text,
+        };
+        let cost = count_tokens(&source.to_prompt());
+        if used + cost > budget_tokens {
+            continue;
+        }
+        used += cost;
+        sources.push(source);
+    }
+
+    sources
+}
+
+/// Truncates text to at most `max_chars` characters.
+fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+/// Builds the question prompt from the selected sources.
+fn question_prompt(question: &str, sources: &[AskSource]) -> String {
+    let sources: Vec<String> = sources.iter().map(AskSource::to_prompt).collect();
+    format!(
+        "Sources:\n\n{}\n\nQuestion: {}",
+        sources.join("\n\n"),
+        question
+    )
+}
+
+/// Answers questions about the mailbox from retrieved emails.
+pub struct AskService<S: SearchStorage> {
+    /// Hybrid retrieval.
+    search: Arc<SearchService<S>>,
+    /// Provider access for answer generation.
+    ai_service: Arc<AiService>,
+    /// Question answering settings.
+    settings: AskSettings,
+}
+
+impl<S: SearchStorage> AskService<S> {
+    /// Creates a new ask service.
+    pub fn new(search: Arc<SearchService<S>>, ai_service: Arc<AiService>) -> Self {
+        Self {
+            search,
+            ai_service,
+            settings: AskSettings::default(),
+        }
+    }
+
+    /// Sets the question answering settings.
+    pub fn with_settings(mut self, settings: AskSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Answers a question, streaming the answer as it is generated.
+    ///
+    /// `query.text` is the question; the query's filters limit which emails
+    /// can be used as sources.
+    pub async fn ask(&self, query: SearchQuery) -> Result<AskAnswer> {
+        let question = query.text.trim().to_string();
+        if question.is_empty() {
+            anyhow::bail!("Question is empty");
+        }
+
+        let provider = self.ai_service.default_provider().await?;
+        let query = SearchQuery {
+            mode: SearchMode::Hybrid,
+            limit: self.settings.max_sources,
+            offset: 0,
+            ..query
+        };
+        let results = self.search.search(query).await?;
+
+        let fixed_cost = provider.count_tokens(SYSTEM_PROMPT)
+            + provider.count_tokens(&question_prompt(&question, &[]))
+            + PROMPT_OVERHEAD_TOKENS
+            + self.settings.max_answer_tokens;
+        let budget = provider.max_context_length().saturating_sub(fixed_cost);
+
+        let sources = select_sources(&results.hits, budget, &self.settings, |text| {
+            provider.count_tokens(text)
+        });
+        if sources.is_empty() {
+            anyhow::bail!("No emails found to answer the question");
+        }
+
+        let request =
+            CompletionRequest::new(vec![Message::user(question_prompt(&question, &sources))])
+                .with_system_prompt(SYSTEM_PROMPT)
+                .with_temperature(0.2)
+                .with_max_tokens(self.settings.max_answer_tokens);
+
+        let stream = provider.stream_complete(&request).await?;
+        Ok(AskAnswer { sources, stream })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AccountId;
+    use crate::providers::ai::{
+        estimate_tokens, CompletionResponse, FinishReason, LlmProvider, LlmResult, StreamChunk,
+    };
+    use crate::services::{AiSettings, EmailMetadata, FtsHit, SearchSource};
+    use futures::StreamExt;
+
+    fn source(number: usize, id: &str) -> AskSource {
+        AskSource {
+            number,
+            email_id: EmailId::from(id),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            subject: Some("Vendor contract".to_string()),
+            from: "legal@example.com".to_string(),
+            date: Utc::now(),
+            text: "The renewal needs a new indemnity clause.".to_string(),
+        }
+    }
+
+    fn hit(id: &str, snippet: &str) -> SearchHit {
+        SearchHit {
+            email_id: EmailId::from(id),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            subject: Some(format!("Subject {}", id)),
+            snippet: snippet.to_string(),
+            from: "legal@example.com".to_string(),
+            date: Utc::now(),
+            is_read: true,
+            score: 1.0,
+            source: SearchSource::FullText,
+            highlights: Vec::new(),
+            rerank_score: None,
+        }
+    }
+
+    #[test]
+    fn parses_citations() {
+        let sources = vec![source(1, "a"), source(2, "b")];
+        let answer = "Legal wants a new clause [1][2], not [7] or [see below].";
+
+        let citations = parse_citations(answer, &sources);
+
+        assert_eq!(citations.len(), 2);
+        assert_eq!(citations[0].number, 1);
+        assert_eq!(citations[0].email_id, EmailId::from("a"));
+        assert_eq!(&answer[citations[0].range.clone()], "[1]");
+        assert_eq!(citations[1].thread_id, ThreadId::from("thread-b"));
+        assert_eq!(&answer[citations[1].range.clone()], "[2]");
+    }
+
+    #[test]
+    fn parses_unterminated_marker() {
+        let sources = vec![source(1, "a")];
+        assert!(parse_citations("Still streaming [1", &sources).is_empty());
+    }
+
+    #[test]
+    fn selects_sources_within_budget() {
+        let long = "word ".repeat(200);
+        let hits = vec![
+            hit("a", "short one"),
+            hit("b", &long),
+            hit("c", "short two"),
+        ];
+
+        let sources = select_sources(&hits, 60, &AskSettings::default(), estimate_tokens);
+
+        let ids: Vec<&str> = sources.iter().map(|s| s.email_id.0.as_str()).collect();
+        assert_eq!(ids, vec!["a", "c"]);
+        assert_eq!(sources[1].number, 2);
+    }
+
+    #[test]
+    fn truncates_long_sources() {
+        let settings = AskSettings {
+            max_source_chars: 5,
+            ..AskSettings::default()
+        };
+        let sources = select_sources(&[hit("a", "héllo world")], 1000, &settings, estimate_tokens);
+        assert_eq!(sources[0].text, "héllo...");
+    }
+
+    struct MockStorage;
+
+    #[async_trait::async_trait]
+    impl SearchStorage for MockStorage {
+        async fn fts_search(&self, _query: &SearchQuery) -> Result<Vec<FtsHit>> {
+            Ok(vec![FtsHit {
+                email_id: EmailId::from("renewal"),
+                thread_id: ThreadId::from("thread-renewal"),
+                rank: -8.0,
+                snippet: "Legal approved the renewal with a 30 day notice period.".to_string(),
+            }])
+        }
+
+        async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>> {
+            Ok(ids
+                .iter()
+                .map(|id| EmailMetadata {
+                    email_id: id.clone(),
+                    account_id: AccountId::from("work"),
+                    thread_id: ThreadId::from("thread-renewal"),
+                    subject: Some("Vendor contract renewal".to_string()),
+                    snippet: String::new(),
+                    from: "legal@example.com".to_string(),
+                    date: Utc::now(),
+                    to: Vec::new(),
+                    is_read: true,
+                    is_starred: false,
+                    is_draft: false,
+                    has_attachments: false,
+                    labels: Vec::new(),
+                })
+                .collect())
+        }
+
+        async fn rebuild_fts_index(&self, _account_id: &AccountId) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Provider that answers with a fixed cited sentence and records prompts.
+    struct CitingProvider {
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LlmProvider for CitingProvider {
+        fn name(&self) -> &str {
+            "citing"
+        }
+
+        async fn complete(&self, _request: &CompletionRequest) -> LlmResult<CompletionResponse> {
+            unimplemented!("ask streams its answers")
+        }
+
+        async fn stream_complete(
+            &self,
+            request: &CompletionRequest,
+        ) -> LlmResult<CompletionStream> {
+            self.prompts
+                .lock()
+                .unwrap()
+                .push(request.messages[0].content.clone());
+            let chunks = vec![
+                Ok(StreamChunk {
+                    text: "Legal approved it ".to_string(),
+                    finish_reason: None,
+                }),
+                Ok(StreamChunk {
+                    text: "[1].".to_string(),
+                    finish_reason: Some(FinishReason::Stop),
+                }),
+            ];
+            Ok(Box::pin(futures::stream::iter(chunks)))
+        }
+
+        fn supports_function_calling(&self) -> bool {
+            false
+        }
+
+        fn max_context_length(&self) -> usize {
+            4096
+        }
+
+        fn model(&self) -> &str {
+            "mock"
+        }
+    }
+
+    #[tokio::test]
+    async fn ask_streams_cited_answer() {
+        let ai_service = Arc::new(AiService::new(AiSettings::default()));
+        let provider = Arc::new(CitingProvider {
+            prompts: std::sync::Mutex::new(Vec::new()),
+        });
+        ai_service
+            .register_provider("anthropic", provider.clone())
+            .await;
+        let search = Arc::new(SearchService::new(Arc::new(MockStorage)));
+        let service = AskService::new(search, ai_service);
+
+        let mut answer = service
+            .ask(SearchQuery::new("what did legal say about the renewal?"))
+            .await
+            .unwrap();
+
+        let mut text = String::new();
+        while let Some(chunk) = answer.stream.next().await {
+            text.push_str(&chunk.unwrap().text);
+        }
+        let citations = parse_citations(&text, &answer.sources);
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].email_id, EmailId::from("renewal"));
+        assert_eq!(citations[0].thread_id, ThreadId::from("thread-renewal"));
+
+        let prompt = provider.prompts.lock().unwrap()[0].clone();
+        assert!(prompt.contains("[1] From: legal@example.com"));
+        assert!(prompt.contains("30 day notice period"));
+        assert!(prompt.ends_with("Question: what did legal say about the renewal?"));
+    }
+
+    #[tokio::test]
+    async fn ask_rejects_empty_question() {
+        let ai_service = Arc::new(AiService::new(AiSettings::default()));
+        let search = Arc::new(SearchService::new(Arc::new(MockStorage)));
+        let service = AskService::new(search, ai_service);
+
+        assert!(service.ask(SearchQuery::new("  ")).await.is_err());
+    }
+}
@@ -311,6 +311,17 @@ impl<S: ContactStorage> ContactService<S> {
         self.query(&ContactFilter::new().search(query), ContactSort::Name)
     }
 
+    /// Counts contacts whose address is at the given domain.
+    pub fn count_at_domain(&self, domain: &str) -> Result<usize> {
+        let suffix = format!("@{}", normalize_email(domain));
+        Ok(self
+            .storage
+            .get_all()?
+            .iter()
+            .filter(|c| normalize_email(&c.email).ends_with(&suffix))
+            .count())
+    }
+
     /// Gets contact statistics.
     pub fn stats(&self) -> Result<ContactStats> {
         let all = self.storage.get_all()?;
@@ -656,4 +667,17 @@ mod tests {
             .unwrap();
         assert_eq!(updated.notes, Some("Important contact".to_string()));
     }
+
+    #[test]
+    fn count_contacts_at_domain() {
+        let storage = MockStorage::new();
+        let service = ContactService::new(storage);
+
+        service.create("alice@acme.com", None).unwrap();
+        service.create("bob@acme.com", None).unwrap();
+        service.create("carol@notacme.com", None).unwrap();
+
+        assert_eq!(service.count_at_domain("ACME.com").unwrap(), 2);
+        assert_eq!(service.count_at_domain("other.com").unwrap(), 0);
+    }
 }
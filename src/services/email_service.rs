@@ -76,6 +76,38 @@ pub trait EmailStorage: Send + Sync {
         thread_id: &ThreadId,
         updates: ThreadMetadataUpdate,
     ) -> Result<()>;
+
+    /// Finds threads in a view with a message from the given senders.
+    async fn find_threads_from(
+        &self,
+        account_id: &AccountId,
+        senders: &SenderScope,
+        view: ViewType,
+    ) -> Result<Vec<ThreadId>>;
+}
+
+/// A set of senders that thread operations can target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SenderScope {
+    /// A single email address.
+    Address(String),
+    /// Every address at a domain or one of its subdomains.
+    Domain(String),
+}
+
+impl SenderScope {
+    /// Returns true if the address is in scope.
+    pub fn matches(&self, address: &str) -> bool {
+        let address = address.trim().to_lowercase();
+        match self {
+            SenderScope::Address(expected) => address == expected.to_lowercase(),
+            SenderScope::Domain(domain) => {
+                let domain = domain.to_lowercase();
+                let sender_domain = address.rsplit_once('@').map_or("", |(_, d)| d);
+                sender_domain == domain || sender_domain.ends_with(&format!(".{}", domain))
+            }
+        }
+    }
 }
 
 /// Updates to thread metadata for local storage.
@@ -113,6 +145,10 @@ pub enum ViewType {
     All,
     /// Snoozed threads.
     Snoozed,
+    /// Threads the local spam filter moved out of the inbox.
+    Junk,
+    /// Threads from senders rejected in the screener.
+    ScreenedOut,
     /// Threads with a specific label.
     Label(LabelId),
 }
@@ -129,6 +165,8 @@ impl ViewType {
             ViewType::Trash => "[Gmail]/Trash",
             ViewType::All => "[Gmail]/All Mail",
             ViewType::Snoozed => "heap/Snoozed",
+            ViewType::Junk => "heap/Junk",
+            ViewType::ScreenedOut => "heap/Screened Out",
             ViewType::Label(_) => "INBOX", // Will be filtered by label
         }
     }
@@ -497,6 +535,88 @@ impl<S: EmailStorage> EmailService<S> {
         Ok(())
     }
 
+    /// Finds an account's threads in a view with a message from the given
+    /// senders.
+    pub async fn find_threads_from(
+        &self,
+        account_id: &AccountId,
+        senders: &SenderScope,
+        view: ViewType,
+    ) -> Result<Vec<ThreadId>> {
+        self.storage
+            .find_threads_from(account_id, senders, view)
+            .await
+    }
+
+    /// Moves threads from the inbox to the Screened Out view.
+    ///
+    /// The threads are archived on the server so other clients stop showing
+    /// them in the inbox too.
+    ///
+    /// # Arguments
+    ///
+    /// * `account_id` - The account the threads belong to
+    /// * `thread_ids` - The threads to screen out
+    pub async fn screen_out(&self, account_id: &AccountId, thread_ids: &[ThreadId]) -> Result<()> {
+        if thread_ids.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<String> = thread_ids.iter().map(|id| id.0.clone()).collect();
+        if let Some(provider) = self.providers.read().await.get(account_id) {
+            provider.archive(&ids).await?;
+        }
+
+        for thread_id in thread_ids {
+            self.storage
+                .update_thread_metadata(
+                    thread_id,
+                    ThreadMetadataUpdate {
+                        remove_labels: vec![LabelId::from("INBOX")],
+                        add_labels: vec![LabelId::from(ViewType::ScreenedOut.folder_name())],
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns screened-out threads to the inbox, on the server and locally.
+    ///
+    /// # Arguments
+    ///
+    /// * `account_id` - The account the threads belong to
+    /// * `thread_ids` - The threads to restore
+    pub async fn restore_screened_out(
+        &self,
+        account_id: &AccountId,
+        thread_ids: &[ThreadId],
+    ) -> Result<()> {
+        let providers = self.providers.read().await;
+        let provider = providers.get(account_id);
+
+        for thread_id in thread_ids {
+            if let Some(provider) = provider {
+                provider.apply_label(&thread_id.0, "INBOX").await?;
+            }
+
+            self.storage
+                .update_thread_metadata(
+                    thread_id,
+                    ThreadMetadataUpdate {
+                        add_labels: vec![LabelId::from("INBOX")],
+                        remove_labels: vec![LabelId::from(ViewType::ScreenedOut.folder_name())],
+                        ..Default::default()
+                    },
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
     /// Snoozes a thread until a specified time.
     ///
     /// Snoozed threads are hidden from the inbox and reappear at the specified time.
@@ -553,6 +673,18 @@ mod tests {
         assert_eq!(ViewType::Trash.folder_name(), "[Gmail]/Trash");
     }
 
+    #[test]
+    fn sender_scope_matching() {
+        let address = SenderScope::Address("News@Example.com".to_string());
+        assert!(address.matches("news@example.com"));
+        assert!(!address.matches("other@example.com"));
+
+        let domain = SenderScope::Domain("example.com".to_string());
+        assert!(domain.matches("a@example.com"));
+        assert!(domain.matches("a@mail.example.com"));
+        assert!(!domain.matches("a@badexample.com"));
+    }
+
     #[test]
     fn pagination_default() {
         let p = Pagination::default();
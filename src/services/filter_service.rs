@@ -0,0 +1,1026 @@
+//! Mail filter rules.
+//!
+//! The [`FilterService`] runs user-defined rules over incoming mail during
+//! sync and, on request, over mail already stored. Each rule has conditions
+//! on the sender, recipients, subject, body, headers, attachments, size or
+//! account, and a list of actions to take when they match. Rules run in
+//! order of position; a matching rule marked `stop_processing` ends the run
+//! for that email.
+//!
+//! Actions that only change the email (labels, archive, star, read) are
+//! applied to new mail before it is stored. Snoozing, forwarding and smart
+//! view assignment need the stored email, so they are carried out through
+//! [`FilterEffects`] once the email has been stored, as is every action of
+//! a retroactive run.
+//!
+//! Patterns are checked when a rule is saved and compiled once when the
+//! service loads its rules, so sync and retroactive runs only match.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::{system_labels, AccountId, Email, EmailId, LabelId};
+use crate::services::{IncomingFilter, SmartViewType};
+
+/// Stored emails a retroactive run looks at.
+const RETROACTIVE_EMAILS: u32 = 5000;
+
+/// Label given to snoozed mail, matching [`EmailService::snooze`](crate::services::EmailService::snooze).
+const SNOOZED_LABEL: &str = "heap/Snoozed";
+
+/// Errors that can occur during filter operations.
+#[derive(Debug, Error)]
+pub enum FilterError {
+    /// Storage error.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// Rule not found.
+    #[error("filter rule not found: {0}")]
+    RuleNotFound(String),
+
+    /// The rule cannot be saved as written.
+    #[error("invalid filter rule: {0}")]
+    InvalidRule(String),
+}
+
+/// Result type for filter operations.
+pub type FilterResult<T> = Result<T, FilterError>;
+
+/// A test on an email.
+///
+/// Text patterns are case-insensitive regular expressions that match
+/// anywhere in the field.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterCondition {
+    /// Sender address, as `Name <email>` when the name is known.
+    From { pattern: String },
+    /// Any primary recipient.
+    To { pattern: String },
+    /// Any CC recipient.
+    Cc { pattern: String },
+    /// Subject line.
+    Subject { pattern: String },
+    /// Plain text body, or the HTML body when there is no text part.
+    Body { pattern: String },
+    /// Any header with the given name.
+    Header { name: String, pattern: String },
+    /// Has at least one non-inline attachment.
+    HasAttachment,
+    /// The `List-Id` header.
+    ListId { pattern: String },
+    /// Message is larger than this many bytes.
+    LargerThan { bytes: u64 },
+    /// Message is smaller than this many bytes.
+    SmallerThan { bytes: u64 },
+    /// Received by this account.
+    Account { account_id: AccountId },
+}
+
+impl FilterCondition {
+    /// Returns whether the email satisfies this condition.
+    ///
+    /// The pattern is compiled on every call, and one that does not compile
+    /// matches nothing.
+    pub fn matches(&self, email: &Email) -> bool {
+        self.compile()
+            .is_ok_and(|re| self.matches_compiled(re.as_ref(), email))
+    }
+
+    /// Checks that the condition's pattern compiles.
+    pub fn validate(&self) -> Result<(), String> {
+        self.compile().map(|_| ())
+    }
+
+    /// Compiles the condition's pattern, if it has one.
+    fn compile(&self) -> Result<Option<Regex>, String> {
+        let pattern = match self {
+            FilterCondition::From { pattern }
+            | FilterCondition::To { pattern }
+            | FilterCondition::Cc { pattern }
+            | FilterCondition::Subject { pattern }
+            | FilterCondition::Body { pattern }
+            | FilterCondition::ListId { pattern } => pattern,
+            FilterCondition::Header { name, pattern } => {
+                if name.trim().is_empty() {
+                    return Err("header condition has no header name".to_string());
+                }
+                pattern
+            }
+            _ => return Ok(None),
+        };
+        regex(pattern).map(Some).map_err(|e| e.to_string())
+    }
+
+    /// Returns whether the email satisfies this condition, given its
+    /// compiled pattern.
+    fn matches_compiled(&self, re: Option<&Regex>, email: &Email) -> bool {
+        match self {
+            FilterCondition::From { .. } => is_match(re, std::iter::once(email.from.display())),
+            FilterCondition::To { .. } => is_match(re, email.to.iter().map(|a| a.display())),
+            FilterCondition::Cc { .. } => is_match(re, email.cc.iter().map(|a| a.display())),
+            FilterCondition::Subject { .. } => is_match(re, email.subject.iter().cloned()),
+            FilterCondition::Body { .. } => {
+                let body = email.body_text.as_ref().or(email.body_html.as_ref());
+                is_match(re, body.into_iter().cloned())
+            }
+            FilterCondition::Header { name, .. } => is_match(
+                re,
+                email
+                    .headers
+                    .iter()
+                    .filter(|h| h.name.eq_ignore_ascii_case(name))
+                    .map(|h| h.value.clone()),
+            ),
+            FilterCondition::HasAttachment => email.attachments.iter().any(|a| !a.is_inline),
+            FilterCondition::ListId { .. } => {
+                is_match(re, email.header("List-Id").map(String::from).into_iter())
+            }
+            FilterCondition::LargerThan { bytes } => message_size(email) > *bytes,
+            FilterCondition::SmallerThan { bytes } => message_size(email) < *bytes,
+            FilterCondition::Account { account_id } => email.account_id == *account_id,
+        }
+    }
+}
+
+/// Something a rule does to a matching email.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Adds a label.
+    ApplyLabel { label: LabelId },
+    /// Removes the email from the inbox.
+    Archive,
+    /// Stars the email.
+    Star,
+    /// Marks the email as read.
+    MarkRead,
+    /// Hides the email from the inbox for a number of hours.
+    Snooze { hours: u32 },
+    /// Forwards the email to an address.
+    Forward { to: String },
+    /// Puts the email's thread in a smart view.
+    AssignSmartView { view: SmartViewType },
+}
+
+impl FilterAction {
+    /// Applies the action's changes to the email itself.
+    ///
+    /// Forwarding and smart view assignment leave the email unchanged.
+    pub fn apply_to(&self, email: &mut Email) {
+        match self {
+            FilterAction::ApplyLabel { label } => add_label(email, label.clone()),
+            FilterAction::Archive => email.labels.retain(|l| *l != system_labels::inbox()),
+            FilterAction::Star => email.is_starred = true,
+            FilterAction::MarkRead => email.is_read = true,
+            FilterAction::Snooze { .. } => {
+                email.labels.retain(|l| *l != system_labels::inbox());
+                add_label(email, LabelId::from(SNOOZED_LABEL));
+            }
+            FilterAction::Forward { .. } | FilterAction::AssignSmartView { .. } => {}
+        }
+    }
+
+    /// Returns whether the action needs more than [`apply_to`](Self::apply_to)
+    /// on a new email, and so runs through [`FilterEffects`] once it is stored.
+    pub fn needs_stored_email(&self) -> bool {
+        matches!(
+            self,
+            FilterAction::Snooze { .. }
+                | FilterAction::Forward { .. }
+                | FilterAction::AssignSmartView { .. }
+        )
+    }
+
+    /// Returns when a snooze action wakes, counting from `now`.
+    pub fn wake_time(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            FilterAction::Snooze { hours } => Some(now + Duration::hours(i64::from(*hours))),
+            _ => None,
+        }
+    }
+}
+
+/// A user-defined filter rule.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterRule {
+    /// Unique ID.
+    pub id: String,
+    /// Name shown in the rule list.
+    pub name: String,
+    /// Tests on the email.
+    pub conditions: Vec<FilterCondition>,
+    /// Whether every condition must hold, rather than any one.
+    pub match_all: bool,
+    /// Actions taken on matching emails, in order.
+    pub actions: Vec<FilterAction>,
+    /// Place in the run order; lower runs first.
+    pub position: i32,
+    /// Whether later rules are skipped for emails this rule matches.
+    pub stop_processing: bool,
+    /// Whether the rule runs at all.
+    pub enabled: bool,
+    /// When the rule was created.
+    pub created_at: DateTime<Utc>,
+}
+
+impl FilterRule {
+    /// Creates an enabled rule that requires all conditions to hold.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            id: format!("flt-{}", uuid::Uuid::new_v4()),
+            name: name.into(),
+            conditions: Vec::new(),
+            match_all: true,
+            actions: Vec::new(),
+            position: 0,
+            stop_processing: false,
+            enabled: true,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Adds a condition.
+    pub fn when(mut self, condition: FilterCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Adds an action.
+    pub fn then(mut self, action: FilterAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// Makes the rule match when any condition holds.
+    pub fn match_any(mut self) -> Self {
+        self.match_all = false;
+        self
+    }
+
+    /// Makes the rule stop later rules from running on emails it matches.
+    pub fn stop_processing(mut self) -> Self {
+        self.stop_processing = true;
+        self
+    }
+
+    /// Returns whether the rule is enabled and matches the email.
+    ///
+    /// The rule's patterns are compiled on every call.
+    pub fn matches(&self, email: &Email) -> bool {
+        CompiledRule::new(self.clone()).is_ok_and(|rule| rule.matches(email))
+    }
+
+    /// Checks that the rule has conditions and actions and that its
+    /// patterns compile.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.conditions.is_empty() {
+            return Err("rule has no conditions".to_string());
+        }
+        if self.actions.is_empty() {
+            return Err("rule has no actions".to_string());
+        }
+        for condition in &self.conditions {
+            condition.validate()?;
+        }
+        for action in &self.actions {
+            if let FilterAction::Forward { to } = action {
+                if !to.contains('@') {
+                    return Err(format!("cannot forward to {:?}", to));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Result of running rules over one email.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterOutcome {
+    /// IDs of the rules that matched, in run order.
+    pub matched_rules: Vec<String>,
+    /// Actions of the matching rules, in run order.
+    pub actions: Vec<FilterAction>,
+}
+
+impl FilterOutcome {
+    /// Returns whether no rule matched.
+    pub fn is_empty(&self) -> bool {
+        self.matched_rules.is_empty()
+    }
+}
+
+/// Runs rules over an email in order of position, stopping after the first
+/// matching rule marked `stop_processing`.
+///
+/// The rules' patterns are compiled on every call.
+pub fn evaluate_filters(rules: &[FilterRule], email: &Email) -> FilterOutcome {
+    evaluate_compiled(&compile_rules(rules.to_vec()), email)
+}
+
+/// A rule with its condition patterns compiled.
+struct CompiledRule {
+    rule: FilterRule,
+    /// Compiled pattern of each condition, in order.
+    patterns: Vec<Option<Regex>>,
+}
+
+impl CompiledRule {
+    /// Compiles a rule's patterns.
+    fn new(rule: FilterRule) -> Result<Self, String> {
+        let patterns = rule
+            .conditions
+            .iter()
+            .map(FilterCondition::compile)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rule, patterns })
+    }
+
+    /// Returns whether the rule is enabled and matches the email.
+    fn matches(&self, email: &Email) -> bool {
+        if !self.rule.enabled || self.rule.conditions.is_empty() {
+            return false;
+        }
+        let mut results = self
+            .rule
+            .conditions
+            .iter()
+            .zip(&self.patterns)
+            .map(|(c, re)| c.matches_compiled(re.as_ref(), email));
+        if self.rule.match_all {
+            results.all(|matched| matched)
+        } else {
+            results.any(|matched| matched)
+        }
+    }
+}
+
+/// Compiles rules in order of position.
+///
+/// Rules whose patterns do not compile are left out; saving rejects them,
+/// so they can only come from older versions.
+fn compile_rules(mut rules: Vec<FilterRule>) -> Vec<CompiledRule> {
+    rules.sort_by(|a, b| {
+        a.position
+            .cmp(&b.position)
+            .then(a.created_at.cmp(&b.created_at))
+    });
+    rules
+        .into_iter()
+        .filter_map(|rule| match CompiledRule::new(rule) {
+            Ok(compiled) => Some(compiled),
+            Err(e) => {
+                tracing::warn!("Skipping filter rule with invalid pattern: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Runs compiled rules, already in order, over an email.
+fn evaluate_compiled(rules: &[CompiledRule], email: &Email) -> FilterOutcome {
+    let mut outcome = FilterOutcome::default();
+    for compiled in rules {
+        if !compiled.matches(email) {
+            continue;
+        }
+        let rule = &compiled.rule;
+        outcome.matched_rules.push(rule.id.clone());
+        outcome.actions.extend(rule.actions.iter().cloned());
+        if rule.stop_processing {
+            break;
+        }
+    }
+    outcome
+}
+
+/// Storage trait for filter persistence.
+#[async_trait]
+pub trait FilterStorage: Send + Sync {
+    /// Gets all rules, in run order.
+    async fn get_rules(&self) -> FilterResult<Vec<FilterRule>>;
+
+    /// Gets a rule by ID.
+    async fn get_rule(&self, id: &str) -> FilterResult<Option<FilterRule>>;
+
+    /// Inserts or replaces a rule.
+    async fn save_rule(&self, rule: &FilterRule) -> FilterResult<()>;
+
+    /// Deletes a rule.
+    async fn delete_rule(&self, id: &str) -> FilterResult<()>;
+
+    /// Gets an account's stored emails, newest first.
+    async fn get_emails(&self, account_id: &AccountId, limit: u32) -> FilterResult<Vec<Email>>;
+}
+
+/// Carries out actions on stored emails.
+///
+/// Implemented by the application on top of the email, snooze and smart
+/// view services, so that actions reach the provider as well as storage.
+#[async_trait]
+pub trait FilterEffects: Send + Sync {
+    /// Performs an action on a stored email.
+    async fn perform(&self, email: &Email, action: &FilterAction) -> anyhow::Result<()>;
+}
+
+/// Service that manages and runs filter rules.
+pub struct FilterService<S: FilterStorage> {
+    storage: S,
+    effects: Arc<dyn FilterEffects>,
+    /// Rules as last loaded, cleared whenever a rule changes.
+    compiled: Mutex<Option<Arc<Vec<CompiledRule>>>>,
+    /// Actions waiting for new emails to be stored.
+    pending: Mutex<HashMap<EmailId, Vec<FilterAction>>>,
+}
+
+impl<S: FilterStorage> FilterService<S> {
+    /// Creates a new filter service.
+    pub fn new(storage: S, effects: Arc<dyn FilterEffects>) -> Self {
+        Self {
+            storage,
+            effects,
+            compiled: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets all rules, in run order.
+    pub async fn get_rules(&self) -> FilterResult<Vec<FilterRule>> {
+        let mut rules = self.storage.get_rules().await?;
+        rules.sort_by(|a, b| {
+            a.position
+                .cmp(&b.position)
+                .then(a.created_at.cmp(&b.created_at))
+        });
+        Ok(rules)
+    }
+
+    /// Adds a rule at the end of the run order.
+    pub async fn add_rule(&self, mut rule: FilterRule) -> FilterResult<FilterRule> {
+        rule.validate().map_err(FilterError::InvalidRule)?;
+        let rules = self.storage.get_rules().await?;
+        rule.position = rules.iter().map(|r| r.position + 1).max().unwrap_or(0);
+        self.save(&rule).await?;
+        Ok(rule)
+    }
+
+    /// Replaces an existing rule, keeping its place in the run order.
+    pub async fn update_rule(&self, mut rule: FilterRule) -> FilterResult<FilterRule> {
+        rule.validate().map_err(FilterError::InvalidRule)?;
+        let existing = self
+            .storage
+            .get_rule(&rule.id)
+            .await?
+            .ok_or_else(|| FilterError::RuleNotFound(rule.id.clone()))?;
+        rule.position = existing.position;
+        self.save(&rule).await?;
+        Ok(rule)
+    }
+
+    /// Deletes a rule.
+    pub async fn delete_rule(&self, id: &str) -> FilterResult<()> {
+        if self.storage.get_rule(id).await?.is_none() {
+            return Err(FilterError::RuleNotFound(id.to_string()));
+        }
+        self.storage.delete_rule(id).await?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Enables or disables a rule.
+    pub async fn set_enabled(&self, id: &str, enabled: bool) -> FilterResult<()> {
+        let mut rule = self
+            .storage
+            .get_rule(id)
+            .await?
+            .ok_or_else(|| FilterError::RuleNotFound(id.to_string()))?;
+        rule.enabled = enabled;
+        self.save(&rule).await
+    }
+
+    /// Moves a rule to a new place in the run order, renumbering the rest.
+    pub async fn move_rule(&self, id: &str, index: usize) -> FilterResult<Vec<FilterRule>> {
+        let mut rules = self.get_rules().await?;
+        let from = rules
+            .iter()
+            .position(|r| r.id == id)
+            .ok_or_else(|| FilterError::RuleNotFound(id.to_string()))?;
+        let rule = rules.remove(from);
+        rules.insert(index.min(rules.len()), rule);
+
+        for (position, rule) in rules.iter_mut().enumerate() {
+            let position = position as i32;
+            if rule.position != position {
+                rule.position = position;
+                self.save(rule).await?;
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Runs the rules over an email without acting on it.
+    pub async fn evaluate(&self, email: &Email) -> FilterResult<FilterOutcome> {
+        Ok(evaluate_compiled(&self.compiled_rules().await?, email))
+    }
+
+    /// Runs rules over an account's stored emails, performing every action
+    /// of each matching rule.
+    ///
+    /// With a rule ID only that rule runs; otherwise all rules run in order
+    /// with stop-processing honoured. Returns the emails that matched.
+    pub async fn run_retroactively(
+        &self,
+        account_id: &AccountId,
+        rule_id: Option<&str>,
+    ) -> FilterResult<Vec<EmailId>> {
+        let rules = match rule_id {
+            Some(id) => {
+                let rule = self
+                    .storage
+                    .get_rule(id)
+                    .await?
+                    .ok_or_else(|| FilterError::RuleNotFound(id.to_string()))?;
+                let rule = CompiledRule::new(rule).map_err(FilterError::InvalidRule)?;
+                Arc::new(vec![rule])
+            }
+            None => self.compiled_rules().await?,
+        };
+
+        let mut matched = Vec::new();
+        for email in self
+            .storage
+            .get_emails(account_id, RETROACTIVE_EMAILS)
+            .await?
+        {
+            let outcome = evaluate_compiled(&rules, &email);
+            if outcome.is_empty() {
+                continue;
+            }
+            self.perform(&email, &outcome.actions).await;
+            matched.push(email.id);
+        }
+        Ok(matched)
+    }
+
+    /// Returns the compiled rules, loading them if a rule has changed.
+    async fn compiled_rules(&self) -> FilterResult<Arc<Vec<CompiledRule>>> {
+        if let Some(rules) = self.compiled.lock().unwrap().clone() {
+            return Ok(rules);
+        }
+        let rules = Arc::new(compile_rules(self.storage.get_rules().await?));
+        *self.compiled.lock().unwrap() = Some(rules.clone());
+        Ok(rules)
+    }
+
+    /// Saves a rule and drops the compiled rules.
+    async fn save(&self, rule: &FilterRule) -> FilterResult<()> {
+        self.storage.save_rule(rule).await?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Drops the compiled rules so the next run reloads them.
+    fn invalidate(&self) {
+        *self.compiled.lock().unwrap() = None;
+    }
+
+    /// Performs actions through the effects, logging any that fail.
+    async fn perform(&self, email: &Email, actions: &[FilterAction]) {
+        for action in actions {
+            if let Err(e) = self.effects.perform(email, action).await {
+                tracing::warn!("Filter action {:?} failed for {}: {}", action, email.id, e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: FilterStorage> IncomingFilter for FilterService<S> {
+    async fn filter_incoming(&self, email: &mut Email) -> anyhow::Result<()> {
+        let outcome = self.evaluate(email).await?;
+        for action in &outcome.actions {
+            action.apply_to(email);
+        }
+
+        let deferred: Vec<FilterAction> = outcome
+            .actions
+            .into_iter()
+            .filter(FilterAction::needs_stored_email)
+            .collect();
+        if !deferred.is_empty() {
+            self.pending
+                .lock()
+                .unwrap()
+                .insert(email.id.clone(), deferred);
+        }
+        Ok(())
+    }
+
+    async fn after_store(&self, email: &Email) -> anyhow::Result<()> {
+        let deferred = self.pending.lock().unwrap().remove(&email.id);
+        if let Some(actions) = deferred {
+            self.perform(email, &actions).await;
+        }
+        Ok(())
+    }
+
+    async fn discard(&self, email: &Email) -> anyhow::Result<()> {
+        self.pending.lock().unwrap().remove(&email.id);
+        Ok(())
+    }
+}
+
+/// Adds a label unless the email already has it.
+fn add_label(email: &mut Email, label: LabelId) {
+    if !email.labels.contains(&label) {
+        email.labels.push(label);
+    }
+}
+
+/// Compiles a case-insensitive pattern.
+fn regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+/// Returns whether the pattern matches any of the values.
+fn is_match(re: Option<&Regex>, mut values: impl Iterator<Item = String>) -> bool {
+    re.is_some_and(|re| values.any(|v| re.is_match(&v)))
+}
+
+/// Approximate message size: headers, bodies and attachments.
+fn message_size(email: &Email) -> u64 {
+    let headers: usize = email
+        .headers
+        .iter()
+        .map(|h| h.name.len() + h.value.len() + 4)
+        .sum();
+    let bodies = email.body_text.as_ref().map_or(0, String::len)
+        + email.body_html.as_ref().map_or(0, String::len);
+    let attachments: u64 = email.attachments.iter().map(|a| a.size_bytes).sum();
+    (headers + bodies) as u64 + attachments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Address, Attachment, Header, MessageId, ThreadId};
+
+    struct MockStorage {
+        rules: Mutex<Vec<FilterRule>>,
+        emails: Vec<Email>,
+    }
+
+    impl MockStorage {
+        fn new() -> Self {
+            Self {
+                rules: Mutex::new(Vec::new()),
+                emails: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl FilterStorage for MockStorage {
+        async fn get_rules(&self) -> FilterResult<Vec<FilterRule>> {
+            Ok(self.rules.lock().unwrap().clone())
+        }
+
+        async fn get_rule(&self, id: &str) -> FilterResult<Option<FilterRule>> {
+            Ok(self
+                .rules
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|r| r.id == id)
+                .cloned())
+        }
+
+        async fn save_rule(&self, rule: &FilterRule) -> FilterResult<()> {
+            let mut rules = self.rules.lock().unwrap();
+            rules.retain(|r| r.id != rule.id);
+            rules.push(rule.clone());
+            Ok(())
+        }
+
+        async fn delete_rule(&self, id: &str) -> FilterResult<()> {
+            self.rules.lock().unwrap().retain(|r| r.id != id);
+            Ok(())
+        }
+
+        async fn get_emails(&self, account_id: &AccountId, limit: u32) -> FilterResult<Vec<Email>> {
+            Ok(self
+                .emails
+                .iter()
+                .filter(|e| e.account_id == *account_id)
+                .take(limit as usize)
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingEffects {
+        performed: Mutex<Vec<(EmailId, FilterAction)>>,
+    }
+
+    #[async_trait]
+    impl FilterEffects for RecordingEffects {
+        async fn perform(&self, email: &Email, action: &FilterAction) -> anyhow::Result<()> {
+            self.performed
+                .lock()
+                .unwrap()
+                .push((email.id.clone(), action.clone()));
+            Ok(())
+        }
+    }
+
+    fn ci_email(id: &str) -> Email {
+        Email {
+            id: EmailId::from(id),
+            account_id: AccountId::from("work"),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            message_id: MessageId::from(format!("<{}@ci.example.com>", id)),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::with_name("builds@ci.example.com", "CI"),
+            to: vec![Address::new("team@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("Build #412 failed on main".to_string()),
+            body_text: Some("The test job failed.".to_string()),
+            body_html: None,
+            snippet: "The test job failed.".to_string(),
+            date: Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![system_labels::inbox()],
+            attachments: vec![],
+            headers: vec![Header::new("List-Id", "Builds <builds.ci.example.com>")],
+        }
+    }
+
+    fn ci_rule() -> FilterRule {
+        FilterRule::new("CI")
+            .when(FilterCondition::ListId {
+                pattern: r"builds\.ci\.example\.com".to_string(),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("CI"),
+            })
+            .then(FilterAction::Archive)
+    }
+
+    #[test]
+    fn conditions_match_fields() {
+        let mut email = ci_email("1");
+        email.attachments.push(Attachment {
+            id: "a1".to_string(),
+            filename: "log.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            size_bytes: 20_000,
+            is_inline: false,
+        });
+
+        let holds = |c: FilterCondition| c.matches(&email);
+        assert!(holds(FilterCondition::From {
+            pattern: "^CI <".to_string()
+        }));
+        assert!(holds(FilterCondition::To {
+            pattern: "team@".to_string()
+        }));
+        assert!(!holds(FilterCondition::Cc {
+            pattern: ".".to_string()
+        }));
+        assert!(holds(FilterCondition::Subject {
+            pattern: r"build #\d+ failed".to_string()
+        }));
+        assert!(holds(FilterCondition::Body {
+            pattern: "test job".to_string()
+        }));
+        assert!(holds(FilterCondition::Header {
+            name: "list-id".to_string(),
+            pattern: "builds".to_string()
+        }));
+        assert!(holds(FilterCondition::HasAttachment));
+        assert!(holds(FilterCondition::LargerThan { bytes: 10_000 }));
+        assert!(!holds(FilterCondition::SmallerThan { bytes: 10_000 }));
+        assert!(holds(FilterCondition::Account {
+            account_id: AccountId::from("work")
+        }));
+    }
+
+    #[test]
+    fn validate_rejects_incomplete_rules() {
+        assert!(FilterRule::new("empty").validate().is_err());
+        assert!(ci_rule().validate().is_ok());
+
+        let bad_regex = FilterRule::new("bad")
+            .when(FilterCondition::Subject {
+                pattern: "(unclosed".to_string(),
+            })
+            .then(FilterAction::Star);
+        assert!(bad_regex.validate().is_err());
+
+        let bad_forward = FilterRule::new("fwd")
+            .when(FilterCondition::HasAttachment)
+            .then(FilterAction::Forward {
+                to: "nobody".to_string(),
+            });
+        assert!(bad_forward.validate().is_err());
+    }
+
+    #[test]
+    fn evaluate_honours_order_and_stop_processing() {
+        let mut first = ci_rule().stop_processing();
+        first.position = 0;
+        let mut second = FilterRule::new("Star failures")
+            .when(FilterCondition::Subject {
+                pattern: "failed".to_string(),
+            })
+            .then(FilterAction::Star);
+        second.position = 1;
+        let email = ci_email("1");
+
+        let outcome = evaluate_filters(&[second.clone(), first.clone()], &email);
+        assert_eq!(outcome.matched_rules, vec![first.id.clone()]);
+        assert!(!outcome.actions.contains(&FilterAction::Star));
+
+        first.stop_processing = false;
+        let outcome = evaluate_filters(&[second.clone(), first.clone()], &email);
+        assert_eq!(outcome.matched_rules, vec![first.id, second.id]);
+        assert_eq!(outcome.actions.last(), Some(&FilterAction::Star));
+    }
+
+    #[test]
+    fn any_mode_and_disabled_rules() {
+        let mut rule = FilterRule::new("either")
+            .when(FilterCondition::Subject {
+                pattern: "nothing like this".to_string(),
+            })
+            .when(FilterCondition::HasAttachment)
+            .then(FilterAction::Star);
+        let email = ci_email("1");
+        assert!(!rule.matches(&email));
+
+        rule = rule.match_any().when(FilterCondition::From {
+            pattern: "ci.example.com".to_string(),
+        });
+        assert!(rule.matches(&email));
+
+        rule.enabled = false;
+        assert!(!rule.matches(&email));
+    }
+
+    #[tokio::test]
+    async fn rules_are_compiled_once_until_changed() {
+        let service = FilterService::new(MockStorage::new(), Arc::new(RecordingEffects::default()));
+        let rule = service.add_rule(ci_rule()).await.unwrap();
+        let email = ci_email("1");
+
+        assert_eq!(
+            service.evaluate(&email).await.unwrap().matched_rules,
+            vec![rule.id.clone()]
+        );
+        let first = service.compiled_rules().await.unwrap();
+        assert!(Arc::ptr_eq(
+            &first,
+            &service.compiled_rules().await.unwrap()
+        ));
+
+        service.set_enabled(&rule.id, false).await.unwrap();
+        assert!(service.evaluate(&email).await.unwrap().is_empty());
+        assert!(!Arc::ptr_eq(
+            &first,
+            &service.compiled_rules().await.unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn invalid_patterns_are_rejected_on_save_and_skipped_on_load() {
+        let storage = MockStorage::new();
+        let bad = FilterRule::new("bad")
+            .when(FilterCondition::Subject {
+                pattern: "(unclosed".to_string(),
+            })
+            .then(FilterAction::Star);
+        storage.rules.lock().unwrap().push(bad.clone());
+        let service = FilterService::new(storage, Arc::new(RecordingEffects::default()));
+
+        assert!(matches!(
+            service.add_rule(bad.clone()).await,
+            Err(FilterError::InvalidRule(_))
+        ));
+        let good = service.add_rule(ci_rule()).await.unwrap();
+        assert_eq!(
+            service
+                .evaluate(&ci_email("1"))
+                .await
+                .unwrap()
+                .matched_rules,
+            vec![good.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn move_rule_renumbers_positions() {
+        let service = FilterService::new(MockStorage::new(), Arc::new(RecordingEffects::default()));
+        let a = service.add_rule(ci_rule()).await.unwrap();
+        let b = service.add_rule(ci_rule()).await.unwrap();
+        let c = service.add_rule(ci_rule()).await.unwrap();
+        assert_eq!((a.position, b.position, c.position), (0, 1, 2));
+
+        service.move_rule(&c.id, 0).await.unwrap();
+        let ids: Vec<String> = service
+            .get_rules()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec![c.id, a.id, b.id]);
+    }
+
+    #[tokio::test]
+    async fn incoming_mail_is_filtered_before_and_after_storage() {
+        let effects = Arc::new(RecordingEffects::default());
+        let service = FilterService::new(MockStorage::new(), effects.clone());
+        service
+            .add_rule(
+                ci_rule()
+                    .then(FilterAction::MarkRead)
+                    .then(FilterAction::AssignSmartView {
+                        view: SmartViewType::FollowUp,
+                    }),
+            )
+            .await
+            .unwrap();
+
+        let mut email = ci_email("1");
+        service.filter_incoming(&mut email).await.unwrap();
+        assert!(email.labels.contains(&LabelId::from("CI")));
+        assert!(!email.labels.contains(&system_labels::inbox()));
+        assert!(email.is_read);
+        assert!(effects.performed.lock().unwrap().is_empty());
+
+        service.after_store(&email).await.unwrap();
+        service.after_store(&email).await.unwrap();
+        assert_eq!(
+            *effects.performed.lock().unwrap(),
+            vec![(
+                email.id.clone(),
+                FilterAction::AssignSmartView {
+                    view: SmartViewType::FollowUp
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn discarded_mail_drops_deferred_actions() {
+        let effects = Arc::new(RecordingEffects::default());
+        let service = FilterService::new(MockStorage::new(), effects.clone());
+        service
+            .add_rule(ci_rule().then(FilterAction::Snooze { hours: 4 }))
+            .await
+            .unwrap();
+
+        let mut email = ci_email("1");
+        service.filter_incoming(&mut email).await.unwrap();
+        assert_eq!(service.pending.lock().unwrap().len(), 1);
+
+        service.discard(&email).await.unwrap();
+        assert!(service.pending.lock().unwrap().is_empty());
+        service.after_store(&email).await.unwrap();
+        assert!(effects.performed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn retroactive_run_performs_every_action() {
+        let mut storage = MockStorage::new();
+        let mut other = ci_email("2");
+        other.from = Address::new("friend@example.com");
+        other.headers.clear();
+        storage.emails = vec![ci_email("1"), other];
+        let effects = Arc::new(RecordingEffects::default());
+        let service = FilterService::new(storage, effects.clone());
+        let rule = service.add_rule(ci_rule()).await.unwrap();
+
+        let matched = service
+            .run_retroactively(&AccountId::from("work"), Some(&rule.id))
+            .await
+            .unwrap();
+
+        assert_eq!(matched, vec![EmailId::from("1")]);
+        let performed = effects.performed.lock().unwrap();
+        assert_eq!(performed.len(), 2);
+        assert_eq!(performed[1].1, FilterAction::Archive);
+    }
+}
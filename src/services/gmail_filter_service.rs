@@ -0,0 +1,920 @@
+//! Server-side rules for Gmail accounts.
+//!
+//! Gmail has no Sieve, so screener and filter rules are mirrored as Gmail
+//! filters instead. Blocked senders skip the inbox and get the Screened Out
+//! label, allowed senders are never sent to spam, and filter rules whose
+//! conditions Gmail can express become the equivalent filter. Rules Gmail
+//! can't express, such as regexes and glob patterns, stay local. Labels
+//! applied by filter rules are mapped to their Gmail label, which is
+//! created if the label only exists locally.
+//!
+//! Every filter created is recorded in a mapping table along with a
+//! fingerprint of what was sent, so edited rules are replaced and deleted
+//! rules are removed on the next sync. Filters the user made in Gmail
+//! itself are imported read-only for display and never changed.
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::domain::{AccountId, Label, LabelId, RuleField, RuleType, ScreenerAction, ScreenerRule};
+use crate::providers::email::{
+    GmailFilter, GmailFilterAction, GmailFilterCriteria, GmailProvider, ProviderError,
+};
+use crate::services::{FilterAction, FilterCondition, FilterRule, ViewType};
+
+/// Gmail's own label IDs, which filters can use as they are.
+const GMAIL_SYSTEM_LABELS: &[&str] = &["INBOX", "STARRED", "IMPORTANT", "UNREAD", "SPAM", "TRASH"];
+
+/// Errors that can occur while syncing Gmail filters.
+#[derive(Debug, Error)]
+pub enum GmailFilterError {
+    /// Storage error.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// Gmail API error.
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+}
+
+/// Result type for Gmail filter operations.
+pub type GmailFilterResult<T> = Result<T, GmailFilterError>;
+
+/// Links a local rule to the Gmail filter created for it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GmailFilterMapping {
+    /// Gmail account the filter lives in.
+    pub account_id: AccountId,
+    /// Screener or filter rule ID.
+    pub rule_id: String,
+    /// Gmail filter ID.
+    pub filter_id: String,
+    /// Fingerprint of the filter as it was sent.
+    pub fingerprint: String,
+    /// When the filter was created.
+    pub synced_at: DateTime<Utc>,
+}
+
+/// A filter made in Gmail itself, kept for display only.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedGmailFilter {
+    /// Gmail account the filter lives in.
+    pub account_id: AccountId,
+    /// The filter as Gmail returned it.
+    pub filter: GmailFilter,
+    /// When the filter was last seen in Gmail.
+    pub imported_at: DateTime<Utc>,
+}
+
+impl ImportedGmailFilter {
+    /// Returns the filter's conditions as a Gmail search query.
+    pub fn query(&self) -> String {
+        self.filter.criteria.to_query()
+    }
+}
+
+/// Counts of what a sync changed in Gmail.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GmailFilterSyncReport {
+    /// Filters created for new rules.
+    pub created: usize,
+    /// Filters replaced because their rule changed or was removed in Gmail.
+    pub updated: usize,
+    /// Filters deleted because their rule was deleted or can no longer be
+    /// expressed.
+    pub deleted: usize,
+    /// Rules whose filter was already up to date.
+    pub unchanged: usize,
+    /// IDs of rules that apply to the account but have no Gmail equivalent.
+    pub skipped: Vec<String>,
+    /// Gmail filters imported read-only.
+    pub imported: usize,
+}
+
+/// Storage trait for Gmail filter mappings and imported filters.
+#[async_trait]
+pub trait GmailFilterStorage: Send + Sync {
+    /// Gets the mappings for an account.
+    async fn get_mappings(
+        &self,
+        account_id: &AccountId,
+    ) -> GmailFilterResult<Vec<GmailFilterMapping>>;
+
+    /// Saves a mapping, replacing any mapping for the same rule.
+    async fn save_mapping(&self, mapping: &GmailFilterMapping) -> GmailFilterResult<()>;
+
+    /// Deletes the mapping for a rule.
+    async fn delete_mapping(&self, account_id: &AccountId, rule_id: &str) -> GmailFilterResult<()>;
+
+    /// Gets the imported filters for an account.
+    async fn get_imported_filters(
+        &self,
+        account_id: &AccountId,
+    ) -> GmailFilterResult<Vec<ImportedGmailFilter>>;
+
+    /// Replaces the imported filters for an account.
+    async fn replace_imported_filters(
+        &self,
+        account_id: &AccountId,
+        filters: &[GmailFilter],
+    ) -> GmailFilterResult<()>;
+}
+
+/// The parts of the Gmail API that filter sync uses.
+#[async_trait]
+pub trait GmailFilterApi: Send + Sync {
+    /// Lists the account's filters.
+    async fn list_filters(&self) -> Result<Vec<GmailFilter>, ProviderError>;
+
+    /// Creates a filter, returning it with its ID.
+    async fn create_filter(&self, filter: &GmailFilter) -> Result<GmailFilter, ProviderError>;
+
+    /// Deletes a filter.
+    async fn delete_filter(&self, filter_id: &str) -> Result<(), ProviderError>;
+
+    /// Returns the ID of the named label, creating it if needed.
+    async fn find_or_create_label(&self, name: &str) -> Result<String, ProviderError>;
+}
+
+#[async_trait]
+impl GmailFilterApi for GmailProvider {
+    async fn list_filters(&self) -> Result<Vec<GmailFilter>, ProviderError> {
+        GmailProvider::list_filters(self).await
+    }
+
+    async fn create_filter(&self, filter: &GmailFilter) -> Result<GmailFilter, ProviderError> {
+        GmailProvider::create_filter(self, filter).await
+    }
+
+    async fn delete_filter(&self, filter_id: &str) -> Result<(), ProviderError> {
+        GmailProvider::delete_filter(self, filter_id).await
+    }
+
+    async fn find_or_create_label(&self, name: &str) -> Result<String, ProviderError> {
+        GmailProvider::find_or_create_label(self, name).await
+    }
+}
+
+/// Returns the Gmail filter for a screener rule, or `None` if Gmail can't
+/// express it.
+///
+/// Rejections skip the inbox and add `screened_out_label`, approvals are
+/// never sent to spam. Review rules, regexes and glob patterns are left to
+/// the app.
+pub fn screener_rule_to_gmail(
+    rule: &ScreenerRule,
+    screened_out_label: &str,
+) -> Option<GmailFilter> {
+    let pattern = rule.pattern.trim();
+    if rule.validate().is_err() {
+        return None;
+    }
+
+    let mut criteria = GmailFilterCriteria::default();
+    match rule.rule_type {
+        // Gmail matches the domain as a word, which covers subdomains too
+        RuleType::DomainAllow | RuleType::DomainBlock => {
+            criteria.from = Some(pattern.trim_start_matches('@').to_lowercase());
+        }
+        RuleType::Pattern if !pattern.contains(['*', '?']) => match rule.field {
+            RuleField::Sender => criteria.from = Some(pattern.to_string()),
+            RuleField::Subject => criteria.subject = Some(pattern.to_string()),
+            RuleField::ListId => criteria.query = Some(format!("list:({})", pattern)),
+        },
+        RuleType::Pattern | RuleType::Regex => return None,
+    }
+
+    let action = match rule.action {
+        ScreenerAction::Reject => GmailFilterAction {
+            add_label_ids: vec![screened_out_label.to_string()],
+            remove_label_ids: vec!["INBOX".to_string()],
+            forward: None,
+        },
+        ScreenerAction::Approve => GmailFilterAction {
+            remove_label_ids: vec!["SPAM".to_string()],
+            ..Default::default()
+        },
+        ScreenerAction::Review => return None,
+    };
+
+    Some(GmailFilter {
+        id: None,
+        criteria,
+        action,
+    })
+}
+
+/// Returns the Gmail filter for a filter rule, or `None` if Gmail can't
+/// express it.
+///
+/// Gmail only ANDs conditions and has no regexes, so the rule must match
+/// all of its conditions (or have just one), every pattern must be plain
+/// text, and every action must have a Gmail equivalent. Rules limited to
+/// another account are never synced.
+///
+/// `label_ids` maps the labels the rule applies to Gmail label IDs. Gmail
+/// system labels such as `STARRED` need no entry; a rule applying any
+/// other unmapped label is left out.
+pub fn filter_rule_to_gmail(
+    rule: &FilterRule,
+    account_id: &AccountId,
+    label_ids: &HashMap<LabelId, String>,
+) -> Option<GmailFilter> {
+    if !rule.enabled || rule.validate().is_err() {
+        return None;
+    }
+    if !rule.match_all && rule.conditions.len() > 1 {
+        return None;
+    }
+
+    let mut criteria = GmailFilterCriteria::default();
+    let mut terms = Vec::new();
+    for condition in &rule.conditions {
+        match condition {
+            FilterCondition::From { pattern } => set_once(&mut criteria.from, literal(pattern)?)?,
+            FilterCondition::To { pattern } => set_once(&mut criteria.to, literal(pattern)?)?,
+            FilterCondition::Subject { pattern } => {
+                set_once(&mut criteria.subject, literal(pattern)?)?
+            }
+            FilterCondition::Cc { pattern } => terms.push(format!("cc:({})", literal(pattern)?)),
+            FilterCondition::Body { pattern } => terms.push(format!("\"{}\"", literal(pattern)?)),
+            FilterCondition::ListId { pattern } => {
+                terms.push(format!("list:({})", literal(pattern)?))
+            }
+            FilterCondition::LargerThan { bytes } => terms.push(format!("larger:{}", bytes)),
+            FilterCondition::SmallerThan { bytes } => terms.push(format!("smaller:{}", bytes)),
+            FilterCondition::HasAttachment => criteria.has_attachment = Some(true),
+            FilterCondition::Account { account_id: id } if id == account_id => {}
+            FilterCondition::Account { .. } | FilterCondition::Header { .. } => return None,
+        }
+    }
+    if !terms.is_empty() {
+        criteria.query = Some(terms.join(" "));
+    }
+
+    let mut action = GmailFilterAction::default();
+    for filter_action in &rule.actions {
+        match filter_action {
+            FilterAction::ApplyLabel { label } => {
+                action.add_label_ids.push(gmail_label_id(label, label_ids)?)
+            }
+            FilterAction::Archive => action.remove_label_ids.push("INBOX".to_string()),
+            FilterAction::Star => action.add_label_ids.push("STARRED".to_string()),
+            FilterAction::MarkRead => action.remove_label_ids.push("UNREAD".to_string()),
+            FilterAction::Forward { to } => set_once(&mut action.forward, to)?,
+            FilterAction::Snooze { .. } | FilterAction::AssignSmartView { .. } => return None,
+        }
+    }
+
+    Some(GmailFilter {
+        id: None,
+        criteria,
+        action,
+    })
+}
+
+/// Returns the Gmail ID of a label, or `None` if it hasn't been resolved.
+fn gmail_label_id(label: &LabelId, label_ids: &HashMap<LabelId, String>) -> Option<String> {
+    if GMAIL_SYSTEM_LABELS.contains(&label.0.as_str()) {
+        return Some(label.0.clone());
+    }
+    label_ids.get(label).cloned()
+}
+
+/// Returns the labels a rule applies that are not Gmail system labels.
+fn user_labels(rule: &FilterRule) -> impl Iterator<Item = &LabelId> {
+    rule.actions.iter().filter_map(|action| match action {
+        FilterAction::ApplyLabel { label } if !GMAIL_SYSTEM_LABELS.contains(&label.0.as_str()) => {
+            Some(label)
+        }
+        _ => None,
+    })
+}
+
+/// Returns the pattern if it has no regex syntax.
+///
+/// A `.` is allowed, since as a regex it also matches itself and
+/// addresses and domains are full of them.
+fn literal(pattern: &str) -> Option<&str> {
+    let pattern = pattern.trim();
+    let is_plain = !pattern.contains([
+        '\\', '^', '$', '*', '+', '?', '(', ')', '[', ']', '{', '}', '|',
+    ]);
+    is_plain.then_some(pattern)
+}
+
+/// Sets a criteria field that Gmail only allows once.
+fn set_once(field: &mut Option<String>, value: &str) -> Option<()> {
+    if field.is_some() {
+        return None;
+    }
+    *field = Some(value.to_string());
+    Some(())
+}
+
+/// Returns a stable fingerprint of a filter's criteria and action.
+fn fingerprint(filter: &GmailFilter) -> String {
+    serde_json::to_string(&(&filter.criteria, &filter.action)).unwrap_or_default()
+}
+
+/// Service for mirroring rules as Gmail filters.
+pub struct GmailFilterService<S: GmailFilterStorage> {
+    storage: S,
+    account_id: AccountId,
+    label_name: String,
+}
+
+impl<S: GmailFilterStorage> GmailFilterService<S> {
+    /// Creates a service for a Gmail account.
+    pub fn new(storage: S, account_id: AccountId) -> Self {
+        Self {
+            storage,
+            account_id,
+            label_name: ViewType::ScreenedOut.folder_name().to_string(),
+        }
+    }
+
+    /// Sets the name of the label given to screened-out mail.
+    pub fn with_label_name(mut self, name: impl Into<String>) -> Self {
+        self.label_name = name.into();
+        self
+    }
+
+    /// Returns the account ID.
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Gets the filters imported from Gmail at the last sync.
+    pub async fn imported_filters(&self) -> GmailFilterResult<Vec<ImportedGmailFilter>> {
+        self.storage.get_imported_filters(&self.account_id).await
+    }
+
+    /// Brings the account's Gmail filters in line with the rules.
+    ///
+    /// Creates filters for new rules, replaces filters whose rule changed
+    /// or that were deleted in Gmail, deletes filters whose rule is gone,
+    /// and imports every other Gmail filter read-only. `labels` are the
+    /// account's local labels, used to find the Gmail label for each label
+    /// a filter rule applies.
+    pub async fn sync(
+        &self,
+        api: &dyn GmailFilterApi,
+        screener_rules: &[ScreenerRule],
+        filter_rules: &[FilterRule],
+        labels: &[Label],
+    ) -> GmailFilterResult<GmailFilterSyncReport> {
+        let mut report = GmailFilterSyncReport::default();
+
+        let needs_label = screener_rules
+            .iter()
+            .any(|rule| rule.applies_to(&self.account_id) && rule.action == ScreenerAction::Reject);
+        let label_id = if needs_label {
+            api.find_or_create_label(&self.label_name).await?
+        } else {
+            String::new()
+        };
+
+        let mut desired = Vec::new();
+        for rule in screener_rules
+            .iter()
+            .filter(|rule| rule.applies_to(&self.account_id))
+        {
+            match screener_rule_to_gmail(rule, &label_id) {
+                Some(filter) => desired.push((rule.id.clone(), filter)),
+                None => report.skipped.push(rule.id.clone()),
+            }
+        }
+        let for_other_account = |rule: &FilterRule| {
+            rule.conditions.iter().any(|condition| {
+                matches!(condition, FilterCondition::Account { account_id } if account_id != &self.account_id)
+            })
+        };
+        let filter_rules: Vec<&FilterRule> = filter_rules
+            .iter()
+            .filter(|rule| rule.enabled && !for_other_account(rule))
+            .collect();
+        let label_ids = self.resolve_labels(api, &filter_rules, labels).await?;
+        for rule in filter_rules {
+            match filter_rule_to_gmail(rule, &self.account_id, &label_ids) {
+                Some(filter) => desired.push((rule.id.clone(), filter)),
+                None => report.skipped.push(rule.id.clone()),
+            }
+        }
+
+        let remote = api.list_filters().await?;
+        let remote_ids: HashSet<&str> = remote.iter().filter_map(|f| f.id.as_deref()).collect();
+        let mut mappings: HashMap<String, GmailFilterMapping> = self
+            .storage
+            .get_mappings(&self.account_id)
+            .await?
+            .into_iter()
+            .map(|mapping| (mapping.rule_id.clone(), mapping))
+            .collect();
+
+        // Every filter the mappings point at, including ones deleted below,
+        // so they aren't mistaken for the user's own filters
+        let mut owned = HashSet::new();
+        for (rule_id, filter) in desired {
+            let print = fingerprint(&filter);
+            let existing = mappings.remove(&rule_id);
+
+            if let Some(mapping) = &existing {
+                if mapping.fingerprint == print && remote_ids.contains(mapping.filter_id.as_str()) {
+                    owned.insert(mapping.filter_id.clone());
+                    report.unchanged += 1;
+                    continue;
+                }
+                self.delete_remote(api, &mapping.filter_id).await?;
+                owned.insert(mapping.filter_id.clone());
+            }
+
+            let created = api.create_filter(&filter).await?;
+            let filter_id = created.id.unwrap_or_default();
+            self.storage
+                .save_mapping(&GmailFilterMapping {
+                    account_id: self.account_id.clone(),
+                    rule_id,
+                    filter_id: filter_id.clone(),
+                    fingerprint: print,
+                    synced_at: Utc::now(),
+                })
+                .await?;
+            owned.insert(filter_id);
+
+            if existing.is_some() {
+                report.updated += 1;
+            } else {
+                report.created += 1;
+            }
+        }
+
+        // Whatever is left belongs to rules that are gone or no longer syncable
+        for mapping in mappings.into_values() {
+            self.delete_remote(api, &mapping.filter_id).await?;
+            self.storage
+                .delete_mapping(&self.account_id, &mapping.rule_id)
+                .await?;
+            owned.insert(mapping.filter_id);
+            report.deleted += 1;
+        }
+
+        let imported: Vec<GmailFilter> = remote
+            .into_iter()
+            .filter(|f| f.id.as_ref().is_some_and(|id| !owned.contains(id)))
+            .collect();
+        report.imported = imported.len();
+        self.storage
+            .replace_imported_filters(&self.account_id, &imported)
+            .await?;
+
+        Ok(report)
+    }
+
+    /// Finds the Gmail label ID of each label the rules apply.
+    ///
+    /// Labels synced from Gmail already know their ID; local-only labels are
+    /// created in Gmail by name. Labels of rules Gmail can't express anyway
+    /// are not created, and labels not found locally are left unmapped, so
+    /// their rules are skipped.
+    async fn resolve_labels(
+        &self,
+        api: &dyn GmailFilterApi,
+        rules: &[&FilterRule],
+        labels: &[Label],
+    ) -> GmailFilterResult<HashMap<LabelId, String>> {
+        let mut label_ids = HashMap::new();
+        for rule in rules {
+            let placeholders = user_labels(rule)
+                .map(|label| (label.clone(), String::new()))
+                .collect();
+            if filter_rule_to_gmail(rule, &self.account_id, &placeholders).is_none() {
+                continue;
+            }
+
+            for id in user_labels(rule) {
+                if label_ids.contains_key(id) {
+                    continue;
+                }
+                let Some(label) = labels
+                    .iter()
+                    .find(|label| &label.id == id && label.account_id == self.account_id)
+                else {
+                    continue;
+                };
+                let gmail_id = match &label.provider_id {
+                    Some(provider_id) => provider_id.clone(),
+                    None => api.find_or_create_label(&label.name).await?,
+                };
+                label_ids.insert(id.clone(), gmail_id);
+            }
+        }
+        Ok(label_ids)
+    }
+
+    /// Deletes a filter, ignoring filters already deleted in Gmail.
+    async fn delete_remote(
+        &self,
+        api: &dyn GmailFilterApi,
+        filter_id: &str,
+    ) -> GmailFilterResult<()> {
+        match api.delete_filter(filter_id).await {
+            Ok(()) | Err(ProviderError::NotFound(_)) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct MockStorage {
+        mappings: Mutex<Vec<GmailFilterMapping>>,
+        imported: Mutex<Vec<ImportedGmailFilter>>,
+    }
+
+    impl MockStorage {
+        fn new() -> Self {
+            Self {
+                mappings: Mutex::new(Vec::new()),
+                imported: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl GmailFilterStorage for MockStorage {
+        async fn get_mappings(
+            &self,
+            account_id: &AccountId,
+        ) -> GmailFilterResult<Vec<GmailFilterMapping>> {
+            let mappings = self.mappings.lock().unwrap();
+            Ok(mappings
+                .iter()
+                .filter(|m| &m.account_id == account_id)
+                .cloned()
+                .collect())
+        }
+
+        async fn save_mapping(&self, mapping: &GmailFilterMapping) -> GmailFilterResult<()> {
+            let mut mappings = self.mappings.lock().unwrap();
+            mappings
+                .retain(|m| !(m.account_id == mapping.account_id && m.rule_id == mapping.rule_id));
+            mappings.push(mapping.clone());
+            Ok(())
+        }
+
+        async fn delete_mapping(
+            &self,
+            account_id: &AccountId,
+            rule_id: &str,
+        ) -> GmailFilterResult<()> {
+            let mut mappings = self.mappings.lock().unwrap();
+            mappings.retain(|m| !(&m.account_id == account_id && m.rule_id == rule_id));
+            Ok(())
+        }
+
+        async fn get_imported_filters(
+            &self,
+            account_id: &AccountId,
+        ) -> GmailFilterResult<Vec<ImportedGmailFilter>> {
+            let imported = self.imported.lock().unwrap();
+            Ok(imported
+                .iter()
+                .filter(|f| &f.account_id == account_id)
+                .cloned()
+                .collect())
+        }
+
+        async fn replace_imported_filters(
+            &self,
+            account_id: &AccountId,
+            filters: &[GmailFilter],
+        ) -> GmailFilterResult<()> {
+            let mut imported = self.imported.lock().unwrap();
+            imported.retain(|f| &f.account_id != account_id);
+            imported.extend(filters.iter().map(|filter| ImportedGmailFilter {
+                account_id: account_id.clone(),
+                filter: filter.clone(),
+                imported_at: Utc::now(),
+            }));
+            Ok(())
+        }
+    }
+
+    struct MockApi {
+        filters: Mutex<Vec<GmailFilter>>,
+        next_id: Mutex<u32>,
+        labels_created: Mutex<Vec<String>>,
+    }
+
+    impl MockApi {
+        fn new(filters: Vec<GmailFilter>) -> Self {
+            Self {
+                filters: Mutex::new(filters),
+                next_id: Mutex::new(0),
+                labels_created: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn ids(&self) -> Vec<String> {
+            let filters = self.filters.lock().unwrap();
+            filters.iter().filter_map(|f| f.id.clone()).collect()
+        }
+    }
+
+    #[async_trait]
+    impl GmailFilterApi for MockApi {
+        async fn list_filters(&self) -> Result<Vec<GmailFilter>, ProviderError> {
+            Ok(self.filters.lock().unwrap().clone())
+        }
+
+        async fn create_filter(&self, filter: &GmailFilter) -> Result<GmailFilter, ProviderError> {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            let created = GmailFilter {
+                id: Some(format!("f{}", next_id)),
+                ..filter.clone()
+            };
+            self.filters.lock().unwrap().push(created.clone());
+            Ok(created)
+        }
+
+        async fn delete_filter(&self, filter_id: &str) -> Result<(), ProviderError> {
+            let mut filters = self.filters.lock().unwrap();
+            let before = filters.len();
+            filters.retain(|f| f.id.as_deref() != Some(filter_id));
+            if filters.len() == before {
+                return Err(ProviderError::NotFound(filter_id.to_string()));
+            }
+            Ok(())
+        }
+
+        async fn find_or_create_label(&self, name: &str) -> Result<String, ProviderError> {
+            if name == ViewType::ScreenedOut.folder_name() {
+                return Ok("Label_7".to_string());
+            }
+            let mut created = self.labels_created.lock().unwrap();
+            created.push(name.to_string());
+            Ok(format!("Label_{}", 100 + created.len()))
+        }
+    }
+
+    fn account() -> AccountId {
+        AccountId::from("gmail")
+    }
+
+    fn label(id: &str, name: &str, provider_id: Option<&str>) -> Label {
+        Label {
+            id: LabelId::from(id),
+            account_id: account(),
+            name: name.to_string(),
+            color: None,
+            is_system: false,
+            provider_id: provider_id.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn screener_rules_map_to_gmail_filters() {
+        let block = ScreenerRule::new(
+            RuleType::DomainBlock,
+            "@Spam.example",
+            ScreenerAction::Reject,
+        );
+        let filter = screener_rule_to_gmail(&block, "Label_7").unwrap();
+        assert_eq!(filter.criteria.from.as_deref(), Some("spam.example"));
+        assert_eq!(filter.action.add_label_ids, vec!["Label_7"]);
+        assert_eq!(filter.action.remove_label_ids, vec!["INBOX"]);
+
+        let allow = ScreenerRule::new(
+            RuleType::DomainAllow,
+            "work.example",
+            ScreenerAction::Approve,
+        );
+        let filter = screener_rule_to_gmail(&allow, "Label_7").unwrap();
+        assert_eq!(filter.action.remove_label_ids, vec!["SPAM"]);
+        assert!(filter.action.add_label_ids.is_empty());
+
+        let list = ScreenerRule::new(RuleType::Pattern, "builds", ScreenerAction::Reject)
+            .on_field(RuleField::ListId);
+        let filter = screener_rule_to_gmail(&list, "Label_7").unwrap();
+        assert_eq!(filter.criteria.query.as_deref(), Some("list:(builds)"));
+
+        let glob = ScreenerRule::new(RuleType::Pattern, "*@news.*", ScreenerAction::Reject);
+        assert!(screener_rule_to_gmail(&glob, "Label_7").is_none());
+        let regex = ScreenerRule::new(RuleType::Regex, "^promo", ScreenerAction::Reject);
+        assert!(screener_rule_to_gmail(&regex, "Label_7").is_none());
+        let review = ScreenerRule::new(RuleType::DomainBlock, "x.example", ScreenerAction::Review);
+        assert!(screener_rule_to_gmail(&review, "Label_7").is_none());
+    }
+
+    #[test]
+    fn filter_rules_map_when_gmail_can_express_them() {
+        let rule = FilterRule::new("Receipts")
+            .when(FilterCondition::From {
+                pattern: "shop.example".to_string(),
+            })
+            .when(FilterCondition::LargerThan { bytes: 1000 })
+            .when(FilterCondition::HasAttachment)
+            .when(FilterCondition::Account {
+                account_id: account(),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("label-receipts"),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("IMPORTANT"),
+            })
+            .then(FilterAction::Archive)
+            .then(FilterAction::MarkRead);
+        let label_ids = HashMap::from([(LabelId::from("label-receipts"), "Label_3".to_string())]);
+        let filter = filter_rule_to_gmail(&rule, &account(), &label_ids).unwrap();
+        assert_eq!(filter.criteria.from.as_deref(), Some("shop.example"));
+        assert_eq!(filter.criteria.query.as_deref(), Some("larger:1000"));
+        assert_eq!(filter.criteria.has_attachment, Some(true));
+        assert_eq!(filter.action.add_label_ids, vec!["Label_3", "IMPORTANT"]);
+        assert_eq!(filter.action.remove_label_ids, vec!["INBOX", "UNREAD"]);
+
+        assert!(filter_rule_to_gmail(&rule, &AccountId::from("other"), &label_ids).is_none());
+        // A user label without a Gmail ID is never sent as one
+        assert!(filter_rule_to_gmail(&rule, &account(), &HashMap::new()).is_none());
+
+        let regex = FilterRule::new("Regex")
+            .when(FilterCondition::Subject {
+                pattern: "^invoice".to_string(),
+            })
+            .then(FilterAction::Star);
+        assert!(filter_rule_to_gmail(&regex, &account(), &HashMap::new()).is_none());
+
+        let snooze = FilterRule::new("Later")
+            .when(FilterCondition::Subject {
+                pattern: "digest".to_string(),
+            })
+            .then(FilterAction::Snooze { hours: 4 });
+        assert!(filter_rule_to_gmail(&snooze, &account(), &HashMap::new()).is_none());
+
+        let any = FilterRule::new("Any")
+            .when(FilterCondition::To {
+                pattern: "a".to_string(),
+            })
+            .when(FilterCondition::To {
+                pattern: "b".to_string(),
+            })
+            .then(FilterAction::Star)
+            .match_any();
+        assert!(filter_rule_to_gmail(&any, &account(), &HashMap::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn sync_creates_updates_and_deletes_filters() {
+        let service = GmailFilterService::new(MockStorage::new(), account());
+        let api = MockApi::new(Vec::new());
+
+        let mut block = ScreenerRule::new(
+            RuleType::DomainBlock,
+            "spam.example",
+            ScreenerAction::Reject,
+        );
+        let allow = ScreenerRule::new(
+            RuleType::DomainAllow,
+            "work.example",
+            ScreenerAction::Approve,
+        );
+        let other = ScreenerRule::new(RuleType::DomainBlock, "x.example", ScreenerAction::Reject)
+            .for_account(AccountId::from("other"));
+        let regex = ScreenerRule::new(RuleType::Regex, "^promo", ScreenerAction::Reject);
+
+        let rules = vec![block.clone(), allow.clone(), other, regex.clone()];
+        let report = service.sync(&api, &rules, &[], &[]).await.unwrap();
+        assert_eq!(report.created, 2);
+        assert_eq!(report.skipped, vec![regex.id.clone()]);
+        assert_eq!(api.ids().len(), 2);
+
+        let report = service.sync(&api, &rules, &[], &[]).await.unwrap();
+        assert_eq!(report.unchanged, 2);
+        assert_eq!(report.created + report.updated + report.deleted, 0);
+
+        block.pattern = "spam.example.org".to_string();
+        let report = service
+            .sync(&api, &[block.clone(), allow.clone()], &[], &[])
+            .await
+            .unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.imported, 0);
+        assert!(service.imported_filters().await.unwrap().is_empty());
+        let filters = api.filters.lock().unwrap().clone();
+        assert!(filters
+            .iter()
+            .any(|f| f.criteria.from.as_deref() == Some("spam.example.org")));
+        assert!(!filters
+            .iter()
+            .any(|f| f.criteria.from.as_deref() == Some("spam.example")));
+
+        let report = service.sync(&api, &[allow], &[], &[]).await.unwrap();
+        assert_eq!(report.deleted, 1);
+        assert_eq!(report.imported, 0);
+        assert!(service.imported_filters().await.unwrap().is_empty());
+        assert_eq!(api.ids().len(), 1);
+        assert_eq!(service.storage.mappings.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_recreates_missing_filters_and_imports_the_rest() {
+        let service = GmailFilterService::new(MockStorage::new(), account());
+        let own = GmailFilter {
+            id: Some("user-1".to_string()),
+            criteria: GmailFilterCriteria {
+                from: Some("boss@work.example".to_string()),
+                ..Default::default()
+            },
+            action: GmailFilterAction {
+                add_label_ids: vec!["STARRED".to_string()],
+                ..Default::default()
+            },
+        };
+        let api = MockApi::new(vec![own.clone()]);
+        let block = ScreenerRule::new(
+            RuleType::DomainBlock,
+            "spam.example",
+            ScreenerAction::Reject,
+        );
+
+        let report = service
+            .sync(&api, std::slice::from_ref(&block), &[], &[])
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 1);
+        let imported = service.imported_filters().await.unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].filter, own);
+        assert_eq!(imported[0].query(), "from:(boss@work.example)");
+
+        // The user deletes our filter in Gmail
+        let mapped = service.storage.mappings.lock().unwrap()[0]
+            .filter_id
+            .clone();
+        api.delete_filter(&mapped).await.unwrap();
+
+        let report = service.sync(&api, &[block], &[], &[]).await.unwrap();
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.imported, 1);
+        assert!(api.ids().contains(&"user-1".to_string()));
+        assert_eq!(api.ids().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sync_maps_applied_labels_to_gmail_labels() {
+        let service = GmailFilterService::new(MockStorage::new(), account());
+        let api = MockApi::new(Vec::new());
+        let labels = vec![
+            label("Label_3", "Receipts", Some("Label_3")),
+            label("label-travel", "Travel", None),
+            label("label-unused", "Unused", None),
+        ];
+
+        let apply = |name: &str, label: &str| {
+            FilterRule::new(name)
+                .when(FilterCondition::From {
+                    pattern: format!("{}.example", name),
+                })
+                .then(FilterAction::ApplyLabel {
+                    label: LabelId::from(label),
+                })
+        };
+        let receipts = apply("shop", "Label_3");
+        let travel = apply("airline", "label-travel");
+        let hotel = apply("hotel", "label-travel");
+        let gone = apply("old", "label-gone");
+        let regex = FilterRule::new("Regex")
+            .when(FilterCondition::Subject {
+                pattern: "^unused".to_string(),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("label-unused"),
+            });
+
+        let rules = vec![receipts, travel, hotel, gone.clone(), regex.clone()];
+        let report = service.sync(&api, &[], &rules, &labels).await.unwrap();
+        assert_eq!(report.created, 3);
+        assert_eq!(report.skipped, vec![gone.id, regex.id]);
+
+        // Only the local-only label is created, once, and by name
+        assert_eq!(*api.labels_created.lock().unwrap(), vec!["Travel"]);
+        let filters = api.filters.lock().unwrap();
+        let added: Vec<_> = filters
+            .iter()
+            .map(|f| f.action.add_label_ids.clone())
+            .collect();
+        assert_eq!(
+            added,
+            vec![vec!["Label_3"], vec!["Label_101"], vec!["Label_101"]]
+        );
+    }
+}
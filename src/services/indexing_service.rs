@@ -0,0 +1,919 @@
+//! Background embedding indexer.
+//!
+//! The [`IndexingService`] keeps the semantic search index in step with
+//! synced mail. It listens for completed syncs, records new and changed
+//! emails in a persistent job queue, and embeds them in batches on the
+//! blocking thread pool. Finished embeddings are stored so that a restart
+//! restores the index instead of re-embedding the mailbox.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{broadcast, Notify, RwLock};
+
+use crate::domain::{AccountId, Email, EmailId};
+use crate::embedding::{EmbeddingEngine, PassageEmbedding};
+use crate::services::SyncEvent;
+
+/// Errors that can occur while indexing.
+#[derive(Debug, Error)]
+pub enum IndexingError {
+    /// Storage error.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// Embedding error.
+    #[error("embedding error: {0}")]
+    Embedding(String),
+}
+
+/// Result type for indexing operations.
+pub type IndexingResult<T> = Result<T, IndexingError>;
+
+/// Progress of the indexing queue, suitable for a status bar.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexingProgress {
+    /// Emails waiting to be embedded.
+    pub pending: usize,
+    /// Emails currently being embedded.
+    pub in_progress: usize,
+    /// Emails embedded successfully.
+    pub completed: usize,
+    /// Emails that could not be embedded.
+    pub failed: usize,
+    /// Whether the worker is pausing between batches to stay under
+    /// [`IndexingSettings::max_cpu_fraction`].
+    #[serde(default)]
+    pub throttled: bool,
+}
+
+impl IndexingProgress {
+    /// Returns the total number of queued emails.
+    pub fn total(&self) -> usize {
+        self.pending + self.in_progress + self.completed + self.failed
+    }
+
+    /// Returns the fraction of queued emails that are finished, from 0.0 to 1.0.
+    pub fn fraction_complete(&self) -> f32 {
+        let total = self.total();
+        if total == 0 {
+            1.0
+        } else {
+            (self.completed + self.failed) as f32 / total as f32
+        }
+    }
+
+    /// Returns true if there is nothing left to embed.
+    pub fn is_idle(&self) -> bool {
+        self.pending == 0 && self.in_progress == 0
+    }
+}
+
+/// Settings for background indexing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexingSettings {
+    /// Enable background indexing.
+    pub enabled: bool,
+    /// Number of emails embedded per batch.
+    pub batch_size: usize,
+    /// Share of one CPU core the indexer may use, from 0.0 to 1.0.
+    ///
+    /// After each batch the worker sleeps long enough to stay under this
+    /// share of wall-clock time.
+    pub max_cpu_fraction: f32,
+    /// Attempts per email before it is marked as failed.
+    pub max_attempts: u32,
+    /// How often an idle worker re-checks the queue.
+    pub idle_interval: Duration,
+}
+
+impl Default for IndexingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            batch_size: 32,
+            max_cpu_fraction: 0.5,
+            max_attempts: 3,
+            idle_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl IndexingSettings {
+    /// Returns how long to pause after a batch that took `elapsed`.
+    pub fn throttle_delay(&self, elapsed: Duration) -> Duration {
+        let fraction = f64::from(self.max_cpu_fraction.clamp(0.05, 1.0));
+        elapsed.mul_f64((1.0 - fraction) / fraction)
+    }
+}
+
+/// Storage trait for the indexing queue and stored embeddings.
+#[async_trait]
+pub trait IndexingStorage: Send + Sync {
+    /// Queues emails for embedding, resetting any that were already queued.
+    async fn enqueue(&self, account_id: &AccountId, email_ids: &[EmailId])
+        -> IndexingResult<usize>;
+
+    /// Claims up to `limit` pending emails for embedding.
+    async fn claim_batch(&self, limit: usize) -> IndexingResult<Vec<EmailId>>;
+
+    /// Stores an email's passage embeddings and marks it as done.
+    async fn complete(
+        &self,
+        email_id: &EmailId,
+        passages: &[PassageEmbedding],
+    ) -> IndexingResult<()>;
+
+    /// Records a failed attempt, giving up after `max_attempts`.
+    async fn fail(&self, email_id: &EmailId, error: &str, max_attempts: u32) -> IndexingResult<()>;
+
+    /// Returns claimed but unfinished emails to the queue.
+    async fn requeue_in_progress(&self) -> IndexingResult<usize>;
+
+    /// Removes queued jobs and stored embeddings for the given emails.
+    async fn remove(&self, email_ids: &[EmailId]) -> IndexingResult<()>;
+
+    /// Gets an email by ID.
+    async fn get_email(&self, email_id: &EmailId) -> IndexingResult<Option<Email>>;
+
+    /// Loads all stored passage embeddings, grouped by email.
+    async fn load_embeddings(&self) -> IndexingResult<Vec<(EmailId, Vec<PassageEmbedding>)>>;
+
+    /// Counts queued emails by state.
+    async fn progress(&self) -> IndexingResult<IndexingProgress>;
+}
+
+/// Vector index that the indexer writes to.
+///
+/// Implemented for a shared [`EmbeddingEngine`]; embedding runs on the
+/// blocking thread pool, so implementations may be CPU-heavy.
+pub trait EmailIndexer: Send + Sync + 'static {
+    /// Embeds an email's passages and adds them to the index.
+    fn index(&self, email: &Email) -> anyhow::Result<Vec<PassageEmbedding>>;
+
+    /// Adds previously computed passage embeddings to the index.
+    fn restore(&self, email_id: &EmailId, passages: Vec<PassageEmbedding>) -> anyhow::Result<()>;
+
+    /// Removes an email from the index.
+    fn remove(&self, email_id: &EmailId);
+}
+
+impl EmailIndexer for std::sync::RwLock<EmbeddingEngine> {
+    fn index(&self, email: &Email) -> anyhow::Result<Vec<PassageEmbedding>> {
+        let passages = self
+            .read()
+            .map_err(|_| anyhow::anyhow!("embedding engine lock poisoned"))?
+            .embed_passages(email)?;
+        self.restore(&email.id, passages.clone())?;
+        Ok(passages)
+    }
+
+    fn restore(&self, email_id: &EmailId, passages: Vec<PassageEmbedding>) -> anyhow::Result<()> {
+        self.write()
+            .map_err(|_| anyhow::anyhow!("embedding engine lock poisoned"))?
+            .vector_store_mut()
+            .insert_passages(email_id, passages)
+    }
+
+    fn remove(&self, email_id: &EmailId) {
+        if let Ok(mut engine) = self.write() {
+            engine.vector_store_mut().remove(email_id);
+        }
+    }
+}
+
+/// Background service that embeds synced mail.
+///
+/// # Example
+///
+/// ```ignore
+/// let indexer = Arc::new(IndexingService::new(storage, engine, IndexingSettings::default()));
+/// indexer.resume().await?;
+/// Arc::clone(&indexer).start(sync_service.subscribe());
+///
+/// // Status bar
+/// let mut progress = indexer.subscribe();
+/// ```
+pub struct IndexingService<S: IndexingStorage, I: EmailIndexer> {
+    /// Storage layer.
+    storage: Arc<S>,
+    /// Vector index receiving embeddings.
+    indexer: Arc<I>,
+    /// Indexing settings.
+    settings: RwLock<IndexingSettings>,
+    /// Flag to stop the background worker.
+    stop_flag: AtomicBool,
+    /// Wakes the worker when new jobs are queued.
+    wake: Notify,
+    /// Progress sender for status updates.
+    progress_sender: broadcast::Sender<IndexingProgress>,
+}
+
+impl<S: IndexingStorage + 'static, I: EmailIndexer> IndexingService<S, I> {
+    /// Creates a new IndexingService.
+    pub fn new(storage: Arc<S>, indexer: Arc<I>, settings: IndexingSettings) -> Self {
+        let (progress_sender, _) = broadcast::channel(16);
+        Self {
+            storage,
+            indexer,
+            settings: RwLock::new(settings),
+            stop_flag: AtomicBool::new(true),
+            wake: Notify::new(),
+            progress_sender,
+        }
+    }
+
+    /// Updates indexing settings.
+    pub async fn update_settings(&self, settings: IndexingSettings) {
+        *self.settings.write().await = settings;
+        self.wake.notify_one();
+    }
+
+    /// Subscribes to progress updates.
+    pub fn subscribe(&self) -> broadcast::Receiver<IndexingProgress> {
+        self.progress_sender.subscribe()
+    }
+
+    /// Returns the current queue progress.
+    pub async fn progress(&self) -> IndexingResult<IndexingProgress> {
+        self.storage.progress().await
+    }
+
+    /// Prepares the indexer after a restart.
+    ///
+    /// Loads stored embeddings into the vector index and returns any jobs
+    /// interrupted mid-batch to the queue. Returns the number of restored
+    /// embeddings.
+    pub async fn resume(&self) -> IndexingResult<usize> {
+        let requeued = self.storage.requeue_in_progress().await?;
+        if requeued > 0 {
+            tracing::info!(requeued, "Requeued interrupted embedding jobs");
+        }
+
+        let stored = self.storage.load_embeddings().await?;
+        let count = stored.len();
+        let indexer = Arc::clone(&self.indexer);
+        tokio::task::spawn_blocking(move || {
+            for (email_id, passages) in stored {
+                indexer.restore(&email_id, passages)?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await
+        .map_err(|e| IndexingError::Embedding(e.to_string()))?
+        .map_err(|e| IndexingError::Embedding(e.to_string()))?;
+
+        self.publish_progress(false).await;
+        Ok(count)
+    }
+
+    /// Queues emails for embedding and wakes the worker.
+    pub async fn enqueue(
+        &self,
+        account_id: &AccountId,
+        email_ids: &[EmailId],
+    ) -> IndexingResult<usize> {
+        if email_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let queued = self.storage.enqueue(account_id, email_ids).await?;
+        self.wake.notify_one();
+        self.publish_progress(false).await;
+        Ok(queued)
+    }
+
+    /// Removes emails from the queue and the vector index.
+    pub async fn remove(&self, email_ids: &[EmailId]) -> IndexingResult<()> {
+        if email_ids.is_empty() {
+            return Ok(());
+        }
+
+        self.storage.remove(email_ids).await?;
+        for email_id in email_ids {
+            self.indexer.remove(email_id);
+        }
+        self.publish_progress(false).await;
+        Ok(())
+    }
+
+    /// Applies a sync event to the queue.
+    ///
+    /// Completed syncs queue their new and changed emails and drop deleted
+    /// ones from the index. Other events are ignored.
+    pub async fn handle_sync_event(&self, event: &SyncEvent) -> IndexingResult<()> {
+        if let SyncEvent::Completed(account_id, result) = event {
+            self.remove(&result.deleted_email_ids).await?;
+            self.enqueue(account_id, &result.changed_email_ids).await?;
+        }
+        Ok(())
+    }
+
+    /// Embeds one batch of queued emails.
+    ///
+    /// Returns the number of emails claimed, or 0 if the queue is empty.
+    pub async fn process_batch(&self) -> IndexingResult<usize> {
+        let (batch_size, max_attempts) = {
+            let settings = self.settings.read().await;
+            (settings.batch_size.max(1), settings.max_attempts)
+        };
+
+        let email_ids = self.storage.claim_batch(batch_size).await?;
+        if email_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut emails = Vec::with_capacity(email_ids.len());
+        let mut missing = Vec::new();
+        for email_id in &email_ids {
+            match self.storage.get_email(email_id).await? {
+                Some(email) => emails.push(email),
+                None => missing.push(email_id.clone()),
+            }
+        }
+
+        // Emails deleted since they were queued have nothing to embed.
+        if !missing.is_empty() {
+            self.storage.remove(&missing).await?;
+        }
+
+        let indexer = Arc::clone(&self.indexer);
+        let results = tokio::task::spawn_blocking(move || {
+            emails
+                .iter()
+                .map(|email| (email.id.clone(), indexer.index(email)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| IndexingError::Embedding(e.to_string()))?;
+
+        for (email_id, result) in results {
+            match result {
+                Ok(passages) => self.storage.complete(&email_id, &passages).await?,
+                Err(e) => {
+                    tracing::warn!(email_id = %email_id, error = %e, "Failed to embed email");
+                    self.storage
+                        .fail(&email_id, &e.to_string(), max_attempts)
+                        .await?;
+                }
+            }
+        }
+
+        self.publish_progress(false).await;
+        Ok(email_ids.len())
+    }
+
+    /// Embeds queued emails until the queue is empty.
+    ///
+    /// Returns the number of emails processed. Does not throttle.
+    pub async fn process_all(&self) -> IndexingResult<usize> {
+        let mut total = 0;
+        loop {
+            let processed = self.process_batch().await?;
+            if processed == 0 {
+                return Ok(total);
+            }
+            total += processed;
+        }
+    }
+
+    /// Starts background indexing.
+    ///
+    /// Spawns one task that queues emails from sync events and one worker
+    /// that embeds them, pausing between batches to respect
+    /// [`IndexingSettings::max_cpu_fraction`]. Call
+    /// [`stop`](Self::stop) to stop both.
+    pub fn start(self: Arc<Self>, mut sync_events: broadcast::Receiver<SyncEvent>) {
+        self.stop_flag.store(false, Ordering::SeqCst);
+
+        let listener = Arc::clone(&self);
+        tokio::spawn(async move {
+            loop {
+                if listener.stop_flag.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                match sync_events.recv().await {
+                    Ok(event) => {
+                        if let Err(e) = listener.handle_sync_event(&event).await {
+                            tracing::warn!(error = %e, "Failed to queue emails for indexing");
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Indexer missed sync events");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let worker = self;
+        tokio::spawn(async move {
+            loop {
+                if worker.stop_flag.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let settings = worker.settings.read().await.clone();
+                if !settings.enabled {
+                    worker.wait(settings.idle_interval).await;
+                    continue;
+                }
+
+                let started = Instant::now();
+                match worker.process_batch().await {
+                    Ok(0) => worker.wait(settings.idle_interval).await,
+                    Ok(_) => {
+                        let delay = settings.throttle_delay(started.elapsed());
+                        if !delay.is_zero() {
+                            worker.publish_progress(true).await;
+                            tokio::time::sleep(delay).await;
+                            worker.publish_progress(false).await;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Embedding batch failed");
+                        worker.wait(settings.idle_interval).await;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Stops background indexing.
+    ///
+    /// Jobs claimed by an in-flight batch finish first; anything left
+    /// running is requeued by [`resume`](Self::resume) on the next start.
+    pub fn stop(&self) {
+        self.stop_flag.store(true, Ordering::SeqCst);
+        self.wake.notify_one();
+    }
+
+    /// Returns whether background indexing is running.
+    pub fn is_running(&self) -> bool {
+        !self.stop_flag.load(Ordering::SeqCst)
+    }
+
+    /// Waits for new jobs or the idle interval, whichever comes first.
+    async fn wait(&self, idle_interval: Duration) {
+        let _ = tokio::time::timeout(idle_interval, self.wake.notified()).await;
+    }
+
+    /// Broadcasts the current progress to subscribers.
+    async fn publish_progress(&self, throttled: bool) {
+        if let Ok(mut progress) = self.storage.progress().await {
+            progress.throttled = throttled;
+            let _ = self.progress_sender.send(progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Address, MessageId, ThreadId};
+    use crate::embedding::Embedding;
+    use crate::services::SyncResult;
+    use chrono::Utc;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum JobState {
+        Pending,
+        Running,
+        Done,
+        Failed,
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        emails: Mutex<HashMap<EmailId, Email>>,
+        jobs: Mutex<Vec<(EmailId, JobState, u32)>>,
+        embeddings: Mutex<HashMap<EmailId, Vec<PassageEmbedding>>>,
+    }
+
+    #[async_trait]
+    impl IndexingStorage for MockStorage {
+        async fn enqueue(
+            &self,
+            _account_id: &AccountId,
+            email_ids: &[EmailId],
+        ) -> IndexingResult<usize> {
+            let mut jobs = self.jobs.lock().unwrap();
+            for email_id in email_ids {
+                jobs.retain(|(id, _, _)| id != email_id);
+                jobs.push((email_id.clone(), JobState::Pending, 0));
+            }
+            Ok(email_ids.len())
+        }
+
+        async fn claim_batch(&self, limit: usize) -> IndexingResult<Vec<EmailId>> {
+            let mut jobs = self.jobs.lock().unwrap();
+            let mut claimed = Vec::new();
+            for (id, state, _) in jobs.iter_mut() {
+                if claimed.len() < limit && *state == JobState::Pending {
+                    *state = JobState::Running;
+                    claimed.push(id.clone());
+                }
+            }
+            Ok(claimed)
+        }
+
+        async fn complete(
+            &self,
+            email_id: &EmailId,
+            passages: &[PassageEmbedding],
+        ) -> IndexingResult<()> {
+            self.set_state(email_id, JobState::Done);
+            self.embeddings
+                .lock()
+                .unwrap()
+                .insert(email_id.clone(), passages.to_vec());
+            Ok(())
+        }
+
+        async fn fail(
+            &self,
+            email_id: &EmailId,
+            _error: &str,
+            max_attempts: u32,
+        ) -> IndexingResult<()> {
+            let mut jobs = self.jobs.lock().unwrap();
+            if let Some((_, state, attempts)) = jobs.iter_mut().find(|(id, _, _)| id == email_id) {
+                *attempts += 1;
+                *state = if *attempts >= max_attempts {
+                    JobState::Failed
+                } else {
+                    JobState::Pending
+                };
+            }
+            Ok(())
+        }
+
+        async fn requeue_in_progress(&self) -> IndexingResult<usize> {
+            let mut jobs = self.jobs.lock().unwrap();
+            let mut count = 0;
+            for (_, state, _) in jobs.iter_mut() {
+                if *state == JobState::Running {
+                    *state = JobState::Pending;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        }
+
+        async fn remove(&self, email_ids: &[EmailId]) -> IndexingResult<()> {
+            self.jobs
+                .lock()
+                .unwrap()
+                .retain(|(id, _, _)| !email_ids.contains(id));
+            let mut embeddings = self.embeddings.lock().unwrap();
+            for email_id in email_ids {
+                embeddings.remove(email_id);
+            }
+            Ok(())
+        }
+
+        async fn get_email(&self, email_id: &EmailId) -> IndexingResult<Option<Email>> {
+            Ok(self.emails.lock().unwrap().get(email_id).cloned())
+        }
+
+        async fn load_embeddings(&self) -> IndexingResult<Vec<(EmailId, Vec<PassageEmbedding>)>> {
+            Ok(self
+                .embeddings
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, passages)| (id.clone(), passages.clone()))
+                .collect())
+        }
+
+        async fn progress(&self) -> IndexingResult<IndexingProgress> {
+            let jobs = self.jobs.lock().unwrap();
+            let count = |wanted: JobState| jobs.iter().filter(|(_, s, _)| *s == wanted).count();
+            Ok(IndexingProgress {
+                pending: count(JobState::Pending),
+                in_progress: count(JobState::Running),
+                completed: count(JobState::Done),
+                failed: count(JobState::Failed),
+                throttled: false,
+            })
+        }
+    }
+
+    impl MockStorage {
+        fn add_email(&self, id: &str, subject: &str) {
+            let email = Email {
+                id: EmailId::from(id),
+                account_id: AccountId::from("account-1"),
+                thread_id: ThreadId::from("thread-1"),
+                message_id: MessageId::from(format!("<{}@example.com>", id)),
+                in_reply_to: None,
+                references: vec![],
+                from: Address::new("sender@example.com"),
+                to: vec![],
+                cc: vec![],
+                bcc: vec![],
+                subject: Some(subject.to_string()),
+                body_text: None,
+                body_html: None,
+                snippet: String::new(),
+                date: Utc::now(),
+                is_read: false,
+                is_starred: false,
+                is_draft: false,
+                labels: vec![],
+                attachments: vec![],
+                headers: vec![],
+            };
+            self.emails.lock().unwrap().insert(email.id.clone(), email);
+        }
+
+        fn set_state(&self, email_id: &EmailId, new_state: JobState) {
+            let mut jobs = self.jobs.lock().unwrap();
+            if let Some((_, state, _)) = jobs.iter_mut().find(|(id, _, _)| id == email_id) {
+                *state = new_state;
+            }
+        }
+    }
+
+    /// Indexer that records calls and fails on subjects containing "fail".
+    #[derive(Default)]
+    struct MockIndexer {
+        indexed: Mutex<Vec<EmailId>>,
+        restored: Mutex<HashSet<EmailId>>,
+    }
+
+    impl EmailIndexer for MockIndexer {
+        fn index(&self, email: &Email) -> anyhow::Result<Vec<PassageEmbedding>> {
+            if email.subject.as_deref().is_some_and(|s| s.contains("fail")) {
+                anyhow::bail!("model error");
+            }
+            self.indexed.lock().unwrap().push(email.id.clone());
+            Ok(vec![PassageEmbedding {
+                text: String::new(),
+                embedding: Embedding::new(vec![1.0, 0.0]),
+            }])
+        }
+
+        fn restore(
+            &self,
+            email_id: &EmailId,
+            _passages: Vec<PassageEmbedding>,
+        ) -> anyhow::Result<()> {
+            self.restored.lock().unwrap().insert(email_id.clone());
+            Ok(())
+        }
+
+        fn remove(&self, email_id: &EmailId) {
+            self.restored.lock().unwrap().remove(email_id);
+        }
+    }
+
+    fn service(
+        storage: &Arc<MockStorage>,
+        indexer: &Arc<MockIndexer>,
+    ) -> IndexingService<MockStorage, MockIndexer> {
+        let settings = IndexingSettings {
+            batch_size: 2,
+            max_attempts: 2,
+            ..Default::default()
+        };
+        IndexingService::new(Arc::clone(storage), Arc::clone(indexer), settings)
+    }
+
+    fn completed(changed: &[&str], deleted: &[&str]) -> SyncEvent {
+        SyncEvent::Completed(
+            AccountId::from("account-1"),
+            SyncResult {
+                emails_received: changed.len(),
+                emails_sent: 0,
+                changes_applied: changed.len() + deleted.len(),
+                pending_synced: 0,
+                errors: vec![],
+                duration_ms: 10,
+                changed_email_ids: changed.iter().map(|id| EmailId::from(*id)).collect(),
+                deleted_email_ids: deleted.iter().map(|id| EmailId::from(*id)).collect(),
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn sync_completion_queues_and_indexes_emails() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        for id in ["e1", "e2", "e3"] {
+            storage.add_email(id, "Quarterly report");
+        }
+        let service = service(&storage, &indexer);
+
+        service
+            .handle_sync_event(&completed(&["e1", "e2", "e3"], &[]))
+            .await
+            .unwrap();
+        assert_eq!(service.progress().await.unwrap().pending, 3);
+
+        assert_eq!(service.process_batch().await.unwrap(), 2);
+        assert_eq!(service.process_all().await.unwrap(), 1);
+
+        let progress = service.progress().await.unwrap();
+        assert_eq!(progress.completed, 3);
+        assert!(progress.is_idle());
+        assert_eq!(indexer.indexed.lock().unwrap().len(), 3);
+        assert_eq!(storage.embeddings.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn failures_retry_then_give_up() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        storage.add_email("bad", "please fail");
+        let service = service(&storage, &indexer);
+
+        service
+            .enqueue(&AccountId::from("account-1"), &[EmailId::from("bad")])
+            .await
+            .unwrap();
+        service.process_all().await.unwrap();
+
+        let progress = service.progress().await.unwrap();
+        assert_eq!(progress.failed, 1);
+        assert_eq!(progress.pending, 0);
+    }
+
+    #[tokio::test]
+    async fn deleted_emails_leave_the_index() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        storage.add_email("e1", "Hello");
+        let service = service(&storage, &indexer);
+
+        service
+            .handle_sync_event(&completed(&["e1"], &[]))
+            .await
+            .unwrap();
+        service.process_all().await.unwrap();
+        service.resume().await.unwrap();
+        assert!(indexer
+            .restored
+            .lock()
+            .unwrap()
+            .contains(&EmailId::from("e1")));
+
+        service
+            .handle_sync_event(&completed(&[], &["e1"]))
+            .await
+            .unwrap();
+        assert!(indexer.restored.lock().unwrap().is_empty());
+        assert!(storage.embeddings.lock().unwrap().is_empty());
+        assert_eq!(service.progress().await.unwrap().total(), 0);
+    }
+
+    #[tokio::test]
+    async fn missing_emails_are_dropped_from_queue() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        let service = service(&storage, &indexer);
+
+        service
+            .enqueue(&AccountId::from("account-1"), &[EmailId::from("gone")])
+            .await
+            .unwrap();
+        service.process_all().await.unwrap();
+
+        assert_eq!(service.progress().await.unwrap().total(), 0);
+        assert!(indexer.indexed.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn resume_restores_without_reembedding() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        for id in ["e1", "e2", "e3"] {
+            storage.add_email(id, "Hello");
+        }
+        let service = service(&storage, &indexer);
+        service
+            .enqueue(
+                &AccountId::from("account-1"),
+                &[
+                    EmailId::from("e1"),
+                    EmailId::from("e2"),
+                    EmailId::from("e3"),
+                ],
+            )
+            .await
+            .unwrap();
+        service.process_batch().await.unwrap();
+        // Simulate a crash with a batch claimed but not finished.
+        storage.claim_batch(1).await.unwrap();
+
+        // Restart with a fresh index.
+        let restarted_indexer = Arc::new(MockIndexer::default());
+        let restarted = IndexingService::new(
+            Arc::clone(&storage),
+            Arc::clone(&restarted_indexer),
+            IndexingSettings::default(),
+        );
+        assert_eq!(restarted.resume().await.unwrap(), 2);
+        assert_eq!(restarted.progress().await.unwrap().pending, 1);
+
+        restarted.process_all().await.unwrap();
+        assert_eq!(
+            *restarted_indexer.indexed.lock().unwrap(),
+            vec![EmailId::from("e3")]
+        );
+        assert_eq!(restarted_indexer.restored.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn progress_is_broadcast() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        storage.add_email("e1", "Hello");
+        let service = service(&storage, &indexer);
+        let mut progress = service.subscribe();
+
+        service
+            .enqueue(&AccountId::from("account-1"), &[EmailId::from("e1")])
+            .await
+            .unwrap();
+        assert_eq!(progress.recv().await.unwrap().pending, 1);
+
+        service.process_all().await.unwrap();
+        assert_eq!(progress.recv().await.unwrap().completed, 1);
+    }
+
+    #[tokio::test]
+    async fn throttled_pauses_are_broadcast() {
+        let storage = Arc::new(MockStorage::default());
+        let indexer = Arc::new(MockIndexer::default());
+        storage.add_email("e1", "Hello");
+        let settings = IndexingSettings {
+            max_cpu_fraction: 0.05,
+            ..Default::default()
+        };
+        let service = Arc::new(IndexingService::new(
+            Arc::clone(&storage),
+            Arc::clone(&indexer),
+            settings,
+        ));
+        service
+            .enqueue(&AccountId::from("account-1"), &[EmailId::from("e1")])
+            .await
+            .unwrap();
+        let mut progress = service.subscribe();
+
+        let (_sync_sender, sync_events) = broadcast::channel(1);
+        Arc::clone(&service).start(sync_events);
+        let throttled = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let update = progress.recv().await.unwrap();
+                if update.throttled {
+                    return update;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        service.stop();
+
+        assert_eq!(throttled.completed, 1);
+        assert!(throttled.is_idle());
+    }
+
+    #[test]
+    fn progress_fraction() {
+        let progress = IndexingProgress {
+            pending: 1,
+            in_progress: 1,
+            completed: 1,
+            failed: 1,
+            throttled: false,
+        };
+        assert_eq!(progress.total(), 4);
+        assert!((progress.fraction_complete() - 0.5).abs() < f32::EPSILON);
+        assert!(!progress.is_idle());
+        assert_eq!(IndexingProgress::default().fraction_complete(), 1.0);
+    }
+
+    #[test]
+    fn throttle_delay_respects_cpu_fraction() {
+        let mut settings = IndexingSettings::default();
+        let batch = Duration::from_millis(100);
+
+        settings.max_cpu_fraction = 0.5;
+        assert_eq!(settings.throttle_delay(batch), Duration::from_millis(100));
+
+        settings.max_cpu_fraction = 1.0;
+        assert_eq!(settings.throttle_delay(batch), Duration::ZERO);
+
+        settings.max_cpu_fraction = 0.25;
+        assert_eq!(settings.throttle_delay(batch), Duration::from_millis(300));
+    }
+}
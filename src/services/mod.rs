@@ -22,67 +22,121 @@
 //! - [`EmailService`]: Orchestrates email operations across providers and storage
 //! - [`AiService`]: Manages AI provider interactions for summarization, drafts, and search
 //! - [`SyncService`]: Handles synchronization between remote providers and local storage
+//! - [`IndexingService`]: Embeds synced emails in the background for semantic search
 //! - [`SearchService`]: Combined full-text and semantic search across emails
+//! - [`AskService`]: Answers questions about the mailbox with cited emails
+//! - [`AgentService`]: Tool-calling assistant that acts on the mailbox with confirmation
 //! - [`ContactService`]: Manages contacts extracted from email interactions
+//! - [`FilterService`]: User-defined mail filter rules run during sync and on stored mail
+//! - [`GmailFilterService`]: Mirrors screener and filter rules as Gmail server-side filters
 //! - [`LabelService`]: Manages email labels and folders
 //! - [`SnoozeService`]: Temporarily hides emails until a scheduled time
+//! - [`SieveService`]: Publishes screener and filter rules to IMAP servers as Sieve scripts
+//! - [`SpamService`]: Local Bayesian spam filter trained from spam reports
 //! - [`TelemetryService`]: Local usage statistics and event tracking
 //! - [`ScreenerService`]: Manages unknown sender triage and screening
+//! - [`SenderSignals`]: Evidence about unknown senders for screener analysis
 //! - [`NotificationService`]: In-app and system notifications
 //! - [`SmartViewService`]: AI-powered email classification into smart views
+//! - [`TriageService`]: On-device classifier learned from the user's triage decisions
 //! - [`StatsService`]: Usage statistics and metrics aggregation
 //! - [`AccountService`]: Manages email account configuration and credentials
 //! - [`ThreadService`]: Thread operations and metadata management
 
 mod account_service;
+mod agent_service;
 mod ai_service;
+mod ask_service;
 mod contact_service;
 mod email_service;
+mod filter_service;
+mod gmail_filter_service;
+mod indexing_service;
 mod label_service;
 mod notification_service;
 mod screener_service;
+mod search_eval;
 mod search_service;
+mod sender_signals;
+mod sieve_service;
 mod smart_view_service;
 mod snooze_service;
+mod spam_service;
 mod stats_service;
 mod sync_service;
 mod telemetry_service;
 mod thread_service;
+mod triage_service;
 mod undo_service;
 
 pub use account_service::{
     AccountError, AccountService, AccountStats, AccountStorage, AccountUpdate,
     CreateAccountRequest, CredentialStore,
 };
+pub use agent_service::{
+    AgentDraft, AgentService, AgentSession, AgentSettings, AgentStep, AgentTool, ProposedAction,
+};
 pub use ai_service::{
-    AiService, AiSettings, Category, DraftSuggestion, SearchResult, Summary, SummarySettings,
+    AiCacheEntry, AiCacheKind, AiCacheStats, AiCacheStorage, AiService, AiSettings, AiTask,
+    Category, DraftSuggestion, RoutePolicy, RoutingSettings, SearchResult, SemanticSearchStorage,
+    ServedRequest, Summary, SummarySettings,
+};
+pub use ask_service::{
+    parse_citations, select_sources, AskAnswer, AskService, AskSettings, AskSource, Citation,
 };
 pub use contact_service::{
     ContactError, ContactFilter, ContactService, ContactSort, ContactStats, ContactStorage,
 };
-pub use email_service::{Draft, EmailService, Pagination, ViewType};
+pub use email_service::{Draft, EmailService, Pagination, SenderScope, ViewType};
+pub use filter_service::{
+    evaluate_filters, FilterAction, FilterCondition, FilterEffects, FilterError, FilterOutcome,
+    FilterResult, FilterRule, FilterService, FilterStorage,
+};
+pub use gmail_filter_service::{
+    filter_rule_to_gmail, screener_rule_to_gmail, GmailFilterApi, GmailFilterError,
+    GmailFilterMapping, GmailFilterResult, GmailFilterService, GmailFilterStorage,
+    GmailFilterSyncReport, ImportedGmailFilter,
+};
+pub use indexing_service::{
+    EmailIndexer, IndexingError, IndexingProgress, IndexingResult, IndexingService,
+    IndexingSettings, IndexingStorage,
+};
 pub use label_service::{LabelError, LabelService, LabelSort, LabelStorage};
 pub use notification_service::{
     NotificationCategory, NotificationError, NotificationPriority, NotificationRequest,
     NotificationService, NotificationSettings, SentNotification,
 };
 pub use screener_service::{
-    ScreenerError, ScreenerFilter, ScreenerService, ScreenerStats, ScreenerStorage,
+    RuleDryRun, RuleDryRunMatch, ScreenerError, ScreenerFilter, ScreenerMailbox, ScreenerService,
+    ScreenerStats, ScreenerStorage,
 };
+pub use search_eval::{evaluate_ranking, load_labelled_queries, LabelledQuery, RankingMetrics};
 pub use search_service::{
-    DateRange, EmailMetadata, FtsHit, SearchFolder, SearchHit, SearchMode, SearchQuery,
-    SearchResults, SearchService, SearchSettings, SearchSource, SearchStorage,
+    fuse_scores, normalize_fts_rank, DateRange, EmailMetadata, FtsHit, FusedScore, HybridStrategy,
+    SearchFolder, SearchHit, SearchMode, SearchQuery, SearchResults, SearchService, SearchSettings,
+    SearchSource, SearchStorage,
+};
+pub use sender_signals::{DomainAge, SenderSignals};
+pub use sieve_service::{
+    CompiledScript, SieveCompiler, SieveError, SieveResult, SieveService, VacationResponse,
+    DEFAULT_SCRIPT_NAME,
 };
 pub use smart_view_service::{
     Classification, ClassificationCriteria, ClassificationInput, SmartViewError, SmartViewService,
     SmartViewStorage, SmartViewType,
 };
 pub use snooze_service::{SnoozeDuration, SnoozeError, SnoozeService, SnoozeStorage, SnoozedItem};
+pub use spam_service::{
+    SpamCounts, SpamError, SpamResult, SpamService, SpamSettings, SpamStorage, SpamVerdict,
+    TokenEvidence,
+};
 pub use stats_service::{
     AiStats, BusiestHour, DailyActivity, EmailStats, ProductivityStats, StatsError, StatsEvent,
     StatsReport, StatsService, StatsStorage, TopCorrespondent,
 };
-pub use sync_service::{SyncResult, SyncService, SyncSettings, SyncStatus};
+pub use sync_service::{
+    IncomingFilter, SyncEvent, SyncResult, SyncService, SyncSettings, SyncStatus,
+};
 pub use telemetry_service::{
     AggregatedStats, DailyStats, EventPayload, EventType, StatsTimeRange, TelemetryError,
     TelemetryEvent, TelemetryService, TelemetryStorage,
@@ -90,6 +144,10 @@ pub use telemetry_service::{
 pub use thread_service::{
     ThreadError, ThreadFilter, ThreadService, ThreadSort, ThreadStats, ThreadStorage,
 };
+pub use triage_service::{
+    TrainingSignal, TriageError, TriageFeatures, TriageLabel, TriageModel, TriagePrediction,
+    TriageResult, TriageService, TriageSettings, TriageStorage,
+};
 pub use undo_service::{
     ActionBuilder, ActionResult, ActionState, ActionType, UndoService, UndoableAction,
 };
@@ -3,18 +3,33 @@
 //! The screener helps users manage emails from unknown senders by:
 //! - Queuing new senders for review
 //! - Applying AI analysis to suggest actions
-//! - Maintaining allow/block rules for automatic decisions
+//! - Maintaining prioritized allow/block rules for automatic decisions
+//! - Trying out a rule against recent senders before saving it
+//! - Applying decisions to a sender's existing threads and to new mail
+//!
+//! Rejecting a sender or blocking a domain moves their inbox threads to
+//! Screened Out, and new mail they send is screened out once it is stored.
+//! Both go through a [`ScreenerMailbox`], normally the [`EmailService`], so
+//! the threads are archived on the server too. Approving the sender or
+//! deleting the block returns the threads to the inbox.
 //! - Learning from user decisions over time
 
 use async_trait::async_trait;
 use chrono::Utc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
 use crate::domain::{
-    AccountId, EmailId, RuleType, ScreenerAction, ScreenerEntry, ScreenerRule, ScreenerStatus,
-    SenderAnalysis, SenderType,
+    first_matching_rule, system_labels, AccountId, Email, EmailId, LabelId, RuleType,
+    ScreenedMessage, ScreenerAction, ScreenerEntry, ScreenerRule, ScreenerStatus, SenderAnalysis,
+    SenderType, ThreadId,
 };
+use crate::services::email_service::EmailStorage;
+use crate::services::{EmailService, IncomingFilter, SenderScope, ViewType};
+
+/// Number of recent senders a rule is tried against in a dry run.
+const DRY_RUN_SENDERS: usize = 1000;
 
 /// Errors that can occur during screener operations.
 #[derive(Debug, Error)]
@@ -68,7 +83,7 @@ pub trait ScreenerStorage: Send + Sync {
     /// Deletes an entry.
     async fn delete_entry(&self, id: &str) -> ScreenerResult<()>;
 
-    /// Gets all rules for an account.
+    /// Gets the rules for an account, including rules for every account.
     async fn get_rules(&self, account_id: &AccountId) -> ScreenerResult<Vec<ScreenerRule>>;
 
     /// Gets a rule by ID.
@@ -86,6 +101,71 @@ pub trait ScreenerStorage: Send + Sync {
         account_id: &AccountId,
         status: ScreenerStatus,
     ) -> ScreenerResult<u32>;
+
+    /// Gets the latest message from each of the account's most recent
+    /// senders, newest first.
+    async fn get_recent_senders(
+        &self,
+        account_id: &AccountId,
+        limit: usize,
+    ) -> ScreenerResult<Vec<ScreenedMessage>>;
+}
+
+/// Moves threads in and out of the Screened Out view.
+///
+/// Implemented by [`EmailService`], which archives screened-out threads on
+/// the server as well as locally.
+#[async_trait]
+pub trait ScreenerMailbox: Send + Sync {
+    /// Finds threads in a view with a message from the given senders.
+    async fn find_threads_from(
+        &self,
+        account_id: &AccountId,
+        senders: &SenderScope,
+        view: ViewType,
+    ) -> anyhow::Result<Vec<ThreadId>>;
+
+    /// Moves threads from the inbox to Screened Out.
+    async fn screen_out(
+        &self,
+        account_id: &AccountId,
+        thread_ids: &[ThreadId],
+    ) -> anyhow::Result<()>;
+
+    /// Returns screened-out threads to the inbox.
+    async fn restore_screened_out(
+        &self,
+        account_id: &AccountId,
+        thread_ids: &[ThreadId],
+    ) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl<E: EmailStorage> ScreenerMailbox for EmailService<E> {
+    async fn find_threads_from(
+        &self,
+        account_id: &AccountId,
+        senders: &SenderScope,
+        view: ViewType,
+    ) -> anyhow::Result<Vec<ThreadId>> {
+        EmailService::find_threads_from(self, account_id, senders, view).await
+    }
+
+    async fn screen_out(
+        &self,
+        account_id: &AccountId,
+        thread_ids: &[ThreadId],
+    ) -> anyhow::Result<()> {
+        EmailService::screen_out(self, account_id, thread_ids).await
+    }
+
+    async fn restore_screened_out(
+        &self,
+        account_id: &AccountId,
+        thread_ids: &[ThreadId],
+    ) -> anyhow::Result<()> {
+        EmailService::restore_screened_out(self, account_id, thread_ids).await
+    }
 }
 
 /// Filter for querying screener entries.
@@ -192,10 +272,44 @@ pub struct ScreenerStats {
     pub by_type: HashMap<SenderType, u32>,
 }
 
+/// A recent sender that a rule matched during a dry run.
+#[derive(Debug, Clone)]
+pub struct RuleDryRunMatch {
+    /// The sender's latest message.
+    pub message: ScreenedMessage,
+    /// An existing rule that takes precedence and would decide this sender
+    /// instead.
+    pub shadowed_by: Option<ScreenerRule>,
+}
+
+/// Outcome of trying a rule against recent senders.
+#[derive(Debug, Clone, Default)]
+pub struct RuleDryRun {
+    /// Number of senders tried.
+    pub senders_tested: usize,
+    /// Senders the rule matched.
+    pub matches: Vec<RuleDryRunMatch>,
+}
+
+impl RuleDryRun {
+    /// Returns the number of senders the rule would decide.
+    pub fn effective_matches(&self) -> usize {
+        self.matches
+            .iter()
+            .filter(|m| m.shadowed_by.is_none())
+            .count()
+    }
+}
+
 /// Service for managing the email screener.
 pub struct ScreenerService<S: ScreenerStorage> {
     storage: S,
     account_id: AccountId,
+    auto_approve_threshold: Option<f32>,
+    /// Where decisions are applied to existing threads.
+    mailbox: Option<Arc<dyn ScreenerMailbox>>,
+    /// New emails waiting to be screened out once stored.
+    pending: Mutex<HashSet<EmailId>>,
 }
 
 impl<S: ScreenerStorage> ScreenerService<S> {
@@ -204,9 +318,28 @@ impl<S: ScreenerStorage> ScreenerService<S> {
         Self {
             storage,
             account_id,
+            auto_approve_threshold: None,
+            mailbox: None,
+            pending: Mutex::new(HashSet::new()),
         }
     }
 
+    /// Applies decisions to the sender's threads in this mailbox.
+    ///
+    /// Without one, decisions only affect the screener entries and the
+    /// labels of new mail.
+    pub fn with_mailbox(mut self, mailbox: Arc<dyn ScreenerMailbox>) -> Self {
+        self.mailbox = Some(mailbox);
+        self
+    }
+
+    /// Approves pending senders automatically when their analysis suggests
+    /// approval with at least this confidence.
+    pub fn with_auto_approve_threshold(mut self, threshold: f32) -> Self {
+        self.auto_approve_threshold = Some(threshold);
+        self
+    }
+
     /// Returns the account ID.
     pub fn account_id(&self) -> &AccountId {
         &self.account_id
@@ -230,18 +363,22 @@ impl<S: ScreenerStorage> ScreenerService<S> {
     }
 
     /// Gets an entry by ID.
+    ///
+    /// Entries belonging to other accounts are reported as not found.
     pub async fn get_entry(&self, id: &str) -> ScreenerResult<ScreenerEntry> {
         self.storage
             .get_entry(id)
             .await?
+            .filter(|entry| entry.account_id == self.account_id)
             .ok_or_else(|| ScreenerError::NotFound(id.to_string()))
     }
 
     /// Checks if a sender is known (approved or rejected).
     pub async fn is_known_sender(&self, email: &str) -> ScreenerResult<Option<ScreenerStatus>> {
+        let message = ScreenedMessage::from_sender(email);
         if let Some(entry) = self
             .storage
-            .get_entry_by_email(&self.account_id, email)
+            .get_entry_by_email(&self.account_id, &message.sender)
             .await?
         {
             if entry.status != ScreenerStatus::Pending {
@@ -250,18 +387,17 @@ impl<S: ScreenerStorage> ScreenerService<S> {
         }
 
         // Check rules
-        let rules = self.storage.get_rules(&self.account_id).await?;
-        for rule in rules {
-            if self.rule_matches(&rule, email) {
-                return Ok(Some(match rule.action {
-                    ScreenerAction::Approve => ScreenerStatus::Approved,
-                    ScreenerAction::Reject => ScreenerStatus::Rejected,
-                    ScreenerAction::Review => ScreenerStatus::Pending,
-                }));
-            }
-        }
+        let rule = self.matching_rule(&message).await?;
+        Ok(rule.map(|rule| rule_status(&rule)))
+    }
 
-        Ok(None)
+    /// Finds the rule that decides a message, if any.
+    pub async fn matching_rule(
+        &self,
+        message: &ScreenedMessage,
+    ) -> ScreenerResult<Option<ScreenerRule>> {
+        let rules = self.storage.get_rules(&self.account_id).await?;
+        Ok(first_matching_rule(&rules, &self.account_id, message).cloned())
     }
 
     /// Adds a new sender to the screener queue.
@@ -270,33 +406,51 @@ impl<S: ScreenerStorage> ScreenerService<S> {
         email: &str,
         name: Option<&str>,
         first_email_id: Option<EmailId>,
+    ) -> ScreenerResult<ScreenerEntry> {
+        self.add_message(&ScreenedMessage::from_sender(email), name, first_email_id)
+            .await
+    }
+
+    /// Adds the sender of an email to the screener queue.
+    ///
+    /// Unlike [`add_sender`](Self::add_sender), rules on the `List-Id` header
+    /// and subject can decide the sender.
+    pub async fn add_email(&self, email: &Email) -> ScreenerResult<ScreenerEntry> {
+        self.add_message(
+            &ScreenedMessage::from_email(email),
+            email.from.name.as_deref(),
+            Some(email.id.clone()),
+        )
+        .await
+    }
+
+    /// Adds a message's sender, keyed by the lowercased address that
+    /// lookups use.
+    async fn add_message(
+        &self,
+        message: &ScreenedMessage,
+        name: Option<&str>,
+        first_email_id: Option<EmailId>,
     ) -> ScreenerResult<ScreenerEntry> {
         // Check if already exists
         if let Some(existing) = self
             .storage
-            .get_entry_by_email(&self.account_id, email)
+            .get_entry_by_email(&self.account_id, &message.sender)
             .await?
         {
             return Ok(existing);
         }
 
         // Check rules for automatic decision
-        let rules = self.storage.get_rules(&self.account_id).await?;
-        let auto_status = rules.iter().find_map(|rule| {
-            if self.rule_matches(rule, email) {
-                Some(match rule.action {
-                    ScreenerAction::Approve => ScreenerStatus::Approved,
-                    ScreenerAction::Reject => ScreenerStatus::Rejected,
-                    ScreenerAction::Review => ScreenerStatus::Pending,
-                })
-            } else {
-                None
-            }
-        });
+        let auto_status = self
+            .matching_rule(message)
+            .await?
+            .map(|rule| rule_status(&rule));
 
         let entry = ScreenerEntry {
             id: format!("scr-{}", uuid::Uuid::new_v4()),
-            sender_email: email.to_string(),
+            account_id: self.account_id.clone(),
+            sender_email: message.sender.clone(),
             sender_name: name.map(String::from),
             first_email_id,
             status: auto_status.unwrap_or(ScreenerStatus::Pending),
@@ -314,20 +468,30 @@ impl<S: ScreenerStorage> ScreenerService<S> {
     }
 
     /// Approves a sender.
+    ///
+    /// A previously rejected sender's screened-out threads return to the
+    /// inbox.
     pub async fn approve(&self, id: &str) -> ScreenerResult<ScreenerEntry> {
         let mut entry = self.get_entry(id).await?;
+        let was_rejected = entry.status == ScreenerStatus::Rejected;
         entry.status = ScreenerStatus::Approved;
         entry.decided_at = Some(Utc::now());
         self.storage.save_entry(&entry).await?;
+        if was_rejected {
+            self.restore(SenderScope::Address(entry.sender_email.to_lowercase()))
+                .await?;
+        }
         Ok(entry)
     }
 
-    /// Rejects a sender.
+    /// Rejects a sender and moves their inbox threads to Screened Out.
     pub async fn reject(&self, id: &str) -> ScreenerResult<ScreenerEntry> {
         let mut entry = self.get_entry(id).await?;
         entry.status = ScreenerStatus::Rejected;
         entry.decided_at = Some(Utc::now());
         self.storage.save_entry(&entry).await?;
+        self.screen_out(SenderScope::Address(entry.sender_email.to_lowercase()))
+            .await?;
         Ok(entry)
     }
 
@@ -354,46 +518,178 @@ impl<S: ScreenerStorage> ScreenerService<S> {
     }
 
     /// Sets AI analysis for an entry.
-    pub async fn set_analysis(&self, id: &str, analysis: SenderAnalysis) -> ScreenerResult<()> {
+    ///
+    /// A pending entry is approved when the analysis suggests approval with a
+    /// confidence at or above the auto-approve threshold.
+    pub async fn set_analysis(
+        &self,
+        id: &str,
+        analysis: SenderAnalysis,
+    ) -> ScreenerResult<ScreenerEntry> {
         let mut entry = self.get_entry(id).await?;
+        let auto_approve = self.auto_approve_threshold.is_some_and(|threshold| {
+            analysis.suggested_action == ScreenerAction::Approve && analysis.confidence >= threshold
+        });
+        if auto_approve && entry.status == ScreenerStatus::Pending {
+            entry.status = ScreenerStatus::Approved;
+            entry.decided_at = Some(Utc::now());
+        }
         entry.ai_analysis = Some(analysis);
-        self.storage.save_entry(&entry).await
+        self.storage.save_entry(&entry).await?;
+        Ok(entry)
     }
 
-    /// Gets all rules.
+    /// Gets all rules that apply to this account, in the order they are
+    /// tried.
     pub async fn get_rules(&self) -> ScreenerResult<Vec<ScreenerRule>> {
-        self.storage.get_rules(&self.account_id).await
+        let mut rules: Vec<ScreenerRule> = self
+            .storage
+            .get_rules(&self.account_id)
+            .await?
+            .into_iter()
+            .filter(|rule| rule.applies_to(&self.account_id))
+            .collect();
+        rules.sort_by(|a, b| a.cmp_precedence(b));
+        Ok(rules)
     }
 
-    /// Adds a domain allow rule.
-    pub async fn allow_domain(&self, domain: &str) -> ScreenerResult<ScreenerRule> {
-        let rule = ScreenerRule {
-            id: format!("rule-{}", uuid::Uuid::new_v4()),
-            rule_type: RuleType::DomainAllow,
-            pattern: domain.to_lowercase(),
-            action: ScreenerAction::Approve,
-            created_at: Utc::now(),
-        };
+    /// Saves a rule after checking that its pattern is valid.
+    pub async fn add_rule(&self, rule: ScreenerRule) -> ScreenerResult<ScreenerRule> {
+        rule.validate().map_err(ScreenerError::InvalidOperation)?;
         self.storage.save_rule(&rule).await?;
         Ok(rule)
     }
 
-    /// Adds a domain block rule.
+    /// Adds a domain allow rule for this account.
+    pub async fn allow_domain(&self, domain: &str) -> ScreenerResult<ScreenerRule> {
+        let rule = ScreenerRule::new(
+            RuleType::DomainAllow,
+            domain.to_lowercase(),
+            ScreenerAction::Approve,
+        )
+        .for_account(self.account_id.clone());
+        self.add_rule(rule).await
+    }
+
+    /// Adds a domain block rule for this account and moves the domain's
+    /// inbox threads to Screened Out.
     pub async fn block_domain(&self, domain: &str) -> ScreenerResult<ScreenerRule> {
-        let rule = ScreenerRule {
-            id: format!("rule-{}", uuid::Uuid::new_v4()),
-            rule_type: RuleType::DomainBlock,
-            pattern: domain.to_lowercase(),
-            action: ScreenerAction::Reject,
-            created_at: Utc::now(),
-        };
-        self.storage.save_rule(&rule).await?;
+        let rule = ScreenerRule::new(
+            RuleType::DomainBlock,
+            domain.to_lowercase(),
+            ScreenerAction::Reject,
+        )
+        .for_account(self.account_id.clone());
+        let rule = self.add_rule(rule).await?;
+        self.screen_out(SenderScope::Domain(rule.pattern.clone()))
+            .await?;
         Ok(rule)
     }
 
-    /// Deletes a rule.
+    /// Tries a rule against the account's last 1,000 senders without
+    /// saving it.
+    ///
+    /// Each match notes any existing rule that takes precedence and would
+    /// decide that sender instead.
+    pub async fn dry_run(&self, rule: &ScreenerRule) -> ScreenerResult<RuleDryRun> {
+        rule.validate().map_err(ScreenerError::InvalidOperation)?;
+
+        let senders = self
+            .storage
+            .get_recent_senders(&self.account_id, DRY_RUN_SENDERS)
+            .await?;
+        let existing: Vec<ScreenerRule> = self
+            .get_rules()
+            .await?
+            .into_iter()
+            .filter(|r| r.id != rule.id)
+            .collect();
+
+        let matches = senders
+            .iter()
+            .filter(|message| rule.matches(message))
+            .map(|message| RuleDryRunMatch {
+                message: message.clone(),
+                shadowed_by: first_matching_rule(&existing, &self.account_id, message)
+                    .filter(|other| other.cmp_precedence(rule).is_lt())
+                    .cloned(),
+            })
+            .collect();
+
+        Ok(RuleDryRun {
+            senders_tested: senders.len(),
+            matches,
+        })
+    }
+
+    /// Deletes a rule of this account or a global rule.
+    ///
+    /// Deleting a domain block returns the domain's screened-out threads to
+    /// the inbox.
     pub async fn delete_rule(&self, id: &str) -> ScreenerResult<()> {
-        self.storage.delete_rule(id).await
+        let rule = self
+            .storage
+            .get_rule(id)
+            .await?
+            .filter(|rule| rule.applies_to(&self.account_id))
+            .ok_or_else(|| ScreenerError::RuleNotFound(id.to_string()))?;
+        self.storage.delete_rule(id).await?;
+        if rule.rule_type == RuleType::DomainBlock {
+            self.restore(SenderScope::Domain(rule.pattern)).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the inbox threads of some senders to Screened Out.
+    async fn screen_out(&self, senders: SenderScope) -> ScreenerResult<Vec<ThreadId>> {
+        let Some(mailbox) = &self.mailbox else {
+            return Ok(Vec::new());
+        };
+        let threads = mailbox
+            .find_threads_from(&self.account_id, &senders, ViewType::Inbox)
+            .await
+            .map_err(|e| ScreenerError::Storage(e.to_string()))?;
+        mailbox
+            .screen_out(&self.account_id, &threads)
+            .await
+            .map_err(|e| ScreenerError::Storage(e.to_string()))?;
+        Ok(threads)
+    }
+
+    /// Returns the screened-out threads of some senders to the inbox.
+    async fn restore(&self, senders: SenderScope) -> ScreenerResult<Vec<ThreadId>> {
+        let Some(mailbox) = &self.mailbox else {
+            return Ok(Vec::new());
+        };
+        let threads = mailbox
+            .find_threads_from(&self.account_id, &senders, ViewType::ScreenedOut)
+            .await
+            .map_err(|e| ScreenerError::Storage(e.to_string()))?;
+        mailbox
+            .restore_screened_out(&self.account_id, &threads)
+            .await
+            .map_err(|e| ScreenerError::Storage(e.to_string()))?;
+        Ok(threads)
+    }
+
+    /// Returns true if new mail like this should skip the inbox.
+    ///
+    /// Mail is screened out when its sender was rejected or the rule that
+    /// decides it rejects.
+    pub async fn is_screened_out(&self, message: &ScreenedMessage) -> ScreenerResult<bool> {
+        if let Some(entry) = self
+            .storage
+            .get_entry_by_email(&self.account_id, &message.sender)
+            .await?
+        {
+            if entry.status != ScreenerStatus::Pending {
+                return Ok(entry.status == ScreenerStatus::Rejected);
+            }
+        }
+        Ok(self
+            .matching_rule(message)
+            .await?
+            .is_some_and(|rule| rule.action == ScreenerAction::Reject))
     }
 
     /// Gets screener statistics.
@@ -427,56 +723,87 @@ impl<S: ScreenerStorage> ScreenerService<S> {
             by_type,
         })
     }
+}
 
-    /// Checks if a rule matches an email address.
-    fn rule_matches(&self, rule: &ScreenerRule, email: &str) -> bool {
-        let email_lower = email.to_lowercase();
-        let pattern = &rule.pattern;
+#[async_trait]
+impl<S: ScreenerStorage> IncomingFilter for ScreenerService<S> {
+    async fn filter_incoming(&self, email: &mut Email) -> anyhow::Result<()> {
+        if email.account_id != self.account_id
+            || !self
+                .is_screened_out(&ScreenedMessage::from_email(email))
+                .await?
+        {
+            return Ok(());
+        }
 
-        match rule.rule_type {
-            RuleType::DomainAllow | RuleType::DomainBlock => {
-                email_lower.ends_with(&format!("@{}", pattern))
-                    || email_lower.ends_with(&format!(".{}", pattern))
-            }
-            RuleType::Pattern => {
-                // Simple glob matching
-                if pattern.contains('*') {
-                    let parts: Vec<&str> = pattern.split('*').collect();
-                    let mut pos = 0;
-                    for part in parts {
-                        if part.is_empty() {
-                            continue;
-                        }
-                        if let Some(idx) = email_lower[pos..].find(part) {
-                            pos += idx + part.len();
-                        } else {
-                            return false;
-                        }
-                    }
-                    true
-                } else {
-                    email_lower.contains(pattern)
-                }
-            }
+        let inbox = system_labels::inbox();
+        let screened_out = LabelId::from(ViewType::ScreenedOut.folder_name());
+        email.labels.retain(|l| *l != inbox);
+        if !email.labels.contains(&screened_out) {
+            email.labels.push(screened_out);
+        }
+        if self.mailbox.is_some() {
+            self.pending.lock().unwrap().insert(email.id.clone());
+        }
+        Ok(())
+    }
+
+    async fn after_store(&self, email: &Email) -> anyhow::Result<()> {
+        if !self.pending.lock().unwrap().remove(&email.id) {
+            return Ok(());
+        }
+        if let Some(mailbox) = &self.mailbox {
+            mailbox
+                .screen_out(&self.account_id, std::slice::from_ref(&email.thread_id))
+                .await?;
         }
+        Ok(())
+    }
+
+    async fn discard(&self, email: &Email) -> anyhow::Result<()> {
+        self.pending.lock().unwrap().remove(&email.id);
+        Ok(())
+    }
+}
+
+/// Returns the status a rule gives the senders it matches.
+fn rule_status(rule: &ScreenerRule) -> ScreenerStatus {
+    match rule.action {
+        ScreenerAction::Approve => ScreenerStatus::Approved,
+        ScreenerAction::Reject => ScreenerStatus::Rejected,
+        ScreenerAction::Review => ScreenerStatus::Pending,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::Mutex;
+    use crate::domain::{Address, Header, MessageId, RuleField, Thread, ThreadSummary};
+    use crate::services::email_service::ThreadMetadataUpdate;
+    use crate::services::Pagination;
+    use std::sync::{Arc, Mutex};
 
     struct MockStorage {
-        entries: Mutex<Vec<ScreenerEntry>>,
-        rules: Mutex<Vec<ScreenerRule>>,
+        entries: Arc<Mutex<Vec<ScreenerEntry>>>,
+        rules: Arc<Mutex<Vec<ScreenerRule>>>,
+        senders: Vec<ScreenedMessage>,
     }
 
     impl MockStorage {
         fn new() -> Self {
             Self {
-                entries: Mutex::new(Vec::new()),
-                rules: Mutex::new(Vec::new()),
+                entries: Arc::new(Mutex::new(Vec::new())),
+                rules: Arc::new(Mutex::new(Vec::new())),
+                senders: Vec::new(),
+            }
+        }
+
+        /// Returns storage backed by the same database.
+        fn shared(&self) -> Self {
+            Self {
+                entries: self.entries.clone(),
+                rules: self.rules.clone(),
+                senders: self.senders.clone(),
             }
         }
     }
@@ -485,14 +812,14 @@ mod tests {
     impl ScreenerStorage for MockStorage {
         async fn get_pending_entries(
             &self,
-            _account_id: &AccountId,
+            account_id: &AccountId,
         ) -> ScreenerResult<Vec<ScreenerEntry>> {
             Ok(self
                 .entries
                 .lock()
                 .unwrap()
                 .iter()
-                .filter(|e| e.status == ScreenerStatus::Pending)
+                .filter(|e| e.account_id == *account_id && e.status == ScreenerStatus::Pending)
                 .cloned()
                 .collect())
         }
@@ -509,7 +836,7 @@ mod tests {
 
         async fn get_entry_by_email(
             &self,
-            _account_id: &AccountId,
+            account_id: &AccountId,
             email: &str,
         ) -> ScreenerResult<Option<ScreenerEntry>> {
             Ok(self
@@ -517,7 +844,7 @@ mod tests {
                 .lock()
                 .unwrap()
                 .iter()
-                .find(|e| e.sender_email == email)
+                .find(|e| e.account_id == *account_id && e.sender_email == email)
                 .cloned())
         }
 
@@ -533,8 +860,15 @@ mod tests {
             Ok(())
         }
 
-        async fn get_rules(&self, _account_id: &AccountId) -> ScreenerResult<Vec<ScreenerRule>> {
-            Ok(self.rules.lock().unwrap().clone())
+        async fn get_rules(&self, account_id: &AccountId) -> ScreenerResult<Vec<ScreenerRule>> {
+            Ok(self
+                .rules
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|r| r.applies_to(account_id))
+                .cloned()
+                .collect())
         }
 
         async fn get_rule(&self, id: &str) -> ScreenerResult<Option<ScreenerRule>> {
@@ -561,7 +895,7 @@ mod tests {
 
         async fn count_by_status(
             &self,
-            _account_id: &AccountId,
+            account_id: &AccountId,
             status: ScreenerStatus,
         ) -> ScreenerResult<u32> {
             Ok(self
@@ -569,9 +903,124 @@ mod tests {
                 .lock()
                 .unwrap()
                 .iter()
-                .filter(|e| e.status == status)
+                .filter(|e| e.account_id == *account_id && e.status == status)
                 .count() as u32)
         }
+
+        async fn get_recent_senders(
+            &self,
+            _account_id: &AccountId,
+            limit: usize,
+        ) -> ScreenerResult<Vec<ScreenedMessage>> {
+            Ok(self.senders.iter().take(limit).cloned().collect())
+        }
+    }
+
+    /// Email storage holding one single-message thread per sender.
+    struct MockEmailStorage {
+        threads: Mutex<Vec<(ThreadId, String, Vec<LabelId>)>>,
+    }
+
+    impl MockEmailStorage {
+        fn with_senders(senders: &[&str]) -> Self {
+            Self {
+                threads: Mutex::new(
+                    senders
+                        .iter()
+                        .enumerate()
+                        .map(|(i, sender)| {
+                            (
+                                ThreadId::from(format!("thread-{}", i)),
+                                sender.to_string(),
+                                vec![system_labels::inbox()],
+                            )
+                        })
+                        .collect(),
+                ),
+            }
+        }
+
+        fn labels(&self, thread_id: &str) -> Vec<LabelId> {
+            let threads = self.threads.lock().unwrap();
+            let thread = threads.iter().find(|(id, _, _)| id.0 == thread_id);
+            thread.map(|(_, _, labels)| labels.clone()).unwrap()
+        }
+    }
+
+    #[async_trait]
+    impl EmailStorage for MockEmailStorage {
+        async fn get_threads(
+            &self,
+            _account_id: &AccountId,
+            _view: ViewType,
+            _pagination: Pagination,
+        ) -> anyhow::Result<Vec<ThreadSummary>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_thread(&self, _thread_id: &ThreadId) -> anyhow::Result<Option<Thread>> {
+            Ok(None)
+        }
+
+        async fn store_thread(&self, _thread: &Thread) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn update_thread_metadata(
+            &self,
+            thread_id: &ThreadId,
+            updates: ThreadMetadataUpdate,
+        ) -> anyhow::Result<()> {
+            let mut threads = self.threads.lock().unwrap();
+            for (_, _, labels) in threads.iter_mut().filter(|(id, _, _)| id == thread_id) {
+                labels.retain(|l| !updates.remove_labels.contains(l));
+                labels.extend(updates.add_labels.iter().cloned());
+            }
+            Ok(())
+        }
+
+        async fn find_threads_from(
+            &self,
+            _account_id: &AccountId,
+            senders: &SenderScope,
+            view: ViewType,
+        ) -> anyhow::Result<Vec<ThreadId>> {
+            let label = LabelId::from(view.folder_name());
+            Ok(self
+                .threads
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, sender, labels)| senders.matches(sender) && labels.contains(&label))
+                .map(|(id, _, _)| id.clone())
+                .collect())
+        }
+    }
+
+    fn email_from(id: &str, sender: &str) -> Email {
+        Email {
+            id: EmailId::from(id),
+            account_id: AccountId::from("test"),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            message_id: MessageId::from(format!("<{}@example.com>", id)),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new(sender),
+            to: vec![],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("News".to_string()),
+            body_text: None,
+            body_html: None,
+            snippet: String::new(),
+            date: Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![system_labels::inbox()],
+            attachments: vec![],
+            headers: vec![],
+        }
     }
 
     #[tokio::test]
@@ -605,12 +1054,329 @@ mod tests {
         assert_eq!(entry.status, ScreenerStatus::Approved);
     }
 
+    #[tokio::test]
+    async fn decisions_are_per_account_unless_global() {
+        let storage = MockStorage::new();
+        let personal = ScreenerService::new(storage.shared(), AccountId::from("personal"));
+        let work = ScreenerService::new(storage, AccountId::from("work"));
+
+        let entry = personal
+            .add_sender("recruiter@agency.com", None, None)
+            .await
+            .unwrap();
+        personal.reject(&entry.id).await.unwrap();
+        personal.block_domain("spam.com").await.unwrap();
+
+        assert_eq!(
+            personal
+                .is_known_sender("recruiter@agency.com")
+                .await
+                .unwrap(),
+            Some(ScreenerStatus::Rejected)
+        );
+        assert_eq!(
+            work.is_known_sender("recruiter@agency.com").await.unwrap(),
+            None
+        );
+        assert_eq!(work.is_known_sender("a@spam.com").await.unwrap(), None);
+        assert!(matches!(
+            work.approve(&entry.id).await,
+            Err(ScreenerError::NotFound(_))
+        ));
+
+        let global = work
+            .add_rule(ScreenerRule::new(
+                RuleType::DomainAllow,
+                "partner.com",
+                ScreenerAction::Approve,
+            ))
+            .await
+            .unwrap();
+        assert!(global.is_global());
+        assert_eq!(
+            personal.is_known_sender("a@partner.com").await.unwrap(),
+            Some(ScreenerStatus::Approved)
+        );
+    }
+
+    #[tokio::test]
+    async fn header_rules_decide_emails() {
+        let storage = MockStorage::new();
+        let service = ScreenerService::new(storage, AccountId::from("test"));
+
+        service
+            .add_rule(
+                ScreenerRule::new(RuleType::Pattern, "*rust-digest*", ScreenerAction::Approve)
+                    .on_field(RuleField::ListId),
+            )
+            .await
+            .unwrap();
+
+        let mut email = Email {
+            id: EmailId::from("email-1"),
+            account_id: AccountId::from("test"),
+            thread_id: ThreadId::from("thread-1"),
+            message_id: MessageId::from("<email-1@example.org>"),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new("digest@lists.example.org"),
+            to: vec![],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("This week".to_string()),
+            body_text: None,
+            body_html: None,
+            snippet: String::new(),
+            date: Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![],
+            attachments: vec![],
+            headers: vec![Header::new("List-Id", "<rust-digest.lists.example.org>")],
+        };
+        let entry = service.add_email(&email).await.unwrap();
+        assert_eq!(entry.status, ScreenerStatus::Approved);
+        assert_eq!(entry.first_email_id, Some(EmailId::from("email-1")));
+
+        email.from = Address::new("other@lists.example.org");
+        email.headers.clear();
+        let entry = service.add_email(&email).await.unwrap();
+        assert_eq!(entry.status, ScreenerStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn invalid_rules_are_refused() {
+        let storage = MockStorage::new();
+        let service = ScreenerService::new(storage, AccountId::from("test"));
+
+        let result = service
+            .add_rule(ScreenerRule::new(
+                RuleType::Regex,
+                "[unclosed",
+                ScreenerAction::Reject,
+            ))
+            .await;
+        assert!(matches!(result, Err(ScreenerError::InvalidOperation(_))));
+        assert!(service.get_rules().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dry_run_reports_matches_and_shadowing() {
+        let mut storage = MockStorage::new();
+        storage.senders = ["deals@shop.com", "receipts@shop.com", "friend@home.net"]
+            .into_iter()
+            .map(ScreenedMessage::from_sender)
+            .collect();
+        let service = ScreenerService::new(storage, AccountId::from("test"));
+
+        service
+            .add_rule(
+                ScreenerRule::new(RuleType::Regex, "^receipts@", ScreenerAction::Approve)
+                    .with_priority(5),
+            )
+            .await
+            .unwrap();
+
+        let candidate =
+            ScreenerRule::new(RuleType::DomainBlock, "shop.com", ScreenerAction::Reject);
+        let result = service.dry_run(&candidate).await.unwrap();
+
+        assert_eq!(result.senders_tested, 3);
+        assert_eq!(result.matches.len(), 2);
+        assert_eq!(result.effective_matches(), 1);
+        let shadowed = result
+            .matches
+            .iter()
+            .find(|m| m.message.sender == "receipts@shop.com")
+            .unwrap();
+        assert!(shadowed.shadowed_by.is_some());
+        assert_eq!(service.get_rules().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rejection_screens_out_past_threads_until_reversed() {
+        let storage = Arc::new(MockEmailStorage::with_senders(&[
+            "promo@shop.com",
+            "friend@home.net",
+        ]));
+        let service = ScreenerService::new(MockStorage::new(), AccountId::from("test"))
+            .with_mailbox(Arc::new(EmailService::new(storage.clone())));
+        let screened_out = LabelId::from(ViewType::ScreenedOut.folder_name());
+
+        let entry = service
+            .add_sender("promo@shop.com", None, None)
+            .await
+            .unwrap();
+        service.reject(&entry.id).await.unwrap();
+        assert_eq!(storage.labels("thread-0"), vec![screened_out.clone()]);
+        assert_eq!(storage.labels("thread-1"), vec![system_labels::inbox()]);
+
+        service.approve(&entry.id).await.unwrap();
+        assert_eq!(storage.labels("thread-0"), vec![system_labels::inbox()]);
+    }
+
+    #[tokio::test]
+    async fn blocked_domains_screen_out_subdomains() {
+        let storage = Arc::new(MockEmailStorage::with_senders(&[
+            "deals@mail.shop.com",
+            "friend@home.net",
+        ]));
+        let service = ScreenerService::new(MockStorage::new(), AccountId::from("test"))
+            .with_mailbox(Arc::new(EmailService::new(storage.clone())));
+        let screened_out = LabelId::from(ViewType::ScreenedOut.folder_name());
+
+        let rule = service.block_domain("shop.com").await.unwrap();
+        assert_eq!(storage.labels("thread-0"), vec![screened_out]);
+        assert_eq!(storage.labels("thread-1"), vec![system_labels::inbox()]);
+
+        service.delete_rule(&rule.id).await.unwrap();
+        assert!(service.get_rules().await.unwrap().is_empty());
+        assert_eq!(storage.labels("thread-0"), vec![system_labels::inbox()]);
+    }
+
+    #[tokio::test]
+    async fn new_mail_is_screened_out_through_the_mailbox_once_stored() {
+        let storage = Arc::new(MockEmailStorage::with_senders(&[]));
+        let service = ScreenerService::new(MockStorage::new(), AccountId::from("test"))
+            .with_mailbox(Arc::new(EmailService::new(storage.clone())));
+        let screened_out = LabelId::from(ViewType::ScreenedOut.folder_name());
+        let entry = service
+            .add_sender("promo@shop.com", None, None)
+            .await
+            .unwrap();
+        service.reject(&entry.id).await.unwrap();
+
+        // Sync stores each email's thread between the filter hooks
+        let store = |thread: &str| {
+            storage.threads.lock().unwrap().push((
+                ThreadId::from(thread),
+                "promo@shop.com".to_string(),
+                vec![system_labels::inbox()],
+            ))
+        };
+
+        let mut stored = email_from("email-1", "promo@shop.com");
+        stored.thread_id = ThreadId::from("thread-1");
+        service.filter_incoming(&mut stored).await.unwrap();
+        store("thread-1");
+        service.after_store(&stored).await.unwrap();
+        assert_eq!(storage.labels("thread-1"), vec![screened_out]);
+
+        let mut failed = email_from("email-2", "promo@shop.com");
+        failed.thread_id = ThreadId::from("thread-2");
+        service.filter_incoming(&mut failed).await.unwrap();
+        service.discard(&failed).await.unwrap();
+        store("thread-2");
+        service.after_store(&failed).await.unwrap();
+        assert_eq!(storage.labels("thread-2"), vec![system_labels::inbox()]);
+    }
+
+    #[tokio::test]
+    async fn new_mail_from_rejected_senders_skips_the_inbox() {
+        let service = ScreenerService::new(MockStorage::new(), AccountId::from("test"));
+        let entry = service
+            .add_sender("promo@shop.com", None, None)
+            .await
+            .unwrap();
+        service.reject(&entry.id).await.unwrap();
+
+        let mut email = Email {
+            id: EmailId::from("email-1"),
+            account_id: AccountId::from("test"),
+            thread_id: ThreadId::from("thread-1"),
+            message_id: MessageId::from("<email-1@shop.com>"),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new("Promo@shop.com"),
+            to: vec![],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("Sale".to_string()),
+            body_text: None,
+            body_html: None,
+            snippet: String::new(),
+            date: Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![system_labels::inbox()],
+            attachments: vec![],
+            headers: vec![],
+        };
+        service.filter_incoming(&mut email).await.unwrap();
+        assert_eq!(
+            email.labels,
+            vec![LabelId::from(ViewType::ScreenedOut.folder_name())]
+        );
+
+        email.from = Address::new("friend@home.net");
+        email.labels = vec![system_labels::inbox()];
+        service.filter_incoming(&mut email).await.unwrap();
+        assert_eq!(email.labels, vec![system_labels::inbox()]);
+    }
+
+    #[tokio::test]
+    async fn sender_addresses_ignore_case() {
+        let service = ScreenerService::new(MockStorage::new(), AccountId::from("test"));
+        let mut email = email_from("email-1", "News@Foo.com");
+
+        let entry = service.add_email(&email).await.unwrap();
+        assert_eq!(entry.sender_email, "news@foo.com");
+        service.reject(&entry.id).await.unwrap();
+
+        email.from = Address::new("NEWS@foo.com");
+        assert_eq!(service.add_email(&email).await.unwrap().id, entry.id);
+        assert_eq!(
+            service.is_known_sender("news@FOO.com").await.unwrap(),
+            Some(ScreenerStatus::Rejected)
+        );
+        service.filter_incoming(&mut email).await.unwrap();
+        assert_eq!(
+            email.labels,
+            vec![LabelId::from(ViewType::ScreenedOut.folder_name())]
+        );
+    }
+
+    #[tokio::test]
+    async fn confident_analysis_auto_approves() {
+        let storage = MockStorage::new();
+        let service =
+            ScreenerService::new(storage, AccountId::from("test")).with_auto_approve_threshold(0.8);
+
+        let analysis = |confidence| SenderAnalysis {
+            likely_type: SenderType::Support,
+            reasoning: "Order confirmation".to_string(),
+            suggested_action: ScreenerAction::Approve,
+            confidence,
+        };
+
+        let unsure = service
+            .add_sender("orders@shop.com", None, None)
+            .await
+            .unwrap();
+        let unsure = service
+            .set_analysis(&unsure.id, analysis(0.6))
+            .await
+            .unwrap();
+        assert_eq!(unsure.status, ScreenerStatus::Pending);
+
+        let sure = service
+            .add_sender("receipts@shop.com", None, None)
+            .await
+            .unwrap();
+        let sure = service.set_analysis(&sure.id, analysis(0.9)).await.unwrap();
+        assert_eq!(sure.status, ScreenerStatus::Approved);
+        assert!(sure.decided_at.is_some());
+    }
+
     #[tokio::test]
     async fn filter_matching() {
         let filter = ScreenerFilter::pending().search("john");
 
         let entry = ScreenerEntry {
             id: "1".to_string(),
+            account_id: AccountId::from("test"),
             sender_email: "john@example.com".to_string(),
             sender_name: None,
             first_email_id: None,
@@ -0,0 +1,210 @@
+//! Offline evaluation of hybrid search ranking.
+//!
+//! A labelled query set records, for each query, the raw rankings returned
+//! by FTS5 and the embedding index together with the emails a person judged
+//! relevant. Replaying the recorded rankings through [`fuse_scores`] lets
+//! ranking strategies and their parameters be compared without a mailbox,
+//! a database or an embedding model.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::domain::EmailId;
+use crate::services::{fuse_scores, SearchSettings};
+
+/// A query with recorded backend rankings and relevance judgements.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelledQuery {
+    /// The search text.
+    pub query: String,
+    /// Emails judged relevant to the query.
+    pub relevant: Vec<EmailId>,
+    /// FTS5 hits as `(email, rank)`.
+    #[serde(default)]
+    pub fts: Vec<(EmailId, f32)>,
+    /// Semantic hits as `(email, cosine similarity)`.
+    #[serde(default)]
+    pub semantic: Vec<(EmailId, f32)>,
+}
+
+/// Loads a labelled query set from a JSON file.
+pub fn load_labelled_queries(path: impl AsRef<Path>) -> Result<Vec<LabelledQuery>> {
+    let path = path.as_ref();
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid query set {}", path.display()))
+}
+
+/// Ranking quality averaged over a query set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankingMetrics {
+    /// Number of queries evaluated.
+    pub queries: usize,
+    /// Cut-off used for nDCG and recall.
+    pub k: usize,
+    /// Mean reciprocal rank of the first relevant result.
+    pub mrr: f32,
+    /// Mean normalized discounted cumulative gain of the top `k`.
+    pub ndcg: f32,
+    /// Mean fraction of relevant emails found in the top `k`.
+    pub recall: f32,
+}
+
+/// Ranks every query with `settings` and scores the result against the
+/// relevance judgements.
+///
+/// Queries without relevant emails are skipped, since no ranking can be
+/// right or wrong for them.
+pub fn evaluate_ranking(
+    queries: &[LabelledQuery],
+    settings: &SearchSettings,
+    k: usize,
+) -> RankingMetrics {
+    let mut metrics = RankingMetrics {
+        queries: 0,
+        k,
+        mrr: 0.0,
+        ndcg: 0.0,
+        recall: 0.0,
+    };
+
+    for query in queries.iter().filter(|q| !q.relevant.is_empty()) {
+        let relevant: HashSet<&EmailId> = query.relevant.iter().collect();
+        let ranking: Vec<EmailId> = fuse_scores(&query.fts, &query.semantic, settings)
+            .into_iter()
+            .map(|f| f.email_id)
+            .collect();
+
+        metrics.queries += 1;
+        metrics.mrr += reciprocal_rank(&ranking, &relevant);
+        metrics.ndcg += ndcg_at(&ranking, &relevant, k);
+        metrics.recall += recall_at(&ranking, &relevant, k);
+    }
+
+    if metrics.queries > 0 {
+        let n = metrics.queries as f32;
+        metrics.mrr /= n;
+        metrics.ndcg /= n;
+        metrics.recall /= n;
+    }
+    metrics
+}
+
+fn reciprocal_rank(ranking: &[EmailId], relevant: &HashSet<&EmailId>) -> f32 {
+    ranking
+        .iter()
+        .position(|id| relevant.contains(id))
+        .map_or(0.0, |i| 1.0 / (i + 1) as f32)
+}
+
+fn ndcg_at(ranking: &[EmailId], relevant: &HashSet<&EmailId>, k: usize) -> f32 {
+    let discount = |i: usize| 1.0 / ((i + 2) as f32).log2();
+    let dcg: f32 = ranking
+        .iter()
+        .take(k)
+        .enumerate()
+        .filter(|(_, id)| relevant.contains(id))
+        .map(|(i, _)| discount(i))
+        .sum();
+    let ideal: f32 = (0..relevant.len().min(k)).map(discount).sum();
+    if ideal > 0.0 {
+        dcg / ideal
+    } else {
+        0.0
+    }
+}
+
+fn recall_at(ranking: &[EmailId], relevant: &HashSet<&EmailId>, k: usize) -> f32 {
+    let found = ranking
+        .iter()
+        .take(k)
+        .filter(|id| relevant.contains(id))
+        .count();
+    found as f32 / relevant.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::HybridStrategy;
+
+    fn ids(ids: &[&str]) -> Vec<EmailId> {
+        ids.iter().map(|id| EmailId::from(*id)).collect()
+    }
+
+    #[test]
+    fn metrics_for_perfect_and_missed_rankings() {
+        let relevant_ids = ids(&["a", "b"]);
+        let relevant: HashSet<&EmailId> = relevant_ids.iter().collect();
+
+        let perfect = ids(&["a", "b", "c"]);
+        assert_eq!(reciprocal_rank(&perfect, &relevant), 1.0);
+        assert!((ndcg_at(&perfect, &relevant, 10) - 1.0).abs() < 1e-6);
+        assert_eq!(recall_at(&perfect, &relevant, 10), 1.0);
+
+        let late = ids(&["c", "d", "a"]);
+        assert!((reciprocal_rank(&late, &relevant) - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(recall_at(&late, &relevant, 2), 0.0);
+        assert_eq!(recall_at(&late, &relevant, 3), 0.5);
+    }
+
+    #[test]
+    fn evaluate_compares_strategies() {
+        // Keyword search finds the invoice by its number; the semantic index
+        // ranks a loosely related email first.
+        let queries = vec![
+            LabelledQuery {
+                query: "invoice 4471".to_string(),
+                relevant: ids(&["invoice"]),
+                fts: vec![(EmailId::from("invoice"), -12.0)],
+                semantic: vec![
+                    (EmailId::from("receipt"), 0.62),
+                    (EmailId::from("invoice"), 0.58),
+                ],
+            },
+            LabelledQuery {
+                query: "unlabelled".to_string(),
+                relevant: Vec::new(),
+                fts: Vec::new(),
+                semantic: Vec::new(),
+            },
+        ];
+
+        let rrf = SearchSettings::default();
+        let metrics = evaluate_ranking(&queries, &rrf, 10);
+        assert_eq!(metrics.queries, 1);
+        assert_eq!(metrics.mrr, 1.0);
+        assert_eq!(metrics.recall, 1.0);
+
+        let semantic_only = SearchSettings {
+            hybrid_strategy: HybridStrategy::Weighted,
+            fts_weight: 0.0,
+            semantic_weight: 1.0,
+            min_score: 0.0,
+            ..SearchSettings::default()
+        };
+        let metrics = evaluate_ranking(&queries, &semantic_only, 10);
+        assert_eq!(metrics.mrr, 0.5);
+    }
+
+    #[test]
+    fn labelled_queries_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queries.json");
+        std::fs::write(
+            &path,
+            r#"[{"query": "q", "relevant": ["a"], "fts": [["a", -3.5]]}]"#,
+        )
+        .unwrap();
+
+        let queries = load_labelled_queries(&path).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].fts, vec![(EmailId::from("a"), -3.5)]);
+        assert!(queries[0].semantic.is_empty());
+
+        assert!(load_labelled_queries(dir.path().join("missing.json")).is_err());
+    }
+}
@@ -5,16 +5,18 @@
 //! - Semantic search via embeddings for conceptual similarity
 //! - Faceted filtering by folder, date range, sender, attachments
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 
-use crate::domain::{AccountId, EmailId, ThreadId};
-use crate::services::{AiService, SearchResult};
+use crate::domain::{AccountId, EmailId, LabelId, ThreadId};
+use crate::embedding::CrossEncoder;
+use crate::services::{AiService, SearchResult, SemanticSearchStorage};
 
 /// Search query with filters and options.
 #[derive(Debug, Clone, Default)]
@@ -108,6 +110,72 @@ impl SearchQuery {
         self.mode = mode;
         self
     }
+
+    /// Returns whether any filter beyond the search text is set.
+    pub fn has_filters(&self) -> bool {
+        !self.account_ids.is_empty()
+            || self
+                .folder
+                .as_ref()
+                .is_some_and(|f| *f != SearchFolder::All)
+            || self.date_range.is_some()
+            || self.from.is_some()
+            || self.to.is_some()
+            || self.has_attachment.is_some()
+            || self.is_unread.is_some()
+            || self.is_starred.is_some()
+    }
+
+    /// Returns whether an email passes the query's filters.
+    ///
+    /// Used for results that do not come from FTS, such as semantic hits,
+    /// so every search mode applies the same predicates.
+    pub fn matches(&self, meta: &EmailMetadata) -> bool {
+        if !self.account_ids.is_empty() && !self.account_ids.contains(&meta.account_id) {
+            return false;
+        }
+        if let Some(folder) = &self.folder {
+            if !folder.contains(meta) {
+                return false;
+            }
+        }
+        if let Some(range) = &self.date_range {
+            if meta.date < range.start || meta.date > range.end {
+                return false;
+            }
+        }
+        if let Some(from) = &self.from {
+            if !contains_ignore_case(&meta.from, from) {
+                return false;
+            }
+        }
+        if let Some(to) = &self.to {
+            if !meta.to.iter().any(|addr| contains_ignore_case(addr, to)) {
+                return false;
+            }
+        }
+        if self
+            .has_attachment
+            .is_some_and(|wanted| meta.has_attachments != wanted)
+        {
+            return false;
+        }
+        if self.is_unread.is_some_and(|wanted| meta.is_read == wanted) {
+            return false;
+        }
+        if self
+            .is_starred
+            .is_some_and(|wanted| meta.is_starred != wanted)
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// Case-insensitive substring match used for address filters.
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
 }
 
 /// Folder to filter search results.
@@ -129,6 +197,25 @@ pub enum SearchFolder {
     Label(String),
 }
 
+impl SearchFolder {
+    /// Returns whether an email is in this folder.
+    ///
+    /// System folders are matched by label name regardless of case, so both
+    /// Gmail (`INBOX`) and IMAP-style (`inbox`) label IDs work.
+    pub fn contains(&self, meta: &EmailMetadata) -> bool {
+        let has_label = |name: &str| meta.labels.iter().any(|l| l.0.eq_ignore_ascii_case(name));
+        match self {
+            Self::All => true,
+            Self::Inbox => has_label("inbox"),
+            Self::Sent => has_label("sent"),
+            Self::Drafts => meta.is_draft || has_label("drafts") || has_label("draft"),
+            Self::Archive => !has_label("inbox") && !has_label("trash"),
+            Self::Trash => has_label("trash"),
+            Self::Label(label) => meta.labels.iter().any(|l| l.0 == *label),
+        }
+    }
+}
+
 /// Date range filter.
 #[derive(Debug, Clone)]
 pub struct DateRange {
@@ -173,6 +260,9 @@ pub struct SearchHit {
     pub source: SearchSource,
     /// Highlighted matching segments.
     pub highlights: Vec<String>,
+    /// Relevance assigned by the re-ranker, if the hit was re-ranked.
+    #[serde(default)]
+    pub rerank_score: Option<f32>,
 }
 
 /// Source of a search result.
@@ -199,6 +289,8 @@ pub struct SearchResults {
     pub took_ms: u64,
     /// Whether semantic search was used.
     pub used_semantic: bool,
+    /// Whether the top hits were re-ranked.
+    pub reranked: bool,
 }
 
 /// Storage trait for search operations.
@@ -214,6 +306,31 @@ pub trait SearchStorage: Send + Sync {
     async fn rebuild_fts_index(&self, account_id: &AccountId) -> Result<()>;
 }
 
+/// Re-scores the top search hits against the query.
+///
+/// Scoring is CPU-bound, so the search service runs it on the blocking
+/// thread pool.
+pub trait Reranker: Send + Sync {
+    /// Scores each passage's relevance to the query, from 0.0 to 1.0.
+    ///
+    /// Scoring stops at `deadline`; the returned scores cover the leading
+    /// passages that were scored in time.
+    fn score(&self, query: &str, passages: &[&str], deadline: Instant) -> Result<Vec<f32>>;
+}
+
+impl Reranker for CrossEncoder {
+    fn score(&self, query: &str, passages: &[&str], deadline: Instant) -> Result<Vec<f32>> {
+        CrossEncoder::score(self, query, passages, deadline)
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: SearchStorage> SemanticSearchStorage for S {
+    async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>> {
+        SearchStorage::get_email_metadata(self, ids).await
+    }
+}
+
 /// Raw FTS hit from database.
 #[derive(Debug, Clone)]
 pub struct FtsHit {
@@ -228,10 +345,15 @@ pub struct FtsHit {
 }
 
 /// Email metadata for search results.
+///
+/// Carries the fields [`SearchQuery::matches`] filters on as well as what
+/// is displayed in a result.
 #[derive(Debug, Clone)]
 pub struct EmailMetadata {
     /// Email ID.
     pub email_id: EmailId,
+    /// Account the email belongs to.
+    pub account_id: AccountId,
     /// Thread ID.
     pub thread_id: ThreadId,
     /// Subject.
@@ -242,35 +364,208 @@ pub struct EmailMetadata {
     pub from: String,
     /// Date.
     pub date: DateTime<Utc>,
+    /// Recipient addresses.
+    pub to: Vec<String>,
     /// Read status.
     pub is_read: bool,
+    /// Starred status.
+    pub is_starred: bool,
+    /// Whether the email is a draft.
+    pub is_draft: bool,
+    /// Whether the email has attachments.
+    pub has_attachments: bool,
+    /// Labels applied to the email.
+    pub labels: Vec<LabelId>,
+}
+
+/// How full-text and semantic rankings are combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HybridStrategy {
+    /// Weighted sum of scores normalized to 0.0-1.0.
+    Weighted,
+    /// Reciprocal Rank Fusion: combines positions in each ranking and
+    /// ignores the raw scores, so no weights need tuning.
+    #[default]
+    ReciprocalRankFusion,
 }
 
 /// Search service settings.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SearchSettings {
     /// Whether semantic search is enabled.
     pub semantic_enabled: bool,
-    /// Weight for FTS results in hybrid mode (0.0-1.0).
+    /// How results are combined in hybrid mode.
+    pub hybrid_strategy: HybridStrategy,
+    /// Weight for FTS results with the weighted strategy (0.0-1.0).
     pub fts_weight: f32,
-    /// Weight for semantic results in hybrid mode (0.0-1.0).
+    /// Weight for semantic results with the weighted strategy (0.0-1.0).
     pub semantic_weight: f32,
-    /// Minimum score threshold.
+    /// Rank offset for Reciprocal Rank Fusion. Larger values flatten the
+    /// advantage of the top few results.
+    pub rrf_k: f32,
+    /// Minimum normalized score with the weighted strategy. Rank fusion
+    /// scores depend only on positions, so no threshold is applied to them.
     pub min_score: f32,
     /// Default result limit.
     pub default_limit: usize,
+    /// Whether the top hits are re-scored with the cross-encoder.
+    pub rerank_enabled: bool,
+    /// Number of top hits the cross-encoder re-scores.
+    pub rerank_top_n: usize,
+    /// Time the re-ranker may spend per search, in milliseconds. Hits not
+    /// scored in time keep their hybrid ranking.
+    pub rerank_budget_ms: u64,
 }
 
 impl Default for SearchSettings {
     fn default() -> Self {
         Self {
             semantic_enabled: true,
+            hybrid_strategy: HybridStrategy::default(),
             fts_weight: 0.6,
             semantic_weight: 0.4,
+            rrf_k: 60.0,
             min_score: 0.3,
             default_limit: 50,
+            rerank_enabled: false,
+            rerank_top_n: 20,
+            rerank_budget_ms: 200,
+        }
+    }
+}
+
+/// An email's combined score across rankings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FusedScore {
+    /// Email ID.
+    pub email_id: EmailId,
+    /// Combined score (0.0-1.0).
+    pub score: f32,
+    /// Which rankings contained the email.
+    pub source: SearchSource,
+}
+
+/// Maps an FTS5 `rank` to 0.0-1.0.
+///
+/// FTS5 reports bm25 negated, so better matches are more negative and the
+/// magnitude is unbounded. `s / (1 + s)` keeps the order and saturates
+/// towards 1.0 for strong matches.
+pub fn normalize_fts_rank(rank: f32) -> f32 {
+    let strength = (-rank).max(0.0);
+    strength / (1.0 + strength)
+}
+
+/// Combines FTS and semantic rankings into one ranking.
+///
+/// Both inputs are `(email, score)` lists as returned by their backend:
+/// FTS5 `rank` values and cosine similarities. Lists are ranked by score,
+/// so their order does not matter. An empty list does not count against
+/// the emails in the other one, which makes single-mode searches score on
+/// the same 0.0-1.0 scale.
+pub fn fuse_scores(
+    fts: &[(EmailId, f32)],
+    semantic: &[(EmailId, f32)],
+    settings: &SearchSettings,
+) -> Vec<FusedScore> {
+    let fts = ranked(fts, normalize_fts_rank);
+    let semantic = ranked(semantic, |similarity| similarity.clamp(0.0, 1.0));
+
+    // Maximum contribution of each ranking, so a result at the top of every
+    // ranking scores 1.0
+    let weight_of = |list: &[(EmailId, f32)], weight: f32| {
+        if list.is_empty() {
+            0.0
+        } else {
+            weight
+        }
+    };
+    let (fts_weight, semantic_weight) = match settings.hybrid_strategy {
+        HybridStrategy::Weighted => (settings.fts_weight, settings.semantic_weight),
+        HybridStrategy::ReciprocalRankFusion => (1.0, 1.0),
+    };
+    let total_weight = weight_of(&fts, fts_weight) + weight_of(&semantic, semantic_weight);
+    if total_weight <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut fused: HashMap<EmailId, (f32, bool, bool)> = HashMap::new();
+    for (is_fts, list, weight) in [
+        (true, &fts, fts_weight),
+        (false, &semantic, semantic_weight),
+    ] {
+        // A zero weight turns a ranking off rather than keeping its emails
+        // at the bottom
+        if weight <= 0.0 {
+            continue;
+        }
+        for (position, (email_id, score)) in list.iter().enumerate() {
+            let contribution = match settings.hybrid_strategy {
+                HybridStrategy::Weighted => weight * score,
+                HybridStrategy::ReciprocalRankFusion => {
+                    (settings.rrf_k + 1.0) / (settings.rrf_k + 1.0 + position as f32)
+                }
+            };
+            let entry = fused.entry(email_id.clone()).or_default();
+            entry.0 += contribution;
+            if is_fts {
+                entry.1 = true;
+            } else {
+                entry.2 = true;
+            }
         }
     }
+
+    let mut results: Vec<FusedScore> = fused
+        .into_iter()
+        .map(|(email_id, (score, in_fts, in_semantic))| {
+            let source = match (in_fts, in_semantic) {
+                (true, true) => SearchSource::Both,
+                (true, false) => SearchSource::FullText,
+                (false, _) => SearchSource::Semantic,
+            };
+            FusedScore {
+                email_id,
+                score: score / total_weight,
+                source,
+            }
+        })
+        .filter(|f| {
+            settings.hybrid_strategy != HybridStrategy::Weighted || f.score >= settings.min_score
+        })
+        .collect();
+
+    // Sort by score descending, then by ID so ties are stable across runs
+    results.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.email_id.0.cmp(&b.email_id.0))
+    });
+    results
+}
+
+/// Normalizes scores and sorts best first, keeping one entry per email.
+fn ranked(list: &[(EmailId, f32)], normalize: impl Fn(f32) -> f32) -> Vec<(EmailId, f32)> {
+    let mut seen = HashSet::new();
+    let mut ranked: Vec<(EmailId, f32)> = list
+        .iter()
+        .map(|(id, score)| (id.clone(), normalize(*score)))
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    ranked.retain(|(id, _)| seen.insert(id.clone()));
+    ranked
+}
+
+/// Text the re-ranker compares with the query: the subject and the best
+/// matching passage, or the snippet.
+fn rerank_passage(hit: &SearchHit) -> String {
+    let body = hit.highlights.first().unwrap_or(&hit.snippet);
+    match &hit.subject {
+        Some(subject) => format!("{}\n{}", subject, body),
+        None => body.clone(),
+    }
 }
 
 /// Search service combining full-text and semantic search.
@@ -283,6 +578,8 @@ pub struct SearchService<S: SearchStorage> {
     storage: Arc<S>,
     /// AI service for semantic search.
     ai_service: Option<Arc<AiService>>,
+    /// Cross-encoder for re-ranking top hits.
+    reranker: Option<Arc<dyn Reranker>>,
     /// Search settings.
     settings: RwLock<SearchSettings>,
     /// Recent queries for suggestions.
@@ -295,6 +592,7 @@ impl<S: SearchStorage> SearchService<S> {
         Self {
             storage,
             ai_service: None,
+            reranker: None,
             settings: RwLock::new(SearchSettings::default()),
             recent_queries: RwLock::new(Vec::new()),
         }
@@ -306,6 +604,12 @@ impl<S: SearchStorage> SearchService<S> {
         self
     }
 
+    /// Sets the re-ranker used when `rerank_enabled` is set.
+    pub fn with_reranker(mut self, reranker: Arc<dyn Reranker>) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
     /// Updates search settings.
     pub async fn update_settings(&self, settings: SearchSettings) {
         let mut current = self.settings.write().await;
@@ -357,9 +661,10 @@ impl<S: SearchStorage> SearchService<S> {
         let used_semantic = !semantic_hits.is_empty();
 
         // Merge and rank results
-        let merged = self
+        let mut merged = self
             .merge_results(&fts_hits, &semantic_hits, &settings)
             .await?;
+        let reranked = self.rerank(&query.text, &mut merged, &settings).await;
 
         // Apply pagination
         let total = merged.len();
@@ -377,6 +682,7 @@ impl<S: SearchStorage> SearchService<S> {
             query: query.text,
             took_ms,
             used_semantic,
+            reranked,
         })
     }
 
@@ -387,9 +693,7 @@ impl<S: SearchStorage> SearchService<S> {
             None => return Ok(vec![]),
         };
 
-        ai_service
-            .semantic_search(&query.text, &query.account_ids)
-            .await
+        ai_service.semantic_search(query).await
     }
 
     /// Merges FTS and semantic results with ranking.
@@ -399,98 +703,117 @@ impl<S: SearchStorage> SearchService<S> {
         semantic_hits: &[SearchResult],
         settings: &SearchSettings,
     ) -> Result<Vec<SearchHit>> {
-        // Collect all unique email IDs
-        let mut email_ids: HashSet<EmailId> = HashSet::new();
-        for hit in fts_hits {
-            email_ids.insert(hit.email_id.clone());
-        }
-        for hit in semantic_hits {
-            email_ids.insert(hit.email_id.clone());
-        }
+        let fts: Vec<(EmailId, f32)> = fts_hits
+            .iter()
+            .map(|h| (h.email_id.clone(), h.rank))
+            .collect();
+        let semantic: Vec<(EmailId, f32)> = semantic_hits
+            .iter()
+            .map(|h| (h.email_id.clone(), h.relevance))
+            .collect();
+        let fused = fuse_scores(&fts, &semantic, settings);
 
-        if email_ids.is_empty() {
+        if fused.is_empty() {
             return Ok(vec![]);
         }
 
         // Fetch metadata for all emails
-        let ids: Vec<EmailId> = email_ids.into_iter().collect();
+        let ids: Vec<EmailId> = fused.iter().map(|f| f.email_id.clone()).collect();
         let metadata = self.storage.get_email_metadata(&ids).await?;
-        let metadata_map: std::collections::HashMap<EmailId, EmailMetadata> = metadata
+        let mut metadata_map: HashMap<EmailId, EmailMetadata> = metadata
             .into_iter()
             .map(|m| (m.email_id.clone(), m))
             .collect();
 
-        // Build scored results
-        let mut results: Vec<SearchHit> = Vec::new();
+        // Build results in fused order, skipping emails deleted since indexing
+        let results = fused
+            .into_iter()
+            .filter_map(|fused| {
+                let meta = metadata_map.remove(&fused.email_id)?;
+
+                // Get snippet/highlights from FTS if available
+                let snippet = fts_hits
+                    .iter()
+                    .find(|h| h.email_id == fused.email_id)
+                    .map(|h| h.snippet.clone())
+                    .unwrap_or(meta.snippet);
+
+                let highlights: Vec<String> = semantic_hits
+                    .iter()
+                    .find(|h| h.email_id == fused.email_id)
+                    .map(|h| h.highlights.clone())
+                    .unwrap_or_default();
+
+                Some(SearchHit {
+                    email_id: fused.email_id,
+                    thread_id: meta.thread_id,
+                    subject: meta.subject,
+                    snippet,
+                    from: meta.from,
+                    date: meta.date,
+                    is_read: meta.is_read,
+                    score: fused.score,
+                    source: fused.source,
+                    highlights,
+                    rerank_score: None,
+                })
+            })
+            .collect();
 
-        // Process FTS results
-        let mut fts_map: std::collections::HashMap<EmailId, f32> = std::collections::HashMap::new();
-        for hit in fts_hits {
-            fts_map.insert(hit.email_id.clone(), hit.rank);
-        }
+        Ok(results)
+    }
 
-        // Process semantic results
-        let mut semantic_map: std::collections::HashMap<EmailId, f32> =
-            std::collections::HashMap::new();
-        for hit in semantic_hits {
-            semantic_map.insert(hit.email_id.clone(), hit.relevance);
+    /// Re-scores the top hits with the re-ranker within the latency budget.
+    ///
+    /// Hits scored in time are reordered among themselves and the rest keep
+    /// their hybrid order. If re-ranking fails, the hybrid order is kept.
+    async fn rerank(&self, query: &str, hits: &mut [SearchHit], settings: &SearchSettings) -> bool {
+        let Some(reranker) = self.reranker.clone() else {
+            return false;
+        };
+        if !settings.rerank_enabled || query.trim().is_empty() || hits.len() < 2 {
+            return false;
         }
 
-        // Combine scores
-        for (email_id, meta) in &metadata_map {
-            let fts_score = fts_map.get(email_id).copied().unwrap_or(0.0);
-            let semantic_score = semantic_map.get(email_id).copied().unwrap_or(0.0);
+        let top_n = settings.rerank_top_n.min(hits.len());
+        let passages: Vec<String> = hits[..top_n].iter().map(rerank_passage).collect();
+        let query = query.to_string();
+        let deadline = Instant::now() + Duration::from_millis(settings.rerank_budget_ms);
 
-            let source = match (fts_score > 0.0, semantic_score > 0.0) {
-                (true, true) => SearchSource::Both,
-                (true, false) => SearchSource::FullText,
-                (false, true) => SearchSource::Semantic,
-                (false, false) => continue,
-            };
-
-            // Calculate combined score
-            let combined_score =
-                (fts_score * settings.fts_weight) + (semantic_score * settings.semantic_weight);
-
-            if combined_score < settings.min_score {
-                continue;
+        let scored = tokio::task::spawn_blocking(move || {
+            let passages: Vec<&str> = passages.iter().map(String::as_str).collect();
+            reranker.score(&query, &passages, deadline)
+        })
+        .await;
+        let scores = match scored {
+            Ok(Ok(scores)) => scores,
+            Ok(Err(e)) => {
+                tracing::warn!(error = %e, "Re-ranking failed");
+                return false;
             }
+            Err(e) => {
+                tracing::warn!(error = %e, "Re-ranking task failed");
+                return false;
+            }
+        };
 
-            // Get snippet/highlights from FTS if available
-            let snippet = fts_hits
-                .iter()
-                .find(|h| h.email_id == *email_id)
-                .map(|h| h.snippet.clone())
-                .unwrap_or_else(|| meta.snippet.clone());
-
-            let highlights: Vec<String> = semantic_hits
-                .iter()
-                .find(|h| h.email_id == *email_id)
-                .map(|h| h.highlights.clone())
-                .unwrap_or_default();
-
-            results.push(SearchHit {
-                email_id: email_id.clone(),
-                thread_id: meta.thread_id.clone(),
-                subject: meta.subject.clone(),
-                snippet,
-                from: meta.from.clone(),
-                date: meta.date,
-                is_read: meta.is_read,
-                score: combined_score,
-                source,
-                highlights,
-            });
+        let scored = scores.len().min(top_n);
+        if scored < top_n {
+            tracing::debug!(scored, top_n, "Re-ranking ran out of its latency budget");
+        }
+        if scored == 0 {
+            return false;
         }
 
-        // Sort by score descending
-        results.sort_by(|a, b| {
-            b.score
-                .partial_cmp(&a.score)
+        for (hit, score) in hits[..scored].iter_mut().zip(scores) {
+            hit.rerank_score = Some(score);
+        }
+        hits[..scored].sort_by(|a, b| {
+            b.rerank_score
+                .partial_cmp(&a.rerank_score)
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
-
-        Ok(results)
+        true
     }
 
     /// Tracks a query for suggestions.
@@ -577,6 +900,278 @@ mod tests {
         matches!(label, SearchFolder::Label(_));
     }
 
+    fn metadata(labels: &[&str]) -> EmailMetadata {
+        EmailMetadata {
+            email_id: EmailId::from("email-1"),
+            account_id: AccountId::from("work"),
+            thread_id: ThreadId::from("thread-1"),
+            subject: Some("Flight details".to_string()),
+            snippet: "Your flight departs at 9".to_string(),
+            from: "Travel Desk <travel@example.com>".to_string(),
+            date: Utc::now(),
+            to: vec!["me@example.com".to_string()],
+            is_read: true,
+            is_starred: false,
+            is_draft: false,
+            has_attachments: true,
+            labels: labels.iter().map(|l| LabelId::from(*l)).collect(),
+        }
+    }
+
+    #[test]
+    fn search_query_matches_filters() {
+        let meta = metadata(&["INBOX", "travel"]);
+
+        assert!(SearchQuery::new("flight").matches(&meta));
+        assert!(SearchQuery::new("flight")
+            .with_accounts(vec![AccountId::from("work")])
+            .with_from("TRAVEL@example.com")
+            .with_to("me@")
+            .with_attachment(true)
+            .matches(&meta));
+        assert!(!SearchQuery::new("flight")
+            .with_accounts(vec![AccountId::from("personal")])
+            .matches(&meta));
+        assert!(!SearchQuery::new("flight")
+            .with_from("bob@example.com")
+            .matches(&meta));
+
+        let mut unread = SearchQuery::new("flight");
+        unread.is_unread = Some(true);
+        assert!(!unread.matches(&meta));
+
+        let now = Utc::now();
+        assert!(!SearchQuery::new("flight")
+            .with_date_range(
+                now - chrono::Duration::days(10),
+                now - chrono::Duration::days(1)
+            )
+            .matches(&meta));
+    }
+
+    #[test]
+    fn search_folder_contains() {
+        let inbox = metadata(&["INBOX", "travel"]);
+        let archived = metadata(&["travel"]);
+
+        assert!(SearchFolder::Inbox.contains(&inbox));
+        assert!(!SearchFolder::Inbox.contains(&archived));
+        assert!(SearchFolder::Archive.contains(&archived));
+        assert!(!SearchFolder::Archive.contains(&inbox));
+        assert!(SearchFolder::Label("travel".to_string()).contains(&archived));
+        assert!(!SearchFolder::Trash.contains(&inbox));
+        assert!(SearchFolder::All.contains(&inbox));
+    }
+
+    #[test]
+    fn search_query_has_filters() {
+        assert!(!SearchQuery::new("flight").has_filters());
+        assert!(!SearchQuery::new("flight")
+            .with_folder(SearchFolder::All)
+            .has_filters());
+        assert!(SearchQuery::new("flight")
+            .with_folder(SearchFolder::Inbox)
+            .has_filters());
+    }
+
+    fn hits(hits: &[(&str, f32)]) -> Vec<(EmailId, f32)> {
+        hits.iter()
+            .map(|(id, score)| (EmailId::from(*id), *score))
+            .collect()
+    }
+
+    #[test]
+    fn fts_rank_normalization_is_bounded_and_ordered() {
+        assert_eq!(normalize_fts_rank(0.0), 0.0);
+        assert_eq!(normalize_fts_rank(1.5), 0.0);
+        assert!(normalize_fts_rank(-2.0) < normalize_fts_rank(-10.0));
+        assert!(normalize_fts_rank(-1000.0) < 1.0);
+    }
+
+    #[test]
+    fn weighted_fusion_uses_normalized_scores() {
+        let settings = SearchSettings {
+            hybrid_strategy: HybridStrategy::Weighted,
+            min_score: 0.0,
+            ..SearchSettings::default()
+        };
+        let fused = fuse_scores(
+            &hits(&[("a", -9.0), ("b", -1.0)]),
+            &hits(&[("b", 0.9)]),
+            &settings,
+        );
+
+        assert_eq!(fused[0].email_id, EmailId::from("b"));
+        assert_eq!(fused[0].source, SearchSource::Both);
+        assert!((fused[0].score - (0.6 * 0.5 + 0.4 * 0.9)).abs() < 1e-6);
+        assert_eq!(fused[1].source, SearchSource::FullText);
+        assert!((fused[1].score - 0.6 * 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weighted_fusion_applies_min_score() {
+        let settings = SearchSettings {
+            hybrid_strategy: HybridStrategy::Weighted,
+            ..SearchSettings::default()
+        };
+        let fused = fuse_scores(&hits(&[("a", -9.0), ("b", -0.1)]), &[], &settings);
+
+        // Without semantic hits, FTS scores are not scaled down by its weight
+        assert_eq!(fused.len(), 1);
+        assert!((fused[0].score - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rank_fusion_ignores_score_scales() {
+        let settings = SearchSettings::default();
+        assert_eq!(
+            settings.hybrid_strategy,
+            HybridStrategy::ReciprocalRankFusion
+        );
+
+        let fused = fuse_scores(
+            &hits(&[("a", -30.0), ("b", -29.0), ("c", -0.5)]),
+            &hits(&[("c", 0.31), ("d", 0.30)]),
+            &settings,
+        );
+        let order: Vec<&str> = fused.iter().map(|f| f.email_id.0.as_str()).collect();
+
+        // "b" and "d" are both second in one list; ties are broken by ID
+        assert_eq!(order, vec!["c", "a", "b", "d"]);
+        assert!(fused.iter().all(|f| f.score > 0.0 && f.score <= 1.0));
+    }
+
+    #[test]
+    fn rank_fusion_top_of_both_lists_scores_one() {
+        let fused = fuse_scores(
+            &hits(&[("a", -3.0)]),
+            &hits(&[("a", 0.5)]),
+            &SearchSettings::default(),
+        );
+        assert!((fused[0].score - 1.0).abs() < 1e-6);
+    }
+
+    /// Storage whose FTS index returns fixed hits, best first.
+    struct MockSearchStorage {
+        hits: Vec<(&'static str, &'static str)>,
+    }
+
+    #[async_trait::async_trait]
+    impl SearchStorage for MockSearchStorage {
+        async fn fts_search(&self, _query: &SearchQuery) -> Result<Vec<FtsHit>> {
+            Ok(self
+                .hits
+                .iter()
+                .enumerate()
+                .map(|(i, (id, _))| FtsHit {
+                    email_id: EmailId::from(*id),
+                    thread_id: ThreadId::from(*id),
+                    rank: -10.0 + i as f32,
+                    snippet: String::new(),
+                })
+                .collect())
+        }
+
+        async fn get_email_metadata(&self, ids: &[EmailId]) -> Result<Vec<EmailMetadata>> {
+            Ok(self
+                .hits
+                .iter()
+                .filter(|(id, _)| ids.contains(&EmailId::from(*id)))
+                .map(|(id, subject)| EmailMetadata {
+                    email_id: EmailId::from(*id),
+                    subject: Some(subject.to_string()),
+                    ..metadata(&["INBOX"])
+                })
+                .collect())
+        }
+
+        async fn rebuild_fts_index(&self, _account_id: &AccountId) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Re-ranker that prefers passages mentioning Berlin.
+    struct KeywordReranker;
+
+    impl Reranker for KeywordReranker {
+        fn score(&self, _query: &str, passages: &[&str], deadline: Instant) -> Result<Vec<f32>> {
+            Ok(passages
+                .iter()
+                .take_while(|_| Instant::now() < deadline)
+                .map(|p| if p.contains("Berlin") { 0.9 } else { 0.1 })
+                .collect())
+        }
+    }
+
+    async fn rerank_service(settings: SearchSettings) -> SearchService<MockSearchStorage> {
+        let storage = MockSearchStorage {
+            hits: vec![
+                ("a", "Invoice for office supplies"),
+                ("b", "Conference schedule"),
+                ("c", "Invoice: Berlin conference registration"),
+            ],
+        };
+        let service =
+            SearchService::new(Arc::new(storage)).with_reranker(Arc::new(KeywordReranker));
+        service.update_settings(settings).await;
+        service
+    }
+
+    fn hit_ids(results: &SearchResults) -> Vec<&str> {
+        results.hits.iter().map(|h| h.email_id.0.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn reranker_reorders_top_hits() {
+        let service = rerank_service(SearchSettings {
+            rerank_enabled: true,
+            ..SearchSettings::default()
+        })
+        .await;
+
+        let results = service
+            .search(SearchQuery::new("the invoice from the Berlin conference"))
+            .await
+            .unwrap();
+
+        assert!(results.reranked);
+        assert_eq!(hit_ids(&results)[0], "c");
+        assert_eq!(results.hits[0].rerank_score, Some(0.9));
+    }
+
+    #[tokio::test]
+    async fn reranker_only_rescores_top_n() {
+        let service = rerank_service(SearchSettings {
+            rerank_enabled: true,
+            rerank_top_n: 2,
+            ..SearchSettings::default()
+        })
+        .await;
+
+        let results = service.search(SearchQuery::new("invoice")).await.unwrap();
+
+        assert_eq!(hit_ids(&results), vec!["a", "b", "c"]);
+        assert!(results.hits[2].rerank_score.is_none());
+    }
+
+    #[tokio::test]
+    async fn reranker_respects_budget_and_toggle() {
+        let service = rerank_service(SearchSettings {
+            rerank_enabled: true,
+            rerank_budget_ms: 0,
+            ..SearchSettings::default()
+        })
+        .await;
+        let results = service.search(SearchQuery::new("invoice")).await.unwrap();
+        assert!(!results.reranked);
+        assert_eq!(hit_ids(&results), vec!["a", "b", "c"]);
+
+        let service = rerank_service(SearchSettings::default()).await;
+        let results = service.search(SearchQuery::new("invoice")).await.unwrap();
+        assert!(!results.reranked);
+        assert_eq!(hit_ids(&results), vec!["a", "b", "c"]);
+    }
+
     #[test]
     fn search_source_serialization() {
         let source = SearchSource::Both;
@@ -598,6 +1193,7 @@ mod tests {
             score: 0.85,
             source: SearchSource::FullText,
             highlights: vec!["matching".to_string()],
+            rerank_score: None,
         };
 
         let json = serde_json::to_string(&hit).unwrap();
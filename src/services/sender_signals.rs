@@ -0,0 +1,469 @@
+//! Sender signals for the screener.
+//!
+//! Extracts evidence about an unknown sender from the first email they sent:
+//! bulk-mail headers, email service provider (ESP) fingerprints, reply-to
+//! mismatches, prior correspondence and domain heuristics. The signals are
+//! shown to the model during sender analysis and used to calibrate the
+//! confidence it reports.
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Contact, Email, ScreenerAction, SenderAnalysis, SenderType};
+
+/// Header and domain fragments that identify bulk email service providers.
+const ESP_FINGERPRINTS: &[(&str, &[&str])] = &[
+    ("SendGrid", &["x-sg-eid", "sendgrid.net"]),
+    (
+        "Mailchimp",
+        &["x-mc-user", "mcsv.net", "mailchimp", "mandrillapp.com"],
+    ),
+    ("Mailgun", &["x-mailgun", "mailgun.org", "mailgun.net"]),
+    ("Amazon SES", &["amazonses.com", "x-ses-outgoing"]),
+    ("Postmark", &["x-pm-message-id", "mtasv.net"]),
+    ("HubSpot", &["hubspotemail.net"]),
+    ("SparkPost", &["sparkpostmail.com"]),
+    ("Brevo", &["sendinblue.com", "brevo.com"]),
+    ("Campaign Monitor", &["createsend.com"]),
+    ("Klaviyo", &["klaviyomail.com"]),
+];
+
+/// Headers searched for ESP fingerprints.
+const ESP_HEADERS: &[&str] = &[
+    "Return-Path",
+    "Received",
+    "DKIM-Signature",
+    "List-Unsubscribe",
+    "List-Id",
+    "Feedback-ID",
+    "Message-ID",
+];
+
+/// Providers whose domains are long established and shared by many senders.
+const WELL_KNOWN_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "yahoo.com",
+    "icloud.com",
+    "me.com",
+    "proton.me",
+    "protonmail.com",
+    "fastmail.com",
+    "aol.com",
+    "gmx.com",
+];
+
+/// Top-level domains that are cheap to register and common in throwaway domains.
+const THROWAWAY_TLDS: &[&str] = &[
+    "xyz", "top", "click", "icu", "buzz", "rest", "monster", "cyou", "sbs", "cfd", "quest", "bond",
+];
+
+/// Local parts of addresses that do not accept replies.
+const NO_REPLY_LOCAL_PARTS: &[&str] = &["noreply", "no-reply", "donotreply", "do-not-reply"];
+
+/// Number of body characters included in the analysis prompt.
+const BODY_EXCERPT_CHARS: usize = 800;
+
+/// Heuristic estimate of how established a sender's domain is.
+///
+/// No registry lookup is made; the estimate comes from correspondence with
+/// the domain and from how the domain name looks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainAge {
+    /// Known provider, or the user has contacts at the domain.
+    Established,
+    /// No evidence either way.
+    Unknown,
+    /// The name looks freshly registered (throwaway TLD, punycode, digit runs).
+    LikelyNew,
+}
+
+/// Evidence about a sender gathered from their first email.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SenderSignals {
+    /// Lowercased sender address.
+    pub sender: String,
+    /// Lowercased sender domain.
+    pub domain: String,
+    /// The email has a List-Unsubscribe header.
+    pub list_unsubscribe: bool,
+    /// Precedence is bulk, list or junk, or the email was auto-submitted.
+    pub bulk_precedence: bool,
+    /// Value of the List-Id header.
+    pub list_id: Option<String>,
+    /// Bulk email service provider the email was sent through.
+    pub esp: Option<String>,
+    /// Reply-To points at a different domain than From.
+    pub reply_to_mismatch: bool,
+    /// The address does not accept replies.
+    pub no_reply: bool,
+    /// Emails previously exchanged with the sender.
+    pub prior_messages: u32,
+    /// The sender is a VIP contact.
+    pub is_vip: bool,
+    /// Number of the user's contacts at the sender's domain.
+    pub domain_contacts: usize,
+    /// How established the sender's domain appears.
+    pub domain_age: DomainAge,
+}
+
+impl SenderSignals {
+    /// Extracts signals from a sender's first email.
+    ///
+    /// `contact` is the sender's contact record if one exists, and
+    /// `domain_contacts` the number of contacts at the sender's domain.
+    pub fn from_email(email: &Email, contact: Option<&Contact>, domain_contacts: usize) -> Self {
+        let sender = email.from.email.trim().to_lowercase();
+        let domain = domain_of(&sender).to_string();
+        let local_part = sender.split('@').next().unwrap_or_default();
+
+        let bulk_precedence = email
+            .header("Precedence")
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "bulk" | "list" | "junk"))
+            .unwrap_or(false)
+            || email
+                .header("Auto-Submitted")
+                .is_some_and(|v| !v.trim().eq_ignore_ascii_case("no"));
+
+        let reply_to_mismatch = email
+            .header("Reply-To")
+            .and_then(extract_address)
+            .map(|addr| domain_of(&addr) != domain)
+            .unwrap_or(false);
+
+        let prior_messages = contact.map(|c| c.frequency).unwrap_or(0);
+        let domain_age = if domain_contacts > 0 || WELL_KNOWN_DOMAINS.contains(&domain.as_str()) {
+            DomainAge::Established
+        } else if looks_new(&domain) {
+            DomainAge::LikelyNew
+        } else {
+            DomainAge::Unknown
+        };
+
+        Self {
+            list_unsubscribe: email.header("List-Unsubscribe").is_some(),
+            bulk_precedence,
+            list_id: email.header("List-Id").map(|v| v.trim().to_string()),
+            esp: detect_esp(email).map(String::from),
+            reply_to_mismatch,
+            no_reply: NO_REPLY_LOCAL_PARTS.contains(&local_part),
+            prior_messages,
+            is_vip: contact.is_some_and(|c| c.is_vip),
+            domain_contacts,
+            domain_age,
+            sender,
+            domain,
+        }
+    }
+
+    /// Whether the email carries the markers of bulk mail.
+    pub fn is_bulk(&self) -> bool {
+        self.list_unsubscribe
+            || self.bulk_precedence
+            || self.list_id.is_some()
+            || self.esp.is_some()
+    }
+
+    /// Sender type the signals point to on their own, if they are conclusive.
+    pub fn indicated_type(&self) -> Option<SenderType> {
+        if self.is_vip || self.prior_messages >= 2 {
+            Some(SenderType::KnownContact)
+        } else if self.list_unsubscribe && (self.bulk_precedence || self.esp.is_some()) {
+            Some(SenderType::Newsletter)
+        } else {
+            None
+        }
+    }
+
+    /// Formats the signals as prompt lines.
+    pub fn describe(&self) -> String {
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+        let mut lines = vec![
+            format!("List-Unsubscribe header: {}", yes_no(self.list_unsubscribe)),
+            format!("Bulk precedence: {}", yes_no(self.bulk_precedence)),
+            format!(
+                "Reply-To on another domain: {}",
+                yes_no(self.reply_to_mismatch)
+            ),
+            format!("No-reply address: {}", yes_no(self.no_reply)),
+            format!("Emails exchanged before: {}", self.prior_messages),
+            format!("VIP contact: {}", yes_no(self.is_vip)),
+            format!("Contacts at this domain: {}", self.domain_contacts),
+            format!(
+                "Domain age estimate: {}",
+                match self.domain_age {
+                    DomainAge::Established => "established",
+                    DomainAge::Unknown => "unknown",
+                    DomainAge::LikelyNew => "likely new",
+                }
+            ),
+        ];
+        if let Some(list_id) = &self.list_id {
+            lines.push(format!("List-Id: {}", list_id));
+        }
+        if let Some(esp) = &self.esp {
+            lines.push(format!("Sent through: {}", esp));
+        }
+        lines
+            .into_iter()
+            .map(|line| format!("- {}\n", line))
+            .collect()
+    }
+
+    /// Calibrates the confidence a model reported for its analysis.
+    ///
+    /// Models tend to be overconfident, so the reported value is first pulled
+    /// towards 0.5, then raised when the signals agree with the classification
+    /// and lowered when they contradict it. Approvals are further discounted
+    /// for reply-to mismatches and domains that look newly registered.
+    pub fn calibrate(&self, analysis: &SenderAnalysis) -> f32 {
+        let reported = analysis.confidence.clamp(0.0, 1.0);
+        let mut confidence = 0.5 + (reported - 0.5) * 0.8;
+
+        if let Some(indicated) = self.indicated_type() {
+            if same_family(indicated, analysis.likely_type) {
+                confidence += (1.0 - confidence) * 0.5;
+            } else {
+                confidence *= 0.5;
+            }
+        }
+
+        if analysis.suggested_action == ScreenerAction::Approve {
+            if self.reply_to_mismatch {
+                confidence *= 0.7;
+            }
+            if self.domain_age == DomainAge::LikelyNew {
+                confidence *= 0.7;
+            }
+        }
+
+        confidence.clamp(0.0, 1.0)
+    }
+}
+
+/// Formats the parts of a sender's first email shown during analysis.
+pub(crate) fn format_email_for_analysis(email: &Email) -> String {
+    let mut content = format!("From: {}\n", email.from.display());
+    for name in ["Reply-To", "Subject", "List-Id"] {
+        if let Some(value) = email.header(name) {
+            content.push_str(&format!("{}: {}\n", name, value));
+        }
+    }
+    if email.header("Subject").is_none() {
+        if let Some(subject) = &email.subject {
+            content.push_str(&format!("Subject: {}\n", subject));
+        }
+    }
+    let body = email.body_text.as_deref().unwrap_or(&email.snippet);
+    let excerpt: String = body.chars().take(BODY_EXCERPT_CHARS).collect();
+    content.push_str(&format!("\n{}\n", excerpt.trim()));
+    content
+}
+
+/// Whether two sender types belong to the same broad family.
+///
+/// Newsletters and marketing are both bulk mail and often confused.
+fn same_family(a: SenderType, b: SenderType) -> bool {
+    let bulk = |t: SenderType| matches!(t, SenderType::Newsletter | SenderType::Marketing);
+    a == b || (bulk(a) && bulk(b))
+}
+
+/// Returns the domain part of an address.
+fn domain_of(address: &str) -> &str {
+    address.rsplit_once('@').map(|(_, d)| d).unwrap_or_default()
+}
+
+/// Extracts the address from a header value such as `Name <a@b.com>`.
+fn extract_address(value: &str) -> Option<String> {
+    let value = value.trim();
+    let address = match (value.find('<'), value.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &value[start + 1..end],
+        _ => value,
+    };
+    address.contains('@').then(|| address.trim().to_lowercase())
+}
+
+/// Identifies the ESP an email was sent through from its headers.
+fn detect_esp(email: &Email) -> Option<&'static str> {
+    let haystack: String = email
+        .headers
+        .iter()
+        .filter(|h| {
+            h.name.to_lowercase().starts_with("x-")
+                || ESP_HEADERS.iter().any(|n| h.name.eq_ignore_ascii_case(n))
+        })
+        .map(|h| format!("{}: {}\n", h.name, h.value).to_lowercase())
+        .collect();
+
+    ESP_FINGERPRINTS
+        .iter()
+        .find(|(_, needles)| needles.iter().any(|n| haystack.contains(n)))
+        .map(|(name, _)| *name)
+}
+
+/// Whether a domain name has the marks of a freshly registered domain.
+fn looks_new(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    let tld = labels.last().copied().unwrap_or_default();
+    let name = labels
+        .len()
+        .checked_sub(2)
+        .map(|i| labels[i])
+        .unwrap_or_default();
+
+    let digits = name.chars().filter(|c| c.is_ascii_digit()).count();
+    let hyphens = name.matches('-').count();
+
+    THROWAWAY_TLDS.contains(&tld)
+        || labels.iter().any(|l| l.starts_with("xn--"))
+        || digits >= 4
+        || hyphens >= 3
+        || name.len() > 24
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AccountId, Address, EmailId, Header, MessageId, ThreadId};
+
+    fn make_email(from: &str, headers: Vec<Header>) -> Email {
+        Email {
+            id: EmailId::from("email-1"),
+            account_id: AccountId::from("account-1"),
+            thread_id: ThreadId::from("thread-1"),
+            message_id: MessageId::from("<email-1@example.com>"),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new(from),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("This week's deals".to_string()),
+            body_text: Some("Save 20% on everything.".to_string()),
+            body_html: None,
+            snippet: "Save 20% on everything.".to_string(),
+            date: chrono::Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![],
+            attachments: vec![],
+            headers,
+        }
+    }
+
+    fn analysis(
+        likely_type: SenderType,
+        action: ScreenerAction,
+        confidence: f32,
+    ) -> SenderAnalysis {
+        SenderAnalysis {
+            likely_type,
+            reasoning: String::new(),
+            suggested_action: action,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn bulk_headers_are_detected() {
+        let email = make_email(
+            "deals@shop.example",
+            vec![
+                Header::new("List-Unsubscribe", "<https://shop.example/unsub>"),
+                Header::new("Precedence", "bulk"),
+                Header::new("Return-Path", "<bounce-123@em.sendgrid.net>"),
+                Header::new("Reply-To", "Support <help@other.example>"),
+            ],
+        );
+        let signals = SenderSignals::from_email(&email, None, 0);
+
+        assert!(signals.list_unsubscribe);
+        assert!(signals.bulk_precedence);
+        assert_eq!(signals.esp.as_deref(), Some("SendGrid"));
+        assert!(signals.reply_to_mismatch);
+        assert_eq!(signals.indicated_type(), Some(SenderType::Newsletter));
+        assert!(signals.describe().contains("Sent through: SendGrid"));
+    }
+
+    #[test]
+    fn prior_correspondence_indicates_known_contact() {
+        let email = make_email("alice@gmail.com", vec![]);
+        let mut contact = Contact::new("alice@gmail.com");
+        contact.frequency = 5;
+        let signals = SenderSignals::from_email(&email, Some(&contact), 0);
+
+        assert!(!signals.is_bulk());
+        assert_eq!(signals.prior_messages, 5);
+        assert_eq!(signals.domain_age, DomainAge::Established);
+        assert_eq!(signals.indicated_type(), Some(SenderType::KnownContact));
+    }
+
+    #[test]
+    fn domain_age_heuristics() {
+        let signals = |from: &str, contacts| {
+            SenderSignals::from_email(&make_email(from, vec![]), None, contacts).domain_age
+        };
+
+        assert_eq!(signals("a@win-big-prize-now.xyz", 0), DomainAge::LikelyNew);
+        assert_eq!(signals("a@shop20240918.com", 0), DomainAge::LikelyNew);
+        assert_eq!(signals("a@xn--80ak6aa92e.com", 0), DomainAge::LikelyNew);
+        assert_eq!(signals("a@acme.com", 0), DomainAge::Unknown);
+        assert_eq!(signals("a@acme.com", 2), DomainAge::Established);
+    }
+
+    #[test]
+    fn calibration_follows_signal_agreement() {
+        let email = make_email(
+            "news@acme.com",
+            vec![
+                Header::new("List-Unsubscribe", "<mailto:unsub@acme.com>"),
+                Header::new("Precedence", "list"),
+            ],
+        );
+        let signals = SenderSignals::from_email(&email, None, 0);
+
+        let agrees = signals.calibrate(&analysis(
+            SenderType::Marketing,
+            ScreenerAction::Review,
+            0.9,
+        ));
+        let contradicts = signals.calibrate(&analysis(
+            SenderType::KnownContact,
+            ScreenerAction::Approve,
+            0.9,
+        ));
+        assert!(agrees > 0.9);
+        assert!(contradicts < 0.5);
+    }
+
+    #[test]
+    fn calibration_discounts_risky_approvals() {
+        let email = make_email(
+            "billing@secure-acct-verify.xyz",
+            vec![Header::new("Reply-To", "<collect@elsewhere.example>")],
+        );
+        let signals = SenderSignals::from_email(&email, None, 0);
+
+        let confidence =
+            signals.calibrate(&analysis(SenderType::Support, ScreenerAction::Approve, 1.0));
+        assert!(confidence < 0.5);
+    }
+
+    #[test]
+    fn analysis_excerpt_includes_headers_and_body() {
+        let email = make_email(
+            "news@acme.com",
+            vec![Header::new("List-Id", "<weekly.acme.com>")],
+        );
+        let content = format_email_for_analysis(&email);
+
+        assert!(content.contains("From: news@acme.com"));
+        assert!(content.contains("List-Id: <weekly.acme.com>"));
+        assert!(content.contains("Subject: This week's deals"));
+        assert!(content.contains("Save 20% on everything."));
+    }
+}
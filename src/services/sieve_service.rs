@@ -0,0 +1,1113 @@
+//! Server-side rules for IMAP accounts.
+//!
+//! Screener and filter rules normally run during sync, so mail arriving
+//! while the app is closed stays unsorted. The [`SieveCompiler`] turns the
+//! rules that apply to an account, and an optional vacation response, into
+//! a Sieve script (RFC 5228), and the [`SieveService`] uploads and
+//! activates it over ManageSieve.
+//!
+//! Screened-out senders are filed first and stop all further processing,
+//! the vacation response comes next, then the filter rules in order.
+//! Snoozing and smart view assignment have no Sieve equivalent and are left
+//! to the app.
+//!
+//! Rule patterns use the `regex` crate's syntax, while Sieve servers use
+//! POSIX extended regular expressions. Patterns are translated where the
+//! two agree; rules whose patterns cannot be translated are left out of the
+//! script and reported, so the server never sorts mail differently from the
+//! app. The same goes for attachment conditions, since plain Sieve cannot
+//! look inside the MIME structure.
+
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::domain::{AccountId, RuleField, RuleType, ScreenerAction, ScreenerRule};
+use crate::providers::email::{ManageSieveClient, ProviderError};
+use crate::services::{FilterAction, FilterCondition, FilterRule, ViewType};
+
+/// Default name of the uploaded script.
+pub const DEFAULT_SCRIPT_NAME: &str = "heap";
+
+/// Errors that can occur while publishing Sieve scripts.
+#[derive(Debug, Error)]
+pub enum SieveError {
+    /// The server rejected the script.
+    #[error("server rejected script: {0}")]
+    InvalidScript(String),
+
+    /// ManageSieve error.
+    #[error(transparent)]
+    Provider(ProviderError),
+}
+
+impl From<ProviderError> for SieveError {
+    fn from(e: ProviderError) -> Self {
+        match e {
+            ProviderError::InvalidRequest(message) => SieveError::InvalidScript(message),
+            other => SieveError::Provider(other),
+        }
+    }
+}
+
+/// Result type for Sieve operations.
+pub type SieveResult<T> = Result<T, SieveError>;
+
+/// An automatic reply sent while the user is away.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VacationResponse {
+    /// Subject of the reply.
+    pub subject: String,
+    /// Body of the reply.
+    pub body: String,
+    /// Days before the same sender is answered again.
+    pub days: u32,
+    /// The user's own addresses, so mail sent to them is answered.
+    pub addresses: Vec<String>,
+    /// First day replies are sent.
+    pub start: Option<NaiveDate>,
+    /// Last day replies are sent.
+    pub end: Option<NaiveDate>,
+}
+
+impl VacationResponse {
+    /// Creates a response sent at most weekly to each sender.
+    pub fn new(subject: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            body: body.into(),
+            days: 7,
+            addresses: Vec::new(),
+            start: None,
+            end: None,
+        }
+    }
+
+    /// Limits replies to a date range.
+    pub fn between(mut self, start: NaiveDate, end: NaiveDate) -> Self {
+        self.start = Some(start);
+        self.end = Some(end);
+        self
+    }
+}
+
+/// A compiled script and the rules left out of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompiledScript {
+    /// The Sieve script.
+    pub script: String,
+    /// IDs of rules that apply to the account but cannot be expressed in
+    /// Sieve, and of the rules after them that would run differently
+    /// without them.
+    pub skipped: Vec<String>,
+}
+
+/// Compiles screener rules, filter rules and a vacation response into a
+/// Sieve script for one account.
+#[derive(Debug, Clone)]
+pub struct SieveCompiler {
+    account_id: AccountId,
+    screened_out_folder: String,
+    archive_folder: String,
+}
+
+impl SieveCompiler {
+    /// Creates a compiler for an account's rules.
+    pub fn new(account_id: AccountId) -> Self {
+        Self {
+            account_id,
+            screened_out_folder: ViewType::ScreenedOut.folder_name().to_string(),
+            archive_folder: "Archive".to_string(),
+        }
+    }
+
+    /// Sets the folder rejected senders are filed into.
+    pub fn with_screened_out_folder(mut self, folder: impl Into<String>) -> Self {
+        self.screened_out_folder = folder.into();
+        self
+    }
+
+    /// Sets the folder archived mail without a label is filed into.
+    pub fn with_archive_folder(mut self, folder: impl Into<String>) -> Self {
+        self.archive_folder = folder.into();
+        self
+    }
+
+    /// Compiles the rules that apply to the account into a script.
+    pub fn compile(
+        &self,
+        screener_rules: &[ScreenerRule],
+        filter_rules: &[FilterRule],
+        vacation: Option<&VacationResponse>,
+    ) -> CompiledScript {
+        let mut script = Script::default();
+        let mut skipped = Vec::new();
+        self.compile_screener(&mut script, &mut skipped, screener_rules);
+        if let Some(vacation) = vacation {
+            compile_vacation(&mut script, vacation);
+        }
+        self.compile_filters(&mut script, &mut skipped, filter_rules);
+        CompiledScript {
+            script: script.finish(),
+            skipped,
+        }
+    }
+
+    /// Compiles screener rules into one `if`/`elsif` chain in precedence
+    /// order, so that an approval shadows rejections below it.
+    ///
+    /// The chain ends before the first rule that cannot be compiled, since
+    /// the rules below it would decide mail that it decides in the app.
+    fn compile_screener(
+        &self,
+        script: &mut Script,
+        skipped: &mut Vec<String>,
+        rules: &[ScreenerRule],
+    ) {
+        let mut rules: Vec<&ScreenerRule> = rules
+            .iter()
+            .filter(|r| r.applies_to(&self.account_id) && r.validate().is_ok())
+            .collect();
+        if !rules.iter().any(|r| r.action == ScreenerAction::Reject) {
+            return;
+        }
+        rules.sort_by(|a, b| a.cmp_precedence(b));
+
+        let mut needs = Script::default();
+        let mut compiled = Vec::new();
+        for (i, rule) in rules.iter().enumerate() {
+            match screener_test(&mut needs, rule) {
+                Some(test) => compiled.push((*rule, test)),
+                None => {
+                    skipped.extend(rules[i..].iter().map(|r| r.id.clone()));
+                    break;
+                }
+            }
+        }
+        if !compiled
+            .iter()
+            .any(|(rule, _)| rule.action == ScreenerAction::Reject)
+        {
+            return;
+        }
+        script.requires.extend(needs.requires);
+
+        script.line("# Screener");
+        for (i, (rule, test)) in compiled.iter().enumerate() {
+            let keyword = if i == 0 { "if" } else { "} elsif" };
+            script.line(&format!("{} {} {{", keyword, test));
+            match rule.action {
+                ScreenerAction::Reject => {
+                    script.require("fileinto");
+                    script.line(&format!(
+                        "    fileinto {};",
+                        quote(&self.screened_out_folder)
+                    ));
+                    script.line("    stop;");
+                }
+                ScreenerAction::Approve | ScreenerAction::Review => {
+                    script.line("    # delivered normally");
+                }
+            }
+        }
+        script.line("}");
+        script.line("");
+    }
+
+    /// Compiles filter rules in order.
+    ///
+    /// A rule that cannot be compiled is skipped. If it stops processing,
+    /// the rules after it are skipped too, since they would otherwise run
+    /// on mail the app never gives them.
+    fn compile_filters(
+        &self,
+        script: &mut Script,
+        skipped: &mut Vec<String>,
+        rules: &[FilterRule],
+    ) {
+        let mut rules: Vec<&FilterRule> = rules
+            .iter()
+            .filter(|r| r.enabled && r.validate().is_ok())
+            .collect();
+        rules.sort_by(|a, b| {
+            a.position
+                .cmp(&b.position)
+                .then(a.created_at.cmp(&b.created_at))
+        });
+
+        for (i, rule) in rules.iter().enumerate() {
+            let mut needs = Script::default();
+            let tests: Option<Vec<String>> = rule
+                .conditions
+                .iter()
+                .map(|c| self.filter_test(&mut needs, c))
+                .collect();
+            let Some(tests) = tests else {
+                if rule.stop_processing {
+                    skipped.extend(rules[i..].iter().map(|r| r.id.clone()));
+                    break;
+                }
+                skipped.push(rule.id.clone());
+                continue;
+            };
+            script.requires.extend(needs.requires);
+            let combinator = if rule.match_all { "allof" } else { "anyof" };
+
+            script.line(&format!("# Filter: {}", one_line(&rule.name)));
+            script.line(&format!("if {}({}) {{", combinator, tests.join(", ")));
+            for command in self.filter_commands(script, &rule.actions) {
+                script.line(&format!("    {}", command));
+            }
+            if rule.stop_processing {
+                script.line("    stop;");
+            }
+            script.line("}");
+            script.line("");
+        }
+    }
+
+    /// Returns the Sieve test for a filter condition, or `None` if it
+    /// cannot be expressed exactly.
+    fn filter_test(&self, script: &mut Script, condition: &FilterCondition) -> Option<String> {
+        let test = match condition {
+            FilterCondition::From { pattern } => header_regex(script, "from", pattern)?,
+            FilterCondition::To { pattern } => header_regex(script, "to", pattern)?,
+            FilterCondition::Cc { pattern } => header_regex(script, "cc", pattern)?,
+            FilterCondition::Subject { pattern } => header_regex(script, "subject", pattern)?,
+            FilterCondition::Header { name, pattern } => header_regex(script, name, pattern)?,
+            FilterCondition::ListId { pattern } => header_regex(script, "list-id", pattern)?,
+            FilterCondition::Body { pattern } => {
+                let pattern = posix_regex(pattern, false)?;
+                script.require("body");
+                script.require("regex");
+                format!("body :text :regex {}", quote(&pattern))
+            }
+            // Sieve cannot look inside the MIME structure without extensions
+            FilterCondition::HasAttachment => return None,
+            FilterCondition::LargerThan { bytes } => format!("size :over {}", bytes),
+            FilterCondition::SmallerThan { bytes } => format!("size :under {}", bytes),
+            FilterCondition::Account { account_id } => {
+                if *account_id == self.account_id {
+                    "true".to_string()
+                } else {
+                    "false".to_string()
+                }
+            }
+        };
+        Some(test)
+    }
+
+    /// Returns the commands for a rule's actions.
+    ///
+    /// Flags are set before mail is filed so that they stick. Labels become
+    /// folder copies, unless the rule archives, in which case the mail is
+    /// moved to its labels' folders or to the archive folder.
+    fn filter_commands(&self, script: &mut Script, actions: &[FilterAction]) -> Vec<String> {
+        let mut flags = Vec::new();
+        let mut folders = Vec::new();
+        let mut redirects = Vec::new();
+        let mut app_only = Vec::new();
+        let mut archive = false;
+
+        for action in actions {
+            match action {
+                FilterAction::ApplyLabel { label } => folders.push(label.0.clone()),
+                FilterAction::Archive => archive = true,
+                FilterAction::Star => flags.push("\\\\Flagged"),
+                FilterAction::MarkRead => flags.push("\\\\Seen"),
+                FilterAction::Forward { to } => redirects.push(to.clone()),
+                FilterAction::Snooze { .. } => app_only.push("snooze"),
+                FilterAction::AssignSmartView { .. } => app_only.push("smart view"),
+            }
+        }
+
+        let mut commands = Vec::new();
+        for flag in flags {
+            script.require("imap4flags");
+            commands.push(format!("addflag \"{}\";", flag));
+        }
+        for to in redirects {
+            script.require("copy");
+            commands.push(format!("redirect :copy {};", quote(&to)));
+        }
+        if archive && folders.is_empty() {
+            folders.push(self.archive_folder.clone());
+        }
+        for folder in folders {
+            script.require("fileinto");
+            if archive {
+                commands.push(format!("fileinto {};", quote(&folder)));
+            } else {
+                script.require("copy");
+                commands.push(format!("fileinto :copy {};", quote(&folder)));
+            }
+        }
+        for action in app_only {
+            commands.push(format!("# {} is applied by the app", action));
+        }
+        commands
+    }
+}
+
+/// Returns a test that matches a header against a regular expression, or
+/// `None` if the pattern cannot be translated.
+fn header_regex(script: &mut Script, header: &str, pattern: &str) -> Option<String> {
+    let pattern = posix_regex(pattern, true)?;
+    script.require("regex");
+    Some(format!(
+        "header :regex {} {}",
+        quote(header),
+        quote(&pattern)
+    ))
+}
+
+/// Returns the Sieve test for a screener rule's condition, or `None` if its
+/// pattern cannot be translated.
+fn screener_test(script: &mut Script, rule: &ScreenerRule) -> Option<String> {
+    let test = match rule.rule_type {
+        RuleType::DomainAllow | RuleType::DomainBlock => {
+            let domain = rule.pattern.trim().trim_start_matches('@').to_lowercase();
+            format!(
+                "anyof(address :domain :is \"from\" {}, address :domain :matches \"from\" {})",
+                quote(&domain),
+                quote(&format!("*.{}", domain))
+            )
+        }
+        RuleType::Pattern | RuleType::Regex => {
+            let (match_type, pattern) = match rule.rule_type {
+                RuleType::Regex => {
+                    let pattern = posix_regex(&rule.pattern, true)?;
+                    script.require("regex");
+                    (":regex", pattern)
+                }
+                // Backslashes escape wildcards in Sieve but not in the app
+                _ if rule.pattern.contains(['*', '?']) => {
+                    (":matches", rule.pattern.replace('\\', "\\\\"))
+                }
+                _ => (":contains", rule.pattern.clone()),
+            };
+            let pattern = quote(&pattern);
+            match rule.field {
+                RuleField::Sender => format!("address :all {} \"from\" {}", match_type, pattern),
+                RuleField::ListId => format!("header {} \"list-id\" {}", match_type, pattern),
+                RuleField::Subject => format!("header {} \"subject\" {}", match_type, pattern),
+            }
+        }
+    };
+    Some(test)
+}
+
+/// Translates a pattern in the `regex` crate's syntax into a POSIX extended
+/// regular expression for the Sieve `regex` extension.
+///
+/// Both sides match case-insensitively, so the `i` flag is dropped, and
+/// laziness never changes whether a pattern matches, so it is dropped too.
+/// Header values are single lines, where `.` and `^`/`$` mean the same with
+/// or without the `s` and `m` flags; in bodies `.` only agrees with POSIX
+/// under the `s` flag. Returns `None` for anything else POSIX cannot
+/// express, such as word boundaries, Unicode classes or class set
+/// operations.
+fn posix_regex(pattern: &str, single_line: bool) -> Option<String> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut posix = String::new();
+    // Whether `.` matches newlines, saved for each open group
+    let mut dot_all = single_line;
+    let mut groups = Vec::new();
+    // POSIX leaves empty alternatives undefined
+    let mut empty_branch = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => {
+                let c = *chars.get(i + 1)?;
+                posix.push_str(&posix_escape(c)?);
+                i += 2;
+            }
+            '[' => {
+                let (class, next) = posix_class(&chars, i + 1)?;
+                posix.push_str(&class);
+                i = next;
+            }
+            '(' if chars.get(i + 1) == Some(&'?') => {
+                let mut j = i + 2;
+                if chars.get(j) == Some(&'P') {
+                    j += 1;
+                }
+                if chars.get(j) == Some(&'<') {
+                    // Named groups match like plain groups
+                    j += chars[j..].iter().position(|&c| c == '>')?;
+                    groups.push(dot_all);
+                    posix.push('(');
+                    empty_branch = true;
+                    i = j + 1;
+                    continue;
+                }
+
+                let mut flag_dot_all = dot_all;
+                loop {
+                    match *chars.get(j)? {
+                        'i' | 'U' => {}
+                        's' => flag_dot_all = true,
+                        'm' if single_line => {}
+                        ':' | ')' => break,
+                        _ => return None,
+                    }
+                    j += 1;
+                }
+                if chars[j] == ':' {
+                    groups.push(dot_all);
+                    posix.push('(');
+                    empty_branch = true;
+                }
+                // A bare flag group applies until its enclosing group ends
+                dot_all = flag_dot_all;
+                i = j + 1;
+                continue;
+            }
+            '(' => {
+                groups.push(dot_all);
+                posix.push('(');
+                empty_branch = true;
+                i += 1;
+                continue;
+            }
+            ')' => {
+                if empty_branch {
+                    return None;
+                }
+                dot_all = groups.pop()?;
+                posix.push(')');
+                i += 1;
+            }
+            '|' => {
+                if empty_branch {
+                    return None;
+                }
+                posix.push('|');
+                empty_branch = true;
+                i += 1;
+                continue;
+            }
+            c @ ('*' | '+' | '?') => {
+                posix.push(c);
+                i += 1;
+                if chars.get(i) == Some(&'?') {
+                    i += 1;
+                }
+            }
+            '{' => {
+                let end = i + chars[i..].iter().position(|&c| c == '}')?;
+                let bounds: String = chars[i + 1..end]
+                    .iter()
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                let valid = !bounds.is_empty()
+                    && !bounds.starts_with(',')
+                    && bounds.matches(',').count() <= 1
+                    && bounds.chars().all(|c| c.is_ascii_digit() || c == ',');
+                if !valid {
+                    return None;
+                }
+                posix.push_str(&format!("{{{}}}", bounds));
+                i = end + 1;
+                if chars.get(i) == Some(&'?') {
+                    i += 1;
+                }
+            }
+            '.' => {
+                if !dot_all {
+                    return None;
+                }
+                posix.push('.');
+                i += 1;
+            }
+            c @ (']' | '}') => {
+                posix.push_str(&format!("[{}]", c));
+                i += 1;
+            }
+            c => {
+                posix.push(c);
+                i += 1;
+            }
+        }
+        empty_branch = false;
+    }
+
+    if empty_branch || !groups.is_empty() {
+        return None;
+    }
+    Some(posix)
+}
+
+/// Translates an escape outside a character class.
+fn posix_escape(c: char) -> Option<String> {
+    let posix = match c {
+        'd' => "[0-9]".to_string(),
+        'D' => "[^0-9]".to_string(),
+        'w' => "[[:alnum:]_]".to_string(),
+        'W' => "[^[:alnum:]_]".to_string(),
+        's' => "[[:space:]]".to_string(),
+        'S' => "[^[:space:]]".to_string(),
+        '.' | '[' | '\\' | '(' | ')' | '*' | '+' | '?' | '{' | '|' | '^' | '$' => {
+            format!("\\{}", c)
+        }
+        ']' | '}' => format!("[{}]", c),
+        c if c.is_ascii_punctuation() || c == ' ' => c.to_string(),
+        _ => return None,
+    };
+    Some(posix)
+}
+
+/// A member of a character class.
+enum ClassItem {
+    /// A single character.
+    Char(char),
+    /// A named set, written as it appears inside a POSIX bracket.
+    Set(&'static str),
+}
+
+/// Translates a character class whose `[` is just before `start`.
+///
+/// Returns the POSIX bracket expression and the index after the class.
+fn posix_class(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let mut i = start;
+    let negated = chars.get(i) == Some(&'^');
+    if negated {
+        i += 1;
+    }
+
+    // `]`, `[`, `^` and `-` only mean themselves in certain positions
+    let (mut close, mut open, mut caret, mut hyphen) = (false, false, false, false);
+    let mut items = String::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !first {
+            i += 1;
+            break;
+        }
+        first = false;
+
+        let item = match c {
+            '[' if chars.get(i + 1) == Some(&':') => {
+                let end = i + 2 + chars[i + 2..].iter().position(|&c| c == ':')?;
+                if chars.get(end + 1) != Some(&']') {
+                    return None;
+                }
+                let name: String = chars[i + 2..end].iter().collect();
+                i = end + 2;
+                ClassItem::Set(posix_class_name(&name)?)
+            }
+            '[' => return None,
+            '&' | '-' | '~' if chars.get(i + 1) == Some(&c) => return None,
+            '\\' => {
+                let escaped = *chars.get(i + 1)?;
+                i += 2;
+                match escaped {
+                    'd' => ClassItem::Set("0-9"),
+                    'w' => ClassItem::Set("[:alnum:]_"),
+                    's' => ClassItem::Set("[:space:]"),
+                    c if c.is_ascii_punctuation() || c == ' ' => ClassItem::Char(c),
+                    _ => return None,
+                }
+            }
+            c => {
+                i += 1;
+                ClassItem::Char(c)
+            }
+        };
+
+        let low = match item {
+            ClassItem::Char(c) => c,
+            ClassItem::Set(set) => {
+                items.push_str(set);
+                continue;
+            }
+        };
+        let is_range = chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&c| c != ']');
+        if is_range {
+            let high = match chars[i + 1] {
+                '\\' => {
+                    let c = *chars.get(i + 2).filter(|c| c.is_ascii_punctuation())?;
+                    i += 3;
+                    c
+                }
+                '[' => return None,
+                c => {
+                    i += 2;
+                    c
+                }
+            };
+            if [low, high]
+                .iter()
+                .any(|c| matches!(c, ']' | '[' | '^' | '-'))
+            {
+                return None;
+            }
+            items.push_str(&format!("{}-{}", low, high));
+            continue;
+        }
+        match low {
+            ']' => close = true,
+            '[' => open = true,
+            '^' => caret = true,
+            '-' => hyphen = true,
+            c => items.push(c),
+        }
+    }
+
+    let mut class = String::new();
+    if close {
+        class.push(']');
+    }
+    class.push_str(&items);
+    if open {
+        class.push('[');
+    }
+    if caret {
+        if class.is_empty() && !negated {
+            if !hyphen {
+                return Some(("\\^".to_string(), i));
+            }
+            class.push('-');
+            hyphen = false;
+        }
+        class.push('^');
+    }
+    if hyphen {
+        class.push('-');
+    }
+    let negation = if negated { "^" } else { "" };
+    Some((format!("[{}{}]", negation, class), i))
+}
+
+/// Returns the POSIX name for an ASCII class, if POSIX has one that
+/// matches the same characters case-insensitively.
+fn posix_class_name(name: &str) -> Option<&'static str> {
+    let set = match name {
+        "alnum" => "[:alnum:]",
+        "alpha" => "[:alpha:]",
+        "blank" => "[:blank:]",
+        "cntrl" => "[:cntrl:]",
+        "digit" => "[:digit:]",
+        "graph" => "[:graph:]",
+        "print" => "[:print:]",
+        "punct" => "[:punct:]",
+        "space" => "[:space:]",
+        "word" => "[:alnum:]_",
+        "xdigit" => "[:xdigit:]",
+        _ => return None,
+    };
+    Some(set)
+}
+
+fn compile_vacation(script: &mut Script, vacation: &VacationResponse) {
+    script.require("vacation");
+
+    // Mailing lists and bulk mail are never answered
+    let mut tests = vec![
+        "not exists \"list-id\"".to_string(),
+        "not header :is \"precedence\" [\"bulk\", \"list\", \"junk\"]".to_string(),
+    ];
+    for (comparison, date) in [("ge", vacation.start), ("le", vacation.end)] {
+        if let Some(date) = date {
+            script.require("date");
+            script.require("relational");
+            tests.push(format!(
+                "currentdate :value {} \"date\" {}",
+                quote(comparison),
+                quote(&date.format("%Y-%m-%d").to_string())
+            ));
+        }
+    }
+
+    let mut command = format!(
+        "vacation :days {} :subject {}",
+        vacation.days.max(1),
+        quote(&vacation.subject)
+    );
+    if !vacation.addresses.is_empty() {
+        let addresses: Vec<String> = vacation.addresses.iter().map(|a| quote(a)).collect();
+        command.push_str(&format!(" :addresses [{}]", addresses.join(", ")));
+    }
+    command.push_str(&format!(" {};", quote(&vacation.body)));
+
+    script.line("# Vacation");
+    script.line(&format!("if allof({}) {{", tests.join(", ")));
+    script.line(&format!("    {}", command));
+    script.line("}");
+    script.line("");
+}
+
+/// A script being built, with the extensions it needs.
+#[derive(Default)]
+struct Script {
+    requires: BTreeSet<&'static str>,
+    body: String,
+}
+
+impl Script {
+    fn require(&mut self, extension: &'static str) {
+        self.requires.insert(extension);
+    }
+
+    fn line(&mut self, line: &str) {
+        self.body.push_str(line);
+        self.body.push_str("\r\n");
+    }
+
+    fn finish(self) -> String {
+        let mut script =
+            String::from("# Generated by The Heap; changes made here are replaced.\r\n");
+        if !self.requires.is_empty() {
+            let requires: Vec<String> = self.requires.iter().map(|r| quote(r)).collect();
+            script.push_str(&format!("require [{}];\r\n", requires.join(", ")));
+        }
+        script.push_str("\r\n");
+        script.push_str(&self.body);
+        script
+    }
+}
+
+/// Quotes a Sieve string.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Collapses a name onto one line for use in a comment.
+fn one_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Publishes an account's rules to its server.
+pub struct SieveService {
+    compiler: SieveCompiler,
+    script_name: String,
+}
+
+impl SieveService {
+    /// Creates a service that publishes the account's rules as the
+    /// [default script](DEFAULT_SCRIPT_NAME).
+    pub fn new(account_id: AccountId) -> Self {
+        Self {
+            compiler: SieveCompiler::new(account_id),
+            script_name: DEFAULT_SCRIPT_NAME.to_string(),
+        }
+    }
+
+    /// Sets the compiler, for example to change the target folders.
+    pub fn with_compiler(mut self, compiler: SieveCompiler) -> Self {
+        self.compiler = compiler;
+        self
+    }
+
+    /// Sets the name the script is stored under.
+    pub fn with_script_name(mut self, name: impl Into<String>) -> Self {
+        self.script_name = name.into();
+        self
+    }
+
+    /// Returns the name the script is stored under.
+    pub fn script_name(&self) -> &str {
+        &self.script_name
+    }
+
+    /// Compiles the rules into a script without publishing it.
+    pub fn compile(
+        &self,
+        screener_rules: &[ScreenerRule],
+        filter_rules: &[FilterRule],
+        vacation: Option<&VacationResponse>,
+    ) -> CompiledScript {
+        self.compiler
+            .compile(screener_rules, filter_rules, vacation)
+    }
+
+    /// Compiles the rules, checks the script with the server, uploads it
+    /// and makes it the active script.
+    ///
+    /// Nothing is replaced on the server if the check fails. Returns the
+    /// uploaded script and the rules left out of it, which only the app
+    /// applies.
+    pub async fn publish<S>(
+        &self,
+        client: &mut ManageSieveClient<S>,
+        screener_rules: &[ScreenerRule],
+        filter_rules: &[FilterRule],
+        vacation: Option<&VacationResponse>,
+    ) -> SieveResult<CompiledScript>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let compiled = self.compile(screener_rules, filter_rules, vacation);
+        client.check_script(&compiled.script).await?;
+        client
+            .put_script(&self.script_name, &compiled.script)
+            .await?;
+        client.set_active(&self.script_name).await?;
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::LabelId;
+
+    fn account() -> AccountId {
+        AccountId::from("work")
+    }
+
+    #[test]
+    fn screener_rules_compile_in_precedence_order() {
+        let block = ScreenerRule::new(RuleType::DomainBlock, "example.com", ScreenerAction::Reject);
+        let allow = ScreenerRule::new(
+            RuleType::Pattern,
+            "*@partners.example.com",
+            ScreenerAction::Approve,
+        )
+        .with_priority(10);
+        let other_account =
+            ScreenerRule::new(RuleType::DomainBlock, "ads.test", ScreenerAction::Reject)
+                .for_account(AccountId::from("personal"));
+
+        let script = SieveCompiler::new(account())
+            .compile(&[block, allow, other_account], &[], None)
+            .script;
+
+        assert!(script.contains("require [\"fileinto\"];"));
+        let allow_at = script
+            .find("if address :all :matches \"from\" \"*@partners.example.com\" {")
+            .unwrap();
+        let block_at = script
+            .find("} elsif anyof(address :domain :is \"from\" \"example.com\", address :domain :matches \"from\" \"*.example.com\") {")
+            .unwrap();
+        assert!(allow_at < block_at);
+        assert!(script.contains("    fileinto \"heap/Screened Out\";\r\n    stop;"));
+        assert!(!script.contains("ads.test"));
+    }
+
+    #[test]
+    fn approvals_alone_produce_no_screener_section() {
+        let allow = ScreenerRule::new(
+            RuleType::DomainAllow,
+            "example.com",
+            ScreenerAction::Approve,
+        );
+        let script = SieveCompiler::new(account())
+            .compile(&[allow], &[], None)
+            .script;
+        assert!(!script.contains("# Screener"));
+        assert!(!script.contains("require"));
+    }
+
+    #[test]
+    fn filter_rules_compile_tests_and_actions() {
+        let rule = FilterRule::new("CI \"builds\"")
+            .when(FilterCondition::ListId {
+                pattern: r"builds\.ci".to_string(),
+            })
+            .when(FilterCondition::LargerThan { bytes: 1000 })
+            .when(FilterCondition::Account {
+                account_id: account(),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("CI"),
+            })
+            .then(FilterAction::MarkRead)
+            .then(FilterAction::Archive)
+            .then(FilterAction::Forward {
+                to: "oncall@example.com".to_string(),
+            })
+            .then(FilterAction::Snooze { hours: 4 })
+            .stop_processing();
+
+        let compiled = SieveCompiler::new(account()).compile(&[], &[rule], None);
+        let script = compiled.script;
+
+        assert!(compiled.skipped.is_empty());
+
+        assert!(script.contains("require [\"copy\", \"fileinto\", \"imap4flags\", \"regex\"];"));
+        assert!(script.contains("# Filter: CI \"builds\""));
+        assert!(script.contains(
+            "if allof(header :regex \"list-id\" \"builds\\\\.ci\", size :over 1000, true) {"
+        ));
+        let body = [
+            "    addflag \"\\\\Seen\";",
+            "    redirect :copy \"oncall@example.com\";",
+            "    fileinto \"CI\";",
+            "    # snooze is applied by the app",
+            "    stop;",
+            "}",
+        ]
+        .join("\r\n");
+        assert!(script.contains(&body), "{}", script);
+    }
+
+    #[test]
+    fn labels_without_archive_are_copies() {
+        let rule = FilterRule::new("Receipts")
+            .when(FilterCondition::Subject {
+                pattern: "receipt".to_string(),
+            })
+            .when(FilterCondition::From {
+                pattern: "shop".to_string(),
+            })
+            .match_any()
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("Receipts"),
+            });
+        let mut disabled = rule.clone();
+        disabled.name = "Disabled".to_string();
+        disabled.enabled = false;
+
+        let script = SieveCompiler::new(account())
+            .compile(&[], &[rule, disabled], None)
+            .script;
+
+        assert!(script.contains(
+            "if anyof(header :regex \"subject\" \"receipt\", header :regex \"from\" \"shop\") {"
+        ));
+        assert!(script.contains("    fileinto :copy \"Receipts\";"));
+        assert!(!script.contains("Disabled"));
+    }
+
+    #[test]
+    fn rust_regex_syntax_is_translated_for_posix() {
+        let header = |pattern| posix_regex(pattern, true);
+        assert_eq!(
+            header(r"(?i)^invoice #\d+").as_deref(),
+            Some("^invoice #[0-9]+")
+        );
+        assert_eq!(
+            header(r"\w+@(?:mail\.)?example\.com$").as_deref(),
+            Some(r"[[:alnum:]_]+@(mail\.)?example\.com$")
+        );
+        assert_eq!(
+            header(r"(?P<user>[\w.\-]+)\s*<").as_deref(),
+            Some(r"([[:alnum:]_.-]+)[[:space:]]*<")
+        );
+        assert_eq!(header(r"re:.*?done").as_deref(), Some("re:.*done"));
+        assert_eq!(header(r"[\]a][\^][^\^]").as_deref(), Some(r"[]a]\^[^^]"));
+        assert_eq!(header(r"a{2, 3}\}").as_deref(), Some("a{2,3}[}]"));
+
+        // `.` only skips newlines in the app unless `s` is set
+        assert_eq!(posix_regex("unsubscribe.*here", false), None);
+        assert_eq!(
+            posix_regex("(?s)unsubscribe.*here", false).as_deref(),
+            Some("unsubscribe.*here")
+        );
+
+        for pattern in [
+            r"\bsale\b",
+            r"\p{Greek}",
+            r"(?x)a b",
+            r"(?-i)Sale",
+            r"[a-z&&[^q]]",
+            r"[[:^alpha:]]",
+            r"sale|",
+            r"()",
+            "",
+        ] {
+            assert_eq!(header(pattern), None, "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn untranslatable_rules_are_skipped_and_reported() {
+        let receipts = ScreenerRule::new(RuleType::Regex, r"^receipts\d*@", ScreenerAction::Reject)
+            .with_priority(10);
+        let word = ScreenerRule::new(RuleType::Regex, r"\bpromo\b", ScreenerAction::Approve)
+            .with_priority(5);
+        let below = ScreenerRule::new(RuleType::DomainBlock, "ads.test", ScreenerAction::Reject);
+
+        let sale = FilterRule::new("Sales")
+            .when(FilterCondition::Subject {
+                pattern: r"\bsale\b".to_string(),
+            })
+            .then(FilterAction::Archive);
+        let digest = FilterRule::new("Digests")
+            .when(FilterCondition::Subject {
+                pattern: r"(?i)digest #\d+".to_string(),
+            })
+            .then(FilterAction::MarkRead);
+        let mut attachments = FilterRule::new("Attachments")
+            .when(FilterCondition::HasAttachment)
+            .then(FilterAction::Star);
+        attachments.position = 1;
+        let mut stop = FilterRule::new("Stop")
+            .when(FilterCondition::Body {
+                pattern: "unsubscribe.*here".to_string(),
+            })
+            .then(FilterAction::Star)
+            .stop_processing();
+        stop.position = 2;
+        let mut after_stop = FilterRule::new("After stop")
+            .when(FilterCondition::HasAttachment)
+            .then(FilterAction::Star);
+        after_stop.position = 3;
+
+        let compiled = SieveCompiler::new(account()).compile(
+            &[receipts, word.clone(), below.clone()],
+            &[
+                sale.clone(),
+                digest,
+                attachments.clone(),
+                stop.clone(),
+                after_stop.clone(),
+            ],
+            None,
+        );
+
+        assert_eq!(
+            compiled.skipped,
+            vec![
+                word.id,
+                below.id,
+                sale.id,
+                attachments.id,
+                stop.id,
+                after_stop.id
+            ]
+        );
+        let script = compiled.script;
+        assert!(script.contains("if address :all :regex \"from\" \"^receipts[0-9]*@\" {"));
+        assert!(!script.contains("elsif"));
+        assert!(!script.contains("ads.test"));
+        assert!(script.contains("if allof(header :regex \"subject\" \"digest #[0-9]+\") {"));
+        assert!(!script.contains("Sales"));
+        assert!(!script.contains("Stop"));
+        assert!(!script.contains("Attachments"));
+        assert!(!script.contains("content-type"));
+        assert!(!script.contains("\\b"));
+        assert!(!script.contains("\"body\""));
+    }
+
+    #[test]
+    fn vacation_skips_lists_and_respects_dates() {
+        let vacation = VacationResponse::new("Away", "Back on \"Monday\".").between(
+            NaiveDate::from_ymd_opt(2026, 10, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 10, 14).unwrap(),
+        );
+        let vacation = VacationResponse {
+            addresses: vec!["me@work.example".to_string()],
+            ..vacation
+        };
+
+        let script = SieveCompiler::new(account())
+            .compile(&[], &[], Some(&vacation))
+            .script;
+
+        assert!(script.contains("require [\"date\", \"relational\", \"vacation\"];"));
+        assert!(script.contains("not exists \"list-id\""));
+        assert!(script.contains("currentdate :value \"ge\" \"date\" \"2026-10-01\""));
+        assert!(script.contains("currentdate :value \"le\" \"date\" \"2026-10-14\""));
+        assert!(script.contains(
+            "vacation :days 7 :subject \"Away\" :addresses [\"me@work.example\"] \"Back on \\\"Monday\\\".\";"
+        ));
+    }
+}
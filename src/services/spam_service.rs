@@ -0,0 +1,681 @@
+//! Local Bayesian spam filter.
+//!
+//! Neither provider exposes a junk folder, so the [`SpamService`] filters
+//! unwanted mail itself. Emails are split into header, body and link-domain
+//! tokens, and per-token spam and ham counts are kept in storage. The
+//! counts are trained from "report spam" and "not spam" actions, and new
+//! mail scored as spam during sync is moved from the inbox to the Junk view
+//! with an explanation naming the tokens that contributed most.
+
+use std::collections::{BTreeSet, HashMap};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::{system_labels, AccountId, Email, EmailId, LabelId};
+use crate::services::{ActionType, IncomingFilter, SenderSignals, ViewType};
+
+/// Most tokens that take part in a verdict.
+const MAX_INTERESTING_TOKENS: usize = 15;
+
+/// Tokens named in a verdict's explanation.
+const MAX_EXPLAINED_TOKENS: usize = 5;
+
+/// Tokens whose spam probability is closer to 0.5 than this are ignored.
+const MIN_TOKEN_DEVIATION: f32 = 0.1;
+
+/// Weight of the neutral prior in a token's spam probability.
+const PRIOR_STRENGTH: f32 = 1.0;
+
+/// Body characters tokenized.
+const BODY_TOKEN_CHARS: usize = 5000;
+
+/// Errors that can occur during spam filtering.
+#[derive(Debug, Error)]
+pub enum SpamError {
+    /// Storage error.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// The action does not train the filter.
+    #[error("not a spam action: {0:?}")]
+    NotSpamAction(ActionType),
+}
+
+/// Result type for spam operations.
+pub type SpamResult<T> = Result<T, SpamError>;
+
+/// How often something was seen in spam and in ham.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpamCounts {
+    /// Times seen in spam.
+    pub spam: u32,
+    /// Times seen in wanted mail.
+    pub ham: u32,
+}
+
+/// A token's contribution to a verdict.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenEvidence {
+    /// The token, with its kind prefix (e.g. `url:`).
+    pub token: String,
+    /// Probability that mail containing the token is spam.
+    pub spam_probability: f32,
+}
+
+impl TokenEvidence {
+    /// Describes the token for display.
+    pub fn describe(&self) -> String {
+        let (kind, value) = self.token.split_once(':').unwrap_or(("", &self.token));
+        match kind {
+            "url" => format!("link to {}", value),
+            "from" => format!("sender {}", value),
+            "domain" => format!("sender domain {}", value),
+            "subject" => format!("\"{}\" in the subject", value),
+            "esp" => format!("sent through {}", value),
+            _ => format!("\"{}\"", self.token),
+        }
+    }
+}
+
+/// Result of scoring an email.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamVerdict {
+    /// Combined spam probability, from 0.0 to 1.0.
+    pub score: f32,
+    /// Whether the score crossed the spam threshold.
+    pub is_spam: bool,
+    /// Tokens that pushed the score towards spam, strongest first.
+    pub top_tokens: Vec<TokenEvidence>,
+}
+
+impl SpamVerdict {
+    /// Explains the verdict in terms of its top contributing tokens.
+    pub fn explanation(&self) -> String {
+        if self.top_tokens.is_empty() {
+            return format!("Spam score {:.0}%", self.score * 100.0);
+        }
+        let reasons = self
+            .top_tokens
+            .iter()
+            .map(|t| format!("{} ({:.0}%)", t.describe(), t.spam_probability * 100.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("Spam score {:.0}%: {}", self.score * 100.0, reasons)
+    }
+}
+
+/// Settings for the spam filter.
+#[derive(Debug, Clone)]
+pub struct SpamSettings {
+    /// Score at or above which mail is treated as spam.
+    pub threshold: f32,
+    /// Messages of each kind to train on before any mail is filtered.
+    pub min_training_messages: u32,
+}
+
+impl Default for SpamSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.9,
+            min_training_messages: 5,
+        }
+    }
+}
+
+/// Storage trait for spam filter persistence.
+#[async_trait]
+pub trait SpamStorage: Send + Sync {
+    /// Gets the counts of the given tokens; unseen tokens are omitted.
+    async fn token_counts(
+        &self,
+        account_id: &AccountId,
+        tokens: &[String],
+    ) -> SpamResult<HashMap<String, SpamCounts>>;
+
+    /// Gets the number of messages trained as spam and as ham.
+    async fn message_counts(&self, account_id: &AccountId) -> SpamResult<SpamCounts>;
+
+    /// Gets how an email was trained, if it was.
+    async fn trained_as(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+    ) -> SpamResult<Option<bool>>;
+
+    /// Records an email as spam or ham, adding its tokens to the counts.
+    ///
+    /// If the email was trained the other way before, its tokens are first
+    /// removed from the counts they were added to.
+    async fn train(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+        tokens: &[String],
+        is_spam: bool,
+    ) -> SpamResult<()>;
+
+    /// Saves the verdict reached for an email.
+    async fn save_verdict(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+        verdict: &SpamVerdict,
+    ) -> SpamResult<()>;
+
+    /// Gets the verdict saved for an email.
+    async fn get_verdict(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+    ) -> SpamResult<Option<SpamVerdict>>;
+}
+
+/// Service that learns and applies the user's spam judgments.
+pub struct SpamService<S: SpamStorage> {
+    storage: S,
+    account_id: AccountId,
+    settings: SpamSettings,
+}
+
+impl<S: SpamStorage> SpamService<S> {
+    /// Creates a new spam service.
+    pub fn new(storage: S, account_id: AccountId) -> Self {
+        Self {
+            storage,
+            account_id,
+            settings: SpamSettings::default(),
+        }
+    }
+
+    /// Sets the filter settings.
+    pub fn with_settings(mut self, settings: SpamSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Returns the account ID.
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Trains the filter on an email.
+    pub async fn train(&self, email: &Email, is_spam: bool) -> SpamResult<()> {
+        if self.storage.trained_as(&self.account_id, &email.id).await? == Some(is_spam) {
+            return Ok(());
+        }
+        let tokens = tokenize(email);
+        self.storage
+            .train(&self.account_id, &email.id, &tokens, is_spam)
+            .await
+    }
+
+    /// Trains the filter from a "report spam" or "not spam" action.
+    pub async fn train_from_action(&self, action: &ActionType, email: &Email) -> SpamResult<()> {
+        match action {
+            ActionType::ReportSpam => self.train(email, true).await,
+            ActionType::NotSpam => self.train(email, false).await,
+            other => Err(SpamError::NotSpamAction(other.clone())),
+        }
+    }
+
+    /// Scores an email against the trained counts.
+    ///
+    /// Until enough spam and ham have been trained, every email scores 0.5
+    /// and nothing is treated as spam.
+    pub async fn classify(&self, email: &Email) -> SpamResult<SpamVerdict> {
+        let messages = self.storage.message_counts(&self.account_id).await?;
+        let min = self.settings.min_training_messages;
+        if messages.spam < min || messages.ham < min {
+            return Ok(SpamVerdict {
+                score: 0.5,
+                is_spam: false,
+                top_tokens: Vec::new(),
+            });
+        }
+
+        let tokens = tokenize(email);
+        let counts = self.storage.token_counts(&self.account_id, &tokens).await?;
+
+        let mut evidence: Vec<TokenEvidence> = counts
+            .into_iter()
+            .filter_map(|(token, counts)| {
+                let spam_probability = token_probability(counts, messages)?;
+                ((spam_probability - 0.5).abs() >= MIN_TOKEN_DEVIATION).then_some(TokenEvidence {
+                    token,
+                    spam_probability,
+                })
+            })
+            .collect();
+        evidence.sort_by(|a, b| {
+            (b.spam_probability - 0.5)
+                .abs()
+                .total_cmp(&(a.spam_probability - 0.5).abs())
+                .then_with(|| a.token.cmp(&b.token))
+        });
+        evidence.truncate(MAX_INTERESTING_TOKENS);
+
+        let log_odds: f32 = evidence
+            .iter()
+            .map(|e| (e.spam_probability / (1.0 - e.spam_probability)).ln())
+            .sum();
+        let score = 1.0 / (1.0 + (-log_odds).exp());
+
+        let mut top_tokens: Vec<TokenEvidence> = evidence
+            .into_iter()
+            .filter(|e| e.spam_probability > 0.5)
+            .collect();
+        top_tokens.sort_by(|a, b| b.spam_probability.total_cmp(&a.spam_probability));
+        top_tokens.truncate(MAX_EXPLAINED_TOKENS);
+
+        Ok(SpamVerdict {
+            score,
+            is_spam: score >= self.settings.threshold,
+            top_tokens,
+        })
+    }
+
+    /// Scores an email and, if it is spam, moves it from the inbox to Junk
+    /// and saves the verdict.
+    pub async fn filter(&self, email: &mut Email) -> SpamResult<SpamVerdict> {
+        let verdict = self.classify(email).await?;
+        if verdict.is_spam {
+            let inbox = system_labels::inbox();
+            let junk = LabelId::from(ViewType::Junk.folder_name());
+            email.labels.retain(|l| *l != inbox);
+            if !email.labels.contains(&junk) {
+                email.labels.push(junk);
+            }
+            self.storage
+                .save_verdict(&self.account_id, &email.id, &verdict)
+                .await?;
+        }
+        Ok(verdict)
+    }
+
+    /// Gets the saved verdict for an email routed to Junk.
+    pub async fn verdict(&self, email_id: &EmailId) -> SpamResult<Option<SpamVerdict>> {
+        self.storage.get_verdict(&self.account_id, email_id).await
+    }
+}
+
+#[async_trait]
+impl<S: SpamStorage> IncomingFilter for SpamService<S> {
+    async fn filter_incoming(&self, email: &mut Email) -> anyhow::Result<()> {
+        if email.account_id == self.account_id {
+            self.filter(email).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Probability that mail containing a token is spam.
+///
+/// Uses the token's frequency in each kind of mail, pulled towards 0.5 for
+/// rarely seen tokens and clamped away from certainty.
+fn token_probability(counts: SpamCounts, messages: SpamCounts) -> Option<f32> {
+    let seen = (counts.spam + counts.ham) as f32;
+    if seen == 0.0 {
+        return None;
+    }
+    let spam_freq = counts.spam as f32 / messages.spam.max(1) as f32;
+    let ham_freq = counts.ham as f32 / messages.ham.max(1) as f32;
+    let raw = spam_freq / (spam_freq + ham_freq);
+    let probability = (PRIOR_STRENGTH * 0.5 + seen * raw) / (PRIOR_STRENGTH + seen);
+    Some(probability.clamp(0.01, 0.99))
+}
+
+/// Splits an email into distinct header, body and link-domain tokens.
+pub fn tokenize(email: &Email) -> Vec<String> {
+    let signals = SenderSignals::from_email(email, None, 0);
+    let mut tokens = BTreeSet::new();
+
+    tokens.insert(format!("from:{}", signals.sender));
+    tokens.insert(format!("domain:{}", signals.domain));
+    if let Some(esp) = &signals.esp {
+        tokens.insert(format!("esp:{}", esp.to_lowercase()));
+    }
+    if let Some(reply_to) = email.header("Reply-To") {
+        if let Some((_, domain)) = reply_to.trim_end_matches('>').rsplit_once('@') {
+            tokens.insert(format!("reply-to:{}", domain.to_lowercase()));
+        }
+    }
+    if let Some(mailer) = email.header("X-Mailer") {
+        if let Some(name) = mailer.split_whitespace().next() {
+            tokens.insert(format!("mailer:{}", name.to_lowercase()));
+        }
+    }
+    for word in words(email.subject.as_deref().unwrap_or_default()) {
+        tokens.insert(format!("subject:{}", word));
+    }
+
+    let body: String = match (&email.body_text, &email.body_html) {
+        (Some(text), _) => text.clone(),
+        (None, Some(html)) => strip_tags(html),
+        (None, None) => email.snippet.clone(),
+    };
+    let body: String = body.chars().take(BODY_TOKEN_CHARS).collect();
+    tokens.extend(words(&body));
+
+    for text in [&email.body_text, &email.body_html].into_iter().flatten() {
+        tokens.extend(url_domains(text).into_iter().map(|d| format!("url:{}", d)));
+    }
+
+    tokens.into_iter().collect()
+}
+
+/// Splits text into lowercase words of a useful length.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '$' || c == '%'))
+        .filter(|w| (3..=24).contains(&w.chars().count()))
+        .filter(|w| !w.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Extracts the host names of http(s) links, without a leading `www.`.
+fn url_domains(text: &str) -> Vec<String> {
+    let mut domains = Vec::new();
+    for scheme in ["http://", "https://"] {
+        let mut rest = text;
+        while let Some(start) = rest.find(scheme) {
+            rest = &rest[start + scheme.len()..];
+            let host: String = rest
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '.' || *c == '-')
+                .collect::<String>()
+                .to_lowercase();
+            let host = host.trim_start_matches("www.").trim_end_matches('.');
+            if host.contains('.') {
+                domains.push(host.to_string());
+            }
+        }
+    }
+    domains
+}
+
+/// Removes HTML tags, keeping the text between them.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Address, MessageId, ThreadId};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockStorage {
+        tokens: Mutex<HashMap<String, SpamCounts>>,
+        trained: Mutex<HashMap<EmailId, bool>>,
+        verdicts: Mutex<HashMap<EmailId, SpamVerdict>>,
+    }
+
+    fn adjust(counts: &mut SpamCounts, is_spam: bool, add: bool) {
+        let count = if is_spam {
+            &mut counts.spam
+        } else {
+            &mut counts.ham
+        };
+        *count = if add {
+            *count + 1
+        } else {
+            count.saturating_sub(1)
+        };
+    }
+
+    #[async_trait]
+    impl SpamStorage for MockStorage {
+        async fn token_counts(
+            &self,
+            _account_id: &AccountId,
+            tokens: &[String],
+        ) -> SpamResult<HashMap<String, SpamCounts>> {
+            let counts = self.tokens.lock().unwrap();
+            Ok(tokens
+                .iter()
+                .filter_map(|t| counts.get(t).map(|c| (t.clone(), *c)))
+                .collect())
+        }
+
+        async fn message_counts(&self, _account_id: &AccountId) -> SpamResult<SpamCounts> {
+            let trained = self.trained.lock().unwrap();
+            let spam = trained.values().filter(|s| **s).count() as u32;
+            Ok(SpamCounts {
+                spam,
+                ham: trained.len() as u32 - spam,
+            })
+        }
+
+        async fn trained_as(
+            &self,
+            _account_id: &AccountId,
+            email_id: &EmailId,
+        ) -> SpamResult<Option<bool>> {
+            Ok(self.trained.lock().unwrap().get(email_id).copied())
+        }
+
+        async fn train(
+            &self,
+            _account_id: &AccountId,
+            email_id: &EmailId,
+            tokens: &[String],
+            is_spam: bool,
+        ) -> SpamResult<()> {
+            let previous = self
+                .trained
+                .lock()
+                .unwrap()
+                .insert(email_id.clone(), is_spam);
+            let mut counts = self.tokens.lock().unwrap();
+            for token in tokens {
+                let entry = counts.entry(token.clone()).or_default();
+                if let Some(previous) = previous {
+                    adjust(entry, previous, false);
+                }
+                adjust(entry, is_spam, true);
+            }
+            Ok(())
+        }
+
+        async fn save_verdict(
+            &self,
+            _account_id: &AccountId,
+            email_id: &EmailId,
+            verdict: &SpamVerdict,
+        ) -> SpamResult<()> {
+            self.verdicts
+                .lock()
+                .unwrap()
+                .insert(email_id.clone(), verdict.clone());
+            Ok(())
+        }
+
+        async fn get_verdict(
+            &self,
+            _account_id: &AccountId,
+            email_id: &EmailId,
+        ) -> SpamResult<Option<SpamVerdict>> {
+            Ok(self.verdicts.lock().unwrap().get(email_id).cloned())
+        }
+    }
+
+    fn make_email(id: &str, from: &str, subject: &str, body: &str) -> Email {
+        Email {
+            id: EmailId::from(id),
+            account_id: AccountId::from("account-1"),
+            thread_id: ThreadId::from(id),
+            message_id: MessageId::from(format!("<{}@example.com>", id)),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new(from),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some(subject.to_string()),
+            body_text: Some(body.to_string()),
+            body_html: None,
+            snippet: body.to_string(),
+            date: chrono::Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![system_labels::inbox()],
+            attachments: vec![],
+            headers: vec![],
+        }
+    }
+
+    fn spam(i: u32) -> Email {
+        make_email(
+            &format!("spam-{}", i),
+            &format!("winner{}@prizes.example", i),
+            "You have won a free cruise",
+            "Claim your prize now at https://www.claim-prize.example/win before it expires",
+        )
+    }
+
+    fn ham(i: u32) -> Email {
+        make_email(
+            &format!("ham-{}", i),
+            "alice@acme.example",
+            "Notes from the design review",
+            "Attached are the notes from today's review, see https://docs.acme.example/notes",
+        )
+    }
+
+    async fn trained_service() -> SpamService<MockStorage> {
+        let service = SpamService::new(MockStorage::default(), AccountId::from("account-1"));
+        for i in 0..5 {
+            service
+                .train_from_action(&ActionType::ReportSpam, &spam(i))
+                .await
+                .unwrap();
+            service
+                .train_from_action(&ActionType::NotSpam, &ham(i))
+                .await
+                .unwrap();
+        }
+        service
+    }
+
+    #[test]
+    fn tokens_cover_headers_body_and_links() {
+        let tokens = tokenize(&spam(1));
+        for expected in [
+            "from:winner1@prizes.example",
+            "domain:prizes.example",
+            "subject:cruise",
+            "prize",
+            "url:claim-prize.example",
+        ] {
+            assert!(tokens.contains(&expected.to_string()), "{}", expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn untrained_filter_lets_mail_through() {
+        let service = SpamService::new(MockStorage::default(), AccountId::from("account-1"));
+        let verdict = service.classify(&spam(1)).await.unwrap();
+        assert!(!verdict.is_spam);
+        assert_eq!(verdict.score, 0.5);
+    }
+
+    #[tokio::test]
+    async fn spam_is_routed_to_junk_with_explanation() {
+        let service = trained_service().await;
+
+        let mut email = spam(9);
+        let verdict = service.filter(&mut email).await.unwrap();
+        assert!(verdict.is_spam);
+        assert!(email
+            .labels
+            .contains(&LabelId::from(ViewType::Junk.folder_name())));
+        assert!(!email.labels.contains(&system_labels::inbox()));
+
+        let saved = service.verdict(&email.id).await.unwrap().unwrap();
+        assert_eq!(saved.top_tokens.len(), MAX_EXPLAINED_TOKENS);
+        assert!(saved.top_tokens.iter().all(|t| t.spam_probability > 0.9));
+        assert!(saved.explanation().starts_with("Spam score 100%: "));
+    }
+
+    #[test]
+    fn evidence_describes_token_kinds() {
+        let evidence = |token: &str| TokenEvidence {
+            token: token.to_string(),
+            spam_probability: 0.9,
+        };
+        assert_eq!(
+            evidence("url:claim-prize.example").describe(),
+            "link to claim-prize.example"
+        );
+        assert_eq!(
+            evidence("subject:cruise").describe(),
+            "\"cruise\" in the subject"
+        );
+        assert_eq!(evidence("prize").describe(), "\"prize\"");
+    }
+
+    #[tokio::test]
+    async fn ham_stays_in_inbox() {
+        let service = trained_service().await;
+
+        let mut email = ham(9);
+        let verdict = service.filter(&mut email).await.unwrap();
+        assert!(!verdict.is_spam);
+        assert!(verdict.score < 0.1);
+        assert!(email.labels.contains(&system_labels::inbox()));
+    }
+
+    #[tokio::test]
+    async fn not_spam_retrains_a_reported_email() {
+        let service = trained_service().await;
+        let email = spam(0);
+
+        service
+            .train_from_action(&ActionType::NotSpam, &email)
+            .await
+            .unwrap();
+
+        let storage = &service.storage;
+        let counts = storage
+            .token_counts(
+                &service.account_id,
+                &["from:winner0@prizes.example".to_string()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            counts["from:winner0@prizes.example"],
+            SpamCounts { spam: 0, ham: 1 }
+        );
+        let messages = storage.message_counts(&service.account_id).await.unwrap();
+        assert_eq!(messages, SpamCounts { spam: 4, ham: 6 });
+    }
+
+    #[tokio::test]
+    async fn other_actions_do_not_train() {
+        let service = trained_service().await;
+        assert!(matches!(
+            service
+                .train_from_action(&ActionType::Archive, &ham(1))
+                .await,
+            Err(SpamError::NotSpamAction(ActionType::Archive))
+        ));
+    }
+}
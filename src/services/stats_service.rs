@@ -92,6 +92,8 @@ pub struct AiStats {
     pub tokens_used: u64,
     /// Estimated cost in USD.
     pub estimated_cost_usd: f32,
+    /// Results served from the AI cache instead of the provider.
+    pub cache_hits: u32,
 }
 
 impl AiStats {
@@ -278,6 +280,8 @@ pub enum StatsEvent {
     AiComposeAccepted,
     /// Semantic search performed.
     AiSemanticSearch { tokens: u32 },
+    /// AI result served from the cache.
+    AiCacheHit,
     /// Response sent.
     ResponseSent { response_time_secs: u64 },
 }
@@ -319,10 +323,7 @@ impl<S: StatsStorage> StatsService<S> {
             .storage
             .get_email_counts(&self.account_id, start, now)
             .await?;
-        let mut ai = self
-            .storage
-            .get_ai_usage(&self.account_id, start, now)
-            .await?;
+        let ai = self.ai_usage(start, now).await?;
         let productivity = self
             .storage
             .get_session_data(&self.account_id, start, now)
@@ -340,9 +341,6 @@ impl<S: StatsStorage> StatsService<S> {
             .get_daily_activity(&self.account_id, start, now)
             .await?;
 
-        // Estimate AI cost
-        ai.estimate_cost(self.cost_per_1k_tokens);
-
         Ok(StatsReport {
             time_range,
             email,
@@ -368,9 +366,18 @@ impl<S: StatsStorage> StatsService<S> {
     pub async fn get_ai_stats(&self, time_range: StatsTimeRange) -> StatsResult<AiStats> {
         let now = Utc::now();
         let start = time_range.start_date().map(naive_date_to_utc);
+        self.ai_usage(start, now).await
+    }
+
+    /// Gets AI usage with its estimated cost.
+    async fn ai_usage(
+        &self,
+        start: Option<DateTime<Utc>>,
+        end: DateTime<Utc>,
+    ) -> StatsResult<AiStats> {
         let mut ai = self
             .storage
-            .get_ai_usage(&self.account_id, start, now)
+            .get_ai_usage(&self.account_id, start, end)
             .await?;
         ai.estimate_cost(self.cost_per_1k_tokens);
         Ok(ai)
@@ -428,6 +435,7 @@ impl<S: StatsStorage> StatsService<S> {
             report.ai.compose_assists
         ));
         csv.push_str(&format!("AI Tokens Used,{}\n", report.ai.tokens_used));
+        csv.push_str(&format!("AI Cache Hits,{}\n", report.ai.cache_hits));
         csv.push_str(&format!(
             "AI Estimated Cost,$\"{:.2}\"\n",
             report.ai.estimated_cost_usd
@@ -472,6 +480,7 @@ struct AiStatsExport {
     semantic_searches: u32,
     tokens_used: u64,
     estimated_cost_usd: f32,
+    cache_hits: u32,
 }
 
 impl From<&StatsReport> for ReportExport {
@@ -500,6 +509,7 @@ impl From<&StatsReport> for ReportExport {
                 semantic_searches: report.ai.semantic_searches,
                 tokens_used: report.ai.tokens_used,
                 estimated_cost_usd: report.ai.estimated_cost_usd,
+                cache_hits: report.ai.cache_hits,
             },
         }
     }
@@ -568,14 +578,63 @@ mod tests {
             ..Default::default()
         };
 
-        let service = StatsService::new(MockStorage, AccountId::from("test"));
+        let service = StatsService::new(MockStorage::default(), AccountId::from("test"));
         let csv = service.export_csv(&report);
 
         assert!(csv.contains("Emails Received,100"));
         assert!(csv.contains("Emails Sent,50"));
     }
 
-    struct MockStorage;
+    #[tokio::test]
+    async fn ai_cache_hits_are_aggregated_by_range() {
+        use crate::services::ai_service::tests::{make_thread, MemoryCache, MockProvider};
+        use crate::services::{AiService, AiSettings};
+        use std::sync::Arc;
+
+        let storage = MockStorage::default();
+        let ai_service = AiService::new(AiSettings::default());
+        ai_service
+            .register_provider(
+                "anthropic",
+                Arc::new(MockProvider::new(
+                    r#"{"text": "Nothing yet.", "key_points": [], "action_items": []}"#,
+                )),
+            )
+            .await;
+        ai_service.set_cache(Arc::new(MemoryCache::default())).await;
+        ai_service
+            .set_stats_storage(Arc::new(storage.clone()))
+            .await;
+
+        let thread = make_thread();
+        ai_service.summarize_thread(&thread).await.unwrap();
+        ai_service.summarize_thread(&thread).await.unwrap();
+
+        // A hit from last month only counts towards all time
+        storage.events.lock().unwrap().push((
+            thread.account_id.clone(),
+            Utc::now() - chrono::Duration::days(30),
+            StatsEvent::AiCacheHit,
+        ));
+
+        let service = StatsService::new(storage, thread.account_id.clone());
+        let week = service.get_ai_stats(StatsTimeRange::Week).await.unwrap();
+        assert_eq!(week.cache_hits, 1);
+        let report = service
+            .generate_report(StatsTimeRange::AllTime)
+            .await
+            .unwrap();
+        assert_eq!(report.ai.cache_hits, 2);
+    }
+
+    /// Events recorded by [`MockStorage`], with the account and time.
+    type EventLog = Vec<(AccountId, DateTime<Utc>, StatsEvent)>;
+
+    /// Storage that keeps recorded events and counts AI cache hits from them.
+    #[derive(Clone, Default)]
+    struct MockStorage {
+        events: std::sync::Arc<std::sync::Mutex<EventLog>>,
+    }
 
     #[async_trait]
     impl StatsStorage for MockStorage {
@@ -590,11 +649,24 @@ mod tests {
 
         async fn get_ai_usage(
             &self,
-            _account_id: &AccountId,
-            _start: Option<DateTime<Utc>>,
-            _end: DateTime<Utc>,
+            account_id: &AccountId,
+            start: Option<DateTime<Utc>>,
+            end: DateTime<Utc>,
         ) -> StatsResult<AiStats> {
-            Ok(AiStats::default())
+            let events = self.events.lock().unwrap();
+            let cache_hits = events
+                .iter()
+                .filter(|(id, at, event)| {
+                    id == account_id
+                        && start.map_or(true, |start| *at >= start)
+                        && *at <= end
+                        && matches!(event, StatsEvent::AiCacheHit)
+                })
+                .count();
+            Ok(AiStats {
+                cache_hits: cache_hits as u32,
+                ..Default::default()
+            })
         }
 
         async fn get_session_data(
@@ -632,11 +704,11 @@ mod tests {
             Ok(Vec::new())
         }
 
-        async fn record_event(
-            &self,
-            _account_id: &AccountId,
-            _event: StatsEvent,
-        ) -> StatsResult<()> {
+        async fn record_event(&self, account_id: &AccountId, event: StatsEvent) -> StatsResult<()> {
+            self.events
+                .lock()
+                .unwrap()
+                .push((account_id.clone(), Utc::now(), event));
             Ok(())
         }
     }
@@ -112,6 +112,12 @@ pub struct SyncResult {
     pub errors: Vec<String>,
     /// Duration of the sync operation.
     pub duration_ms: u64,
+    /// Emails inserted or updated locally by this sync.
+    #[serde(default)]
+    pub changed_email_ids: Vec<EmailId>,
+    /// Emails deleted locally by this sync.
+    #[serde(default)]
+    pub deleted_email_ids: Vec<EmailId>,
 }
 
 impl SyncResult {
@@ -205,6 +211,32 @@ pub trait SyncStorage: Send + Sync {
     async fn delete_email(&self, email_id: &EmailId) -> Result<()>;
 }
 
+/// Filter run on each new email before it is stored.
+///
+/// Filters may change the email, for example to move it out of the inbox.
+/// A failing filter is logged and skipped; the email is still stored.
+#[async_trait::async_trait]
+pub trait IncomingFilter: Send + Sync {
+    /// Inspects and possibly modifies a newly received email.
+    async fn filter_incoming(&self, email: &mut Email) -> Result<()>;
+
+    /// Called once the filtered email has been stored.
+    ///
+    /// Lets filters act on the stored email, for example to snooze its
+    /// thread or forward it.
+    async fn after_store(&self, _email: &Email) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called instead of [`after_store`](Self::after_store) when the
+    /// filtered email could not be stored.
+    ///
+    /// Lets filters drop anything they kept for the email.
+    async fn discard(&self, _email: &Email) -> Result<()> {
+        Ok(())
+    }
+}
+
 /// Event emitted by the sync service.
 #[derive(Debug, Clone)]
 pub enum SyncEvent {
@@ -253,6 +285,8 @@ pub struct SyncService<S: SyncStorage> {
     settings: RwLock<SyncSettings>,
     /// Current sync status by account.
     status: RwLock<HashMap<AccountId, SyncStatus>>,
+    /// Filters applied to new emails, in order.
+    filters: RwLock<Vec<Arc<dyn IncomingFilter>>>,
     /// Flag to stop background sync.
     stop_flag: AtomicBool,
     /// Event sender for sync events.
@@ -268,6 +302,7 @@ impl<S: SyncStorage + 'static> SyncService<S> {
             storage,
             settings: RwLock::new(settings),
             status: RwLock::new(HashMap::new()),
+            filters: RwLock::new(Vec::new()),
             stop_flag: AtomicBool::new(false),
             event_sender,
         }
@@ -291,6 +326,11 @@ impl<S: SyncStorage + 'static> SyncService<S> {
         status.remove(account_id);
     }
 
+    /// Adds a filter to run on new emails before they are stored.
+    pub async fn add_filter(&self, filter: Arc<dyn IncomingFilter>) {
+        self.filters.write().await.push(filter);
+    }
+
     /// Updates sync settings.
     pub async fn update_settings(&self, settings: SyncSettings) {
         let mut current = self.settings.write().await;
@@ -376,9 +416,17 @@ impl<S: SyncStorage + 'static> SyncService<S> {
 
         // Apply changes locally
         let mut errors = Vec::new();
+        let mut changed_email_ids = Vec::new();
+        let mut deleted_email_ids = Vec::new();
         for change in changes {
             if let Err(e) = self.apply_change(&change).await {
                 errors.push(format!("Failed to apply change: {}", e));
+                continue;
+            }
+            match change {
+                Change::NewEmail(email) => changed_email_ids.push(email.id),
+                Change::Updated(email_id, _) => changed_email_ids.push(email_id),
+                Change::Deleted(email_id) => deleted_email_ids.push(email_id),
             }
         }
 
@@ -415,6 +463,8 @@ impl<S: SyncStorage + 'static> SyncService<S> {
             pending_synced: synced_count,
             errors,
             duration_ms: 0, // Filled in by caller
+            changed_email_ids,
+            deleted_email_ids,
         })
     }
 
@@ -422,7 +472,26 @@ impl<S: SyncStorage + 'static> SyncService<S> {
     async fn apply_change(&self, change: &Change) -> Result<()> {
         match change {
             Change::NewEmail(email) => {
-                self.storage.insert_email(email).await?;
+                let mut email = email.as_ref().clone();
+                let filters = self.filters.read().await;
+                for filter in filters.iter() {
+                    if let Err(e) = filter.filter_incoming(&mut email).await {
+                        tracing::warn!("Incoming filter failed for {}: {}", email.id, e);
+                    }
+                }
+                if let Err(e) = self.storage.insert_email(&email).await {
+                    for filter in filters.iter() {
+                        if let Err(e) = filter.discard(&email).await {
+                            tracing::warn!("Incoming filter failed to discard {}: {}", email.id, e);
+                        }
+                    }
+                    return Err(e);
+                }
+                for filter in filters.iter() {
+                    if let Err(e) = filter.after_store(&email).await {
+                        tracing::warn!("Incoming filter failed for stored {}: {}", email.id, e);
+                    }
+                }
             }
             Change::Updated(email_id, updates) => {
                 self.storage.update_email(email_id, updates).await?;
@@ -517,6 +586,242 @@ impl<S: SyncStorage + 'static> SyncService<S> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::{system_labels, Address, MessageId, ThreadId};
+    use std::sync::Mutex;
+
+    /// Records calls from storage and filters in one log, in order.
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct MockStorage {
+        log: Log,
+        emails: Mutex<Vec<Email>>,
+        fail_insert: bool,
+    }
+
+    impl MockStorage {
+        fn new(log: Log) -> Self {
+            Self {
+                log,
+                emails: Mutex::new(Vec::new()),
+                fail_insert: false,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl SyncStorage for MockStorage {
+        async fn get_sync_state(&self, _account_id: &AccountId) -> Result<SyncState> {
+            Ok(SyncState::now())
+        }
+
+        async fn update_sync_state(
+            &self,
+            _account_id: &AccountId,
+            _state: SyncState,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_pending_changes(&self, _account_id: &AccountId) -> Result<Vec<PendingChange>> {
+            Ok(Vec::new())
+        }
+
+        async fn mark_change_synced(&self, _change_id: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn insert_email(&self, email: &Email) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("insert {}", email.id));
+            if self.fail_insert {
+                anyhow::bail!("disk full");
+            }
+            self.emails.lock().unwrap().push(email.clone());
+            Ok(())
+        }
+
+        async fn update_email(&self, _email_id: &EmailId, _updates: &EmailUpdates) -> Result<()> {
+            Ok(())
+        }
+
+        async fn delete_email(&self, _email_id: &EmailId) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    struct MockProvider {
+        emails: Vec<Email>,
+    }
+
+    #[async_trait::async_trait]
+    impl SyncProvider for MockProvider {
+        async fn fetch_changes_since(&self, _state: &SyncState) -> Result<Vec<Change>> {
+            Ok(self
+                .emails
+                .iter()
+                .map(|e| Change::NewEmail(Box::new(e.clone())))
+                .collect())
+        }
+
+        async fn push_change(&self, _change: &PendingChange) -> Result<()> {
+            Ok(())
+        }
+
+        async fn get_current_state(&self) -> Result<SyncState> {
+            Ok(SyncState::now())
+        }
+    }
+
+    /// Archives every email and logs each hook it is called through.
+    struct DeferringFilter {
+        log: Log,
+    }
+
+    #[async_trait::async_trait]
+    impl IncomingFilter for DeferringFilter {
+        async fn filter_incoming(&self, email: &mut Email) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("filter {}", email.id));
+            email.labels.retain(|l| *l != system_labels::inbox());
+            Ok(())
+        }
+
+        async fn after_store(&self, email: &Email) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("after_store {}", email.id));
+            Ok(())
+        }
+
+        async fn discard(&self, email: &Email) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("discard {}", email.id));
+            Ok(())
+        }
+    }
+
+    struct FailingFilter;
+
+    #[async_trait::async_trait]
+    impl IncomingFilter for FailingFilter {
+        async fn filter_incoming(&self, email: &mut Email) -> Result<()> {
+            email.is_starred = true;
+            anyhow::bail!("rules unavailable")
+        }
+    }
+
+    fn account() -> AccountId {
+        AccountId::from("account-1")
+    }
+
+    fn new_email(id: &str) -> Email {
+        Email {
+            id: EmailId::from(id),
+            account_id: account(),
+            thread_id: ThreadId::from(format!("thread-{}", id)),
+            message_id: MessageId::from(format!("<{}@example.com>", id)),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new("sender@example.com"),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some("Hello".to_string()),
+            body_text: Some("Hi there".to_string()),
+            body_html: None,
+            snippet: "Hi there".to_string(),
+            date: Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![system_labels::inbox()],
+            attachments: vec![],
+            headers: vec![],
+        }
+    }
+
+    async fn service_with(storage: MockStorage, emails: Vec<Email>) -> SyncService<MockStorage> {
+        let service = SyncService::new(Arc::new(storage), SyncSettings::default());
+        service
+            .register_provider(account(), Arc::new(MockProvider { emails }))
+            .await;
+        service
+    }
+
+    #[tokio::test]
+    async fn filters_change_new_mail_before_it_is_stored() {
+        let log = Log::default();
+        let service = service_with(MockStorage::new(log.clone()), vec![new_email("1")]).await;
+        service.add_filter(Arc::new(FailingFilter)).await;
+        service
+            .add_filter(Arc::new(DeferringFilter { log: log.clone() }))
+            .await;
+
+        let result = service.sync_account(&account()).await.unwrap();
+
+        assert!(result.is_success());
+        assert_eq!(result.changed_email_ids, vec![EmailId::from("1")]);
+        let stored = service.storage.emails.lock().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert!(!stored[0].labels.contains(&system_labels::inbox()));
+        // A failing filter is skipped, but changes it made are kept.
+        assert!(stored[0].is_starred);
+    }
+
+    #[tokio::test]
+    async fn deferred_filter_work_runs_after_store() {
+        let log = Log::default();
+        let service = service_with(
+            MockStorage::new(log.clone()),
+            vec![new_email("1"), new_email("2")],
+        )
+        .await;
+        service
+            .add_filter(Arc::new(DeferringFilter { log: log.clone() }))
+            .await;
+
+        service.sync_account(&account()).await.unwrap();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "filter 1",
+                "insert 1",
+                "after_store 1",
+                "filter 2",
+                "insert 2",
+                "after_store 2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_store_discards_filter_work() {
+        let log = Log::default();
+        let mut storage = MockStorage::new(log.clone());
+        storage.fail_insert = true;
+        let service = service_with(storage, vec![new_email("1")]).await;
+        service
+            .add_filter(Arc::new(DeferringFilter { log: log.clone() }))
+            .await;
+
+        let result = service.sync_account(&account()).await.unwrap();
+
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.changed_email_ids.is_empty());
+        assert!(service.storage.emails.lock().unwrap().is_empty());
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["filter 1", "insert 1", "discard 1"]
+        );
+    }
 
     #[test]
     fn sync_state_now() {
@@ -534,6 +839,8 @@ mod tests {
             pending_synced: 2,
             errors: vec![],
             duration_ms: 1500,
+            changed_email_ids: vec![],
+            deleted_email_ids: vec![],
         };
         assert!(success.is_success());
 
@@ -544,6 +851,8 @@ mod tests {
             pending_synced: 0,
             errors: vec!["Connection failed".to_string()],
             duration_ms: 500,
+            changed_email_ids: vec![],
+            deleted_email_ids: vec![],
         };
         assert!(!failure.is_success());
     }
@@ -0,0 +1,686 @@
+//! On-device triage classifier learned from the user's own decisions.
+//!
+//! The [`TriageService`] trains one logistic regression per label over
+//! hashed sender, header and text features, optionally extended with the
+//! email's stored embedding. It learns online from the user's actions:
+//! screener approvals and rejections, category labels applied through the
+//! label service, emails archived without being read, and manual smart view
+//! assignments.
+//!
+//! Predictions are used to pre-fill [`Category`] and [`SmartViewType`]
+//! classifications instantly and offline. Categorization falls back to the
+//! LLM through [`AiService`] only when no learned category is confident.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::{AccountId, Email, Label};
+use crate::services::{AiService, Category, Classification, SenderSignals, SmartViewType};
+
+/// Version of the feature extraction.
+///
+/// Stored with the model; a model trained on other features is discarded on
+/// load.
+const TRIAGE_FEATURE_VERSION: u32 = 1;
+
+/// Number of buckets sparse features are hashed into.
+const FEATURE_BUCKETS: u32 = 1 << 20;
+
+/// Body characters tokenized for features.
+const BODY_FEATURE_CHARS: usize = 1000;
+
+/// Errors that can occur during triage operations.
+#[derive(Debug, Error)]
+pub enum TriageError {
+    /// Storage error.
+    #[error("storage error: {0}")]
+    Storage(String),
+
+    /// LLM fallback classification error.
+    #[error("classification error: {0}")]
+    Classification(String),
+}
+
+/// Result type for triage operations.
+pub type TriageResult<T> = Result<T, TriageError>;
+
+/// Something the classifier predicts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriageLabel {
+    /// The email belongs to a category.
+    Category(Category),
+    /// The thread belongs in a smart view.
+    View(SmartViewType),
+    /// The user would approve the sender in the screener.
+    ScreenerApproval,
+}
+
+impl TriageLabel {
+    /// Returns every label the classifier can learn.
+    pub fn all() -> Vec<TriageLabel> {
+        Category::all()
+            .iter()
+            .map(|c| TriageLabel::Category(*c))
+            .chain(SmartViewType::all().iter().map(|v| TriageLabel::View(*v)))
+            .chain(std::iter::once(TriageLabel::ScreenerApproval))
+            .collect()
+    }
+
+    /// Stable key the label's weights are stored under.
+    fn key(&self) -> String {
+        match self {
+            TriageLabel::Category(category) => format!("category:{}", category.display_name()),
+            TriageLabel::View(view) => format!("view:{:?}", view),
+            TriageLabel::ScreenerApproval => "screener:approve".to_string(),
+        }
+    }
+}
+
+/// A user action the classifier learns from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrainingSignal {
+    /// The sender was approved or rejected in the screener.
+    Screener {
+        /// Whether the sender was approved.
+        approved: bool,
+    },
+    /// A label matching a category was applied to the email.
+    Category(Category),
+    /// The email was archived without being read.
+    ArchivedUnread,
+    /// The thread was assigned to a smart view by hand.
+    Manual(SmartViewType),
+}
+
+impl TrainingSignal {
+    /// Creates a signal from a label applied through the label service, if
+    /// the label's name matches a category.
+    pub fn from_label(label: &Label) -> Option<Self> {
+        let name = label.name.trim();
+        Category::all()
+            .iter()
+            .find(|c| c.display_name().eq_ignore_ascii_case(name))
+            .map(|c| TrainingSignal::Category(*c))
+    }
+
+    /// Creates a signal from a classification, if it was assigned manually.
+    pub fn from_classification(classification: &Classification) -> Option<Self> {
+        classification
+            .manual
+            .then_some(TrainingSignal::Manual(classification.view_type))
+    }
+
+    /// Labelled examples this signal provides, as (label, is positive).
+    fn examples(&self) -> Vec<(TriageLabel, bool)> {
+        match self {
+            TrainingSignal::Screener { approved } => {
+                vec![(TriageLabel::ScreenerApproval, *approved)]
+            }
+            TrainingSignal::Category(category) => Category::all()
+                .iter()
+                .map(|c| (TriageLabel::Category(*c), c == category))
+                .collect(),
+            TrainingSignal::ArchivedUnread => vec![
+                (TriageLabel::View(SmartViewType::NeedsReply), false),
+                (TriageLabel::View(SmartViewType::Vip), false),
+                (TriageLabel::View(SmartViewType::FollowUp), false),
+            ],
+            TrainingSignal::Manual(view) => vec![(TriageLabel::View(*view), true)],
+        }
+    }
+}
+
+/// Settings for the triage classifier.
+#[derive(Debug, Clone)]
+pub struct TriageSettings {
+    /// Probability a prediction needs before it is used.
+    pub confidence_threshold: f32,
+    /// Positive and negative examples a label needs before it is predicted.
+    pub min_examples: u32,
+    /// Step size of each online update.
+    pub learning_rate: f32,
+    /// L2 regularization applied to updated weights.
+    pub l2: f32,
+}
+
+impl Default for TriageSettings {
+    fn default() -> Self {
+        Self {
+            confidence_threshold: 0.8,
+            min_examples: 3,
+            learning_rate: 0.5,
+            l2: 1e-4,
+        }
+    }
+}
+
+/// A label predicted for an email.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriagePrediction {
+    /// The predicted label.
+    pub label: TriageLabel,
+    /// Probability that the label applies.
+    pub probability: f32,
+}
+
+/// Sparse feature vector of an email.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriageFeatures(Vec<(u32, f32)>);
+
+impl TriageFeatures {
+    /// Extracts features from an email and, if available, its embedding.
+    pub fn from_email(email: &Email, embedding: Option<&[f32]>) -> Self {
+        let signals = SenderSignals::from_email(email, None, 0);
+        let mut features = HashMap::new();
+        let mut add = |name: String, value: f32| {
+            *features.entry(hash_feature(&name)).or_insert(0.0) += value;
+        };
+
+        add(format!("from:{}", signals.sender), 1.0);
+        add(format!("domain:{}", signals.domain), 1.0);
+        if signals.list_unsubscribe {
+            add("list_unsubscribe".to_string(), 1.0);
+        }
+        if signals.bulk_precedence {
+            add("bulk".to_string(), 1.0);
+        }
+        if signals.no_reply {
+            add("no_reply".to_string(), 1.0);
+        }
+        if let Some(list_id) = &signals.list_id {
+            add(format!("list:{}", list_id.to_lowercase()), 1.0);
+        }
+        if let Some(esp) = &signals.esp {
+            add(format!("esp:{}", esp), 1.0);
+        }
+        if !email.attachments.is_empty() {
+            add("attachments".to_string(), 1.0);
+        }
+        if email.in_reply_to.is_some() {
+            add("reply".to_string(), 1.0);
+        }
+        if !email.cc.is_empty() {
+            add("cc".to_string(), 1.0);
+        }
+
+        let subject = tokenize(email.subject.as_deref().unwrap_or_default());
+        for token in &subject {
+            add(
+                format!("subject:{}", token),
+                1.0 / (subject.len() as f32).sqrt(),
+            );
+        }
+        let body: String = email
+            .body_text
+            .as_deref()
+            .unwrap_or(&email.snippet)
+            .chars()
+            .take(BODY_FEATURE_CHARS)
+            .collect();
+        let body = tokenize(&body);
+        for token in &body {
+            add(format!("body:{}", token), 1.0 / (body.len() as f32).sqrt());
+        }
+
+        let mut features: Vec<(u32, f32)> = features.into_iter().collect();
+        if let Some(embedding) = embedding {
+            features.extend(
+                embedding
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| (FEATURE_BUCKETS + i as u32, *value)),
+            );
+        }
+        features.sort_by_key(|(index, _)| *index);
+        Self(features)
+    }
+}
+
+/// Weights of one label's logistic regression.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LabelModel {
+    bias: f32,
+    weights: HashMap<u32, f32>,
+    positives: u32,
+    negatives: u32,
+}
+
+impl LabelModel {
+    fn probability(&self, features: &TriageFeatures) -> f32 {
+        let z = features.0.iter().fold(self.bias, |z, (index, value)| {
+            z + self.weights.get(index).copied().unwrap_or(0.0) * value
+        });
+        1.0 / (1.0 + (-z).exp())
+    }
+
+    fn train(&mut self, features: &TriageFeatures, positive: bool, settings: &TriageSettings) {
+        let target = if positive { 1.0 } else { 0.0 };
+        let gradient = self.probability(features) - target;
+
+        self.bias -= settings.learning_rate * gradient;
+        for (index, value) in &features.0 {
+            let weight = self.weights.entry(*index).or_insert(0.0);
+            *weight -= settings.learning_rate * (gradient * value + settings.l2 * *weight);
+        }
+
+        if positive {
+            self.positives += 1;
+        } else {
+            self.negatives += 1;
+        }
+    }
+}
+
+/// One-vs-rest logistic regression over all triage labels.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriageModel {
+    version: u32,
+    labels: HashMap<String, LabelModel>,
+}
+
+impl Default for TriageModel {
+    fn default() -> Self {
+        Self {
+            version: TRIAGE_FEATURE_VERSION,
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl TriageModel {
+    /// Updates the model with the examples of a training signal.
+    pub fn train(
+        &mut self,
+        features: &TriageFeatures,
+        signal: TrainingSignal,
+        settings: &TriageSettings,
+    ) {
+        for (label, positive) in signal.examples() {
+            self.labels
+                .entry(label.key())
+                .or_default()
+                .train(features, positive, settings);
+        }
+    }
+
+    /// Predicts every label with enough examples, most probable first.
+    pub fn predict(
+        &self,
+        features: &TriageFeatures,
+        settings: &TriageSettings,
+    ) -> Vec<TriagePrediction> {
+        let mut predictions: Vec<TriagePrediction> = TriageLabel::all()
+            .into_iter()
+            .filter_map(|label| {
+                let model = self.labels.get(&label.key())?;
+                let trained = model.positives >= settings.min_examples
+                    && model.negatives >= settings.min_examples;
+                trained.then(|| TriagePrediction {
+                    label,
+                    probability: model.probability(features),
+                })
+            })
+            .collect();
+        predictions.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+        predictions
+    }
+}
+
+/// Storage trait for persisting triage models.
+#[async_trait]
+pub trait TriageStorage: Send + Sync {
+    /// Loads the model for an account.
+    async fn load_model(&self, account_id: &AccountId) -> TriageResult<Option<TriageModel>>;
+
+    /// Saves the model for an account.
+    async fn save_model(&self, account_id: &AccountId, model: &TriageModel) -> TriageResult<()>;
+}
+
+/// Service that learns triage decisions and predicts them for new mail.
+pub struct TriageService<S: TriageStorage> {
+    storage: S,
+    account_id: AccountId,
+    settings: TriageSettings,
+    model: RwLock<TriageModel>,
+}
+
+impl<S: TriageStorage> TriageService<S> {
+    /// Creates a new triage service with an untrained model.
+    pub fn new(storage: S, account_id: AccountId) -> Self {
+        Self {
+            storage,
+            account_id,
+            settings: TriageSettings::default(),
+            model: RwLock::new(TriageModel::default()),
+        }
+    }
+
+    /// Sets the classifier settings.
+    pub fn with_settings(mut self, settings: TriageSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Returns the account ID.
+    pub fn account_id(&self) -> &AccountId {
+        &self.account_id
+    }
+
+    /// Loads the stored model, keeping the untrained one if none matches the
+    /// current feature version.
+    pub async fn load(&self) -> TriageResult<()> {
+        if let Some(model) = self.storage.load_model(&self.account_id).await? {
+            if model.version == TRIAGE_FEATURE_VERSION {
+                *self.model.write().unwrap() = model;
+            }
+        }
+        Ok(())
+    }
+
+    /// Learns from a user action on an email and saves the updated model.
+    pub async fn record(
+        &self,
+        email: &Email,
+        embedding: Option<&[f32]>,
+        signal: TrainingSignal,
+    ) -> TriageResult<()> {
+        let features = TriageFeatures::from_email(email, embedding);
+        let snapshot = {
+            let mut model = self.model.write().unwrap();
+            model.train(&features, signal, &self.settings);
+            model.clone()
+        };
+        self.storage.save_model(&self.account_id, &snapshot).await
+    }
+
+    /// Predicts labels for an email, most probable first.
+    pub fn predict(&self, email: &Email, embedding: Option<&[f32]>) -> Vec<TriagePrediction> {
+        let features = TriageFeatures::from_email(email, embedding);
+        self.model
+            .read()
+            .unwrap()
+            .predict(&features, &self.settings)
+    }
+
+    /// Returns the categories predicted with confidence, if any.
+    pub fn confident_categories(
+        &self,
+        email: &Email,
+        embedding: Option<&[f32]>,
+    ) -> Option<Vec<Category>> {
+        let categories: Vec<Category> = self
+            .predict(email, embedding)
+            .into_iter()
+            .filter(|p| p.probability >= self.settings.confidence_threshold)
+            .filter_map(|p| match p.label {
+                TriageLabel::Category(category) => Some(category),
+                _ => None,
+            })
+            .collect();
+        (!categories.is_empty()).then_some(categories)
+    }
+
+    /// Categorizes an email, asking the LLM only when no learned category
+    /// is confident.
+    pub async fn categorize(
+        &self,
+        email: &Email,
+        embedding: Option<&[f32]>,
+        ai: &AiService,
+    ) -> TriageResult<Vec<Category>> {
+        if let Some(categories) = self.confident_categories(email, embedding) {
+            return Ok(categories);
+        }
+        ai.categorize_email(email)
+            .await
+            .map_err(|e| TriageError::Classification(e.to_string()))
+    }
+
+    /// Smart view classifications predicted with confidence for an email's
+    /// thread.
+    pub fn prefill_views(&self, email: &Email, embedding: Option<&[f32]>) -> Vec<Classification> {
+        self.predict(email, embedding)
+            .into_iter()
+            .filter(|p| p.probability >= self.settings.confidence_threshold)
+            .filter_map(|p| match p.label {
+                TriageLabel::View(view) => Some(Classification::ai_classified(
+                    email.thread_id.clone(),
+                    view,
+                    p.probability,
+                    "Learned from your past decisions",
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Hashes a feature name into a bucket (FNV-1a).
+fn hash_feature(name: &str) -> u32 {
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    (hash % u64::from(FEATURE_BUCKETS)) as u32
+}
+
+/// Splits text into distinct lowercase word tokens.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let len = word.chars().count();
+        if !(3..=24).contains(&len) || word.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let word = word.to_lowercase();
+        if !tokens.contains(&word) {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Address, EmailId, Header, LabelId, MessageId, ThreadId};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MockStorage {
+        model: Mutex<Option<TriageModel>>,
+    }
+
+    #[async_trait]
+    impl TriageStorage for MockStorage {
+        async fn load_model(&self, _account_id: &AccountId) -> TriageResult<Option<TriageModel>> {
+            Ok(self.model.lock().unwrap().clone())
+        }
+
+        async fn save_model(
+            &self,
+            _account_id: &AccountId,
+            model: &TriageModel,
+        ) -> TriageResult<()> {
+            *self.model.lock().unwrap() = Some(model.clone());
+            Ok(())
+        }
+    }
+
+    fn make_email(from: &str, subject: &str, body: &str, bulk: bool) -> Email {
+        Email {
+            id: EmailId::from("email-1"),
+            account_id: AccountId::from("account-1"),
+            thread_id: ThreadId::from("thread-1"),
+            message_id: MessageId::from("<email-1@example.com>"),
+            in_reply_to: None,
+            references: vec![],
+            from: Address::new(from),
+            to: vec![Address::new("me@example.com")],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some(subject.to_string()),
+            body_text: Some(body.to_string()),
+            body_html: None,
+            snippet: body.to_string(),
+            date: chrono::Utc::now(),
+            is_read: false,
+            is_starred: false,
+            is_draft: false,
+            labels: vec![],
+            attachments: vec![],
+            headers: if bulk {
+                vec![Header::new(
+                    "List-Unsubscribe",
+                    "<mailto:unsub@example.com>",
+                )]
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    fn newsletter(issue: u32) -> Email {
+        make_email(
+            "news@digest.example",
+            &format!("Weekly digest issue {}", issue),
+            "Top stories this week in tech and science",
+            true,
+        )
+    }
+
+    fn work() -> Email {
+        make_email(
+            "alice@acme.example",
+            "Quarterly plan review",
+            "Can we go over the roadmap before the meeting on Friday",
+            false,
+        )
+    }
+
+    async fn trained_service(rounds: u32) -> TriageService<MockStorage> {
+        let service = TriageService::new(MockStorage::default(), AccountId::from("account-1"));
+        for issue in 0..rounds {
+            service
+                .record(
+                    &newsletter(issue),
+                    None,
+                    TrainingSignal::Category(Category::Newsletters),
+                )
+                .await
+                .unwrap();
+            service
+                .record(&work(), None, TrainingSignal::Category(Category::Work))
+                .await
+                .unwrap();
+        }
+        service
+    }
+
+    #[tokio::test]
+    async fn learns_categories_from_labels() {
+        let service = trained_service(4).await;
+
+        let email = make_email(
+            "news@digest.example",
+            "Weekly digest issue 9",
+            "More top stories this week",
+            true,
+        );
+        assert_eq!(
+            service.confident_categories(&email, None),
+            Some(vec![Category::Newsletters])
+        );
+
+        let predictions = service.predict(&email, None);
+        assert_eq!(
+            predictions[0].label,
+            TriageLabel::Category(Category::Newsletters)
+        );
+        let work = predictions
+            .iter()
+            .find(|p| p.label == TriageLabel::Category(Category::Work))
+            .unwrap();
+        assert!(work.probability < 0.5);
+    }
+
+    #[tokio::test]
+    async fn unfamiliar_email_is_left_to_the_llm() {
+        let service = trained_service(4).await;
+
+        let email = make_email(
+            "someone@elsewhere.example",
+            "Garden party",
+            "Bring snacks on Sunday",
+            false,
+        );
+        assert_eq!(service.confident_categories(&email, None), None);
+    }
+
+    #[tokio::test]
+    async fn labels_need_enough_examples() {
+        let service = trained_service(2).await;
+        assert!(service.predict(&newsletter(9), None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn model_is_saved_and_loaded() {
+        let service = trained_service(4).await;
+        let storage = MockStorage {
+            model: Mutex::new(service.storage.model.lock().unwrap().clone()),
+        };
+
+        let restored = TriageService::new(storage, AccountId::from("account-1"));
+        restored.load().await.unwrap();
+        assert_eq!(
+            restored.confident_categories(&newsletter(9), None),
+            Some(vec![Category::Newsletters])
+        );
+    }
+
+    #[test]
+    fn signals_from_user_actions() {
+        let label = Label {
+            id: LabelId::from("label-1"),
+            account_id: AccountId::from("account-1"),
+            name: "finance".to_string(),
+            color: None,
+            is_system: false,
+            provider_id: None,
+        };
+        assert_eq!(
+            TrainingSignal::from_label(&label),
+            Some(TrainingSignal::Category(Category::Finance))
+        );
+
+        let manual = Classification::manual(
+            ThreadId::from("thread-1"),
+            SmartViewType::FollowUp,
+            "Pinned",
+        );
+        assert_eq!(
+            TrainingSignal::from_classification(&manual),
+            Some(TrainingSignal::Manual(SmartViewType::FollowUp))
+        );
+        let automatic = Classification::ai_classified(
+            ThreadId::from("thread-1"),
+            SmartViewType::NeedsReply,
+            0.9,
+            "Unread",
+        );
+        assert_eq!(TrainingSignal::from_classification(&automatic), None);
+    }
+
+    #[test]
+    fn embeddings_extend_the_features() {
+        let email = work();
+        let plain = TriageFeatures::from_email(&email, None);
+        let embedded = TriageFeatures::from_email(&email, Some(&[0.6, 0.8]));
+
+        assert_eq!(embedded.0.len(), plain.0.len() + 2);
+        assert_eq!(embedded.0.last(), Some(&(FEATURE_BUCKETS + 1, 0.8)));
+    }
+}
@@ -93,6 +93,10 @@ impl Database {
             for migration in schema::all_migrations() {
                 conn.execute_batch(migration)?;
             }
+            add_missing_columns(&conn)?;
+            for migration in schema::column_migrations() {
+                conn.execute_batch(migration)?;
+            }
 
             Ok(())
         })
@@ -158,6 +162,24 @@ impl Database {
     }
 }
 
+/// Adds any [`schema::ADDED_COLUMNS`] that existing tables lack.
+fn add_missing_columns(conn: &Connection) -> Result<()> {
+    for (table, column, definition) in schema::ADDED_COLUMNS {
+        let (columns, present): (u32, u32) = conn.query_row(
+            "SELECT COUNT(*), COUNT(*) FILTER (WHERE name = ?2) FROM pragma_table_info(?1)",
+            [table, column],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        if columns > 0 && present == 0 {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))?;
+        }
+    }
+    Ok(())
+}
+
 impl std::fmt::Debug for Database {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Database").finish_non_exhaustive()
@@ -188,6 +210,68 @@ mod tests {
         assert!(tables.contains(&"labels".to_string()));
     }
 
+    #[test]
+    fn missing_columns_are_added_to_old_tables() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE screener_rules (
+                id TEXT PRIMARY KEY,
+                rule_type TEXT NOT NULL,
+                pattern TEXT NOT NULL,
+                action TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+        )
+        .unwrap();
+
+        add_missing_columns(&conn).unwrap();
+        add_missing_columns(&conn).unwrap();
+
+        let priority: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('screener_rules') WHERE name = 'priority'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(priority, 1);
+    }
+
+    #[tokio::test]
+    async fn screener_entries_are_backfilled_to_accounts() {
+        let db = Database::open_in_memory().await.unwrap();
+
+        let assigned: Vec<(String, String)> = db
+            .with_conn(|conn| {
+                conn.execute_batch(
+                    "INSERT INTO accounts (id, email, provider_type, provider_config, created_at, updated_at)
+                     VALUES ('work', 'me@work.example', 'gmail', '{}', '2024-01-01', '2024-01-01'),
+                            ('home', 'me@home.example', 'gmail', '{}', '2023-01-01', '2023-01-01');
+                     INSERT INTO emails (id, account_id, thread_id, message_id, from_address, to_addresses, date, created_at, updated_at)
+                     VALUES ('e1', 'work', 't1', 'm1', 'a@example.com', '[]', '2024-02-01', '2024-02-01', '2024-02-01');
+                     INSERT INTO screener_entries (id, sender_email, first_email_id, status, created_at)
+                     VALUES ('s1', 'a@example.com', 'e1', 'pending', '2024-02-01'),
+                            ('s2', 'b@example.com', NULL, 'pending', '2024-02-01');",
+                )?;
+                conn.execute_batch(schema::BACKFILL_SCREENER_ACCOUNTS)?;
+
+                let mut stmt =
+                    conn.prepare("SELECT id, account_id FROM screener_entries ORDER BY id")?;
+                let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+                Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            assigned,
+            vec![
+                ("s1".to_string(), "work".to_string()),
+                ("s2".to_string(), "home".to_string()),
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn with_conn_executes_query() {
         let db = Database::open_in_memory().await.unwrap();
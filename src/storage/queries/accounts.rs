@@ -183,10 +183,33 @@ pub async fn delete(db: &Database, account_id: &AccountId) -> Result<()> {
             "DELETE FROM embeddings WHERE email_id IN (SELECT id FROM emails WHERE account_id = ?1)",
             [&account_id.0],
         )?;
+        tx.execute(
+            "DELETE FROM passage_embeddings WHERE email_id IN (SELECT id FROM emails WHERE account_id = ?1)",
+            [&account_id.0],
+        )?;
+        tx.execute(
+            "DELETE FROM embedding_jobs WHERE account_id = ?1",
+            [&account_id.0],
+        )?;
         tx.execute(
             "DELETE FROM attachments WHERE email_id IN (SELECT id FROM emails WHERE account_id = ?1)",
             [&account_id.0],
         )?;
+        tx.execute(
+            "DELETE FROM screener_entries WHERE account_id = ?1",
+            [&account_id.0],
+        )?;
+        tx.execute(
+            "DELETE FROM screener_rules WHERE account_id = ?1",
+            [&account_id.0],
+        )?;
+        // Entries from before screening was per account may still point at
+        // the account's mail
+        tx.execute(
+            "UPDATE screener_entries SET first_email_id = NULL
+             WHERE first_email_id IN (SELECT id FROM emails WHERE account_id = ?1)",
+            [&account_id.0],
+        )?;
         tx.execute("DELETE FROM emails WHERE account_id = ?1", [&account_id.0])?;
         tx.execute("DELETE FROM threads WHERE account_id = ?1", [&account_id.0])?;
         tx.execute("DELETE FROM labels WHERE account_id = ?1", [&account_id.0])?;
@@ -196,6 +219,34 @@ pub async fn delete(db: &Database, account_id: &AccountId) -> Result<()> {
             [&account_id.0],
         )?;
         tx.execute("DELETE FROM sync_state WHERE account_id = ?1", [&account_id.0])?;
+        tx.execute(
+            "DELETE FROM triage_models WHERE account_id = ?1",
+            [&account_id.0],
+        )?;
+        for table in ["spam_tokens", "spam_training", "spam_verdicts"] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE account_id = ?1"),
+                [&account_id.0],
+            )?;
+        }
+        // Filter rules are global, but a rule that only matches the account's
+        // mail can never match again
+        tx.execute(
+            "DELETE FROM filter_rules
+             WHERE (match_all = 1 OR json_array_length(conditions) = 1)
+               AND EXISTS (
+                   SELECT 1 FROM json_each(filter_rules.conditions)
+                   WHERE json_extract(value, '$.type') = 'account'
+                     AND json_extract(value, '$.account_id') = ?1
+               )",
+            [&account_id.0],
+        )?;
+        for table in ["gmail_filter_mappings", "imported_gmail_filters"] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE account_id = ?1"),
+                [&account_id.0],
+            )?;
+        }
         tx.execute("DELETE FROM accounts WHERE id = ?1", [&account_id.0])?;
 
         Ok(())
@@ -414,6 +465,230 @@ mod tests {
         assert!(get_by_id(&db, &account.id).await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn delete_account_with_indexed_mail() {
+        use crate::domain::EmailId;
+        use crate::storage::queries::embeddings::{self, StoredPassage};
+
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                    to_addresses, date, created_at, updated_at)
+                VALUES ('e1', 'account-1', 'thread-1', 'm1', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01'),
+                       ('e2', 'account-1', 'thread-1', 'm2', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01');
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let email_ids = [EmailId::from("e1"), EmailId::from("e2")];
+        embeddings::enqueue_jobs(&db, &account.id, &email_ids)
+            .await
+            .unwrap();
+        embeddings::claim_jobs(&db, 1).await.unwrap();
+        let passage = StoredPassage {
+            content: "hello".to_string(),
+            embedding: vec![1.0, 0.0],
+        };
+        embeddings::complete_job(&db, &email_ids[0], &[passage])
+            .await
+            .unwrap();
+
+        delete(&db, &account.id).await.unwrap();
+
+        assert!(get_by_id(&db, &account.id).await.unwrap().is_none());
+        assert!(embeddings::load_passages(&db).await.unwrap().is_empty());
+        let counts = embeddings::job_counts(&db).await.unwrap();
+        assert_eq!(counts, embeddings::JobCounts::default());
+    }
+
+    #[tokio::test]
+    async fn delete_account_with_screener_data() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                    to_addresses, date, created_at, updated_at)
+                VALUES ('e1', 'account-1', 'thread-1', 'm1', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01');
+                INSERT INTO screener_entries (id, sender_email, first_email_id, status, created_at, account_id)
+                VALUES ('s1', 'a@example.com', 'e1', 'pending', '2025-01-01', 'account-1'),
+                       ('s2', 'b@example.com', 'e1', 'approved', '2025-01-01', NULL);
+                INSERT INTO screener_rules (id, rule_type, pattern, action, created_at, account_id)
+                VALUES ('r1', 'domain_block', 'spam.example', 'reject', '2025-01-01', 'account-1'),
+                       ('r2', 'domain_block', 'junk.example', 'reject', '2025-01-01', NULL);
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &account.id).await.unwrap();
+
+        let (entries, rules): (Vec<(String, Option<String>)>, Vec<String>) = db
+            .with_conn(|conn| {
+                let entries = conn
+                    .prepare("SELECT id, first_email_id FROM screener_entries")?
+                    .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<rusqlite::Result<_>>()?;
+                let rules = conn
+                    .prepare("SELECT id FROM screener_rules")?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()?;
+                Ok((entries, rules))
+            })
+            .await
+            .unwrap();
+        // Global entries and rules stay, without the deleted mail
+        assert_eq!(entries, vec![("s2".to_string(), None)]);
+        assert_eq!(rules, vec!["r2"]);
+    }
+
+    /// Counts the rows of a table.
+    async fn count_rows(db: &Database, table: &'static str) -> i64 {
+        db.with_conn(move |conn| {
+            let count = conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })?;
+            Ok(count)
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn delete_account_removes_triage_model() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO triage_models (account_id, model, updated_at)
+                 VALUES ('account-1', '{}', '2025-01-01')",
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &account.id).await.unwrap();
+        assert_eq!(count_rows(&db, "triage_models").await, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_account_removes_spam_filter() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO spam_tokens (account_id, token, spam_count, ham_count)
+                VALUES ('account-1', 'prize', 3, 0), ('other', 'prize', 1, 0);
+                INSERT INTO spam_training (account_id, email_id, is_spam, trained_at)
+                VALUES ('account-1', 'e1', 1, '2025-01-01');
+                INSERT INTO spam_verdicts (account_id, email_id, verdict, created_at)
+                VALUES ('account-1', 'e1', '{}', '2025-01-01');
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &account.id).await.unwrap();
+        assert_eq!(count_rows(&db, "spam_tokens").await, 1);
+        assert_eq!(count_rows(&db, "spam_training").await, 0);
+        assert_eq!(count_rows(&db, "spam_verdicts").await, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_account_removes_its_filter_rules() {
+        use crate::services::{FilterAction, FilterCondition, FilterRule};
+        use crate::storage::queries::filters;
+
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        let in_account = FilterCondition::Account {
+            account_id: account.id.clone(),
+        };
+        let from_ci = FilterCondition::From {
+            pattern: "ci.example".to_string(),
+        };
+        let scoped = FilterRule::new("Scoped")
+            .when(in_account.clone())
+            .when(from_ci.clone())
+            .then(FilterAction::Archive);
+        let either = FilterRule::new("Either")
+            .when(in_account)
+            .when(from_ci.clone())
+            .then(FilterAction::Star)
+            .match_any();
+        let global = FilterRule::new("Global")
+            .when(from_ci)
+            .then(FilterAction::MarkRead);
+        for rule in [&scoped, &either, &global] {
+            filters::save_rule(&db, rule).await.unwrap();
+        }
+
+        delete(&db, &account.id).await.unwrap();
+
+        // A rule that also matches other accounts' mail is kept
+        let kept: Vec<String> = filters::get_rules(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|rule| rule.name)
+            .collect();
+        assert_eq!(kept.len(), 2);
+        assert!(kept.contains(&"Either".to_string()));
+        assert!(kept.contains(&"Global".to_string()));
+    }
+
+    #[tokio::test]
+    async fn delete_account_removes_gmail_filters() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account = make_test_account();
+        insert(&db, &account).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO gmail_filter_mappings (account_id, rule_id, filter_id, fingerprint, synced_at)
+                VALUES ('account-1', 'r1', 'f1', '{}', '2025-01-01');
+                INSERT INTO imported_gmail_filters (account_id, id, criteria, action, imported_at)
+                VALUES ('account-1', 'f2', '{}', '{}', '2025-01-01');
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &account.id).await.unwrap();
+        assert_eq!(count_rows(&db, "gmail_filter_mappings").await, 0);
+        assert_eq!(count_rows(&db, "imported_gmail_filters").await, 0);
+    }
+
     #[tokio::test]
     async fn count_accounts() {
         let db = Database::open_in_memory().await.unwrap();
@@ -0,0 +1,212 @@
+//! Cached AI result operations.
+//!
+//! Keys hash the full prompt input, so a changed thread never hits a stale
+//! entry. Superseded entries are still removed: storing a result replaces
+//! earlier results of the same kind for its thread, and a trigger on
+//! `emails` drops a thread's entries when it gains a message.
+
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+
+use crate::domain::ThreadId;
+use crate::services::{AiCacheEntry, AiCacheStorage};
+use crate::storage::database::{Database, Result};
+
+/// Returns the cached value for a key.
+pub async fn get(db: &Database, key: &str) -> Result<Option<String>> {
+    let key = key.to_string();
+
+    db.with_conn(move |conn| {
+        let value = conn
+            .query_row("SELECT value FROM ai_cache WHERE key = ?1", [&key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(value)
+    })
+    .await
+}
+
+/// Stores a result, replacing earlier results of the same kind for its
+/// thread.
+pub async fn put(db: &Database, entry: &AiCacheEntry) -> Result<()> {
+    let entry = entry.clone();
+
+    db.transaction(move |tx| {
+        let thread_id = entry.thread_id.as_ref().map(|id| id.0.clone());
+        if let Some(thread_id) = &thread_id {
+            tx.execute(
+                "DELETE FROM ai_cache WHERE thread_id = ?1 AND kind = ?2",
+                params![thread_id, entry.kind.as_str()],
+            )?;
+        }
+        tx.execute(
+            r#"
+            INSERT OR REPLACE INTO ai_cache (key, kind, thread_id, provider, model, value, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+            params![
+                entry.key,
+                entry.kind.as_str(),
+                thread_id,
+                entry.provider,
+                entry.model,
+                entry.value,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Removes the cached results for a thread.
+pub async fn delete_for_thread(db: &Database, thread_id: &ThreadId) -> Result<usize> {
+    let thread_id = thread_id.clone();
+
+    db.with_conn(move |conn| {
+        let count = conn.execute("DELETE FROM ai_cache WHERE thread_id = ?1", [&thread_id.0])?;
+        Ok(count)
+    })
+    .await
+}
+
+/// Removes every cached result.
+pub async fn clear(db: &Database) -> Result<usize> {
+    db.with_conn(|conn| {
+        let count = conn.execute("DELETE FROM ai_cache", [])?;
+        Ok(count)
+    })
+    .await
+}
+
+/// Counts cached results.
+pub async fn count(db: &Database) -> Result<usize> {
+    db.with_conn(|conn| {
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM ai_cache", [], |row| row.get(0))?;
+        Ok(count as usize)
+    })
+    .await
+}
+
+#[async_trait::async_trait]
+impl AiCacheStorage for Database {
+    async fn get_cached(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(get(self, key).await?)
+    }
+
+    async fn put_cached(&self, entry: AiCacheEntry) -> anyhow::Result<()> {
+        Ok(put(self, &entry).await?)
+    }
+
+    async fn invalidate_thread(&self, thread_id: &ThreadId) -> anyhow::Result<usize> {
+        Ok(delete_for_thread(self, thread_id).await?)
+    }
+
+    async fn clear_cached(&self) -> anyhow::Result<usize> {
+        Ok(clear(self).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::AiCacheKind;
+
+    fn entry(key: &str, kind: AiCacheKind, thread_id: Option<&str>) -> AiCacheEntry {
+        AiCacheEntry {
+            key: key.to_string(),
+            kind,
+            thread_id: thread_id.map(ThreadId::from),
+            provider: "anthropic".to_string(),
+            model: "claude".to_string(),
+            value: format!("\"{}\"", key),
+        }
+    }
+
+    #[tokio::test]
+    async fn put_and_get() {
+        let db = Database::open_in_memory().await.unwrap();
+        put(&db, &entry("k1", AiCacheKind::SenderAnalysis, None))
+            .await
+            .unwrap();
+
+        assert_eq!(get(&db, "k1").await.unwrap().as_deref(), Some("\"k1\""));
+        assert!(get(&db, "missing").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn new_summary_replaces_old_one_for_thread() {
+        let db = Database::open_in_memory().await.unwrap();
+        put(&db, &entry("old", AiCacheKind::Summary, Some("thread-1")))
+            .await
+            .unwrap();
+        put(&db, &entry("other", AiCacheKind::Summary, Some("thread-2")))
+            .await
+            .unwrap();
+        put(&db, &entry("new", AiCacheKind::Summary, Some("thread-1")))
+            .await
+            .unwrap();
+
+        assert!(get(&db, "old").await.unwrap().is_none());
+        assert!(get(&db, "new").await.unwrap().is_some());
+        assert_eq!(count(&db).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn new_message_invalidates_thread() {
+        let db = Database::open_in_memory().await.unwrap();
+        put(
+            &db,
+            &entry("summary", AiCacheKind::Summary, Some("thread-1")),
+        )
+        .await
+        .unwrap();
+        put(&db, &entry("sender", AiCacheKind::SenderAnalysis, None))
+            .await
+            .unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute(
+                r#"
+                INSERT INTO accounts (id, email, provider_type, provider_config, created_at, updated_at)
+                VALUES ('account-1', 'test@example.com', 'gmail', '{}', '2025-01-01', '2025-01-01')
+                "#,
+                [],
+            )?;
+            conn.execute(
+                r#"
+                INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                    to_addresses, date, created_at, updated_at)
+                VALUES ('e1', 'account-1', 'thread-1', 'e1', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01')
+                "#,
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        assert!(get(&db, "summary").await.unwrap().is_none());
+        assert!(get(&db, "sender").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn delete_and_clear() {
+        let db = Database::open_in_memory().await.unwrap();
+        put(&db, &entry("a", AiCacheKind::Summary, Some("thread-1")))
+            .await
+            .unwrap();
+        put(&db, &entry("b", AiCacheKind::Categories, None))
+            .await
+            .unwrap();
+
+        let deleted = delete_for_thread(&db, &ThreadId::from("thread-1"))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert_eq!(clear(&db).await.unwrap(), 1);
+        assert_eq!(count(&db).await.unwrap(), 0);
+    }
+}
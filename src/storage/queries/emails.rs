@@ -5,7 +5,7 @@
 use chrono::{DateTime, Utc};
 use rusqlite::{params, OptionalExtension, Row};
 
-use crate::domain::{AccountId, Address, Email, EmailId, LabelId, MessageId, ThreadId};
+use crate::domain::{AccountId, Address, Email, EmailId, Header, LabelId, MessageId, ThreadId};
 use crate::storage::database::{Database, Result};
 
 /// Inserts a new email into the database.
@@ -19,6 +19,7 @@ pub async fn insert(db: &Database, email: &Email) -> Result<()> {
         let cc_json = serde_json::to_string(&email.cc).unwrap_or_default();
         let bcc_json = serde_json::to_string(&email.bcc).unwrap_or_default();
         let labels_json = serde_json::to_string(&email.labels).unwrap_or_default();
+        let headers_json = serde_json::to_string(&email.headers).unwrap_or_default();
 
         conn.execute(
             r#"
@@ -26,12 +27,12 @@ pub async fn insert(db: &Database, email: &Email) -> Result<()> {
                 id, account_id, thread_id, message_id, in_reply_to, references_json,
                 from_address, from_name, to_addresses, cc_addresses, bcc_addresses,
                 subject, body_text, body_html, snippet, date,
-                is_read, is_starred, is_draft, labels, created_at, updated_at
+                is_read, is_starred, is_draft, labels, raw_headers, created_at, updated_at
             ) VALUES (
                 ?1, ?2, ?3, ?4, ?5, ?6,
                 ?7, ?8, ?9, ?10, ?11,
                 ?12, ?13, ?14, ?15, ?16,
-                ?17, ?18, ?19, ?20, ?21, ?22
+                ?17, ?18, ?19, ?20, ?21, ?22, ?23
             )
             "#,
             params![
@@ -55,6 +56,7 @@ pub async fn insert(db: &Database, email: &Email) -> Result<()> {
                 email.is_starred as i32,
                 email.is_draft as i32,
                 labels_json,
+                headers_json,
                 now,
                 now,
             ],
@@ -76,7 +78,7 @@ pub async fn get_by_id(db: &Database, email_id: &EmailId) -> Result<Option<Email
                 id, account_id, thread_id, message_id, in_reply_to, references_json,
                 from_address, from_name, to_addresses, cc_addresses, bcc_addresses,
                 subject, body_text, body_html, snippet, date,
-                is_read, is_starred, is_draft, labels
+                is_read, is_starred, is_draft, labels, raw_headers
             FROM emails
             WHERE id = ?1
             "#,
@@ -99,7 +101,7 @@ pub async fn get_by_thread(db: &Database, thread_id: &ThreadId) -> Result<Vec<Em
                 id, account_id, thread_id, message_id, in_reply_to, references_json,
                 from_address, from_name, to_addresses, cc_addresses, bcc_addresses,
                 subject, body_text, body_html, snippet, date,
-                is_read, is_starred, is_draft, labels
+                is_read, is_starred, is_draft, labels, raw_headers
             FROM emails
             WHERE thread_id = ?1
             ORDER BY date ASC
@@ -129,7 +131,7 @@ pub async fn get_by_account(
                 id, account_id, thread_id, message_id, in_reply_to, references_json,
                 from_address, from_name, to_addresses, cc_addresses, bcc_addresses,
                 subject, body_text, body_html, snippet, date,
-                is_read, is_starred, is_draft, labels
+                is_read, is_starred, is_draft, labels, raw_headers
             FROM emails
             WHERE account_id = ?1
             ORDER BY date DESC
@@ -174,11 +176,25 @@ pub async fn set_starred(db: &Database, email_id: &EmailId, is_starred: bool) ->
     .await
 }
 
-/// Deletes an email by its ID.
+/// Deletes an email by its ID, along with its stored embeddings and
+/// indexing job. Screener entries for its sender no longer point at it.
 pub async fn delete(db: &Database, email_id: &EmailId) -> Result<()> {
     let email_id = email_id.clone();
 
     db.with_conn(move |conn| {
+        conn.execute("DELETE FROM embeddings WHERE email_id = ?1", [&email_id.0])?;
+        conn.execute(
+            "DELETE FROM passage_embeddings WHERE email_id = ?1",
+            [&email_id.0],
+        )?;
+        conn.execute(
+            "DELETE FROM embedding_jobs WHERE email_id = ?1",
+            [&email_id.0],
+        )?;
+        conn.execute(
+            "UPDATE screener_entries SET first_email_id = NULL WHERE first_email_id = ?1",
+            [&email_id.0],
+        )?;
         conn.execute("DELETE FROM emails WHERE id = ?1", [&email_id.0])?;
         Ok(())
     })
@@ -222,12 +238,16 @@ fn row_to_email(row: &Row<'_>) -> std::result::Result<Email, rusqlite::Error> {
     let bcc_json: String = row.get(10)?;
     let labels_json: String = row.get(19)?;
     let date_str: String = row.get(15)?;
+    let headers_json: Option<String> = row.get(20)?;
 
     let references: Vec<MessageId> = serde_json::from_str(&references_json).unwrap_or_default();
     let to: Vec<Address> = serde_json::from_str(&to_json).unwrap_or_default();
     let cc: Vec<Address> = serde_json::from_str(&cc_json).unwrap_or_default();
     let bcc: Vec<Address> = serde_json::from_str(&bcc_json).unwrap_or_default();
     let labels: Vec<LabelId> = serde_json::from_str(&labels_json).unwrap_or_default();
+    let headers: Vec<Header> = headers_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
 
     let date = DateTime::parse_from_rfc3339(&date_str)
         .map(|dt| dt.with_timezone(&Utc))
@@ -260,6 +280,7 @@ fn row_to_email(row: &Row<'_>) -> std::result::Result<Email, rusqlite::Error> {
         is_draft: row.get::<_, i32>(18)? != 0,
         labels,
         attachments: vec![], // Loaded separately if needed
+        headers,
     })
 }
 
@@ -289,6 +310,7 @@ mod tests {
             is_draft: false,
             labels: vec![LabelId::from("INBOX")],
             attachments: vec![],
+            headers: vec![],
         }
     }
 
@@ -327,6 +349,18 @@ mod tests {
         assert_eq!(retrieved.from.email, email.from.email);
     }
 
+    #[tokio::test]
+    async fn headers_round_trip() {
+        let db = setup_db_with_account().await;
+        let mut email = make_test_email();
+        email.headers = vec![Header::new("Precedence", "bulk")];
+
+        insert(&db, &email).await.unwrap();
+
+        let retrieved = get_by_id(&db, &email.id).await.unwrap().unwrap();
+        assert_eq!(retrieved.header("Precedence"), Some("bulk"));
+    }
+
     #[tokio::test]
     async fn get_nonexistent_email_returns_none() {
         let db = Database::open_in_memory().await.unwrap();
@@ -390,6 +424,41 @@ mod tests {
         assert!(get_by_id(&db, &email.id).await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn delete_email_kept_by_screener_entry() {
+        let db = setup_db_with_account().await;
+        let email = make_test_email();
+        insert(&db, &email).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute(
+                r#"
+                INSERT INTO screener_entries (id, sender_email, first_email_id, status, created_at, account_id)
+                VALUES ('s1', 'sender@example.com', 'email-1', 'approved', '2025-01-01', 'account-1')
+                "#,
+                [],
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &email.id).await.unwrap();
+
+        let first_email_id: Option<String> = db
+            .with_conn(|conn| {
+                let id = conn.query_row(
+                    "SELECT first_email_id FROM screener_entries WHERE id = 's1'",
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok(id)
+            })
+            .await
+            .unwrap();
+        assert_eq!(first_email_id, None);
+    }
+
     #[tokio::test]
     async fn count_emails_in_thread() {
         let db = setup_db_with_account().await;
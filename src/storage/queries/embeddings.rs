@@ -0,0 +1,581 @@
+//! Embedding storage and indexing job queue operations.
+//!
+//! Jobs move from `pending` to `running` when claimed by the indexer and end
+//! up `done` or `failed`. Finished passage embeddings are stored alongside so
+//! the vector index can be rebuilt on startup without re-embedding.
+
+use chrono::Utc;
+use rusqlite::params;
+
+use crate::domain::{AccountId, Email, EmailId};
+use crate::embedding::{Embedding, PassageEmbedding};
+use crate::services::{IndexingError, IndexingProgress, IndexingResult, IndexingStorage};
+use crate::storage::database::{Database, Result};
+use crate::storage::queries::emails;
+
+/// Number of embedding jobs in each state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobCounts {
+    /// Jobs waiting to be embedded.
+    pub pending: usize,
+    /// Jobs claimed by the indexer.
+    pub running: usize,
+    /// Jobs embedded successfully.
+    pub done: usize,
+    /// Jobs that exhausted their retries.
+    pub failed: usize,
+}
+
+/// A stored passage of an email and its embedding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredPassage {
+    /// Passage text.
+    pub content: String,
+    /// Embedding vector.
+    pub embedding: Vec<f32>,
+}
+
+/// Queues emails for embedding.
+///
+/// Emails already in the queue are reset to `pending` so changed content is
+/// embedded again. Returns the number of queued emails.
+pub async fn enqueue_jobs(
+    db: &Database,
+    account_id: &AccountId,
+    email_ids: &[EmailId],
+) -> Result<usize> {
+    let account_id = account_id.clone();
+    let email_ids = email_ids.to_vec();
+
+    db.transaction(move |tx| {
+        let now = Utc::now().to_rfc3339();
+        let mut stmt = tx.prepare(
+            r#"
+            INSERT INTO embedding_jobs (email_id, account_id, status, attempts, enqueued_at, updated_at)
+            VALUES (?1, ?2, 'pending', 0, ?3, ?3)
+            ON CONFLICT(email_id) DO UPDATE SET
+                status = 'pending',
+                attempts = 0,
+                last_error = NULL,
+                enqueued_at = excluded.enqueued_at,
+                updated_at = excluded.updated_at
+            "#,
+        )?;
+
+        for email_id in &email_ids {
+            stmt.execute(params![email_id.0, account_id.0, now])?;
+        }
+        Ok(email_ids.len())
+    })
+    .await
+}
+
+/// Claims up to `limit` pending jobs, oldest first, marking them `running`.
+pub async fn claim_jobs(db: &Database, limit: usize) -> Result<Vec<EmailId>> {
+    db.transaction(move |tx| {
+        let email_ids: Vec<EmailId> = {
+            let mut stmt = tx.prepare(
+                "SELECT email_id FROM embedding_jobs WHERE status = 'pending'
+                 ORDER BY enqueued_at, email_id LIMIT ?1",
+            )?;
+            let rows = stmt.query_map([limit as i64], |row| {
+                Ok(EmailId::from(row.get::<_, String>(0)?))
+            })?;
+            rows.collect::<std::result::Result<_, _>>()?
+        };
+
+        let now = Utc::now().to_rfc3339();
+        for email_id in &email_ids {
+            tx.execute(
+                "UPDATE embedding_jobs SET status = 'running', updated_at = ?1 WHERE email_id = ?2",
+                params![now, email_id.0],
+            )?;
+        }
+        Ok(email_ids)
+    })
+    .await
+}
+
+/// Replaces an email's stored passages and marks its job `done`.
+pub async fn complete_job(
+    db: &Database,
+    email_id: &EmailId,
+    passages: &[StoredPassage],
+) -> Result<()> {
+    let email_id = email_id.clone();
+    let passages = passages.to_vec();
+
+    db.transaction(move |tx| {
+        let now = Utc::now().to_rfc3339();
+        tx.execute(
+            "DELETE FROM passage_embeddings WHERE email_id = ?1",
+            [&email_id.0],
+        )?;
+        for (index, passage) in passages.iter().enumerate() {
+            tx.execute(
+                r#"
+                INSERT INTO passage_embeddings (email_id, passage_index, content, embedding, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+                params![
+                    email_id.0,
+                    index as i64,
+                    passage.content,
+                    encode_embedding(&passage.embedding),
+                    now,
+                ],
+            )?;
+        }
+        tx.execute(
+            "UPDATE embedding_jobs SET status = 'done', last_error = NULL, updated_at = ?1
+             WHERE email_id = ?2",
+            params![now, email_id.0],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Records a failed embedding attempt.
+///
+/// The job returns to `pending` until it has been attempted `max_attempts`
+/// times, after which it is marked `failed`.
+pub async fn fail_job(
+    db: &Database,
+    email_id: &EmailId,
+    error: &str,
+    max_attempts: u32,
+) -> Result<()> {
+    let email_id = email_id.clone();
+    let error = error.to_string();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            r#"
+            UPDATE embedding_jobs SET
+                attempts = attempts + 1,
+                status = CASE WHEN attempts + 1 >= ?1 THEN 'failed' ELSE 'pending' END,
+                last_error = ?2,
+                updated_at = ?3
+            WHERE email_id = ?4
+            "#,
+            params![max_attempts, error, Utc::now().to_rfc3339(), email_id.0],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns jobs left `running` by an interrupted indexer to `pending`.
+pub async fn requeue_running_jobs(db: &Database) -> Result<usize> {
+    db.with_conn(|conn| {
+        let count = conn.execute(
+            "UPDATE embedding_jobs SET status = 'pending', updated_at = ?1 WHERE status = 'running'",
+            [Utc::now().to_rfc3339()],
+        )?;
+        Ok(count)
+    })
+    .await
+}
+
+/// Removes jobs and stored passages for the given emails.
+pub async fn remove(db: &Database, email_ids: &[EmailId]) -> Result<()> {
+    let email_ids = email_ids.to_vec();
+
+    db.transaction(move |tx| {
+        for email_id in &email_ids {
+            tx.execute(
+                "DELETE FROM embedding_jobs WHERE email_id = ?1",
+                [&email_id.0],
+            )?;
+            tx.execute(
+                "DELETE FROM passage_embeddings WHERE email_id = ?1",
+                [&email_id.0],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Counts embedding jobs by state.
+pub async fn job_counts(db: &Database) -> Result<JobCounts> {
+    db.with_conn(|conn| {
+        let mut stmt =
+            conn.prepare("SELECT status, COUNT(*) FROM embedding_jobs GROUP BY status")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+        })?;
+
+        let mut counts = JobCounts::default();
+        for row in rows {
+            let (status, count) = row?;
+            match status.as_str() {
+                "pending" => counts.pending = count,
+                "running" => counts.running = count,
+                "done" => counts.done = count,
+                "failed" => counts.failed = count,
+                _ => {}
+            }
+        }
+        Ok(counts)
+    })
+    .await
+}
+
+/// Loads all stored passages, grouped by email in passage order.
+pub async fn load_passages(db: &Database) -> Result<Vec<(EmailId, Vec<StoredPassage>)>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT email_id, content, embedding FROM passage_embeddings
+             ORDER BY email_id, passage_index",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let email_id = EmailId::from(row.get::<_, String>(0)?);
+            let blob: Vec<u8> = row.get(2)?;
+            Ok((
+                email_id,
+                StoredPassage {
+                    content: row.get(1)?,
+                    embedding: decode_embedding(&blob),
+                },
+            ))
+        })?;
+
+        let mut grouped: Vec<(EmailId, Vec<StoredPassage>)> = Vec::new();
+        for row in rows {
+            let (email_id, passage) = row?;
+            match grouped.last_mut() {
+                Some((last, passages)) if *last == email_id => passages.push(passage),
+                _ => grouped.push((email_id, vec![passage])),
+            }
+        }
+        Ok(grouped)
+    })
+    .await
+}
+
+/// Encodes an embedding as little-endian f32 bytes.
+fn encode_embedding(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Decodes an embedding stored by [`encode_embedding`].
+fn decode_embedding(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+fn storage_error(e: impl std::fmt::Display) -> IndexingError {
+    IndexingError::Storage(e.to_string())
+}
+
+#[async_trait::async_trait]
+impl IndexingStorage for Database {
+    async fn enqueue(
+        &self,
+        account_id: &AccountId,
+        email_ids: &[EmailId],
+    ) -> IndexingResult<usize> {
+        enqueue_jobs(self, account_id, email_ids)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn claim_batch(&self, limit: usize) -> IndexingResult<Vec<EmailId>> {
+        claim_jobs(self, limit).await.map_err(storage_error)
+    }
+
+    async fn complete(
+        &self,
+        email_id: &EmailId,
+        passages: &[PassageEmbedding],
+    ) -> IndexingResult<()> {
+        let passages: Vec<StoredPassage> = passages
+            .iter()
+            .map(|p| StoredPassage {
+                content: p.text.clone(),
+                embedding: p.embedding.values.clone(),
+            })
+            .collect();
+        complete_job(self, email_id, &passages)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn fail(&self, email_id: &EmailId, error: &str, max_attempts: u32) -> IndexingResult<()> {
+        fail_job(self, email_id, error, max_attempts)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn requeue_in_progress(&self) -> IndexingResult<usize> {
+        requeue_running_jobs(self).await.map_err(storage_error)
+    }
+
+    async fn remove(&self, email_ids: &[EmailId]) -> IndexingResult<()> {
+        remove(self, email_ids).await.map_err(storage_error)
+    }
+
+    async fn get_email(&self, email_id: &EmailId) -> IndexingResult<Option<Email>> {
+        emails::get_by_id(self, email_id)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn load_embeddings(&self) -> IndexingResult<Vec<(EmailId, Vec<PassageEmbedding>)>> {
+        let stored = load_passages(self).await.map_err(storage_error)?;
+        Ok(stored
+            .into_iter()
+            .map(|(email_id, passages)| {
+                let passages = passages
+                    .into_iter()
+                    .map(|p| PassageEmbedding {
+                        text: p.content,
+                        embedding: Embedding::new(p.embedding),
+                    })
+                    .collect();
+                (email_id, passages)
+            })
+            .collect())
+    }
+
+    async fn progress(&self) -> IndexingResult<IndexingProgress> {
+        let counts = job_counts(self).await.map_err(storage_error)?;
+        Ok(IndexingProgress {
+            pending: counts.pending,
+            in_progress: counts.running,
+            completed: counts.done,
+            failed: counts.failed,
+            throttled: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn setup_db_with_emails(ids: &[&str]) -> Database {
+        let db = Database::open_in_memory().await.unwrap();
+        insert_emails(&db, ids).await;
+        db
+    }
+
+    async fn insert_emails(db: &Database, ids: &[&str]) {
+        let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+
+        db.with_conn(move |conn| {
+            conn.execute(
+                r#"
+                INSERT INTO accounts (id, email, provider_type, provider_config, created_at, updated_at)
+                VALUES ('account-1', 'test@example.com', 'gmail', '{}', '2025-01-01', '2025-01-01')
+                "#,
+                [],
+            )?;
+            for id in &ids {
+                conn.execute(
+                    r#"
+                    INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                        to_addresses, date, created_at, updated_at)
+                    VALUES (?1, 'account-1', 'thread-1', ?1, 'a@example.com', '[]',
+                        '2025-01-01', '2025-01-01', '2025-01-01')
+                    "#,
+                    [id],
+                )?;
+            }
+            Ok(())
+        })
+        .await
+        .unwrap();
+    }
+
+    fn ids(ids: &[&str]) -> Vec<EmailId> {
+        ids.iter().map(|id| EmailId::from(*id)).collect()
+    }
+
+    #[tokio::test]
+    async fn enqueue_and_claim() {
+        let db = setup_db_with_emails(&[]).await;
+        let account = AccountId::from("account-1");
+
+        let queued = enqueue_jobs(&db, &account, &ids(&["e1", "e2", "e3"]))
+            .await
+            .unwrap();
+        assert_eq!(queued, 3);
+
+        let claimed = claim_jobs(&db, 2).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+
+        let counts = job_counts(&db).await.unwrap();
+        assert_eq!(counts.pending, 1);
+        assert_eq!(counts.running, 2);
+
+        let rest = claim_jobs(&db, 10).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert!(!claimed.contains(&rest[0]));
+    }
+
+    fn passage(content: &str, embedding: &[f32]) -> StoredPassage {
+        StoredPassage {
+            content: content.to_string(),
+            embedding: embedding.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn complete_stores_passages() {
+        let db = setup_db_with_emails(&["e1", "e2"]).await;
+        enqueue_jobs(&db, &AccountId::from("account-1"), &ids(&["e1", "e2"]))
+            .await
+            .unwrap();
+        claim_jobs(&db, 2).await.unwrap();
+
+        let e1 = vec![
+            passage("first", &[0.5, -1.0]),
+            passage("second", &[2.25, 0.0]),
+        ];
+        complete_job(&db, &EmailId::from("e1"), &e1).await.unwrap();
+        complete_job(&db, &EmailId::from("e2"), &[passage("only", &[1.0, 1.0])])
+            .await
+            .unwrap();
+
+        let counts = job_counts(&db).await.unwrap();
+        assert_eq!(counts.done, 2);
+        assert_eq!(counts.running, 0);
+
+        let stored = load_passages(&db).await.unwrap();
+        assert_eq!(
+            stored,
+            vec![
+                (EmailId::from("e1"), e1),
+                (EmailId::from("e2"), vec![passage("only", &[1.0, 1.0])]),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_replaces_previous_passages() {
+        let db = setup_db_with_emails(&["e1"]).await;
+        let email_id = EmailId::from("e1");
+        let two = [passage("a", &[1.0]), passage("b", &[2.0])];
+        complete_job(&db, &email_id, &two).await.unwrap();
+        complete_job(&db, &email_id, &[passage("c", &[3.0])])
+            .await
+            .unwrap();
+
+        let stored = load_passages(&db).await.unwrap();
+        assert_eq!(stored, vec![(email_id, vec![passage("c", &[3.0])])]);
+    }
+
+    #[tokio::test]
+    async fn failures_retry_until_limit() {
+        let db = setup_db_with_emails(&[]).await;
+        let email_id = EmailId::from("e1");
+        enqueue_jobs(
+            &db,
+            &AccountId::from("account-1"),
+            std::slice::from_ref(&email_id),
+        )
+        .await
+        .unwrap();
+
+        claim_jobs(&db, 1).await.unwrap();
+        fail_job(&db, &email_id, "boom", 2).await.unwrap();
+        assert_eq!(job_counts(&db).await.unwrap().pending, 1);
+
+        claim_jobs(&db, 1).await.unwrap();
+        fail_job(&db, &email_id, "boom", 2).await.unwrap();
+        let counts = job_counts(&db).await.unwrap();
+        assert_eq!(counts.pending, 0);
+        assert_eq!(counts.failed, 1);
+    }
+
+    #[tokio::test]
+    async fn requeue_interrupted_jobs() {
+        let db = setup_db_with_emails(&[]).await;
+        enqueue_jobs(&db, &AccountId::from("account-1"), &ids(&["e1", "e2"]))
+            .await
+            .unwrap();
+        claim_jobs(&db, 2).await.unwrap();
+
+        assert_eq!(requeue_running_jobs(&db).await.unwrap(), 2);
+        assert_eq!(job_counts(&db).await.unwrap().pending, 2);
+    }
+
+    #[tokio::test]
+    async fn enqueue_resets_finished_jobs() {
+        let db = setup_db_with_emails(&["e1"]).await;
+        let account = AccountId::from("account-1");
+        enqueue_jobs(&db, &account, &ids(&["e1"])).await.unwrap();
+        claim_jobs(&db, 1).await.unwrap();
+        complete_job(&db, &EmailId::from("e1"), &[passage("a", &[1.0])])
+            .await
+            .unwrap();
+
+        enqueue_jobs(&db, &account, &ids(&["e1"])).await.unwrap();
+
+        let counts = job_counts(&db).await.unwrap();
+        assert_eq!(counts.pending, 1);
+        assert_eq!(counts.done, 0);
+    }
+
+    #[tokio::test]
+    async fn remove_deletes_jobs_and_passages() {
+        let db = setup_db_with_emails(&["e1"]).await;
+        enqueue_jobs(&db, &AccountId::from("account-1"), &ids(&["e1"]))
+            .await
+            .unwrap();
+        complete_job(&db, &EmailId::from("e1"), &[passage("a", &[1.0])])
+            .await
+            .unwrap();
+
+        remove(&db, &ids(&["e1"])).await.unwrap();
+
+        assert_eq!(job_counts(&db).await.unwrap(), JobCounts::default());
+        assert!(load_passages(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn queue_survives_reopening_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heap.db");
+        let e1 = EmailId::from("e1");
+
+        {
+            let db = Database::open(&path).await.unwrap();
+            insert_emails(&db, &["e1", "e2", "e3"]).await;
+            let storage: &dyn IndexingStorage = &db;
+            storage
+                .enqueue(&AccountId::from("account-1"), &ids(&["e1", "e2", "e3"]))
+                .await
+                .unwrap();
+            assert_eq!(storage.claim_batch(2).await.unwrap(), ids(&["e1", "e2"]));
+            let passage = PassageEmbedding {
+                text: "Quarterly report".to_string(),
+                embedding: Embedding::new(vec![0.25, -0.5]),
+            };
+            storage.complete(&e1, &[passage]).await.unwrap();
+        }
+
+        let db = Database::open(&path).await.unwrap();
+        let storage: &dyn IndexingStorage = &db;
+        assert_eq!(storage.requeue_in_progress().await.unwrap(), 1);
+        let progress = storage.progress().await.unwrap();
+        assert_eq!((progress.pending, progress.completed), (2, 1));
+        assert_eq!(storage.claim_batch(10).await.unwrap(), ids(&["e2", "e3"]));
+
+        let restored = storage.load_embeddings().await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].0, e1);
+        assert_eq!(restored[0].1[0].text, "Quarterly report");
+        assert_eq!(restored[0].1[0].embedding.values, vec![0.25, -0.5]);
+    }
+
+    #[test]
+    fn embedding_encoding_round_trip() {
+        let values = vec![0.0, 1.5, -3.25, f32::MAX];
+        assert_eq!(decode_embedding(&encode_embedding(&values)), values);
+    }
+}
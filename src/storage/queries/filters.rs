@@ -0,0 +1,184 @@
+//! Filter rule operations.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, OptionalExtension, Row};
+
+use crate::domain::{AccountId, Email};
+use crate::services::{FilterError, FilterResult, FilterRule, FilterStorage};
+use crate::storage::database::{Database, Result};
+use crate::storage::queries::emails;
+
+const RULE_COLUMNS: &str =
+    "id, name, conditions, match_all, actions, position, stop_processing, enabled, created_at";
+
+/// Retrieves all filter rules in run order.
+pub async fn get_rules(db: &Database) -> Result<Vec<FilterRule>> {
+    db.with_conn(|conn| {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {RULE_COLUMNS} FROM filter_rules ORDER BY position, created_at"
+        ))?;
+        let rows = stmt.query_map([], row_to_rule)?;
+        let rules: std::result::Result<Vec<_>, _> = rows.collect();
+        Ok(rules?)
+    })
+    .await
+}
+
+/// Retrieves a filter rule by ID.
+pub async fn get_rule(db: &Database, id: &str) -> Result<Option<FilterRule>> {
+    let id = id.to_string();
+
+    db.with_conn(move |conn| {
+        let rule = conn
+            .query_row(
+                &format!("SELECT {RULE_COLUMNS} FROM filter_rules WHERE id = ?1"),
+                [&id],
+                row_to_rule,
+            )
+            .optional()?;
+        Ok(rule)
+    })
+    .await
+}
+
+/// Inserts a filter rule, replacing any rule with the same ID.
+pub async fn save_rule(db: &Database, rule: &FilterRule) -> Result<()> {
+    let rule = rule.clone();
+    let conditions = serde_json::to_string(&rule.conditions).unwrap_or_default();
+    let actions = serde_json::to_string(&rule.actions).unwrap_or_default();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            &format!(
+                "INSERT OR REPLACE INTO filter_rules ({RULE_COLUMNS})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+            ),
+            params![
+                rule.id,
+                rule.name,
+                conditions,
+                rule.match_all,
+                actions,
+                rule.position,
+                rule.stop_processing,
+                rule.enabled,
+                rule.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Deletes a filter rule.
+pub async fn delete_rule(db: &Database, id: &str) -> Result<()> {
+    let id = id.to_string();
+
+    db.with_conn(move |conn| {
+        conn.execute("DELETE FROM filter_rules WHERE id = ?1", [&id])?;
+        Ok(())
+    })
+    .await
+}
+
+fn row_to_rule(row: &Row<'_>) -> std::result::Result<FilterRule, rusqlite::Error> {
+    let conditions: String = row.get(2)?;
+    let actions: String = row.get(4)?;
+    let created_at: String = row.get(8)?;
+
+    Ok(FilterRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        // Conditions or actions that no longer parse leave the rule inert
+        conditions: serde_json::from_str(&conditions).unwrap_or_default(),
+        match_all: row.get(3)?,
+        actions: serde_json::from_str(&actions).unwrap_or_default(),
+        position: row.get(5)?,
+        stop_processing: row.get(6)?,
+        enabled: row.get(7)?,
+        created_at: DateTime::parse_from_rfc3339(&created_at)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    })
+}
+
+fn storage_error(e: impl std::fmt::Display) -> FilterError {
+    FilterError::Storage(e.to_string())
+}
+
+#[async_trait::async_trait]
+impl FilterStorage for Database {
+    async fn get_rules(&self) -> FilterResult<Vec<FilterRule>> {
+        get_rules(self).await.map_err(storage_error)
+    }
+
+    async fn get_rule(&self, id: &str) -> FilterResult<Option<FilterRule>> {
+        get_rule(self, id).await.map_err(storage_error)
+    }
+
+    async fn save_rule(&self, rule: &FilterRule) -> FilterResult<()> {
+        save_rule(self, rule).await.map_err(storage_error)
+    }
+
+    async fn delete_rule(&self, id: &str) -> FilterResult<()> {
+        delete_rule(self, id).await.map_err(storage_error)
+    }
+
+    async fn get_emails(&self, account_id: &AccountId, limit: u32) -> FilterResult<Vec<Email>> {
+        emails::get_by_account(self, account_id, limit, 0)
+            .await
+            .map_err(storage_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::LabelId;
+    use crate::services::{FilterAction, FilterCondition};
+
+    fn make_rule(name: &str, position: i32) -> FilterRule {
+        let mut rule = FilterRule::new(name)
+            .when(FilterCondition::ListId {
+                pattern: "builds".to_string(),
+            })
+            .then(FilterAction::ApplyLabel {
+                label: LabelId::from("CI"),
+            })
+            .stop_processing();
+        rule.position = position;
+        rule
+    }
+
+    #[tokio::test]
+    async fn rules_round_trip_in_order() {
+        let db = Database::open_in_memory().await.unwrap();
+        let second = make_rule("second", 1);
+        let first = make_rule("first", 0);
+
+        save_rule(&db, &second).await.unwrap();
+        save_rule(&db, &first).await.unwrap();
+
+        let rules = get_rules(&db).await.unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].name, "first");
+        assert_eq!(rules[0].conditions, first.conditions);
+        assert_eq!(rules[0].actions, first.actions);
+        assert!(rules[0].stop_processing);
+    }
+
+    #[tokio::test]
+    async fn save_replaces_and_delete_removes() {
+        let db = Database::open_in_memory().await.unwrap();
+        let mut rule = make_rule("CI", 0);
+        save_rule(&db, &rule).await.unwrap();
+
+        rule.enabled = false;
+        save_rule(&db, &rule).await.unwrap();
+        let fetched = get_rule(&db, &rule.id).await.unwrap().unwrap();
+        assert!(!fetched.enabled);
+
+        delete_rule(&db, &rule.id).await.unwrap();
+        assert!(get_rule(&db, &rule.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! Gmail filter mapping and import operations.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Row};
+
+use crate::domain::AccountId;
+use crate::providers::email::GmailFilter;
+use crate::services::{
+    GmailFilterError, GmailFilterMapping, GmailFilterResult, GmailFilterStorage,
+    ImportedGmailFilter,
+};
+use crate::storage::database::{Database, Result};
+
+/// Retrieves the filter mappings for an account.
+pub async fn get_mappings(
+    db: &Database,
+    account_id: &AccountId,
+) -> Result<Vec<GmailFilterMapping>> {
+    let account_id = account_id.0.clone();
+
+    db.with_conn(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT account_id, rule_id, filter_id, fingerprint, synced_at
+             FROM gmail_filter_mappings WHERE account_id = ?1 ORDER BY rule_id",
+        )?;
+        let rows = stmt.query_map([&account_id], row_to_mapping)?;
+        let mappings: std::result::Result<Vec<_>, _> = rows.collect();
+        Ok(mappings?)
+    })
+    .await
+}
+
+/// Inserts a filter mapping, replacing any mapping for the same rule.
+pub async fn save_mapping(db: &Database, mapping: &GmailFilterMapping) -> Result<()> {
+    let mapping = mapping.clone();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO gmail_filter_mappings
+             (account_id, rule_id, filter_id, fingerprint, synced_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                mapping.account_id.0,
+                mapping.rule_id,
+                mapping.filter_id,
+                mapping.fingerprint,
+                mapping.synced_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Deletes the filter mapping for a rule.
+pub async fn delete_mapping(db: &Database, account_id: &AccountId, rule_id: &str) -> Result<()> {
+    let account_id = account_id.0.clone();
+    let rule_id = rule_id.to_string();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            "DELETE FROM gmail_filter_mappings WHERE account_id = ?1 AND rule_id = ?2",
+            [&account_id, &rule_id],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Retrieves the filters imported from Gmail for an account.
+pub async fn get_imported(
+    db: &Database,
+    account_id: &AccountId,
+) -> Result<Vec<ImportedGmailFilter>> {
+    let account_id = account_id.0.clone();
+
+    db.with_conn(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT account_id, id, criteria, action, imported_at
+             FROM imported_gmail_filters WHERE account_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([&account_id], row_to_imported)?;
+        let filters: std::result::Result<Vec<_>, _> = rows.collect();
+        Ok(filters?)
+    })
+    .await
+}
+
+/// Replaces the filters imported from Gmail for an account.
+pub async fn replace_imported(
+    db: &Database,
+    account_id: &AccountId,
+    filters: &[GmailFilter],
+) -> Result<()> {
+    let account_id = account_id.0.clone();
+    let filters = filters.to_vec();
+    let imported_at = Utc::now().to_rfc3339();
+
+    db.transaction(move |tx| {
+        tx.execute(
+            "DELETE FROM imported_gmail_filters WHERE account_id = ?1",
+            [&account_id],
+        )?;
+        for filter in &filters {
+            let Some(id) = &filter.id else { continue };
+            tx.execute(
+                "INSERT OR REPLACE INTO imported_gmail_filters
+                 (account_id, id, criteria, action, imported_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    account_id,
+                    id,
+                    serde_json::to_string(&filter.criteria).unwrap_or_default(),
+                    serde_json::to_string(&filter.action).unwrap_or_default(),
+                    imported_at,
+                ],
+            )?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+fn parse_time(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn row_to_mapping(row: &Row<'_>) -> std::result::Result<GmailFilterMapping, rusqlite::Error> {
+    let account_id: String = row.get(0)?;
+    let synced_at: String = row.get(4)?;
+
+    Ok(GmailFilterMapping {
+        account_id: AccountId::from(account_id),
+        rule_id: row.get(1)?,
+        filter_id: row.get(2)?,
+        fingerprint: row.get(3)?,
+        synced_at: parse_time(&synced_at),
+    })
+}
+
+fn row_to_imported(row: &Row<'_>) -> std::result::Result<ImportedGmailFilter, rusqlite::Error> {
+    let account_id: String = row.get(0)?;
+    let criteria: String = row.get(2)?;
+    let action: String = row.get(3)?;
+    let imported_at: String = row.get(4)?;
+
+    Ok(ImportedGmailFilter {
+        account_id: AccountId::from(account_id),
+        filter: GmailFilter {
+            id: Some(row.get(1)?),
+            criteria: serde_json::from_str(&criteria).unwrap_or_default(),
+            action: serde_json::from_str(&action).unwrap_or_default(),
+        },
+        imported_at: parse_time(&imported_at),
+    })
+}
+
+fn storage_error(e: impl std::fmt::Display) -> GmailFilterError {
+    GmailFilterError::Storage(e.to_string())
+}
+
+#[async_trait::async_trait]
+impl GmailFilterStorage for Database {
+    async fn get_mappings(
+        &self,
+        account_id: &AccountId,
+    ) -> GmailFilterResult<Vec<GmailFilterMapping>> {
+        get_mappings(self, account_id).await.map_err(storage_error)
+    }
+
+    async fn save_mapping(&self, mapping: &GmailFilterMapping) -> GmailFilterResult<()> {
+        save_mapping(self, mapping).await.map_err(storage_error)
+    }
+
+    async fn delete_mapping(&self, account_id: &AccountId, rule_id: &str) -> GmailFilterResult<()> {
+        delete_mapping(self, account_id, rule_id)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn get_imported_filters(
+        &self,
+        account_id: &AccountId,
+    ) -> GmailFilterResult<Vec<ImportedGmailFilter>> {
+        get_imported(self, account_id).await.map_err(storage_error)
+    }
+
+    async fn replace_imported_filters(
+        &self,
+        account_id: &AccountId,
+        filters: &[GmailFilter],
+    ) -> GmailFilterResult<()> {
+        replace_imported(self, account_id, filters)
+            .await
+            .map_err(storage_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::email::{GmailFilterAction, GmailFilterCriteria};
+
+    fn mapping(account: &str, rule_id: &str, filter_id: &str) -> GmailFilterMapping {
+        GmailFilterMapping {
+            account_id: AccountId::from(account),
+            rule_id: rule_id.to_string(),
+            filter_id: filter_id.to_string(),
+            fingerprint: "{}".to_string(),
+            synced_at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn mappings_are_scoped_to_accounts() {
+        let db = Database::open_in_memory().await.unwrap();
+        let work = AccountId::from("work");
+
+        save_mapping(&db, &mapping("work", "rule-1", "f1"))
+            .await
+            .unwrap();
+        save_mapping(&db, &mapping("home", "rule-1", "f9"))
+            .await
+            .unwrap();
+        save_mapping(&db, &mapping("work", "rule-1", "f2"))
+            .await
+            .unwrap();
+
+        let mappings = get_mappings(&db, &work).await.unwrap();
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(mappings[0].filter_id, "f2");
+
+        delete_mapping(&db, &work, "rule-1").await.unwrap();
+        assert!(get_mappings(&db, &work).await.unwrap().is_empty());
+        assert_eq!(
+            get_mappings(&db, &AccountId::from("home"))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn imported_filters_are_replaced() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account = AccountId::from("work");
+        let filter = GmailFilter {
+            id: Some("f1".to_string()),
+            criteria: GmailFilterCriteria {
+                from: Some("boss@work.example".to_string()),
+                ..Default::default()
+            },
+            action: GmailFilterAction {
+                add_label_ids: vec!["STARRED".to_string()],
+                ..Default::default()
+            },
+        };
+
+        replace_imported(&db, &account, std::slice::from_ref(&filter))
+            .await
+            .unwrap();
+        let imported = get_imported(&db, &account).await.unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].filter, filter);
+
+        replace_imported(&db, &account, &[]).await.unwrap();
+        assert!(get_imported(&db, &account).await.unwrap().is_empty());
+    }
+}
@@ -3,8 +3,14 @@
 //! Each module provides async functions that operate on the database.
 
 pub mod accounts;
+pub mod ai_cache;
 pub mod contacts;
 pub mod emails;
+pub mod embeddings;
+pub mod filters;
+pub mod gmail_filters;
 pub mod labels;
 pub mod screener;
+pub mod spam;
 pub mod threads;
+pub mod triage;
@@ -5,11 +5,17 @@
 use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection, OptionalExtension, Result};
 
-use crate::domain::EmailId;
+use crate::domain::{first_matching_rule, AccountId, EmailId, Header};
 use crate::domain::{
-    RuleType, ScreenerAction, ScreenerEntry, ScreenerRule, ScreenerStatus, SenderAnalysis,
+    RuleField, RuleType, ScreenedMessage, ScreenerAction, ScreenerEntry, ScreenerRule,
+    ScreenerStatus, SenderAnalysis,
 };
 
+const ENTRY_COLUMNS: &str = "id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at, account_id";
+
+const RULE_COLUMNS: &str =
+    "id, rule_type, pattern, action, created_at, account_id, field, priority";
+
 /// Inserts a new screener entry.
 pub fn insert_entry(conn: &Connection, entry: &ScreenerEntry) -> Result<()> {
     let ai_analysis_json = entry
@@ -18,8 +24,7 @@ pub fn insert_entry(conn: &Connection, entry: &ScreenerEntry) -> Result<()> {
         .map(|a| serde_json::to_string(a).unwrap_or_default());
 
     conn.execute(
-        "INSERT INTO screener_entries (id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        &format!("INSERT INTO screener_entries ({ENTRY_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"),
         params![
             entry.id,
             entry.sender_email,
@@ -29,6 +34,7 @@ pub fn insert_entry(conn: &Connection, entry: &ScreenerEntry) -> Result<()> {
             ai_analysis_json,
             entry.decided_at.map(|dt| dt.to_rfc3339()),
             entry.created_at.to_rfc3339(),
+            entry.account_id.0,
         ],
     )?;
     Ok(())
@@ -37,47 +43,53 @@ pub fn insert_entry(conn: &Connection, entry: &ScreenerEntry) -> Result<()> {
 /// Gets a screener entry by ID.
 pub fn get_entry_by_id(conn: &Connection, id: &str) -> Result<Option<ScreenerEntry>> {
     conn.query_row(
-        "SELECT id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at
-         FROM screener_entries WHERE id = ?1",
+        &format!("SELECT {ENTRY_COLUMNS} FROM screener_entries WHERE id = ?1"),
         params![id],
         row_to_entry,
     )
     .optional()
 }
 
-/// Gets a screener entry by sender email.
-pub fn get_entry_by_sender(conn: &Connection, sender_email: &str) -> Result<Option<ScreenerEntry>> {
+/// Gets an account's screener entry for a sender email.
+///
+/// Addresses are compared without regard to case, so entries saved before
+/// addresses were lowercased are still found.
+pub fn get_entry_by_sender(
+    conn: &Connection,
+    account_id: &AccountId,
+    sender_email: &str,
+) -> Result<Option<ScreenerEntry>> {
     conn.query_row(
-        "SELECT id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at
-         FROM screener_entries WHERE sender_email = ?1",
-        params![sender_email],
+        &format!(
+            "SELECT {ENTRY_COLUMNS} FROM screener_entries
+             WHERE account_id = ?1 AND sender_email = ?2 COLLATE NOCASE"
+        ),
+        params![account_id.0, sender_email],
         row_to_entry,
     )
     .optional()
 }
 
-/// Gets all pending screener entries.
-pub fn get_pending_entries(conn: &Connection) -> Result<Vec<ScreenerEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at
-         FROM screener_entries WHERE status = 'pending' ORDER BY created_at DESC",
-    )?;
-
-    let entries = stmt.query_map([], row_to_entry)?;
-    entries.collect()
+/// Gets an account's pending screener entries.
+pub fn get_pending_entries(
+    conn: &Connection,
+    account_id: &AccountId,
+) -> Result<Vec<ScreenerEntry>> {
+    get_entries_by_status(conn, account_id, &ScreenerStatus::Pending)
 }
 
-/// Gets screener entries by status.
+/// Gets an account's screener entries by status.
 pub fn get_entries_by_status(
     conn: &Connection,
+    account_id: &AccountId,
     status: &ScreenerStatus,
 ) -> Result<Vec<ScreenerEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, sender_email, sender_name, first_email_id, status, ai_analysis, decided_at, created_at
-         FROM screener_entries WHERE status = ?1 ORDER BY created_at DESC",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS} FROM screener_entries
+         WHERE account_id = ?1 AND status = ?2 ORDER BY created_at DESC"
+    ))?;
 
-    let entries = stmt.query_map(params![status_to_str(status)], row_to_entry)?;
+    let entries = stmt.query_map(params![account_id.0, status_to_str(status)], row_to_entry)?;
     entries.collect()
 }
 
@@ -111,20 +123,20 @@ pub fn delete_entry(conn: &Connection, id: &str) -> Result<()> {
     Ok(())
 }
 
-/// Counts pending screener entries.
-pub fn count_pending(conn: &Connection) -> Result<u32> {
-    conn.query_row(
-        "SELECT COUNT(*) FROM screener_entries WHERE status = 'pending'",
-        [],
-        |row| row.get(0),
-    )
+/// Counts an account's pending screener entries.
+pub fn count_pending(conn: &Connection, account_id: &AccountId) -> Result<u32> {
+    count_by_status(conn, account_id, &ScreenerStatus::Pending)
 }
 
-/// Counts entries by status.
-pub fn count_by_status(conn: &Connection, status: &ScreenerStatus) -> Result<u32> {
+/// Counts an account's entries by status.
+pub fn count_by_status(
+    conn: &Connection,
+    account_id: &AccountId,
+    status: &ScreenerStatus,
+) -> Result<u32> {
     conn.query_row(
-        "SELECT COUNT(*) FROM screener_entries WHERE status = ?1",
-        params![status_to_str(status)],
+        "SELECT COUNT(*) FROM screener_entries WHERE account_id = ?1 AND status = ?2",
+        params![account_id.0, status_to_str(status)],
         |row| row.get(0),
     )
 }
@@ -134,14 +146,17 @@ pub fn count_by_status(conn: &Connection, status: &ScreenerStatus) -> Result<u32
 /// Inserts a new screener rule.
 pub fn insert_rule(conn: &Connection, rule: &ScreenerRule) -> Result<()> {
     conn.execute(
-        "INSERT INTO screener_rules (id, rule_type, pattern, action, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO screener_rules (id, rule_type, pattern, action, created_at, account_id, field, priority)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
         params![
             rule.id,
             rule_type_to_str(&rule.rule_type),
             rule.pattern,
             action_to_str(&rule.action),
             rule.created_at.to_rfc3339(),
+            rule.account_id.as_ref().map(|id| id.0.as_str()),
+            field_to_str(&rule.field),
+            rule.priority,
         ],
     )?;
     Ok(())
@@ -150,72 +165,91 @@ pub fn insert_rule(conn: &Connection, rule: &ScreenerRule) -> Result<()> {
 /// Gets a rule by ID.
 pub fn get_rule_by_id(conn: &Connection, id: &str) -> Result<Option<ScreenerRule>> {
     conn.query_row(
-        "SELECT id, rule_type, pattern, action, created_at
-         FROM screener_rules WHERE id = ?1",
+        &format!("SELECT {RULE_COLUMNS} FROM screener_rules WHERE id = ?1"),
         params![id],
         row_to_rule,
     )
     .optional()
 }
 
-/// Gets all screener rules.
+/// Gets all screener rules, highest priority first.
 pub fn get_all_rules(conn: &Connection) -> Result<Vec<ScreenerRule>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, rule_type, pattern, action, created_at
-         FROM screener_rules ORDER BY created_at DESC",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RULE_COLUMNS} FROM screener_rules ORDER BY priority DESC, created_at DESC"
+    ))?;
 
     let rules = stmt.query_map([], row_to_rule)?;
     rules.collect()
 }
 
+/// Gets the rules that apply to an account, including rules for every
+/// account, highest priority first.
+pub fn get_rules_for_account(
+    conn: &Connection,
+    account_id: &AccountId,
+) -> Result<Vec<ScreenerRule>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RULE_COLUMNS} FROM screener_rules
+         WHERE account_id IS NULL OR account_id = ?1
+         ORDER BY priority DESC, created_at DESC"
+    ))?;
+
+    let rules = stmt.query_map(params![account_id.0], row_to_rule)?;
+    rules.collect()
+}
+
 /// Gets rules by type.
 pub fn get_rules_by_type(conn: &Connection, rule_type: &RuleType) -> Result<Vec<ScreenerRule>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, rule_type, pattern, action, created_at
-         FROM screener_rules WHERE rule_type = ?1 ORDER BY created_at DESC",
-    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RULE_COLUMNS} FROM screener_rules WHERE rule_type = ?1 ORDER BY created_at DESC"
+    ))?;
 
     let rules = stmt.query_map(params![rule_type_to_str(rule_type)], row_to_rule)?;
     rules.collect()
 }
 
-/// Finds a matching rule for an email address.
-pub fn find_matching_rule(conn: &Connection, email: &str) -> Result<Option<ScreenerRule>> {
-    // Extract domain from email
-    let domain = email.split('@').nth(1).unwrap_or("");
-
-    // Check domain rules first
-    if let Some(rule) = conn
-        .query_row(
-            "SELECT id, rule_type, pattern, action, created_at
-         FROM screener_rules WHERE rule_type IN ('domain_allow', 'domain_block') AND pattern = ?1",
-            params![domain],
-            row_to_rule,
-        )
-        .optional()?
-    {
-        return Ok(Some(rule));
-    }
+/// Finds the rule that decides a message for an account.
+///
+/// Rules are tried in precedence order: priority, then account-specific
+/// before global, then newest first.
+pub fn find_matching_rule(
+    conn: &Connection,
+    account_id: &AccountId,
+    message: &ScreenedMessage,
+) -> Result<Option<ScreenerRule>> {
+    let rules = get_rules_for_account(conn, account_id)?;
+    Ok(first_matching_rule(&rules, account_id, message).cloned())
+}
 
-    // Check pattern rules
+/// Gets the most recent message from each of an account's latest senders.
+pub fn get_recent_senders(
+    conn: &Connection,
+    account_id: &AccountId,
+    limit: usize,
+) -> Result<Vec<ScreenedMessage>> {
+    // SQLite takes the bare columns from the row holding MAX(date)
     let mut stmt = conn.prepare(
-        "SELECT id, rule_type, pattern, action, created_at
-         FROM screener_rules WHERE rule_type = 'pattern'",
+        "SELECT from_address, subject, raw_headers, MAX(date) AS last_date
+         FROM emails WHERE account_id = ?1
+         GROUP BY lower(from_address) ORDER BY last_date DESC LIMIT ?2",
     )?;
 
-    let rules: Vec<ScreenerRule> = stmt
-        .query_map([], row_to_rule)?
-        .filter_map(|r| r.ok())
-        .collect();
-
-    for rule in rules {
-        if email.contains(&rule.pattern) || domain.contains(&rule.pattern) {
-            return Ok(Some(rule));
-        }
-    }
-
-    Ok(None)
+    let senders = stmt.query_map(params![account_id.0, limit as i64], |row| {
+        let sender: String = row.get(0)?;
+        let headers: Vec<Header> = row
+            .get::<_, Option<String>>(2)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Ok(ScreenedMessage {
+            sender: sender.trim().to_lowercase(),
+            list_id: headers
+                .into_iter()
+                .find(|h| h.name.eq_ignore_ascii_case("List-Id"))
+                .map(|h| h.value),
+            subject: row.get(1)?,
+        })
+    })?;
+    senders.collect()
 }
 
 /// Deletes a rule.
@@ -252,6 +286,7 @@ fn rule_type_to_str(rt: &RuleType) -> &'static str {
         RuleType::DomainAllow => "domain_allow",
         RuleType::DomainBlock => "domain_block",
         RuleType::Pattern => "pattern",
+        RuleType::Regex => "regex",
     }
 }
 
@@ -259,10 +294,27 @@ fn str_to_rule_type(s: &str) -> RuleType {
     match s {
         "domain_allow" => RuleType::DomainAllow,
         "domain_block" => RuleType::DomainBlock,
+        "regex" => RuleType::Regex,
         _ => RuleType::Pattern,
     }
 }
 
+fn field_to_str(field: &RuleField) -> &'static str {
+    match field {
+        RuleField::Sender => "sender",
+        RuleField::ListId => "list_id",
+        RuleField::Subject => "subject",
+    }
+}
+
+fn str_to_field(s: &str) -> RuleField {
+    match s {
+        "list_id" => RuleField::ListId,
+        "subject" => RuleField::Subject,
+        _ => RuleField::Sender,
+    }
+}
+
 fn action_to_str(action: &ScreenerAction) -> &'static str {
     match action {
         ScreenerAction::Approve => "approve",
@@ -284,9 +336,11 @@ fn row_to_entry(row: &rusqlite::Row) -> Result<ScreenerEntry> {
     let decided_at_str: Option<String> = row.get(6)?;
     let created_at_str: String = row.get(7)?;
     let first_email_id: Option<String> = row.get(3)?;
+    let account_id: Option<String> = row.get(8)?;
 
     Ok(ScreenerEntry {
         id: row.get(0)?,
+        account_id: AccountId::from(account_id.unwrap_or_default()),
         sender_email: row.get(1)?,
         sender_name: row.get(2)?,
         first_email_id: first_email_id.map(EmailId::from),
@@ -305,12 +359,16 @@ fn row_to_entry(row: &rusqlite::Row) -> Result<ScreenerEntry> {
 
 fn row_to_rule(row: &rusqlite::Row) -> Result<ScreenerRule> {
     let created_at_str: String = row.get(4)?;
+    let account_id: Option<String> = row.get(5)?;
 
     Ok(ScreenerRule {
         id: row.get(0)?,
+        account_id: account_id.map(AccountId::from),
         rule_type: str_to_rule_type(row.get::<_, String>(1)?.as_str()),
+        field: str_to_field(row.get::<_, String>(6)?.as_str()),
         pattern: row.get(2)?,
         action: str_to_action(row.get::<_, String>(3)?.as_str()),
+        priority: row.get(7)?,
         created_at: DateTime::parse_from_rfc3339(&created_at_str)
             .map(|dt| dt.with_timezone(&Utc))
             .unwrap_or_else(|_| Utc::now()),
@@ -329,9 +387,14 @@ mod tests {
         conn
     }
 
+    fn account() -> AccountId {
+        AccountId::from("account-1")
+    }
+
     fn make_entry(id: &str, sender_email: &str) -> ScreenerEntry {
         ScreenerEntry {
             id: id.to_string(),
+            account_id: account(),
             sender_email: sender_email.to_string(),
             sender_name: None,
             first_email_id: None,
@@ -350,10 +413,7 @@ mod tests {
     ) -> ScreenerRule {
         ScreenerRule {
             id: id.to_string(),
-            rule_type,
-            pattern: pattern.to_string(),
-            action,
-            created_at: Utc::now(),
+            ..ScreenerRule::new(rule_type, pattern, action)
         }
     }
 
@@ -375,10 +435,23 @@ mod tests {
         let entry = make_entry("e1", "test@example.com");
         insert_entry(&conn, &entry).unwrap();
 
-        let fetched = super::get_entry_by_sender(&conn, "test@example.com")
+        let fetched = super::get_entry_by_sender(&conn, &account(), "test@example.com")
             .unwrap()
             .unwrap();
         assert_eq!(fetched.id, "e1");
+        assert_eq!(fetched.account_id, account());
+        assert!(
+            super::get_entry_by_sender(&conn, &account(), "Test@Example.com")
+                .unwrap()
+                .is_some()
+        );
+
+        let other = AccountId::from("account-2");
+        assert!(
+            super::get_entry_by_sender(&conn, &other, "test@example.com")
+                .unwrap()
+                .is_none()
+        );
     }
 
     #[test]
@@ -403,7 +476,7 @@ mod tests {
 
         set_entry_status(&conn, "e1", &ScreenerStatus::Approved).unwrap();
 
-        let pending = super::get_pending_entries(&conn).unwrap();
+        let pending = super::get_pending_entries(&conn, &account()).unwrap();
         assert_eq!(pending.len(), 1);
         assert_eq!(pending[0].id, "e2");
     }
@@ -415,10 +488,23 @@ mod tests {
         insert_entry(&conn, &make_entry("e1", "a@example.com")).unwrap();
         insert_entry(&conn, &make_entry("e2", "b@example.com")).unwrap();
 
-        assert_eq!(super::count_pending(&conn).unwrap(), 2);
+        insert_entry(
+            &conn,
+            &ScreenerEntry {
+                account_id: AccountId::from("account-2"),
+                ..make_entry("e3", "a@example.com")
+            },
+        )
+        .unwrap();
+
+        assert_eq!(super::count_pending(&conn, &account()).unwrap(), 2);
 
         set_entry_status(&conn, "e1", &ScreenerStatus::Rejected).unwrap();
-        assert_eq!(super::count_pending(&conn).unwrap(), 1);
+        assert_eq!(super::count_pending(&conn, &account()).unwrap(), 1);
+        assert_eq!(
+            super::count_pending(&conn, &AccountId::from("account-2")).unwrap(),
+            1
+        );
     }
 
     #[test]
@@ -450,14 +536,74 @@ mod tests {
         );
         insert_rule(&conn, &rule).unwrap();
 
-        let matched = find_matching_rule(&conn, "user@trusted.com").unwrap();
+        let account_id = AccountId::from("account-1");
+        let matched = find_matching_rule(
+            &conn,
+            &account_id,
+            &ScreenedMessage::from_sender("user@mail.trusted.com"),
+        )
+        .unwrap();
         assert!(matched.is_some());
         assert_eq!(matched.unwrap().pattern, "trusted.com");
 
-        let no_match = find_matching_rule(&conn, "user@other.com").unwrap();
+        let no_match = find_matching_rule(
+            &conn,
+            &account_id,
+            &ScreenedMessage::from_sender("user@untrusted.com"),
+        )
+        .unwrap();
         assert!(no_match.is_none());
     }
 
+    #[test]
+    fn find_matching_rule_uses_priority_and_scope() {
+        let conn = setup();
+        let account_id = AccountId::from("account-1");
+
+        insert_rule(
+            &conn,
+            &make_rule(
+                "r1",
+                RuleType::DomainBlock,
+                "shop.com",
+                ScreenerAction::Reject,
+            ),
+        )
+        .unwrap();
+        insert_rule(
+            &conn,
+            &ScreenerRule {
+                id: "r2".to_string(),
+                ..ScreenerRule::new(RuleType::Regex, "^receipts@", ScreenerAction::Approve)
+                    .with_priority(5)
+            },
+        )
+        .unwrap();
+        insert_rule(
+            &conn,
+            &ScreenerRule {
+                id: "r3".to_string(),
+                ..ScreenerRule::new(RuleType::Pattern, "*", ScreenerAction::Review)
+                    .with_priority(10)
+                    .for_account(AccountId::from("account-2"))
+            },
+        )
+        .unwrap();
+
+        let rule = |sender: &str| {
+            find_matching_rule(&conn, &account_id, &ScreenedMessage::from_sender(sender))
+                .unwrap()
+                .map(|r| r.id)
+        };
+        assert_eq!(rule("receipts@shop.com").as_deref(), Some("r2"));
+        assert_eq!(rule("deals@shop.com").as_deref(), Some("r1"));
+        assert_eq!(rule("friend@home.net"), None);
+
+        let scoped = get_rule_by_id(&conn, "r3").unwrap().unwrap();
+        assert_eq!(scoped.account_id, Some(AccountId::from("account-2")));
+        assert_eq!(scoped.priority, 10);
+    }
+
     #[test]
     fn filter_rules_by_type() {
         let conn = setup();
@@ -0,0 +1,312 @@
+//! Spam filter operations.
+//!
+//! Token counts are updated in the same transaction that records how an
+//! email was trained, so reversing a judgment never leaves the counts and
+//! the training record out of step.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+
+use crate::domain::{AccountId, EmailId};
+use crate::services::{SpamCounts, SpamError, SpamResult, SpamStorage, SpamVerdict};
+use crate::storage::database::{Database, Result};
+
+/// Returns the counts of the given tokens; unseen tokens are omitted.
+pub async fn get_token_counts(
+    db: &Database,
+    account_id: &AccountId,
+    tokens: &[String],
+) -> Result<HashMap<String, SpamCounts>> {
+    let account_id = account_id.clone();
+    let tokens = tokens.to_vec();
+
+    db.with_conn(move |conn| {
+        let mut stmt = conn.prepare_cached(
+            "SELECT spam_count, ham_count FROM spam_tokens WHERE account_id = ?1 AND token = ?2",
+        )?;
+        let mut counts = HashMap::new();
+        for token in tokens {
+            let found = stmt
+                .query_row(params![account_id.0, token], |row| {
+                    Ok(SpamCounts {
+                        spam: row.get(0)?,
+                        ham: row.get(1)?,
+                    })
+                })
+                .optional()?;
+            if let Some(found) = found {
+                counts.insert(token, found);
+            }
+        }
+        Ok(counts)
+    })
+    .await
+}
+
+/// Returns the number of emails trained as spam and as ham.
+pub async fn get_message_counts(db: &Database, account_id: &AccountId) -> Result<SpamCounts> {
+    let account_id = account_id.clone();
+
+    db.with_conn(move |conn| {
+        let counts = conn.query_row(
+            "SELECT COALESCE(SUM(is_spam), 0), COALESCE(SUM(1 - is_spam), 0)
+             FROM spam_training WHERE account_id = ?1",
+            [&account_id.0],
+            |row| {
+                Ok(SpamCounts {
+                    spam: row.get(0)?,
+                    ham: row.get(1)?,
+                })
+            },
+        )?;
+        Ok(counts)
+    })
+    .await
+}
+
+/// Returns whether an email was trained as spam, if it was trained.
+pub async fn get_trained_as(
+    db: &Database,
+    account_id: &AccountId,
+    email_id: &EmailId,
+) -> Result<Option<bool>> {
+    let account_id = account_id.clone();
+    let email_id = email_id.clone();
+
+    db.with_conn(move |conn| {
+        let is_spam = conn
+            .query_row(
+                "SELECT is_spam FROM spam_training WHERE account_id = ?1 AND email_id = ?2",
+                params![account_id.0, email_id.0],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(is_spam)
+    })
+    .await
+}
+
+/// Records an email as spam or ham and adds its tokens to the counts,
+/// first removing them from the other side if it was trained before.
+pub async fn train(
+    db: &Database,
+    account_id: &AccountId,
+    email_id: &EmailId,
+    tokens: &[String],
+    is_spam: bool,
+) -> Result<()> {
+    let account_id = account_id.clone();
+    let email_id = email_id.clone();
+    let tokens = tokens.to_vec();
+
+    db.transaction(move |tx| {
+        let previous: Option<bool> = tx
+            .query_row(
+                "SELECT is_spam FROM spam_training WHERE account_id = ?1 AND email_id = ?2",
+                params![account_id.0, email_id.0],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if previous == Some(is_spam) {
+            return Ok(());
+        }
+
+        let (add, remove) = if is_spam {
+            ("spam_count", "ham_count")
+        } else {
+            ("ham_count", "spam_count")
+        };
+        let upsert = format!(
+            "INSERT INTO spam_tokens (account_id, token, {add}) VALUES (?1, ?2, 1)
+             ON CONFLICT(account_id, token) DO UPDATE SET {add} = {add} + 1"
+        );
+        let untrain = format!(
+            "UPDATE spam_tokens SET {remove} = MAX({remove} - 1, 0)
+             WHERE account_id = ?1 AND token = ?2"
+        );
+        for token in &tokens {
+            if previous.is_some() {
+                tx.execute(&untrain, params![account_id.0, token])?;
+            }
+            tx.execute(&upsert, params![account_id.0, token])?;
+        }
+
+        tx.execute(
+            "INSERT OR REPLACE INTO spam_training (account_id, email_id, is_spam, trained_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![account_id.0, email_id.0, is_spam, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Stores the verdict JSON for an email.
+pub async fn put_verdict(
+    db: &Database,
+    account_id: &AccountId,
+    email_id: &EmailId,
+    verdict: &str,
+) -> Result<()> {
+    let account_id = account_id.clone();
+    let email_id = email_id.clone();
+    let verdict = verdict.to_string();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO spam_verdicts (account_id, email_id, verdict, created_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![account_id.0, email_id.0, verdict, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+/// Returns the stored verdict JSON for an email.
+pub async fn get_verdict(
+    db: &Database,
+    account_id: &AccountId,
+    email_id: &EmailId,
+) -> Result<Option<String>> {
+    let account_id = account_id.clone();
+    let email_id = email_id.clone();
+
+    db.with_conn(move |conn| {
+        let verdict = conn
+            .query_row(
+                "SELECT verdict FROM spam_verdicts WHERE account_id = ?1 AND email_id = ?2",
+                params![account_id.0, email_id.0],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(verdict)
+    })
+    .await
+}
+
+fn storage_error(e: impl std::fmt::Display) -> SpamError {
+    SpamError::Storage(e.to_string())
+}
+
+#[async_trait::async_trait]
+impl SpamStorage for Database {
+    async fn token_counts(
+        &self,
+        account_id: &AccountId,
+        tokens: &[String],
+    ) -> SpamResult<HashMap<String, SpamCounts>> {
+        get_token_counts(self, account_id, tokens)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn message_counts(&self, account_id: &AccountId) -> SpamResult<SpamCounts> {
+        get_message_counts(self, account_id)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn trained_as(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+    ) -> SpamResult<Option<bool>> {
+        get_trained_as(self, account_id, email_id)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn train(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+        tokens: &[String],
+        is_spam: bool,
+    ) -> SpamResult<()> {
+        train(self, account_id, email_id, tokens, is_spam)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn save_verdict(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+        verdict: &SpamVerdict,
+    ) -> SpamResult<()> {
+        let json = serde_json::to_string(verdict).map_err(storage_error)?;
+        put_verdict(self, account_id, email_id, &json)
+            .await
+            .map_err(storage_error)
+    }
+
+    async fn get_verdict(
+        &self,
+        account_id: &AccountId,
+        email_id: &EmailId,
+    ) -> SpamResult<Option<SpamVerdict>> {
+        let json = get_verdict(self, account_id, email_id)
+            .await
+            .map_err(storage_error)?;
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn retraining_moves_counts() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account_id = AccountId::from("account-1");
+        let email_id = EmailId::from("email-1");
+        let tokens = vec!["url:prize.example".to_string(), "cruise".to_string()];
+
+        train(&db, &account_id, &email_id, &tokens, true)
+            .await
+            .unwrap();
+        train(&db, &account_id, &email_id, &tokens, true)
+            .await
+            .unwrap();
+        let counts = get_token_counts(&db, &account_id, &tokens).await.unwrap();
+        assert_eq!(counts["cruise"], SpamCounts { spam: 1, ham: 0 });
+
+        train(&db, &account_id, &email_id, &tokens, false)
+            .await
+            .unwrap();
+        let counts = get_token_counts(&db, &account_id, &tokens).await.unwrap();
+        assert_eq!(counts["cruise"], SpamCounts { spam: 0, ham: 1 });
+        assert_eq!(
+            get_message_counts(&db, &account_id).await.unwrap(),
+            SpamCounts { spam: 0, ham: 1 }
+        );
+        assert_eq!(
+            get_trained_as(&db, &account_id, &email_id).await.unwrap(),
+            Some(false)
+        );
+    }
+
+    #[tokio::test]
+    async fn verdict_round_trips() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account_id = AccountId::from("account-1");
+        let email_id = EmailId::from("email-1");
+        let verdict = SpamVerdict {
+            score: 0.97,
+            is_spam: true,
+            top_tokens: vec![],
+        };
+
+        db.save_verdict(&account_id, &email_id, &verdict)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_verdict(&account_id, &email_id).await.unwrap(),
+            Some(verdict)
+        );
+    }
+}
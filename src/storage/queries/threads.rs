@@ -232,6 +232,19 @@ pub async fn delete(db: &Database, thread_id: &ThreadId) -> Result<()> {
     let thread_id = thread_id.clone();
 
     db.transaction(move |tx| {
+        for table in ["embeddings", "passage_embeddings", "embedding_jobs"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE email_id IN (SELECT id FROM emails WHERE thread_id = ?1)"
+                ),
+                [&thread_id.0],
+            )?;
+        }
+        tx.execute(
+            "UPDATE screener_entries SET first_email_id = NULL
+             WHERE first_email_id IN (SELECT id FROM emails WHERE thread_id = ?1)",
+            [&thread_id.0],
+        )?;
         tx.execute("DELETE FROM emails WHERE thread_id = ?1", [&thread_id.0])?;
         tx.execute("DELETE FROM threads WHERE id = ?1", [&thread_id.0])?;
         Ok(())
@@ -488,6 +501,86 @@ mod tests {
         assert!(get_by_id(&db, &summary.id).await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn delete_thread_with_indexed_mail() {
+        let db = setup_db_with_account().await;
+        let summary = make_test_summary();
+        upsert(&db, &summary).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                    to_addresses, date, created_at, updated_at)
+                VALUES ('e1', 'account-1', 'thread-1', 'm1', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01');
+                INSERT INTO passage_embeddings (email_id, passage_index, content, embedding, created_at)
+                VALUES ('e1', 0, 'hello', x'0000803f', '2025-01-01');
+                INSERT INTO embedding_jobs (email_id, account_id, status, enqueued_at, updated_at)
+                VALUES ('e1', 'account-1', 'done', '2025-01-01', '2025-01-01');
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &summary.id).await.unwrap();
+
+        let leftover: i64 = db
+            .with_conn(|conn| {
+                let count = conn.query_row(
+                    "SELECT (SELECT COUNT(*) FROM passage_embeddings)
+                          + (SELECT COUNT(*) FROM embedding_jobs)
+                          + (SELECT COUNT(*) FROM emails)",
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok(count)
+            })
+            .await
+            .unwrap();
+        assert_eq!(leftover, 0);
+    }
+
+    #[tokio::test]
+    async fn delete_thread_kept_by_screener_entry() {
+        let db = setup_db_with_account().await;
+        let summary = make_test_summary();
+        upsert(&db, &summary).await.unwrap();
+
+        db.with_conn(|conn| {
+            conn.execute_batch(
+                r#"
+                INSERT INTO emails (id, account_id, thread_id, message_id, from_address,
+                    to_addresses, date, created_at, updated_at)
+                VALUES ('e1', 'account-1', 'thread-1', 'm1', 'a@example.com', '[]',
+                    '2025-01-01', '2025-01-01', '2025-01-01');
+                INSERT INTO screener_entries (id, sender_email, first_email_id, status, created_at, account_id)
+                VALUES ('s1', 'a@example.com', 'e1', 'approved', '2025-01-01', 'account-1');
+                "#,
+            )?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        delete(&db, &summary.id).await.unwrap();
+
+        let first_email_id: Option<String> = db
+            .with_conn(|conn| {
+                let id = conn.query_row(
+                    "SELECT first_email_id FROM screener_entries WHERE id = 's1'",
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok(id)
+            })
+            .await
+            .unwrap();
+        assert_eq!(first_email_id, None);
+    }
+
     #[tokio::test]
     async fn count_threads() {
         let db = setup_db_with_account().await;
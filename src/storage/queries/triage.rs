@@ -0,0 +1,92 @@
+//! Triage model operations.
+//!
+//! Each account's classifier is stored as one JSON document and replaced
+//! whenever it learns from a user action.
+
+use chrono::Utc;
+use rusqlite::{params, OptionalExtension};
+
+use crate::domain::AccountId;
+use crate::services::{TriageError, TriageModel, TriageResult, TriageStorage};
+use crate::storage::database::{Database, Result};
+
+/// Returns the stored model JSON for an account.
+pub async fn get(db: &Database, account_id: &AccountId) -> Result<Option<String>> {
+    let account_id = account_id.clone();
+
+    db.with_conn(move |conn| {
+        let model = conn
+            .query_row(
+                "SELECT model FROM triage_models WHERE account_id = ?1",
+                [&account_id.0],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(model)
+    })
+    .await
+}
+
+/// Stores the model JSON for an account, replacing any earlier model.
+pub async fn put(db: &Database, account_id: &AccountId, model: &str) -> Result<()> {
+    let account_id = account_id.clone();
+    let model = model.to_string();
+
+    db.with_conn(move |conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO triage_models (account_id, model, updated_at) VALUES (?1, ?2, ?3)",
+            params![account_id.0, model, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    })
+    .await
+}
+
+#[async_trait::async_trait]
+impl TriageStorage for Database {
+    async fn load_model(&self, account_id: &AccountId) -> TriageResult<Option<TriageModel>> {
+        let json = get(self, account_id)
+            .await
+            .map_err(|e| TriageError::Storage(e.to_string()))?;
+        // A model that no longer parses is retrained from scratch
+        Ok(json.and_then(|json| serde_json::from_str(&json).ok()))
+    }
+
+    async fn save_model(&self, account_id: &AccountId, model: &TriageModel) -> TriageResult<()> {
+        let json = serde_json::to_string(model).map_err(|e| TriageError::Storage(e.to_string()))?;
+        put(self, account_id, &json)
+            .await
+            .map_err(|e| TriageError::Storage(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn put_replaces_model() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account_id = AccountId::from("account-1");
+
+        assert!(get(&db, &account_id).await.unwrap().is_none());
+        put(&db, &account_id, "{\"old\":1}").await.unwrap();
+        put(&db, &account_id, "{\"new\":1}").await.unwrap();
+
+        assert_eq!(
+            get(&db, &account_id).await.unwrap().as_deref(),
+            Some("{\"new\":1}")
+        );
+    }
+
+    #[tokio::test]
+    async fn model_round_trips() {
+        let db = Database::open_in_memory().await.unwrap();
+        let account_id = AccountId::from("account-1");
+
+        db.save_model(&account_id, &TriageModel::default())
+            .await
+            .unwrap();
+        assert!(db.load_model(&account_id).await.unwrap().is_some());
+    }
+}
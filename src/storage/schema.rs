@@ -157,7 +157,8 @@ CREATE TABLE IF NOT EXISTS screener_entries (
     status TEXT NOT NULL,
     ai_analysis TEXT,
     decided_at TEXT,
-    created_at TEXT NOT NULL
+    created_at TEXT NOT NULL,
+    account_id TEXT
 )
 "#;
 
@@ -168,7 +169,10 @@ CREATE TABLE IF NOT EXISTS screener_rules (
     rule_type TEXT NOT NULL,
     pattern TEXT NOT NULL,
     action TEXT NOT NULL,
-    created_at TEXT NOT NULL
+    created_at TEXT NOT NULL,
+    account_id TEXT,
+    field TEXT NOT NULL DEFAULT 'sender',
+    priority INTEGER NOT NULL DEFAULT 0
 )
 "#;
 
@@ -218,6 +222,40 @@ CREATE TABLE IF NOT EXISTS embeddings (
 )
 "#;
 
+/// SQL to create the passage_embeddings table.
+///
+/// Long emails are embedded as several overlapping passages.
+pub const CREATE_PASSAGE_EMBEDDINGS: &str = r#"
+CREATE TABLE IF NOT EXISTS passage_embeddings (
+    email_id TEXT NOT NULL REFERENCES emails(id),
+    passage_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding BLOB NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (email_id, passage_index)
+)
+"#;
+
+/// SQL to create the embedding job queue.
+///
+/// Rows persist across restarts so indexing resumes where it stopped.
+pub const CREATE_EMBEDDING_JOBS: &str = r#"
+CREATE TABLE IF NOT EXISTS embedding_jobs (
+    email_id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    enqueued_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+)
+"#;
+
+/// SQL to create the embedding job queue index.
+pub const CREATE_EMBEDDING_JOBS_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_embedding_jobs_status ON embedding_jobs(status, enqueued_at)
+"#;
+
 /// SQL to create the telemetry_events table.
 pub const CREATE_TELEMETRY_EVENTS: &str = r#"
 CREATE TABLE IF NOT EXISTS telemetry_events (
@@ -261,6 +299,120 @@ CREATE TABLE IF NOT EXISTS settings (
 )
 "#;
 
+/// SQL to create the ai_cache table.
+///
+/// Holds generated summaries, categorizations and sender analyses keyed by
+/// a hash of their prompt input.
+pub const CREATE_AI_CACHE: &str = r#"
+CREATE TABLE IF NOT EXISTS ai_cache (
+    key TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    thread_id TEXT,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    value TEXT NOT NULL,
+    created_at TEXT NOT NULL
+)
+"#;
+
+/// SQL to create the ai_cache thread index.
+pub const CREATE_AI_CACHE_INDEX: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_ai_cache_thread ON ai_cache(thread_id, kind)
+"#;
+
+/// SQL to create the trigger that drops cached results for a thread when
+/// it gains a message.
+pub const CREATE_AI_CACHE_TRIGGERS: &str = r#"
+CREATE TRIGGER IF NOT EXISTS emails_ai_cache AFTER INSERT ON emails BEGIN
+    DELETE FROM ai_cache WHERE thread_id = NEW.thread_id;
+END
+"#;
+
+/// SQL to create the triage_models table.
+///
+/// Holds each account's on-device triage classifier as JSON.
+pub const CREATE_TRIAGE_MODELS: &str = r#"
+CREATE TABLE IF NOT EXISTS triage_models (
+    account_id TEXT PRIMARY KEY,
+    model TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+)
+"#;
+
+/// SQL to create the spam filter tables.
+///
+/// `spam_tokens` holds per-account token counts, `spam_training` records
+/// how each trained email was judged so a reversal can be untrained, and
+/// `spam_verdicts` keeps the explanation for mail routed to Junk.
+pub const CREATE_SPAM_FILTER: &str = r#"
+CREATE TABLE IF NOT EXISTS spam_tokens (
+    account_id TEXT NOT NULL,
+    token TEXT NOT NULL,
+    spam_count INTEGER NOT NULL DEFAULT 0,
+    ham_count INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (account_id, token)
+);
+
+CREATE TABLE IF NOT EXISTS spam_training (
+    account_id TEXT NOT NULL,
+    email_id TEXT NOT NULL,
+    is_spam INTEGER NOT NULL,
+    trained_at TEXT NOT NULL,
+    PRIMARY KEY (account_id, email_id)
+);
+
+CREATE TABLE IF NOT EXISTS spam_verdicts (
+    account_id TEXT NOT NULL,
+    email_id TEXT NOT NULL,
+    verdict TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (account_id, email_id)
+)
+"#;
+
+/// SQL to create the filter_rules table.
+///
+/// Conditions and actions are stored as JSON arrays.
+pub const CREATE_FILTER_RULES: &str = r#"
+CREATE TABLE IF NOT EXISTS filter_rules (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    conditions TEXT NOT NULL,
+    match_all INTEGER NOT NULL DEFAULT 1,
+    actions TEXT NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0,
+    stop_processing INTEGER NOT NULL DEFAULT 0,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL
+)
+"#;
+
+/// SQL to create the Gmail filter tables.
+///
+/// `gmail_filter_mappings` links each synced screener rule to the Gmail
+/// filter created for it, with a fingerprint of what was sent so edits can
+/// be detected. `imported_gmail_filters` holds the account's other Gmail
+/// filters, kept read-only for display.
+pub const CREATE_GMAIL_FILTERS: &str = r#"
+CREATE TABLE IF NOT EXISTS gmail_filter_mappings (
+    account_id TEXT NOT NULL,
+    rule_id TEXT NOT NULL,
+    filter_id TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    synced_at TEXT NOT NULL,
+    PRIMARY KEY (account_id, rule_id)
+);
+
+CREATE TABLE IF NOT EXISTS imported_gmail_filters (
+    account_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    criteria TEXT NOT NULL,
+    action TEXT NOT NULL,
+    imported_at TEXT NOT NULL,
+    PRIMARY KEY (account_id, id)
+)
+"#;
+
 /// SQL to create the FTS5 virtual table for email search.
 pub const CREATE_EMAILS_FTS: &str = r#"
 CREATE VIRTUAL TABLE IF NOT EXISTS emails_fts USING fts5(
@@ -294,6 +446,43 @@ CREATE TRIGGER IF NOT EXISTS emails_au AFTER UPDATE ON emails BEGIN
 END
 "#;
 
+/// Columns added to existing tables after they were first created.
+///
+/// Each entry is `(table, column, definition)`. The create statements
+/// already include these columns; databases created before a column existed
+/// get it through `ALTER TABLE ... ADD COLUMN`.
+pub const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("screener_entries", "account_id", "TEXT"),
+    ("screener_rules", "account_id", "TEXT"),
+    ("screener_rules", "field", "TEXT NOT NULL DEFAULT 'sender'"),
+    ("screener_rules", "priority", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+/// SQL to assign screener entries from before entries were per-account.
+///
+/// Each entry goes to the account that received the sender's first email,
+/// or to the oldest account when that email is unknown. Rules from that
+/// time keep a null account and stay global.
+pub const BACKFILL_SCREENER_ACCOUNTS: &str = r#"
+UPDATE screener_entries
+SET account_id = COALESCE(
+    (SELECT account_id FROM emails WHERE emails.id = screener_entries.first_email_id),
+    (SELECT id FROM accounts ORDER BY created_at LIMIT 1)
+)
+WHERE account_id IS NULL
+"#;
+
+/// SQL to create screener indexes on the per-account columns.
+pub const CREATE_SCREENER_INDEXES: &str = r#"
+CREATE INDEX IF NOT EXISTS idx_screener_entries_sender ON screener_entries(account_id, sender_email);
+CREATE INDEX IF NOT EXISTS idx_screener_rules_account ON screener_rules(account_id)
+"#;
+
+/// Returns statements that run once [`ADDED_COLUMNS`] exist, in order.
+pub fn column_migrations() -> Vec<&'static str> {
+    vec![BACKFILL_SCREENER_ACCOUNTS, CREATE_SCREENER_INDEXES]
+}
+
 /// Returns all schema creation statements in order.
 pub fn all_migrations() -> Vec<&'static str> {
     vec![
@@ -314,10 +503,20 @@ pub fn all_migrations() -> Vec<&'static str> {
         CREATE_SYNC_STATE,
         CREATE_PENDING_CHANGES,
         CREATE_EMBEDDINGS,
+        CREATE_PASSAGE_EMBEDDINGS,
+        CREATE_EMBEDDING_JOBS,
+        CREATE_EMBEDDING_JOBS_INDEX,
         CREATE_TELEMETRY_EVENTS,
         CREATE_TELEMETRY_INDEX,
         CREATE_DAILY_STATS,
         CREATE_SETTINGS,
+        CREATE_AI_CACHE,
+        CREATE_AI_CACHE_INDEX,
+        CREATE_AI_CACHE_TRIGGERS,
+        CREATE_TRIAGE_MODELS,
+        CREATE_SPAM_FILTER,
+        CREATE_FILTER_RULES,
+        CREATE_GMAIL_FILTERS,
         CREATE_EMAILS_FTS,
         CREATE_EMAILS_FTS_TRIGGERS,
     ]
@@ -1,12 +1,17 @@
 //! Command palette view.
 //!
-//! Fuzzy-searchable command launcher overlay.
+//! Fuzzy-searchable command launcher overlay. In ask mode the palette
+//! takes a question about the inbox and streams back a cited answer.
 
+use futures::StreamExt;
 use gpui::{
-    div, prelude::FluentBuilder, px, Context, InteractiveElement, IntoElement, ParentElement,
-    Render, SharedString, Styled, Window,
+    div, prelude::FluentBuilder, px, ClickEvent, Context, EventEmitter, FontWeight,
+    InteractiveElement, IntoElement, ParentElement, Render, SharedString,
+    StatefulInteractiveElement, Styled, Window,
 };
 
+use crate::app::{AppEvent, AskState};
+use crate::services::{AskAnswer, AskSource};
 use crate::ui::theme::ThemeColors;
 
 /// Command palette view component.
@@ -17,6 +22,18 @@ pub struct CommandPalette {
     filtered_commands: Vec<usize>,
     selected_index: usize,
     visible: bool,
+    mode: PaletteMode,
+    ask: AskState,
+}
+
+/// What the palette input is used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PaletteMode {
+    /// Filter and run commands.
+    #[default]
+    Commands,
+    /// Ask a question about the inbox.
+    Ask,
 }
 
 /// A command in the palette.
@@ -63,6 +80,8 @@ impl CommandPalette {
             commands,
             selected_index: 0,
             visible: false,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
         }
     }
 
@@ -164,6 +183,18 @@ impl CommandPalette {
                 shortcut: None,
                 category: CommandCategory::Ai,
             },
+            Command {
+                id: "ai-ask".to_string(),
+                label: "AI: Ask My Inbox".to_string(),
+                shortcut: None,
+                category: CommandCategory::Ai,
+            },
+            Command {
+                id: "ai-clear-cache".to_string(),
+                label: "AI: Clear Cache".to_string(),
+                shortcut: None,
+                category: CommandCategory::Ai,
+            },
             Command {
                 id: "settings".to_string(),
                 label: "Open Settings".to_string(),
@@ -182,6 +213,7 @@ impl CommandPalette {
     /// Show the command palette.
     pub fn show(&mut self) {
         self.visible = true;
+        self.mode = PaletteMode::Commands;
         self.query.clear();
         self.filter_commands();
         self.selected_index = 0;
@@ -197,11 +229,76 @@ impl CommandPalette {
         self.visible
     }
 
-    /// Set the search query.
+    /// Get the current mode.
+    pub fn mode(&self) -> PaletteMode {
+        self.mode
+    }
+
+    /// Switch the input to asking a question about the inbox.
+    pub fn enter_ask_mode(&mut self) {
+        self.mode = PaletteMode::Ask;
+        self.query.clear();
+        self.ask.clear();
+    }
+
+    /// Set the search query, or the question in ask mode.
     pub fn set_query(&mut self, query: String) {
         self.query = query;
-        self.filter_commands();
-        self.selected_index = 0;
+        if self.mode == PaletteMode::Commands {
+            self.filter_commands();
+            self.selected_index = 0;
+        }
+    }
+
+    /// Get the question typed in ask mode, if any.
+    pub fn question(&self) -> Option<&str> {
+        let question = self.query.trim();
+        (self.mode == PaletteMode::Ask && !question.is_empty()).then_some(question)
+    }
+
+    /// Get the state of the current answer.
+    pub fn ask_state(&self) -> &AskState {
+        &self.ask
+    }
+
+    /// Displays a streamed answer to the current question.
+    pub fn stream_answer(&mut self, answer: AskAnswer, cx: &mut Context<Self>) {
+        let AskAnswer {
+            sources,
+            mut stream,
+        } = answer;
+        self.ask.begin(self.query.trim().to_string(), sources);
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            while let Some(chunk) = stream.next().await {
+                let failed = chunk.is_err();
+                let updated = this.update(cx, |palette, cx| {
+                    match &chunk {
+                        Ok(chunk) => palette.ask.push_chunk(chunk),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Ask answer stream failed");
+                            palette.ask.fail(e.to_string());
+                        }
+                    }
+                    cx.notify();
+                });
+                if failed || updated.is_err() {
+                    return;
+                }
+            }
+            let _ = this.update(cx, |palette, cx| {
+                palette.ask.finish();
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Shows why a question could not be answered.
+    pub fn show_ask_error(&mut self, error: String) {
+        self.ask.begin(self.query.trim().to_string(), Vec::new());
+        self.ask.fail(error);
     }
 
     /// Move selection up.
@@ -244,8 +341,12 @@ impl CommandPalette {
         let text_primary = self.colors.text_primary;
         let text_muted = self.colors.text_muted;
 
+        let (placeholder, prompt) = match self.mode {
+            PaletteMode::Commands => ("Type a command...", ">"),
+            PaletteMode::Ask => ("Ask a question about your email...", "?"),
+        };
         let display = if self.query.is_empty() {
-            "Type a command..."
+            placeholder
         } else {
             &self.query
         };
@@ -265,7 +366,11 @@ impl CommandPalette {
                     .flex()
                     .items_center()
                     .gap(px(8.0))
-                    .child(div().text_color(text_muted).child(SharedString::from(">")))
+                    .child(
+                        div()
+                            .text_color(text_muted)
+                            .child(SharedString::from(prompt)),
+                    )
                     .child(
                         div()
                             .flex_1()
@@ -316,6 +421,90 @@ impl CommandPalette {
             )
     }
 
+    fn render_answer(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = if self.ask.streaming {
+            "Searching your email..."
+        } else if self.ask.question.is_empty() {
+            "Press Enter to ask"
+        } else {
+            ""
+        };
+        let sources: Vec<AskSource> = self.ask.cited_sources().into_iter().cloned().collect();
+
+        div()
+            .px(px(16.0))
+            .py(px(12.0))
+            .flex()
+            .flex_col()
+            .gap(px(8.0))
+            .when(!status.is_empty(), |this| {
+                this.child(
+                    div()
+                        .text_xs()
+                        .text_color(self.colors.text_muted)
+                        .child(SharedString::from(status)),
+                )
+            })
+            .when(!self.ask.answer.is_empty(), |this| {
+                this.child(
+                    div()
+                        .text_sm()
+                        .text_color(self.colors.text_primary)
+                        .child(SharedString::from(self.ask.answer.clone())),
+                )
+            })
+            .when_some(self.ask.error.clone(), |this, error| {
+                this.child(
+                    div()
+                        .text_sm()
+                        .text_color(self.colors.text_muted)
+                        .child(SharedString::from(error)),
+                )
+            })
+            .when(!sources.is_empty(), |this| {
+                this.child(
+                    div()
+                        .text_xs()
+                        .font_weight(FontWeight::MEDIUM)
+                        .text_color(self.colors.text_muted)
+                        .child(SharedString::from("Sources")),
+                )
+                .children(sources.iter().map(|source| self.render_source(source, cx)))
+            })
+    }
+
+    fn render_source(&self, source: &AskSource, cx: &mut Context<Self>) -> impl IntoElement {
+        let hover_bg = self.colors.surface_elevated;
+        let thread_id = source.thread_id.clone();
+        let email_id = source.email_id.clone();
+        let open_handler = cx.listener(move |this, _: &ClickEvent, _, cx| {
+            cx.emit(AppEvent::OpenEmail {
+                thread_id: thread_id.clone(),
+                email_id: email_id.clone(),
+            });
+            this.hide();
+            cx.notify();
+        });
+        let label = format!(
+            "[{}] {} - {}",
+            source.number,
+            source.subject.as_deref().unwrap_or("(no subject)"),
+            source.from
+        );
+
+        div()
+            .id(SharedString::from(format!("ask-source-{}", source.number)))
+            .px(px(8.0))
+            .py(px(4.0))
+            .rounded(px(4.0))
+            .text_sm()
+            .text_color(self.colors.accent)
+            .cursor_pointer()
+            .hover(move |style| style.bg(hover_bg))
+            .on_click(open_handler)
+            .child(SharedString::from(label))
+    }
+
     fn render_empty_state(&self) -> impl IntoElement {
         div()
             .px(px(16.0))
@@ -328,7 +517,7 @@ impl CommandPalette {
 }
 
 impl Render for CommandPalette {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         if !self.visible {
             return div().id("command-palette-hidden");
         }
@@ -361,23 +550,34 @@ impl Render for CommandPalette {
                         div()
                             .max_h(px(320.0))
                             .overflow_y_hidden()
-                            .when(self.filtered_commands.is_empty(), |this| {
-                                this.child(self.render_empty_state())
+                            .when(self.mode == PaletteMode::Ask, |this| {
+                                this.child(self.render_answer(cx))
                             })
-                            .when(!self.filtered_commands.is_empty(), |this| {
-                                this.children(self.filtered_commands.iter().enumerate().map(
-                                    |(display_idx, &cmd_idx)| {
-                                        let cmd = &self.commands[cmd_idx];
-                                        let is_selected = display_idx == self.selected_index;
-                                        self.render_command(cmd, is_selected)
-                                    },
-                                ))
-                            }),
+                            .when(
+                                self.mode == PaletteMode::Commands
+                                    && self.filtered_commands.is_empty(),
+                                |this| this.child(self.render_empty_state()),
+                            )
+                            .when(
+                                self.mode == PaletteMode::Commands
+                                    && !self.filtered_commands.is_empty(),
+                                |this| {
+                                    this.children(self.filtered_commands.iter().enumerate().map(
+                                        |(display_idx, &cmd_idx)| {
+                                            let cmd = &self.commands[cmd_idx];
+                                            let is_selected = display_idx == self.selected_index;
+                                            self.render_command(cmd, is_selected)
+                                        },
+                                    ))
+                                },
+                            ),
                     ),
             )
     }
 }
 
+impl EventEmitter<AppEvent> for CommandPalette {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -391,6 +591,8 @@ mod tests {
             filtered_commands: Vec::new(),
             selected_index: 0,
             visible: false,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
         };
 
         assert!(!palette.is_visible());
@@ -424,6 +626,8 @@ mod tests {
             filtered_commands: vec![0, 1],
             selected_index: 0,
             visible: true,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
         };
 
         assert_eq!(palette.filtered_commands.len(), 2);
@@ -461,6 +665,8 @@ mod tests {
             filtered_commands: vec![0, 1, 2],
             selected_index: 0,
             visible: true,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
         };
 
         assert_eq!(palette.selected_index, 0);
@@ -492,9 +698,43 @@ mod tests {
             filtered_commands: vec![0],
             selected_index: 0,
             visible: true,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
         };
 
         let cmd = palette.selected_command().unwrap();
         assert_eq!(cmd.id, "test");
     }
+
+    #[test]
+    fn ask_mode_takes_a_question() {
+        let mut palette = CommandPalette {
+            colors: ThemeColors::dark(),
+            query: String::new(),
+            commands: CommandPalette::default_commands(),
+            filtered_commands: Vec::new(),
+            selected_index: 0,
+            visible: true,
+            mode: PaletteMode::Commands,
+            ask: AskState::default(),
+        };
+
+        palette.set_query("ask my".to_string());
+        assert_eq!(palette.selected_command().unwrap().id, "ai-ask");
+        assert!(palette.question().is_none());
+
+        palette.enter_ask_mode();
+        assert_eq!(palette.mode(), PaletteMode::Ask);
+        assert!(palette.question().is_none());
+
+        palette.set_query(" who owns the Q3 budget? ".to_string());
+        assert_eq!(palette.question(), Some("who owns the Q3 budget?"));
+
+        palette.show_ask_error("No emails found".to_string());
+        assert_eq!(palette.ask_state().question, "who owns the Q3 budget?");
+        assert!(!palette.ask_state().streaming);
+
+        palette.show();
+        assert_eq!(palette.mode(), PaletteMode::Commands);
+    }
 }
@@ -2,12 +2,14 @@
 //!
 //! Email composition window for new messages, replies, and forwards.
 
+use futures::StreamExt;
 use gpui::{
     div, prelude::FluentBuilder, px, Context, FontWeight, InteractiveElement, IntoElement,
     ParentElement, Render, SharedString, Styled, Window,
 };
 
 use crate::app::ComposerMode;
+use crate::providers::ai::{CompletionStream, StreamChunk};
 use crate::ui::theme::ThemeColors;
 
 /// Composer view component.
@@ -23,6 +25,7 @@ pub struct Composer {
     is_dirty: bool,
     is_sending: bool,
     ai_suggestion: Option<String>,
+    ai_suggestion_streaming: bool,
     show_cc: bool,
     show_bcc: bool,
 }
@@ -50,6 +53,7 @@ impl Composer {
             is_dirty: false,
             is_sending: false,
             ai_suggestion: None,
+            ai_suggestion_streaming: false,
             show_cc: false,
             show_bcc: false,
         }
@@ -75,6 +79,7 @@ impl Composer {
             is_dirty: false,
             is_sending: false,
             ai_suggestion: None,
+            ai_suggestion_streaming: false,
             show_cc: false,
             show_bcc: false,
         }
@@ -85,8 +90,64 @@ impl Composer {
         self.ai_suggestion = Some(suggestion);
     }
 
+    /// Start a new AI suggestion, replacing any previous one.
+    pub fn begin_ai_suggestion(&mut self) {
+        self.ai_suggestion = Some(String::new());
+        self.ai_suggestion_streaming = true;
+    }
+
+    /// Append a streamed chunk to the AI suggestion.
+    pub fn push_ai_suggestion_chunk(&mut self, chunk: &StreamChunk) {
+        if let Some(suggestion) = &mut self.ai_suggestion {
+            suggestion.push_str(&chunk.text);
+        }
+        if chunk.finish_reason.is_some() {
+            self.ai_suggestion_streaming = false;
+        }
+    }
+
+    /// Mark the AI suggestion as complete.
+    pub fn finish_ai_suggestion(&mut self) {
+        self.ai_suggestion_streaming = false;
+    }
+
+    /// Displays a streamed AI draft, rendering tokens as they arrive.
+    pub fn stream_ai_suggestion(&mut self, mut stream: CompletionStream, cx: &mut Context<Self>) {
+        self.begin_ai_suggestion();
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            while let Some(chunk) = stream.next().await {
+                let failed = chunk.is_err();
+                let updated = this.update(cx, |composer, cx| {
+                    match &chunk {
+                        Ok(chunk) => composer.push_ai_suggestion_chunk(chunk),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "AI draft stream failed");
+                            composer.finish_ai_suggestion();
+                        }
+                    }
+                    cx.notify();
+                });
+                if failed || updated.is_err() {
+                    return;
+                }
+            }
+            let _ = this.update(cx, |composer, cx| {
+                composer.finish_ai_suggestion();
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
     /// Accept AI suggestion.
+    ///
+    /// Does nothing while the suggestion is still streaming.
     pub fn accept_ai_suggestion(&mut self) {
+        if self.ai_suggestion_streaming {
+            return;
+        }
         if let Some(suggestion) = self.ai_suggestion.take() {
             self.body = suggestion;
             self.is_dirty = true;
@@ -96,6 +157,7 @@ impl Composer {
     /// Reject AI suggestion.
     pub fn reject_ai_suggestion(&mut self) {
         self.ai_suggestion = None;
+        self.ai_suggestion_streaming = false;
     }
 
     /// Add attachment.
@@ -253,6 +315,7 @@ impl Composer {
         let text_primary = self.colors.text_primary;
         let text_muted = self.colors.text_muted;
         let accent = self.colors.accent;
+        let streaming = self.ai_suggestion_streaming;
 
         div()
             .m(px(16.0))
@@ -262,13 +325,26 @@ impl Composer {
             .border_1()
             .border_color(border)
             .child(
-                div().flex().items_center().gap(px(8.0)).mb(px(8.0)).child(
-                    div()
-                        .text_sm()
-                        .text_color(accent)
-                        .font_weight(FontWeight::MEDIUM)
-                        .child(SharedString::from("AI Suggestion")),
-                ),
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(8.0))
+                    .mb(px(8.0))
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(accent)
+                            .font_weight(FontWeight::MEDIUM)
+                            .child(SharedString::from("AI Suggestion")),
+                    )
+                    .when(streaming, |this| {
+                        this.child(
+                            div()
+                                .text_xs()
+                                .text_color(text_muted)
+                                .child(SharedString::from("Writing...")),
+                        )
+                    }),
             )
             .child(
                 div()
@@ -280,17 +356,19 @@ impl Composer {
                 div()
                     .flex()
                     .gap(px(8.0))
-                    .child(
-                        div()
-                            .px(px(12.0))
-                            .py(px(4.0))
-                            .rounded(px(4.0))
-                            .bg(accent)
-                            .text_sm()
-                            .text_color(text_primary)
-                            .cursor_pointer()
-                            .child(SharedString::from("Accept")),
-                    )
+                    .when(!streaming, |this| {
+                        this.child(
+                            div()
+                                .px(px(12.0))
+                                .py(px(4.0))
+                                .rounded(px(4.0))
+                                .bg(accent)
+                                .text_sm()
+                                .text_color(text_primary)
+                                .cursor_pointer()
+                                .child(SharedString::from("Accept")),
+                        )
+                    })
                     .child(
                         div()
                             .px(px(12.0))
@@ -400,6 +478,7 @@ mod tests {
             is_dirty: false,
             is_sending: false,
             ai_suggestion: None,
+            ai_suggestion_streaming: false,
             show_cc: false,
             show_bcc: false,
         };
@@ -427,6 +506,7 @@ mod tests {
             is_dirty: false,
             is_sending: false,
             ai_suggestion: None,
+            ai_suggestion_streaming: false,
             show_cc: false,
             show_bcc: false,
         };
@@ -454,6 +534,7 @@ mod tests {
             is_dirty: false,
             is_sending: false,
             ai_suggestion: None,
+            ai_suggestion_streaming: false,
             show_cc: false,
             show_bcc: false,
         };
@@ -470,4 +551,41 @@ mod tests {
         composer.remove_attachment(0);
         assert!(composer.attachments.is_empty());
     }
+
+    #[test]
+    fn streamed_ai_suggestion() {
+        let mut composer = Composer {
+            colors: ThemeColors::dark(),
+            mode: ComposerMode::Reply,
+            to: vec!["test@example.com".to_string()],
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            subject: "Re: Test".to_string(),
+            body: String::new(),
+            attachments: Vec::new(),
+            is_dirty: false,
+            is_sending: false,
+            ai_suggestion: None,
+            ai_suggestion_streaming: false,
+            show_cc: false,
+            show_bcc: false,
+        };
+
+        composer.begin_ai_suggestion();
+        composer.push_ai_suggestion_chunk(&StreamChunk {
+            text: "Thanks, ".to_string(),
+            finish_reason: None,
+        });
+        composer.accept_ai_suggestion();
+        assert!(composer.body.is_empty());
+
+        composer.push_ai_suggestion_chunk(&StreamChunk {
+            text: "will do.".to_string(),
+            finish_reason: Some(crate::providers::ai::FinishReason::Stop),
+        });
+        assert!(!composer.ai_suggestion_streaming);
+
+        composer.accept_ai_suggestion();
+        assert_eq!(composer.body, "Thanks, will do.");
+    }
 }
@@ -10,6 +10,7 @@ use gpui::{
     MouseDownEvent, MouseMoveEvent, MouseUpEvent, ParentElement, Render, SharedString,
     StatefulInteractiveElement, Styled, Window,
 };
+use tokio::sync::broadcast;
 
 use crate::ui::components::{KeyInputResult, TextBuffer};
 
@@ -47,7 +48,7 @@ use crate::app::{
     ScreenerReject, Search, Snooze, Star, Trash, Undo, ViewType,
 };
 use crate::domain::{EmailId, LabelId, ScreenerAction, SenderType, ThreadId};
-use crate::services::SnoozeDuration;
+use crate::services::{IndexingProgress, SnoozeDuration};
 use crate::ui::theme::Theme;
 use crate::ui::views::{ScreenerEntry, StatsTimeRange};
 
@@ -319,6 +320,7 @@ pub struct MainWindow {
     is_offline: bool,
     ai_status: Option<String>,
     last_sync: Option<String>,
+    indexing_progress: Option<IndexingProgress>,
 
     // Undo system
     undo_stack: Vec<UndoableAction>,
@@ -488,6 +490,7 @@ impl MainWindow {
             is_offline: false,
             ai_status: None,
             last_sync: Some("2 minutes ago".to_string()),
+            indexing_progress: None,
             undo_stack: Vec::new(),
             toast: None,
             screener_entries: Vec::new(),
@@ -527,6 +530,29 @@ impl MainWindow {
         this
     }
 
+    /// Shows background indexing progress in the status bar.
+    pub fn watch_indexing(
+        &mut self,
+        mut progress: broadcast::Receiver<IndexingProgress>,
+        cx: &mut Context<Self>,
+    ) {
+        cx.spawn(async move |this, cx| loop {
+            let update = match progress.recv().await {
+                Ok(update) => update,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let updated = this.update(cx, |window, cx| {
+                window.indexing_progress = Some(update);
+                cx.notify();
+            });
+            if updated.is_err() {
+                return;
+            }
+        })
+        .detach();
+    }
+
     fn load_sample_data(&mut self) {
         self.threads = vec![
             ThreadListItem {
@@ -624,6 +650,7 @@ impl MainWindow {
         self.screener_entries = vec![
             ScreenerEntry::new("screener-1", "newsletter@techweekly.io")
                 .with_name("Tech Weekly")
+                .with_account("user@gmail.com")
                 .with_email_preview(
                     Some("This Week in Tech: AI Revolution".to_string()),
                     "The latest news in artificial intelligence and machine learning...",
@@ -635,6 +662,7 @@ impl MainWindow {
                 ),
             ScreenerEntry::new("screener-2", "sarah.recruiter@linkedin.com")
                 .with_name("Sarah Johnson")
+                .with_account("user@company.com")
                 .with_email_preview(
                     Some("Exciting opportunity at TechCorp".to_string()),
                     "Hi! I came across your profile and wanted to reach out about...",
@@ -646,6 +674,7 @@ impl MainWindow {
                 ),
             ScreenerEntry::new("screener-3", "notifications@github.com")
                 .with_name("GitHub")
+                .with_account("user@company.com")
                 .with_email_preview(
                     Some("New issue assigned to you".to_string()),
                     "You have been assigned to issue #1234 in repo/project...",
@@ -657,6 +686,7 @@ impl MainWindow {
                 ),
             ScreenerEntry::new("screener-4", "promo@randomstore.xyz")
                 .with_name("Random Store")
+                .with_account("user@gmail.com")
                 .with_email_preview(
                     Some("EXCLUSIVE DEAL: 90% OFF TODAY ONLY!!!".to_string()),
                     "Don't miss this incredible limited-time offer! Click now to save...",
@@ -1581,6 +1611,13 @@ impl MainWindow {
                                 None,
                                 cx,
                             ))
+                            .child(self.render_sidebar_item(
+                                "junk",
+                                "Junk",
+                                ViewType::Junk,
+                                None,
+                                cx,
+                            ))
                         },
                     )
                     // Smart Views section
@@ -1630,6 +1667,13 @@ impl MainWindow {
                                 Some(3),
                                 cx,
                             ))
+                            .child(self.render_sidebar_item(
+                                "screened-out",
+                                "Screened Out",
+                                ViewType::ScreenedOut,
+                                None,
+                                cx,
+                            ))
                         },
                     )
                     // Labels section
@@ -2448,7 +2492,7 @@ impl MainWindow {
             .unwrap_or('?')
             .to_uppercase()
             .to_string();
-        let email = entry.email.clone();
+        let email = entry.sender_line();
         let subject = entry.first_email_subject.clone();
         let badge_text = entry.sender_type_badge();
         let entry_id = entry.id.clone();
@@ -3185,6 +3229,17 @@ impl MainWindow {
             .items_center()
             .gap(px(12.0))
             .child(self.render_sync_status())
+            .when_some(
+                self.indexing_progress.as_ref().and_then(indexing_status),
+                |this, status| {
+                    this.child(
+                        div()
+                            .text_color(colors.text_muted)
+                            .text_xs()
+                            .child(SharedString::from(status)),
+                    )
+                },
+            )
             .when(self.is_offline, |this| {
                 this.child(
                     div()
@@ -3671,7 +3726,9 @@ impl MainWindow {
             ViewType::Archive => "Archive",
             ViewType::Trash => "Trash",
             ViewType::Snoozed => "Snoozed",
+            ViewType::Junk => "Junk",
             ViewType::Screener => "New Senders",
+            ViewType::ScreenedOut => "Screened Out",
             ViewType::Settings => "Settings",
             ViewType::Stats => "Statistics",
             ViewType::Label(_) => "Label",
@@ -6464,3 +6521,46 @@ impl Render for MainWindow {
             })
     }
 }
+
+/// Status bar text for background indexing, or `None` when nothing is queued.
+fn indexing_status(progress: &IndexingProgress) -> Option<String> {
+    if progress.is_idle() {
+        return None;
+    }
+
+    let queued = progress.pending + progress.in_progress;
+    let done = progress.completed + progress.failed;
+    let mut status = format!("Indexing: {} queued, {} done", queued, done);
+    if progress.throttled {
+        status.push_str(" (throttled)");
+    }
+    Some(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexing_status_shows_queue_and_throttling() {
+        assert_eq!(indexing_status(&IndexingProgress::default()), None);
+
+        let mut progress = IndexingProgress {
+            pending: 30,
+            in_progress: 2,
+            completed: 7,
+            failed: 1,
+            throttled: false,
+        };
+        assert_eq!(
+            indexing_status(&progress).as_deref(),
+            Some("Indexing: 32 queued, 8 done")
+        );
+
+        progress.throttled = true;
+        assert_eq!(
+            indexing_status(&progress).as_deref(),
+            Some("Indexing: 32 queued, 8 done (throttled)")
+        );
+    }
+}
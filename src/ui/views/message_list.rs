@@ -154,8 +154,10 @@ impl MessageList {
             ViewType::Archive => "Archive",
             ViewType::Trash => "Trash",
             ViewType::Snoozed => "Snoozed",
+            ViewType::Junk => "Junk",
             ViewType::Label(_) => "Label",
             ViewType::Screener => "New Senders",
+            ViewType::ScreenedOut => "Screened Out",
             ViewType::Search(_) => "Search Results",
             ViewType::Settings => "Settings",
             ViewType::Stats => "Statistics",
@@ -4,12 +4,14 @@
 
 use std::collections::HashSet;
 
+use futures::StreamExt;
 use gpui::{
     div, prelude::FluentBuilder, px, ClickEvent, Context, FontWeight, InteractiveElement,
     IntoElement, ParentElement, Render, SharedString, StatefulInteractiveElement, Styled, Window,
 };
 
 use crate::domain::{EmailId, ThreadId};
+use crate::providers::ai::{CompletionStream, StreamChunk};
 use crate::ui::theme::ThemeColors;
 
 /// Reading pane view component.
@@ -19,6 +21,8 @@ pub struct ReadingPane {
     expanded_messages: HashSet<EmailId>,
     inline_composer_visible: bool,
     scroll_offset: f32,
+    ai_summary: Option<String>,
+    ai_summary_streaming: bool,
 }
 
 /// Detailed thread data for display.
@@ -62,6 +66,8 @@ impl ReadingPane {
             expanded_messages: HashSet::new(),
             inline_composer_visible: false,
             scroll_offset: 0.0,
+            ai_summary: None,
+            ai_summary_streaming: false,
         }
     }
 
@@ -70,6 +76,8 @@ impl ReadingPane {
         self.expanded_messages.clear();
         self.inline_composer_visible = false;
         self.scroll_offset = 0.0;
+        self.ai_summary = None;
+        self.ai_summary_streaming = false;
 
         if let Some(ref t) = thread {
             // Expand the last message by default
@@ -114,6 +122,57 @@ impl ReadingPane {
         self.inline_composer_visible = false;
     }
 
+    /// Start a new AI summary, replacing any previous one.
+    pub fn begin_summary(&mut self) {
+        self.ai_summary = Some(String::new());
+        self.ai_summary_streaming = true;
+    }
+
+    /// Append a streamed chunk to the AI summary.
+    pub fn push_summary_chunk(&mut self, chunk: &StreamChunk) {
+        if let Some(summary) = &mut self.ai_summary {
+            summary.push_str(&chunk.text);
+        }
+        if chunk.finish_reason.is_some() {
+            self.ai_summary_streaming = false;
+        }
+    }
+
+    /// Mark the AI summary as complete.
+    pub fn finish_summary(&mut self) {
+        self.ai_summary_streaming = false;
+    }
+
+    /// Displays a streamed AI summary, rendering tokens as they arrive.
+    pub fn stream_summary(&mut self, mut stream: CompletionStream, cx: &mut Context<Self>) {
+        self.begin_summary();
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            while let Some(chunk) = stream.next().await {
+                let failed = chunk.is_err();
+                let updated = this.update(cx, |pane, cx| {
+                    match &chunk {
+                        Ok(chunk) => pane.push_summary_chunk(chunk),
+                        Err(e) => {
+                            tracing::warn!(error = %e, "AI summary stream failed");
+                            pane.finish_summary();
+                        }
+                    }
+                    cx.notify();
+                });
+                if failed || updated.is_err() {
+                    return;
+                }
+            }
+            let _ = this.update(cx, |pane, cx| {
+                pane.finish_summary();
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
     fn render_empty_state(&self) -> impl IntoElement {
         div().flex_1().flex().items_center().justify_center().child(
             div()
@@ -512,6 +571,49 @@ impl ReadingPane {
             )
     }
 
+    fn render_ai_summary(&self, summary: &str) -> impl IntoElement {
+        let status = if self.ai_summary_streaming {
+            "Summarizing..."
+        } else {
+            ""
+        };
+
+        div()
+            .mx(px(24.0))
+            .mt(px(16.0))
+            .p(px(12.0))
+            .rounded(px(8.0))
+            .bg(self.colors.surface_elevated)
+            .border_1()
+            .border_color(self.colors.border)
+            .child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap(px(8.0))
+                    .mb(px(8.0))
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_weight(FontWeight::MEDIUM)
+                            .text_color(self.colors.accent)
+                            .child(SharedString::from("AI Summary")),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(self.colors.text_muted)
+                            .child(SharedString::from(status)),
+                    ),
+            )
+            .child(
+                div()
+                    .text_sm()
+                    .text_color(self.colors.text_primary)
+                    .child(SharedString::from(summary.to_string())),
+            )
+    }
+
     fn render_inline_composer(&self) -> impl IntoElement {
         div()
             .px(px(24.0))
@@ -573,6 +675,7 @@ impl Render for ReadingPane {
 
             let header = self.render_thread_header(thread, cx);
             let show_composer = self.inline_composer_visible;
+            let summary = self.ai_summary.clone();
 
             div()
                 .id("reading-pane")
@@ -582,6 +685,9 @@ impl Render for ReadingPane {
                 .flex_col()
                 .bg(bg)
                 .child(header)
+                .when_some(summary, |this, summary| {
+                    this.child(self.render_ai_summary(&summary))
+                })
                 .child(div().flex_1().overflow_y_hidden().children(message_items))
                 .when(show_composer, |this| {
                     this.child(self.render_inline_composer())
@@ -641,6 +747,8 @@ mod tests {
             expanded_messages: HashSet::new(),
             inline_composer_visible: false,
             scroll_offset: 0.0,
+            ai_summary: None,
+            ai_summary_streaming: false,
         };
 
         let msg_id = EmailId::from("msg-1");
@@ -20,6 +20,8 @@ pub struct ScreenerEntry {
     pub email: String,
     /// Sender name if known.
     pub name: Option<String>,
+    /// Account that received the email, shown when several are screened.
+    pub account: Option<String>,
     /// Subject of first email.
     pub first_email_subject: Option<String>,
     /// Preview of first email.
@@ -43,6 +45,7 @@ impl ScreenerEntry {
             id: id.into(),
             email: email.into(),
             name: None,
+            account: None,
             first_email_subject: None,
             first_email_preview: String::new(),
             received_at: Utc::now(),
@@ -59,6 +62,12 @@ impl ScreenerEntry {
         self
     }
 
+    /// Sets the receiving account.
+    pub fn with_account(mut self, account: impl Into<String>) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
     /// Sets the first email details.
     pub fn with_email_preview(
         mut self,
@@ -88,6 +97,14 @@ impl ScreenerEntry {
         self.name.as_deref().unwrap_or(&self.email)
     }
 
+    /// Returns the sender email, followed by the receiving account if set.
+    pub fn sender_line(&self) -> String {
+        match &self.account {
+            Some(account) => format!("{} · to {}", self.email, account),
+            None => self.email.clone(),
+        }
+    }
+
     /// Returns the sender type badge text.
     pub fn sender_type_badge(&self) -> Option<&'static str> {
         self.ai_sender_type.map(|t| match t {
@@ -123,6 +140,8 @@ pub struct ScreenerQueue {
     ai_loading: bool,
     /// Filter by sender type.
     filter: Option<SenderType>,
+    /// Filter by receiving account.
+    account_filter: Option<String>,
 }
 
 impl ScreenerQueue {
@@ -134,6 +153,7 @@ impl ScreenerQueue {
             selected_index: None,
             ai_loading: false,
             filter: None,
+            account_filter: None,
         }
     }
 
@@ -244,22 +264,27 @@ impl ScreenerQueue {
         self.filter = filter;
     }
 
+    /// Sets the account filter; `None` shows every account's senders.
+    pub fn set_account_filter(&mut self, account: Option<String>) {
+        self.account_filter = account;
+    }
+
     /// Returns filtered entries.
     fn filtered_entries(&self) -> Vec<&ScreenerEntry> {
-        match self.filter {
-            Some(filter_type) => self
-                .entries
-                .iter()
-                .filter(|e| e.ai_sender_type == Some(filter_type))
-                .collect(),
-            None => self.entries.iter().collect(),
-        }
+        self.entries
+            .iter()
+            .filter(|e| self.filter.is_none() || e.ai_sender_type == self.filter)
+            .filter(|e| {
+                self.account_filter.is_none() || e.account.as_ref() == self.account_filter.as_ref()
+            })
+            .collect()
     }
 
     fn render_entry(&self, entry: &ScreenerEntry, index: usize) -> impl IntoElement {
         let is_selected = self.selected_index == Some(index);
         let display_name = entry.display_name().to_string();
         let email = entry.email.clone();
+        let sender_line = entry.sender_line();
         let first_char = email
             .chars()
             .next()
@@ -323,7 +348,7 @@ impl ScreenerQueue {
                                     .text_xs()
                                     .text_color(rgba(0x71717AFF))
                                     .truncate()
-                                    .child(email),
+                                    .child(sender_line),
                             ),
                     )
                     // Type badge
@@ -745,6 +770,15 @@ mod tests {
         assert_eq!(entry.display_name(), "test@example.com");
     }
 
+    #[test]
+    fn screener_entry_sender_line_shows_account() {
+        let entry = ScreenerEntry::new("entry-1", "test@example.com");
+        assert_eq!(entry.sender_line(), "test@example.com");
+
+        let entry = entry.with_account("me@work.example");
+        assert_eq!(entry.sender_line(), "test@example.com · to me@work.example");
+    }
+
     #[test]
     fn screener_queue_selection() {
         // Selection tests require ViewContext
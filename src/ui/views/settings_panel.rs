@@ -12,6 +12,8 @@ use gpui::{
     SharedString, Styled, Window,
 };
 
+use crate::embedding::DownloadStatus;
+
 /// Settings tab categories.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SettingsTab {
@@ -160,6 +162,8 @@ pub struct SettingsPanel {
     selects: Vec<(SettingsTab, Vec<SelectSetting>)>,
     /// Whether there are unsaved changes.
     has_changes: bool,
+    /// State of the local embedding model used for semantic search.
+    embedding_status: DownloadStatus,
 }
 
 impl SettingsPanel {
@@ -171,6 +175,7 @@ impl SettingsPanel {
             toggles: Self::default_toggles(),
             selects: Self::default_selects(),
             has_changes: false,
+            embedding_status: DownloadStatus::NotDownloaded,
         }
     }
 
@@ -372,6 +377,16 @@ impl SettingsPanel {
         }
     }
 
+    /// Updates the displayed state of the embedding model.
+    pub fn set_embedding_status(&mut self, status: DownloadStatus) {
+        self.embedding_status = status;
+    }
+
+    /// Returns the displayed state of the embedding model.
+    pub fn embedding_status(&self) -> &DownloadStatus {
+        &self.embedding_status
+    }
+
     /// Returns toggle settings for the current tab.
     fn current_toggles(&self) -> &[ToggleSetting] {
         self.toggles
@@ -513,6 +528,35 @@ impl SettingsPanel {
             )
     }
 
+    fn render_model_status(&self, _cx: &mut Context<Self>) -> impl IntoElement {
+        let status = &self.embedding_status;
+        let color = match status {
+            DownloadStatus::Ready => rgba(0x22C55EFF),
+            DownloadStatus::Failed(_) => rgba(0xEF4444FF),
+            _ => rgba(0xA1A1AAFF),
+        };
+
+        div()
+            .id("embedding-model-status")
+            .py(px(12.0))
+            .flex()
+            .flex_col()
+            .gap(px(4.0))
+            .child(
+                div()
+                    .text_sm()
+                    .font_weight(gpui::FontWeight::MEDIUM)
+                    .text_color(rgba(0xE4E4E7FF))
+                    .child("Semantic Search Model"),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(color)
+                    .child(SharedString::from(status.label())),
+            )
+    }
+
     fn render_keybindings_content(&self, _cx: &mut Context<Self>) -> impl IntoElement {
         let bindings = vec![
             ("Compose", "C"),
@@ -578,6 +622,9 @@ impl SettingsPanel {
                     .flex_col()
                     .children(toggles.iter().map(|t| self.render_toggle(t, cx)))
                     .children(selects.iter().map(|s| self.render_select(s, cx)))
+                    .when(self.current_tab == SettingsTab::Ai, |d| {
+                        d.child(self.render_model_status(cx))
+                    })
             }
         }
     }
@@ -219,6 +219,7 @@ impl Render for Sidebar {
         let archive =
             self.render_mailbox_item("archive", "Archive", "A", ViewType::Archive, None, cx);
         let trash = self.render_mailbox_item("trash", "Trash", "T", ViewType::Trash, None, cx);
+        let junk = self.render_mailbox_item("junk", "Junk", "J", ViewType::Junk, None, cx);
         let screener = self.render_mailbox_item(
             "screener",
             "New Senders",
@@ -227,6 +228,14 @@ impl Render for Sidebar {
             Some(5),
             cx,
         );
+        let screened_out = self.render_mailbox_item(
+            "screened-out",
+            "Screened Out",
+            "X",
+            ViewType::ScreenedOut,
+            None,
+            cx,
+        );
         let settings =
             self.render_mailbox_item("settings", "Settings", "G", ViewType::Settings, None, cx);
         let stats = self.render_mailbox_item("stats", "Statistics", "#", ViewType::Stats, None, cx);
@@ -261,10 +270,11 @@ impl Render for Sidebar {
                             .child(sent)
                             .child(drafts)
                             .child(archive)
-                            .child(trash),
+                            .child(trash)
+                            .child(junk),
                     )
                     .child(self.render_section_header("SCREENER"))
-                    .child(div().child(screener))
+                    .child(div().child(screener).child(screened_out))
                     .when(has_labels, |this| {
                         this.child(self.render_section_header("LABELS"))
                             .children(label_items)
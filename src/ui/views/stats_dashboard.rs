@@ -130,6 +130,8 @@ pub struct AiStats {
     pub tokens_used: u64,
     /// Estimated cost in USD.
     pub estimated_cost_usd: f32,
+    /// Results served from the AI cache.
+    pub cache_hits: u32,
 }
 
 impl AiStats {
@@ -442,6 +444,8 @@ impl StatsDashboard {
             .ai_stats
             .acceptance_rate()
             .map(|r| format!("{:.0}% accepted", r));
+        let cached = (self.ai_stats.cache_hits > 0)
+            .then(|| format!("{} from cache", self.ai_stats.cache_hits));
 
         div()
             .flex()
@@ -461,7 +465,7 @@ impl StatsDashboard {
                     .child(self.render_stat_card(
                         "Summaries",
                         &format!("{}", self.ai_stats.summaries_generated),
-                        None,
+                        cached.as_deref(),
                         false,
                     ))
                     .child(self.render_stat_card(